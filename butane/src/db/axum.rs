@@ -0,0 +1,206 @@
+//! Axum support for Butane.
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use axum::extract::{FromRef, FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use deadpool::managed::{Object, Pool, PoolError};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use super::ConnectionManager;
+use crate::db::ConnectionAsync;
+use crate::Error;
+
+/// Extractor of a connection checked out of the deadpool pool in the
+/// state of an axum router, which is returned to the pool once the
+/// handler is done with it. The state must be a
+/// `Pool<ConnectionManager>`, or have one which it gives with
+/// [`FromRef`]. Requires the `axum` feature.
+///
+/// ```ignore
+/// async fn list_posts(conn: PooledConnection) -> Json<Vec<String>> {
+///     let posts = Post::query().load(&*conn).await.unwrap();
+///     Json(posts.into_iter().map(|post| post.title).collect())
+/// }
+///
+/// let app = Router::new().route("/posts", get(list_posts)).with_state(pool);
+/// ```
+#[derive(Debug)]
+pub struct PooledConnection(Object<ConnectionManager>);
+
+impl PooledConnection {
+    /// The connection as checked out of the pool.
+    pub fn into_inner(self) -> Object<ConnectionManager> {
+        self.0
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = ConnectionAsync;
+    fn deref(&self) -> &ConnectionAsync {
+        &self.0
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut ConnectionAsync {
+        &mut self.0
+    }
+}
+
+impl<S> FromRequestParts<S> for PooledConnection
+where
+    Pool<ConnectionManager>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = DbRejection;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, DbRejection> {
+        let pool = Pool::<ConnectionManager>::from_ref(state);
+        Ok(PooledConnection(checkout(&pool).await?))
+    }
+}
+
+/// Middleware running each request in a transaction of its own, on a
+/// connection checked out of `pool`. The transaction is committed if
+/// the response is not an error, that is if its status is not 4xx or
+/// 5xx, and otherwise rolled back. Handlers use the transaction by
+/// extracting a [`RequestTransaction`]. Requires the `axum` feature.
+///
+/// ```ignore
+/// let app = Router::new()
+///     .route("/posts", post(create_post))
+///     .layer(axum::middleware::from_fn_with_state(
+///         pool.clone(),
+///         butane::db::request_transaction,
+///     ))
+///     .with_state(pool);
+/// ```
+///
+/// A request whose handler panics leaves its transaction open, so its
+/// connection is closed rather than returned to the pool.
+pub async fn request_transaction(
+    State(pool): State<Pool<ConnectionManager>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let conn = match checkout(&pool).await {
+        Ok(conn) => conn,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if let Err(e) = conn.execute("BEGIN;").await {
+        return DbRejection(e).into_response();
+    }
+    let tx = Arc::new(Mutex::new(TransactionConnection {
+        conn: Some(conn),
+        finished: false,
+    }));
+    request.extensions_mut().insert(tx.clone());
+    let response = next.run(request).await;
+
+    // Handlers are done with the connection once they have responded.
+    let mut tx = tx.lock().await;
+    let status = response.status();
+    let end = if status.is_client_error() || status.is_server_error() {
+        "ROLLBACK;"
+    } else {
+        "COMMIT;"
+    };
+    if let Err(e) = tx.execute(end).await {
+        return DbRejection(e).into_response();
+    }
+    tx.finished = true;
+    response
+}
+
+/// Extractor of the transaction [`request_transaction`] runs the
+/// request in. Its connection is locked until the extractor is dropped,
+/// which it is at the latest when the handler returns. Requires the
+/// `axum` feature.
+///
+/// Extraction fails with an internal server error if the
+/// `request_transaction` middleware is not in use.
+#[derive(Debug)]
+pub struct RequestTransaction(OwnedMutexGuard<TransactionConnection>);
+
+impl Deref for RequestTransaction {
+    type Target = ConnectionAsync;
+    fn deref(&self) -> &ConnectionAsync {
+        &self.0
+    }
+}
+
+impl<S> FromRequestParts<S> for RequestTransaction
+where
+    S: Send + Sync,
+{
+    type Rejection = DbRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, DbRejection> {
+        let tx = parts
+            .extensions
+            .get::<Arc<Mutex<TransactionConnection>>>()
+            .cloned()
+            .ok_or_else(|| {
+                DbRejection(Error::Internal(
+                    "RequestTransaction requires the request_transaction middleware".to_string(),
+                ))
+            })?;
+        Ok(RequestTransaction(tx.lock_owned().await))
+    }
+}
+
+/// The connection of a [`RequestTransaction`].
+#[derive(Debug)]
+struct TransactionConnection {
+    conn: Option<Object<ConnectionManager>>,
+    finished: bool,
+}
+
+impl Deref for TransactionConnection {
+    type Target = ConnectionAsync;
+    fn deref(&self) -> &ConnectionAsync {
+        self.conn
+            .as_ref()
+            .expect("connection taken before the transaction finished")
+    }
+}
+
+impl Drop for TransactionConnection {
+    fn drop(&mut self) {
+        // The transaction was not committed or rolled back, so the
+        // connection cannot be reused.
+        if !self.finished {
+            if let Some(conn) = self.conn.take() {
+                drop(Object::take(conn));
+            }
+        }
+    }
+}
+
+/// Rejection of the extractors of a connection, when one cannot be
+/// checked out of the pool. Responds with an internal server error,
+/// leaving out the error so as not to reveal it to clients.
+#[derive(Debug)]
+pub struct DbRejection(pub Error);
+
+impl IntoResponse for DbRejection {
+    fn into_response(self) -> Response {
+        butane_core::warn!("Database error handling request: {}", self.0);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Database unavailable").into_response()
+    }
+}
+
+async fn checkout(
+    pool: &Pool<ConnectionManager>,
+) -> Result<Object<ConnectionManager>, DbRejection> {
+    pool.get().await.map_err(|e| {
+        DbRejection(match e {
+            PoolError::Backend(e) => e,
+            e => Error::Generic(Box::new(e)),
+        })
+    })
+}
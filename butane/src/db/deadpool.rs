@@ -1,8 +1,13 @@
 //! Deadpool support for Butane.
+use std::time::Duration;
+
 use super::ConnectionManager;
-use crate::db::{BackendConnectionAsync, ConnectionAsync};
-use crate::Result;
-use deadpool::managed::{Manager, Metrics, RecycleError, RecycleResult};
+use crate::db::{BackendConnectionAsync, ConnectionAsync, QueryResult};
+use crate::query::{Query, QueryOpsAsync};
+use crate::{DataResult, Error, Result};
+use deadpool::managed::{Manager, Metrics, Pool, PoolError, RecycleError, RecycleResult};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
 
 impl Manager for ConnectionManager {
     type Type = ConnectionAsync;
@@ -13,9 +18,66 @@ async fn create(&self) -> Result<ConnectionAsync> {
     }
 
     async fn recycle(&self, conn: &mut ConnectionAsync, _: &Metrics) -> RecycleResult<Self::Error> {
-        if conn.is_closed() {
-            return Err(RecycleError::message("Connection is closed"));
+        if conn.is_broken() {
+            return Err(RecycleError::message("Connection is broken"));
+        }
+        conn.ping().await.map_err(RecycleError::Backend)
+    }
+}
+
+/// Spawn a task on the current Tokio runtime which pings the idle
+/// connections of `pool` every `interval`, so that they are not
+/// closed by the server, or a proxy, for being idle too long. A
+/// connection which fails to respond is discarded then, rather than
+/// by the first request to check it out after a quiet period.
+///
+/// The task stops once the pool is [closed][Pool::close], or when
+/// the returned handle is aborted. Choose an `interval` shorter than
+/// the idle timeout to guard against, such as PostgreSQL's
+/// `idle_session_timeout`.
+pub fn spawn_keepalive(pool: &Pool<ConnectionManager>, interval: Duration) -> JoinHandle<()> {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately.
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            if pool.is_closed() {
+                break;
+            }
+            ping_idle(&pool).await;
+        }
+    })
+}
+
+/// Ping each connection which is idle in `pool`. The connections are
+/// checked out together, so that each is recycled, and so pinged,
+/// once. Those which fail are replaced by the pool.
+async fn ping_idle(pool: &Pool<ConnectionManager>) {
+    let idle = pool.status().available;
+    let mut checked = Vec::with_capacity(idle);
+    // Stop once none are idle, so as not to wait for a connection in
+    // use or create a new one.
+    while checked.len() < idle && pool.status().available > 0 {
+        match pool.get().await {
+            Ok(conn) => checked.push(conn),
+            Err(_) => break,
         }
-        Ok(())
     }
 }
+
+/// Load `query` on a connection checked out of `pool`, which is
+/// returned to it afterwards. Used by [`try_join_queries`][crate::try_join_queries]
+/// to run queries on connections of their own.
+pub async fn load_pooled<T: DataResult>(
+    pool: &Pool<ConnectionManager>,
+    query: Query<T>,
+) -> Result<QueryResult<T>> {
+    let conn = pool.get().await.map_err(|e| match e {
+        PoolError::Backend(e) => e,
+        e => Error::Generic(Box::new(e)),
+    })?;
+    query.load(&*conn).await
+}
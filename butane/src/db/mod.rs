@@ -19,6 +19,13 @@
 
 #[cfg(feature = "deadpool")]
 mod deadpool;
+#[cfg(feature = "deadpool")]
+pub use deadpool::{load_pooled, spawn_keepalive};
+
+#[cfg(feature = "axum")]
+mod axum;
+#[cfg(feature = "axum")]
+pub use axum::{request_transaction, DbRejection, PooledConnection, RequestTransaction};
 
 /// Connection manager used with connection pooling systems such as r2d2 or deadpool.
 /// With the `r2d2` feature enabled, it implements `r2d2::ManageConnection`.
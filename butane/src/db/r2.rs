@@ -14,10 +14,10 @@ fn connect(&self) -> Result<Connection> {
     }
 
     fn is_valid(&self, conn: &mut Connection) -> Result<()> {
-        conn.execute("SELECT 1")
+        conn.ping()
     }
 
     fn has_broken(&self, conn: &mut Connection) -> bool {
-        conn.is_closed()
+        conn.is_broken()
     }
 }
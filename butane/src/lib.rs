@@ -6,17 +6,31 @@
 
 #![deny(missing_docs)]
 
-pub use butane_codegen::{butane_type, dataresult, model, FieldType, PrimaryKeyType};
+#[cfg(feature = "test-utils")]
+pub use butane_codegen::test;
+pub use butane_codegen::{
+    butane_type, dataresult, embed_migrations, model, FieldType, PrimaryKeyType,
+};
 pub use butane_core::custom;
 pub use butane_core::fkey::{ForeignKey, ForeignKeyOpsSync};
-pub use butane_core::many::{Many, ManyOpsSync};
+pub use butane_core::largeblob::{LargeBlob, LargeBlobChunks, LargeBlobOpsSync};
+pub use butane_core::many::{Many, ManyOpsSync, ManyPages};
 pub use butane_core::migrations;
+#[cfg(feature = "decimal")]
+pub use butane_core::money::{self, Money};
 pub use butane_core::query;
+#[cfg(feature = "bitflags")]
+pub use butane_core::FlagSet;
+#[cfg(feature = "json")]
+pub use butane_core::Json;
 #[cfg(feature = "async")]
-pub use butane_core::{fkey::ForeignKeyOpsAsync, many::ManyOpsAsync, DataObjectOpsAsync};
 pub use butane_core::{
-    AsPrimaryKey, AutoPk, DataObject, DataObjectOpsSync, DataResult, Error, FieldType, FromSql,
-    PrimaryKeyType, Result, SqlType, SqlVal, SqlValRef, ToSql,
+    fkey::ForeignKeyOpsAsync, largeblob::LargeBlobOpsAsync, many::ManyOpsAsync, DataObjectOpsAsync,
+};
+pub use butane_core::{
+    ArrayElement, AsPrimaryKey, AutoPk, CiString, DataObject, DataObjectOpsSync, DataResult, Error,
+    FieldType, FromSql, PrimaryKeyType, Redacted, Result, SqlType, SqlVal, SqlValRef, ToSql,
+    GET_MANY_CHUNK_SIZE,
 };
 
 pub mod db;
@@ -188,6 +202,39 @@ macro_rules! find_async {
     };
 }
 
+/// Loads several queries concurrently, each on its own connection
+/// from a deadpool pool, rather than one after another on a single
+/// connection.
+///
+/// Use as `try_join_queries!(pool, query1, query2, ...)` within an
+/// async function, where `pool` is a
+/// `deadpool::managed::Pool<ConnectionManager>`. Returns
+/// [`Result`]`<(`[`QueryResult`]`<A>, `[`QueryResult`]`<B>, ...)>`,
+/// with the results of the queries in order, or the first error.
+/// Requires the `deadpool` feature.
+///
+/// As the queries use different connections, they are not in one
+/// transaction, and may see the database as it was at different times.
+///
+/// ```ignore
+/// let (posts, blogs) = try_join_queries!(
+///     pool,
+///     query!(Post, published == true),
+///     Blog::query(),
+/// )?;
+/// ```
+///
+/// [`Result`]: crate::Result
+/// [`QueryResult`]: crate::db::QueryResult
+#[cfg(feature = "deadpool")]
+#[macro_export]
+macro_rules! try_join_queries {
+    ($pool:expr, $($query:expr),+ $(,)?) => {{
+        let pool = &$pool;
+        butane::internal::try_join!($(butane::db::load_pooled(pool, $query)),+)
+    }};
+}
+
 mod prelude_common {
     #[doc(no_inline)]
     pub use crate::DataObject;
@@ -205,6 +252,7 @@ pub mod prelude {
 
     pub use butane_core::db::BackendConnection;
     pub use butane_core::fkey::ForeignKeyOpsSync;
+    pub use butane_core::largeblob::LargeBlobOpsSync;
     pub use butane_core::many::ManyOpsSync;
     pub use butane_core::query::QueryOpsSync;
     pub use butane_core::DataObjectOpsSync;
@@ -219,15 +267,29 @@ pub mod prelude_async {
 
     pub use butane_core::db::BackendConnectionAsync;
     pub use butane_core::fkey::ForeignKeyOpsAsync;
+    pub use butane_core::largeblob::LargeBlobOpsAsync;
     pub use butane_core::many::ManyOpsAsync;
     pub use butane_core::query::QueryOpsAsync;
     pub use butane_core::DataObjectOpsAsync;
 }
 
+#[cfg(feature = "test-utils")]
+pub mod test_utils {
+    //! Factories building and saving instances of models for tests,
+    //! and the databases of tests generated by [`macro@crate::test`].
+    //!
+    //! See [`Factory`][macro@Factory] for deriving a factory of a model.
+
+    pub use butane_codegen::Factory;
+    pub use butane_core::test_utils::*;
+}
+
 pub mod internal {
     //! Internals used in macro-generated code.
     //!
     //! Do not use directly. Semver-exempt.
 
     pub use butane_core::internal::*;
+    #[cfg(feature = "deadpool")]
+    pub use futures_util::try_join;
 }
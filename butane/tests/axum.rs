@@ -0,0 +1,104 @@
+use std::ops::DerefMut;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::routing::{get, post};
+use axum::Router;
+use butane::db::{ConnectionManager, PooledConnection, RequestTransaction};
+use butane::prelude_async::*;
+use butane_test_helper::*;
+use deadpool::managed::Pool;
+use tower::ServiceExt;
+
+mod common;
+use common::blog::Blog;
+
+/// A pool of one connection to an in-memory SQLite database with the
+/// schema of the test models.
+async fn pool() -> Pool<ConnectionManager> {
+    let manager = ConnectionManager::new(sqlite_connspec());
+    let pool: Pool<ConnectionManager> = Pool::builder(manager).max_size(1).build().unwrap();
+    setup_db_async(pool.get().await.unwrap().deref_mut()).await;
+    pool
+}
+
+async fn status(app: &Router, method: &str, uri: &str) -> StatusCode {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+async fn count_blogs(conn: PooledConnection) -> String {
+    Blog::query().load(&*conn).await.unwrap().len().to_string()
+}
+
+async fn create_blog(conn: PooledConnection) -> StatusCode {
+    Blog::new(1, "Cats").save(&*conn).await.unwrap();
+    StatusCode::CREATED
+}
+
+async fn create_blog_in_transaction(tx: RequestTransaction) -> StatusCode {
+    Blog::new(2, "Dogs").save(&*tx).await.unwrap();
+    StatusCode::CREATED
+}
+
+async fn fail_in_transaction(tx: RequestTransaction) -> StatusCode {
+    Blog::new(3, "Birds").save(&*tx).await.unwrap();
+    StatusCode::BAD_REQUEST
+}
+
+#[tokio::test]
+async fn pooled_connection() {
+    let pool = pool().await;
+    let app = Router::new()
+        .route("/blogs", get(count_blogs).post(create_blog))
+        .with_state(pool.clone());
+    assert_eq!(status(&app, "POST", "/blogs").await, StatusCode::CREATED);
+    assert_eq!(
+        Blog::query()
+            .load(&*pool.get().await.unwrap())
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    // The connection was returned to the pool.
+    assert_eq!(pool.status().available, 1);
+}
+
+#[tokio::test]
+async fn request_transaction() {
+    let pool = pool().await;
+    let app = Router::new()
+        .route("/commit", post(create_blog_in_transaction))
+        .route("/rollback", post(fail_in_transaction))
+        .layer(axum::middleware::from_fn_with_state(
+            pool.clone(),
+            butane::db::request_transaction,
+        ))
+        .with_state(pool.clone());
+
+    assert_eq!(
+        status(&app, "POST", "/rollback").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(status(&app, "POST", "/commit").await, StatusCode::CREATED);
+    let conn = pool.get().await.unwrap();
+    let blogs = Blog::query().load(&*conn).await.unwrap();
+    assert_eq!(blogs.len(), 1);
+    assert_eq!(blogs[0].name, "Dogs");
+}
+
+#[tokio::test]
+async fn request_transaction_without_middleware() {
+    let app = Router::new()
+        .route("/blogs", post(create_blog_in_transaction))
+        .with_state(pool().await);
+    assert_eq!(
+        status(&app, "POST", "/blogs").await,
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+}
@@ -1,14 +1,18 @@
 #![allow(clippy::disallowed_names)]
 
 use butane::colname;
-use butane::db::{Connection, ConnectionAsync};
-use butane::{butane_type, find, find_async, model, query, AutoPk, ForeignKey};
+use butane::db::{
+    Connection, ConnectionAsync, IsolationLevel, Pipeline, QueryLogger, RetryPolicy, Session,
+    SlowQueryLog, TransactionOptions,
+};
+use butane::{butane_type, find, find_async, model, query, AutoPk, ForeignKey, Redacted};
 use butane_test_helper::*;
 use butane_test_macros::butane_test;
 #[cfg(feature = "datetime")]
-use chrono::{naive::NaiveDateTime, offset::Utc, DateTime};
+use chrono::{naive::NaiveDateTime, offset::FixedOffset, offset::Utc, DateTime};
 #[cfg(feature = "sqlite")]
 use rusqlite;
+use std::num::{NonZero, NonZeroI64, NonZeroU32};
 use std::ops::Deref;
 #[cfg(feature = "pg")]
 use tokio_postgres as postgres;
@@ -100,6 +104,14 @@ fn new(id: i32) -> Self {
     }
 }
 
+#[model]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct DeferredReferential {
+    pub id: i32,
+    #[deferrable]
+    pub reference: Option<ForeignKey<DeferredReferential>>,
+}
+
 #[cfg(feature = "datetime")]
 #[model]
 #[derive(Debug, Default, PartialEq, Clone)]
@@ -108,6 +120,98 @@ struct TimeHolder {
     pub naive: NaiveDateTime,
     pub utc: DateTime<Utc>,
     pub when: chrono::DateTime<Utc>,
+    pub offset: DateTime<FixedOffset>,
+}
+
+#[cfg(feature = "datetime")]
+#[model]
+#[derive(Debug, PartialEq, Clone)]
+struct PreciseTime {
+    pub id: i32,
+    #[timestamp(precision = 3)]
+    pub millis: NaiveDateTime,
+    pub micros: DateTime<Utc>,
+}
+
+#[cfg(feature = "datetime")]
+#[model]
+#[derive(Debug, PartialEq, Clone)]
+struct DateHolder {
+    pub id: i32,
+    pub date: chrono::NaiveDate,
+    pub time: chrono::NaiveTime,
+    pub maybe: Option<chrono::NaiveDate>,
+}
+
+#[cfg(feature = "decimal")]
+#[model]
+#[derive(Debug, PartialEq, Clone)]
+struct Price {
+    pub id: i32,
+    #[numeric(precision = 30, scale = 2)]
+    pub amount: rust_decimal::Decimal,
+    pub rate: Option<rust_decimal::Decimal>,
+}
+
+#[cfg(feature = "decimal")]
+#[model]
+#[derive(Debug, PartialEq, Clone)]
+struct Product {
+    pub id: i32,
+    #[numeric(precision = 12, scale = 2)]
+    pub price: butane::Money<butane::money::Usd>,
+    pub discount: Option<butane::Money<butane::money::Usd>>,
+}
+
+#[model]
+#[table = "wide_ints"]
+#[derive(Debug, PartialEq, Clone)]
+struct WideInts {
+    pub id: i32,
+    pub small: u32,
+    pub big: u64,
+    pub huge: i128,
+    pub maybe: Option<i128>,
+}
+
+#[model]
+#[table = "nonzero_ints"]
+#[derive(Debug, PartialEq, Clone)]
+struct NonZeroInts {
+    pub id: NonZeroI64,
+    pub count: NonZeroU32,
+    #[check = "checked <> 0"]
+    pub checked: NonZero<i16>,
+    pub maybe: Option<NonZero<u64>>,
+}
+
+#[model]
+#[derive(Debug, PartialEq, Clone)]
+struct ApiKey {
+    pub id: i64,
+    pub owner: String,
+    pub token: Redacted<String>,
+    pub previous: Option<Redacted<String>>,
+}
+
+#[model]
+#[derive(Debug, PartialEq, Clone)]
+struct Tagged {
+    pub id: i32,
+    pub labels: Vec<String>,
+    pub scores: Vec<i64>,
+    pub maybe: Option<Vec<f64>>,
+}
+
+#[cfg(feature = "time")]
+#[model]
+#[derive(Debug, PartialEq, Clone)]
+struct TimeCrateHolder {
+    pub id: i32,
+    pub primitive: time::PrimitiveDateTime,
+    pub offset: time::OffsetDateTime,
+    pub date: time::Date,
+    pub time: time::Time,
 }
 
 #[butane_test]
@@ -304,6 +408,30 @@ async fn basic_dropped_transaction(mut conn: ConnectionAsync) {
     }
 }
 
+#[butane_test(async)]
+async fn cancelled_transaction(mut conn: ConnectionAsync) {
+    use futures_util::FutureExt;
+
+    // Cancel beginning a transaction after it has been requested. It
+    // must not be left open on the connection.
+    drop(conn.transaction().now_or_never());
+    let mut tr = conn.transaction().await.unwrap();
+    drop(tr.transaction().now_or_never());
+    let mut foo = Foo::new(1);
+    foo.save(&tr).await.unwrap();
+    tr.commit().await.unwrap();
+
+    // Cancel a save in a transaction, which is then dropped.
+    {
+        let tr = conn.transaction().await.unwrap();
+        let mut foo = Foo::new(2);
+        foo.bar = 2;
+        drop(foo.save(&tr).now_or_never());
+    }
+    Foo::get(&conn, 1).await.unwrap();
+    assert!(Foo::try_get(&conn, 2).await.unwrap().is_none());
+}
+
 #[butane_test]
 async fn basic_rollback_transaction(mut conn: ConnectionAsync) {
     let tr = conn.transaction().await.unwrap();
@@ -322,6 +450,356 @@ async fn basic_rollback_transaction(mut conn: ConnectionAsync) {
     }
 }
 
+#[butane_test]
+async fn nested_transaction(mut conn: ConnectionAsync) {
+    let mut tr = conn.transaction().await.unwrap();
+    let mut foo = Foo::new(1);
+    foo.bar = 1;
+    foo.save(&tr).await.unwrap();
+
+    // A committed nested transaction keeps its changes.
+    let nested = tr.transaction().await.unwrap();
+    let mut foo = Foo::new(2);
+    foo.bar = 2;
+    foo.save(&nested).await.unwrap();
+    nested.commit().await.unwrap();
+
+    // A rolled back one undoes only its own, as does a dropped one.
+    let mut nested = tr.transaction().await.unwrap();
+    let mut foo = Foo::new(3);
+    foo.bar = 3;
+    foo.save(&nested).await.unwrap();
+    {
+        let inner = nested.transaction().await.unwrap();
+        let mut foo = Foo::new(4);
+        foo.bar = 4;
+        foo.save(&inner).await.unwrap();
+    }
+    assert!(Foo::get(&nested, 3).await.is_ok());
+    assert!(Foo::get(&nested, 4).await.is_err());
+    nested.rollback().await.unwrap();
+    assert!(Foo::get(&tr, 3).await.is_err());
+    tr.commit().await.unwrap();
+
+    assert!(Foo::get(&conn, 1).await.is_ok());
+    assert!(Foo::get(&conn, 2).await.is_ok());
+    assert!(Foo::get(&conn, 3).await.is_err());
+    assert!(Foo::get(&conn, 4).await.is_err());
+}
+
+#[butane_test]
+async fn nested_transaction_rolled_back_with_outer(mut conn: ConnectionAsync) {
+    let mut tr = conn.transaction().await.unwrap();
+    let nested = tr.transaction().await.unwrap();
+    let mut foo = Foo::new(1);
+    foo.bar = 1;
+    foo.save(&nested).await.unwrap();
+    nested.commit().await.unwrap();
+    tr.rollback().await.unwrap();
+
+    assert!(Foo::get(&conn, 1).await.is_err());
+}
+
+#[butane_test]
+async fn transaction_with_options(mut conn: ConnectionAsync) {
+    let options = TransactionOptions {
+        isolation: Some(IsolationLevel::Serializable),
+        read_only: false,
+    };
+    let tr = conn.transaction_with(options).await.unwrap();
+    let mut foo = Foo::new(1);
+    foo.bar = 1;
+    foo.save(&tr).await.unwrap();
+    tr.commit().await.unwrap();
+    assert!(Foo::get(&conn, 1).await.is_ok());
+}
+
+#[butane_test]
+async fn read_only_transaction(mut conn: ConnectionAsync) {
+    let options = TransactionOptions {
+        read_only: true,
+        ..Default::default()
+    };
+    let tr = conn.transaction_with(options).await.unwrap();
+    let mut foo = Foo::new(1);
+    foo.bar = 1;
+    assert!(foo.save(&tr).await.is_err());
+    drop(tr);
+
+    // The connection may write again once the transaction has ended.
+    foo.save(&conn).await.unwrap();
+    let tr = conn.transaction_with(options).await.unwrap();
+    assert!(Foo::get(&tr, 1).await.is_ok());
+    tr.commit().await.unwrap();
+    let mut foo = Foo::new(2);
+    foo.bar = 2;
+    foo.save(&conn).await.unwrap();
+}
+
+#[butane_test(async)]
+async fn pipelined_operations(conn: ConnectionAsync) {
+    let mut foos: Vec<Foo> = (1..=10)
+        .map(|id| {
+            let mut foo = Foo::new(id);
+            foo.bar = id as u32;
+            foo
+        })
+        .collect();
+    let mut pipeline = Pipeline::new();
+    for foo in &mut foos {
+        pipeline.push(foo.save(&conn));
+    }
+    assert_eq!(pipeline.len(), 10);
+    pipeline.run().await.unwrap();
+
+    let mut pipeline = Pipeline::new();
+    for id in [3, 1, 2] {
+        pipeline.push(Foo::get(&conn, id));
+    }
+    let ids: Vec<i64> = pipeline
+        .run()
+        .await
+        .unwrap()
+        .iter()
+        .map(|foo| foo.id)
+        .collect();
+    assert_eq!(ids, vec![3, 1, 2]);
+
+    // A failing operation fails the pipeline.
+    let mut pipeline = Pipeline::new();
+    pipeline.push(Foo::get(&conn, 1)).push(Foo::get(&conn, 11));
+    assert!(matches!(
+        pipeline.run().await,
+        Err(butane::Error::NoSuchObject)
+    ));
+}
+
+#[butane_test(async)]
+async fn pipeline_first_error(conn: ConnectionAsync) {
+    let mut foo = Foo::new(1);
+    foo.save(&conn).await.unwrap();
+
+    // The error is that of the first operation queued to fail, even
+    // when a later one fails sooner.
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .push(Foo::get(&conn, 1))
+        .push(Foo::get(&conn, 11))
+        .push(async { Err(butane::Error::Internal("later".to_string())) });
+    assert!(matches!(
+        pipeline.run().await,
+        Err(butane::Error::NoSuchObject)
+    ));
+
+    // The pipeline stops at the error, without waiting for the rest.
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .push(Foo::get(&conn, 11))
+        .push(std::future::pending());
+    assert!(matches!(
+        pipeline.run().await,
+        Err(butane::Error::NoSuchObject)
+    ));
+}
+
+#[butane_test]
+async fn query_logging(conn: ConnectionAsync) {
+    type Logged = (String, Vec<String>, Option<u64>);
+    let entries: std::sync::Arc<std::sync::Mutex<Vec<Logged>>> = Default::default();
+    let sink = entries.clone();
+    conn.set_query_logger(Some(QueryLogger::new(move |info| {
+        assert!(info.error.is_none());
+        sink.lock()
+            .unwrap()
+            .push((info.sql.to_string(), info.params.to_vec(), info.rows));
+    })));
+
+    let mut foo = Foo::new(1);
+    foo.bar = 42;
+    foo.baz = "secret".to_string();
+    foo.save(&conn).await.unwrap();
+    let cnt = query!(Foo, bar == 42).delete(&conn).await.unwrap();
+    assert_eq!(cnt, 1);
+
+    let logged = std::mem::take(&mut *entries.lock().unwrap());
+    let (sql, params, _) = &logged[0];
+    assert!(sql.contains("Foo"));
+    // Text values are redacted, while numbers are shown.
+    assert!(params.contains(&"42".to_string()));
+    assert!(params.contains(&"<text, 6 chars>".to_string()));
+    assert!(!params.iter().any(|p| p.contains("secret")));
+    let (sql, _, rows) = logged.last().unwrap();
+    assert!(sql.starts_with("DELETE FROM"));
+    assert_eq!(*rows, Some(1));
+
+    // Nothing is logged once the logger is removed.
+    conn.set_query_logger(None);
+    Foo::get(&conn, 1).await.unwrap_err();
+    assert!(entries.lock().unwrap().is_empty());
+}
+
+#[butane_test]
+async fn slow_query_log(conn: ConnectionAsync) {
+    type Reported = (String, Option<String>);
+    let entries: std::sync::Arc<std::sync::Mutex<Vec<Reported>>> = Default::default();
+    let sink = entries.clone();
+    let slow = SlowQueryLog::new(std::time::Duration::ZERO)
+        .hook(move |info| {
+            let explain = info.explain.map(str::to_string);
+            sink.lock().unwrap().push((info.sql.to_string(), explain));
+        })
+        .explain(true);
+    conn.set_slow_query_log(Some(slow));
+
+    let mut foo = Foo::new(1);
+    foo.bar = 42;
+    foo.save(&conn).await.unwrap();
+    query!(Foo, bar == 42).load(&conn).await.unwrap();
+    let logged = std::mem::take(&mut *entries.lock().unwrap());
+    let (sql, explain) = logged.last().unwrap();
+    assert!(sql.starts_with("SELECT"));
+    // Plans are only available from PostgreSQL.
+    match conn.backend_name() {
+        "pg" => assert!(explain.as_ref().unwrap().contains("Scan")),
+        _ => assert!(explain.is_none()),
+    }
+
+    // Statements faster than the threshold are not reported.
+    let slow = SlowQueryLog::new(std::time::Duration::from_secs(3600)).hook(|info| {
+        panic!("{} reported as slow", info.sql);
+    });
+    conn.set_slow_query_log(Some(slow));
+    Foo::get(&conn, 1).await.unwrap();
+    conn.set_slow_query_log(None);
+    assert!(entries.lock().unwrap().is_empty());
+}
+
+#[butane_test(pg)]
+async fn two_phase_commit(mut conn: ConnectionAsync) {
+    // Prepared transaction ids are shared by all databases on the server.
+    let gid = uuid_for_test::Uuid::new_v4().to_string();
+    let tr = conn.transaction().await.unwrap();
+    let mut foo = Foo::new(1);
+    foo.bar = 1;
+    foo.save(&tr).await.unwrap();
+    tr.prepare(&gid).await.unwrap();
+    assert!(Foo::get(&conn, 1).await.is_err());
+    conn.commit_prepared(&gid).await.unwrap();
+    assert!(Foo::get(&conn, 1).await.is_ok());
+
+    let gid = uuid_for_test::Uuid::new_v4().to_string();
+    let tr = conn.transaction().await.unwrap();
+    let mut foo = Foo::new(2);
+    foo.bar = 2;
+    foo.save(&tr).await.unwrap();
+    tr.prepare(&gid).await.unwrap();
+    conn.rollback_prepared(&gid).await.unwrap();
+    assert!(Foo::get(&conn, 2).await.is_err());
+}
+
+#[butane_test(sync)]
+fn run_in_transaction(mut conn: Connection) {
+    let id = conn
+        .run_in_transaction(|tr| {
+            let mut foo = Foo::new(1);
+            foo.bar = 1;
+            foo.save(tr)?;
+            Ok(foo.id)
+        })
+        .unwrap();
+    assert!(Foo::get(&conn, id).is_ok());
+
+    let result: butane::Result<()> = conn.run_in_transaction(|tr| {
+        let mut foo = Foo::new(2);
+        foo.bar = 2;
+        foo.save(tr)?;
+        Err(butane::Error::NoSuchObject)
+    });
+    assert!(matches!(result, Err(butane::Error::NoSuchObject)));
+    assert!(Foo::get(&conn, 2).is_err());
+}
+
+#[butane_test(async)]
+async fn run_in_transaction_boxed_future(mut conn: ConnectionAsync) {
+    let id = conn
+        .run_in_transaction(|tr| {
+            Box::pin(async move {
+                let mut foo = Foo::new(1);
+                foo.bar = 1;
+                foo.save(tr).await?;
+                Ok(foo.id)
+            })
+        })
+        .await
+        .unwrap();
+    assert!(Foo::get(&conn, id).await.is_ok());
+
+    let result: butane::Result<()> = conn
+        .run_in_transaction(|tr| {
+            Box::pin(async move {
+                let mut foo = Foo::new(2);
+                foo.bar = 2;
+                foo.save(tr).await?;
+                Err(butane::Error::NoSuchObject)
+            })
+        })
+        .await;
+    assert!(matches!(result, Err(butane::Error::NoSuchObject)));
+    assert!(Foo::get(&conn, 2).await.is_err());
+}
+
+#[cfg(feature = "sqlite")]
+#[butane_test(sync)]
+fn run_in_transaction_retries(mut conn: Connection) {
+    let busy = || {
+        butane::Error::SQLite(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        ))
+    };
+    let retry = RetryPolicy {
+        max_retries: 2,
+        ..Default::default()
+    };
+
+    // Each attempt's changes are rolled back before the next.
+    let mut attempts = 0;
+    conn.run_in_transaction_with(TransactionOptions::default(), retry, |tr| {
+        attempts += 1;
+        let mut foo = Foo::new(attempts);
+        foo.bar = attempts as u32;
+        foo.save(tr)?;
+        if attempts < 2 {
+            return Err(busy());
+        }
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(attempts, 2);
+    assert!(Foo::get(&conn, 1).is_err());
+    assert!(Foo::get(&conn, 2).is_ok());
+
+    // It gives up after max_retries.
+    let mut attempts = 0;
+    let result: butane::Result<()> =
+        conn.run_in_transaction_with(TransactionOptions::default(), retry, |_| {
+            attempts += 1;
+            Err(busy())
+        });
+    assert!(result.unwrap_err().is_retryable());
+    assert_eq!(attempts, 3);
+
+    // Other errors are not retried.
+    let mut attempts = 0;
+    let result: butane::Result<()> =
+        conn.run_in_transaction_with(TransactionOptions::default(), retry, |_| {
+            attempts += 1;
+            Err(butane::Error::NoSuchObject)
+        });
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+}
+
 #[butane_test]
 async fn basic_unique_field_error_on_non_unique(conn: ConnectionAsync) {
     let mut foo1 = Foo::new(1);
@@ -361,6 +839,28 @@ async fn fkey_same_type(conn: ConnectionAsync) {
     assert!(inner.reference.is_none());
 }
 
+#[butane_test]
+async fn deferrable_fkey_circular_reference(mut conn: ConnectionAsync) {
+    let tr = conn.transaction().await.unwrap();
+    // Neither row exists when the first one is inserted, which is only
+    // allowed because the constraint is checked at commit.
+    let mut o1 = DeferredReferential {
+        id: 1,
+        reference: Some(ForeignKey::from_pk(2)),
+    };
+    o1.save(&tr).await.unwrap();
+    let mut o2 = DeferredReferential {
+        id: 2,
+        reference: Some(ForeignKey::from_pk(1)),
+    };
+    o2.save(&tr).await.unwrap();
+    tr.commit().await.unwrap();
+
+    let o1 = DeferredReferential::get(&conn, 1).await.unwrap();
+    let inner = o1.reference.unwrap().load(&conn).await.unwrap().clone();
+    assert_eq!(inner.id, 2);
+}
+
 #[butane_test]
 async fn cant_save_unsaved_fkey(conn: ConnectionAsync) {
     let foo = Foo::new(1);
@@ -383,17 +883,375 @@ async fn basic_time(conn: ConnectionAsync) {
         naive: now.naive_utc(),
         utc: now,
         when: now,
+        offset: now.with_timezone(&FixedOffset::east_opt(5 * 3600).unwrap()),
     };
     time.save(&conn).await.unwrap();
 
     let time2 = TimeHolder::get(&conn, 1).await.unwrap();
+    // Every backend keeps microseconds.
+    assert_eq!(time.utc.timestamp_micros(), time2.utc.timestamp_micros());
+    assert_eq!(time2.utc.timestamp_subsec_nanos() % 1000, 0);
+    // The same instant is read back, though not in its original offset.
+    assert_eq!(
+        time.offset.timestamp_micros(),
+        time2.offset.timestamp_micros()
+    );
+}
+
+#[cfg(feature = "decimal")]
+#[butane_test]
+async fn decimal(conn: ConnectionAsync) {
+    // Too many digits for an f64 to hold exactly.
+    let amount: rust_decimal::Decimal = "1234567890123456789012.50".parse().unwrap();
+    let mut price = Price {
+        id: 1,
+        amount,
+        rate: Some("0.000000000123456789".parse().unwrap()),
+    };
+    price.save(&conn).await.unwrap();
+    let mut other = Price {
+        id: 2,
+        amount: rust_decimal::Decimal::new(1, 2),
+        rate: None,
+    };
+    other.save(&conn).await.unwrap();
+
+    let price2 = Price::get(&conn, 1).await.unwrap();
+    assert_eq!(price, price2);
+    // The scale is kept, not only the value.
+    assert_eq!(price2.amount.to_string(), "1234567890123456789012.50");
+    assert_eq!(Price::get(&conn, 2).await.unwrap(), other);
+
+    let found = query!(Price, amount == { amount })
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![price]);
+}
+
+#[cfg(feature = "decimal")]
+#[butane_test]
+async fn money_fields(conn: ConnectionAsync) {
+    use butane::money::Usd;
+    use butane::Money;
+
+    let usd = |amount: &str| Money::<Usd>::new(amount.parse().unwrap());
+    let mut cheap = Product {
+        id: 1,
+        price: usd("4.99"),
+        discount: Some(usd("0.50")),
+    };
+    cheap.save(&conn).await.unwrap();
+    let mut dear = Product {
+        id: 2,
+        price: usd("120.00"),
+        discount: None,
+    };
+    dear.save(&conn).await.unwrap();
+
+    assert_eq!(Product::get(&conn, 1).await.unwrap(), cheap);
+    assert_eq!(Product::get(&conn, 2).await.unwrap(), dear);
+    let found = query!(Product, price < { usd("10") })
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![cheap.clone()]);
+
+    // The currency is stored alongside the amount, and checked on load.
+    conn.execute("UPDATE Product SET price_currency = 'EUR' WHERE id = 1")
+        .await
+        .unwrap();
+    let err = Product::get(&conn, 1).await.unwrap_err();
+    assert!(
+        matches!(&err, butane::Error::CurrencyMismatch("USD", found) if found == "EUR"),
+        "{err:?}"
+    );
+}
+
+#[butane_test]
+async fn wide_ints(conn: ConnectionAsync) {
+    let mut ints = WideInts {
+        id: 1,
+        small: u32::MAX,
+        big: u64::MAX,
+        huge: i128::MIN,
+        maybe: Some(i128::MAX),
+    };
+    ints.save(&conn).await.unwrap();
+    let mut other = WideInts {
+        id: 2,
+        small: 0,
+        big: 10_000,
+        huge: -1,
+        maybe: None,
+    };
+    other.save(&conn).await.unwrap();
+
+    assert_eq!(WideInts::get(&conn, 1).await.unwrap(), ints);
+    assert_eq!(WideInts::get(&conn, 2).await.unwrap(), other);
+    let found = query!(WideInts, big == { u64::MAX })
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![ints]);
+
+    // A stored value which does not fit the field is an error on load.
+    conn.execute("UPDATE wide_ints SET small = -1 WHERE id = 2")
+        .await
+        .unwrap();
+    let err = WideInts::get(&conn, 2).await.unwrap_err();
+    assert!(matches!(err, butane::Error::OutOfRange), "{err:?}");
+}
+
+#[butane_test]
+async fn nonzero_ints(conn: ConnectionAsync) {
+    let mut ints = NonZeroInts {
+        id: NonZeroI64::MIN,
+        count: NonZeroU32::MAX,
+        checked: NonZero::new(-1).unwrap(),
+        maybe: NonZero::new(u64::MAX),
+    };
+    ints.save(&conn).await.unwrap();
+    let mut other = NonZeroInts {
+        id: NonZeroI64::new(1).unwrap(),
+        count: NonZeroU32::MIN,
+        checked: NonZero::new(1).unwrap(),
+        maybe: None,
+    };
+    other.save(&conn).await.unwrap();
+
+    assert_eq!(NonZeroInts::get(&conn, ints.id).await.unwrap(), ints);
+    assert_eq!(NonZeroInts::get(&conn, other.id).await.unwrap(), other);
+    let found = query!(NonZeroInts, count == { NonZeroU32::MAX })
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![ints]);
+
+    // A stored zero is an error on load.
+    conn.execute("UPDATE nonzero_ints SET count = 0 WHERE id = 1")
+        .await
+        .unwrap();
+    let err = NonZeroInts::get(&conn, other.id).await.unwrap_err();
+    assert!(matches!(err, butane::Error::UnexpectedZero), "{err:?}");
+
+    // The check constraint keeps zeros out of its column.
+    assert!(conn
+        .execute("UPDATE nonzero_ints SET checked = 0 WHERE id = 1")
+        .await
+        .is_err());
+}
+
+#[butane_test]
+async fn redacted_fields(conn: ConnectionAsync) {
+    let mut key = ApiKey {
+        id: 1,
+        owner: "alice".to_string(),
+        token: "hunter2".into(),
+        previous: Some("swordfish".into()),
+    };
+    assert!(!format!("{key:?}").contains("hunter2"));
+    assert!(!format!("{key:?}").contains("swordfish"));
+    key.save(&conn).await.unwrap();
+
+    let key2 = ApiKey::get(&conn, 1).await.unwrap();
+    assert_eq!(key2, key);
+    assert_eq!(key2.token.expose(), "hunter2");
+    let found = query!(ApiKey, token == { Redacted::from("hunter2") })
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![key]);
+}
+
+#[butane_test]
+async fn array_fields(conn: ConnectionAsync) {
+    let mut tagged = Tagged {
+        id: 1,
+        labels: vec!["red".to_string(), "blue".to_string()],
+        scores: vec![3, 5, 8],
+        maybe: Some(vec![0.5]),
+    };
+    tagged.save(&conn).await.unwrap();
+    let mut empty = Tagged {
+        id: 2,
+        labels: Vec::new(),
+        scores: vec![1],
+        maybe: None,
+    };
+    empty.save(&conn).await.unwrap();
+
+    assert_eq!(Tagged::get(&conn, 1).await.unwrap(), tagged);
+    assert_eq!(Tagged::get(&conn, 2).await.unwrap(), empty);
+
+    let found = query!(Tagged, labels.contains("blue"))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![tagged.clone()]);
+    let found = query!(Tagged, labels.overlaps({ ["green", "red"] }))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![tagged.clone()]);
+    let found = query!(Tagged, labels.contains("green"))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert!(found.is_empty());
+    let found = query!(Tagged, scores.overlaps({ [1, 8] }))
+        .order_asc(colname!(Tagged, id))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![tagged, empty]);
+    let found = query!(Tagged, scores.overlaps({ [2, 4] }))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert!(found.is_empty());
+}
+
+#[cfg(feature = "time")]
+#[butane_test]
+async fn time_crate(conn: ConnectionAsync) {
+    let now = time::OffsetDateTime::now_utc();
+    // Postgres keeps microseconds.
+    let now = now.replace_microsecond(now.microsecond()).unwrap();
+    let mut holder = TimeCrateHolder {
+        id: 1,
+        primitive: time::PrimitiveDateTime::new(now.date(), now.time()),
+        offset: now.to_offset(time::UtcOffset::from_hms(-3, 30, 0).unwrap()),
+        date: now.date(),
+        time: now.time(),
+    };
+    holder.save(&conn).await.unwrap();
+
+    let holder2 = TimeCrateHolder::get(&conn, 1).await.unwrap();
+    assert_eq!(holder.primitive, holder2.primitive);
+    assert_eq!(holder.date, holder2.date);
+    assert_eq!(holder.time, holder2.time);
+    // The same instant, read back in UTC.
+    assert_eq!(holder.offset, holder2.offset);
+    assert_eq!(holder2.offset.offset(), time::UtcOffset::UTC);
+
+    let found = query!(TimeCrateHolder, date == { now.date() })
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+}
+
+#[cfg(feature = "datetime")]
+#[butane_test]
+async fn date_and_time(conn: ConnectionAsync) {
+    use chrono::{NaiveDate, NaiveTime};
+    let mut holder = DateHolder {
+        id: 1,
+        date: NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(),
+        time: NaiveTime::from_hms_opt(13, 45, 30).unwrap(),
+        maybe: None,
+    };
+    holder.save(&conn).await.unwrap();
+    let mut other = DateHolder {
+        id: 2,
+        date: NaiveDate::from_ymd_opt(2023, 12, 1).unwrap(),
+        time: NaiveTime::from_hms_opt(8, 5, 0).unwrap(),
+        maybe: Some(NaiveDate::from_ymd_opt(2023, 12, 2).unwrap()),
+    };
+    other.save(&conn).await.unwrap();
+
+    let holder2 = DateHolder::get(&conn, 1).await.unwrap();
+    assert_eq!(holder, holder2);
+
+    let found = query!(DateHolder, date.year() == 2024)
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![holder.clone()]);
+    let found = query!(DateHolder, date.month() == 2 && date.day() == 29)
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![holder.clone()]);
+    let found = query!(DateHolder, time.hour() < 12 && time.minute() == 5)
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![other.clone()]);
+    let found = query!(DateHolder, time.second() >= 30)
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![holder]);
+    let found = query!(DateHolder, maybe.day() == 2)
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![other]);
+}
+
+#[cfg(feature = "datetime")]
+#[butane_test]
+async fn timestamp_precision(conn: ConnectionAsync) {
+    use chrono::{NaiveDate, Timelike};
+    let when = NaiveDate::from_ymd_opt(2024, 1, 2)
+        .unwrap()
+        .and_hms_nano_opt(3, 4, 5, 123_456_789)
+        .unwrap();
+    let mut time = PreciseTime {
+        id: 1,
+        millis: when,
+        micros: when.and_utc(),
+    };
+    time.save(&conn).await.unwrap();
+
+    // Each backend keeps the same fraction of a second.
+    let time2 = PreciseTime::get(&conn, 1).await.unwrap();
+    assert_eq!(time2.millis, when.with_nanosecond(123_000_000).unwrap());
+    assert_eq!(
+        time2.micros,
+        when.and_utc().with_nanosecond(123_456_000).unwrap()
+    );
+
     if conn.backend_name() == "sqlite" {
-        assert_eq!(time.utc, time2.utc);
-    } else {
-        assert_eq!(time.utc.timestamp_micros(), time2.utc.timestamp_micros());
+        // A timestamp without a time zone cannot be read as one with.
+        conn.execute("UPDATE PreciseTime SET micros = '2024-01-02 03:04:05';")
+            .await
+            .unwrap();
+        let err = PreciseTime::get(&conn, 1).await.unwrap_err();
+        assert!(
+            matches!(err, butane::Error::TimezoneMismatch(..)),
+            "{err:?}"
+        );
     }
 }
 
+#[cfg(feature = "datetime")]
+#[butane_test]
+async fn timestamptz_ordering(conn: ConnectionAsync) {
+    let now = Utc::now();
+    // Earlier than `now`, though later on the clock of its own offset.
+    let earlier =
+        (now - chrono::Duration::hours(1)).with_timezone(&FixedOffset::east_opt(3 * 3600).unwrap());
+    for (id, offset) in [(1, now.fixed_offset()), (2, earlier)] {
+        let mut time = TimeHolder {
+            id,
+            naive: now.naive_utc(),
+            utc: now,
+            when: now,
+            offset,
+        };
+        time.save(&conn).await.unwrap();
+    }
+    let times = query!(TimeHolder, offset < { now.fixed_offset() })
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(times.len(), 1);
+    assert_eq!(times[0].id, 2);
+}
+
 #[butane_test]
 async fn basic_load_first(conn: ConnectionAsync) {
     //create
@@ -474,6 +1332,48 @@ async fn tokio_spawn(conn: ConnectionAsync) {
     });
 }
 
+#[model]
+#[derive(Debug, PartialEq)]
+struct CowFields {
+    id: i64,
+    text: std::borrow::Cow<'static, str>,
+    data: std::borrow::Cow<'static, [u8]>,
+}
+
+#[butane_test]
+async fn cow_fields(conn: ConnectionAsync) {
+    let mut cow = CowFields {
+        id: 1,
+        text: "borrowed".into(),
+        data: std::borrow::Cow::Borrowed(b"bytes"),
+    };
+    cow.save(&conn).await.unwrap();
+    let loaded = CowFields::get(&conn, 1).await.unwrap();
+    assert_eq!(loaded, cow);
+    let found = find_async!(CowFields, text == "borrowed", &conn).unwrap();
+    assert_eq!(found.data.as_ref(), b"bytes");
+}
+
+#[cfg(feature = "bytes")]
+#[model]
+#[derive(Debug, PartialEq)]
+struct BytesField {
+    id: i64,
+    data: bytes::Bytes,
+}
+
+#[cfg(feature = "bytes")]
+#[butane_test]
+async fn bytes_field(conn: ConnectionAsync) {
+    let mut obj = BytesField {
+        id: 1,
+        data: bytes::Bytes::from_static(b"\x00\x01\x02"),
+    };
+    obj.save(&conn).await.unwrap();
+    let loaded = BytesField::get(&conn, 1).await.unwrap();
+    assert_eq!(loaded, obj);
+}
+
 #[model]
 struct TypeVariantsTest {
     id: i64,
@@ -484,3 +1384,211 @@ struct TypeVariantsTest {
     blob2: std::vec::Vec<u8>,
     blob3: ::std::vec::Vec<u8>,
 }
+
+#[butane_test]
+async fn get_many_by_pk(conn: ConnectionAsync) {
+    for id in [1, 2, 3] {
+        let mut foo = Foo::new(id);
+        foo.bar = id as u32;
+        foo.save(&conn).await.unwrap();
+    }
+
+    let mut found = Foo::get_many(&conn, &[3, 1, 7]).await.unwrap();
+    found.sort_by_key(|foo| foo.id);
+    assert_eq!(found.iter().map(|foo| foo.id).collect::<Vec<_>>(), [1, 3]);
+    assert!(Foo::get_many(&conn, &[] as &[i64])
+        .await
+        .unwrap()
+        .is_empty());
+
+    // More keys than fit in one query are looked up in chunks.
+    let ids: Vec<i64> = (0..2500).collect();
+    assert_eq!(Foo::get_many(&conn, &ids).await.unwrap().len(), 3);
+
+    let by_pk = Foo::get_many_map(&conn, &[2, 3]).await.unwrap();
+    assert_eq!(by_pk.len(), 2);
+    assert_eq!(by_pk[&2].bar, 2);
+}
+
+#[butane_test]
+async fn ping_connection(conn: ConnectionAsync) {
+    assert!(!conn.is_broken());
+    conn.ping().await.unwrap();
+}
+
+#[butane_test]
+async fn session_identity_map(conn: ConnectionAsync) {
+    let mut foo = Foo::new(1);
+    foo.save(&conn).await.unwrap();
+    for name in ["tarzan", "jane"] {
+        Bar::new(name, foo.clone()).save(&conn).await.unwrap();
+    }
+    let queries = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = queries.clone();
+    conn.set_query_logger(Some(QueryLogger::new(move |_| {
+        counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    })));
+    let count = || queries.load(std::sync::atomic::Ordering::SeqCst);
+
+    let session = Session::new(&conn);
+    let tarzan = Bar::get(&session, "tarzan".to_string()).await.unwrap();
+    let jane = Bar::get(&session, "jane".to_string()).await.unwrap();
+    assert_eq!(count(), 2);
+    // Both foreign keys are loaded with one query.
+    assert_eq!(tarzan.foo.load(&session).await.unwrap(), &foo);
+    assert_eq!(jane.foo.load(&session).await.unwrap(), &foo);
+    assert_eq!(count(), 3);
+    assert_eq!(Foo::get(&session, 1).await.unwrap(), foo);
+    assert_eq!(count(), 3);
+
+    // Saving through the session forgets the cached objects of its table.
+    foo.bar = 5;
+    foo.save(&session).await.unwrap();
+    let saved = count();
+    assert_eq!(Foo::get(&session, 1).await.unwrap().bar, 5);
+    assert_eq!(count(), saved + 1);
+    Bar::get(&session, "tarzan".to_string()).await.unwrap();
+    assert_eq!(count(), saved + 1);
+}
+
+#[cfg(feature = "moka")]
+#[butane_test]
+async fn cached_get(mut conn: ConnectionAsync) {
+    let cache = std::sync::Arc::new(butane::db::MokaCache::new(100));
+    conn.set_cache(Some(cache));
+    let mut foo = Foo::new(1);
+    foo.bar = 1;
+    foo.save(&conn).await.unwrap();
+    let queries = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = queries.clone();
+    conn.set_query_logger(Some(QueryLogger::new(move |info| {
+        if info.sql.starts_with("SELECT") {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    })));
+    let count = || queries.load(std::sync::atomic::Ordering::SeqCst);
+
+    assert_eq!(Foo::get(&conn, 1).await.unwrap(), foo);
+    assert_eq!(Foo::get(&conn, 1).await.unwrap(), foo);
+    assert_eq!(count(), 1);
+
+    // Saving invalidates the cached object.
+    foo.bar = 2;
+    foo.save(&conn).await.unwrap();
+    assert_eq!(Foo::get(&conn, 1).await.unwrap().bar, 2);
+    assert_eq!(count(), 2);
+
+    // Transactions do not read through the cache, but their writes
+    // invalidate it.
+    let tx = conn.transaction().await.unwrap();
+    assert_eq!(Foo::get(&tx, 1).await.unwrap().bar, 2);
+    assert_eq!(count(), 3);
+    foo.bar = 3;
+    foo.save(&tx).await.unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!(Foo::get(&conn, 1).await.unwrap().bar, 3);
+    assert_eq!(count(), 4);
+
+    // Deleting with a query invalidates the whole table.
+    query!(Foo, bar == 3).delete(&conn).await.unwrap();
+    assert!(Foo::try_get(&conn, 1).await.unwrap().is_none());
+}
+
+/// A row written in a transaction is invalidated again as it commits,
+/// as another connection sharing the cache may cache it as it was
+/// before in between.
+#[cfg(all(feature = "moka", feature = "sqlite"))]
+#[test]
+fn cached_get_across_transaction() {
+    use butane::db::{BackendConnection, ConnectionSpec};
+    use butane::DataObjectOpsSync;
+
+    let path = std::env::temp_dir().join(format!("butane-cache-{}.db", std::process::id()));
+    let spec = ConnectionSpec::new("sqlite", path.to_str().unwrap());
+    let cache = std::sync::Arc::new(butane::db::MokaCache::new(100));
+    let mut writer = butane::db::connect(&spec).unwrap();
+    setup_db(&mut writer);
+    writer.set_cache(Some(cache.clone()));
+    let mut reader = butane::db::connect(&spec).unwrap();
+    reader.set_cache(Some(cache));
+
+    let mut foo = Foo::new(1);
+    foo.bar = 1;
+    foo.save(&writer).unwrap();
+
+    let tx = writer.transaction().unwrap();
+    foo.bar = 2;
+    foo.save(&tx).unwrap();
+    // The reader sees, and caches, the row as last committed.
+    assert_eq!(Foo::get(&reader, 1).unwrap().bar, 1);
+    tx.commit().unwrap();
+    assert_eq!(Foo::get(&reader, 1).unwrap().bar, 2);
+
+    // Likewise for a transaction which is dropped, rolling it back.
+    let tx = writer.transaction().unwrap();
+    foo.bar = 3;
+    foo.save(&tx).unwrap();
+    assert_eq!(Foo::get(&reader, 1).unwrap().bar, 2);
+    drop(tx);
+    assert_eq!(Foo::get(&reader, 1).unwrap().bar, 2);
+    assert_eq!(Foo::get(&writer, 1).unwrap().bar, 2);
+    drop(writer);
+    drop(reader);
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_shared_writers() {
+    use butane::db::sqlite::SQLiteShared;
+    use butane::db::BackendConnection;
+    use butane::query::QueryOpsSync;
+    use butane::DataObjectOpsSync;
+
+    let path = std::env::temp_dir().join(format!("butane-shared-{}.db", std::process::id()));
+    let shared = SQLiteShared::open(path.to_str().unwrap()).unwrap();
+    setup_db(&mut shared.connection());
+
+    // Threads writing at once wait their turn rather than failing
+    // because the database is locked.
+    let threads: Vec<_> = (0..8)
+        .map(|t| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                let mut conn = shared.connection();
+                for i in 0..10 {
+                    let mut foo = Foo::new(t * 100 + i);
+                    foo.bar = foo.id as u32;
+                    foo.save(&conn).unwrap();
+                    assert_eq!(Foo::get(&conn, foo.id).unwrap(), foo);
+                }
+                let tx = conn.transaction().unwrap();
+                let mut foo = Foo::new(t * 100 + 99);
+                foo.bar = foo.id as u32;
+                foo.save(&tx).unwrap();
+                tx.commit().unwrap();
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let mut conn = shared.connection();
+    assert_eq!(query!(Foo, bar >= 0).load(&conn).unwrap().len(), 88);
+    let options = TransactionOptions {
+        read_only: true,
+        ..Default::default()
+    };
+    let tx = conn.transaction_with(options).unwrap();
+    assert_eq!(query!(Foo, bar >= 0).load(&tx).unwrap().len(), 88);
+    assert!(Foo::new(1000).save(&tx).is_err());
+    drop(tx);
+    drop(conn);
+    drop(shared);
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+}
@@ -0,0 +1,57 @@
+use butane::db::ConnectionAsync;
+use butane::{model, query, CiString};
+use butane_test_helper::*;
+use butane_test_macros::butane_test;
+
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct User {
+    id: i64,
+    #[unique]
+    email: CiString,
+}
+impl User {
+    fn new(id: i64, email: &str) -> Self {
+        User {
+            id,
+            email: email.into(),
+        }
+    }
+}
+
+#[butane_test]
+async fn citext_roundtrip(conn: ConnectionAsync) {
+    let mut user = User::new(1, "Alice@Example.com");
+    user.save(&conn).await.unwrap();
+
+    let user2 = User::get(&conn, 1).await.unwrap();
+    // The case is kept as written.
+    assert_eq!(user2.email.as_str(), "Alice@Example.com");
+    assert_eq!(user2.email, "alice@example.com");
+}
+
+#[butane_test]
+async fn citext_filters(conn: ConnectionAsync) {
+    User::new(1, "Alice@Example.com").save(&conn).await.unwrap();
+    User::new(2, "bob@example.com").save(&conn).await.unwrap();
+
+    let found = query!(User, email == "alice@EXAMPLE.com")
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, 1);
+    let found = query!(User, email != "BOB@example.com")
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, 1);
+}
+
+#[butane_test]
+async fn citext_unique(conn: ConnectionAsync) {
+    User::new(1, "alice@example.com").save(&conn).await.unwrap();
+    let mut shouting = User::new(2, "ALICE@EXAMPLE.COM");
+    assert!(shouting.save(&conn).await.is_err());
+}
@@ -0,0 +1,94 @@
+use butane::db::{Connection, ConnectionAsync};
+use butane::test_utils::{Factory, HasFactory};
+use butane::{model, AutoPk, ForeignKey, Many};
+use butane_test_helper::*;
+use butane_test_macros::butane_test;
+
+#[model]
+#[derive(Debug, Factory)]
+struct Author {
+    id: i64,
+    #[factory(default = "Anonymous".to_string())]
+    name: String,
+}
+
+#[model]
+#[derive(Debug, Factory)]
+struct Label {
+    #[pk]
+    #[factory(sequence)]
+    name: String,
+}
+
+#[model]
+#[derive(Debug, Factory)]
+struct Story {
+    id: AutoPk<i64>,
+    title: String,
+    published: bool,
+    author: ForeignKey<Author>,
+    editor: Option<ForeignKey<Author>>,
+    labels: Many<Label>,
+}
+
+#[butane_test]
+async fn create_with_defaults(conn: ConnectionAsync) {
+    use butane::test_utils::FactoryOpsAsync;
+
+    let author = AuthorFactory::new().create(&conn).await.unwrap();
+    assert_eq!(author.name, "Anonymous");
+    let other = Author::factory().create(&conn).await.unwrap();
+    assert_ne!(author.id, other.id);
+    assert_eq!(Author::query().load(&conn).await.unwrap().len(), 2);
+
+    let label = LabelFactory::new().create(&conn).await.unwrap();
+    let other = LabelFactory::new().create(&conn).await.unwrap();
+    assert_ne!(label.name, other.name);
+}
+
+#[butane_test]
+async fn create_with_overrides(conn: ConnectionAsync) {
+    use butane::test_utils::FactoryOpsAsync;
+
+    let story = StoryFactory::new()
+        .title("Factories")
+        .published(true)
+        .create(&conn)
+        .await
+        .unwrap();
+    let story = Story::get(&conn, story.id).await.unwrap();
+    assert_eq!(story.title, "Factories");
+    assert!(story.published);
+    assert!(story.editor.is_none());
+}
+
+#[butane_test]
+async fn create_parents(conn: ConnectionAsync) {
+    use butane::test_utils::FactoryOpsAsync;
+
+    // The required author is created, but not the optional editor.
+    let story = StoryFactory::new().create(&conn).await.unwrap();
+    let author = story.author.load(&conn).await.unwrap();
+    assert_eq!(author.name, "Anonymous");
+    assert_eq!(Author::query().load(&conn).await.unwrap().len(), 1);
+
+    // A given author is used instead.
+    let author = AuthorFactory::new()
+        .name("Ada")
+        .create(&conn)
+        .await
+        .unwrap();
+    let story = StoryFactory::new()
+        .author(&author)
+        .create(&conn)
+        .await
+        .unwrap();
+    assert_eq!(story.author.pk(), author.id);
+    assert_eq!(Author::query().load(&conn).await.unwrap().len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "foreign key author of StoryFactory not set")]
+fn build_without_parent() {
+    StoryFactory::new().build();
+}
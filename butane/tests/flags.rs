@@ -0,0 +1,88 @@
+use bitflags::bitflags;
+use butane::db::ConnectionAsync;
+use butane::{model, query, FlagSet};
+use butane_test_helper::*;
+use butane_test_macros::butane_test;
+
+bitflags! {
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    struct Perms: u32 {
+        const READ = 1;
+        const WRITE = 1 << 1;
+        const ADMIN = 1 << 31;
+    }
+}
+
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Account {
+    id: i64,
+    perms: FlagSet<Perms>,
+    extra: Option<FlagSet<Perms>>,
+}
+impl Account {
+    fn new(id: i64, perms: Perms) -> Self {
+        Account {
+            id,
+            perms: FlagSet(perms),
+            extra: None,
+        }
+    }
+}
+
+#[butane_test]
+async fn basic_flags(conn: ConnectionAsync) {
+    // create
+    let mut account = Account::new(1, Perms::READ | Perms::ADMIN);
+    account.save(&conn).await.unwrap();
+
+    // read
+    let mut account2 = Account::get(&conn, 1).await.unwrap();
+    assert_eq!(account, account2);
+    assert!(account2.perms.contains(Perms::ADMIN));
+
+    // update
+    account2.perms.insert(Perms::WRITE);
+    account2.extra = Some(Perms::empty().into());
+    account2.save(&conn).await.unwrap();
+    let account3 = Account::get(&conn, 1).await.unwrap();
+    assert_eq!(account2, account3);
+}
+
+#[butane_test]
+async fn flag_filters(conn: ConnectionAsync) {
+    let mut reader = Account::new(1, Perms::READ);
+    reader.save(&conn).await.unwrap();
+    let mut writer = Account::new(2, Perms::READ | Perms::WRITE);
+    writer.extra = Some(Perms::ADMIN.into());
+    writer.save(&conn).await.unwrap();
+    let mut admin = Account::new(3, Perms::ADMIN);
+    admin.save(&conn).await.unwrap();
+
+    let ids = |accounts: Vec<Account>| {
+        let mut ids: Vec<i64> = accounts.into_iter().map(|a| a.id).collect();
+        ids.sort();
+        ids
+    };
+
+    let found = query!(Account, perms.has_flag(Perms::READ))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(ids(found), vec![1, 2]);
+    let found = query!(Account, perms.has_flag(Perms::READ | Perms::WRITE))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(ids(found), vec![2]);
+    let found = query!(Account, perms.has_any(Perms::WRITE | Perms::ADMIN))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(ids(found), vec![2, 3]);
+    let found = query!(Account, extra.has_any(Perms::all()))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(ids(found), vec![2]);
+}
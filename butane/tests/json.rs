@@ -2,14 +2,14 @@
 
 use std::collections::{BTreeMap, HashMap};
 
-use butane::model;
 use butane::{
     db::{Connection, ConnectionAsync},
     FieldType,
 };
+use butane::{model, query, Json};
 use butane_test_helper::*;
 use butane_test_macros::butane_test;
-use serde_json::Value;
+use serde_json::{json, Value};
 
 #[model]
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -114,6 +114,32 @@ async fn basic_hashmap(conn: ConnectionAsync) {
     assert_eq!(foo2, foo3);
 }
 
+#[butane_test]
+async fn hashmap_key_equals(conn: ConnectionAsync) {
+    for (id, color) in [(1, "red"), (2, "blue")] {
+        let mut foo = FooHH::new(id);
+        foo.val.insert("color".to_string(), color.to_string());
+        foo.val.insert("size".to_string(), "large".to_string());
+        foo.save(&conn).await.unwrap();
+    }
+
+    let found = query!(FooHH, val.key_equals("color", "blue"))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found.iter().map(|foo| foo.id).collect::<Vec<_>>(), vec![2]);
+    let found = query!(FooHH, val.key_equals("size", "large"))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 2);
+    let found = query!(FooHH, val.key_equals("shape", "large"))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert!(found.is_empty());
+}
+
 #[model]
 #[derive(PartialEq, Eq, Debug, Clone)]
 struct FooFullPrefixHashMap {
@@ -278,3 +304,117 @@ async fn inline_json(conn: ConnectionAsync) {
     let foo3 = OuterFoo::get(&conn, id).await.unwrap();
     assert_eq!(foo2, foo3);
 }
+
+#[derive(PartialEq, Eq, Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct Profile {
+    name: String,
+    age: u32,
+    tags: Vec<String>,
+}
+
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Member {
+    #[pk]
+    id: i64,
+    profile: Json<Profile>,
+    extra: Option<Json<Vec<i64>>>,
+}
+impl Member {
+    fn new(id: i64, name: &str, age: u32, tags: &[&str]) -> Self {
+        Member {
+            id,
+            profile: Json(Profile {
+                name: name.to_string(),
+                age,
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+            }),
+            extra: None,
+        }
+    }
+}
+
+#[butane_test]
+async fn json_wrapper(conn: ConnectionAsync) {
+    let mut alice = Member::new(1, "Alice", 30, &["admin", "staff"]);
+    alice.extra = Some(Json(vec![1, 2]));
+    alice.save(&conn).await.unwrap();
+    let mut bob = Member::new(2, "Bob", 40, &["staff"]);
+    bob.save(&conn).await.unwrap();
+
+    let alice2 = Member::get(&conn, 1).await.unwrap();
+    assert_eq!(alice, alice2);
+    assert_eq!(alice2.profile.name, "Alice");
+    let bob2 = Member::get(&conn, 2).await.unwrap();
+    assert_eq!(bob, bob2);
+
+    bob.profile.age = 41;
+    bob.save(&conn).await.unwrap();
+    let bob3 = Member::get(&conn, 2).await.unwrap();
+    assert_eq!(bob3.profile.age, 41);
+}
+
+#[butane_test]
+async fn json_wrapper_filters(conn: ConnectionAsync) {
+    let mut alice = Member::new(1, "Alice", 30, &["admin", "staff"]);
+    alice.extra = Some(Json(vec![1, 2]));
+    alice.save(&conn).await.unwrap();
+    let mut bob = Member::new(2, "Bob", 40, &["staff"]);
+    bob.save(&conn).await.unwrap();
+
+    let ids = |members: Vec<Member>| members.into_iter().map(|m| m.id).collect::<Vec<_>>();
+
+    let found = query!(Member, profile.contains({ json!({ "tags": ["admin"] }) }))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(ids(found), vec![1]);
+    let mut found = query!(Member, profile.contains({ json!({ "tags": ["staff"] }) }))
+        .load(&conn)
+        .await
+        .unwrap();
+    found.sort_by_key(|m| m.id);
+    assert_eq!(ids(found), vec![1, 2]);
+    let found = query!(
+        Member,
+        profile.contains({ json!({ "name": "Bob", "age": 40 }) })
+    )
+    .load(&conn)
+    .await
+    .unwrap();
+    assert_eq!(ids(found), vec![2]);
+    let found = query!(Member, profile.contains({ json!({ "age": "40" }) }))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert!(found.is_empty());
+    let found = query!(Member, extra.contains({ [2] }))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(ids(found), vec![1]);
+
+    let found = query!(Member, profile.path_eq(["name"], "Alice"))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(ids(found), vec![1]);
+    let found = query!(Member, profile.path_eq(["tags", "0"], "staff"))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(ids(found), vec![2]);
+    let found = query!(Member, profile.path_eq(["tags"], { ["staff"] }))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(ids(found), vec![2]);
+    let found = query!(
+        Member,
+        profile.path_eq(["age"], 30) && extra.path_eq(["1"], 2)
+    )
+    .load(&conn)
+    .await
+    .unwrap();
+    assert_eq!(ids(found), vec![1]);
+}
@@ -0,0 +1,100 @@
+use butane::db::{Connection, ConnectionAsync};
+use butane::{model, query, AutoPk, LargeBlob};
+use butane_test_helper::*;
+use butane_test_macros::butane_test;
+use futures_util::TryStreamExt;
+
+#[model]
+#[derive(Debug)]
+struct Attachment {
+    id: AutoPk<i64>,
+    name: String,
+    content: LargeBlob,
+}
+impl Attachment {
+    fn new(name: &str, content: &[u8]) -> Self {
+        Attachment {
+            id: AutoPk::uninitialized(),
+            name: name.to_string(),
+            content: content.to_vec().into(),
+        }
+    }
+}
+
+#[butane_test]
+async fn large_blob_not_loaded(conn: ConnectionAsync) {
+    let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+    let mut attachment = Attachment::new("big", &data);
+    attachment.save(&conn).await.unwrap();
+
+    let mut attachment2 = Attachment::get(&conn, attachment.id).await.unwrap();
+    assert_eq!(attachment2.name, "big");
+    assert!(attachment2.content.get().is_err());
+    assert_eq!(attachment2.content.size(&conn).await.unwrap(), 10_000);
+    let range = attachment2
+        .content
+        .read_range(&conn, 9_990, 100)
+        .await
+        .unwrap();
+    assert_eq!(range, &data[9_990..]);
+    assert_eq!(attachment2.content.load(&conn).await.unwrap(), &data[..]);
+
+    let listed = query!(Attachment, name == "big").load(&conn).await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert!(listed[0].content.get().is_err());
+}
+
+#[butane_test]
+async fn large_blob_set(conn: ConnectionAsync) {
+    let mut attachment = Attachment::new("small", b"first");
+    attachment.save(&conn).await.unwrap();
+
+    // Saving without setting the blob leaves it alone
+    let mut attachment2 = Attachment::get(&conn, attachment.id).await.unwrap();
+    attachment2.name = "renamed".to_string();
+    attachment2.save(&conn).await.unwrap();
+    let mut attachment3 = Attachment::get(&conn, attachment.id).await.unwrap();
+    assert_eq!(attachment3.name, "renamed");
+    assert_eq!(attachment3.content.load(&conn).await.unwrap(), b"first");
+
+    attachment3.content.set(b"second".to_vec());
+    attachment3.save(&conn).await.unwrap();
+    let mut attachment4 = Attachment::get(&conn, attachment.id).await.unwrap();
+    assert_eq!(attachment4.content.load(&conn).await.unwrap(), b"second");
+}
+
+#[butane_test(async)]
+async fn large_blob_chunks(conn: ConnectionAsync) {
+    let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+    let mut attachment = Attachment::new("chunked", &data);
+    attachment.save(&conn).await.unwrap();
+
+    let attachment2 = Attachment::get(&conn, attachment.id).await.unwrap();
+    let chunks: Vec<Vec<u8>> = attachment2
+        .content
+        .stream(&conn, 300)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![300, 300, 300, 100]
+    );
+    assert_eq!(chunks.concat(), data);
+}
+
+#[butane_test(sync)]
+fn large_blob_chunk_iter(conn: Connection) {
+    let data: Vec<u8> = (0..=255).cycle().take(600).collect();
+    let mut attachment = Attachment::new("chunked", &data);
+    attachment.save(&conn).unwrap();
+
+    let attachment2 = Attachment::get(&conn, attachment.id).unwrap();
+    let chunks: Vec<Vec<u8>> = attachment2
+        .content
+        .chunks(&conn, 300)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks.concat(), data);
+}
@@ -1,4 +1,4 @@
-use butane::{model, query::OrderDirection, AutoPk, Many};
+use butane::{model, query, query::OrderDirection, AutoPk, Many};
 use butane_test_helper::*;
 use butane_test_macros::butane_test;
 
@@ -38,12 +38,46 @@ fn new() -> Self {
     }
 }
 
+#[model]
+struct Article {
+    id: AutoPk<i64>,
+    #[many_table(name = "article_tags", owner = "article_id", has = "tag_id")]
+    tags: Many<Tag>,
+    #[many_table = "article_items"]
+    items: Many<AutoItem>,
+}
+impl Article {
+    fn new() -> Self {
+        Article {
+            id: AutoPk::uninitialized(),
+            tags: Many::default(),
+            items: Many::default(),
+        }
+    }
+}
+
 #[model]
 struct AutoItem {
     id: AutoPk<i64>,
     val: String,
 }
 
+#[model]
+struct Follower {
+    id: AutoPk<i64>,
+    name: String,
+    follows: Many<Self>,
+}
+impl Follower {
+    fn new(name: &str) -> Self {
+        Follower {
+            id: AutoPk::uninitialized(),
+            name: name.to_string(),
+            follows: Many::default(),
+        }
+    }
+}
+
 #[butane_test]
 async fn load_sorted_from_many(conn: ConnectionAsync) {
     let mut cats_blog = Blog::new(1, "Cats");
@@ -207,3 +241,138 @@ async fn can_add_to_many_with_custom_table_name(conn: ConnectionAsync) {
     let tags = obj.tags.load(&conn).await.unwrap();
     assert_eq!(tags.count(), 2);
 }
+
+#[butane_test]
+async fn self_referential_many(conn: ConnectionAsync) {
+    let mut alice = Follower::new("alice");
+    alice.save(&conn).await.unwrap();
+    let mut bob = Follower::new("bob");
+    bob.save(&conn).await.unwrap();
+    let mut carol = Follower::new("carol");
+    carol.follows.add(&alice).unwrap();
+    carol.follows.add(&bob).unwrap();
+    carol.save(&conn).await.unwrap();
+    bob.follows.add(&carol).unwrap();
+    bob.save(&conn).await.unwrap();
+
+    let carol = Follower::get(&conn, carol.id).await.unwrap();
+    let mut names: Vec<String> = carol
+        .follows
+        .load(&conn)
+        .await
+        .unwrap()
+        .map(|f| f.name.clone())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["alice", "bob"]);
+
+    let bob = Follower::get(&conn, bob.id).await.unwrap();
+    let follows: Vec<&Follower> = bob.follows.load(&conn).await.unwrap().collect();
+    assert_eq!(follows.len(), 1);
+    assert_eq!(follows[0].name, "carol");
+    assert_eq!(alice.follows.load(&conn).await.unwrap().count(), 0);
+}
+
+#[butane_test]
+async fn many_with_custom_table_and_column_names(conn: ConnectionAsync) {
+    let mut obj = Article::new();
+    obj.tags.add(&create_tag(&conn, "blue").await).unwrap();
+    obj.tags.add(&create_tag(&conn, "red").await).unwrap();
+    let mut item = AutoItem {
+        id: AutoPk::uninitialized(),
+        val: "shiny".to_string(),
+    };
+    item.save(&conn).await.unwrap();
+    obj.items.add(&item).unwrap();
+    obj.save(&conn).await.unwrap();
+
+    conn.execute("SELECT article_id, tag_id FROM article_tags")
+        .await
+        .unwrap();
+    conn.execute("SELECT owner, has FROM article_items")
+        .await
+        .unwrap();
+
+    let obj = Article::get(&conn, obj.id).await.unwrap();
+    assert_eq!(obj.tags.load(&conn).await.unwrap().count(), 2);
+    assert_eq!(obj.items.load(&conn).await.unwrap().count(), 1);
+
+    let found = query!(Article, tags.contains("red"))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+}
+
+#[butane_test]
+async fn load_many_in_pages(conn: ConnectionAsync) {
+    let mut obj = AutoPkWithMany::new();
+    for name in ["a", "b", "c", "d", "e"] {
+        obj.tags.add(&create_tag(&conn, name).await).unwrap();
+    }
+    obj.save(&conn).await.unwrap();
+    let obj = AutoPkWithMany::get(&conn, obj.id).await.unwrap();
+
+    let page = obj.tags.load_page(&conn, None, 2).await.unwrap();
+    let tags: Vec<&str> = page.iter().map(|t| t.tag.as_str()).collect();
+    assert_eq!(tags, vec!["a", "b"]);
+
+    let after = page.last().map(|t| t.tag.clone());
+    let page = obj.tags.load_page(&conn, after.as_ref(), 2).await.unwrap();
+    let tags: Vec<&str> = page.iter().map(|t| t.tag.as_str()).collect();
+    assert_eq!(tags, vec!["c", "d"]);
+
+    let after = page.last().map(|t| t.tag.clone());
+    let page = obj.tags.load_page(&conn, after.as_ref(), 2).await.unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].tag, "e");
+
+    // Paging never populates the cache used by `load`.
+    assert!(obj.tags.get().is_err());
+}
+
+#[test]
+fn iterate_many_pages() {
+    use butane::DataObjectOpsSync;
+
+    let mut conn = sqlite_connection();
+    setup_db(&mut conn);
+    let mut obj = AutoPkWithMany::new();
+    for name in ["a", "b", "c", "d", "e"] {
+        let mut tag = Tag::new(name);
+        tag.save(&conn).unwrap();
+        obj.tags.add(&tag).unwrap();
+    }
+    obj.save(&conn).unwrap();
+    let obj = AutoPkWithMany::get(&conn, obj.id).unwrap();
+
+    let pages: Vec<Vec<String>> = obj
+        .tags
+        .pages(&conn, 2)
+        .map(|page| page.unwrap().into_iter().map(|t| t.tag).collect())
+        .collect();
+    assert_eq!(pages, vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+}
+
+#[tokio::test]
+async fn stream_many_pages() {
+    use butane::DataObjectOpsAsync;
+    use futures_util::StreamExt;
+
+    let mut conn = butane::db::connect_async(&sqlite_connspec()).await.unwrap();
+    setup_db_async(&mut conn).await;
+    let mut obj = AutoPkWithMany::new();
+    for name in ["a", "b", "c"] {
+        obj.tags.add(&create_tag(&conn, name).await).unwrap();
+    }
+    obj.save(&conn).await.unwrap();
+    let obj = AutoPkWithMany::get(&conn, obj.id).await.unwrap();
+
+    let pages: Vec<usize> = obj
+        .tags
+        .stream(&conn, 2)
+        .map(|page| page.unwrap().len())
+        .collect()
+        .await;
+    assert_eq!(pages, vec![2, 1]);
+}
@@ -6,6 +6,8 @@
 use r2d2;
 use std::ops::DerefMut;
 
+mod common;
+
 #[cfg(feature = "sqlite")]
 #[test]
 fn r2d2_sqlite() {
@@ -64,3 +66,52 @@ async fn deadpool_test_pg_async() {
     assert_eq!(pool.status().size, 1);
     assert_eq!(pool.status().available, 1);
 }
+
+#[tokio::test]
+async fn deadpool_keepalive() {
+    let (connspec, _data) = pg_connspec().await;
+    let manager = ConnectionManager::new(connspec);
+    let pool = deadpool::managed::Pool::builder(manager).build().unwrap();
+    {
+        let mut conn1: deadpool::managed::Object<ConnectionManager> = pool.get().await.unwrap();
+        let _conn2 = pool.get().await.unwrap();
+        setup_db_async(conn1.deref_mut()).await;
+    }
+    assert_eq!(pool.status().available, 2);
+
+    let keepalive = butane::db::spawn_keepalive(&pool, std::time::Duration::from_millis(10));
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    // The idle connections are pinged without creating more.
+    assert_eq!(pool.status().size, 2);
+    assert_eq!(pool.status().available, 2);
+    // Each check out recycles the connection, which pings it.
+    let conn = pool.get().await.unwrap();
+    assert!(deadpool::managed::Object::metrics(&conn).recycle_count > 1);
+    drop(conn);
+
+    pool.close();
+    keepalive.await.unwrap();
+}
+
+#[tokio::test]
+async fn deadpool_try_join_queries() {
+    use butane::query;
+    use butane::DataResult;
+    use common::blog::{self, Blog, Post};
+
+    let (connspec, _data) = pg_connspec().await;
+    let manager = ConnectionManager::new(connspec);
+    let pool = deadpool::managed::Pool::builder(manager).build().unwrap();
+    {
+        let mut conn: deadpool::managed::Object<ConnectionManager> = pool.get().await.unwrap();
+        setup_db_async(conn.deref_mut()).await;
+        blog::setup_blog(&conn).await;
+    }
+    let (posts, blogs) =
+        butane::try_join_queries!(pool, query!(Post, published == true), Blog::query(),).unwrap();
+    assert_eq!(posts.len(), 3);
+    assert_eq!(blogs.len(), 2);
+    // Each query checked out a connection of its own.
+    assert_eq!(pool.status().size, 2);
+    assert_eq!(pool.status().available, 2);
+}
@@ -1,6 +1,8 @@
-use butane::db::{Connection, ConnectionAsync};
-use butane::query::BoolExpr;
-use butane::{colname, filter, find, find_async, query, Many};
+use butane::db::{
+    BackendRows, Column, Connection, ConnectionAsync, ConnectionMethodsAsync, QueryLimits,
+};
+use butane::query::{BoolExpr, Expr};
+use butane::{colname, filter, find, find_async, query, Many, SqlType};
 use butane_test_helper::*;
 use butane_test_macros::butane_test;
 #[cfg(feature = "datetime")]
@@ -277,3 +279,158 @@ async fn offset(conn: ConnectionAsync) {
     assert_eq!(posts[0].title, "Sir Charles");
     assert_eq!(posts[1].title, "The Tiger");
 }
+
+#[butane_test(async)]
+async fn stream(conn: ConnectionAsync) {
+    use futures_util::TryStreamExt;
+    blog::setup_blog(&conn).await;
+    let posts: Vec<Post> = query!(Post, published == true)
+        .order_asc(colname!(Post, title))
+        .stream(&conn)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(posts.len(), 3);
+    assert_eq!(posts[0].title, "Mount Doom");
+    assert_eq!(posts[1].title, "Sir Charles");
+    assert_eq!(posts[2].title, "The Tiger");
+}
+
+#[butane_test(async)]
+async fn stream_many_rows(mut conn: ConnectionAsync) {
+    use futures_util::TryStreamExt;
+    // More rows than Postgres fetches from the cursor at a time.
+    let count = 2500;
+    let tr = conn.transaction().await.unwrap();
+    for i in 0..count {
+        Tag::new(&format!("tag{i:04}")).save(&tr).await.unwrap();
+    }
+    tr.commit().await.unwrap();
+
+    let mut stream = Box::pin(Tag::query().order_asc(colname!(Tag, tag)).stream(&conn));
+    let mut seen = 0;
+    while let Some(tag) = stream.try_next().await.unwrap() {
+        assert_eq!(tag.tag, format!("tag{seen:04}"));
+        seen += 1;
+    }
+    assert_eq!(seen, count);
+    drop(stream);
+
+    // Within a transaction, the cursor is not held past it.
+    let tr = conn.transaction().await.unwrap();
+    let tags: Vec<Tag> = query!(Tag, tag.like("tag1%"))
+        .stream(&tr)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(tags.len(), 1000);
+    tr.commit().await.unwrap();
+}
+
+#[butane_test]
+async fn max_rows(conn: ConnectionAsync) {
+    blog::setup_blog(&conn).await;
+    conn.set_query_limits(QueryLimits {
+        max_rows: Some(2),
+        ..QueryLimits::default()
+    });
+    let err = query!(Post, published == true)
+        .load(&conn)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, butane::Error::TooManyRows(2)), "{err:?}");
+    // Queries within the limit are unaffected.
+    let posts = query!(Post, published == true)
+        .limit(2)
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+}
+
+#[butane_test(async)]
+async fn stream_fetch_size(conn: ConnectionAsync) {
+    use futures_util::TryStreamExt;
+    blog::setup_blog(&conn).await;
+    conn.set_query_limits(QueryLimits {
+        fetch_size: 2,
+        ..QueryLimits::default()
+    });
+    let posts: Vec<Post> = query!(Post, published == true)
+        .order_asc(colname!(Post, title))
+        .stream(&conn)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(posts.len(), 3);
+    assert_eq!(posts[2].title, "The Tiger");
+}
+
+/// The number of cursors butane has open in this Postgres session.
+async fn open_cursor_count(conn: &impl ConnectionMethodsAsync) -> usize {
+    let columns = [Column::new("name", SqlType::Text)];
+    let expr = BoolExpr::Like("name", Expr::Val("butane_cursor%".into()));
+    let mut rows = conn
+        .query("pg_cursors", &columns, Some(expr), None, None, None)
+        .await
+        .unwrap();
+    let mut count = 0;
+    while rows.next().unwrap().is_some() {
+        count += 1;
+    }
+    count
+}
+
+#[butane_test(async, pg)]
+async fn stream_dropped_early(mut conn: ConnectionAsync) {
+    use futures_util::TryStreamExt;
+    blog::setup_blog(&conn).await;
+    conn.set_query_limits(QueryLimits {
+        fetch_size: 1,
+        ..QueryLimits::default()
+    });
+    let mut stream = Box::pin(query!(Post, published == true).stream(&conn));
+    assert!(stream.try_next().await.unwrap().is_some());
+    drop(stream);
+    assert_eq!(open_cursor_count(&conn).await, 0);
+
+    // Within a transaction, the cursor is closed before it ends too.
+    let tr = conn.transaction().await.unwrap();
+    let mut stream = Box::pin(query!(Post, published == true).stream(&tr));
+    assert!(stream.try_next().await.unwrap().is_some());
+    drop(stream);
+    assert_eq!(open_cursor_count(&tr).await, 0);
+    tr.commit().await.unwrap();
+}
+
+#[butane_test(async)]
+async fn load_with_timeout(conn: ConnectionAsync) {
+    use std::time::Duration;
+    blog::setup_blog(&conn).await;
+    let posts = query!(Post, published == true)
+        .load_with_timeout(&conn, Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(posts.len(), 3);
+
+    // The query waits behind a slow statement until it times out, which
+    // cancels the slow statement running on the connection.
+    let slow = match conn.backend_name() {
+        "pg" => "SELECT pg_sleep(60);",
+        _ => {
+            "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 10000000000) \
+             SELECT count(*) FROM c;"
+        }
+    };
+    let (slow, loaded) = futures_util::join!(
+        conn.execute(slow),
+        query!(Post, published == true).load_with_timeout(&conn, Duration::from_millis(100))
+    );
+    assert!(slow.is_err());
+    let err = loaded.unwrap_err();
+    assert!(matches!(err, butane::Error::Timeout(_)), "{err:?}");
+
+    // The connection can be used afterwards.
+    let posts = query!(Post, published == true).load(&conn).await.unwrap();
+    assert_eq!(posts.len(), 3);
+}
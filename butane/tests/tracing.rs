@@ -0,0 +1,156 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use butane::db::ConnectionAsync;
+use butane::model;
+use butane_test_helper::*;
+use butane_test_macros::butane_test;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_core::span::Current;
+
+#[model]
+#[derive(Debug, Clone)]
+struct Traced {
+    id: i64,
+    label: String,
+}
+
+#[derive(Debug)]
+struct RecordedSpan {
+    name: &'static str,
+    metadata: &'static Metadata<'static>,
+    parent: Option<u64>,
+    fields: HashMap<&'static str, String>,
+}
+
+impl Visit for RecordedSpan {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.fields.insert(field.name(), format!("{value:?}"));
+    }
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name(), value.to_string());
+    }
+}
+
+thread_local! {
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records the spans created on the threads it is the default for.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<RecordedSpan>>>);
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let parent = match attrs.parent() {
+            Some(parent) => Some(parent.into_u64()),
+            None if attrs.is_contextual() => ENTERED.with(|e| e.borrow().last().copied()),
+            None => None,
+        };
+        let mut span = RecordedSpan {
+            name: attrs.metadata().name(),
+            metadata: attrs.metadata(),
+            parent,
+            fields: HashMap::new(),
+        };
+        attrs.record(&mut span);
+        let mut spans = self.0.lock().unwrap();
+        spans.push(span);
+        Id::from_u64(spans.len() as u64)
+    }
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.0.lock().unwrap();
+        values.record(&mut spans[span.into_u64() as usize - 1]);
+    }
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+    fn event(&self, _event: &Event<'_>) {}
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|e| e.borrow_mut().push(span.into_u64()));
+    }
+    fn exit(&self, _span: &Id) {
+        ENTERED.with(|e| e.borrow_mut().pop());
+    }
+    fn current_span(&self) -> Current {
+        match ENTERED.with(|e| e.borrow().last().copied()) {
+            Some(id) => Current::new(
+                Id::from_u64(id),
+                self.0.lock().unwrap()[id as usize - 1].metadata,
+            ),
+            None => Current::none(),
+        }
+    }
+}
+
+#[butane_test]
+async fn operation_spans(mut conn: ConnectionAsync) {
+    let recorder = Recorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let mut traced = Traced {
+        id: 1,
+        label: "hello".to_string(),
+    };
+    traced.save(&conn).await.unwrap();
+    Traced::get(&conn, 1).await.unwrap();
+    let tx = conn.transaction().await.unwrap();
+    traced.delete(&tx).await.unwrap();
+    tx.commit().await.unwrap();
+
+    let recorded = recorder.0.lock().unwrap();
+    let find = |name: &str| recorded.iter().position(|s| s.name == name).unwrap();
+
+    let save = &recorded[find("butane.save")];
+    assert_eq!(save.fields["table"], "Traced");
+    assert!(save.fields["db.statement"].contains("Traced"));
+    assert!(save.fields.contains_key("duration_ms"));
+
+    let query = &recorded[find("butane.query")];
+    assert_eq!(query.fields["table"], "Traced");
+    assert!(query.fields["db.statement"].starts_with("SELECT"));
+
+    // Statements in a transaction are within its span.
+    let transaction = find("butane.transaction");
+    assert!(recorded[transaction].fields.contains_key("duration_ms"));
+    let delete = &recorded[find("butane.delete")];
+    assert_eq!(delete.parent, Some(transaction as u64 + 1));
+    assert!(delete.fields["db.statement"].starts_with("DELETE"));
+}
+
+#[cfg(feature = "otel")]
+#[butane_test]
+async fn otel_attributes(conn: ConnectionAsync) {
+    let recorder = Recorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let mut traced = Traced {
+        id: 1,
+        label: "hello".to_string(),
+    };
+    traced.save(&conn).await.unwrap();
+    Traced::get(&conn, 1).await.unwrap();
+    traced.delete(&conn).await.unwrap();
+    conn.execute("SELECT 1").await.unwrap();
+
+    let recorded = recorder.0.lock().unwrap();
+    let system = match conn.backend_name() {
+        "pg" => "postgresql",
+        name => name,
+    };
+    for (name, operation) in [
+        ("butane.save", "INSERT"),
+        ("butane.query", "SELECT"),
+        ("butane.delete", "DELETE"),
+        ("butane.execute", "SELECT"),
+    ] {
+        let span = recorded.iter().find(|s| s.name == name).unwrap();
+        assert_eq!(span.fields["db.system"], system);
+        assert_eq!(span.fields["db.operation"], operation);
+        assert!(!span.fields["db.name"].is_empty());
+    }
+}
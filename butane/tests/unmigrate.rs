@@ -2,7 +2,7 @@
 //! all of the other tests in the butane/tests directory.
 #![cfg(test)]
 use butane::db::{Connection, ConnectionAsync};
-use butane::migrations::{Migration, Migrations};
+use butane::migrations::{Migration, Migrations, SchemaDrift};
 use butane_test_helper::*;
 use butane_test_macros::*;
 
@@ -39,3 +39,27 @@ fn unmigrate_sync(mut conn: Connection) {
     let migrations = mem_migrations.unapplied_migrations(&conn).unwrap();
     assert_eq!(migrations.len(), 1);
 }
+
+#[butane_test(sync)]
+fn no_schema_drift_sync(conn: Connection) {
+    let mem_migrations = create_current_migrations(conn.backend());
+
+    let drift = mem_migrations.check_drift(&conn).unwrap();
+    assert!(drift.is_empty(), "{drift:#?}");
+
+    conn.execute("CREATE TABLE drifted (id INTEGER NOT NULL PRIMARY KEY);")
+        .unwrap();
+    let drift = mem_migrations.check_drift(&conn).unwrap();
+    assert_eq!(drift, vec![SchemaDrift::ExtraTable("drifted".to_string())]);
+}
+
+#[butane_test(async)]
+async fn no_schema_drift_async(mut connection: ConnectionAsync) {
+    let mem_migrations = create_current_migrations(connection.backend());
+
+    let drift = mem_migrations
+        .check_drift_async(&mut connection)
+        .await
+        .unwrap();
+    assert!(drift.is_empty(), "{drift:#?}");
+}
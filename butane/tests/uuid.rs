@@ -10,6 +10,13 @@ struct FooUU {
     id: Uuid,
     bar: u32,
 }
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct UuidList {
+    id: i32,
+    members: Vec<Uuid>,
+}
+
 impl FooUU {
     fn new(id: Uuid) -> Self {
         FooUU { id, bar: 0 }
@@ -35,3 +42,25 @@ async fn basic_uuid(conn: ConnectionAsync) {
     let foo3 = FooUU::get(&conn, id).await.unwrap();
     assert_eq!(foo2, foo3);
 }
+
+#[butane_test]
+async fn uuid_array(conn: ConnectionAsync) {
+    let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+    let mut list = UuidList {
+        id: 1,
+        members: vec![a, b],
+    };
+    list.save(&conn).await.unwrap();
+    assert_eq!(UuidList::get(&conn, 1).await.unwrap(), list);
+
+    let found = butane::query!(UuidList, members.contains({ b }))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert_eq!(found, vec![list]);
+    let found = butane::query!(UuidList, members.contains({ Uuid::new_v4() }))
+        .load(&conn)
+        .await
+        .unwrap();
+    assert!(found.is_empty());
+}
@@ -0,0 +1,153 @@
+//! Entity-relationship diagrams of a schema.
+
+use std::fmt::Write;
+
+use butane::migrations::adb::{AColumn, ARef, ARefLiteral, ATable, TypeIdentifier, ADB};
+
+use crate::introspect::many_table;
+
+/// The language of a diagram made by [`schema_graph`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT, rendered with `dot -Tsvg`.
+    #[default]
+    Dot,
+    /// A Mermaid `erDiagram`, which Markdown renderers such as GitHub's draw.
+    Mermaid,
+}
+
+/// Draw the tables of `db` with their columns, an edge for each foreign
+/// key, and an edge for each many-to-many relationship in place of the
+/// table holding it.
+pub fn schema_graph(db: &ADB, format: GraphFormat) -> String {
+    let mut tables: Vec<&ATable> = Vec::new();
+    let mut many = Vec::new();
+    for table in db.tables() {
+        match many_table(db, table) {
+            Some(m) => many.push(m),
+            None => tables.push(table),
+        }
+    }
+    let mut out = String::new();
+    match format {
+        GraphFormat::Dot => {
+            out.push_str("digraph schema {\n  rankdir=LR;\n  node [shape=plaintext];\n");
+            for table in &tables {
+                write!(
+                    out,
+                    "  \"{0}\" [label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"><tr><td bgcolor=\"lightgrey\"><b>{0}</b></td></tr>",
+                    html_escape(&table.name)
+                )
+                .unwrap();
+                for column in &table.columns {
+                    write!(
+                        out,
+                        "<tr><td align=\"left\" port=\"{0}\">{0}: {1}{2}</td></tr>",
+                        html_escape(column.name()),
+                        html_escape(&type_name(column)),
+                        match (column.is_pk(), column.reference().is_some()) {
+                            (true, _) => " (PK)",
+                            (false, true) => " (FK)",
+                            (false, false) => "",
+                        }
+                    )
+                    .unwrap();
+                }
+                out.push_str("</table>>];\n");
+            }
+            for table in &tables {
+                for (column, reference) in references(table) {
+                    writeln!(
+                        out,
+                        "  \"{}\":\"{}\" -> \"{}\":\"{}\";",
+                        table.name,
+                        column.name(),
+                        reference.table_name(),
+                        reference.column_name()
+                    )
+                    .unwrap();
+                }
+            }
+            for m in &many {
+                writeln!(
+                    out,
+                    "  \"{}\" -> \"{}\" [label=\"{}\", dir=both, arrowhead=crow, arrowtail=crow];",
+                    m.owner, m.has, m.field
+                )
+                .unwrap();
+            }
+            out.push_str("}\n");
+        }
+        GraphFormat::Mermaid => {
+            out.push_str("erDiagram\n");
+            for table in &tables {
+                writeln!(out, "    {} {{", table.name).unwrap();
+                for column in &table.columns {
+                    let key = match (column.is_pk(), column.reference().is_some()) {
+                        (true, _) => " PK",
+                        (false, true) => " FK",
+                        (false, false) => "",
+                    };
+                    writeln!(
+                        out,
+                        "        {} {}{key}",
+                        mermaid_word(&type_name(column)),
+                        column.name()
+                    )
+                    .unwrap();
+                }
+                out.push_str("    }\n");
+            }
+            for table in &tables {
+                for (column, reference) in references(table) {
+                    let one = if column.nullable() { "o|" } else { "||" };
+                    writeln!(
+                        out,
+                        "    {} }}o--{one} {} : \"{}\"",
+                        table.name,
+                        reference.table_name(),
+                        column.name()
+                    )
+                    .unwrap();
+                }
+            }
+            for m in &many {
+                writeln!(out, "    {} }}o--o{{ {} : \"{}\"", m.owner, m.has, m.field).unwrap();
+            }
+        }
+    }
+    out
+}
+
+/// The columns of `table` which refer to other tables, with what they refer to.
+fn references(table: &ATable) -> Vec<(&AColumn, &ARefLiteral)> {
+    table
+        .columns
+        .iter()
+        .filter_map(|column| match column.reference() {
+            Some(ARef::Literal(reference)) => Some((column, reference)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn type_name(column: &AColumn) -> String {
+    match column.typeid() {
+        Ok(TypeIdentifier::Ty(ty)) => format!("{ty:?}"),
+        Ok(TypeIdentifier::Name(name)) => name,
+        Err(_) => "unknown".to_string(),
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Mermaid attribute types are single words.
+fn mermaid_word(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
@@ -0,0 +1,316 @@
+//! Generation of model definitions from a database schema.
+
+use std::fmt::Write;
+
+use butane::migrations::adb::{AColumn, ARef, ATable, TypeIdentifier, ADB, MANY_SUFFIX};
+use butane::SqlType;
+
+/// How the names of model structs are made from the names of tables.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum StructNaming {
+    /// `blog_post` becomes `BlogPost`.
+    #[default]
+    Pascal,
+    /// Like `pascal`, but a plural table name is made singular, so
+    /// `blog_posts` becomes `BlogPost`.
+    Singular,
+    /// The struct has the name of the table.
+    Preserve,
+}
+
+impl StructNaming {
+    fn struct_name(self, table_name: &str) -> String {
+        match self {
+            StructNaming::Pascal => pascal_case(table_name),
+            StructNaming::Singular => pascal_case(&singular(table_name)),
+            StructNaming::Preserve => table_name.to_string(),
+        }
+    }
+}
+
+/// Generate `#[model]` struct definitions for the tables in `db`.
+///
+/// Tables which look like the join table of a `Many` field (an
+/// `owner` and a `has` column, named `<table>_<field>_Many`) become
+/// `Many` fields of the owning model rather than models of their own.
+/// Tables without a primary key cannot be models and are reported in a
+/// comment.
+pub fn models_source(db: &ADB, naming: StructNaming) -> String {
+    let many_tables: Vec<ManyTable> = db.tables().filter_map(|t| many_table(db, t)).collect();
+    let mut src = String::new();
+    for table in db.tables() {
+        if many_tables.iter().any(|m| m.table == table.name) {
+            continue;
+        }
+        src.push('\n');
+        if table.pk().is_none() {
+            no_pk_comment(table, &mut src);
+            continue;
+        }
+        model_source(table, &many_tables, naming, &mut src);
+    }
+    header("Models", &src, &[]) + &src
+}
+
+/// Generate a module of models for the tables in `db` as in
+/// [`models_source`], returning the names and contents of its files:
+/// one per model, named after the model in snake case, and `mod.rs`
+/// declaring them.
+pub fn models_module(db: &ADB, naming: StructNaming) -> Vec<(String, String)> {
+    let many_tables: Vec<ManyTable> = db.tables().filter_map(|t| many_table(db, t)).collect();
+    let mut files = Vec::new();
+    let mut mod_src = String::from("//! Models generated by `butane introspect`.\n\n");
+    for table in db.tables() {
+        if many_tables.iter().any(|m| m.table == table.name) {
+            continue;
+        }
+        if table.pk().is_none() {
+            no_pk_comment(table, &mut mod_src);
+            continue;
+        }
+        let name = naming.struct_name(&table.name);
+        let module = snake_case(&name);
+        writeln!(mod_src, "mod {module};\npub use {module}::{name};").unwrap();
+
+        let mut src = String::from("\n");
+        model_source(table, &many_tables, naming, &mut src);
+        let mut models: Vec<String> = table
+            .columns
+            .iter()
+            .filter_map(|column| match column.reference() {
+                Some(ARef::Literal(reference)) => Some(naming.struct_name(reference.table_name())),
+                _ => None,
+            })
+            .chain(
+                many_tables
+                    .iter()
+                    .filter(|m| m.owner == table.name)
+                    .map(|m| naming.struct_name(&m.has)),
+            )
+            .filter(|model| *model != name)
+            .collect();
+        models.sort();
+        models.dedup();
+        let src = header("Model", &src, &models) + &src;
+        files.push((format!("{module}.rs"), src));
+    }
+    files.push(("mod.rs".to_string(), mod_src));
+    files
+}
+
+/// The doc comment and imports of a file of models, which use the
+/// `models` of other files in the module.
+fn header(what: &str, src: &str, models: &[String]) -> String {
+    let imports: Vec<&str> = ["AutoPk", "ForeignKey", "Many"]
+        .into_iter()
+        .filter(|name| src.contains(&format!("{name}<")))
+        .collect();
+    let mut header = format!("//! {what} generated by `butane introspect`.\n\n");
+    if imports.is_empty() {
+        header.push_str("use butane::model;\n");
+    } else {
+        writeln!(header, "use butane::{{model, {}}};", imports.join(", ")).unwrap();
+    }
+    match models {
+        [] => {}
+        [model] => writeln!(header, "\nuse super::{model};").unwrap(),
+        _ => writeln!(header, "\nuse super::{{{}}};", models.join(", ")).unwrap(),
+    }
+    header
+}
+
+fn no_pk_comment(table: &ATable, src: &mut String) {
+    writeln!(
+        src,
+        "// Table {} has no primary key and cannot be a model.",
+        table.name
+    )
+    .unwrap();
+}
+
+fn model_source(table: &ATable, many_tables: &[ManyTable], naming: StructNaming, src: &mut String) {
+    let name = naming.struct_name(&table.name);
+    src.push_str("#[model]\n");
+    if name != table.name {
+        writeln!(src, "#[table = \"{}\"]", table.name).unwrap();
+    }
+    src.push_str("#[derive(Debug)]\n");
+    writeln!(src, "pub struct {name} {{").unwrap();
+    for column in &table.columns {
+        if column.is_pk() && column.name() != "id" {
+            src.push_str("    #[pk]\n");
+        }
+        if column.unique() && !column.is_pk() {
+            src.push_str("    #[unique]\n");
+        }
+        let ty = match field_type(column, naming) {
+            Ok(ty) => ty,
+            Err(name) => {
+                writeln!(
+                    src,
+                    "    // Column type {name} is not known to butane; adjust as needed."
+                )
+                .unwrap();
+                "String".to_string()
+            }
+        };
+        writeln!(src, "    pub {}: {ty},", column.name()).unwrap();
+    }
+    for many in many_tables.iter().filter(|m| m.owner == table.name) {
+        let default_name = format!("{}_{}{MANY_SUFFIX}", table.name, many.field);
+        if many.table != default_name {
+            writeln!(src, "    #[many_table = \"{}\"]", many.table).unwrap();
+        }
+        writeln!(
+            src,
+            "    pub {}: Many<{}>,",
+            many.field,
+            naming.struct_name(&many.has)
+        )
+        .unwrap();
+    }
+    src.push_str("}\n");
+}
+
+/// The Rust type for a column, or the name of its SQL type if that is
+/// not known to butane.
+fn field_type(column: &AColumn, naming: StructNaming) -> Result<String, String> {
+    let ty = match column.reference() {
+        Some(ARef::Literal(reference)) => {
+            format!("ForeignKey<{}>", naming.struct_name(reference.table_name()))
+        }
+        _ => {
+            let ty = match column.typeid() {
+                Ok(TypeIdentifier::Ty(ty)) => sql_type_rust_type(&ty)?,
+                Ok(TypeIdentifier::Name(name)) => return Err(name),
+                Err(_) => return Err("unknown".to_string()),
+            };
+            if column.is_auto() {
+                format!("AutoPk<{ty}>")
+            } else {
+                ty
+            }
+        }
+    };
+    if column.nullable() {
+        Ok(format!("Option<{ty}>"))
+    } else {
+        Ok(ty)
+    }
+}
+
+fn sql_type_rust_type(ty: &SqlType) -> Result<String, String> {
+    Ok(match ty {
+        SqlType::Bool => "bool",
+        SqlType::Int => "i32",
+        SqlType::BigInt => "i64",
+        SqlType::HugeInt => "i128",
+        SqlType::Real => "f64",
+        SqlType::Text => "String",
+        SqlType::CiText => "butane::CiString",
+        SqlType::Timestamp => "chrono::NaiveDateTime",
+        SqlType::TimestampTz => "chrono::DateTime<chrono::Utc>",
+        SqlType::Date => "chrono::NaiveDate",
+        SqlType::Time => "chrono::NaiveTime",
+        SqlType::Numeric(_) => "rust_decimal::Decimal",
+        SqlType::Blob => "Vec<u8>",
+        SqlType::Json => "serde_json::Value",
+        SqlType::KeyValue => "std::collections::HashMap<String, String>",
+        SqlType::Uuid => "uuid::Uuid",
+        SqlType::Array(elem) => return Ok(format!("Vec<{}>", sql_type_rust_type(elem)?)),
+        SqlType::Custom(custom) => return Err(format!("{custom:?}")),
+    }
+    .to_string())
+}
+
+/// A table holding the contents of a `Many` field.
+pub(crate) struct ManyTable {
+    pub(crate) table: String,
+    pub(crate) owner: String,
+    pub(crate) field: String,
+    pub(crate) has: String,
+}
+
+pub(crate) fn many_table(db: &ADB, table: &ATable) -> Option<ManyTable> {
+    if table.columns.len() != 2 {
+        return None;
+    }
+    let referenced_table = |name: &str| match table.column(name)?.reference() {
+        Some(ARef::Literal(reference)) => {
+            db.get_table(reference.table_name()).map(|t| t.name.clone())
+        }
+        _ => None,
+    };
+    let owner = referenced_table("owner")?;
+    let has = referenced_table("has")?;
+    // Postgres folds unquoted names to lower case.
+    let prefix = format!("{owner}_");
+    let field_end = table.name.len().checked_sub(MANY_SUFFIX.len())?;
+    if !table
+        .name
+        .get(..prefix.len())?
+        .eq_ignore_ascii_case(&prefix)
+        || !table
+            .name
+            .get(field_end..)?
+            .eq_ignore_ascii_case(MANY_SUFFIX)
+    {
+        return None;
+    }
+    let field = table.name.get(prefix.len()..field_end)?;
+    if field.is_empty() {
+        return None;
+    }
+    Some(ManyTable {
+        table: table.name.clone(),
+        owner,
+        field: field.to_string(),
+        has,
+    })
+}
+
+/// Convert a table name such as `blog_post` to a struct name such as `BlogPost`.
+fn pascal_case(table_name: &str) -> String {
+    table_name
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Make the last word of a table name such as `blog_posts` singular.
+fn singular(table_name: &str) -> String {
+    let lower = table_name.to_lowercase();
+    let len = table_name.len();
+    if lower.ends_with("ies") {
+        format!("{}y", &table_name[..len - 3])
+    } else if ["sses", "xes", "ches", "shes"]
+        .iter()
+        .any(|suffix| lower.ends_with(suffix))
+    {
+        table_name[..len - 2].to_string()
+    } else if lower.ends_with('s') && !lower.ends_with("ss") {
+        table_name[..len - 1].to_string()
+    } else {
+        table_name.to_string()
+    }
+}
+
+/// Convert a struct name such as `BlogPost` to a module name such as `blog_post`.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_uppercase() && prev_lower {
+            snake.push('_');
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
@@ -11,24 +11,33 @@
 
 use std::{
     fs::File,
-    io::Write,
+    io::{BufRead, BufReader, BufWriter, IsTerminal, Write},
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
 use butane::db::Backend;
-use butane::db::{Connection, ConnectionMethods};
+use butane::db::{BackendConnection, Connection, ConnectionMethods};
 use butane::migrations::adb;
 use butane::migrations::adb::{diff, AColumn, ARef, Operation, ADB};
 use butane::migrations::{
-    copy_migration, FsMigrations, MemMigrations, Migration, MigrationMut, Migrations, MigrationsMut,
+    locking_operations, sqlval_from_json, sqlval_to_json, FsMigrations, Migration, MigrationMut,
+    MigrationStatus, Migrations, MigrationsMut,
 };
 use butane::query::BoolExpr;
 use butane::{db, migrations};
 use cargo_metadata::MetadataCommand;
-use chrono::Utc;
+use chrono::{DateTime, SecondsFormat, Utc};
 use nonempty::NonEmpty;
 use serde::{Deserialize, Serialize};
 
+mod graph;
+pub use graph::{schema_graph, GraphFormat};
+mod introspect;
+pub use introspect::{models_module, models_source, StructNaming};
+mod shell;
+pub use shell::{Shell, ShellHelper};
+
 pub type Result<T> = std::result::Result<T, anyhow::Error>;
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
@@ -70,14 +79,33 @@ pub fn init(base_dir: &PathBuf, name: &str, connstr: &str, connect: bool) -> Res
         db::connect(&spec)?;
     }
     std::fs::create_dir_all(base_dir)?;
-    spec.save(base_dir)?;
+    match profile() {
+        Some(profile) => {
+            let mut profiles = match db::ConnectionProfiles::load(base_dir) {
+                Ok(profiles) => profiles,
+                Err(butane::Error::IO(_)) => db::ConnectionProfiles::default(),
+                Err(e) => return Err(e.into()),
+            };
+            profiles.insert(profile, spec);
+            profiles.save(base_dir)?;
+        }
+        None => spec.save(base_dir)?,
+    }
 
     Ok(())
 }
 
 /// Make a migration.
 /// The backends are selected from the existing migrations, or the initialised connection.
-pub fn make_migration(base_dir: &Path, name: Option<&String>) -> Result<()> {
+/// Create a migration from the latest migration to the current models.
+/// If `online` is true, see [`MigrationsMut::create_online_migration`].
+/// Each of `backfills`, given as `TABLE.COLUMN=EXPR`, fills in an added column.
+pub fn make_migration(
+    base_dir: &Path,
+    name: Option<&String>,
+    online: bool,
+    backfills: &[String],
+) -> Result<()> {
     let name = match name {
         Some(name) => format!("{}_{}", default_name(), name),
         None => default_name(),
@@ -89,16 +117,90 @@ pub fn make_migration(base_dir: &Path, name: Option<&String>) -> Result<()> {
     }
     let backends = load_backends(base_dir)?;
 
-    let created = ms.create_migration(&backends, &name, ms.latest().as_ref())?;
+    let from_db = match ms.latest() {
+        Some(latest) => latest.db()?,
+        None => ADB::new(),
+    };
+    let mut to_db = ms.current().db()?;
+    let ops = diff(&from_db, &to_db);
+    set_backfills(&mut to_db, &ops, backfills)?;
+    if std::io::stdin().is_terminal() {
+        prompt_conversions(&mut to_db, &ops)?;
+        prompt_backfills(&mut to_db, &ops)?;
+    }
+
+    let latest = ms.latest();
+    let created = if online {
+        ms.create_online_migration_to(&backends, &name, latest.as_ref(), to_db)?
+    } else {
+        ms.create_migration_to(&backends, &name, latest.as_ref(), to_db)?
+    };
     if created {
         update_embedded(base_dir)?;
+    }
+    if json_output() {
+        return print_json(serde_json::json!({
+            "created": created,
+            "name": created.then_some(&name),
+            "rename_hints": if created { rename_hints(&from_db, &ops) } else { Vec::new() },
+            "locking_warnings": if created {
+                locking_operations(&from_db, &ops, online)
+            } else {
+                Vec::new()
+            },
+        }));
+    }
+    if created {
         println!("Created migration {name}");
+        print_rename_hints(&from_db, &ops);
+        print_locking_warnings(&from_db, &ops, online);
     } else {
         println!("No changes to migrate");
     }
     Ok(())
 }
 
+/// Create a migration with no schema changes, whose SQL is written by hand.
+pub fn make_empty_migration(base_dir: &Path, name: &str) -> Result<()> {
+    let name = format!("{}_{}", default_name(), name);
+    let mut ms = get_migrations(base_dir)?;
+    if ms.get_migration(&name).is_some() {
+        eprintln!("Migration {name} already exists");
+        std::process::exit(1);
+    }
+    let backends = load_backends(base_dir)?;
+    let latest = ms.latest();
+    ms.create_empty_migration(&backends, &name, latest.as_ref())?;
+    update_embedded(base_dir)?;
+    let dir = base_dir.join("migrations").join(&name);
+    if json_output() {
+        let files: Vec<String> = backends
+            .iter()
+            .flat_map(|b| {
+                ["up", "down"].map(|s| {
+                    dir.join(format!("{}_{s}.sql", b.name()))
+                        .display()
+                        .to_string()
+                })
+            })
+            .collect();
+        return print_json(serde_json::json!({
+            "created": true,
+            "name": name,
+            "files": files,
+        }));
+    }
+    println!("Created empty migration {name}");
+    for backend in &backends {
+        println!(
+            "Write its SQL for {0} in {1}/{0}_up.sql and {1}/{0}_down.sql",
+            backend.name(),
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
 /// Print a description of a column change indented by two spaces.
 pub fn print_column_diff(old: &AColumn, new: &AColumn) -> Result<()> {
     if old.typeid()? != new.typeid()? {
@@ -136,9 +238,176 @@ pub fn print_column_diff(old: &AColumn, new: &AColumn) -> Result<()> {
         };
         println!("  references: {} -> {}", old, new);
     }
+    if old.is_deferrable() != new.is_deferrable() {
+        println!(
+            "  deferrable: {} -> {}",
+            old.is_deferrable(),
+            new.is_deferrable()
+        );
+    }
     Ok(())
 }
 
+/// Ask for an expression converting the values of each column in `ops`
+/// whose type changes and which has no `#[convert_using]` attribute,
+/// and record it in `to_db`. An empty answer leaves the database to
+/// convert the values itself.
+fn prompt_conversions(to_db: &mut ADB, ops: &[Operation]) -> Result<()> {
+    for op in ops {
+        let Operation::ChangeColumn(table_name, old, new) = op else {
+            continue;
+        };
+        if old.typeid()? == new.typeid()? || new.convert_using().is_some() {
+            continue;
+        }
+        println!(
+            "Column {table_name}.{} changes type from {:?} to {:?}.",
+            new.name(),
+            old.typeid()?,
+            new.typeid()?
+        );
+        print!("SQL expression converting its values (empty to let the database convert them): ");
+        std::io::stdout().flush()?;
+        let mut expr = String::new();
+        std::io::stdin().read_line(&mut expr)?;
+        let expr = expr.trim();
+        if expr.is_empty() {
+            continue;
+        }
+        update_column(to_db, table_name, new.name(), |column| {
+            column.set_convert_using(Some(expr.to_string()))
+        });
+    }
+    Ok(())
+}
+
+/// Record each of `backfills`, given as `TABLE.COLUMN=EXPR`, as the
+/// backfill expression of a column added by `ops` in `to_db`.
+fn set_backfills(to_db: &mut ADB, ops: &[Operation], backfills: &[String]) -> Result<()> {
+    for backfill in backfills {
+        let Some((table_name, column_name, expr)) = backfill
+            .split_once('=')
+            .and_then(|(column, expr)| column.split_once('.').map(|(t, c)| (t, c, expr)))
+        else {
+            return Err(anyhow::anyhow!(
+                "Backfill {backfill} is not of the form TABLE.COLUMN=EXPR"
+            ));
+        };
+        let added = ops.iter().any(|op| {
+            matches!(op, Operation::AddColumn(t, c) if t == table_name && c.name() == column_name)
+        });
+        if !added {
+            return Err(anyhow::anyhow!(
+                "Column {table_name}.{column_name} is not added by this migration"
+            ));
+        }
+        update_column(to_db, table_name, column_name, |column| {
+            column.set_backfill(Some(expr.trim().to_string()))
+        });
+    }
+    Ok(())
+}
+
+/// Ask for an expression giving the value in existing rows of each
+/// column added by `ops` as NOT NULL without a default or backfill
+/// expression, and record it in `to_db`. An empty answer fills the
+/// rows with the default value of the column's type.
+fn prompt_backfills(to_db: &mut ADB, ops: &[Operation]) -> Result<()> {
+    for op in ops {
+        let Operation::AddColumn(table_name, column) = op else {
+            continue;
+        };
+        let has_backfill = to_db
+            .get_table(table_name)
+            .and_then(|table| table.column(column.name()))
+            .is_some_and(|column| column.backfill().is_some());
+        if column.nullable() || column.default().is_some() || has_backfill {
+            continue;
+        }
+        println!(
+            "Column {table_name}.{} is added as NOT NULL without a default.",
+            column.name()
+        );
+        print!(
+            "SQL expression for its value in existing rows (empty for the default of its type): "
+        );
+        std::io::stdout().flush()?;
+        let mut expr = String::new();
+        std::io::stdin().read_line(&mut expr)?;
+        let expr = expr.trim();
+        if expr.is_empty() {
+            continue;
+        }
+        update_column(to_db, table_name, column.name(), |column| {
+            column.set_backfill(Some(expr.to_string()))
+        });
+    }
+    Ok(())
+}
+
+/// Apply `f` to column `column_name` of table `table_name` in `db`, if it exists.
+fn update_column(db: &mut ADB, table_name: &str, column_name: &str, f: impl FnOnce(&mut AColumn)) {
+    let Some(mut table) = db.get_table(table_name).cloned() else {
+        return;
+    };
+    if let Some(column) = table.columns.iter_mut().find(|c| c.name() == column_name) {
+        f(column);
+    }
+    db.replace_table(table);
+}
+
+/// Warn about operations in `ops` which lock a table while they run.
+fn print_locking_warnings(from_db: &ADB, ops: &[Operation], online: bool) {
+    let descriptions = locking_operations(from_db, ops, online);
+    for description in &descriptions {
+        eprintln!("Warning: {description}.");
+    }
+    if !descriptions.is_empty() {
+        eprintln!("On a large table this may take a long time.");
+        if !online {
+            eprintln!("Consider `butane makemigration --online`, or splitting the change into several migrations.");
+        }
+    }
+}
+
+/// Print a hint for each table where a column was removed and another
+/// column of the same type was added, as that may have been a rename.
+pub fn print_rename_hints(from_db: &ADB, ops: &[Operation]) {
+    for hint in rename_hints(from_db, ops) {
+        println!("{hint}");
+    }
+}
+
+/// The hints printed by [`print_rename_hints`].
+pub fn rename_hints(from_db: &ADB, ops: &[Operation]) -> Vec<String> {
+    let mut hints = Vec::new();
+    for op in ops {
+        let Operation::RemoveColumn(table_name, removed) = op else {
+            continue;
+        };
+        let Some(removed_column) = from_db
+            .get_table(table_name)
+            .and_then(|table| table.column(removed))
+        else {
+            continue;
+        };
+        for op in ops {
+            match op {
+                Operation::AddColumn(t, added)
+                    if t == table_name && added.typeid().ok() == removed_column.typeid().ok() =>
+                {
+                    hints.push(format!(
+                        "Column {table_name}.{removed} was dropped and {table_name}.{} was added with the same type. If this is a rename, add #[renamed_from = \"{removed}\"] to the field and regenerate the migration to preserve its data.",
+                        added.name()
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+    hints
+}
+
 /// Print description of a list of [`Operation`].
 pub fn print_ops(ops: Vec<Operation>) -> Result<()> {
     if ops.is_empty() {
@@ -155,6 +424,9 @@ pub fn print_ops(ops: Vec<Operation>) -> Result<()> {
                 }
             }
             AddTableConstraints(_) | RemoveTableConstraints(_) => {}
+            RenameTable(from, to) => {
+                println!("Rename table {from} to {to}");
+            }
             RemoveTable(name) => {
                 println!("Remove table {}", name);
             }
@@ -170,12 +442,43 @@ pub fn print_ops(ops: Vec<Operation>) -> Result<()> {
             }
             ChangeColumn(table_name, old, new) => {
                 let column_name = old.name();
-                // Rename currently isn't supported.
-                // https://github.com/Electron100/butane/issues/89
-                assert_eq!(column_name, new.name());
+                if column_name != new.name() {
+                    println!(
+                        "Rename column {}.{column_name} to {}",
+                        table_name,
+                        new.name()
+                    );
+                }
                 println!("Change column {}.{column_name}", table_name);
                 print_column_diff(old, new)?;
             }
+            AddIndex(table_name, index) => {
+                println!(
+                    "New {}index {} on {table_name}({})",
+                    if index.unique() { "unique " } else { "" },
+                    index.name(),
+                    index.columns().join(", ")
+                );
+            }
+            RemoveIndex(table_name, index_name) => {
+                println!("Remove index {index_name} from {table_name}");
+            }
+            AddConstraint(table_name, constraint) => {
+                println!(
+                    "New constraint {} on {table_name}: {:?}",
+                    constraint.name(),
+                    constraint.kind()
+                );
+            }
+            RemoveConstraint(table_name, constraint_name) => {
+                println!("Remove constraint {constraint_name} from {table_name}");
+            }
+            ChangeSqliteOptions(..)
+            | AddCustomType(_)
+            | RemoveCustomType(_)
+            | ChangeCustomType(..) => {
+                println!("{op}");
+            }
         }
     }
     Ok(())
@@ -194,6 +497,197 @@ pub fn describe_current_migration(base_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Print a plain language description of the changes to the models
+/// which the next migration would make.
+pub fn describe_changes(base_dir: &Path) -> Result<()> {
+    let changes = get_migrations(base_dir)?.describe_changes()?;
+    if json_output() {
+        return print_json(serde_json::json!({ "changes": changes }));
+    }
+    if changes.is_empty() {
+        println!("No changes");
+    }
+    for change in changes {
+        println!("{change}");
+    }
+    Ok(())
+}
+
+/// Watch the source files of the crate whose `.butane` directory is
+/// `base_dir`, and whenever one changes, rebuild the crate and print the
+/// changes the next migration would make. If `write` is given, a
+/// migration with that name is also created, replacing the one created
+/// for the previous change while that is the latest and has not been
+/// applied.
+pub fn watch(base_dir: &Path, write: Option<&String>) -> Result<()> {
+    let crate_dir = base_dir
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("{} has no parent directory", base_dir.display()))?;
+    let src = crate_dir.join("src");
+    eprintln!("Watching {} for changes to models", src.display());
+    let mut seen = None;
+    let mut written: Option<String> = None;
+    loop {
+        let sources = source_times(&src)?;
+        if seen.as_ref() != Some(&sources) {
+            seen = Some(sources);
+            if rebuild_current(base_dir, crate_dir)? {
+                if let Some(written) = written.take() {
+                    discard_watched_migration(base_dir, &written)?;
+                }
+                describe_changes(base_dir)?;
+                if let Some(name) = write {
+                    written = write_watched_migration(base_dir, name)?;
+                }
+            }
+            if write.is_some() {
+                // Writing a migration may have updated the embedded
+                // migrations among the source files.
+                seen = Some(source_times(&src)?);
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+}
+
+/// The modification time of each Rust source file under `dir`.
+fn source_times(dir: &Path) -> Result<Vec<(PathBuf, std::time::SystemTime)>> {
+    let mut times = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            times.extend(source_times(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            times.push((path.clone(), std::fs::metadata(&path)?.modified()?));
+        }
+    }
+    times.sort();
+    Ok(times)
+}
+
+/// Run `cargo check` for the crate in `crate_dir` so that the current
+/// migration is written again for its models. The old current migration
+/// is put back if the build fails or does not recompile the models.
+/// Returns whether there is a new current migration.
+fn rebuild_current(base_dir: &Path, crate_dir: &Path) -> Result<bool> {
+    let current = base_dir.join("migrations").join("current");
+    let saved = base_dir.join("migrations").join("current.watch");
+    if current.exists() {
+        std::fs::rename(&current, &saved)?;
+    }
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let status = std::process::Command::new(cargo)
+        .args(["check", "--quiet"])
+        .current_dir(crate_dir)
+        .status()?;
+    let rebuilt = status.success() && current.exists();
+    if rebuilt {
+        if saved.exists() {
+            // Keep files such as .gitignore which the build does not write.
+            for entry in std::fs::read_dir(&saved)? {
+                let entry = entry?;
+                let target = current.join(entry.file_name());
+                if entry.file_name().to_string_lossy().starts_with('.') && !target.exists() {
+                    std::fs::rename(entry.path(), target)?;
+                }
+            }
+            std::fs::remove_dir_all(&saved)?;
+        }
+    } else {
+        if current.exists() {
+            std::fs::remove_dir_all(&current)?;
+        }
+        if saved.exists() {
+            std::fs::rename(&saved, &current)?;
+        }
+        if !status.success() {
+            eprintln!("The build failed; waiting for further changes.");
+        }
+    }
+    Ok(rebuilt)
+}
+
+/// Delete the migration `written` which `butane watch` created for an
+/// earlier change, if it is still the latest migration and has not
+/// been applied.
+fn discard_watched_migration(base_dir: &Path, written: &str) -> Result<()> {
+    let mut ms = get_migrations(base_dir)?;
+    if ms.latest().is_none_or(|m| m.name() != written) {
+        return Ok(());
+    }
+    if let Ok(spec) = db::ConnectionSpec::load_profile(base_dir, profile()) {
+        let applied = ms.last_applied_migration(&db::connect(&spec)?)?;
+        if applied.is_some_and(|m| m.name() == written) {
+            return Ok(());
+        }
+    }
+    ms.delete_migration(written)?;
+    update_embedded(base_dir)
+}
+
+/// Create a migration named `name` for `butane watch`, returning its
+/// full name if there were changes to migrate.
+fn write_watched_migration(base_dir: &Path, name: &String) -> Result<Option<String>> {
+    let before = get_migrations(base_dir)?
+        .latest()
+        .map(|m| m.name().to_string());
+    make_migration(base_dir, Some(name), false, &[])?;
+    let after = get_migrations(base_dir)?
+        .latest()
+        .map(|m| m.name().to_string());
+    Ok(if after != before { after } else { None })
+}
+
+/// Print how the schema of the database at `url` differs from the
+/// schema of the latest migration, exiting with an error if it does. If
+/// `fix` is true, print the SQL correcting the database instead.
+pub fn diff_database(base_dir: &Path, url: &str, fix: bool) -> Result<()> {
+    let ms = get_migrations(base_dir)?;
+    let expected = match ms.latest() {
+        Some(m) => m.db()?,
+        None => ADB::new(),
+    };
+    let conn = db::connect(&db::ConnectionSpec::try_from(url)?)?;
+    let mut actual = conn.introspect()?;
+    actual.remove_table(migrations::migrations_table().name.as_str());
+    let backend = conn.backend();
+    let drift = migrations::schema_drift(&expected, &actual, backend.as_ref());
+    if json_output() {
+        let fix_sql = match fix && !drift.is_empty() {
+            true => Some(migrations::drift_fix_sql(
+                &expected,
+                &actual,
+                backend.as_ref(),
+            )?),
+            false => None,
+        };
+        print_json(serde_json::json!({
+            "matches": drift.is_empty(),
+            "differences": drift.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "fix_sql": fix_sql,
+        }))?;
+        if drift.is_empty() || fix {
+            return Ok(());
+        }
+        std::process::exit(1);
+    }
+    if drift.is_empty() {
+        eprintln!("The database matches the latest migration");
+        return Ok(());
+    }
+    if fix {
+        println!(
+            "-- Corrects the schema of the database to match the latest migration. Review before running.\n{}",
+            migrations::drift_fix_sql(&expected, &actual, backend.as_ref())?
+        );
+        return Ok(());
+    }
+    for difference in drift {
+        println!("{difference}");
+    }
+    std::process::exit(1);
+}
+
 /// Describe a migration.
 /// Use name "current" to describe the changes that have been made in the code
 /// and will be included when a new migration is created.
@@ -240,7 +734,7 @@ pub fn detach_latest_migration(base_dir: &PathBuf) -> Result<()> {
         eprintln!("Can not detach initial migration");
         std::process::exit(1);
     }
-    if let Ok(spec) = db::ConnectionSpec::load(base_dir) {
+    if let Ok(spec) = db::ConnectionSpec::load_profile(base_dir, profile()) {
         let conn = db::connect(&spec)?;
         if let Some(top_applied_migration) = ms.last_applied_migration(&conn)? {
             if top_applied_migration == top_migration {
@@ -263,34 +757,187 @@ pub fn detach_latest_migration(base_dir: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-pub fn migrate(base_dir: &PathBuf, name: Option<String>) -> Result<()> {
+/// Apply migrations. If `name` is given, migrations are applied or
+/// undone so that it is the last migration applied.
+pub fn migrate(base_dir: &PathBuf, name: Option<String>, dry_run: bool) -> Result<()> {
     let spec = load_connspec(base_dir)?;
     let mut conn = db::connect(&spec)?;
-    let to_apply = get_migrations(base_dir)?.unapplied_migrations(&conn)?;
-    println!("{} migrations to apply", to_apply.len());
-    for m in to_apply {
-        println!("Applying migration {}", m.name());
-        m.apply(&mut conn)?;
-        if let Some(ref name) = name {
-            if name == &m.name().to_string() {
-                println!("Finishing at migration {}", m.name());
-                break;
+    let ms = get_migrations(base_dir)?;
+    warn_unmerged_heads(&ms)?;
+    let (to_apply, to_undo) = match &name {
+        Some(name) => {
+            if ms.get_migration(name).is_none() {
+                eprintln!("No such migration!");
+                std::process::exit(1);
+            }
+            let steps = ms.migrations_to(&conn, name)?;
+            (steps.to_apply, steps.to_undo)
+        }
+        None => (ms.unapplied_migrations(&conn)?, Vec::new()),
+    };
+    if json_output() {
+        let mut applied = Vec::new();
+        for m in &to_apply {
+            applied.push(serde_json::json!({
+                "name": m.name(),
+                "sql": m.apply_sql(conn.backend_name())?,
+            }));
+        }
+        let mut rolled_back = Vec::new();
+        for m in &to_undo {
+            rolled_back.push(serde_json::json!({
+                "name": m.name(),
+                "sql": m.down_sql(conn.backend_name())?,
+            }));
+        }
+        if !dry_run {
+            match name {
+                Some(name) => ms.migrate_to(&mut conn, &name)?,
+                None => ms.migrate(&mut conn)?,
             }
         }
+        return print_json(serde_json::json!({
+            "dry_run": dry_run,
+            "applied": applied,
+            "rolled_back": rolled_back,
+        }));
+    }
+    if dry_run {
+        eprintln!("{} migrations to apply", to_apply.len());
+        for m in &to_apply {
+            print!("{}", m.apply_sql(conn.backend_name())?);
+        }
+        if !to_undo.is_empty() {
+            eprintln!("{} migrations to roll back", to_undo.len());
+        }
+        for m in &to_undo {
+            let sql = m
+                .down_sql(conn.backend_name())?
+                .ok_or_else(|| anyhow::anyhow!("No SQL for backend {}", conn.backend_name()))?;
+            println!("-- Roll back migration {}\n{}", m.name(), sql.trim_end());
+        }
+        return Ok(());
+    }
+
+    println!("{} migrations to apply", to_apply.len());
+    for m in &to_apply {
+        println!("Applying migration {}", m.name());
+    }
+    for m in &to_undo {
+        println!("Rolling back migration {}", m.name());
+    }
+    match name {
+        Some(name) => ms.migrate_to(&mut conn, &name)?,
+        None => ms.migrate(&mut conn)?,
+    }
+    Ok(())
+}
+
+/// Record the migrations up to and including `name` as applied without
+/// running them.
+pub fn fake_migrate(base_dir: &PathBuf, name: &str) -> Result<()> {
+    let spec = load_connspec(base_dir)?;
+    let mut conn = db::connect(&spec)?;
+    let ms = get_migrations(base_dir)?;
+    if ms.get_migration(name).is_none() {
+        eprintln!("No such migration!");
+        std::process::exit(1);
     }
+    let faked = ms.fake_migrate_to(&mut conn, name)?;
+    for m in &faked {
+        println!("Recorded migration {} as applied", m.name());
+    }
+    println!("{} migrations recorded as applied", faked.len());
+    Ok(())
+}
+
+/// Insert or update the rows of the seeds for environment `env`, or
+/// only of the seed `name` and those it depends on, in one transaction.
+pub fn seed(base_dir: &PathBuf, env: &str, name: Option<&str>) -> Result<()> {
+    let spec = load_connspec(base_dir)?;
+    let mut conn = db::connect(&spec)?;
+    let ms = get_migrations(base_dir)?;
+    let tx = conn.transaction()?;
+    let count = match name {
+        Some(name) => ms.seed_named(&tx, env, name)?,
+        None => ms.seed(&tx, env)?,
+    };
+    tx.commit()?;
+    println!("Seeded {count} rows for {env}");
+    Ok(())
+}
+
+/// Create a database with a unique name next to the one of the
+/// connection, or a temporary file for SQLite, and apply all migrations
+/// to it. Its connection string is printed, and it is kept until
+/// dropped with [`testdb_drop`].
+pub fn testdb_create(base_dir: &PathBuf) -> Result<()> {
+    let spec = load_connspec(base_dir)?;
+    let ms = get_migrations(base_dir)?;
+    let testdb = migrations::TestDatabase::create(&spec, &ms)?;
+    eprintln!("Created test database {}", testdb.name());
+    println!("{}", testdb.keep().conn_str);
     Ok(())
 }
 
-pub fn unmigrate(base_dir: &PathBuf, name: Option<String>) -> Result<()> {
+/// Drop the test database `name` made by [`testdb_create`], or for
+/// SQLite delete its file.
+pub fn testdb_drop(base_dir: &PathBuf, name: &str) -> Result<()> {
+    let spec = load_connspec(base_dir)?;
+    migrations::TestDatabase::drop_database(&spec, name)?;
+    eprintln!("Dropped test database {name}");
+    Ok(())
+}
+
+pub fn unmigrate(base_dir: &PathBuf, name: Option<String>, all: bool, yes: bool) -> Result<()> {
     let spec = load_connspec(base_dir)?;
     let conn = butane::db::connect(&spec)?;
 
     match name {
+        _ if all => unmigrate_all(base_dir, conn, yes),
         Some(to) => unmigrate_to(base_dir, conn, &to),
         None => unmigrate_latest(base_dir, conn),
     }
 }
 
+/// Undo every applied migration, latest first, after confirming unless
+/// `yes` is true.
+pub fn unmigrate_all(base_dir: &Path, mut conn: Connection, yes: bool) -> Result<()> {
+    let ms = get_migrations(base_dir)?;
+    let applied = ms.applied_migrations(&conn)?;
+    if applied.is_empty() {
+        eprintln!("No migrations applied!");
+        std::process::exit(1);
+    }
+    if !yes {
+        eprintln!("Rolling back all migrations drops every table they created:");
+        for m in applied.iter().rev() {
+            eprintln!("  {}", m.name());
+        }
+        if !confirm("Pass --yes to roll back all migrations")? {
+            println!("Unmigrate cancelled");
+            return Ok(());
+        }
+    }
+    for m in applied.into_iter().rev() {
+        println!("Rolling back migration {}", m.name());
+        m.downgrade(&mut conn)?;
+    }
+    Ok(())
+}
+
+/// Ask whether to continue, returning true if the answer is yes. Fails
+/// with `not_terminal` if there is no terminal to ask from.
+fn confirm(not_terminal: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!("{not_terminal}"));
+    }
+    eprint!("Continue? [y/N] ");
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
 pub fn unmigrate_to(base_dir: &Path, mut conn: Connection, to: &str) -> Result<()> {
     let ms = get_migrations(base_dir)?;
     let to_migration = match ms.get_migration(to) {
@@ -353,6 +1000,187 @@ pub fn unmigrate_latest(base_dir: &Path, mut conn: Connection) -> Result<()> {
     Ok(())
 }
 
+/// Undo applied migrations using their down SQL: the last `target`
+/// migrations if it is a number, or those applied after the migration
+/// it names, or else the latest one. If `backup` is true, the data they
+/// drop is first copied into backup tables. Otherwise dropping tables
+/// or columns must be confirmed, unless `yes` is true. If `dry_run` is
+/// true, the SQL is printed instead of executed.
+pub fn rollback(
+    base_dir: &PathBuf,
+    target: Option<&str>,
+    backup: bool,
+    dry_run: bool,
+    yes: bool,
+) -> Result<()> {
+    let spec = load_connspec(base_dir)?;
+    let mut conn = butane::db::connect(&spec)?;
+    let ms = get_migrations(base_dir)?;
+    let mut applied = ms.applied_migrations(&conn)?;
+    let n = match target {
+        None => 1,
+        Some(target) => match target.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                let position = applied
+                    .iter()
+                    .position(|m| m.name() == target)
+                    .ok_or_else(|| anyhow::anyhow!("Migration {target} is not applied"))?;
+                applied.len() - position - 1
+            }
+        },
+    };
+    if n == 0 {
+        println!("No migrations to roll back");
+        return Ok(());
+    }
+    if applied.len() < n {
+        return Err(anyhow::anyhow!(
+            "Cannot roll back {n} migrations, only {} are applied",
+            applied.len()
+        ));
+    }
+    let mut to_undo = applied.split_off(applied.len() - n);
+    to_undo.reverse();
+
+    if dry_run {
+        for m in &to_undo {
+            let sql = m
+                .down_sql(conn.backend_name())?
+                .ok_or_else(|| anyhow::anyhow!("No SQL for backend {}", conn.backend_name()))?;
+            println!("-- Roll back migration {}\n{}", m.name(), sql.trim_end());
+        }
+        return Ok(());
+    }
+
+    if !backup && !yes {
+        let mut dropped = Vec::new();
+        for m in &to_undo {
+            let prev_db = match m.migration_from()? {
+                Some(from) => ms
+                    .get_migration(&from)
+                    .ok_or_else(|| anyhow::anyhow!("No migration {from}"))?
+                    .db()?,
+                None => ADB::default(),
+            };
+            dropped.extend(diff(&m.db()?, &prev_db).into_iter().filter(|op| {
+                matches!(op, Operation::RemoveTable(_) | Operation::RemoveColumn(..))
+            }));
+        }
+        if !dropped.is_empty() {
+            eprintln!("Rolling back drops data:");
+            for op in &dropped {
+                eprintln!("  {op}");
+            }
+            if !confirm("Rolling back drops data. Pass --yes to confirm, or --backup to keep it")? {
+                println!("Rollback cancelled");
+                return Ok(());
+            }
+        }
+    }
+
+    if backup {
+        for table in ms.rollback_with_backup(&mut conn, n)? {
+            println!("Backed up dropped data to table {table}");
+        }
+    } else {
+        ms.rollback(&mut conn, n)?;
+    }
+    match ms.last_applied_migration(&conn)? {
+        Some(m) => println!(
+            "Rolled back {n} migration(s). Latest applied migration is now {}",
+            m.name()
+        ),
+        None => println!("Rolled back {n} migration(s). No migrations are applied"),
+    }
+    Ok(())
+}
+
+/// Print, or write to `output`, the SQL creating the schema of the
+/// latest migration with the named backend. The backend may be omitted
+/// if the migrations only have one.
+pub fn dump_schema(base_dir: &Path, backend: Option<&str>, output: Option<&Path>) -> Result<()> {
+    let ms = get_migrations(base_dir)?;
+    let Some(latest) = ms.latest() else {
+        eprintln!("There are no migrations.");
+        std::process::exit(1);
+    };
+    let backend = dump_backend(base_dir, backend)?;
+    let sql = format!(
+        "-- Schema of migration {} for {}\n{}\n",
+        latest.name(),
+        backend.name(),
+        ms.schema_sql(backend.as_ref())?
+    );
+    match output {
+        Some(path) => {
+            std::fs::write(path, sql)?;
+            println!("Wrote schema to {}", path.display());
+        }
+        None => print!("{sql}"),
+    }
+    Ok(())
+}
+
+/// Dump the schema as [`dump_schema`] does. If `migrations_dir` is
+/// given, also write the SQL of each migration there, numbered in
+/// order: `NNNN_<name>.up.sql`, and `NNNN_<name>.down.sql` if it can
+/// be rolled back.
+pub fn sqldump(
+    base_dir: &Path,
+    backend: Option<&str>,
+    output: Option<&Path>,
+    migrations_dir: Option<&Path>,
+) -> Result<()> {
+    dump_schema(base_dir, backend, output)?;
+    let Some(dir) = migrations_dir else {
+        return Ok(());
+    };
+    let backend = dump_backend(base_dir, backend)?;
+    let migrations = get_migrations(base_dir)?.all_migrations()?;
+    std::fs::create_dir_all(dir)?;
+    for (i, m) in migrations.iter().enumerate() {
+        let stem = format!("{:04}_{}", i + 1, m.name());
+        std::fs::write(
+            dir.join(format!("{stem}.up.sql")),
+            m.apply_sql(backend.name())?,
+        )?;
+        if let Some(sql) = m.down_sql(backend.name())? {
+            std::fs::write(
+                dir.join(format!("{stem}.down.sql")),
+                format!("-- Roll back migration {}\n{}\n", m.name(), sql.trim_end()),
+            )?;
+        }
+    }
+    eprintln!(
+        "Wrote the SQL of {} migrations to {}",
+        migrations.len(),
+        dir.display()
+    );
+    Ok(())
+}
+
+/// The backend named `backend`, or else the only backend of the migrations.
+fn dump_backend(base_dir: &Path, backend: Option<&str>) -> Result<Box<dyn Backend>> {
+    match backend {
+        Some(name) => {
+            Ok(db::get_backend(name).ok_or_else(|| anyhow::anyhow!("Unknown backend {name}"))?)
+        }
+        None => {
+            let backends = load_backends(base_dir)?;
+            if backends.len() > 1 {
+                let names: Vec<&str> = backends.iter().map(|b| b.name()).collect();
+                eprintln!(
+                    "The migrations have several backends, choose one with --backend: {}",
+                    names.join(", ")
+                );
+                std::process::exit(1);
+            }
+            Ok(backends.head)
+        }
+    }
+}
+
 /// Create `src/butane_migrations.rs` containing the migrations metadata.
 pub fn embed(base_dir: &Path) -> Result<()> {
     let srcdir = base_dir.join("../src");
@@ -362,28 +1190,16 @@ pub fn embed(base_dir: &Path) -> Result<()> {
     }
     let path = srcdir.join("butane_migrations.rs");
 
-    let mut mem_ms = MemMigrations::new();
-    let migrations = get_migrations(base_dir)?;
-    let migration_list = migrations.all_migrations()?;
-    for m in migration_list {
-        let mut new_m = mem_ms.new_migration(&m.name());
-        copy_migration(&m, &mut new_m)?;
-        mem_ms.add_migration(new_m)?;
-    }
-    let json = serde_json::to_string_pretty(&mem_ms)?;
-
-    let src = format!(
-        "//! Butane migrations embedded in Rust.
+    // The migrations are read when the crate is compiled.
+    let src = "//! Butane migrations embedded in Rust.
 
 use butane::migrations::MemMigrations;
 
 /// Load the butane migrations embedded in Rust.
-pub fn get_migrations() -> Result<MemMigrations, butane::Error> {{
-    let json = r#\"{json}\"#;
-    MemMigrations::from_json(json)
-}}
-"
-    );
+pub fn get_migrations() -> Result<MemMigrations, butane::Error> {
+    butane::embed_migrations!()
+}
+";
 
     let mut f = std::fs::File::create(path)?;
     f.write_all(src.as_bytes())?;
@@ -404,8 +1220,39 @@ pub fn update_embedded(base_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// The connection profile chosen with `--profile`, if any.
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Use the connection profile `profile` of `.butane/connection.json`
+/// rather than the default one.
+pub fn set_profile(profile: &str) {
+    PROFILE.get_or_init(|| profile.to_string());
+}
+
+fn profile() -> Option<&'static str> {
+    PROFILE.get().map(String::as_str)
+}
+
+/// Whether `--json` was given.
+static JSON_OUTPUT: OnceLock<bool> = OnceLock::new();
+
+/// Print the results of `status`, `diff`, `migrate` and `makemigration`,
+/// and errors, as JSON for tools to read.
+pub fn set_json_output() {
+    JSON_OUTPUT.get_or_init(|| true);
+}
+
+fn json_output() -> bool {
+    JSON_OUTPUT.get().copied().unwrap_or_default()
+}
+
+fn print_json(value: serde_json::Value) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&value)?);
+    Ok(())
+}
+
 pub fn load_connspec(base_dir: &PathBuf) -> Result<db::ConnectionSpec> {
-    match db::ConnectionSpec::load(base_dir) {
+    match db::ConnectionSpec::load_profile(base_dir, profile()) {
         Ok(spec) => Ok(spec),
         Err(butane::Error::IO(_)) => {
             eprintln!("No Butane connection info found. Did you run butane init?");
@@ -441,25 +1288,56 @@ pub fn add_backend(base_dir: &Path, backend_name: &str) -> Result<()> {
 
     let migrations = get_migrations(base_dir)?;
     let migration_list = migrations.all_migrations()?;
-    let mut from_db = adb::ADB::new();
     for mut m in migration_list {
         println!("Updating {}", m.name());
+        let from_db = match m.migration_from()? {
+            Some(from) => migrations
+                .get_migration(&from)
+                .ok_or(anyhow::anyhow!("Migration {from} not found"))?
+                .db()?,
+            None => adb::ADB::new(),
+        };
         let to_db = m.db()?;
-        let mut ops = diff(&from_db, &to_db);
-        assert!(!ops.is_empty());
+        let steps = m.steps()?;
+        let (mut up_sql, down_sql) = if !m.merges()?.is_empty() {
+            // The changes of a merge are made by the migrations it merges.
+            (String::new(), String::new())
+        } else if !steps.is_empty() {
+            migrations::render_steps(backend.as_ref(), &from_db, &steps)?
+        } else {
+            let ops = diff(&from_db, &to_db);
+            let hand_written = m.sql_backends()?.iter().any(|name| {
+                m.up_sql(name)
+                    .ok()
+                    .flatten()
+                    .is_some_and(|sql| !sql.trim().is_empty())
+            });
+            if ops.is_empty() && hand_written {
+                eprintln!(
+                    "Warning: migration {} has hand-written SQL, which must be written for {backend_name} by hand",
+                    m.name()
+                );
+            }
+            (
+                backend.create_migration_sql(&from_db, ops)?,
+                backend.create_migration_sql(&to_db, diff(&to_db, &from_db))?,
+            )
+        };
 
-        if from_db.tables().count() == 0 {
+        if m.migration_from()?.is_none() {
             // This is the first migration. Create the butane_migration table
-            ops.push(adb::Operation::AddTableIfNotExists(
-                migrations::migrations_table(),
-            ));
+            let sql = backend.create_migration_sql(
+                &to_db,
+                vec![adb::Operation::AddTableIfNotExists(
+                    migrations::migrations_table(),
+                )],
+            )?;
+            if !up_sql.is_empty() {
+                up_sql.push('\n');
+            }
+            up_sql.push_str(&sql);
         }
-
-        let up_sql = backend.create_migration_sql(&from_db, ops)?;
-        let down_sql = backend.create_migration_sql(&to_db, diff(&to_db, &from_db))?;
         m.add_sql(backend.name(), &up_sql, &down_sql)?;
-
-        from_db = to_db;
     }
 
     update_embedded(base_dir)?;
@@ -501,12 +1379,22 @@ pub fn regenerate_migrations(base_dir: &Path) -> Result<()> {
     let mut from_migration_name: Option<String> = None;
 
     for m in migration_list {
-        println!("Updating {}", m.name());
         let to_db = m.db()?;
         let mut from_migration = None;
         if let Some(from_migration_name) = from_migration_name {
             from_migration = migrations.get_migration(&from_migration_name);
         }
+        let from_db = match &from_migration {
+            Some(from) => from.db()?,
+            None => adb::ADB::new(),
+        };
+        if !m.steps()?.is_empty() || !m.merges()?.is_empty() || diff(&from_db, &to_db).is_empty() {
+            // Migrations made of steps, merges and hand-written migrations cannot be regenerated.
+            println!("Keeping {}", m.name());
+            from_migration_name = Some(m.name().to_string());
+            continue;
+        }
+        println!("Updating {}", m.name());
 
         m.delete_db()?;
         migrations.create_migration_to(
@@ -560,9 +1448,18 @@ pub fn load_backends(base_dir: &Path) -> Result<NonEmpty<Box<dyn Backend>>> {
         return backends;
     }
 
-    // Otherwise use the backend used during `init`.
-    if let Ok(spec) = db::ConnectionSpec::load(base_dir) {
-        return Ok(nonempty::nonempty![spec.get_backend().unwrap()]);
+    // Otherwise use the backends of the connection profiles saved by `init`.
+    if let Ok(profiles) = db::ConnectionProfiles::load(base_dir) {
+        let mut backends: Vec<Box<dyn Backend>> = Vec::new();
+        for spec in profiles.profiles.values() {
+            let backend = spec.get_backend()?;
+            if !backends.iter().any(|b| b.name() == backend.name()) {
+                backends.push(backend);
+            }
+        }
+        if let Some(backends) = NonEmpty::from_vec(backends) {
+            return Ok(backends);
+        }
     }
 
     Err(anyhow::anyhow!(
@@ -587,6 +1484,162 @@ pub fn list_migrations(base_dir: &PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Print the state of each migration in the database: whether it is
+/// applied and when, and whether it still matches the checksum recorded
+/// when it was applied. With the global `--json` flag, set by
+/// [`set_json_output`], prints a JSON array instead.
+pub fn status(base_dir: &PathBuf) -> Result<()> {
+    let spec = load_connspec(base_dir)?;
+    let conn = db::connect(&spec)?;
+    let ms = get_migrations(base_dir)?;
+    let statuses = ms.status(&conn)?;
+    let applied_at = |s: &MigrationStatus| {
+        s.applied_at
+            .map(|t| DateTime::<Utc>::from(t).to_rfc3339_opts(SecondsFormat::Secs, true))
+    };
+    if json_output() {
+        let statuses: Vec<serde_json::Value> = statuses
+            .iter()
+            .map(|s| {
+                serde_json::json!({
+                    "name": s.name,
+                    "applied": s.applied,
+                    "applied_at": applied_at(s),
+                    "checksum_matches": s.checksum_matches,
+                    "has_down": s.has_down,
+                })
+            })
+            .collect();
+        return print_json(serde_json::Value::Array(statuses));
+    }
+    for s in &statuses {
+        let mut state = match (s.applied, applied_at(s)) {
+            (false, _) => "pending".to_string(),
+            (true, Some(at)) => format!("applied {at}"),
+            (true, None) => "applied".to_string(),
+        };
+        if s.checksum_matches == Some(false) {
+            state.push_str(", modified since it was applied");
+        }
+        println!("Migration '{}' ({state})", s.name);
+    }
+    let pending = statuses.iter().filter(|s| !s.applied).count();
+    println!("{} applied, {pending} pending", statuses.len() - pending);
+    Ok(())
+}
+
+/// Print, or write to `output`, a diagram of the tables of the latest
+/// migration and the relationships between them.
+pub fn graph(base_dir: &Path, format: GraphFormat, output: Option<&Path>) -> Result<()> {
+    let Some(latest) = get_migrations(base_dir)?.latest() else {
+        eprintln!("There are no migrations.");
+        std::process::exit(1);
+    };
+    let diagram = schema_graph(&latest.db()?, format);
+    match output {
+        Some(path) => {
+            std::fs::write(path, diagram)?;
+            println!("Wrote diagram to {}", path.display());
+        }
+        None => print!("{diagram}"),
+    }
+    Ok(())
+}
+
+/// Query the tables of the latest migration applied to the database
+/// interactively.
+pub fn shell(base_dir: &PathBuf) -> Result<()> {
+    let spec = load_connspec(base_dir)?;
+    let conn = db::connect(&spec)?;
+    let Some(latest) = get_migrations(base_dir)?.last_applied_migration(&conn)? else {
+        eprintln!("No migrations have been applied, so no tables are known.");
+        std::process::exit(1);
+    };
+    let shell = Shell::new(latest.db()?)?;
+    let mut editor = rustyline::Editor::<ShellHelper, rustyline::history::DefaultHistory>::new()?;
+    editor.set_helper(Some(ShellHelper(&shell)));
+    eprintln!(
+        "Connected to {} at migration {}. Type help for help.",
+        spec.backend_name,
+        latest.name()
+    );
+    loop {
+        let line = match editor.readline("butane> ") {
+            Ok(line) => line,
+            Err(rustyline::error::ReadlineError::Interrupted) => continue,
+            Err(rustyline::error::ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.eq_ignore_ascii_case("quit") || line.eq_ignore_ascii_case("exit") {
+            break;
+        }
+        editor.add_history_entry(line)?;
+        match shell.run(&conn, line) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{output}"),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+    Ok(())
+}
+
+/// Check that the migrations are consistent: that each is based on one
+/// which exists and they form a single chain, that the models have no
+/// changes since the latest migration, and, if there is a connection,
+/// that no applied migration was modified since it was applied. Exits
+/// with an error if any check fails.
+pub fn verify(base_dir: &PathBuf) -> Result<()> {
+    let ms = get_migrations(base_dir)?;
+    let chain = ms.chain_problems()?;
+    let models = ms.describe_changes()?;
+    let checksums = match db::ConnectionSpec::load_profile(base_dir, profile()) {
+        Ok(spec) => {
+            let conn = db::connect(&spec)?;
+            let modified: Vec<String> = ms
+                .status(&conn)?
+                .into_iter()
+                .filter(|s| s.checksum_matches == Some(false))
+                .map(|s| format!("Migration {} was modified since it was applied", s.name))
+                .collect();
+            Some(modified)
+        }
+        Err(butane::Error::IO(_)) => None,
+        Err(e) => return Err(e.into()),
+    };
+    let ok =
+        chain.is_empty() && models.is_empty() && checksums.as_ref().is_none_or(|c| c.is_empty());
+    if json_output() {
+        print_json(serde_json::json!({
+            "ok": ok,
+            "chain": chain,
+            "models": models,
+            "checksums": checksums,
+        }))?;
+    } else {
+        let report = |title: &str, problems: &[String]| {
+            if problems.is_empty() {
+                println!("{title}: ok");
+            } else {
+                println!("{title}:");
+                for problem in problems {
+                    println!("  {problem}");
+                }
+            }
+        };
+        report("Migration chain", &chain);
+        report("Models since the latest migration", &models);
+        match &checksums {
+            Some(checksums) => report("Applied migrations", checksums),
+            None => println!("Applied migrations: not checked, no connection"),
+        }
+    }
+    if !ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 /// Collapse multiple applied migrations into a new migration.
 pub fn collapse_migrations(base_dir: &PathBuf, new_initial_name: Option<&String>) -> Result<()> {
     let name = match new_initial_name {
@@ -627,6 +1680,7 @@ pub fn collapse_migrations(base_dir: &PathBuf, new_initial_name: Option<&String>
     ms.clear_migrations(&conn)?;
     ms.create_migration_to(&backends, &name, None, latest_db)?;
     let new_migration = ms.latest().unwrap();
+    migrations::ensure_migrations_table(&conn)?;
     new_migration.mark_applied(&conn)?;
 
     update_embedded(base_dir)?;
@@ -635,6 +1689,155 @@ pub fn collapse_migrations(base_dir: &PathBuf, new_initial_name: Option<&String>
     Ok(())
 }
 
+/// Squash a chain of migrations into a new migration.
+pub fn squash_migrations(base_dir: &Path, name: &str, first: &str, last: &str) -> Result<()> {
+    let name = format!("{}_{}", default_name(), name);
+    let backends = load_latest_migration_backends(base_dir)?;
+    let mut ms = get_migrations(base_dir)?;
+    ms.squash(&backends, &name, first..=last)?;
+
+    update_embedded(base_dir)?;
+
+    println!("Squashed migrations {first} to {last} into new migration '{name}'");
+    Ok(())
+}
+
+/// Create a migration merging migrations created in parallel with the
+/// latest migration. If `heads` is empty, every head other than the
+/// latest migration is merged.
+pub fn merge_migrations(base_dir: &Path, name: &str, heads: &[String]) -> Result<()> {
+    let name = format!("{}_{}", default_name(), name);
+    let backends = load_latest_migration_backends(base_dir)?;
+    let mut ms = get_migrations(base_dir)?;
+    let heads = if heads.is_empty() {
+        let latest = ms.latest();
+        ms.heads()?
+            .into_iter()
+            .filter(|m| Some(m) != latest.as_ref())
+            .collect()
+    } else {
+        heads
+            .iter()
+            .map(|head| {
+                ms.get_migration(head)
+                    .ok_or_else(|| anyhow::anyhow!("No such migration {head}"))
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+    if heads.is_empty() {
+        eprintln!("There are no parallel migrations to merge.");
+        std::process::exit(1);
+    }
+    let head_names: Vec<String> = heads.iter().map(|m| m.name().to_string()).collect();
+    ms.merge_migrations(&backends, &name, &heads)?;
+
+    update_embedded(base_dir)?;
+
+    println!("Created migration {name} merging {}", head_names.join(", "));
+    Ok(())
+}
+
+/// Warn if there are migrations which are not in the migration chain.
+fn warn_unmerged_heads(ms: &impl Migrations) -> Result<()> {
+    let latest = ms.latest();
+    let others: Vec<String> = ms
+        .heads()?
+        .into_iter()
+        .filter(|m| Some(m) != latest.as_ref())
+        .map(|m| m.name().to_string())
+        .collect();
+    if !others.is_empty() {
+        eprintln!(
+            "Warning: migrations {} are not in the migration chain. If they were created in parallel, merge them with `butane merge-migrations`.",
+            others.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Generate models from the schema of the connected database, and an
+/// initial migration matching it which is marked as applied.
+/// The models are written to `output`, or printed if it is `None`.
+/// Generate models and an initial migration, marked as applied, from
+/// the schema of the database at `connection`, or else the saved
+/// connection. The models are written to `output`, or to a module in
+/// the crate's `src/models/` if `module` is true, or else printed.
+pub fn introspect(
+    base_dir: &PathBuf,
+    connection: Option<&str>,
+    output: Option<&Path>,
+    module: bool,
+    naming: StructNaming,
+) -> Result<()> {
+    let spec = match connection {
+        Some(url) => db::ConnectionSpec::try_from(url)?,
+        None => load_connspec(base_dir)?,
+    };
+    let conn = db::connect(&spec)?;
+    let root = base_dir.join("migrations");
+    std::fs::create_dir_all(&root)?;
+    let mut ms = migrations::from_root(root);
+    if ms.latest().is_some() {
+        eprintln!("Migrations already exist, so the database cannot be adopted by introspection.");
+        std::process::exit(1);
+    }
+
+    let mut db = conn.introspect()?;
+    db.remove_table(migrations::migrations_table().name.as_str());
+    if db.tables().next().is_none() {
+        eprintln!("The database has no tables to introspect.");
+        std::process::exit(1);
+    }
+    if module {
+        let dir = base_dir
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("No crate directory for {}", base_dir.display()))?
+            .join("src")
+            .join("models");
+        let files = models_module(&db, naming);
+        for (file, _) in &files {
+            if dir.join(file).exists() {
+                return Err(anyhow::anyhow!(
+                    "{} already exists",
+                    dir.join(file).display()
+                ));
+            }
+        }
+        std::fs::create_dir_all(&dir)?;
+        for (file, source) in files {
+            std::fs::write(dir.join(file), source)?;
+        }
+        println!(
+            "Wrote models to {}. Declare the module with `mod models;`",
+            dir.display()
+        );
+    } else {
+        let source = models_source(&db, naming);
+        match output {
+            Some(path) => {
+                std::fs::write(path, source)?;
+                println!("Wrote models to {}", path.display());
+            }
+            None => print!("{source}"),
+        }
+    }
+
+    let name = format!("{}_introspected", default_name());
+    let backends = match connection {
+        Some(_) => nonempty::nonempty![spec.get_backend()?],
+        None => load_backends(base_dir)?,
+    };
+    ms.create_migration_to(&backends, &name, None, db)?;
+    let migration = ms
+        .latest()
+        .ok_or_else(|| anyhow::anyhow!("Migration {name} was not created"))?;
+    migrations::ensure_migrations_table(&conn)?;
+    migration.mark_applied(&conn)?;
+    update_embedded(base_dir)?;
+    eprintln!("Created migration {name}, marked as applied");
+    Ok(())
+}
+
 pub fn delete_table(base_dir: &Path, name: &str) -> Result<()> {
     let mut ms = get_migrations(base_dir)?;
     let current = ms.current();
@@ -642,6 +1845,13 @@ pub fn delete_table(base_dir: &Path, name: &str) -> Result<()> {
     Ok(())
 }
 
+pub fn delete_type(base_dir: &Path, name: &str) -> Result<()> {
+    let mut ms = get_migrations(base_dir)?;
+    let current = ms.current();
+    current.delete_type_definition(name)?;
+    Ok(())
+}
+
 pub fn clear_data(base_dir: &PathBuf) -> Result<()> {
     let spec = load_connspec(base_dir)?;
     let conn = db::connect(&spec)?;
@@ -659,6 +1869,192 @@ pub fn clear_data(base_dir: &PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Format of the rows written by [`data_export`] and read by [`data_import`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum DataFormat {
+    /// One JSON object per line, mapping column names to values.
+    #[default]
+    Json,
+    /// CSV with a header row of column names. An empty field is null in
+    /// a nullable column.
+    Csv,
+}
+
+/// The table `name` of the latest migration applied to `conn`, with the
+/// columns to query it by. Blobs and timestamps are converted to JSON as
+/// in seeds.
+fn data_table(
+    base_dir: &Path,
+    conn: &Connection,
+    name: &str,
+) -> Result<(adb::ATable, Vec<db::Column>)> {
+    let Some(latest) = get_migrations(base_dir)?.last_applied_migration(conn)? else {
+        eprintln!("No migrations have been applied, so no data is recognized.");
+        std::process::exit(1);
+    };
+    let Some(table) = latest.db()?.get_table(name).cloned() else {
+        eprintln!("Table {name} is not in migration {}", latest.name());
+        std::process::exit(1);
+    };
+    let mut columns = Vec::new();
+    for column in &table.columns {
+        let adb::TypeIdentifier::Ty(ty) = column.typeid()? else {
+            return Err(anyhow::anyhow!(
+                "Column {}.{} has a custom type which cannot be exported",
+                table.name,
+                column.name()
+            ));
+        };
+        // Columns name themselves with static strings, and this process
+        // only ever loads one table.
+        let name: &'static str = column.name().to_string().leak();
+        columns.push(db::Column::new(name, ty));
+    }
+    Ok((table, columns))
+}
+
+/// Write the rows of table `table` to `output`, or print them.
+pub fn data_export(
+    base_dir: &Path,
+    table: &str,
+    format: DataFormat,
+    output: Option<&Path>,
+) -> Result<()> {
+    let spec = load_connspec(&base_dir.to_path_buf())?;
+    let conn = db::connect(&spec)?;
+    let (table, columns) = data_table(base_dir, &conn, table)?;
+    let out: Box<dyn Write> = match output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut rows = conn.query(&table.name, &columns, None, None, None, None)?;
+    let mut next_row = || -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        let mut object = serde_json::Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            let value = sqlval_to_json(&row.get(i, column.ty().clone())?)
+                .ok_or_else(|| anyhow::anyhow!("Cannot export column {}", column.name()))?;
+            object.insert(column.name().to_string(), value);
+        }
+        Ok(Some(object))
+    };
+    let mut count = 0;
+    match format {
+        DataFormat::Json => {
+            let mut out = BufWriter::new(out);
+            while let Some(object) = next_row()? {
+                serde_json::to_writer(&mut out, &object)?;
+                writeln!(out)?;
+                count += 1;
+            }
+            out.flush()?;
+        }
+        DataFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(out);
+            csv.write_record(columns.iter().map(|c| c.name()))?;
+            while let Some(object) = next_row()? {
+                csv.write_record(columns.iter().map(|c| match (&object[c.name()], c.ty()) {
+                    (serde_json::Value::Null, _) => String::new(),
+                    (serde_json::Value::String(s), ty) if *ty != butane::SqlType::Json => s.clone(),
+                    (value, _) => value.to_string(),
+                }))?;
+                count += 1;
+            }
+            csv.flush()?;
+        }
+    }
+    eprintln!("Exported {count} rows from {}", table.name);
+    Ok(())
+}
+
+/// Insert the rows in `input`, written by [`data_export`], into table
+/// `table`. The rows are inserted in one transaction.
+pub fn data_import(base_dir: &Path, table: &str, format: DataFormat, input: &Path) -> Result<()> {
+    let spec = load_connspec(&base_dir.to_path_buf())?;
+    let mut conn = db::connect(&spec)?;
+    let (table, columns) = data_table(base_dir, &conn, table)?;
+    let input = BufReader::new(File::open(input)?);
+    let tx = conn.transaction()?;
+    let insert = |object: serde_json::Map<String, serde_json::Value>| -> Result<()> {
+        let mut row_columns = Vec::new();
+        let mut values = Vec::new();
+        for (name, value) in object {
+            let column = columns
+                .iter()
+                .find(|c| c.name() == name)
+                .ok_or_else(|| anyhow::anyhow!("Table {} has no column {name}", table.name))?;
+            let val = sqlval_from_json(&value, column.ty()).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{value} is not a valid value for column {}.{name}",
+                    table.name
+                )
+            })?;
+            row_columns.push(column.clone());
+            values.push(val);
+        }
+        let values: Vec<_> = values.iter().map(butane::SqlVal::as_ref).collect();
+        tx.insert_only(&table.name, &row_columns, &values)?;
+        Ok(())
+    };
+    let mut count = 0;
+    match format {
+        DataFormat::Json => {
+            for line in input.lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    insert(serde_json::from_str(&line)?)?;
+                    count += 1;
+                }
+            }
+        }
+        DataFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(input);
+            let headers = reader.headers()?.clone();
+            for record in reader.records() {
+                let record = record?;
+                let mut object = serde_json::Map::new();
+                for (name, field) in headers.iter().zip(record.iter()) {
+                    object.insert(name.to_string(), csv_field_json(&table, name, field)?);
+                }
+                insert(object)?;
+                count += 1;
+            }
+        }
+    }
+    tx.commit()?;
+    eprintln!("Imported {count} rows into {}", table.name);
+    Ok(())
+}
+
+/// The JSON value of a CSV field of column `name`, whose type decides
+/// whether the field is a string or JSON.
+fn csv_field_json(table: &adb::ATable, name: &str, field: &str) -> Result<serde_json::Value> {
+    let column = table
+        .column(name)
+        .ok_or_else(|| anyhow::anyhow!("Table {} has no column {name}", table.name))?;
+    if field.is_empty() && column.nullable() {
+        return Ok(serde_json::Value::Null);
+    }
+    Ok(match column.typeid()? {
+        adb::TypeIdentifier::Ty(
+            butane::SqlType::Text
+            | butane::SqlType::CiText
+            | butane::SqlType::Blob
+            | butane::SqlType::HugeInt
+            | butane::SqlType::Timestamp
+            | butane::SqlType::TimestampTz
+            | butane::SqlType::Date
+            | butane::SqlType::Time
+            | butane::SqlType::Numeric(_)
+            | butane::SqlType::Uuid,
+        ) => serde_json::Value::from(field),
+        _ => serde_json::from_str(field)
+            .map_err(|_| anyhow::anyhow!("{field} is not a valid value for column {name}"))?,
+    })
+}
+
 pub fn clean(base_dir: &Path) -> Result<()> {
     get_migrations(base_dir)?.clear_current()?;
     Ok(())
@@ -696,20 +2092,54 @@ pub fn extract_package_directory(
 
 /// Find all cargo workspace members that have a `.butane` subdirectory
 pub fn find_butane_workspace_member_paths() -> Result<Vec<PathBuf>> {
-    let metadata = MetadataCommand::new().no_deps().exec()?;
-    let workspace_members = metadata.workspace_members;
+    Ok(butane_workspace_members()?
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect())
+}
 
-    let mut possible_directories: Vec<PathBuf> = vec![];
-    // Find all workspace member with a .butane
-    for member in workspace_members {
-        let package_dir = extract_package_directory(&metadata.packages, member)?;
-        let member_butane_dir = package_dir.join(".butane/");
+/// The package name and directory of each cargo workspace member that
+/// has a `.butane` subdirectory, which is made when its models are
+/// compiled.
+pub fn butane_workspace_members() -> Result<Vec<(String, PathBuf)>> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let mut members = Vec::new();
+    for member in &metadata.workspace_members {
+        let package_dir = extract_package_directory(&metadata.packages, member.clone())?;
+        if package_dir.join(".butane/").is_dir() {
+            let name = metadata
+                .packages
+                .iter()
+                .find(|p| &p.id == member)
+                .map(|p| p.name.to_string())
+                .unwrap_or_default();
+            members.push((name, package_dir));
+        }
+    }
+    Ok(members)
+}
 
-        if member_butane_dir.is_dir() {
-            possible_directories.push(package_dir);
+/// The directory of the workspace member named `name` which uses butane.
+pub fn member_path(name: &str) -> Result<PathBuf> {
+    let members = butane_workspace_members()?;
+    match members.iter().find(|(member, _)| member == name) {
+        Some((_, path)) => Ok(path.clone()),
+        None => {
+            let names: Vec<&str> = members.iter().map(|(name, _)| name.as_str()).collect();
+            Err(anyhow::anyhow!(
+                "No workspace member {name} uses butane. Members using butane: {}",
+                names.join(", ")
+            ))
         }
     }
-    Ok(possible_directories)
+}
+
+/// List the workspace members which use butane.
+pub fn list_members() -> Result<()> {
+    for (name, path) in butane_workspace_members()? {
+        println!("{name} ({})", path.display());
+    }
+    Ok(())
 }
 
 /// Get the project path if only one workspace member contains a `.butane` directory
@@ -730,7 +2160,7 @@ pub fn base_dir() -> PathBuf {
 
     if !local_butane_dir.is_dir() {
         if let Ok(member_dir) = get_butane_project_path() {
-            println!("Using workspace member {:?}", member_dir);
+            eprintln!("Using workspace member {:?}", member_dir);
             return member_dir;
         }
     }
@@ -749,6 +2179,10 @@ pub enum CliError {
 
 pub fn handle_error(r: Result<()>) {
     if let Err(e) = r {
+        if json_output() {
+            println!("{}", serde_json::json!({ "error": e.to_string() }));
+            std::process::exit(1);
+        }
         match e.downcast_ref::<CliError>() {
             Some(e2) => eprintln!("{e2}"),
             None => eprintln!("Encountered unexpected error: {e}"),
@@ -4,10 +4,14 @@
 use std::path::PathBuf;
 
 use butane_cli::{
-    add_backend, base_dir, clean, clear_data, collapse_migrations, delete_table,
-    describe_migration, detach_latest_migration, embed, get_migrations, handle_error, init,
-    list_backends, list_migrations, make_migration, migrate, regenerate_migrations, remove_backend,
-    unmigrate,
+    add_backend, base_dir, butane_workspace_members, clean, clear_data, collapse_migrations,
+    data_export, data_import, delete_table, delete_type, describe_changes, describe_migration,
+    detach_latest_migration, diff_database, dump_schema, embed, fake_migrate, get_migrations,
+    graph, handle_error, init, introspect, list_backends, list_members, list_migrations,
+    make_empty_migration, make_migration, member_path, merge_migrations, migrate,
+    regenerate_migrations, remove_backend, rollback, seed, set_json_output, set_profile, shell,
+    sqldump, squash_migrations, status, testdb_create, testdb_drop, unmigrate, verify, watch,
+    DataFormat, GraphFormat, StructNaming,
 };
 use clap::{ArgAction, Parser, Subcommand};
 
@@ -17,8 +21,20 @@
 struct Cli {
     #[command(subcommand)]
     command: Commands,
-    #[arg(short = 'p', long, default_value=base_dir().into_os_string())]
+    #[arg(short = 'p', long, global = true, default_value=base_dir().into_os_string())]
     path: PathBuf,
+    /// Workspace member to act on, by package name, instead of the crate in the current directory.
+    #[arg(long, global = true, conflicts_with = "path")]
+    member: Option<String>,
+    /// Run makemigration or migrate for every workspace member using butane.
+    #[arg(long, global = true, conflicts_with_all = ["path", "member"])]
+    all_members: bool,
+    /// Connection profile of .butane/connection.json to use instead of the default one.
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Print the results of status, diff, migrate and makemigration, and errors, as JSON.
+    #[arg(long, global = true)]
+    json: bool,
     #[command(flatten)]
     verbose: clap_verbosity_flag::Verbosity,
 }
@@ -37,6 +53,19 @@ enum Commands {
     MakeMigration {
         /// Name to use for the migration.
         name: String,
+        /// Create a migration with no schema changes, whose SQL is written by hand.
+        #[arg(long, conflicts_with = "online")]
+        empty: bool,
+        /// Avoid locking tables against writes where the backend can,
+        /// for databases which are in use. The migration does not run
+        /// in a transaction.
+        #[arg(long)]
+        online: bool,
+        /// SQL expression giving the value in existing rows of a column
+        /// added as NOT NULL without a default. May be repeated; asked
+        /// for from a terminal if not given.
+        #[arg(long, value_name = "TABLE.COLUMN=EXPR", conflicts_with = "empty")]
+        backfill: Vec<String>,
     },
     /// Detach the latest migration.
     #[command(
@@ -53,10 +82,30 @@ enum Commands {
 "
     )]
     DetachMigration,
-    /// Apply migrations.
+    /// Apply migrations. If the name of a migration is specified, applies or rolls back migrations until it is the latest applied migration.
     Migrate {
         /// Migration to migrate to.
         name: Option<String>,
+        /// Print the SQL which would be executed, without applying it.
+        #[arg(long)]
+        dry_run: bool,
+        /// Record the migrations up to the named one as applied without
+        /// running their SQL, for a database whose schema already matches.
+        #[arg(long, requires = "name", conflicts_with = "dry_run")]
+        fake: bool,
+    },
+    /// Insert or update the rows of the seeds in .butane/seeds/<ENV>/.
+    #[command(
+        after_help = "Each seed is a JSON file listing rows by table, such as [{\"table\": \"Tag\", \"rows\": [{\"tag\": \"rust\"}]}]. Each row must give its primary key. A row whose primary key already exists is updated, so seeding can be repeated. A seed may also be an SQL file, which should be written so that it can be repeated.
+
+Seeds are applied in order of file name, except that a seed comes after the seeds it depends on. These are listed as {\"depends_on\": [\"NAME\"], \"tables\": [...]} in JSON, or in a first-line comment such as -- depends_on: NAME in SQL. All migrations must be applied first."
+    )]
+    Seed {
+        /// Seed to apply, after the seeds it depends on. Defaults to every seed of the environment.
+        name: Option<String>,
+        /// Environment to seed.
+        #[arg(long, default_value = "development")]
+        env: String,
     },
     /// Regenerate migrations in place.
     Regenerate,
@@ -64,20 +113,137 @@ enum Commands {
         /// Name of migration to be described, or `current`.
         name: String,
     },
+    /// Describe in plain language the changes to the models since the latest migration, or how a database differs from it.
+    Diff {
+        /// URL of a database to compare with the latest migration, such as postgres://user@host/db or sqlite://file.db.
+        #[arg(long, value_name = "URL")]
+        connection: Option<String>,
+        /// Print SQL correcting the schema of the database.
+        #[arg(long, requires = "connection")]
+        fix: bool,
+    },
     /// List migrations.
     List,
+    /// Show whether each migration is applied to the database, when, and whether it was modified since.
+    Status,
+    /// Draw the tables of the latest migration and the relationships between them.
+    Graph {
+        /// Language of the diagram.
+        #[arg(long, value_enum, default_value_t)]
+        format: GraphFormat,
+        /// File to write the diagram to. If omitted, it is printed.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// List the workspace members which use butane.
+    Members,
+    /// Query the tables of the database interactively, with tab completion of tables and columns.
+    Shell,
+    /// Rebuild the crate whenever its source files change and print the changes the next migration would make.
+    Watch {
+        /// Also create the migration, with this name, replacing the one created for the previous change.
+        #[arg(long, value_name = "NAME")]
+        write: Option<String>,
+    },
+    /// Check that the migrations form a single chain, that the models match the latest migration, and that no applied migration was modified.
+    Verify,
     /// Replace all migrations with a single migration representing the current model state.
     Collapse {
         /// Name to use for the new migration.
         name: String,
     },
+    /// Replace a chain of migrations with a single equivalent migration.
+    #[command(
+        after_help = "The SQL of the new migration is regenerated from the schema before the first migration and after the last. Hand-edited SQL in the squashed migrations is not kept.
+
+Databases which have applied the last squashed migration consider the new migration applied. Later migrations are kept and follow the new migration."
+    )]
+    Squash {
+        /// Name to use for the new migration.
+        name: String,
+        /// First migration to squash.
+        first: String,
+        /// Last migration to squash.
+        last: String,
+    },
+    /// Generate models and an initial migration from the schema of an existing database.
+    #[command(
+        after_help = "The initial migration is marked as applied, as the database already has its schema. Review the generated models before use, as not every schema can be represented exactly."
+    )]
+    Introspect {
+        /// URL of the database, such as postgres://user@host/db or sqlite://file.db. Defaults to the connection saved by `butane init`.
+        #[arg(long, value_name = "URL")]
+        connection: Option<String>,
+        /// File to write the models to. If omitted, the models are printed.
+        #[arg(short, long, conflicts_with = "module")]
+        output: Option<PathBuf>,
+        /// Write each model to its own file in the crate's src/models/ directory, with a mod.rs declaring them.
+        #[arg(long)]
+        module: bool,
+        /// How struct names are made from table names.
+        #[arg(long, value_enum, default_value_t)]
+        naming: StructNaming,
+    },
+    /// Schema of the latest migration.
+    Schema {
+        #[clap(subcommand)]
+        subcommand: SchemaCommands,
+    },
+    /// Print the SQL creating the schema of the latest migration from scratch, and optionally write the SQL of each migration to files, for review or for other tools.
+    Sqldump {
+        /// Backend to write SQL for. Required if the migrations have several backends.
+        #[arg(long)]
+        backend: Option<String>,
+        /// File to write the schema to. If omitted, the schema is printed.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Directory to write the up and down SQL of each migration to, as NNNN_<name>.up.sql and NNNN_<name>.down.sql.
+        #[arg(long, value_name = "DIR")]
+        migrations: Option<PathBuf>,
+    },
+    /// Merge migrations created in parallel from the same migration into the migration chain.
+    #[command(
+        alias = "mergemigrations",
+        after_help = "When migrations are created on separate branches of version control, each branch has its own latest migration. This command creates a migration following the latest one which combines the schema changes of the other branches, so that the migrations of every branch are applied.
+
+Branches which change the same table cannot be merged automatically."
+    )]
+    MergeMigrations {
+        /// Name to use for the new migration.
+        #[arg(default_value = "merge")]
+        name: String,
+        /// Migrations to merge. Defaults to every migration which is not in the migration chain and which no other migration follows.
+        #[arg(long = "head")]
+        heads: Vec<String>,
+    },
     /// Embed migrations in the source code.
     Embed,
     /// Undo migrations. With no arguments, undoes the latest migration. If the name of a migration is specified, rolls back until that migration is the latest applied migration.
-    #[command(alias = "rollback")]
     Unmigrate {
         /// Migration to roll back to.
+        #[arg(conflicts_with = "all")]
         name: Option<String>,
+        /// Roll back every applied migration, leaving the database without the tables they create.
+        #[arg(long)]
+        all: bool,
+        /// Do not ask for confirmation before rolling back all migrations.
+        #[arg(short, long, requires = "all")]
+        yes: bool,
+    },
+    /// Undo the last N applied migrations (default 1) using their down SQL, or those applied after the named migration.
+    Rollback {
+        /// Number of migrations to roll back, or the migration to roll back to, which stays applied.
+        #[arg(value_name = "N|NAME")]
+        target: Option<String>,
+        /// Copy the tables and columns the migrations drop into timestamped backup tables first.
+        #[arg(long)]
+        backup: bool,
+        /// Print the SQL which would be executed, without running it.
+        #[arg(long, conflicts_with = "backup")]
+        dry_run: bool,
+        /// Do not ask for confirmation before dropping tables or columns.
+        #[arg(short, long)]
+        yes: bool,
     },
     /// Clear.
     Clear {
@@ -89,6 +255,16 @@ enum Commands {
         #[clap(subcommand)]
         subcommand: DeleteCommands,
     },
+    /// Export and import the rows of a table.
+    Data {
+        #[clap(subcommand)]
+        subcommand: DataCommands,
+    },
+    /// Databases for tests, with all migrations applied.
+    Testdb {
+        #[clap(subcommand)]
+        subcommand: TestdbCommands,
+    },
     /// Clean current migration state. Deletes the current migration working state which is generated on each build. This can be used as a workaround to remove stale tables from the schema, as Butane does not currently auto-detect model removals. The next build will recreate with only tables for the extant models.
     Clean,
 }
@@ -120,6 +296,55 @@ enum BackendCommands {
     List,
 }
 
+#[derive(Subcommand)]
+enum SchemaCommands {
+    /// Print the SQL creating the schema of the latest migration from scratch, as a single script.
+    Dump {
+        /// Backend to write SQL for. Required if the migrations have several backends.
+        #[arg(long)]
+        backend: Option<String>,
+        /// File to write the SQL to. If omitted, the SQL is printed.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DataCommands {
+    /// Write the rows of a table, converted as in seeds: blobs as hex and timestamps as ISO 8601.
+    Export {
+        /// Table of the model to export.
+        table: String,
+        /// Format to write.
+        #[arg(long, value_enum, default_value_t)]
+        format: DataFormat,
+        /// File to write the rows to. If omitted, the rows are printed.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Insert rows written by `data export` into a table, in one transaction.
+    Import {
+        /// Table of the model to import into.
+        table: String,
+        /// File to read the rows from.
+        input: PathBuf,
+        /// Format to read.
+        #[arg(long, value_enum, default_value_t)]
+        format: DataFormat,
+    },
+}
+
+#[derive(Subcommand)]
+enum TestdbCommands {
+    /// Create a database with a unique name on the server of the connection, or a temporary file for SQLite, and apply all migrations to it. Its connection string is printed.
+    Create,
+    /// Drop a database made by `testdb create`.
+    Drop {
+        /// Name of the database, or path of the file for SQLite.
+        name: String,
+    },
+}
+
 #[derive(Subcommand)]
 enum ClearCommands {
     /// Clear all data from the database. The schema is left intact, but all instances of all models (i.e. all rows of all tables defined by the models) are deleted.
@@ -133,6 +358,11 @@ enum DeleteCommands {
         /// Table name.
         name: String,
     },
+    /// Delete a user-defined SQL type, which the next migration drops.
+    Type {
+        /// Type name.
+        name: String,
+    },
 }
 
 fn main() {
@@ -142,11 +372,47 @@ fn main() {
         .filter_level(cli.verbose.log_level_filter())
         .init();
 
-    let mut base_dir = cli.path;
-    if !base_dir.ends_with(".butane") {
-        base_dir.push(".butane");
+    if let Some(profile) = &cli.profile {
+        set_profile(profile);
+    }
+    if cli.json {
+        set_json_output();
+    }
+
+    let paths = if cli.all_members {
+        if !matches!(
+            cli.command,
+            Commands::MakeMigration { .. } | Commands::Migrate { .. }
+        ) {
+            eprintln!("--all-members can only be used with makemigration and migrate");
+            std::process::exit(1);
+        }
+        let members = butane_workspace_members().unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+        members.into_iter().map(|(_, path)| path).collect()
+    } else if let Some(member) = &cli.member {
+        vec![member_path(member).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        })]
+    } else {
+        vec![cli.path]
+    };
+    let several = paths.len() > 1;
+    for mut base_dir in paths {
+        if several {
+            eprintln!("Workspace member {}", base_dir.display());
+        }
+        if !base_dir.ends_with(".butane") {
+            base_dir.push(".butane");
+        }
+        run(&cli.command, base_dir);
     }
+}
 
+fn run(command: &Commands, base_dir: PathBuf) {
     // List any detached migrations.
     if let Ok(ms) = get_migrations(&base_dir) {
         if let Ok(detached_migrations) = ms.detached_migration_paths() {
@@ -161,7 +427,7 @@ fn main() {
         };
     };
 
-    match &cli.command {
+    match command {
         Commands::Init(args) => handle_error(init(
             &base_dir,
             &args.backend,
@@ -173,20 +439,117 @@ fn main() {
             BackendCommands::Remove { name } => handle_error(remove_backend(&base_dir, name)),
             BackendCommands::List => handle_error(list_backends(&base_dir)),
         },
-        Commands::MakeMigration { name } => handle_error(make_migration(&base_dir, Some(name))),
+        Commands::MakeMigration {
+            name,
+            empty: false,
+            online,
+            backfill,
+        } => handle_error(make_migration(&base_dir, Some(name), *online, backfill)),
+        Commands::MakeMigration {
+            name, empty: true, ..
+        } => handle_error(make_empty_migration(&base_dir, name)),
         Commands::DescribeMigration { name } => handle_error(describe_migration(&base_dir, name)),
+        Commands::Diff {
+            connection: Some(url),
+            fix,
+        } => handle_error(diff_database(&base_dir, url, *fix)),
+        Commands::Diff {
+            connection: None, ..
+        } => handle_error(describe_changes(&base_dir)),
         Commands::Regenerate => handle_error(regenerate_migrations(&base_dir)),
         Commands::DetachMigration => handle_error(detach_latest_migration(&base_dir)),
-        Commands::Migrate { name } => handle_error(migrate(&base_dir, name.to_owned())),
-        Commands::Unmigrate { name } => handle_error(unmigrate(&base_dir, name.to_owned())),
+        Commands::Migrate {
+            name: Some(name),
+            fake: true,
+            ..
+        } => handle_error(fake_migrate(&base_dir, name)),
+        Commands::Migrate { name, dry_run, .. } => {
+            handle_error(migrate(&base_dir, name.to_owned(), *dry_run))
+        }
+        Commands::Seed { name, env } => handle_error(seed(&base_dir, env, name.as_deref())),
+        Commands::Unmigrate { name, all, yes } => {
+            handle_error(unmigrate(&base_dir, name.to_owned(), *all, *yes))
+        }
+        Commands::Rollback {
+            target,
+            backup,
+            dry_run,
+            yes,
+        } => handle_error(rollback(
+            &base_dir,
+            target.as_deref(),
+            *backup,
+            *dry_run,
+            *yes,
+        )),
+        Commands::Squash { name, first, last } => {
+            handle_error(squash_migrations(&base_dir, name, first, last))
+        }
+        Commands::Introspect {
+            connection,
+            output,
+            module,
+            naming,
+        } => handle_error(introspect(
+            &base_dir,
+            connection.as_deref(),
+            output.as_deref(),
+            *module,
+            *naming,
+        )),
+        Commands::Schema { subcommand } => match subcommand {
+            SchemaCommands::Dump { backend, output } => handle_error(dump_schema(
+                &base_dir,
+                backend.as_deref(),
+                output.as_deref(),
+            )),
+        },
+        Commands::Sqldump {
+            backend,
+            output,
+            migrations,
+        } => handle_error(sqldump(
+            &base_dir,
+            backend.as_deref(),
+            output.as_deref(),
+            migrations.as_deref(),
+        )),
+        Commands::MergeMigrations { name, heads } => {
+            handle_error(merge_migrations(&base_dir, name, heads))
+        }
         Commands::Embed => handle_error(embed(&base_dir)),
         Commands::List => handle_error(list_migrations(&base_dir)),
+        Commands::Status => handle_error(status(&base_dir)),
+        Commands::Verify => handle_error(verify(&base_dir)),
+        Commands::Shell => handle_error(shell(&base_dir)),
+        Commands::Watch { write } => handle_error(watch(&base_dir, write.as_ref())),
+        Commands::Members => handle_error(list_members()),
+        Commands::Graph { format, output } => {
+            handle_error(graph(&base_dir, *format, output.as_deref()))
+        }
         Commands::Collapse { name } => handle_error(collapse_migrations(&base_dir, Some(name))),
         Commands::Clear { subcommand } => match subcommand {
             ClearCommands::Data => handle_error(clear_data(&base_dir)),
         },
         Commands::Delete { subcommand } => match subcommand {
             DeleteCommands::Table { name } => handle_error(delete_table(&base_dir, name)),
+            DeleteCommands::Type { name } => handle_error(delete_type(&base_dir, name)),
+        },
+        Commands::Data { subcommand } => match subcommand {
+            DataCommands::Export {
+                table,
+                format,
+                output,
+            } => handle_error(data_export(&base_dir, table, *format, output.as_deref())),
+            DataCommands::Import {
+                table,
+                input,
+                format,
+            } => handle_error(data_import(&base_dir, table, *format, input)),
+        },
+        Commands::Testdb { subcommand } => match subcommand {
+            TestdbCommands::Create => handle_error(testdb_create(&base_dir)),
+            TestdbCommands::Drop { name } => handle_error(testdb_drop(&base_dir, name)),
         },
         Commands::Clean => handle_error(clean(&base_dir)),
     }
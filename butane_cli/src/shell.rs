@@ -0,0 +1,396 @@
+//! Interactive queries of the tables of a schema.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use butane::db::{BackendRows, Column, ConnectionMethods};
+use butane::migrations::adb::{ATable, TypeIdentifier, ADB};
+use butane::migrations::{sqlval_from_json, sqlval_to_json};
+use butane::query::{BoolExpr, Expr, Order, OrderDirection};
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use serde_json::Value;
+
+use crate::Result;
+
+const HELP: &str = "\
+Queries have the form
+  <table> [where <column> <op> <value> [and ...]] [order by <column> [asc|desc]] [limit <n>]
+where <op> is one of = != < <= > >= like, or the condition is `<column> is [not] null`.
+Values are numbers, true, false, null or quoted strings; blobs are hex strings and timestamps
+ISO 8601 strings.
+Other commands:
+  tables             list the tables
+  describe <table>   list the columns of a table
+  help               show this help
+  quit               leave the shell";
+
+const KEYWORDS: [&str; 9] = [
+    "where", "and", "order", "by", "asc", "desc", "limit", "is", "null",
+];
+const COMMANDS: [&str; 4] = ["tables", "describe", "help", "quit"];
+
+/// Ad-hoc queries of the tables of a schema, for `butane shell`.
+pub struct Shell {
+    db: ADB,
+    /// The columns to query each table by. Columns of custom types
+    /// cannot be shown and are left out.
+    columns: BTreeMap<String, Vec<Column>>,
+}
+
+impl Shell {
+    pub fn new(db: ADB) -> Result<Self> {
+        let mut columns = BTreeMap::new();
+        for table in db.tables() {
+            let mut table_columns = Vec::new();
+            for column in &table.columns {
+                if let TypeIdentifier::Ty(ty) = column.typeid()? {
+                    // Columns name themselves with static strings, and the
+                    // tables are loaded once for the life of the shell.
+                    let name: &'static str = column.name().to_string().leak();
+                    table_columns.push(Column::new(name, ty));
+                }
+            }
+            columns.insert(table.name.clone(), table_columns);
+        }
+        Ok(Shell { db, columns })
+    }
+
+    /// Run a line of input, returning the text to print.
+    pub fn run(&self, conn: &impl ConnectionMethods, line: &str) -> Result<String> {
+        let tokens = tokenize(line)?;
+        match tokens.as_slice() {
+            [] => Ok(String::new()),
+            [Token::Word(w)] if w.eq_ignore_ascii_case("help") => Ok(HELP.to_string()),
+            [Token::Word(w)] if w.eq_ignore_ascii_case("tables") => Ok(self
+                .db
+                .tables()
+                .map(|t| t.name.clone())
+                .collect::<Vec<_>>()
+                .join("\n")),
+            [Token::Word(w), Token::Word(table)] if w.eq_ignore_ascii_case("describe") => {
+                let table = self.table(table)?;
+                let mut rows = Vec::new();
+                for column in &table.columns {
+                    rows.push(vec![
+                        column.name().to_string(),
+                        match column.typeid()? {
+                            TypeIdentifier::Ty(ty) => format!("{ty:?}"),
+                            TypeIdentifier::Name(name) => name,
+                        },
+                        if column.nullable() {
+                            "null"
+                        } else {
+                            "not null"
+                        }
+                        .to_string(),
+                        if column.is_pk() { "primary key" } else { "" }.to_string(),
+                    ]);
+                }
+                Ok(render(&["column", "type", "nullable", "key"], &rows))
+            }
+            [Token::Word(table), rest @ ..] => self.query(conn, table, rest),
+            _ => Err(anyhow::anyhow!("Expected a table name. Type help for help")),
+        }
+    }
+
+    /// Names to complete the word being typed in `line` with.
+    fn completions(&self, line: &str, word: &str) -> Vec<String> {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if !word.is_empty() {
+            words.pop();
+        }
+        let candidates: Vec<String> = match words.as_slice() {
+            [] => self
+                .db
+                .tables()
+                .map(|t| t.name.clone())
+                .chain(COMMANDS.iter().map(|c| c.to_string()))
+                .collect(),
+            [command] if command.eq_ignore_ascii_case("describe") => {
+                self.db.tables().map(|t| t.name.clone()).collect()
+            }
+            [table, ..] => self
+                .table(table)
+                .map(|t| {
+                    t.columns
+                        .iter()
+                        .map(|c| c.name().to_string())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+                .into_iter()
+                .chain(KEYWORDS.iter().map(|k| k.to_string()))
+                .collect(),
+        };
+        let word = word.to_lowercase();
+        candidates
+            .into_iter()
+            .filter(|c| c.to_lowercase().starts_with(&word))
+            .collect()
+    }
+
+    fn table(&self, name: &str) -> Result<&ATable> {
+        self.db
+            .get_table(name)
+            .or_else(|| self.db.tables().find(|t| t.name.eq_ignore_ascii_case(name)))
+            .ok_or_else(|| anyhow::anyhow!("No table {name}"))
+    }
+
+    fn query(
+        &self,
+        conn: &impl ConnectionMethods,
+        table: &str,
+        tokens: &[Token],
+    ) -> Result<String> {
+        let table = self.table(table)?;
+        let columns = &self.columns[&table.name];
+        let column = |name: &str| {
+            columns
+                .iter()
+                .find(|c| c.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| anyhow::anyhow!("Table {} has no column {name}", table.name))
+        };
+        let mut tokens = tokens.iter().peekable();
+        let mut conditions = Vec::new();
+        let mut order = None;
+        let mut limit = None;
+        while let Some(token) = tokens.next() {
+            if token.is_word("where") || token.is_word("and") {
+                let Some(Token::Word(name)) = tokens.next() else {
+                    return Err(anyhow::anyhow!("Expected a column after {token}"));
+                };
+                let column = column(name)?;
+                let op = tokens
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Expected an operator after {name}"))?;
+                if op.is_word("is") {
+                    let not = tokens.next_if(|t| t.is_word("not")).is_some();
+                    if !tokens.next().is_some_and(|t| t.is_word("null")) {
+                        return Err(anyhow::anyhow!("Expected null after is"));
+                    }
+                    let null = Expr::Val(butane::SqlVal::Null);
+                    conditions.push(match not {
+                        true => BoolExpr::Ne(column.name(), null),
+                        false => BoolExpr::Eq(column.name(), null),
+                    });
+                    continue;
+                }
+                let value = tokens
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Expected a value after {op}"))?;
+                let val = sqlval_from_json(&value.json()?, column.ty()).ok_or_else(|| {
+                    anyhow::anyhow!("{value} is not a valid value for column {name}")
+                })?;
+                let (name, val) = (column.name(), Expr::Val(val));
+                conditions.push(match op {
+                    Token::Op(op) if op == "=" => BoolExpr::Eq(name, val),
+                    Token::Op(op) if op == "!=" || op == "<>" => BoolExpr::Ne(name, val),
+                    Token::Op(op) if op == "<" => BoolExpr::Lt(name, val),
+                    Token::Op(op) if op == "<=" => BoolExpr::Le(name, val),
+                    Token::Op(op) if op == ">" => BoolExpr::Gt(name, val),
+                    Token::Op(op) if op == ">=" => BoolExpr::Ge(name, val),
+                    t if t.is_word("like") => BoolExpr::Like(name, val),
+                    _ => return Err(anyhow::anyhow!("Unknown operator {op}")),
+                });
+            } else if token.is_word("order") {
+                if !tokens.next().is_some_and(|t| t.is_word("by")) {
+                    return Err(anyhow::anyhow!("Expected by after order"));
+                }
+                let Some(Token::Word(name)) = tokens.next() else {
+                    return Err(anyhow::anyhow!("Expected a column after order by"));
+                };
+                let direction = match tokens.next_if(|t| t.is_word("asc") || t.is_word("desc")) {
+                    Some(t) if t.is_word("desc") => OrderDirection::Descending,
+                    _ => OrderDirection::Ascending,
+                };
+                order = Some(Order {
+                    direction,
+                    column: column(name)?.name(),
+                });
+            } else if token.is_word("limit") {
+                limit = match tokens.next() {
+                    Some(Token::Word(n)) => Some(n.parse::<i32>()?),
+                    _ => return Err(anyhow::anyhow!("Expected a number after limit")),
+                };
+            } else {
+                return Err(anyhow::anyhow!("Unexpected {token}. Type help for help"));
+            }
+        }
+
+        let expr = match conditions.len() {
+            0 => None,
+            1 => conditions.pop(),
+            _ => Some(BoolExpr::AllOf(conditions)),
+        };
+        let mut rows = conn.query(
+            &table.name,
+            columns,
+            expr,
+            limit,
+            None,
+            order.as_ref().map(std::slice::from_ref),
+        )?;
+        let mut lines = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mut line = Vec::new();
+            for (i, column) in columns.iter().enumerate() {
+                line.push(match sqlval_to_json(&row.get(i, column.ty().clone())?) {
+                    Some(Value::Null) => "NULL".to_string(),
+                    Some(Value::String(s)) => s,
+                    Some(value) => value.to_string(),
+                    None => "?".to_string(),
+                });
+            }
+            lines.push(line);
+        }
+        let headers: Vec<&str> = columns.iter().map(|c| c.name()).collect();
+        Ok(render(&headers, &lines))
+    }
+}
+
+/// Render rows as a table with a header, followed by the number of rows.
+fn render(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+    let line = |values: &mut dyn Iterator<Item = &str>| {
+        values
+            .zip(&widths)
+            .map(|(v, w)| format!("{v:w$}"))
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+    let mut out = String::new();
+    writeln!(out, "{}", line(&mut headers.iter().copied())).unwrap();
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    writeln!(out, "{}", rule.join("-+-")).unwrap();
+    for row in rows {
+        writeln!(out, "{}", line(&mut row.iter().map(String::as_str))).unwrap();
+    }
+    match rows.len() {
+        1 => write!(out, "(1 row)").unwrap(),
+        n => write!(out, "({n} rows)").unwrap(),
+    }
+    out
+}
+
+#[derive(Debug)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(String),
+}
+
+impl Token {
+    fn is_word(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(w) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    /// The JSON value of a literal, converted by the type of its column.
+    fn json(&self) -> Result<Value> {
+        match self {
+            Token::Str(s) => Ok(Value::from(s.as_str())),
+            Token::Word(w) if w.eq_ignore_ascii_case("null") => Ok(Value::Null),
+            Token::Word(w) if w.eq_ignore_ascii_case("true") => Ok(Value::Bool(true)),
+            Token::Word(w) if w.eq_ignore_ascii_case("false") => Ok(Value::Bool(false)),
+            Token::Word(w) => serde_json::from_str::<serde_json::Number>(w)
+                .map(Value::Number)
+                .map_err(|_| anyhow::anyhow!("{w} is not a value. Quote strings")),
+            Token::Op(op) => Err(anyhow::anyhow!("Expected a value, not {op}")),
+        }
+    }
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(s) | Token::Op(s) => f.write_str(s),
+            Token::Str(s) => write!(f, "'{s}'"),
+        }
+    }
+}
+
+const OPERATOR_CHARS: &str = "=!<>";
+
+/// Split a line into words, quoted strings, in which a doubled quote is
+/// a quote, and comparison operators.
+fn tokenize(line: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '\'' || c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some(q) if q == c && chars.next_if_eq(&c).is_some() => s.push(c),
+                    Some(q) if q == c => break,
+                    Some(other) => s.push(other),
+                    None => return Err(anyhow::anyhow!("Unterminated string")),
+                }
+            }
+            tokens.push(Token::Str(s));
+        } else if OPERATOR_CHARS.contains(c) {
+            let mut op = String::new();
+            while let Some(c) = chars.next_if(|c| OPERATOR_CHARS.contains(*c)) {
+                op.push(c);
+            }
+            tokens.push(Token::Op(op));
+        } else {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"=!<>'\"".contains(*c)) {
+                word.push(c);
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Completes table names, column names and keywords for the shell's
+/// line editor.
+pub struct ShellHelper<'a>(pub &'a Shell);
+
+impl Completer for ShellHelper<'_> {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before
+            .rfind(|c: char| c.is_whitespace() || OPERATOR_CHARS.contains(c))
+            .map_or(0, |i| i + 1);
+        let candidates = self
+            .0
+            .completions(before, &before[start..])
+            .into_iter()
+            .map(|c| Pair {
+                display: c.clone(),
+                replacement: c,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper<'_> {
+    type Hint = String;
+}
+impl Highlighter for ShellHelper<'_> {}
+impl Validator for ShellHelper<'_> {}
+impl Helper for ShellHelper<'_> {}
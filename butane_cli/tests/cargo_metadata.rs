@@ -5,3 +5,15 @@ fn find_existing_butane_dirs() {
     // will only exist if the tests in that directory have been run.
     assert!(possible_directories.len() > 1);
 }
+
+#[test]
+fn find_workspace_members() {
+    let members = butane_cli::butane_workspace_members().unwrap();
+    let (_, path) = members
+        .iter()
+        .find(|(name, _)| name == "getting_started")
+        .unwrap();
+    assert!(path.ends_with("examples/getting_started"));
+    assert_eq!(butane_cli::member_path("getting_started").unwrap(), *path);
+    assert!(butane_cli::member_path("no_such_member").is_err());
+}
@@ -0,0 +1,218 @@
+use butane::migrations::adb::{
+    AColumn, ARef, ARefLiteral, ATable, DeferredSqlType, TypeIdentifier, ADB,
+};
+use butane::SqlType;
+use butane_cli::{schema_graph, GraphFormat, StructNaming};
+
+fn known(ty: SqlType) -> DeferredSqlType {
+    DeferredSqlType::KnownId(TypeIdentifier::Ty(ty))
+}
+
+fn reference(table: &str, column: &str) -> Option<ARef> {
+    Some(ARef::Literal(ARefLiteral::new(table, column)))
+}
+
+fn table(name: &str, columns: Vec<AColumn>) -> ATable {
+    let mut table = ATable::new(name.to_string());
+    for column in columns {
+        table.add_column(column);
+    }
+    table
+}
+
+fn blog_db() -> ADB {
+    let mut db = ADB::new();
+    db.replace_table(table(
+        "blog",
+        vec![
+            AColumn::new(
+                "id",
+                known(SqlType::BigInt),
+                false,
+                true,
+                true,
+                false,
+                None,
+                None,
+            ),
+            AColumn::new(
+                "name",
+                known(SqlType::Text),
+                false,
+                false,
+                false,
+                true,
+                None,
+                None,
+            ),
+        ],
+    ));
+    db.replace_table(table(
+        "blog_post",
+        vec![
+            AColumn::new(
+                "id",
+                known(SqlType::Int),
+                false,
+                true,
+                false,
+                false,
+                None,
+                None,
+            ),
+            AColumn::new(
+                "blog",
+                known(SqlType::BigInt),
+                false,
+                false,
+                false,
+                false,
+                None,
+                reference("blog", "id"),
+            ),
+            AColumn::new(
+                "body",
+                known(SqlType::Text),
+                true,
+                false,
+                false,
+                false,
+                None,
+                None,
+            ),
+        ],
+    ));
+    db.replace_table(table(
+        "Tag",
+        vec![AColumn::new(
+            "tag",
+            known(SqlType::Text),
+            false,
+            true,
+            false,
+            false,
+            None,
+            None,
+        )],
+    ));
+    db.replace_table(table(
+        "blog_post_tags_many",
+        vec![
+            AColumn::new(
+                "owner",
+                known(SqlType::Int),
+                false,
+                false,
+                false,
+                false,
+                None,
+                reference("blog_post", "id"),
+            ),
+            AColumn::new(
+                "has",
+                known(SqlType::Text),
+                false,
+                false,
+                false,
+                false,
+                None,
+                reference("Tag", "tag"),
+            ),
+        ],
+    ));
+    db.replace_table(table(
+        "log",
+        vec![AColumn::new_simple("line", known(SqlType::Text))],
+    ));
+    db
+}
+
+#[test]
+fn models_source() {
+    let db = blog_db();
+    let source = butane_cli::models_source(&db, StructNaming::Pascal);
+    assert!(source.contains("use butane::{model, AutoPk, ForeignKey, Many};"));
+    assert!(source.contains(
+        "#[model]\n#[table = \"blog\"]\n#[derive(Debug)]\npub struct Blog {\n    pub id: AutoPk<i64>,\n    #[unique]\n    pub name: String,\n}\n"
+    ));
+    assert!(source.contains("pub struct BlogPost {\n    pub id: i32,\n    pub blog: ForeignKey<Blog>,\n    pub body: Option<String>,\n    #[many_table = \"blog_post_tags_many\"]\n    pub tags: Many<Tag>,\n}\n"));
+    assert!(source.contains(
+        "#[model]\n#[derive(Debug)]\npub struct Tag {\n    #[pk]\n    pub tag: String,\n}\n"
+    ));
+    assert!(source.contains("// Table log has no primary key and cannot be a model."));
+    assert!(!source.contains("struct BlogPostTagsMany"));
+}
+
+#[test]
+fn models_module() {
+    let mut db = blog_db();
+    let mut posts = db.get_table("blog_post").unwrap().clone();
+    posts.name = "blog_posts".to_string();
+    db.remove_table("blog_post");
+    db.remove_table("blog_post_tags_many");
+    db.replace_table(posts);
+
+    let files = butane_cli::models_module(&db, StructNaming::Singular);
+    let file = |name: &str| {
+        files
+            .iter()
+            .find(|(file, _)| file == name)
+            .map(|(_, src)| src.as_str())
+            .unwrap_or_else(|| panic!("no file {name}"))
+    };
+    assert_eq!(files.len(), 4);
+    let mod_rs = file("mod.rs");
+    assert!(mod_rs.contains("mod blog;\npub use blog::Blog;\n"));
+    assert!(mod_rs.contains("mod blog_post;\npub use blog_post::BlogPost;\n"));
+    assert!(mod_rs.contains("// Table log has no primary key and cannot be a model."));
+    assert!(file("blog_post.rs").contains(
+        "use butane::{model, ForeignKey};\n\nuse super::Blog;\n\n#[model]\n#[table = \"blog_posts\"]\n#[derive(Debug)]\npub struct BlogPost {\n    pub id: i32,\n    pub blog: ForeignKey<Blog>,\n"
+    ));
+    assert!(file("tag.rs").contains("use butane::model;\n\n#[model]\n"));
+}
+
+#[test]
+fn schema_graph_mermaid() {
+    let diagram = schema_graph(&blog_db(), GraphFormat::Mermaid);
+    assert_eq!(
+        diagram,
+        "erDiagram
+    Tag {
+        Text tag PK
+    }
+    blog {
+        BigInt id PK
+        Text name
+    }
+    blog_post {
+        Int id PK
+        BigInt blog FK
+        Text body
+    }
+    log {
+        Text line
+    }
+    blog_post }o--|| blog : \"blog\"
+    blog_post }o--o{ Tag : \"tags\"
+"
+    );
+}
+
+#[test]
+fn schema_graph_dot() {
+    let diagram = schema_graph(&blog_db(), GraphFormat::Dot);
+    assert!(diagram.starts_with("digraph schema {\n"), "{diagram}");
+    assert!(
+        diagram.contains("<td align=\"left\" port=\"blog\">blog: BigInt (FK)</td>"),
+        "{diagram}"
+    );
+    assert!(
+        diagram.contains("  \"blog_post\":\"blog\" -> \"blog\":\"id\";\n"),
+        "{diagram}"
+    );
+    assert!(
+        diagram.contains("  \"blog_post\" -> \"Tag\" [label=\"tags\""),
+        "{diagram}"
+    );
+    assert!(!diagram.contains("blog_post_tags_many"), "{diagram}");
+}
@@ -0,0 +1,64 @@
+use butane::db::{ConnectionMethods, ConnectionSpec};
+use butane_cli::Shell;
+
+fn blog() -> (butane::db::Connection, Shell) {
+    let conn = butane::db::connect(&ConnectionSpec::new("sqlite", ":memory:")).unwrap();
+    conn.execute(
+        "CREATE TABLE post (id INTEGER NOT NULL PRIMARY KEY, title TEXT NOT NULL, byline TEXT, likes INTEGER NOT NULL);
+         INSERT INTO post VALUES (1, 'First', NULL, 3), (2, 'It''s', 'me', 7), (3, 'Third', 'you', 1);",
+    )
+    .unwrap();
+    let shell = Shell::new(conn.introspect().unwrap()).unwrap();
+    (conn, shell)
+}
+
+#[test]
+fn shell_query() {
+    let (conn, shell) = blog();
+    let output = shell
+        .run(
+            &conn,
+            "post where likes>=2 and byline is not null order by id desc limit 5",
+        )
+        .unwrap();
+    assert_eq!(
+        output,
+        "id | title | byline | likes\n\
+         ---+-------+--------+------\n\
+         2  | It's  | me     | 7\n\
+         (1 row)"
+    );
+    let output = shell
+        .run(&conn, "Post where title = 'It''s' or likes = 1")
+        .unwrap_err();
+    assert!(output.to_string().contains("Unexpected or"), "{output}");
+    let output = shell.run(&conn, "post where byline is null").unwrap();
+    assert!(
+        output.contains("First") && output.ends_with("(1 row)"),
+        "{output}"
+    );
+    let output = shell.run(&conn, "post order by likes").unwrap();
+    let titles: Vec<&str> = output
+        .lines()
+        .skip(2)
+        .filter_map(|l| l.split(" | ").nth(1))
+        .map(str::trim)
+        .collect();
+    assert_eq!(titles, ["Third", "First", "It's"]);
+}
+
+#[test]
+fn shell_errors() {
+    let (conn, shell) = blog();
+    let error = |line: &str| shell.run(&conn, line).unwrap_err().to_string();
+    assert_eq!(error("nope"), "No table nope");
+    assert_eq!(
+        error("post where nope = 1"),
+        "Table post has no column nope"
+    );
+    assert_eq!(
+        error("post where likes = 'x'"),
+        "'x' is not a valid value for column likes"
+    );
+    assert_eq!(error("post where title = 'x"), "Unterminated string");
+}
@@ -57,10 +57,14 @@ fn handle_bin_op(fields: &impl ToTokens, binop: &ExprBinary) -> TokenStream2 {
 fn handle_call(fields: &impl ToTokens, mcall: &ExprMethodCall) -> TokenStream2 {
     let method = mcall.method.to_string();
     match method.as_str() {
-        "contains" | "matches" => {
-            if mcall.args.len() != 1 {
-                return make_compile_error!(mcall.span()=> "expected one argument to '{}'", method);
-            };
+        "contains" | "matches" | "overlaps" | "has_flag" | "has_any" if mcall.args.len() != 1 => {
+            return make_compile_error!(mcall.span()=> "expected one argument to '{}'", method);
+        }
+        "year" | "month" | "day" | "hour" | "minute" | "second" if !mcall.args.is_empty() => {
+            return make_compile_error!(mcall.span()=> "expected no arguments to '{}'", method);
+        }
+        "path_eq" | "key_equals" if mcall.args.len() != 2 => {
+            return make_compile_error!(mcall.span()=> "expected two arguments to '{}'", method);
         }
         _ => (),
     };
@@ -68,6 +72,15 @@ fn handle_call(fields: &impl ToTokens, mcall: &ExprMethodCall) -> TokenStream2 {
         "matches" => handle_in(fields, &mcall.receiver, mcall.args.first().unwrap()),
         "contains" => handle_contains(fields, &mcall.receiver, mcall.args.first().unwrap()),
         "like" => handle_like(fields, &mcall.receiver, mcall.args.first().unwrap()),
+        "overlaps" => handle_overlaps(fields, &mcall.receiver, mcall.args.first().unwrap()),
+        "has_flag" | "has_any" => {
+            handle_flags(fields, &mcall.method, &mcall.receiver, &mcall.args[0])
+        }
+        "path_eq" => handle_path_eq(fields, &mcall.receiver, &mcall.args[0], &mcall.args[1]),
+        "key_equals" => handle_key_equals(fields, &mcall.receiver, &mcall.args[0], &mcall.args[1]),
+        "year" | "month" | "day" | "hour" | "minute" | "second" => {
+            handle_date_part(fields, &mcall.method, &mcall.receiver)
+        }
         _ => make_compile_error!("Unknown method call {}", method),
     }
 }
@@ -112,6 +125,55 @@ fn handle_like(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStr
     }
 }
 
+fn handle_overlaps(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStream2 {
+    let fex = fieldexpr(fields, receiver);
+    let q = handle_expr(fields, expr);
+    let span = receiver.span();
+    quote_spanned!(span=> #fex.overlaps(&#q))
+}
+
+fn handle_flags(
+    fields: &impl ToTokens,
+    method: &Ident,
+    receiver: &Expr,
+    flags: &Expr,
+) -> TokenStream2 {
+    let fex = fieldexpr(fields, receiver);
+    // The flags are never a field, so are taken as they are.
+    let span = receiver.span();
+    quote_spanned!(span=> #fex.#method(#flags))
+}
+
+fn handle_path_eq(
+    fields: &impl ToTokens,
+    receiver: &Expr,
+    path: &Expr,
+    expr: &Expr,
+) -> TokenStream2 {
+    let fex = fieldexpr(fields, receiver);
+    let q = handle_expr(fields, expr);
+    let span = receiver.span();
+    quote_spanned!(span=> #fex.path_eq(&#path, #q))
+}
+
+fn handle_key_equals(
+    fields: &impl ToTokens,
+    receiver: &Expr,
+    key: &Expr,
+    expr: &Expr,
+) -> TokenStream2 {
+    let fex = fieldexpr(fields, receiver);
+    let q = handle_expr(fields, expr);
+    let span = receiver.span();
+    quote_spanned!(span=> #fex.key_equals(#key, #q))
+}
+
+fn handle_date_part(fields: &impl ToTokens, part: &Ident, receiver: &Expr) -> TokenStream2 {
+    let fex = fieldexpr(fields, receiver);
+    let span = receiver.span();
+    quote_spanned!(span=> #fex.#part())
+}
+
 fn handle_path(fields: &impl ToTokens, expr: &ExprPath) -> TokenStream2 {
     if expr.path.is_ident("None") {
         return quote!(None);
@@ -33,6 +33,27 @@
 ///   (perhaps implemented as the SQL UNIQUE constraint by some backends).
 /// * `#[default]` should be used on fields added by later migrations to avoid errors on existing objects.
 ///   Unnecessary if the new field is an `Option<>`
+/// * `#[many_table = "NAME"]` on a [`Many`] field to specify the name of the join table
+///   (defaults to `<table>_<field>_Many`). The column names may also be chosen with
+///   `#[many_table(name = "NAME", owner = "OWNER_COLUMN", has = "HAS_COLUMN")]`.
+/// * `#[deferrable]` on a [`ForeignKey`] field makes its constraint `DEFERRABLE INITIALLY DEFERRED`,
+///   so it is only checked when the transaction commits.
+/// * `#[renamed_from = "OLD_NAME"]` on a field that was renamed, so the next migration renames
+///   the column instead of dropping the old column and adding a new one.
+///   On the struct, it gives the previous name of the table, so the next migration renames the
+///   table and its `Many` tables. A table with the same columns as a single dropped table is
+///   renamed even without it.
+/// * `#[index]` on a field creates an index on its column, named `<table>_<field>_idx`.
+///   The name may be given with `#[index = "NAME"]`, or with
+///   `#[index(name = "NAME", unique, method = "METHOD")]` which can also make the index
+///   unique and choose the index method (e.g. `gin` on Postgres; ignored by SQLite).
+/// * `#[check = "EXPR"]` on a field adds a check constraint named `<table>_<field>_check`,
+///   requiring the SQL expression to hold for every row.
+/// * `#[timestamp(precision = 3)]` on a timestamp field keeps that many digits of the fraction
+///   of a second, at most 6, declaring the column e.g. `TIMESTAMP(3)` on Postgres. Values are
+///   truncated to it when saved, so every backend stores the same value.
+/// * `#[sqlite(strict = false, without_rowid)]` on the struct chooses whether SQLite creates the
+///   table `STRICT` (the default) and `WITHOUT ROWID` (not the default). Other backends ignore it.
 ///
 /// For example
 /// ```ignore
@@ -51,6 +72,7 @@
 ///
 /// [`FieldType`]: crate::FieldType
 /// [`Many`]: butane_core::many::Many
+/// [`ForeignKey`]: butane_core::fkey::ForeignKey
 #[proc_macro_attribute]
 pub fn model(_args: TokenStream, input: TokenStream) -> TokenStream {
     codegen::model_with_migrations(input.into(), &mut migrations_for_dir()).into()
@@ -111,7 +133,19 @@ pub fn dataresult(args: TokenStream, input: TokenStream) -> TokenStream {
 ///   `tags: Many<Tag>` we could filter to posts with a "cats" with
 ///   the following `tags.contains(tag == "cats"). If the expression
 ///   is single literal, it is assumed to be used to match the
-///   primary key.
+///   primary key. Used with an array field, such as `scores: Vec<i64>`,
+///   the parameter is instead a value, and `scores.contains(10)` is true
+///   if the array contains it. Used with a [`Json`] field, the parameter
+///   is JSON contained in the field, e.g. `data.contains({ json!({"a": 1}) })`.
+/// * `overlaps`: Parameter is a slice of values. Use with an array
+///   field to evaluate as true if the array has an element in common
+///   with the slice, e.g. `labels.overlaps({ ["a".to_string()] })`.
+/// * `path_eq`: Parameters are a path of object keys or array indices
+///   and a value. Use with a [`Json`] field to evaluate as true if the
+///   JSON at the path equals the value, e.g. `data.path_eq(["a", "0"], 1)`.
+/// * `has_flag` and `has_any`: Parameter is a set of flags. Use with a
+///   [`FlagSet`] field to evaluate as true if every or any of the flags
+///   is set, e.g. `perms.has_flag(Perms::READ | Perms::WRITE)`.
 ///
 /// # Examples
 /// ```ignore
@@ -134,6 +168,8 @@ pub fn dataresult(args: TokenStream, input: TokenStream) -> TokenStream {
 /// [`BoolExpr`]: butane_core::query::BoolExpr
 /// [`ForeignKey`]: butane_core::fkey::ForeignKey
 /// [`Many`]: butane_core::many::Many
+/// [`FlagSet`]: butane_core::FlagSet
+/// [`Json`]: butane_core::Json
 /// [`Query`]: butane_core::query::Query
 #[proc_macro]
 pub fn filter(input: TokenStream) -> TokenStream {
@@ -205,20 +241,66 @@ pub fn filter(input: TokenStream) -> TokenStream {
 ///  }
 /// }
 /// ```
+///
+/// `Custom(name)` gives a type the database knows by `name`. With
+/// `domain = "<definition>"` or `composite = "<definition>"` it is a
+/// user-defined type, a Postgres `DOMAIN` or composite `TYPE`, which
+/// the migrations create, redefine and drop:
+///
+/// ```ignore
+/// #[butane_type(Custom(email_address), domain = "TEXT CHECK (VALUE LIKE '%@%')")]
+/// pub struct Email(String);
+/// ```
 #[proc_macro_attribute]
 pub fn butane_type(args: TokenStream, input: TokenStream) -> TokenStream {
     codegen::butane_type_with_migrations(args.into(), input.into(), &mut migrations_for_dir())
         .into()
 }
 
+/// Macro which embeds the migrations in a directory, read when the
+/// crate is compiled, and expands to an expression loading them as
+/// [`MemMigrations`]. Its value is a `Result<MemMigrations, butane::Error>`.
+///
+/// The directory is relative to the crate's `Cargo.toml`, and defaults
+/// to `.butane/migrations`. The crate is rebuilt when the migrations
+/// change.
+///
+/// E.g.
+/// ```ignore
+/// pub fn get_migrations() -> Result<MemMigrations, butane::Error> {
+///     butane::embed_migrations!(".butane/migrations")
+/// }
+/// ```
+///
+/// [`MemMigrations`]: butane_core::migrations::MemMigrations
+#[proc_macro]
+pub fn embed_migrations(input: TokenStream) -> TokenStream {
+    let dir = if input.is_empty() {
+        migrations_dir()
+    } else {
+        match syn::parse::<syn::LitStr>(input) {
+            Ok(path) => manifest_dir().join(path.value()),
+            Err(_) => {
+                return make_compile_error!("Expected embed_migrations!(\"path/to/migrations\")")
+                    .into()
+            }
+        }
+    };
+    codegen::embed_migrations(&dir).into()
+}
+
 fn migrations_for_dir() -> migrations::FsMigrations {
     migrations::from_root(migrations_dir())
 }
 
-fn migrations_dir() -> PathBuf {
-    let mut dir = PathBuf::from(
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(
         std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR expected to be set"),
-    );
+    )
+}
+
+fn migrations_dir() -> PathBuf {
+    let mut dir = manifest_dir();
     dir.push(".butane");
     dir.push("migrations");
     dir
@@ -435,6 +517,79 @@ fn derive_field_type_with_json(_struct_name: &Ident) -> TokenStream {
     panic!("Feature 'json' is required to derive FieldType")
 }
 
+/// Derive macro for a factory building a model for tests, named as
+/// the model followed by `Factory`. Place it after `#[model]`.
+///
+/// The factory has a method setting each field, and gives the fields
+/// which are not set their defaults: `Default::default()`, or
+/// * `#[factory(default = EXPR)]` on a field for the value of `EXPR`;
+/// * `#[factory(sequence)]` on a field for a number unique within the process,
+///   which is the default of a field named `id` of an integer or `String` type.
+///
+/// A required [`ForeignKey`] field which is not set refers to a parent created by
+/// the parent model's own factory when the factory's `create` saves the model.
+/// E.g.
+/// ```ignore
+/// #[model]
+/// #[derive(Factory)]
+/// pub struct Post {
+///   pub id: AutoPk<i64>,
+///   #[factory(default = "Untitled".to_string())]
+///   pub title: String,
+///   pub published: bool,
+///   pub blog: ForeignKey<Blog>,
+/// }
+///
+/// let post = PostFactory::new().published(true).create(&conn)?;
+/// ```
+///
+/// [`ForeignKey`]: butane_core::fkey::ForeignKey
+#[cfg(feature = "test-utils")]
+#[proc_macro_derive(Factory, attributes(factory))]
+pub fn derive_factory(input: TokenStream) -> TokenStream {
+    codegen::derive_factory(input.into()).into()
+}
+
+/// Attribute macro for a test run on a database with the crate's
+/// migrations applied. Requires the `test-utils` feature.
+///
+/// The test function takes the connection as its only argument, a
+/// [`Connection`], or if it is `async` a [`ConnectionAsync`]. The
+/// migrations are those embedded by [`embed_migrations!`] from
+/// `.butane/migrations`, or from the directory given by
+/// `migrations = "<dir>"`. The database is torn down after the test.
+///
+/// The backends are given as arguments: `sqlite` for an in-memory
+/// SQLite database, the default, and `pg` for a database created on
+/// the Postgres server whose connection string is in the environment
+/// variable `BUTANE_PG_CONNSTR`. With more than one, a test is
+/// generated for each, its name suffixed with the backend's.
+/// An `async` test runs on a [`tokio`](https://crates.io/crates/tokio)
+/// runtime, so the crate must depend on `tokio`.
+///
+/// E.g.
+/// ```ignore
+/// #[butane::test(sqlite, pg)]
+/// fn create_blog(conn: Connection) {
+///     let mut blog = Blog::new("Cats");
+///     blog.save(&conn).unwrap();
+/// }
+///
+/// #[butane::test(migrations = "tests/.butane/migrations")]
+/// async fn create_blog_async(conn: ConnectionAsync) {
+///     let mut blog = Blog::new("Cats");
+///     blog.save(&conn).await.unwrap();
+/// }
+/// ```
+///
+/// [`Connection`]: butane_core::db::Connection
+/// [`ConnectionAsync`]: butane_core::db::ConnectionAsync
+#[cfg(feature = "test-utils")]
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, input: TokenStream) -> TokenStream {
+    codegen::test_fn(args.into(), input.into()).into()
+}
+
 /// Derive macro for marker trait `PrimaryKeyType`.
 /// E.g.
 /// ```ignore
@@ -0,0 +1,145 @@
+//! Contains the [CiString] type for case-insensitive text.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Error, FieldType, FromSql, PrimaryKeyType, Result, SqlType, SqlVal, SqlValRef, ToSql};
+
+/// A string which the database compares without regard to case,
+/// stored as a `CITEXT` on PostgreSQL and as text with the `NOCASE`
+/// collation on SQLite. A unique `CiString` field cannot hold two
+/// values differing only in case, and a filter such as
+/// `query!(User, email == "Alice@Example.com")` finds
+/// `alice@example.com`. Dereferences to the string as written.
+///
+/// Values compare equal in Rust if their lowercase forms are equal.
+/// SQLite folds only the case of ASCII letters.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct CiString(pub String);
+
+impl CiString {
+    /// Unwrap the string.
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+
+    /// The string as written.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn folded(&self) -> Cow<'_, str> {
+        fold(&self.0)
+    }
+}
+
+/// The form of `s` compared: lowercase, borrowing `s` when it is
+/// already.
+fn fold(s: &str) -> Cow<'_, str> {
+    if s.chars().any(char::is_uppercase) {
+        Cow::Owned(s.to_lowercase())
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+impl Deref for CiString {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CiString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<String> for CiString {
+    fn from(s: String) -> Self {
+        CiString(s)
+    }
+}
+
+impl From<&str> for CiString {
+    fn from(s: &str) -> Self {
+        CiString(s.to_string())
+    }
+}
+
+impl PartialEq for CiString {
+    fn eq(&self, other: &CiString) -> bool {
+        self.folded() == other.folded()
+    }
+}
+impl Eq for CiString {}
+
+impl PartialEq<str> for CiString {
+    fn eq(&self, other: &str) -> bool {
+        self.folded() == fold(other)
+    }
+}
+
+impl PartialEq<&str> for CiString {
+    fn eq(&self, other: &&str) -> bool {
+        self.folded() == fold(other)
+    }
+}
+
+impl PartialEq<String> for CiString {
+    fn eq(&self, other: &String) -> bool {
+        self.folded() == fold(other)
+    }
+}
+
+impl Hash for CiString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.folded().hash(state)
+    }
+}
+
+impl PartialOrd for CiString {
+    fn partial_cmp(&self, other: &CiString) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CiString {
+    fn cmp(&self, other: &CiString) -> Ordering {
+        self.folded().cmp(&other.folded())
+    }
+}
+
+impl FromSql for CiString {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::Text(val) => Ok(CiString(val.to_string())),
+            _ => Err(Error::CannotConvertSqlVal(SqlType::CiText, valref.into())),
+        }
+    }
+}
+
+impl ToSql for CiString {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Text(self.0.clone())
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Text(&self.0)
+    }
+    fn into_sql(self) -> SqlVal {
+        SqlVal::Text(self.0)
+    }
+}
+
+impl FieldType for CiString {
+    const SQLTYPE: SqlType = SqlType::CiText;
+    type RefType = Self;
+}
+impl PrimaryKeyType for CiString {}
@@ -4,16 +4,23 @@
 use syn::{spanned::Spanned, Field, ItemStruct, LitStr};
 
 use super::{
-    fields, get_autopk_sql_type, get_type_argument, is_auto, is_many_to_many, is_row_field,
-    make_ident_literal_str, make_lit, pk_field, MANY_TYNAMES,
+    field_index, fields, get_autopk_sql_type, get_type_argument, is_auto, is_deferrable,
+    is_foreign_key, is_large_blob, is_many_to_many, is_money, is_row_field, make_ident_literal_str,
+    make_lit, many_table_names, money_currency_column, pk_field, MANY_TYNAMES,
 };
-use crate::migrations::adb::{DeferredSqlType, TypeIdentifier, MANY_SUFFIX};
+use crate::migrations::adb::{DeferredSqlType, TypeIdentifier};
 use crate::SqlType;
 
 /// Configuration that can be specified with attributes to override default behavior
 #[derive(Clone, Debug, Default)]
 pub struct Config {
     pub table_name: Option<String>,
+    /// Previous name of the table, if it has been renamed.
+    pub renamed_from: Option<String>,
+    /// Whether the table is `STRICT` on SQLite, if given.
+    pub sqlite_strict: Option<bool>,
+    /// Whether the table is `WITHOUT ROWID` on SQLite, if given.
+    pub sqlite_without_rowid: Option<bool>,
 }
 
 /// Code generation to implement the DataObject trait for a model
@@ -159,7 +166,9 @@ fn as_pk(&self) -> std::borrow::Cow<<#tyname as butane::DataObject>::PKType> {
 /// Code generation to implement the DataResult trait for a model
 pub fn impl_dataresult(ast_struct: &ItemStruct, dbo: &Ident, config: &Config) -> TokenStream2 {
     let tyname = &ast_struct.ident;
-    let numdbfields = fields(ast_struct).filter(|f| is_row_field(f)).count();
+    // A Money field has a second column for its currency.
+    let numdbfields = fields(ast_struct).filter(|f| is_row_field(f)).count()
+        + fields(ast_struct).filter(|f| is_money(f)).count();
     let rows = rows_for_from(ast_struct);
     let cols = columns(ast_struct, |_| true);
 
@@ -167,17 +176,24 @@ pub fn impl_dataresult(ast_struct: &ItemStruct, dbo: &Ident, config: &Config) ->
         .filter(|f| is_many_to_many(f))
         .map(|f| {
             let ident = f.ident.clone().expect("Fields must be named for butane");
-            let many_table_lit = many_table_lit(ast_struct, f, config);
+            let [many_table_lit, owner_lit, has_lit] = many_table_lits(ast_struct, f, config);
             let pksqltype =
                 quote!(<<Self as butane::DataObject>::PKType as butane::FieldType>::SQLTYPE);
             quote!(
                 obj.#ident.ensure_init(
                     #many_table_lit,
+                    #owner_lit,
+                    #has_lit,
                     butane::ToSql::to_sql(obj.pk()),
                     #pksqltype,
                 );
             )
         })
+        .chain(fields(ast_struct).filter(|f| is_large_blob(f)).map(|f| {
+            let ident = f.ident.clone().expect("Fields must be named for butane");
+            let init = large_blob_init(f, quote!(&obj));
+            quote!(obj.#ident.#init;)
+        }))
         .collect();
 
     let from_row_body = if many_init.is_empty() {
@@ -231,7 +247,9 @@ fn make_tablelit(config: &Config, tyname: &Ident) -> LitStr {
 pub fn add_fieldexprs(ast_struct: &ItemStruct, config: &Config) -> TokenStream2 {
     let tyname = &ast_struct.ident;
     let vis = &ast_struct.vis;
+    // Large blobs are not loaded with their objects, so cannot be filtered on.
     let fieldexprs: Vec<TokenStream2> = fields(ast_struct)
+        .filter(|f| !is_large_blob(f))
         .map(|f| {
             if is_many_to_many(f) {
                 fieldexpr_func_many(f, ast_struct, config)
@@ -276,12 +294,16 @@ fn fieldexpr_func_regular(f: &Field, ast_struct: &ItemStruct) -> TokenStream2 {
 fn fieldexpr_func_many(f: &Field, ast_struct: &ItemStruct, config: &Config) -> TokenStream2 {
     let tyname = &ast_struct.ident;
     let fty = get_type_argument(&f.ty, &MANY_TYNAMES).expect("Many field misdetected");
-    let many_table_lit = many_table_lit(ast_struct, f, config);
+    let [many_table_lit, owner_lit, has_lit] = many_table_lits(ast_struct, f, config);
     fieldexpr_func(
         f,
         ast_struct,
         quote!(butane::query::ManyFieldExpr<#tyname, #fty>),
-        quote!(butane::query::ManyFieldExpr::<#tyname, #fty>::new(#many_table_lit)),
+        quote!(butane::query::ManyFieldExpr::<#tyname, #fty>::new(
+            #many_table_lit,
+            #owner_lit,
+            #has_lit
+        )),
     )
 }
 
@@ -327,12 +349,26 @@ fn fields_type(tyname: &Ident) -> Ident {
     Ident::new(&format!("{tyname}Fields"), Span::call_site())
 }
 
+/// The field initializers of `from_row`. Each row field is read from
+/// the column at its position in `COLUMNS`, which is fixed when the
+/// model is compiled.
 fn rows_for_from(ast_struct: &ItemStruct) -> Vec<TokenStream2> {
     let mut i: usize = 0;
     fields(ast_struct)
         .map(|f| {
             let ident = f.ident.clone().unwrap();
-            if is_row_field(f) {
+            if is_money(f) {
+                let fty = &f.ty;
+                let currency = i + 1;
+                let ret = quote!(
+                    #ident: <#fty as butane::money::MoneyField>::from_sql_with_currency(
+                        row.get(#i, <#fty as butane::FieldType>::SQLTYPE)?,
+                        row.get(#currency, butane::SqlType::Text)?,
+                    )?
+                );
+                i += 2;
+                ret
+            } else if is_row_field(f) {
                 let fty = &f.ty;
                 let ret = quote!(
                     #ident: butane::FromSql::from_sql_ref(
@@ -343,6 +379,8 @@ fn rows_for_from(ast_struct: &ItemStruct) -> Vec<TokenStream2> {
                 ret
             } else if is_many_to_many(f) {
                 quote!(#ident: butane::Many::new())
+            } else if is_large_blob(f) {
+                quote!(#ident: butane::LargeBlob::new())
             } else {
                 make_compile_error!(f.span()=> "Unexpected struct field")
             }
@@ -360,7 +398,14 @@ fn columns<P>(ast_struct: &ItemStruct, mut predicate: P) -> TokenStream2
             Some(fname) => {
                 let ident = make_ident_literal_str(&fname);
                 let fty = &f.ty;
-                quote!(butane::db::Column::new(#ident, <#fty as butane::FieldType>::SQLTYPE),)
+                let column =
+                    quote!(butane::db::Column::new(#ident, <#fty as butane::FieldType>::SQLTYPE),);
+                if is_money(f) {
+                    let currency = make_lit(&money_currency_column(&fname.to_string()));
+                    quote!(#column butane::db::Column::new(#currency, butane::SqlType::Text),)
+                } else {
+                    column
+                }
             }
             None => quote_spanned! {
                 f.span() =>
@@ -370,17 +415,21 @@ fn columns<P>(ast_struct: &ItemStruct, mut predicate: P) -> TokenStream2
         .collect()
 }
 
-fn many_table_lit(ast_struct: &ItemStruct, field: &Field, config: &Config) -> LitStr {
-    let ident = field
-        .ident
-        .clone()
-        .expect("Fields must be named for butane");
+/// Literals for the table, owner column and has column names of the
+/// join table backing a `Many` field.
+fn many_table_lits(ast_struct: &ItemStruct, field: &Field, config: &Config) -> [LitStr; 3] {
     let binding = ast_struct.ident.to_string();
     let tyname = match &config.table_name {
         Some(s) => s,
         None => &binding,
     };
-    make_lit(&format!("{}_{}{MANY_SUFFIX}", &tyname, &ident))
+    let names = many_table_names(field, tyname)
+        .unwrap_or_else(|_| panic!("Malformed many_table attribute"));
+    [
+        make_lit(&names.table),
+        make_lit(&names.owner),
+        make_lit(&names.has),
+    ]
 }
 
 fn verify_fields(ast_struct: &ItemStruct) -> Option<TokenStream2> {
@@ -390,6 +439,33 @@ fn verify_fields(ast_struct: &ItemStruct) -> Option<TokenStream2> {
     };
     let pk_field = pk_field.unwrap();
     for f in fields(ast_struct) {
+        if is_many_to_many(f) {
+            if let Err(err) = many_table_names(f, "") {
+                return Some(err.ts);
+            }
+        }
+        match field_index(f, "") {
+            Err(err) => return Some(err.ts),
+            Ok(Some(_)) if is_many_to_many(f) => {
+                return Some(quote_spanned!(
+                    f.span() =>
+                        compile_error!("index is not supported on Many fields");
+                ))
+            }
+            Ok(Some(_)) if is_large_blob(f) => {
+                return Some(quote_spanned!(
+                    f.span() =>
+                        compile_error!("index is not supported on LargeBlob fields");
+                ))
+            }
+            Ok(_) => (),
+        }
+        if is_deferrable(f) && !is_foreign_key(f) {
+            return Some(quote_spanned!(
+                f.span() =>
+                    compile_error!("deferrable is only supported on ForeignKey fields");
+            ));
+        }
         if is_auto(f) {
             match get_autopk_sql_type(&f.ty) {
                 Some(DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int))) => (),
@@ -421,17 +497,75 @@ fn push_values<P>(ast_struct: &ItemStruct, mut predicate: P) -> Vec<TokenStream2
         .filter(|f| is_row_field(f) && !is_auto(f) && predicate(f))
         .map(|f| {
             let ident = f.ident.clone().unwrap();
-            quote!(values.push(butane::ToSql::to_sql_ref(&self.#ident));)
+            if is_money(f) {
+                quote!(
+                    values.push(butane::ToSql::to_sql_ref(&self.#ident));
+                    values.push(butane::money::MoneyField::currency_sql(&self.#ident));
+                )
+            } else {
+                let value = quote!(butane::ToSql::to_sql_ref(&self.#ident));
+                let value = with_timestamp_precision(f, value);
+                quote!(values.push(#value);)
+            }
         })
         .collect()
 }
 
+/// The value `value` of `field`, truncated to the precision of a
+/// `#[timestamp(precision = 3)]` attribute on the field if it has one,
+/// so that each backend stores the same fraction of a second.
+#[cfg(feature = "datetime")]
+fn with_timestamp_precision(field: &Field, value: TokenStream2) -> TokenStream2 {
+    match super::get_timestamp_precision(field, &super::get_deferred_sql_type(&field.ty)) {
+        Ok(Some(digits)) => quote!(#value.with_precision(#digits)),
+        Ok(None) => value,
+        Err(err) => err.ts,
+    }
+}
+
+#[cfg(not(feature = "datetime"))]
+fn with_timestamp_precision(_field: &Field, value: TokenStream2) -> TokenStream2 {
+    value
+}
+
+/// The call initializing a `LargeBlob` field of the object `obj`,
+/// made on the field.
+fn large_blob_init(field: &Field, obj: TokenStream2) -> TokenStream2 {
+    let column_lit = field_ident_lit(field);
+    quote!(ensure_init(
+        <Self as butane::DataObject>::TABLE,
+        <Self as butane::DataObject>::PKCOL,
+        #column_lit,
+        butane::ToSql::to_sql(butane::DataObject::pk(#obj)),
+        <<Self as butane::DataObject>::PKType as butane::FieldType>::SQLTYPE,
+    ))
+}
+
+/// Saves the fields which are not columns of the row: the
+/// relationships of `Many` fields and any `LargeBlob` which has been
+/// set.
 fn impl_many_save(ast_struct: &ItemStruct, config: &Config, is_async: bool) -> TokenStream2 {
-    fields(ast_struct)
+    let blob_save: TokenStream2 = fields(ast_struct)
+        .filter(|f| is_large_blob(f))
+        .map(|f| {
+            let ident = f.ident.clone().expect("Fields must be named for butane");
+            let init = large_blob_init(f, quote!(self));
+            let save_with_conn = if is_async {
+                quote!(butane::LargeBlobOpsAsync::save(&mut self.#ident, conn).await?;)
+            } else {
+                quote!(butane::LargeBlobOpsSync::save(&mut self.#ident, conn)?;)
+            };
+            quote!(
+                self.#ident.#init;
+                #save_with_conn
+            )
+        })
+        .collect();
+    let many_save: TokenStream2 = fields(ast_struct)
         .filter(|f| is_many_to_many(f))
         .map(|f| {
             let ident = f.ident.clone().expect("Fields must be named for butane");
-            let many_table_lit = many_table_lit(ast_struct, f, config);
+            let [many_table_lit, owner_lit, has_lit] = many_table_lits(ast_struct, f, config);
             let pksqltype =
                 quote!(<<Self as butane::DataObject>::PKType as butane::FieldType>::SQLTYPE);
 
@@ -445,13 +579,16 @@ fn impl_many_save(ast_struct: &ItemStruct, config: &Config, is_async: bool) -> T
             quote!(
                 self.#ident.ensure_init(
                     #many_table_lit,
+                    #owner_lit,
+                    #has_lit,
                     butane::ToSql::to_sql(butane::DataObject::pk(self)),
                     #pksqltype,
                 );
                 #save_with_conn
             )
         })
-        .collect()
+        .collect();
+    quote!(#many_save #blob_save)
 }
 
 #[cfg(feature = "async")]
@@ -0,0 +1,208 @@
+use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{Ident, Span};
+use quote::quote;
+use syn::{parse_quote, Field, ItemStruct};
+
+use super::{fields, get_type_argument, is_row_field, FKEY_TYNAMES};
+
+/// How a factory gives a field a value when it is not set.
+enum FieldDefault {
+    /// `Default::default()`.
+    Default,
+    /// The next value of the factories' sequence.
+    Sequence,
+    /// The expression of a `#[factory(default = EXPR)]` attribute.
+    Expr(syn::Expr),
+}
+
+/// The factory of the model `input`, and its implementations of
+/// `Factory` and `HasFactory`.
+pub fn impl_factory(input: TokenStream2) -> TokenStream2 {
+    let ast_struct: ItemStruct = match syn::parse2(input) {
+        Ok(ast_struct) => ast_struct,
+        Err(err) => return err.to_compile_error(),
+    };
+    let tyname = &ast_struct.ident;
+    let vis = &ast_struct.vis;
+    let factory = Ident::new(&format!("{tyname}Factory"), Span::call_site());
+
+    let mut decls = Vec::new();
+    let mut setters = Vec::new();
+    let mut inits = Vec::new();
+    let mut parents = Vec::new();
+    for f in fields(&ast_struct) {
+        let ident = f.ident.clone().expect("Fields must be named for butane");
+        if !is_row_field(f) {
+            // Many and LargeBlob fields start empty.
+            inits.push(quote!(#ident: Default::default()));
+            continue;
+        }
+        let default = match field_default(f) {
+            Ok(default) => default,
+            Err(err) => return err.to_compile_error(),
+        };
+        let fty = &f.ty;
+        decls.push(quote!(#ident: Option<#fty>));
+        let doc = format!("Set `{ident}`.");
+        setters.push(quote!(
+            #[doc = #doc]
+            pub fn #ident(mut self, #ident: impl Into<#fty>) -> Self {
+                self.#ident = Some(#ident.into());
+                self
+            }
+        ));
+        match get_type_argument(fty, &FKEY_TYNAMES) {
+            // A required foreign key to another model is created by its
+            // own factory if not set. One to the same model would be
+            // created without end.
+            Some(parent) if !parent.is_ident(tyname) => {
+                let msg = format!("foreign key {ident} of {factory} not set");
+                inits.push(quote!(#ident: self.#ident.expect(#msg)));
+                parents.push((ident, parent.clone()));
+            }
+            _ => inits.push(match default {
+                FieldDefault::Default => quote!(#ident: self.#ident.unwrap_or_default()),
+                FieldDefault::Sequence => quote!(
+                    #ident: self.#ident.unwrap_or_else(|| {
+                        butane::test_utils::Sequence::from_sequence(
+                            butane::test_utils::next_sequence(),
+                        )
+                    })
+                ),
+                FieldDefault::Expr(expr) => quote!(#ident: self.#ident.unwrap_or_else(|| #expr)),
+            }),
+        }
+    }
+
+    let create_parents_sync = create_parents(&parents, false);
+    let create_parents_async = def_for_create_parents_async(&parents);
+    let conn_arg_name = if parents.is_empty() {
+        Ident::new("_conn", Span::call_site())
+    } else {
+        Ident::new("conn", Span::call_site())
+    };
+    let doc = format!(
+        "Factory building [`{tyname}`] for tests, giving the fields which are not set their defaults."
+    );
+
+    quote!(
+        #[doc = #doc]
+        #[derive(Default)]
+        #vis struct #factory {
+            #(#decls,)*
+        }
+        impl #factory {
+            /// A factory with none of its fields set.
+            pub fn new() -> Self {
+                Self::default()
+            }
+            #(#setters)*
+        }
+        impl butane::test_utils::Factory for #factory {
+            type Model = #tyname;
+
+            fn build(self) -> #tyname {
+                #tyname {
+                    #(#inits,)*
+                }
+            }
+            #create_parents_async
+            fn create_parents_sync(
+                &mut self,
+                #conn_arg_name: &impl butane::db::ConnectionMethods,
+            ) -> butane::Result<()> {
+                #create_parents_sync
+                Ok(())
+            }
+        }
+        impl butane::test_utils::HasFactory for #tyname {
+            type Factory = #factory;
+        }
+    )
+}
+
+/// The default of `field`, from its `#[factory(sequence)]` or
+/// `#[factory(default = EXPR)]` attribute. Without either, a field named
+/// `id` of an integer or string type is numbered by the sequence, as it
+/// is the primary key unless another field is declared `#[pk]`.
+fn field_default(field: &Field) -> syn::Result<FieldDefault> {
+    let mut default = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("factory"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("sequence") {
+                default = Some(FieldDefault::Sequence);
+            } else if meta.path.is_ident("default") {
+                default = Some(FieldDefault::Expr(meta.value()?.parse()?));
+            } else {
+                return Err(meta.error("expected `sequence` or `default`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(default.unwrap_or_else(|| {
+        let sequence_types: [syn::Type; 7] = [
+            parse_quote!(i16),
+            parse_quote!(i32),
+            parse_quote!(i64),
+            parse_quote!(u16),
+            parse_quote!(u32),
+            parse_quote!(u64),
+            parse_quote!(String),
+        ];
+        match &field.ident {
+            Some(ident) if ident == "id" && sequence_types.contains(&field.ty) => {
+                FieldDefault::Sequence
+            }
+            _ => FieldDefault::Default,
+        }
+    }))
+}
+
+/// Creates the parents of the required foreign keys `parents` which
+/// have not been set.
+fn create_parents(parents: &[(Ident, syn::Path)], is_async: bool) -> TokenStream2 {
+    parents
+        .iter()
+        .map(|(ident, parent)| {
+            let factory = quote!(<#parent as butane::test_utils::HasFactory>::factory());
+            let create = if is_async {
+                quote!(butane::test_utils::FactoryOpsAsync::create(#factory, conn).await?)
+            } else {
+                quote!(butane::test_utils::FactoryOpsSync::create(#factory, conn)?)
+            };
+            quote!(
+                if self.#ident.is_none() {
+                    self.#ident = Some(butane::ForeignKey::from(#create));
+                }
+            )
+        })
+        .collect()
+}
+
+#[cfg(feature = "async")]
+fn def_for_create_parents_async(parents: &[(Ident, syn::Path)]) -> TokenStream2 {
+    let create_parents_async = create_parents(parents, true);
+    let conn_arg_name = if parents.is_empty() {
+        Ident::new("_conn", Span::call_site())
+    } else {
+        Ident::new("conn", Span::call_site())
+    };
+    quote!(
+        async fn create_parents_async(
+            &mut self,
+            #conn_arg_name: &impl butane::db::ConnectionMethodsAsync,
+        ) -> butane::Result<()> {
+            #create_parents_async
+            Ok(())
+        }
+    )
+}
+
+#[cfg(not(feature = "async"))]
+fn def_for_create_parents_async(_parents: &[(Ident, syn::Path)]) -> TokenStream2 {
+    quote!()
+}
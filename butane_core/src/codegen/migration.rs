@@ -1,12 +1,17 @@
 use syn::{Field, ItemStruct};
 
 use super::{
-    dbobj, fields, get_default, get_deferred_sql_type, get_many_sql_type, is_auto, is_foreign_key,
-    is_many_to_many, is_option, is_row_field, is_unique, pk_field,
+    dbobj, field_index, fields, get_check, get_convert_using, get_default, get_deferred_sql_type,
+    get_many_sql_type, get_renamed_from, is_auto, is_deferrable, is_foreign_key, is_large_blob,
+    is_many_to_many, is_money, is_option, is_row_field, is_unique, many_table_names,
+    money_currency_column, pk_field,
+};
+use crate::migrations::adb::{
+    create_named_many_table, AColumn, AConstraint, ARef, ATable, DeferredSqlType,
+    SqliteTableOptions, TypeIdentifier, TypeKey,
 };
-use crate::migrations::adb::{create_many_table, AColumn, ARef, ATable, DeferredSqlType, TypeKey};
 use crate::migrations::{MigrationMut, MigrationsMut};
-use crate::Result;
+use crate::{Result, SqlType};
 
 pub fn write_table_to_disk<M>(
     ms: &mut impl MigrationsMut<M = M>,
@@ -16,8 +21,9 @@ pub fn write_table_to_disk<M>(
 where
     M: MigrationMut,
 {
+    let defaults = ms.default_sqlite_options()?;
     let current_migration = ms.current();
-    for table in create_atables(ast_struct, config) {
+    for table in create_atables(ast_struct, config, defaults) {
         current_migration.add_modified_table(&table)?;
     }
     if let Some(name) = &config.table_name {
@@ -31,12 +37,24 @@ pub fn write_table_to_disk<M>(
     Ok(())
 }
 
-fn create_atables(ast_struct: &ItemStruct, config: &dbobj::Config) -> Vec<ATable> {
+fn create_atables(
+    ast_struct: &ItemStruct,
+    config: &dbobj::Config,
+    defaults: SqliteTableOptions,
+) -> Vec<ATable> {
     let name = match &config.table_name {
         Some(n) => n.clone(),
         None => ast_struct.ident.to_string(),
     };
     let mut table = ATable::new(name);
+    table.set_renamed_from(config.renamed_from.clone());
+    let sqlite_options = SqliteTableOptions {
+        strict: config.sqlite_strict.unwrap_or(defaults.strict),
+        without_rowid: config
+            .sqlite_without_rowid
+            .unwrap_or(defaults.without_rowid),
+    };
+    table.set_sqlite_options(sqlite_options);
     let pk = pk_field(ast_struct)
         .expect("No primary key found. Expected 'id' field or field with #[pk] attribute.");
     let mut result: Vec<ATable> = Vec::new();
@@ -48,6 +66,32 @@ fn create_atables(ast_struct: &ItemStruct, config: &dbobj::Config) -> Vec<ATable
             .to_string();
         if is_row_field(f) {
             let deferred_type = get_deferred_sql_type(&f.ty);
+            #[cfg(feature = "decimal")]
+            let deferred_type = super::with_numeric_precision(deferred_type, f)
+                .expect("Malformed numeric attribute");
+            #[cfg(feature = "datetime")]
+            let precision = super::get_timestamp_precision(f, &deferred_type)
+                .expect("Malformed timestamp attribute");
+            let check = get_check(f)
+                .expect("Malformed check attribute")
+                .map(|expr| {
+                    AConstraint::check(AConstraint::default_check_name(&table.name, &name), expr)
+                });
+            let renamed_from = get_renamed_from(f).expect("Malformed renamed_from attribute");
+            let money_currency = is_money(f).then(|| {
+                let mut currency = AColumn::new(
+                    money_currency_column(&name),
+                    DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Text)),
+                    is_nullable(f),
+                    false,
+                    false,
+                    false,
+                    None,
+                    None,
+                );
+                currency.set_renamed_from(renamed_from.as_deref().map(money_currency_column));
+                currency
+            });
             let mut col = AColumn::new(
                 name,
                 deferred_type.clone(),
@@ -59,11 +103,45 @@ fn create_atables(ast_struct: &ItemStruct, config: &dbobj::Config) -> Vec<ATable
                 None,
             );
             if is_foreign_key(f) {
-                col.add_reference(&ARef::Deferred(deferred_type))
+                col.add_reference(&ARef::Deferred(deferred_type));
+                col.set_deferrable(is_deferrable(f));
+            }
+            col.set_renamed_from(renamed_from);
+            col.set_convert_using(get_convert_using(f).expect("Malformed convert_using attribute"));
+            #[cfg(feature = "datetime")]
+            col.set_precision(precision);
+            table.add_column(col);
+            if let Some(currency) = money_currency {
+                table.add_column(currency);
+            }
+            if let Some(index) = field_index(f, &table.name).expect("Malformed index attribute") {
+                table.add_index(index);
             }
+            if let Some(check) = check {
+                table.add_constraint(check);
+            }
+        } else if is_large_blob(f) {
+            // Large blobs are null until first set
+            let mut col = AColumn::new(
+                name,
+                DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Blob)),
+                true,
+                false,
+                false,
+                false,
+                None,
+                None,
+            );
+            col.set_renamed_from(get_renamed_from(f).expect("Malformed renamed_from attribute"));
             table.add_column(col);
         } else if is_many_to_many(f) {
-            result.push(many_table(&table.name, f, &pk));
+            // Many tables have no primary key, so always have a rowid
+            let mut many = many_table(&table.name, f, &pk);
+            many.set_sqlite_options(SqliteTableOptions {
+                strict: sqlite_options.strict,
+                without_rowid: false,
+            });
+            result.push(many);
         }
     }
     result.insert(0, table);
@@ -84,10 +162,14 @@ fn many_table(main_table_name: &str, many_field: &Field, pk_field: &Field) -> AT
         .expect("fields must be named")
         .to_string();
     let pk_field_type = get_deferred_sql_type(&pk_field.ty);
+    let names = many_table_names(many_field, main_table_name)
+        .unwrap_or_else(|_| panic!("Malformed many_table attribute on {field_name}"));
 
-    create_many_table(
+    create_named_many_table(
+        &names.table,
+        &names.owner,
+        &names.has,
         main_table_name,
-        &field_name,
         many_field_type,
         &pk_field_name,
         pk_field_type,
@@ -1,5 +1,7 @@
 //! Code-generation backend
 
+use std::path::Path;
+
 use proc_macro2::TokenStream as TokenStream2;
 use proc_macro2::{Ident, Span, TokenTree};
 use quote::{quote, ToTokens};
@@ -10,14 +12,23 @@
     MetaNameValue,
 };
 
-use crate::migrations::adb::{DeferredSqlType, TypeIdentifier, TypeKey};
-use crate::migrations::{MigrationMut, MigrationsMut};
+use crate::migrations::adb::{
+    ACustomType, ACustomTypeKind, AIndex, DeferredSqlType, TypeIdentifier, TypeKey, MANY_SUFFIX,
+};
+use crate::migrations::{MemMigrations, MigrationMut, MigrationsMut};
 use crate::{SqlType, SqlVal};
 
 const OPTION_TYNAMES: [&str; 3] = ["Option", "option::Option", "std::option::Option"];
 const MANY_TYNAMES: [&str; 2] = ["Many", "butane::Many"];
 const FKEY_TYNAMES: [&str; 2] = ["ForeignKey", "butane::ForeignKey"];
 const AUTOPK_TYNAMES: [&str; 2] = ["AutoPk", "butane::AutoPk"];
+const LARGE_BLOB_TYNAMES: [&str; 2] = ["LargeBlob", "butane::LargeBlob"];
+const MONEY_TYNAMES: [&str; 4] = [
+    "Money",
+    "money::Money",
+    "butane::Money",
+    "butane::money::Money",
+];
 
 /// Create a compiler error.
 #[macro_export]
@@ -33,7 +44,66 @@ macro_rules! make_compile_error {
 }
 
 mod dbobj;
+mod factory;
 mod migration;
+mod testfn;
+
+/// Implementation of `butane::embed_migrations!`. Expands to an
+/// expression which loads the migrations in `dir` into a
+/// [`MemMigrations`][crate::migrations::MemMigrations].
+pub fn embed_migrations(dir: &Path) -> TokenStream2 {
+    if !dir.is_dir() {
+        return make_compile_error!("Butane migrations directory {} not found", dir.display());
+    }
+    let ms = crate::migrations::from_root(dir);
+    let bytes = match MemMigrations::from_migrations(&ms).and_then(|mem_ms| mem_ms.to_bytes()) {
+        Ok(bytes) => proc_macro2::Literal::byte_string(&bytes),
+        Err(err) => {
+            return make_compile_error!(
+                "Cannot embed butane migrations from {}: {err}",
+                dir.display()
+            )
+        }
+    };
+    // Including the files makes the crate rebuild when they change.
+    let files = match migration_files(dir) {
+        Ok(files) => files,
+        Err(err) => {
+            return make_compile_error!("Cannot read {}: {err}", dir.display());
+        }
+    };
+    quote!({
+        #(const _: &[u8] = include_bytes!(#files);)*
+        butane::migrations::MemMigrations::from_bytes(#bytes)
+    })
+}
+
+/// The files which make up the migrations in `dir`, leaving out the
+/// current state written by `#[model]`.
+fn migration_files(dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.file_name().is_some_and(|name| name == "state.json") {
+            files.push(path);
+            continue;
+        }
+        if !path.is_dir() || path.file_name().is_some_and(|name| name == "current") {
+            continue;
+        }
+        for entry in std::fs::read_dir(&path)? {
+            let path = entry?.path();
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect())
+}
 
 /// Implementation of `#[butane::model]`.
 pub fn model_with_migrations<M>(
@@ -47,6 +117,7 @@ pub fn model_with_migrations<M>(
     // attributes but proc macro attributes can't yet (nor can they
     // create field attributes)
     let mut ast_struct: ItemStruct = syn::parse2(input).unwrap();
+    resolve_self_types(&mut ast_struct);
     let config: dbobj::Config = config_from_attributes(&ast_struct);
 
     // Filter out our helper attributes
@@ -77,6 +148,16 @@ pub fn model_with_migrations<M>(
     )
 }
 
+/// Implementation of `#[derive(butane::test_utils::Factory)]`.
+pub fn derive_factory(input: TokenStream2) -> TokenStream2 {
+    factory::impl_factory(input)
+}
+
+/// Implementation of `#[butane::test]`.
+pub fn test_fn(args: TokenStream2, input: TokenStream2) -> TokenStream2 {
+    testfn::impl_test(args, input)
+}
+
 /// Implementation of `#[butane::dataresult(<Model>)]`.
 pub fn dataresult(args: TokenStream2, input: TokenStream2) -> TokenStream2 {
     let dbo: Ident = syn::parse2(args)
@@ -107,7 +188,11 @@ pub fn dataresult(args: TokenStream2, input: TokenStream2) -> TokenStream2 {
     )
 }
 
-fn parse_butane_type_args(args: TokenStream2) -> std::result::Result<TypeIdentifier, TokenStream2> {
+/// The SqlType given to `#[butane_type]`, and the definition of the
+/// user-defined type, if one is given with `Custom(name)`.
+type ButaneTypeArgs = (TypeIdentifier, Option<ACustomType>);
+
+fn parse_butane_type_args(args: TokenStream2) -> std::result::Result<ButaneTypeArgs, TokenStream2> {
     let args: Vec<TokenTree> = args.into_iter().collect();
     if args.is_empty() {
         return Err(quote!(compile_error!("Expected butane_type(sqltype)");));
@@ -118,7 +203,7 @@ fn parse_butane_type_args(args: TokenStream2) -> std::result::Result<TypeIdentif
     };
     if args.len() == 1 {
         return Ok(match sqltype_from_name(&tyid) {
-            Some(ty) => ty,
+            Some(ty) => (ty, None),
             None => {
                 eprintln!("No SqlType value named {tyid}");
                 return Err(quote!(compile_error!("No SqlType value with the given name");));
@@ -128,20 +213,51 @@ fn parse_butane_type_args(args: TokenStream2) -> std::result::Result<TypeIdentif
         let customerr = quote!(
             compile_error!("Unexpected tokens custom in butane_type. Expected butane_type(Custom(name)).");
         );
-        return match args.get(1) {
+        let name = match args.get(1) {
             Some(TokenTree::Group(g)) if !g.stream().is_empty() => {
                 let customid = g.stream().into_iter().nth(0).unwrap();
                 match customid {
-                    TokenTree::Ident(tyid) => Ok(TypeIdentifier::Name(tyid.to_string())),
-                    _ => Err(customerr),
+                    TokenTree::Ident(tyid) => tyid.to_string(),
+                    _ => return Err(customerr),
                 }
             }
-            _ => Err(customerr),
+            _ => return Err(customerr),
         };
+        let definition = parse_type_definition(&name, &args[2..])?;
+        return Ok((TypeIdentifier::Name(name), definition));
     }
     Err(quote!(compile_error!("Unexpected tokens in butane_type");))
 }
 
+/// Parse the definition of a user-defined type following
+/// `Custom(name)`, as `, domain = "<definition>"` or
+/// `, composite = "<definition>"`.
+fn parse_type_definition(
+    name: &str,
+    args: &[TokenTree],
+) -> std::result::Result<Option<ACustomType>, TokenStream2> {
+    let deferr = quote!(
+        compile_error!("Expected butane_type(Custom(name), domain = \"definition\") or butane_type(Custom(name), composite = \"definition\").");
+    );
+    match args {
+        [] => Ok(None),
+        [TokenTree::Punct(comma), TokenTree::Ident(kind), TokenTree::Punct(eq), TokenTree::Literal(lit)]
+            if comma.as_char() == ',' && eq.as_char() == '=' =>
+        {
+            let kind = match kind.to_string().as_str() {
+                "domain" => ACustomTypeKind::Domain,
+                "composite" => ACustomTypeKind::Composite,
+                _ => return Err(deferr),
+            };
+            match Lit::new(lit.clone()) {
+                Lit::Str(definition) => Ok(Some(ACustomType::new(name, kind, definition.value()))),
+                _ => Err(deferr),
+            }
+        }
+        _ => Err(deferr),
+    }
+}
+
 /// Implementation of `#[butane::butane_type(<SqlType>)]`.
 pub fn butane_type_with_migrations<M>(
     args: TokenStream2,
@@ -162,10 +278,16 @@ pub fn butane_type_with_migrations<M>(
 
     if tyinfo.is_none() {
         // For types below here, we need the SqlType given to us
-        let sqltype = match parse_butane_type_args(args) {
-            Ok(sqltype) => sqltype,
+        let (sqltype, definition) = match parse_butane_type_args(args) {
+            Ok(args) => args,
             Err(t) => return t,
         };
+        if let Some(definition) = definition {
+            if let Err(e) = ms.current().add_type_definition(&definition) {
+                eprintln!("unable to save type definition {e}");
+                return quote!(compile_error!("unable to save type definition"););
+            }
+        }
         if let Ok(item) = syn::parse2::<ItemStruct>(input.clone()) {
             tyinfo = Some(CustomTypeInfo {
                 name: item.ident.to_string(),
@@ -209,7 +331,11 @@ fn filter_helper_attributes(ast_struct: &ItemStruct) -> Vec<Attribute> {
         .attrs
         .clone()
         .into_iter()
-        .filter(|a| !a.path().is_ident("table"))
+        .filter(|a| {
+            !a.path().is_ident("table")
+                && !a.path().is_ident("renamed_from")
+                && !a.path().is_ident("sqlite")
+        })
         .collect()
 }
 
@@ -228,6 +354,30 @@ fn config_from_attributes(ast_struct: &ItemStruct) -> dbobj::Config {
             if path.is_ident("table") {
                 config.table_name = Some(s.value())
             }
+            // #[renamed_from = "name"]
+            if path.is_ident("renamed_from") {
+                config.renamed_from = Some(s.value())
+            }
+        }
+        // #[sqlite(strict = false, without_rowid)]
+        if attr.path().is_ident("sqlite") {
+            attr.parse_nested_meta(|meta| {
+                let value = if meta.input.peek(syn::Token![=]) {
+                    let value: syn::LitBool = meta.value()?.parse()?;
+                    value.value
+                } else {
+                    true
+                };
+                if meta.path.is_ident("strict") {
+                    config.sqlite_strict = Some(value);
+                } else if meta.path.is_ident("without_rowid") {
+                    config.sqlite_without_rowid = Some(value);
+                } else {
+                    return Err(meta.error("expected `strict` or `without_rowid`"));
+                }
+                Ok(())
+            })
+            .expect("Malformed sqlite attribute");
         }
     }
     config
@@ -244,6 +394,14 @@ fn remove_helper_field_attributes(
                         && !a.path().is_ident("sqltype")
                         && !a.path().is_ident("default")
                         && !a.path().is_ident("unique")
+                        && !a.path().is_ident("many_table")
+                        && !a.path().is_ident("deferrable")
+                        && !a.path().is_ident("renamed_from")
+                        && !a.path().is_ident("convert_using")
+                        && !a.path().is_ident("index")
+                        && !a.path().is_ident("check")
+                        && !a.path().is_ident("numeric")
+                        && !a.path().is_ident("timestamp")
                 });
             }
             Ok(fields)
@@ -252,6 +410,37 @@ fn remove_helper_field_attributes(
     }
 }
 
+/// Replace `Self` used as a type argument in field types (e.g. `Many<Self>`)
+/// with the struct name. The generated code refers to the model type from
+/// other impls where `Self` would mean something else.
+fn resolve_self_types(ast_struct: &mut ItemStruct) {
+    let ident = ast_struct.ident.clone();
+    for field in ast_struct.fields.iter_mut() {
+        resolve_self_type_arguments(&mut field.ty, &ident);
+    }
+}
+
+fn resolve_self_type_arguments(ty: &mut syn::Type, ident: &Ident) {
+    let path = match ty {
+        syn::Type::Path(typath) => &mut typath.path,
+        _ => return,
+    };
+    for seg in path.segments.iter_mut() {
+        if let syn::PathArguments::AngleBracketed(args) = &mut seg.arguments {
+            for arg in args.args.iter_mut() {
+                if let syn::GenericArgument::Type(argty) = arg {
+                    if matches!(argty, syn::Type::Path(p) if p.qself.is_none() && p.path.is_ident("Self"))
+                    {
+                        *argty = parse_quote!(#ident);
+                    } else {
+                        resolve_self_type_arguments(argty, ident);
+                    }
+                }
+            }
+        }
+    }
+}
+
 fn pk_field(ast_struct: &ItemStruct) -> Option<Field> {
     let pk_by_attribute =
         fields(ast_struct).find(|f| f.attrs.iter().any(|attr| attr.path().is_ident("pk")));
@@ -276,6 +465,259 @@ fn is_unique(field: &Field) -> bool {
         .any(|attr| attr.path().is_ident("unique"))
 }
 
+fn get_renamed_from(field: &Field) -> std::result::Result<Option<String>, CompilerErrorMsg> {
+    get_str_attribute(field, "renamed_from")
+}
+
+fn get_convert_using(field: &Field) -> std::result::Result<Option<String>, CompilerErrorMsg> {
+    get_str_attribute(field, "convert_using")
+}
+
+fn get_check(field: &Field) -> std::result::Result<Option<String>, CompilerErrorMsg> {
+    get_str_attribute(field, "check")
+}
+
+/// The value of a `#[name = "value"]` attribute on `field`.
+fn get_str_attribute(
+    field: &Field,
+    name: &str,
+) -> std::result::Result<Option<String>, CompilerErrorMsg> {
+    let attr: Option<&Attribute> = field.attrs.iter().find(|attr| attr.path().is_ident(name));
+    match attr {
+        None => Ok(None),
+        Some(attr) => match &attr.meta {
+            Meta::NameValue(MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: Lit::Str(s), ..
+                    }),
+                ..
+            }) => Ok(Some(s.value())),
+            _ => Err(make_compile_error!("malformed {} attribute", name).into()),
+        },
+    }
+}
+
+/// Apply the precision and scale given by a
+/// `#[numeric(precision = 10, scale = 2)]` attribute on `field` to its
+/// type `ty`, which must then be numeric.
+#[cfg(feature = "decimal")]
+fn with_numeric_precision(
+    ty: DeferredSqlType,
+    field: &Field,
+) -> std::result::Result<DeferredSqlType, CompilerErrorMsg> {
+    let attr = match field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("numeric"))
+    {
+        Some(attr) => attr,
+        None => return Ok(ty),
+    };
+    if !matches!(
+        ty,
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Numeric(_)))
+    ) {
+        return Err(
+            make_compile_error!("numeric attribute is only valid on a Decimal field").into(),
+        );
+    }
+    let mut precision = None;
+    let mut scale = 0;
+    attr.parse_nested_meta(|meta| {
+        let value: syn::LitInt = meta.value()?.parse()?;
+        if meta.path.is_ident("precision") {
+            precision = Some(value.base10_parse()?);
+        } else if meta.path.is_ident("scale") {
+            scale = value.base10_parse()?;
+        } else {
+            return Err(meta.error("expected `precision` or `scale`"));
+        }
+        Ok(())
+    })
+    .map_err(|err| CompilerErrorMsg::new(err.to_compile_error()))?;
+    let precision =
+        precision.ok_or_else(|| make_compile_error!("numeric attribute requires a precision"))?;
+    Ok(DeferredSqlType::KnownId(TypeIdentifier::Ty(
+        SqlType::Numeric(Some((precision, scale))),
+    )))
+}
+
+/// The number of fractional digits of a second given by a
+/// `#[timestamp(precision = 3)]` attribute on `field`, whose type `ty`
+/// must then be a timestamp, if it has one.
+#[cfg(feature = "datetime")]
+fn get_timestamp_precision(
+    field: &Field,
+    ty: &DeferredSqlType,
+) -> std::result::Result<Option<u32>, CompilerErrorMsg> {
+    let attr = match field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("timestamp"))
+    {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    if !matches!(
+        ty,
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(
+            SqlType::Timestamp | SqlType::TimestampTz
+        ))
+    ) {
+        return Err(
+            make_compile_error!("timestamp attribute is only valid on a timestamp field").into(),
+        );
+    }
+    let mut precision = None;
+    attr.parse_nested_meta(|meta| {
+        if !meta.path.is_ident("precision") {
+            return Err(meta.error("expected `precision`"));
+        }
+        let value: syn::LitInt = meta.value()?.parse()?;
+        let digits: u32 = value.base10_parse()?;
+        if digits > crate::sqlval::MAX_TIMESTAMP_PRECISION {
+            return Err(syn::Error::new(
+                value.span(),
+                "timestamp precision may be at most 6",
+            ));
+        }
+        precision = Some(digits);
+        Ok(())
+    })
+    .map_err(|err| CompilerErrorMsg::new(err.to_compile_error()))?;
+    precision
+        .ok_or_else(|| make_compile_error!("timestamp attribute requires a precision").into())
+        .map(Some)
+}
+
+fn is_deferrable(field: &Field) -> bool {
+    field
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("deferrable"))
+}
+
+/// Table and column names for the join table backing a `Many` field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ManyTableNames {
+    table: String,
+    owner: String,
+    has: String,
+}
+
+/// Determine the join table names for a `Many` field, honouring either
+/// `#[many_table = "name"]` or
+/// `#[many_table(name = "name", owner = "owner_col", has = "has_col")]`.
+fn many_table_names(
+    field: &Field,
+    main_table_name: &str,
+) -> std::result::Result<ManyTableNames, CompilerErrorMsg> {
+    let field_name = field
+        .ident
+        .as_ref()
+        .expect("Fields must be named for butane")
+        .to_string();
+    let mut names = ManyTableNames {
+        table: format!("{main_table_name}_{field_name}{MANY_SUFFIX}"),
+        owner: "owner".to_string(),
+        has: "has".to_string(),
+    };
+    let attr = match field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("many_table"))
+    {
+        Some(attr) => attr,
+        None => return Ok(names),
+    };
+    match &attr.meta {
+        Meta::NameValue(MetaNameValue {
+            value: syn::Expr::Lit(syn::ExprLit {
+                lit: Lit::Str(s), ..
+            }),
+            ..
+        }) => names.table = s.value(),
+        Meta::List(_) => attr
+            .parse_nested_meta(|meta| {
+                let value: LitStr = meta.value()?.parse()?;
+                if meta.path.is_ident("name") {
+                    names.table = value.value();
+                } else if meta.path.is_ident("owner") {
+                    names.owner = value.value();
+                } else if meta.path.is_ident("has") {
+                    names.has = value.value();
+                } else {
+                    return Err(meta.error("expected `name`, `owner` or `has`"));
+                }
+                Ok(())
+            })
+            .map_err(|err| CompilerErrorMsg::new(err.to_compile_error()))?,
+        _ => return Err(make_compile_error!("malformed many_table attribute").into()),
+    }
+    if names.owner == names.has {
+        return Err(make_compile_error!(
+            "many_table owner and has columns must have different names"
+        )
+        .into());
+    }
+    Ok(names)
+}
+
+/// Determine the index of a field marked with `#[index]`,
+/// `#[index = "name"]` or
+/// `#[index(name = "name", unique, method = "method")]`.
+fn field_index(
+    field: &Field,
+    table_name: &str,
+) -> std::result::Result<Option<AIndex>, CompilerErrorMsg> {
+    let attr = match field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("index"))
+    {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    let field_name = field
+        .ident
+        .as_ref()
+        .expect("Fields must be named for butane")
+        .to_string();
+    let mut index = AIndex::new(
+        AIndex::default_name(table_name, &field_name),
+        vec![field_name],
+    );
+    match &attr.meta {
+        Meta::Path(_) => {}
+        Meta::NameValue(MetaNameValue {
+            value: syn::Expr::Lit(syn::ExprLit {
+                lit: Lit::Str(s), ..
+            }),
+            ..
+        }) => index.set_name(s.value()),
+        Meta::List(_) => attr
+            .parse_nested_meta(|meta| {
+                if meta.path.is_ident("unique") {
+                    index.set_unique(true);
+                    return Ok(());
+                }
+                let value: LitStr = meta.value()?.parse()?;
+                if meta.path.is_ident("name") {
+                    index.set_name(value.value());
+                } else if meta.path.is_ident("method") {
+                    index.set_method(Some(value.value()));
+                } else {
+                    return Err(meta.error("expected `name`, `unique` or `method`"));
+                }
+                Ok(())
+            })
+            .map_err(|err| CompilerErrorMsg::new(err.to_compile_error()))?,
+        _ => return Err(make_compile_error!("malformed index attribute").into()),
+    }
+    Ok(Some(index))
+}
+
 fn fields(ast_struct: &ItemStruct) -> impl Iterator<Item = &Field> {
     ast_struct.fields.iter()
 }
@@ -329,6 +771,41 @@ fn is_foreign_key(field: &Field) -> bool {
     get_foreign_key_sql_type(field).is_some()
 }
 
+fn is_large_blob(field: &Field) -> bool {
+    let path = match &field.ty {
+        syn::Type::Path(typath) => &typath.path,
+        _ => return false,
+    };
+    LARGE_BLOB_TYNAMES
+        .iter()
+        .any(|tyname| match syn::parse_str::<syn::Path>(tyname) {
+            Ok(ty_path) => is_same_path_ident(path, &ty_path),
+            // Should only happen if there's a bug in butane
+            Err(_) => panic!("Cannot parse {tyname} as syn::Path"),
+        })
+}
+
+/// Whether the field holds [`Money`][crate::money::Money], which has
+/// a second column for its currency.
+fn is_money(field: &Field) -> bool {
+    match get_type_argument(&field.ty, &OPTION_TYNAMES) {
+        Some(inner) => {
+            let inner: syn::Type = syn::TypePath {
+                qself: None,
+                path: inner.clone(),
+            }
+            .into();
+            get_type_argument(&inner, &MONEY_TYNAMES).is_some()
+        }
+        None => get_type_argument(&field.ty, &MONEY_TYNAMES).is_some(),
+    }
+}
+
+/// The name of the currency column of the `Money` field `name`.
+fn money_currency_column(name: &str) -> String {
+    format!("{name}_currency")
+}
+
 fn is_option(field: &Field) -> bool {
     get_type_argument(&field.ty, &OPTION_TYNAMES).is_some()
 }
@@ -336,7 +813,7 @@ fn is_option(field: &Field) -> bool {
 /// Check for special fields which won't correspond to rows and don't
 /// implement FieldType
 fn is_row_field(f: &Field) -> bool {
-    !is_many_to_many(f)
+    !is_many_to_many(f) && !is_large_blob(f)
 }
 
 /// Test if the ident of each segment in two paths is the same without
@@ -402,6 +879,7 @@ fn get_foreign_sql_type(ty: &syn::Type, tynames: &[&'static str]) -> Option<Defe
 /// It looks inside an [Option] or [crate::fkey::ForeignKey] to determine the inner type.
 pub fn get_deferred_sql_type(ty: &syn::Type) -> DeferredSqlType {
     get_primitive_sql_type(ty)
+        .or_else(|| get_array_sql_type(ty))
         .or_else(|| get_option_sql_type(ty))
         .or_else(|| get_foreign_sql_type(ty, &FKEY_TYNAMES))
         .or_else(|| get_autopk_sql_type(ty))
@@ -412,9 +890,36 @@ pub fn get_deferred_sql_type(ty: &syn::Type) -> DeferredSqlType {
         })
 }
 
+/// If the type is a `Vec` of a primitive which may be an array
+/// element, return its array type. A `Vec<u8>` is instead a blob.
+fn get_array_sql_type(ty: &syn::Type) -> Option<DeferredSqlType> {
+    let segment = match ty {
+        syn::Type::Path(typath) => typath.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Vec" {
+        return None;
+    }
+    let elem_ty = match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(elem_ty) => elem_ty,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match get_primitive_sql_type(elem_ty)? {
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(elem)) => {
+            some_known(SqlType::Array(elem.array_element()?))
+        }
+        _ => None,
+    }
+}
+
 /// Defaults are used for fields added by later migrations
 /// Example
 /// #[default = 42]
+/// or, for an array field,
+/// #[default = [1, 2]]
 fn get_default(field: &Field) -> std::result::Result<Option<SqlVal>, CompilerErrorMsg> {
     let attr: Option<&Attribute> = field
         .attrs
@@ -427,12 +932,39 @@ fn get_default(field: &Field) -> std::result::Result<Option<SqlVal>, CompilerErr
                 value: syn::Expr::Lit(expr_lit),
                 ..
             }) => expr_lit.lit.clone(),
+            Meta::NameValue(MetaNameValue {
+                value: syn::Expr::Array(array),
+                ..
+            }) => return Ok(Some(array_default(field, array)?)),
             _ => return Err(make_compile_error!("malformed default value").into()),
         },
     };
     Ok(Some(sqlval_from_lit(lit)?))
 }
 
+fn array_default(
+    field: &Field,
+    array: &syn::ExprArray,
+) -> std::result::Result<SqlVal, CompilerErrorMsg> {
+    let elem = match get_deferred_sql_type(&field.ty) {
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Array(elem))) => elem,
+        _ => {
+            return Err(
+                make_compile_error!("an array default is only allowed for an array field").into(),
+            )
+        }
+    };
+    let items = array
+        .elems
+        .iter()
+        .map(|item| match item {
+            syn::Expr::Lit(expr_lit) => sqlval_from_lit(expr_lit.lit.clone()),
+            _ => Err(make_compile_error!("elements of an array default must be literals").into()),
+        })
+        .collect::<std::result::Result<Vec<SqlVal>, CompilerErrorMsg>>()?;
+    Ok(SqlVal::Array(elem, items))
+}
+
 fn some_id(ty: SqlType) -> Option<TypeIdentifier> {
     Some(TypeIdentifier::Ty(ty))
 }
@@ -441,6 +973,38 @@ fn some_known(ty: SqlType) -> Option<DeferredSqlType> {
     Some(DeferredSqlType::KnownId(TypeIdentifier::Ty(ty)))
 }
 
+/// If the type is one of the `std::num` non-zero integers, either
+/// named like `NonZeroI64` or written `NonZero<i64>`, the SqlType of
+/// the integer it holds.
+fn nonzero_sql_type(ty: &syn::Type) -> Option<SqlType> {
+    let segment = last_path_segment(ty)?;
+    let int: syn::Type = match segment.ident.to_string().as_str() {
+        "NonZero" => match &segment.arguments {
+            syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+                syn::GenericArgument::Type(int) => int.clone(),
+                _ => return None,
+            },
+            _ => return None,
+        },
+        "NonZeroI8" => parse_quote!(i8),
+        "NonZeroU8" => parse_quote!(u8),
+        "NonZeroI16" => parse_quote!(i16),
+        "NonZeroU16" => parse_quote!(u16),
+        "NonZeroI32" => parse_quote!(i32),
+        "NonZeroU32" => parse_quote!(u32),
+        "NonZeroI64" => parse_quote!(i64),
+        "NonZeroU64" => parse_quote!(u64),
+        "NonZeroI128" => parse_quote!(i128),
+        _ => return None,
+    };
+    match get_primitive_sql_type(&int)? {
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(
+            sqltype @ (SqlType::Int | SqlType::BigInt | SqlType::HugeInt),
+        )) => Some(sqltype),
+        _ => None,
+    }
+}
+
 /// If the field refers to a primitive, return its SqlType
 pub fn get_primitive_sql_type(ty: &syn::Type) -> Option<DeferredSqlType> {
     if *ty == parse_quote!(bool) {
@@ -454,28 +1018,74 @@ pub fn get_primitive_sql_type(ty: &syn::Type) -> Option<DeferredSqlType> {
     {
         return some_known(SqlType::Int);
     } else if *ty == parse_quote!(u32) || *ty == parse_quote!(i64) {
-        // Future improvement: better support unsigned integers
-        // here. Sqlite has no u64, though Postgres does
         return some_known(SqlType::BigInt);
+    } else if *ty == parse_quote!(u64) || *ty == parse_quote!(i128) {
+        return some_known(SqlType::HugeInt);
+    } else if let Some(sqltype) = nonzero_sql_type(ty) {
+        return some_known(sqltype);
     } else if *ty == parse_quote!(f32) || *ty == parse_quote!(f64) {
         return some_known(SqlType::Real);
     } else if *ty == parse_quote!(String)
         || *ty == parse_quote!(std::string::String)
         || *ty == parse_quote!(::std::string::String)
+        || *ty == parse_quote!(Cow<'static, str>)
+        || *ty == parse_quote!(std::borrow::Cow<'static, str>)
     {
         return some_known(SqlType::Text);
+    } else if *ty == parse_quote!(CiString) || *ty == parse_quote!(butane::CiString) {
+        return some_known(SqlType::CiText);
     } else if *ty == parse_quote!(Vec<u8>)
         || *ty == parse_quote!(std::vec::Vec<u8>)
         || *ty == parse_quote!(::std::vec::Vec<u8>)
+        || *ty == parse_quote!(Cow<'static, [u8]>)
+        || *ty == parse_quote!(std::borrow::Cow<'static, [u8]>)
+        || *ty == parse_quote!(Bytes)
+        || *ty == parse_quote!(bytes::Bytes)
     {
         return some_known(SqlType::Blob);
     }
 
+    // A Redacted<T> wrapper is stored as what it wraps.
+    if let Some(syn::PathSegment {
+        ident,
+        arguments: syn::PathArguments::AngleBracketed(args),
+    }) = last_path_segment(ty)
+    {
+        if ident == "Redacted" {
+            if let Some(syn::GenericArgument::Type(inner)) = args.args.first() {
+                return get_primitive_sql_type(inner);
+            }
+        }
+    }
+
+    #[cfg(feature = "bitflags")]
+    {
+        if let Some(syn::PathSegment {
+            ident,
+            arguments: syn::PathArguments::AngleBracketed(_),
+        }) = last_path_segment(ty)
+        {
+            if ident == "FlagSet" {
+                return some_known(SqlType::BigInt);
+            }
+        }
+    }
+
     #[cfg(feature = "json")]
     {
         if *ty == parse_quote!(serde_json::Value) || *ty == parse_quote!(Value) {
             return some_known(SqlType::Json);
         }
+        // Any Json<T> wrapper is stored as JSON, whatever it wraps.
+        if let Some(syn::PathSegment {
+            ident,
+            arguments: syn::PathArguments::AngleBracketed(_),
+        }) = last_path_segment(ty)
+        {
+            if ident == "Json" {
+                return some_known(SqlType::Json);
+            }
+        }
     }
 
     #[cfg(feature = "datetime")]
@@ -488,15 +1098,16 @@ pub fn get_primitive_sql_type(ty: &syn::Type) -> Option<DeferredSqlType> {
         if let Some(syn::PathSegment { ident, arguments }) = last_path_segment(ty) {
             match ident.to_string().as_str() {
                 "NaiveDateTime" => return some_known(SqlType::Timestamp),
+                "NaiveDate" => return some_known(SqlType::Date),
+                "NaiveTime" => return some_known(SqlType::Time),
                 "DateTime" => {
-                    // Only if the parameter is UTC, as we don't support attached
-                    // time zones
-                    if template_type(arguments)
+                    // Only if the parameter is UTC or a fixed offset, as
+                    // we don't support attached time zones
+                    let tz = template_type(arguments)
                         .map(|ident| ident.to_string())
-                        .unwrap_or_default()
-                        == "Utc"
-                    {
-                        return some_known(SqlType::Timestamp);
+                        .unwrap_or_default();
+                    if tz == "Utc" || tz == "FixedOffset" {
+                        return some_known(SqlType::TimestampTz);
                     }
                 }
                 _ => {}
@@ -504,17 +1115,58 @@ pub fn get_primitive_sql_type(ty: &syn::Type) -> Option<DeferredSqlType> {
         }
     }
 
+    #[cfg(feature = "time")]
+    {
+        // As with chrono, only the last path segment is checked for
+        // the types with distinctive names.
+        if let Some(syn::PathSegment { ident, .. }) = last_path_segment(ty) {
+            match ident.to_string().as_str() {
+                "OffsetDateTime" => return some_known(SqlType::TimestampTz),
+                "PrimitiveDateTime" => return some_known(SqlType::Timestamp),
+                _ => {}
+            }
+        }
+        if *ty == parse_quote!(Date)
+            || *ty == parse_quote!(time::Date)
+            || *ty == parse_quote!(::time::Date)
+        {
+            return some_known(SqlType::Date);
+        }
+        if *ty == parse_quote!(Time)
+            || *ty == parse_quote!(time::Time)
+            || *ty == parse_quote!(::time::Time)
+        {
+            return some_known(SqlType::Time);
+        }
+    }
+
+    #[cfg(feature = "decimal")]
+    {
+        if *ty == parse_quote!(Decimal) || *ty == parse_quote!(rust_decimal::Decimal) {
+            return some_known(SqlType::Numeric(None));
+        }
+        // The amount of a Money<C> is a decimal, whatever its currency.
+        if let Some(syn::PathSegment {
+            ident,
+            arguments: syn::PathArguments::AngleBracketed(_),
+        }) = last_path_segment(ty)
+        {
+            if ident == "Money" {
+                return some_known(SqlType::Numeric(None));
+            }
+        }
+    }
+
     #[cfg(feature = "uuid")]
     {
         if *ty == parse_quote!(Uuid) || *ty == parse_quote!(uuid::Uuid) {
-            return some_known(SqlType::Blob);
+            return some_known(SqlType::Uuid);
         }
     }
 
     None
 }
 
-#[cfg(feature = "datetime")]
 fn last_path_segment(ty: &syn::Type) -> Option<&syn::PathSegment> {
     if let syn::Type::Path(syn::TypePath {
         path: syn::Path { segments, .. },
@@ -583,13 +1235,25 @@ fn sqltype_from_name(name: &Ident) -> Option<TypeIdentifier> {
         "Bool" => return some_id(SqlType::Bool),
         "Int" => return some_id(SqlType::Int),
         "BigInt" => return some_id(SqlType::BigInt),
+        "HugeInt" => return some_id(SqlType::HugeInt),
         "Real" => return some_id(SqlType::Real),
         "Text" => return some_id(SqlType::Text),
+        "CiText" => return some_id(SqlType::CiText),
         "Blob" => return some_id(SqlType::Blob),
         #[cfg(feature = "json")]
         "Json" => return some_id(SqlType::Json),
         #[cfg(feature = "datetime")]
         "Timestamp" => return some_id(SqlType::Timestamp),
+        #[cfg(feature = "datetime")]
+        "TimestampTz" => return some_id(SqlType::TimestampTz),
+        #[cfg(feature = "datetime")]
+        "Date" => return some_id(SqlType::Date),
+        #[cfg(feature = "datetime")]
+        "Time" => return some_id(SqlType::Time),
+        #[cfg(feature = "decimal")]
+        "Numeric" => return some_id(SqlType::Numeric(None)),
+        #[cfg(feature = "uuid")]
+        "Uuid" => return some_id(SqlType::Uuid),
         _ => (),
     }
     if let Some(custom_name) = Regex::new(r"^Custom\((.*)\)$").unwrap().captures(&name) {
@@ -660,6 +1324,25 @@ fn test_get_type_argument_fky() {
         assert!(rv.is_none());
     }
 
+    #[test]
+    fn test_resolve_self_types() {
+        let mut ast_struct: ItemStruct = syn::parse_quote!(
+            struct User {
+                id: i64,
+                follows: Many<Self>,
+                mentor: Option<ForeignKey<Self>>,
+            }
+        );
+        resolve_self_types(&mut ast_struct);
+        let types: Vec<syn::Type> = ast_struct.fields.iter().map(|f| f.ty.clone()).collect();
+        let expected: Vec<syn::Type> = vec![
+            syn::parse_quote!(i64),
+            syn::parse_quote!(Many<User>),
+            syn::parse_quote!(Option<ForeignKey<User>>),
+        ];
+        assert_eq!(types, expected);
+    }
+
     #[test]
     fn test_get_type_argument_many() {
         let expected_type_path: syn::Path = syn::parse_quote!(Foo);
@@ -708,4 +1391,59 @@ fn test_is_foreign_key() {
         let field = syn::Field::parse_named.parse2(tokens).unwrap();
         assert!(!is_foreign_key(&field));
     }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_with_numeric_precision() {
+        let field = syn::Field::parse_named
+            .parse2(quote::quote! {
+                #[numeric(precision = 10, scale = 2)]
+                price: Option<Decimal>
+            })
+            .unwrap();
+        let ty = with_numeric_precision(get_deferred_sql_type(&field.ty), &field).unwrap();
+        assert_eq!(
+            ty,
+            DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Numeric(Some((10, 2)))))
+        );
+
+        let field = syn::Field::parse_named
+            .parse2(quote::quote! {
+                #[numeric(precision = 10)]
+                price: i64
+            })
+            .unwrap();
+        assert!(with_numeric_precision(get_deferred_sql_type(&field.ty), &field).is_err());
+    }
+
+    #[cfg(feature = "datetime")]
+    #[test]
+    fn test_timestamp_precision() {
+        let field = syn::Field::parse_named
+            .parse2(quote::quote! {
+                #[timestamp(precision = 3)]
+                created: Option<NaiveDateTime>
+            })
+            .unwrap();
+        let ty = get_deferred_sql_type(&field.ty);
+        assert_eq!(get_timestamp_precision(&field, &ty).unwrap(), Some(3));
+
+        let field = syn::Field::parse_named
+            .parse2(quote::quote! {
+                #[timestamp(precision = 9)]
+                created: NaiveDateTime
+            })
+            .unwrap();
+        let ty = get_deferred_sql_type(&field.ty);
+        assert!(get_timestamp_precision(&field, &ty).is_err());
+
+        let field = syn::Field::parse_named
+            .parse2(quote::quote! {
+                #[timestamp(precision = 3)]
+                created: NaiveDate
+            })
+            .unwrap();
+        let ty = get_deferred_sql_type(&field.ty);
+        assert!(get_timestamp_precision(&field, &ty).is_err());
+    }
 }
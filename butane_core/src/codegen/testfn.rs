@@ -0,0 +1,101 @@
+use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{Ident, Span};
+use quote::quote;
+use syn::{punctuated::Punctuated, Expr, ItemFn, Lit, Meta, Token};
+
+/// The directory of the migrations applied, relative to the crate's
+/// `Cargo.toml`, unless given by `migrations = "<dir>"`.
+const DEFAULT_MIGRATIONS_DIR: &str = ".butane/migrations";
+
+/// The `#[test]` functions running `input` on a database of each
+/// backend given in `args`, or SQLite if none is.
+pub fn impl_test(args: TokenStream2, input: TokenStream2) -> TokenStream2 {
+    let func: ItemFn = match syn::parse2(input) {
+        Ok(func) => func,
+        Err(err) => return err.to_compile_error(),
+    };
+    let metas =
+        match syn::parse::Parser::parse2(Punctuated::<Meta, Token![,]>::parse_terminated, args) {
+            Ok(metas) => metas,
+            Err(err) => return err.to_compile_error(),
+        };
+
+    let mut backends: Vec<&str> = Vec::new();
+    let mut migrations_dir = DEFAULT_MIGRATIONS_DIR.to_string();
+    for meta in metas {
+        match &meta {
+            Meta::Path(path) if path.is_ident("sqlite") => backends.push("sqlite"),
+            Meta::Path(path) if path.is_ident("pg") => backends.push("pg"),
+            Meta::NameValue(nv) if nv.path.is_ident("migrations") => match &nv.value {
+                Expr::Lit(syn::ExprLit {
+                    lit: Lit::Str(dir), ..
+                }) => migrations_dir = dir.value(),
+                _ => {
+                    return syn::Error::new_spanned(&nv.value, "expected a string literal")
+                        .to_compile_error()
+                }
+            },
+            _ => {
+                return syn::Error::new_spanned(
+                    &meta,
+                    "expected `sqlite`, `pg` or `migrations = \"<dir>\"`",
+                )
+                .to_compile_error()
+            }
+        }
+    }
+    if backends.is_empty() {
+        backends.push("sqlite");
+    }
+
+    if func.sig.inputs.len() != 1 {
+        return syn::Error::new_spanned(
+            &func.sig,
+            "a butane test takes one argument, the connection",
+        )
+        .to_compile_error();
+    }
+
+    let fname = &func.sig.ident;
+    let output = &func.sig.output;
+    // Attributes such as `#[should_panic]` belong to the `#[test]`.
+    let attrs = &func.attrs;
+    let mut inner = func.clone();
+    inner.attrs.clear();
+
+    let run = if func.sig.asyncness.is_some() {
+        quote!(
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Cannot build tokio runtime")
+                .block_on(async {
+                    let conn = db.connect_async().await.expect("Cannot connect to test database");
+                    #fname(conn).await
+                })
+        )
+    } else {
+        quote!(#fname(db.connect().expect("Cannot connect to test database")))
+    };
+
+    let tests = backends.iter().map(|backend| {
+        let test_name = if backends.len() == 1 {
+            fname.clone()
+        } else {
+            Ident::new(&format!("{fname}_{backend}"), Span::call_site())
+        };
+        quote!(
+            #[test]
+            #(#attrs)*
+            fn #test_name() #output {
+                #inner
+                let migrations = butane::embed_migrations!(#migrations_dir)
+                    .expect("Cannot load butane migrations");
+                let db = butane::test_utils::TestDb::new(#backend, migrations)
+                    .expect("Cannot set up test database");
+                #run
+            }
+        )
+    });
+    quote!(#(#tests)*)
+}
@@ -19,6 +19,11 @@
 pub enum SqlTypeCustom {
     #[cfg(feature = "pg")]
     Pg(#[serde(with = "pgtypeser")] tokio_postgres::types::Type),
+    /// A user-defined type created by the migrations, such as one
+    /// given a definition by `#[butane_type(Custom(name), domain = "...")]`,
+    /// referred to by its name. A value of any type may be loaded from
+    /// a column of the type, and is checked by the field's `FromSql`.
+    Named(std::borrow::Cow<'static, str>),
 }
 
 /// For use with [SqlVal::Custom](crate::SqlVal)
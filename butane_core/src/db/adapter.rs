@@ -44,18 +44,14 @@ fn new() -> Self {
 
     /// Invokes a blocking function `func` as if it were async. This
     /// is implemented by running it on the special thread created when the `AsyncAdapterEnv` was created.
-    async fn invoke<'c, 's, 'result, F, T, U>(
-        &'s self,
-        context: &SyncSendPtrMut<T>,
-        func: F,
-    ) -> Result<U>
+    /// If the future is cancelled, `func` still runs, after the caller
+    /// may have freed what it borrowed, so `func` is `'static`: it owns
+    /// everything it uses besides the context.
+    async fn invoke<F, T, U>(&self, context: &SyncSendPtrMut<T>, func: F) -> Result<U>
     where
-        F: FnOnce(&'c T) -> Result<U> + Send,
-        F: 'result,
-        U: Send + 'result,
-        T: ?Sized + 'c,
-        's: 'result,
-        'c: 'result,
+        F: for<'c> FnOnce(&'c T) -> Result<U> + Send + 'static,
+        U: Send + 'static,
+        T: ?Sized,
     {
         // func itself must be `Send`, but we do not require &T to be
         // Send (and thus don't reuire T to be Sync).  We do this by
@@ -68,31 +64,26 @@ async fn invoke<'c, 's, 'result, F, T, U>(
         // and forth, it's essentially owned by the worker thread -- all operations
         // with context occur on that worker thread.
         let (tx, rx) = tokio::sync::oneshot::channel();
+        let context = unsafe { context.clone_unsafe() };
         let func_taking_ptr = |ctx: SyncSendPtrMut<T>| func(unsafe { ctx.inner.as_ref() }.unwrap());
         unsafe {
-            let wrapped_func = move || _ = tx.send(func_taking_ptr(context.clone_unsafe()));
+            let wrapped_func = move || _ = tx.send(func_taking_ptr(context));
             self.invoke_internal_unsafe(wrapped_func)?;
         }
         rx.await?
     }
 
-    async fn invoke_mut<'c, 's, 'result, F, T, U>(
-        &'s self,
-        context: &SyncSendPtrMut<T>,
-        func: F,
-    ) -> Result<U>
+    async fn invoke_mut<F, T, U>(&self, context: &SyncSendPtrMut<T>, func: F) -> Result<U>
     where
-        F: FnOnce(&'c mut T) -> Result<U> + Send,
-        F: 'result,
-        U: Send + 'result,
-        T: ?Sized + 'c,
-        's: 'result,
-        'c: 'result,
+        F: for<'c> FnOnce(&'c mut T) -> Result<U> + Send + 'static,
+        U: Send + 'static,
+        T: ?Sized,
     {
         let (tx, rx) = tokio::sync::oneshot::channel();
+        let context = unsafe { context.clone_unsafe() };
         let func_taking_ptr = |ctx: SyncSendPtrMut<T>| func(unsafe { ctx.inner.as_mut().unwrap() });
         unsafe {
-            let wrapped_func = move || _ = tx.send(func_taking_ptr(context.clone_unsafe()));
+            let wrapped_func = move || _ = tx.send(func_taking_ptr(context));
             self.invoke_internal_unsafe(wrapped_func)?;
         }
         rx.await?
@@ -130,9 +121,13 @@ unsafe fn invoke_internal_unsafe<'s, 'result>(
         // *we* know that our worker thread will immediately execute
         // the function and the caller to this method will wait to
         // hear from the worker thread before proceeding (and thus
-        // before letting the lifetime lapse)
+        // before letting the lifetime lapse). An async caller which is
+        // cancelled does not wait, so the functions it sends are
+        // `'static` but for the context, which is only dropped by a
+        // later command.
         // https://stackoverflow.com/questions/52424449/
-        let boxed_func: Box<dyn FnOnce() + Send + 'result> = Box::new(wrapped_func);
+        let boxed_func: Box<dyn FnOnce() + Send + 'result> =
+            Box::new(crate::trace::in_current_span(wrapped_func));
         let static_func: Box<dyn FnOnce() + Send + 'static> =
             unsafe { std::mem::transmute(boxed_func) };
         self.sender.send(Command::Func(static_func))?;
@@ -191,6 +186,60 @@ fn from(val: T) -> Self {
 unsafe impl<T: ?Sized> Send for SyncSendPtrMut<T> {}
 unsafe impl<T: ?Sized> Sync for SyncSendPtrMut<T> {}
 
+/// A boxed value created on the worker thread, such as a transaction,
+/// which is dropped there unless it is received. If the future waiting
+/// for it is cancelled, the worker's attempt to send it back fails and
+/// drops it, so that a transaction begun for a cancelled future is
+/// rolled back rather than leaked, open, on the connection.
+struct Pending<T: ?Sized>(Option<SyncSendPtrMut<T>>);
+impl<T: ?Sized> Pending<T> {
+    /// Take ownership of the box `inner` points to.
+    unsafe fn new(inner: *mut T) -> Self {
+        Self(Some(unsafe { SyncSendPtrMut::new(inner) }))
+    }
+    fn receive(mut self) -> SyncSendPtrMut<T> {
+        self.0.take().unwrap()
+    }
+}
+impl<T: ?Sized> Drop for Pending<T> {
+    fn drop(&mut self) {
+        if let Some(ptr) = self.0.take() {
+            unsafe { std::mem::drop(Box::from_raw(ptr.inner)) }
+        }
+    }
+}
+
+/// A transaction created on the worker thread, with the lifetime of its
+/// borrow of the context erased, so that the function creating it,
+/// which is `'static`, can return it.
+fn pending_transaction<'t>(
+    trans: Box<dyn BackendTransaction<'t> + 't>,
+) -> Pending<dyn BackendTransaction<'static>> {
+    let ptr: *mut (dyn BackendTransaction<'t> + 't) = Box::into_raw(trans);
+    // The adapter receiving the transaction borrows the adapter of its
+    // context for as long as it exists, see [received_transaction].
+    unsafe {
+        Pending::new(std::mem::transmute::<
+            *mut (dyn BackendTransaction<'t> + 't),
+            *mut dyn BackendTransaction<'static>,
+        >(ptr))
+    }
+}
+
+/// The transaction of [pending_transaction], given back the lifetime
+/// `'t` of the borrow of the adapter it is created through.
+fn received_transaction<'t>(
+    pending: Pending<dyn BackendTransaction<'static>>,
+) -> SyncSendPtrMut<dyn BackendTransaction<'t> + 't> {
+    let ptr = pending.receive();
+    unsafe {
+        SyncSendPtrMut::new(std::mem::transmute::<
+            *mut dyn BackendTransaction<'static>,
+            *mut (dyn BackendTransaction<'t> + 't),
+        >(ptr.inner))
+    }
+}
+
 impl<T: Debug + ?Sized> Debug for SyncSendPtrMut<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         // We enforce that inner is non-null and valid.
@@ -202,6 +251,10 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::f
 pub(super) struct AsyncAdapter<T: ?Sized> {
     env: Arc<AsyncAdapterEnv>,
     context: SyncSendPtrMut<T>,
+    /// Taken when the connection is created, as it could not be taken
+    /// from the worker thread while it is busy with the statement to
+    /// be cancelled.
+    cancel: Option<super::CancelHandle>,
 }
 
 impl<T: ?Sized> AsyncAdapter<T> {
@@ -210,28 +263,27 @@ fn create_with_same_env<U: ?Sized>(&self, context_ptr: SyncSendPtrMut<U>) -> Asy
         AsyncAdapter {
             env: self.env.clone(),
             context: context_ptr,
+            cancel: self.cancel.clone(),
         }
     }
 
-    /// Invokes the provided function with a sync method.
-    async fn invoke<'c, 's, 'result, F, U>(&'s self, func: F) -> Result<U>
+    /// Invokes the provided function with a sync method. As the
+    /// function may run after the future is cancelled, it owns
+    /// everything it uses besides the context.
+    async fn invoke<F, U>(&self, func: F) -> Result<U>
     where
-        F: FnOnce(&'c T) -> Result<U> + Send,
-        F: 'result,
-        U: Send + 'result,
-        's: 'result,
-        'c: 'result,
-        's: 'c,
+        F: for<'c> FnOnce(&'c T) -> Result<U> + Send + 'static,
+        U: Send + 'static,
     {
         self.env.invoke(&self.context, func).await
     }
 
-    async fn invoke_mut<'c, 'result, F, U>(&'c self, func: F) -> Result<U>
+    /// Like [`invoke`][AsyncAdapter::invoke], with mutable access to
+    /// the context.
+    async fn invoke_mut<F, U>(&self, func: F) -> Result<U>
     where
-        F: FnOnce(&'c mut T) -> Result<U> + Send,
-        F: 'result,
-        U: Send + 'result,
-        'c: 'result,
+        F: for<'c> FnOnce(&'c mut T) -> Result<U> + Send + 'static,
+        U: Send + 'static,
     {
         self.env.invoke_mut(&self.context, func).await
     }
@@ -247,7 +299,7 @@ fn invoke_blocking<'c, 'result, F, U>(&'c self, func: F) -> Result<U>
     }
 }
 
-impl<T> AsyncAdapter<T> {
+impl<T: ConnectionMethods> AsyncAdapter<T> {
     /// Create a new async adapter using `create_context` to create an instance of the inner type `T`.
     pub(super) fn new<F>(create_context: F) -> Result<Self>
     where
@@ -258,29 +310,37 @@ pub(super) fn new<F>(create_context: F) -> Result<Self>
 
         // Execute the context creation function on our worker thread.
         let dummy = (); // because we have to pass a context pointer to env.invoke
-        let context = env.invoke_blocking(&dummy, |_ctx: &()| {
+        let (context, cancel) = env.invoke_blocking(&dummy, |_ctx: &()| {
             let concrete_context = create_context()?;
+            let cancel = concrete_context.cancel_handle();
             // See comments about soundness on AsyncAdapterEnv::invoke
             let context = unsafe { SyncSendPtrMut::new(Box::into_raw(Box::new(concrete_context))) };
-            Ok(context)
+            Ok((context, cancel))
         })?;
 
         Ok(Self {
             env: Arc::new(env),
             context,
+            cancel,
         })
     }
 }
 
 impl<T: ?Sized> Drop for AsyncAdapter<T> {
     fn drop(&mut self) {
-        // Drops the box to Drop T
-        self.env
-            .invoke_blocking(&self.context, |context| unsafe {
-                std::mem::drop(Box::from_raw(context.inner));
-                Ok(())
-            })
-            .unwrap();
+        // Drops the box to Drop T on the worker thread. This does not
+        // wait for the worker, which may be busy, so that dropping a
+        // transaction, which rolls it back, never blocks the task.
+        // Commands sent afterwards, including dropping the connection a
+        // transaction borrows, run after it.
+        let context = unsafe { self.context.clone_unsafe() };
+        let drop_context = move || {
+            let context = context;
+            unsafe { std::mem::drop(Box::from_raw(context.inner)) }
+        };
+        if unsafe { self.env.invoke_internal_unsafe(drop_context) }.is_err() {
+            crate::error!("Cannot drop async adapter context because channel is disconnected.");
+        }
         // Note, self.context.inner is now a dangling pointer
     }
 }
@@ -291,7 +351,8 @@ impl<T> ConnectionMethodsAsync for AsyncAdapter<T>
     T: ConnectionMethods + ?Sized,
 {
     async fn execute(&self, sql: &str) -> Result<()> {
-        self.invoke(|conn| conn.execute(sql)).await
+        let sql = sql.to_string();
+        self.invoke(move |conn| conn.execute(&sql)).await
     }
 
     async fn query<'c>(
@@ -303,11 +364,14 @@ async fn query<'c>(
         offset: Option<i32>,
         sort: Option<&[Order]>,
     ) -> Result<RawQueryResult<'c>> {
+        let table = table.to_string();
+        let columns = columns.to_vec();
+        let sort = sort.map(<[Order]>::to_vec);
         let rows = self
-            .invoke(|conn| {
+            .invoke(move |conn| {
                 let rows: Box<dyn BackendRows> =
-                    conn.query(table, columns, expr, limit, offset, sort)?;
-                let vec_rows = super::connmethods::vec_from_backend_rows(rows, columns)?;
+                    conn.query(&table, &columns, expr, limit, offset, sort.as_deref())?;
+                let vec_rows = super::connmethods::vec_from_backend_rows(rows, &columns)?;
                 Ok(Box::new(vec_rows))
             })
             .await?;
@@ -320,8 +384,12 @@ async fn insert_returning_pk(
         pkcol: &Column,
         values: &[SqlValRef<'_>],
     ) -> Result<SqlVal> {
-        self.invoke(|conn| conn.insert_returning_pk(table, columns, pkcol, values))
-            .await
+        let (table, columns, pkcol) = (table.to_string(), columns.to_vec(), pkcol.clone());
+        let values = owned_values(values);
+        self.invoke(move |conn| {
+            conn.insert_returning_pk(&table, &columns, &pkcol, &value_refs(&values))
+        })
+        .await
     }
     /// Like `insert_returning_pk` but with no return value.
     async fn insert_only(
@@ -330,7 +398,9 @@ async fn insert_only(
         columns: &[Column],
         values: &[SqlValRef<'_>],
     ) -> Result<()> {
-        self.invoke(|conn| conn.insert_only(table, columns, values))
+        let (table, columns) = (table.to_string(), columns.to_vec());
+        let values = owned_values(values);
+        self.invoke(move |conn| conn.insert_only(&table, &columns, &value_refs(&values)))
             .await
     }
     /// Insert unless there's a conflict on the primary key column, in which case update.
@@ -341,8 +411,12 @@ async fn insert_or_replace(
         pkcol: &Column,
         values: &[SqlValRef<'_>],
     ) -> Result<()> {
-        self.invoke(|conn| conn.insert_or_replace(table, columns, pkcol, values))
-            .await
+        let (table, columns, pkcol) = (table.to_string(), columns.to_vec(), pkcol.clone());
+        let values = owned_values(values);
+        self.invoke(move |conn| {
+            conn.insert_or_replace(&table, &columns, &pkcol, &value_refs(&values))
+        })
+        .await
     }
     async fn update(
         &self,
@@ -352,15 +426,29 @@ async fn update(
         columns: &[Column],
         values: &[SqlValRef<'_>],
     ) -> Result<()> {
-        self.invoke(|conn| conn.update(table, pkcol, pk, columns, values))
-            .await
+        let (table, columns) = (table.to_string(), columns.to_vec());
+        let pk = SqlVal::from(pk);
+        let values = owned_values(values);
+        self.invoke(move |conn| {
+            conn.update(&table, pkcol, pk.as_ref(), &columns, &value_refs(&values))
+        })
+        .await
     }
     async fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
-        self.invoke(|conn| conn.delete_where(table, expr)).await
+        let table = table.to_string();
+        self.invoke(move |conn| conn.delete_where(&table, expr))
+            .await
     }
     /// Tests if a table exists in the database.
     async fn has_table(&self, table: &str) -> Result<bool> {
-        self.invoke(|conn| conn.has_table(table)).await
+        let table = table.to_string();
+        self.invoke(move |conn| conn.has_table(&table)).await
+    }
+    async fn introspect(&self) -> Result<crate::migrations::adb::ADB> {
+        self.invoke(|conn| conn.introspect()).await
+    }
+    fn cancel_handle(&self) -> Option<super::CancelHandle> {
+        self.cancel.clone()
     }
 }
 
@@ -369,17 +457,29 @@ impl<T> BackendConnectionAsync for AsyncAdapter<T>
 where
     T: BackendConnection,
 {
-    async fn transaction<'c>(&'c mut self) -> Result<TransactionAsync<'c>> {
-        let transaction_ptr: SyncSendPtrMut<dyn BackendTransaction> = self
-            .invoke_mut(|conn| {
-                let transaction: Transaction = conn.transaction()?;
-                let transaction_ptr: *mut dyn BackendTransaction = Box::into_raw(transaction.trans);
-                Ok(unsafe { SyncSendPtrMut::new(transaction_ptr) })
+    async fn transaction_with<'c>(
+        &'c mut self,
+        options: TransactionOptions,
+    ) -> Result<TransactionAsync<'c>> {
+        let pending = self
+            .invoke_mut(move |conn| {
+                let transaction: Transaction = conn.transaction_with(options)?;
+                Ok(pending_transaction(transaction.trans))
             })
             .await?;
-        let transaction_adapter = self.create_with_same_env(transaction_ptr);
+        let transaction_adapter = self.create_with_same_env(received_transaction(pending));
         Ok(TransactionAsync::new(Box::new(transaction_adapter)))
     }
+    async fn commit_prepared(&mut self, gid: &str) -> Result<()> {
+        let gid = gid.to_string();
+        self.invoke_mut(move |conn| conn.commit_prepared(&gid))
+            .await
+    }
+    async fn rollback_prepared(&mut self, gid: &str) -> Result<()> {
+        let gid = gid.to_string();
+        self.invoke_mut(move |conn| conn.rollback_prepared(&gid))
+            .await
+    }
 
     fn backend(&self) -> Box<dyn Backend> {
         ok_or_panic_with_adapter_error(self.invoke_blocking(|conn| Ok(conn.backend())))
@@ -394,6 +494,53 @@ fn backend_name(&self) -> &'static str {
     fn is_closed(&self) -> bool {
         ok_or_panic_with_adapter_error(self.invoke_blocking(|conn| Ok(conn.is_closed())))
     }
+
+    async fn ping(&self) -> Result<()> {
+        self.invoke(|conn| conn.ping()).await
+    }
+
+    fn is_broken(&self) -> bool {
+        // The connection is unusable if the thread running it has died.
+        self.invoke_blocking(|conn| Ok(conn.is_broken()))
+            .unwrap_or(true)
+    }
+
+    fn set_statement_cache_capacity(&self, capacity: usize) {
+        ok_or_panic_with_adapter_error(self.invoke_blocking(|conn| {
+            conn.set_statement_cache_capacity(capacity);
+            Ok(())
+        }))
+    }
+
+    fn set_query_logger(&self, logger: Option<QueryLogger>) {
+        ok_or_panic_with_adapter_error(self.invoke_blocking(|conn| {
+            conn.set_query_logger(logger);
+            Ok(())
+        }))
+    }
+
+    fn set_slow_query_log(&self, slow: Option<SlowQueryLog>) {
+        ok_or_panic_with_adapter_error(self.invoke_blocking(|conn| {
+            conn.set_slow_query_log(slow);
+            Ok(())
+        }))
+    }
+
+    fn set_query_limits(&self, limits: QueryLimits) {
+        ok_or_panic_with_adapter_error(self.invoke_blocking(move |conn| {
+            conn.set_query_limits(limits);
+            Ok(())
+        }))
+    }
+}
+
+/// Copies of `values`, which a function sent to the worker can own.
+fn owned_values(values: &[SqlValRef<'_>]) -> Vec<SqlVal> {
+    values.iter().cloned().map(SqlVal::from).collect()
+}
+
+fn value_refs(values: &[SqlVal]) -> Vec<SqlValRef<'_>> {
+    values.iter().map(SqlValRef::from).collect()
 }
 
 fn ok_or_panic_with_adapter_error<T>(r: Result<T>) -> T {
@@ -420,9 +567,7 @@ impl<T> AsyncAdapter<T>
     T: BackendConnection + 'static,
 {
     pub fn into_connection(self) -> ConnectionAsync {
-        ConnectionAsync {
-            conn: Box::new(self),
-        }
+        ConnectionAsync::new(Box::new(self))
     }
 }
 
@@ -437,6 +582,20 @@ async fn commit(&mut self) -> Result<()> {
     async fn rollback(&mut self) -> Result<()> {
         self.invoke_mut(|conn| conn.rollback()).await
     }
+    async fn savepoint<'s>(&'s mut self) -> Result<TransactionAsync<'s>> {
+        let pending = self
+            .invoke_mut(|trans| {
+                let savepoint: Transaction = trans.savepoint()?;
+                Ok(pending_transaction(savepoint.trans))
+            })
+            .await?;
+        let savepoint_adapter = self.create_with_same_env(received_transaction(pending));
+        Ok(TransactionAsync::new(Box::new(savepoint_adapter)))
+    }
+    async fn prepare(&mut self, gid: &str) -> Result<()> {
+        let gid = gid.to_string();
+        self.invoke_mut(move |trans| trans.prepare(&gid)).await
+    }
     fn connection_methods(&self) -> &dyn ConnectionMethodsAsync {
         self
     }
@@ -452,8 +611,10 @@ pub async fn connect_async_via_sync<B>(backend: &B, conn_str: &str) -> Result<Co
     let backend2 = backend.clone();
     let conn_str2 = conn_str.to_string();
     tokio::task::spawn_blocking(move || {
-        let connmethods_async = adapter::AsyncAdapter::new(|| backend2.connect(&conn_str2))?;
-        Ok(connmethods_async.into_connection())
+        let conn = backend2.connect(&conn_str2)?;
+        let db = conn.db;
+        let connmethods_async = adapter::AsyncAdapter::new(|| Ok(conn.conn))?;
+        Ok(connmethods_async.into_connection().with_db_attributes(db))
     })
     .await?
 }
@@ -0,0 +1,190 @@
+//! Caching of objects across connections.
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::SqlVal;
+
+/// A cache of the rows of objects, keyed by table and primary key,
+/// which may be shared by many connections. Set it with
+/// [`set_cache`][crate::db::Connection::set_cache].
+///
+/// [`get`][crate::DataObjectOps::get] and
+/// [`try_get`][crate::DataObjectOps::try_get] look objects up in the
+/// cache of their connection before querying the database, and cache
+/// what they find. Saving or deleting an object through a connection,
+/// or a transaction begun on it, invalidates its entry (in a
+/// transaction, again as it ends), deleting with a query invalidates
+/// its whole table, and running SQL with `execute` invalidates
+/// everything. Changes made through connections without
+/// the cache, or by cascading deletes, are not seen until the entries
+/// are invalidated or expire, so the cache should be shared by all the
+/// connections which write the tables it holds.
+pub trait Cache: Debug + Send + Sync {
+    /// The column values of the row of `table` with primary key `pk`,
+    /// if it is cached.
+    fn get(&self, table: &str, pk: &SqlVal) -> Option<Vec<SqlVal>>;
+    /// Cache the column values of the row of `table` with primary key
+    /// `pk`.
+    fn put(&self, table: &str, pk: SqlVal, row: Vec<SqlVal>);
+    /// Forget the row of `table` with primary key `pk`.
+    fn invalidate(&self, table: &str, pk: &SqlVal);
+    /// Forget all the rows of `table`.
+    fn invalidate_table(&self, table: &str);
+    /// Forget everything.
+    fn invalidate_all(&self);
+}
+
+/// The primary key `pk` as it is given to a [`Cache`]. Integers are
+/// widened, as the same key may be given as either.
+pub(crate) fn cache_key(pk: SqlVal) -> SqlVal {
+    match pk {
+        SqlVal::Int(v) => SqlVal::BigInt(v.into()),
+        pk => pk,
+    }
+}
+
+/// The cache of a connection as a transaction begun on it sees it.
+/// The rows the transaction writes are invalidated as they are written,
+/// and again as the transaction ends, as until it is committed another
+/// connection sharing the cache may cache them as they were before.
+#[derive(Debug)]
+pub(crate) struct TransactionCache {
+    cache: Arc<dyn Cache>,
+    written: Mutex<Vec<Written>>,
+}
+
+/// What a transaction has invalidated.
+#[derive(Debug)]
+enum Written {
+    Row(String, SqlVal),
+    Table(String),
+    All,
+}
+
+impl TransactionCache {
+    pub(crate) fn new(cache: Arc<dyn Cache>) -> Self {
+        TransactionCache {
+            cache,
+            written: Mutex::new(Vec::new()),
+        }
+    }
+    /// Invalidate again all that the transaction has written, as it
+    /// ends.
+    pub(crate) fn invalidate_written(&self) {
+        let written = std::mem::take(&mut *self.lock());
+        for w in written {
+            match w {
+                Written::Row(table, pk) => self.cache.invalidate(&table, &pk),
+                Written::Table(table) => self.cache.invalidate_table(&table),
+                Written::All => self.cache.invalidate_all(),
+            }
+        }
+    }
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Written>> {
+        self.written.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    fn record(&self, w: Written) {
+        let mut written = self.lock();
+        // Invalidating everything covers anything else.
+        if !matches!(written.last(), Some(Written::All)) {
+            if matches!(w, Written::All) {
+                written.clear();
+            }
+            written.push(w);
+        }
+    }
+}
+
+impl Cache for TransactionCache {
+    fn get(&self, table: &str, pk: &SqlVal) -> Option<Vec<SqlVal>> {
+        self.cache.get(table, pk)
+    }
+    fn put(&self, table: &str, pk: SqlVal, row: Vec<SqlVal>) {
+        self.cache.put(table, pk, row)
+    }
+    fn invalidate(&self, table: &str, pk: &SqlVal) {
+        self.cache.invalidate(table, pk);
+        self.record(Written::Row(table.to_string(), pk.clone()));
+    }
+    fn invalidate_table(&self, table: &str) {
+        self.cache.invalidate_table(table);
+        self.record(Written::Table(table.to_string()));
+    }
+    fn invalidate_all(&self) {
+        self.cache.invalidate_all();
+        self.record(Written::All);
+    }
+}
+
+impl Drop for TransactionCache {
+    fn drop(&mut self) {
+        // The transaction is done with, committed or rolled back.
+        self.invalidate_written();
+    }
+}
+
+/// The rows of a [`MokaCache`], keyed by table and primary key.
+#[cfg(feature = "moka")]
+type RowCache = moka::sync::Cache<(String, String), Vec<SqlVal>>;
+
+/// An in-memory [`Cache`] using the [`moka`](https://crates.io/crates/moka)
+/// crate, which evicts the least recently used rows once it holds
+/// `max_capacity` of them.
+#[cfg(feature = "moka")]
+#[derive(Clone, Debug)]
+pub struct MokaCache {
+    // SqlVal is not Hash, as it may hold a float, so each primary key
+    // is keyed by its debug representation, which also distinguishes
+    // its type.
+    cache: RowCache,
+}
+
+#[cfg(feature = "moka")]
+impl MokaCache {
+    /// A cache of at most `max_capacity` rows.
+    pub fn new(max_capacity: u64) -> Self {
+        MokaCache {
+            cache: Self::builder(max_capacity).build(),
+        }
+    }
+    /// A cache of at most `max_capacity` rows, each of which expires
+    /// `ttl` after it is cached, bounding how long a row changed other
+    /// than through a connection with the cache may be stale.
+    pub fn with_time_to_live(max_capacity: u64, ttl: std::time::Duration) -> Self {
+        MokaCache {
+            cache: Self::builder(max_capacity).time_to_live(ttl).build(),
+        }
+    }
+    fn builder(
+        max_capacity: u64,
+    ) -> moka::sync::CacheBuilder<(String, String), Vec<SqlVal>, RowCache> {
+        RowCache::builder()
+            .max_capacity(max_capacity)
+            .support_invalidation_closures()
+    }
+    fn key(table: &str, pk: &SqlVal) -> (String, String) {
+        (table.to_string(), format!("{pk:?}"))
+    }
+}
+
+#[cfg(feature = "moka")]
+impl Cache for MokaCache {
+    fn get(&self, table: &str, pk: &SqlVal) -> Option<Vec<SqlVal>> {
+        self.cache.get(&Self::key(table, pk))
+    }
+    fn put(&self, table: &str, pk: SqlVal, row: Vec<SqlVal>) {
+        self.cache.insert(Self::key(table, &pk), row);
+    }
+    fn invalidate(&self, table: &str, pk: &SqlVal) {
+        self.cache.invalidate(&Self::key(table, pk));
+    }
+    fn invalidate_table(&self, table: &str) {
+        let table = table.to_string();
+        self.cache
+            .invalidate_entries_if(move |key, _| key.0 == table)
+            .expect("invalidation closures are enabled");
+    }
+    fn invalidate_all(&self) {
+        self.cache.invalidate_all();
+    }
+}
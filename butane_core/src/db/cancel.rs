@@ -0,0 +1,107 @@
+//! Cancelling statements while they run.
+use std::fmt::Debug;
+#[cfg(feature = "sqlite")]
+use std::sync::Arc;
+
+/// Cancels the statement a connection is running, from another thread
+/// or task. Get one with
+/// [`cancel_handle`][crate::db::ConnectionMethods::cancel_handle].
+///
+/// On PostgreSQL, [`cancel`][Self::cancel] asks the server to cancel
+/// the statement over a new connection; on SQLite, it interrupts the
+/// statement. Either way, the statement fails with an error, and if it
+/// was in a transaction, the transaction can only be rolled back.
+/// Nothing happens if the connection is not running a statement, but
+/// one which begins just as the handle is used may be cancelled.
+#[derive(Clone)]
+pub struct CancelHandle(Inner);
+
+#[derive(Clone)]
+enum Inner {
+    #[cfg(feature = "sqlite")]
+    Sqlite(Arc<rusqlite::InterruptHandle>),
+    #[cfg(feature = "pg")]
+    Pg(tokio_postgres::CancelToken),
+}
+
+impl CancelHandle {
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn sqlite(handle: rusqlite::InterruptHandle) -> Self {
+        CancelHandle(Inner::Sqlite(Arc::new(handle)))
+    }
+
+    #[cfg(feature = "pg")]
+    pub(crate) fn pg(token: tokio_postgres::CancelToken) -> Self {
+        CancelHandle(Inner::Pg(token))
+    }
+
+    /// Cancel the statement the connection is running, if any. This
+    /// does not block: on PostgreSQL, the request to cancel is sent
+    /// from a new task, or from a new thread outside a Tokio runtime.
+    pub fn cancel(&self) {
+        match self.0 {
+            #[cfg(feature = "sqlite")]
+            Inner::Sqlite(ref handle) => handle.interrupt(),
+            #[cfg(feature = "pg")]
+            Inner::Pg(ref token) => {
+                let token = token.clone();
+                let cancel = async move {
+                    #[allow(unused_variables)] // used only when logging is enabled
+                    if let Err(e) = super::pg::cancel_query(&token).await {
+                        crate::warn!("Cannot cancel Postgres statement: {}", e);
+                    }
+                };
+                match tokio::runtime::Handle::try_current() {
+                    Ok(runtime) => {
+                        runtime.spawn(cancel);
+                    }
+                    Err(_) => {
+                        std::thread::spawn(move || {
+                            match tokio::runtime::Builder::new_current_thread()
+                                .enable_all()
+                                .build()
+                            {
+                                Ok(runtime) => runtime.block_on(cancel),
+                                #[allow(unused_variables)] // used only when logging is enabled
+                                Err(e) => {
+                                    crate::warn!("Cannot cancel Postgres statement: {}", e);
+                                }
+                            }
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Debug for CancelHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancelHandle").finish_non_exhaustive()
+    }
+}
+
+/// Cancels the statement of a [`CancelHandle`] when dropped, unless it
+/// is first [disarmed][Self::disarm].
+// unused may occur if the async feature is not selected
+#[allow(unused)]
+pub(crate) struct CancelOnDrop(Option<CancelHandle>);
+
+#[allow(unused)]
+impl CancelOnDrop {
+    pub(crate) fn new(handle: Option<CancelHandle>) -> Self {
+        CancelOnDrop(handle)
+    }
+    /// The statement finished, so there is nothing to cancel.
+    pub(crate) fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(handle) = self.0.take() {
+            handle.cancel();
+        }
+    }
+}
@@ -5,6 +5,7 @@
 
 use async_trait::async_trait;
 
+use crate::migrations::adb::ADB;
 use crate::query::{BoolExpr, Expr, Order};
 use crate::{Result, SqlType, SqlVal, SqlValRef};
 
@@ -29,6 +30,27 @@ async fn query<'c>(
         offset: Option<i32>,
         sort: Option<&[Order]>,
     ) -> Result<RawQueryResult<'c>>;
+    /// Like [`query`][Self::query], but the rows are read from the
+    /// database as the stream is polled, where the backend can. By
+    /// default, all the rows are read before the stream is returned.
+    #[maybe_async_cfg::only_if(key = "async")]
+    async fn query_stream<'c>(
+        &'c self,
+        table: &str,
+        columns: &[Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+    ) -> Result<RowStream<'c>> {
+        let rows = self
+            .query(table, columns, expr, limit, offset, sort)
+            .await?;
+        let rows = vec_from_backend_rows(rows, columns)?.rows;
+        Ok(Box::pin(futures_util::stream::iter(rows.into_iter().map(
+            |row| Ok(Box::new(row) as Box<dyn BackendRow + Send + 'c>),
+        ))))
+    }
     async fn insert_returning_pk(
         &self,
         table: &str,
@@ -67,6 +89,23 @@ async fn delete(&self, table: &str, pkcol: &'static str, pk: SqlVal) -> Result<(
     async fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize>;
     /// Tests if a table exists in the database.
     async fn has_table(&self, table: &str) -> Result<bool>;
+    /// Reads the schema of the tables in the database. Column types
+    /// are those the backend would use for the reported SQL type, see
+    /// [Backend::introspected_type][crate::db::Backend::introspected_type].
+    /// Column defaults are not read.
+    async fn introspect(&self) -> Result<ADB>;
+    /// The cache [`get`][crate::DataObjectOps::get] looks objects up in
+    /// before querying the database, if any. Only connections have
+    /// one; transactions do not, so that they see their own writes.
+    fn cache(&self) -> Option<&dyn super::Cache> {
+        None
+    }
+    /// A handle to cancel the statement this connection is running
+    /// from another thread or task, if the backend supports it. Used
+    /// by [`load_with_timeout`][crate::query::QueryOpsAsync::load_with_timeout].
+    fn cancel_handle(&self) -> Option<super::CancelHandle> {
+        None
+    }
 }
 
 /// Represents a database column. Most users do not need to use this
@@ -75,10 +114,32 @@ async fn delete(&self, table: &str, pkcol: &'static str, pk: SqlVal) -> Result<(
 pub struct Column {
     name: &'static str,
     ty: SqlType,
+    part: ColumnPart,
 }
 impl Column {
     pub const fn new(name: &'static str, ty: SqlType) -> Self {
-        Column { name, ty }
+        Column {
+            name,
+            ty,
+            part: ColumnPart::Whole,
+        }
+    }
+    /// The length in bytes of the blob in column `name`, when selected.
+    pub const fn length(name: &'static str) -> Self {
+        Column {
+            name,
+            ty: SqlType::BigInt,
+            part: ColumnPart::Length,
+        }
+    }
+    /// At most `len` bytes from `offset` of the blob in column `name`,
+    /// or all of those from `offset` if `len` is `None`, when selected.
+    pub const fn range(name: &'static str, offset: u32, len: Option<u32>) -> Self {
+        Column {
+            name,
+            ty: SqlType::Blob,
+            part: ColumnPart::Range { offset, len },
+        }
     }
     pub fn name(&self) -> &'static str {
         self.name
@@ -86,6 +147,21 @@ pub fn name(&self) -> &'static str {
     pub fn ty(&self) -> &SqlType {
         &self.ty
     }
+    pub fn part(&self) -> ColumnPart {
+        self.part
+    }
+}
+
+/// The part of a column's value selected by a [Column].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnPart {
+    /// The whole value.
+    Whole,
+    /// The length in bytes of a blob.
+    Length,
+    /// At most `len` bytes of a blob from `offset`, or all of those
+    /// from `offset` if `len` is `None`.
+    Range { offset: u32, len: Option<u32> },
 }
 
 /// Backend-specific row abstraction. Only implementors of new
@@ -142,6 +218,10 @@ fn next(&mut self) -> Result<Option<Self::Item>> {
 }
 
 pub type RawQueryResult<'a> = Box<dyn BackendRows + 'a>;
+/// Rows returned from [`query_stream`][ConnectionMethodsAsync::query_stream].
+#[cfg(feature = "async")]
+pub type RowStream<'a> =
+    futures_util::stream::BoxStream<'a, Result<Box<dyn BackendRow + Send + 'a>>>;
 pub type QueryResult<T> = Vec<T>;
 
 #[derive(Debug)]
@@ -156,7 +236,8 @@ pub fn new(rows: Vec<T>) -> Self {
     }
 }
 
-#[cfg(feature = "async-adapter")]
+// unused may occur if no backends are selected
+#[allow(unused)]
 pub(crate) fn vec_from_backend_rows<'a>(
     mut other: Box<dyn BackendRows + 'a>,
     columns: &[Column],
@@ -192,15 +273,13 @@ fn current(&self) -> Option<&(dyn BackendRow)> {
     }
 }
 
-#[cfg(feature = "async-adapter")]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct VecRow {
     values: Vec<SqlVal>,
 }
 
-#[cfg(feature = "async-adapter")]
 impl VecRow {
-    fn new(original: &(dyn BackendRow), columns: &[Column]) -> Result<Self> {
+    pub(crate) fn new(original: &(dyn BackendRow), columns: &[Column]) -> Result<Self> {
         if original.len() != columns.len() {
             return Err(crate::Error::BoundsError(
                 "row length doesn't match columns specifier length".into(),
@@ -216,9 +295,14 @@ fn new(original: &(dyn BackendRow), columns: &[Column]) -> Result<Self> {
                 .collect::<Result<Vec<SqlVal>>>()?,
         })
     }
+    pub(crate) fn from_values(values: Vec<SqlVal>) -> Self {
+        Self { values }
+    }
+    pub(crate) fn into_values(self) -> Vec<SqlVal> {
+        self.values
+    }
 }
 
-#[cfg(feature = "async-adapter")]
 impl BackendRow for VecRow {
     fn get(&self, idx: usize, ty: SqlType) -> Result<SqlValRef> {
         self.values
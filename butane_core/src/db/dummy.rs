@@ -25,6 +25,12 @@ fn row_id_column(&self) -> Option<&'static str> {
     fn create_migration_sql(&self, current: &adb::ADB, ops: Vec<adb::Operation>) -> Result<String> {
         Err(Error::PoisonedConnection)
     }
+    fn introspected_column(&self, col: &adb::AColumn) -> adb::AColumn {
+        col.clone()
+    }
+    fn migration_lock_sql(&self) -> Option<(&'static str, &'static str)> {
+        None
+    }
     fn connect(&self, conn_str: &str) -> Result<Connection> {
         Err(Error::PoisonedConnection)
     }
@@ -110,6 +116,9 @@ async fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
     async fn has_table(&self, table: &str) -> Result<bool> {
         Err(Error::PoisonedConnection)
     }
+    async fn introspect(&self) -> Result<adb::ADB> {
+        Err(Error::PoisonedConnection)
+    }
 }
 
 #[maybe_async_cfg::maybe(
@@ -123,7 +132,10 @@ async fn has_table(&self, table: &str) -> Result<bool> {
 )]
 #[async_trait]
 impl BackendConnection for DummyConnection {
-    async fn transaction(&mut self) -> Result<Transaction<'_>> {
+    async fn transaction_with(
+        &mut self,
+        _options: super::TransactionOptions,
+    ) -> Result<Transaction<'_>> {
         Err(Error::PoisonedConnection)
     }
     fn backend(&self) -> Box<dyn Backend> {
@@ -135,4 +147,14 @@ fn backend_name(&self) -> &'static str {
     fn is_closed(&self) -> bool {
         true
     }
+    async fn ping(&self) -> Result<()> {
+        Err(Error::PoisonedConnection)
+    }
+    fn is_broken(&self) -> bool {
+        true
+    }
+    fn set_statement_cache_capacity(&self, _capacity: usize) {}
+    fn set_query_logger(&self, _logger: Option<super::QueryLogger>) {}
+    fn set_slow_query_log(&self, _slow: Option<super::SlowQueryLog>) {}
+    fn set_query_limits(&self, _limits: super::QueryLimits) {}
 }
@@ -6,8 +6,8 @@
 use std::borrow::Cow;
 use std::fmt::Write;
 
-use super::Column;
-use crate::migrations::adb::{AColumn, TypeIdentifier};
+use super::{Column, ColumnPart};
+use crate::migrations::adb::{AColumn, AConstraint, AConstraintKind, AIndex, TypeIdentifier};
 use crate::query::Expr::{Condition, Placeholder, Val};
 use crate::query::{BoolExpr::*, Expr, Join, Order, OrderDirection};
 use crate::Error;
@@ -17,9 +17,15 @@ pub trait PlaceholderSource {
     fn next_placeholder(&mut self) -> Cow<str>;
 }
 
+/// Whether `word` is a reserved word, which is quoted when used as a
+/// name.
+pub fn is_reserved_word(word: &str) -> bool {
+    sqlparser::keywords::ALL_KEYWORDS.contains(&word.to_uppercase().as_str())
+}
+
 /// Quotes the `word` if it is a reserved word.
 pub fn quote_reserved_word(word: &str) -> Cow<str> {
-    if sqlparser::keywords::ALL_KEYWORDS.contains(&word.to_uppercase().as_str()) {
+    if is_reserved_word(word) {
         format!("\"{}\"", word).into()
     } else {
         word.into()
@@ -63,6 +69,30 @@ pub fn sql_for_expr<F, P, W>(expr: Expr, f: F, values: &mut Vec<SqlVal>, pls: &m
             Le(col, ex) => write!(w, "{col} <= ").and_then(|_| Ok(f(ex, values, pls, w))),
             Ge(col, ex) => write!(w, "{col} >= ").and_then(|_| Ok(f(ex, values, pls, w))),
             Like(col, ex) => write!(w, "{col} like ").and_then(|_| Ok(f(ex, values, pls, w))),
+            Contains(col, ex) => write!(w, "{} @> ", quote_reserved_word(col))
+                .and_then(|_| Ok(f(ex, values, pls, w))),
+            Overlaps(col, ex) => write!(w, "{} && ", quote_reserved_word(col))
+                .and_then(|_| Ok(f(ex, values, pls, w))),
+            PathEq(col, path, ex) => write!(
+                w,
+                "{} #> {} = ",
+                quote_reserved_word(col),
+                text_array_literal(&path)
+            )
+            .and_then(|_| Ok(f(ex, values, pls, w))),
+            KeyEq(col, key, ex) => write!(
+                w,
+                "{} -> {}::text = ",
+                quote_reserved_word(col),
+                sql_literal_value(&SqlVal::Text(key)).unwrap()
+            )
+            .and_then(|_| Ok(f(ex, values, pls, w))),
+            HasFlags(col, ex) => write!(w, "(~{} & ", quote_reserved_word(col))
+                .and_then(|_| Ok(f(ex, values, pls, w)))
+                .and_then(|_| write!(w, ") = 0")),
+            HasAnyFlag(col, ex) => write!(w, "({} & ", quote_reserved_word(col))
+                .and_then(|_| Ok(f(ex, values, pls, w)))
+                .and_then(|_| write!(w, ") <> 0")),
             AllOf(conds) => {
                 let mut remaining = conds.len();
                 for cond in conds {
@@ -135,6 +165,27 @@ pub fn sql_for_expr<F, P, W>(expr: Expr, f: F, values: &mut Vec<SqlVal>, pls: &m
                 }
                 write!(w, ")")
             }
+            DatePart {
+                col,
+                part,
+                op,
+                expr,
+            } => {
+                let col = quote_reserved_word(col);
+                match part {
+                    query::DatePart::Year => write!(w, "EXTRACT(YEAR FROM {col})"),
+                    query::DatePart::Month => write!(w, "EXTRACT(MONTH FROM {col})"),
+                    query::DatePart::Day => write!(w, "EXTRACT(DAY FROM {col})"),
+                    query::DatePart::Hour => write!(w, "EXTRACT(HOUR FROM {col})"),
+                    query::DatePart::Minute => write!(w, "EXTRACT(MINUTE FROM {col})"),
+                    // The seconds extracted include their fraction.
+                    query::DatePart::Second => write!(w, "FLOOR(EXTRACT(SECOND FROM {col}))"),
+                }
+                .unwrap();
+                write!(w, " {} ", op.sql()).unwrap();
+                f(expr, values, pls, w);
+                Ok(())
+            }
         },
     }
     .unwrap()
@@ -142,7 +193,23 @@ pub fn sql_for_expr<F, P, W>(expr: Expr, f: F, values: &mut Vec<SqlVal>, pls: &m
 
 pub fn sql_select(columns: &[Column], table: &str, w: &mut impl Write) {
     write!(w, "SELECT ").unwrap();
-    list_columns(columns, w);
+    columns.iter().fold("", |sep, c| {
+        let name = quote_reserved_word(c.name());
+        match c.part() {
+            ColumnPart::Whole => write!(w, "{sep}{name}"),
+            ColumnPart::Length => write!(w, "{sep}length({name})"),
+            // SQL counts the bytes of a blob from 1.
+            ColumnPart::Range { offset, len: None } => {
+                write!(w, "{sep}substr({name}, {})", u64::from(offset) + 1)
+            }
+            ColumnPart::Range {
+                offset,
+                len: Some(len),
+            } => write!(w, "{sep}substr({name}, {}, {len})", u64::from(offset) + 1),
+        }
+        .unwrap();
+        ", "
+    });
     write!(w, " FROM {}", quote_reserved_word(table)).unwrap();
 }
 
@@ -231,21 +298,111 @@ pub fn column_default(col: &AColumn) -> Result<SqlVal> {
             SqlType::Bool => SqlVal::Bool(false),
             SqlType::Int => SqlVal::Int(0),
             SqlType::BigInt => SqlVal::Int(0),
+            SqlType::HugeInt => SqlVal::HugeInt(0),
             SqlType::Real => SqlVal::Real(0.0),
-            SqlType::Text => SqlVal::Text("".to_string()),
+            SqlType::Text | SqlType::CiText => SqlVal::Text("".to_string()),
             SqlType::Blob => SqlVal::Blob(Vec::new()),
             #[cfg(feature = "json")]
             SqlType::Json => SqlVal::Json(serde_json::Value::default()),
+            #[cfg(feature = "json")]
+            SqlType::KeyValue => SqlVal::Json(serde_json::Value::Object(Default::default())),
             #[cfg(feature = "datetime")]
             SqlType::Timestamp => {
                 SqlVal::Timestamp(chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc())
             }
+            #[cfg(feature = "datetime")]
+            SqlType::TimestampTz => SqlVal::TimestampTz(
+                chrono::DateTime::from_timestamp(0, 0)
+                    .unwrap()
+                    .fixed_offset(),
+            ),
+            #[cfg(feature = "datetime")]
+            SqlType::Date => SqlVal::Date(chrono::NaiveDate::default()),
+            #[cfg(feature = "datetime")]
+            SqlType::Time => SqlVal::Time(chrono::NaiveTime::MIN),
+            #[cfg(feature = "decimal")]
+            SqlType::Numeric(_) => SqlVal::Decimal(rust_decimal::Decimal::ZERO),
+            #[cfg(feature = "uuid")]
+            SqlType::Uuid => SqlVal::Uuid(uuid::Uuid::nil()),
+            SqlType::Array(elem) => SqlVal::Array(elem, Vec::new()),
             SqlType::Custom(_) => return Err(Error::NoCustomDefault),
         },
         TypeIdentifier::Name(_) => return Err(Error::NoCustomDefault),
     })
 }
 
+/// SQL to create `index` on the table `table_name`. The index method
+/// is only included if `with_method` is true. If `concurrently` is
+/// true the index is built without blocking writes to the table, which
+/// only PostgreSQL supports.
+pub fn create_index(
+    table_name: &str,
+    index: &AIndex,
+    with_method: bool,
+    concurrently: bool,
+) -> String {
+    let columns = index
+        .columns()
+        .iter()
+        .map(|column| quote_reserved_word(column))
+        .collect::<Vec<Cow<str>>>()
+        .join(", ");
+    let method = match index.method() {
+        Some(method) if with_method => format!(" USING {method}"),
+        _ => String::new(),
+    };
+    format!(
+        "CREATE {}INDEX {}{} ON {}{} ({});",
+        if index.unique() { "UNIQUE " } else { "" },
+        if concurrently { "CONCURRENTLY " } else { "" },
+        quote_reserved_word(index.name()),
+        quote_reserved_word(table_name),
+        method,
+        columns
+    )
+}
+
+/// SQL defining `constraint`, as used in "CREATE TABLE" and
+/// "ALTER TABLE ADD".
+pub fn define_constraint(constraint: &AConstraint) -> String {
+    let definition = match constraint.kind() {
+        AConstraintKind::Unique(columns) => format!("UNIQUE ({})", list_names(columns)),
+        AConstraintKind::Check(expr) => format!("CHECK ({expr})"),
+        AConstraintKind::ForeignKey {
+            columns,
+            ref_table,
+            ref_columns,
+        } => format!(
+            "FOREIGN KEY ({}) REFERENCES {}({})",
+            list_names(columns),
+            quote_reserved_word(ref_table),
+            list_names(ref_columns)
+        ),
+    };
+    format!(
+        "CONSTRAINT {} {definition}",
+        quote_reserved_word(constraint.name())
+    )
+}
+
+fn list_names(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| quote_reserved_word(name))
+        .collect::<Vec<Cow<str>>>()
+        .join(", ")
+}
+
+/// SQL to drop the index named `name`, without blocking the table if
+/// `concurrently` is true.
+pub fn drop_index(name: &str, concurrently: bool) -> String {
+    format!(
+        "DROP INDEX {}{};",
+        if concurrently { "CONCURRENTLY " } else { "" },
+        quote_reserved_word(name)
+    )
+}
+
 /// Writes to `w` the SQL of the list of `columns`.
 pub fn list_columns(columns: &[Column], w: &mut impl Write) {
     let mut colnames: Vec<&'static str> = Vec::new();
@@ -293,6 +450,15 @@ fn sql_column(col: query::Column, w: &mut impl Write) {
     .unwrap()
 }
 
+/// A PostgreSQL `text[]` literal of `items`.
+fn text_array_literal(items: &[String]) -> String {
+    let items: Vec<String> = items
+        .iter()
+        .map(|item| format!("\"{}\"", item.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    format!("'{{{}}}'", items.join(",").replace('\'', "''"))
+}
+
 pub fn sql_literal_value(val: &SqlVal) -> Result<String> {
     use SqlVal::*;
     match val {
@@ -300,13 +466,41 @@ pub fn sql_literal_value(val: &SqlVal) -> Result<String> {
         SqlVal::Bool(val) => Ok(val.to_string()),
         Int(val) => Ok(val.to_string()),
         BigInt(val) => Ok(val.to_string()),
+        // Quoted, which SQLite stores as text and PostgreSQL converts
+        // to a numeric.
+        HugeInt(val) => Ok(format!("'{val}'")),
         Real(val) => Ok(val.to_string()),
-        Text(val) => Ok(format!("'{val}'")),
+        Text(val) => Ok(format!("'{}'", val.replace('\'', "''"))),
         Blob(val) => Ok(format!("x'{}'", hex::encode_upper(val))),
         #[cfg(feature = "json")]
-        Json(val) => Ok(format!("{val}")),
+        Json(val) => Ok(format!("'{}'", val.to_string().replace('\'', "''"))),
         #[cfg(feature = "datetime")]
         Timestamp(ndt) => Ok(ndt.format("'%Y-%m-%dT%H:%M:%S%.f'").to_string()),
+        // In UTC, as SQLite stores them, so that they compare equal.
+        #[cfg(feature = "datetime")]
+        TimestampTz(dt) => Ok(dt.to_utc().format("'%Y-%m-%dT%H:%M:%S%.9fZ'").to_string()),
+        #[cfg(feature = "datetime")]
+        Date(d) => Ok(d.format("'%Y-%m-%d'").to_string()),
+        #[cfg(feature = "datetime")]
+        Time(t) => Ok(t.format("'%H:%M:%S%.f'").to_string()),
+        // Quoted, which SQLite stores as text and PostgreSQL converts
+        // to a numeric, neither losing any digits.
+        #[cfg(feature = "decimal")]
+        Decimal(d) => Ok(format!("'{d}'")),
+        // As SQLite stores them. PostgreSQL has its own UUID literal.
+        #[cfg(all(feature = "uuid", not(feature = "sqlite-uuid-text")))]
+        Uuid(u) => Ok(format!("x'{}'", hex::encode_upper(u.as_bytes()))),
+        #[cfg(feature = "sqlite-uuid-text")]
+        Uuid(u) => Ok(format!("'{u}'")),
+        // The JSON array which SQLite stores. PostgreSQL has its own
+        // array literal.
+        Array(..) => Ok(format!(
+            "'{}'",
+            crate::migrations::sqlval_to_json(&val.as_ref())
+                .unwrap_or_default()
+                .to_string()
+                .replace('\'', "''")
+        )),
         Custom(val) => Err(Error::LiteralForCustomUnsupported(*(*val).clone())),
     }
 }
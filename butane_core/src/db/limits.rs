@@ -0,0 +1,105 @@
+//! Limits on the rows read for queries.
+use std::sync::RwLock;
+
+use super::connmethods::{BackendRow, BackendRows};
+use crate::{Error, Result};
+
+/// How many rows a streamed query fetches at a time unless
+/// [`set_query_limits`][crate::db::BackendConnection::set_query_limits]
+/// is called.
+pub const DEFAULT_FETCH_SIZE: usize = 1000;
+
+/// Limits on the rows a connection reads for its queries. Set them with
+/// [`set_query_limits`][crate::db::BackendConnection::set_query_limits].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueryLimits {
+    /// How many rows a streamed query fetches from the database at a
+    /// time. PostgreSQL fetches the rows of
+    /// [`query_stream`][crate::db::ConnectionMethodsAsync::query_stream]
+    /// from a cursor in batches of this size, while SQLite reads rows
+    /// one at a time regardless.
+    pub fetch_size: usize,
+    /// The most rows a query may read into memory. A query which
+    /// returns more fails with [`Error::TooManyRows`] rather than
+    /// loading them. Streams which fetch a batch at a time are not
+    /// limited, but those of backends without cursors, which read all
+    /// the rows first, are.
+    pub max_rows: Option<usize>,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        QueryLimits {
+            fetch_size: DEFAULT_FETCH_SIZE,
+            max_rows: None,
+        }
+    }
+}
+
+impl QueryLimits {
+    /// Fail with [`Error::TooManyRows`] if `count` rows is more than
+    /// allowed.
+    // unused may occur if the pg backend is not selected
+    #[allow(unused)]
+    pub(crate) fn check(&self, count: usize) -> Result<()> {
+        match self.max_rows {
+            Some(max) if count > max => Err(Error::TooManyRows(max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The query limits of a connection, shared with its transactions.
+#[derive(Debug, Default)]
+pub(crate) struct SharedLimits(RwLock<QueryLimits>);
+
+// unused may occur if no backends are selected
+#[allow(unused)]
+impl SharedLimits {
+    pub(crate) fn get(&self) -> QueryLimits {
+        *self
+            .0
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+    pub(crate) fn set(&self, limits: QueryLimits) {
+        *self
+            .0
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = limits;
+    }
+}
+
+/// Rows which fail with [`Error::TooManyRows`] once more than `max`
+/// are read.
+// unused may occur if the sqlite backend is not selected
+#[allow(unused)]
+pub(crate) struct LimitedRows<R> {
+    rows: R,
+    read: usize,
+    max: usize,
+}
+
+// unused may occur if the sqlite backend is not selected
+#[allow(unused)]
+impl<R> LimitedRows<R> {
+    pub(crate) fn new(rows: R, max: usize) -> Self {
+        LimitedRows { rows, read: 0, max }
+    }
+}
+
+impl<R: BackendRows> BackendRows for LimitedRows<R> {
+    fn next<'a>(&'a mut self) -> Result<Option<&'a (dyn BackendRow + 'a)>> {
+        let row = self.rows.next()?;
+        if row.is_some() {
+            self.read += 1;
+            if self.read > self.max {
+                return Err(Error::TooManyRows(self.max));
+            }
+        }
+        Ok(row)
+    }
+    fn current<'a>(&'a self) -> Option<&'a (dyn BackendRow + 'a)> {
+        self.rows.current()
+    }
+}
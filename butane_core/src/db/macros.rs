@@ -5,7 +5,8 @@ macro_rules! connection_method_wrapper {
             idents(
                 Connection(sync = "Connection"),
                 ConnectionMethods(sync = "ConnectionMethods"),
-                Transaction(sync = "Transaction")
+                Transaction(sync = "Transaction"),
+                in_span(sync = "in_span", async = "in_span_async")
             ),
             sync(keep_self),
             async(feature = "async")
@@ -13,7 +14,17 @@ macro_rules! connection_method_wrapper {
         #[async_trait::async_trait]
         impl ConnectionMethods for $ty {
             async fn execute(&self, sql: &str) -> Result<()> {
-                ConnectionMethods::execute(self.wrapped_connection_methods()?, sql).await
+                let span = $crate::trace::op_span!("butane.execute", within: self.trace_parent());
+                $crate::trace::record_db(&span, self.db_attributes(), $crate::trace::operation(sql));
+                let result = $crate::trace::in_span(span, move || async move {
+                    let conn = self.wrapped_connection_methods()?;
+                    ConnectionMethods::execute(conn, sql).await
+                })
+                .await;
+                if let Some(cache) = self.invalidated_cache() {
+                    cache.invalidate_all();
+                }
+                result
             }
             async fn query<'c>(
                 &'c self,
@@ -24,9 +35,39 @@ async fn query<'c>(
                 offset: Option<i32>,
                 sort: Option<&[$crate::query::Order]>,
             ) -> Result<RawQueryResult<'c>> {
-                self.wrapped_connection_methods()?
-                    .query(table, columns, expr, limit, offset, sort)
-                    .await
+                let span = $crate::trace::op_span!(
+                    "butane.query",
+                    within: self.trace_parent(),
+                    table = table
+                );
+                $crate::trace::record_db(&span, self.db_attributes(), "SELECT");
+                $crate::trace::in_span(span, move || async move {
+                    let conn = self.wrapped_connection_methods()?;
+                    conn.query(table, columns, expr, limit, offset, sort).await
+                })
+                .await
+            }
+            #[maybe_async_cfg::only_if(key = "async")]
+            async fn query_stream<'c>(
+                &'c self,
+                table: &str,
+                columns: &[Column],
+                expr: Option<BoolExpr>,
+                limit: Option<i32>,
+                offset: Option<i32>,
+                sort: Option<&[$crate::query::Order]>,
+            ) -> Result<$crate::db::RowStream<'c>> {
+                let span = $crate::trace::op_span!(
+                    "butane.query",
+                    within: self.trace_parent(),
+                    table = table
+                );
+                $crate::trace::record_db(&span, self.db_attributes(), "SELECT");
+                $crate::trace::in_span(span, move || async move {
+                    let conn = self.wrapped_connection_methods()?;
+                    conn.query_stream(table, columns, expr, limit, offset, sort).await
+                })
+                .await
             }
             async fn insert_returning_pk(
                 &self,
@@ -35,9 +76,17 @@ async fn insert_returning_pk(
                 pkcol: &Column,
                 values: &[SqlValRef<'_>],
             ) -> Result<SqlVal> {
-                self.wrapped_connection_methods()?
-                    .insert_returning_pk(table, columns, pkcol, values)
-                    .await
+                let span = $crate::trace::op_span!(
+                    "butane.save",
+                    within: self.trace_parent(),
+                    table = table
+                );
+                $crate::trace::record_db(&span, self.db_attributes(), "INSERT");
+                $crate::trace::in_span(span, move || async move {
+                    let conn = self.wrapped_connection_methods()?;
+                    conn.insert_returning_pk(table, columns, pkcol, values).await
+                })
+                .await
             }
             async fn insert_only(
                 &self,
@@ -45,9 +94,17 @@ async fn insert_only(
                 columns: &[Column],
                 values: &[SqlValRef<'_>],
             ) -> Result<()> {
-                self.wrapped_connection_methods()?
-                    .insert_only(table, columns, values)
-                    .await
+                let span = $crate::trace::op_span!(
+                    "butane.save",
+                    within: self.trace_parent(),
+                    table = table
+                );
+                $crate::trace::record_db(&span, self.db_attributes(), "INSERT");
+                $crate::trace::in_span(span, move || async move {
+                    let conn = self.wrapped_connection_methods()?;
+                    conn.insert_only(table, columns, values).await
+                })
+                .await
             }
             async fn insert_or_replace(
                 &self,
@@ -56,9 +113,27 @@ async fn insert_or_replace(
                 pkcol: &Column,
                 values: &[SqlValRef<'_>],
             ) -> Result<()> {
-                self.wrapped_connection_methods()?
-                    .insert_or_replace(table, columns, pkcol, values)
-                    .await
+                let span = $crate::trace::op_span!(
+                    "butane.save",
+                    within: self.trace_parent(),
+                    table = table
+                );
+                $crate::trace::record_db(&span, self.db_attributes(), "INSERT");
+                let pk = columns
+                    .iter()
+                    .position(|column| column.name() == pkcol.name())
+                    .map(|i| $crate::db::cache_key(values[i].clone().into()));
+                let result = $crate::trace::in_span(span, move || async move {
+                    let conn = self.wrapped_connection_methods()?;
+                    conn.insert_or_replace(table, columns, pkcol, values).await
+                })
+                .await;
+                match (self.invalidated_cache(), pk) {
+                    (Some(cache), Some(pk)) => cache.invalidate(table, &pk),
+                    (Some(cache), None) => cache.invalidate_table(table),
+                    (None, _) => {}
+                }
+                result
             }
             async fn update(
                 &self,
@@ -68,23 +143,70 @@ async fn update(
                 columns: &[Column],
                 values: &[SqlValRef<'_>],
             ) -> Result<()> {
-                self.wrapped_connection_methods()?
-                    .update(table, pkcol, pk, columns, values)
-                    .await
+                let span = $crate::trace::op_span!(
+                    "butane.save",
+                    within: self.trace_parent(),
+                    table = table
+                );
+                $crate::trace::record_db(&span, self.db_attributes(), "UPDATE");
+                let key = $crate::db::cache_key(pk.clone().into());
+                let result = $crate::trace::in_span(span, move || async move {
+                    let conn = self.wrapped_connection_methods()?;
+                    conn.update(table, pkcol, pk, columns, values).await
+                })
+                .await;
+                if let Some(cache) = self.invalidated_cache() {
+                    cache.invalidate(table, &key);
+                }
+                result
             }
             async fn delete(&self, table: &str, pkcol: &'static str, pk: SqlVal) -> Result<()> {
-                self.wrapped_connection_methods()?
-                    .delete(table, pkcol, pk)
-                    .await
+                let span = $crate::trace::op_span!(
+                    "butane.delete",
+                    within: self.trace_parent(),
+                    table = table
+                );
+                $crate::trace::record_db(&span, self.db_attributes(), "DELETE");
+                let key = $crate::db::cache_key(pk.clone());
+                let result = $crate::trace::in_span(span, move || async move {
+                    let conn = self.wrapped_connection_methods()?;
+                    conn.delete(table, pkcol, pk).await
+                })
+                .await;
+                if let Some(cache) = self.invalidated_cache() {
+                    cache.invalidate(table, &key);
+                }
+                result
             }
             async fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
-                self.wrapped_connection_methods()?
-                    .delete_where(table, expr)
-                    .await
+                let span = $crate::trace::op_span!(
+                    "butane.delete",
+                    within: self.trace_parent(),
+                    table = table
+                );
+                $crate::trace::record_db(&span, self.db_attributes(), "DELETE");
+                let result = $crate::trace::in_span(span, move || async move {
+                    let conn = self.wrapped_connection_methods()?;
+                    conn.delete_where(table, expr).await
+                })
+                .await;
+                if let Some(cache) = self.invalidated_cache() {
+                    cache.invalidate_table(table);
+                }
+                result
             }
             async fn has_table(&self, table: &str) -> Result<bool> {
                 self.wrapped_connection_methods()?.has_table(table).await
             }
+            async fn introspect(&self) -> Result<$crate::migrations::adb::ADB> {
+                self.wrapped_connection_methods()?.introspect().await
+            }
+            fn cache(&self) -> Option<&dyn $crate::db::Cache> {
+                self.lookup_cache()
+            }
+            fn cancel_handle(&self) -> Option<$crate::db::CancelHandle> {
+                self.wrapped_connection_methods().ok()?.cancel_handle()
+            }
         }
     };
 }
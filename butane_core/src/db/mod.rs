@@ -15,17 +15,23 @@
 #![allow(missing_docs)]
 
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::fs;
 use std::io::Write;
 use std::ops::{Deref, DerefMut};
 use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use dyn_clone::DynClone;
+#[cfg(feature = "async")]
+use futures_util::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 
 use crate::query::{BoolExpr, Order};
+use crate::trace::{self, op_span};
 use crate::{migrations::adb, Error, Result, SqlVal, SqlValRef};
 
 #[cfg(feature = "async-adapter")]
@@ -40,14 +46,40 @@
 #[cfg(feature = "async")]
 pub use sync_adapter::SyncAdapter;
 
+mod cache;
+pub(crate) use cache::cache_key;
+pub use cache::Cache;
+#[cfg(feature = "moka")]
+pub use cache::MokaCache;
+use cache::TransactionCache;
+mod cancel;
+pub use cancel::CancelHandle;
+#[allow(unused_imports)] // unused if the async feature is not selected
+pub(crate) use cancel::CancelOnDrop;
 mod connmethods;
-#[cfg(feature = "async")]
-pub use connmethods::ConnectionMethodsAsync;
+pub(crate) use connmethods::VecRow;
 pub use connmethods::{
-    BackendRow, BackendRows, Column, ConnectionMethods, MapDeref, QueryResult, RawQueryResult,
+    BackendRow, BackendRows, Column, ColumnPart, ConnectionMethods, MapDeref, QueryResult,
+    RawQueryResult,
 };
-mod helper;
+#[cfg(feature = "async")]
+pub use connmethods::{ConnectionMethodsAsync, RowStream};
+pub(crate) mod helper;
+mod limits;
 mod macros;
+#[cfg(feature = "async")]
+mod pipeline;
+mod querylog;
+mod session;
+// unused may occur if no backends are selected
+#[allow(unused_imports)]
+pub(crate) use limits::{LimitedRows, SharedLimits};
+pub use limits::{QueryLimits, DEFAULT_FETCH_SIZE};
+#[cfg(feature = "async")]
+pub use pipeline::Pipeline;
+pub(crate) use querylog::QueryLog;
+pub use querylog::{QueryInfo, QueryLogger, SlowQueryLog};
+pub use session::Session;
 #[cfg(feature = "pg")]
 pub mod pg;
 
@@ -82,6 +114,75 @@ pub trait AsyncRequiresSync: Sync {}
     impl<T: Sync> AsyncRequiresSync for T {}
 }
 
+/// How many prepared statements a connection keeps for reuse unless
+/// [`set_statement_cache_capacity`][BackendConnection::set_statement_cache_capacity]
+/// is called.
+pub const STATEMENT_CACHE_CAPACITY: usize = 128;
+
+/// Isolation level of a transaction. SQLite transactions are always
+/// serializable; see [`TransactionOptions`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+/// Options for beginning a transaction with
+/// [`transaction_with`][BackendConnection::transaction_with].
+///
+/// On PostgreSQL these are set with `SET TRANSACTION`. SQLite begins
+/// a serializable transaction with `BEGIN IMMEDIATE`, taking the write
+/// lock at once rather than when the transaction first writes, so that
+/// it cannot fail part way because another connection is writing, and
+/// any other transaction with `BEGIN DEFERRED`. A read-only SQLite
+/// transaction sets `PRAGMA query_only` until it ends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TransactionOptions {
+    /// The isolation level, or the backend's default if `None`.
+    pub isolation: Option<IsolationLevel>,
+    /// Whether the transaction may only read from the database.
+    pub read_only: bool,
+}
+
+/// How [`run_in_transaction_with`][Connection::run_in_transaction_with]
+/// retries a transaction which failed with a [retryable][crate::Error::is_retryable]
+/// error. The wait before each retry doubles, starting from
+/// `initial_backoff` and limited to `max_backoff`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times to retry before returning the error.
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+impl RetryPolicy {
+    /// Never retry.
+    pub const fn none() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        }
+    }
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+impl Default for RetryPolicy {
+    /// Retry up to 5 times, waiting from 10ms up to 1s.
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
 /// Database connection.
 #[maybe_async_cfg::maybe(
     idents(
@@ -95,7 +196,22 @@ impl<T: Sync> AsyncRequiresSync for T {}
 pub trait BackendConnection: ConnectionMethods + Debug + Send {
     /// Begin a database transaction. The transaction object must be
     /// used in place of this connection until it is committed or aborted.
-    async fn transaction(&mut self) -> Result<Transaction<'_>>;
+    async fn transaction(&mut self) -> Result<Transaction<'_>> {
+        self.transaction_with(TransactionOptions::default()).await
+    }
+    /// Begin a database transaction with the given isolation level and
+    /// access mode, like [`transaction`][Self::transaction].
+    async fn transaction_with(&mut self, options: TransactionOptions) -> Result<Transaction<'_>>;
+    /// Commit a transaction prepared for two-phase commit with
+    /// [`Transaction::prepare`], by this or any other connection.
+    async fn commit_prepared(&mut self, _gid: &str) -> Result<()> {
+        Err(Error::Unsupported("Two-phase commit", self.backend_name()))
+    }
+    /// Roll back a transaction prepared for two-phase commit with
+    /// [`Transaction::prepare`], by this or any other connection.
+    async fn rollback_prepared(&mut self, _gid: &str) -> Result<()> {
+        Err(Error::Unsupported("Two-phase commit", self.backend_name()))
+    }
     /// Retrieve the backend for this connection.
     fn backend(&self) -> Box<dyn Backend>;
     /// Retrieve the backend name for this connection.
@@ -103,6 +219,30 @@ pub trait BackendConnection: ConnectionMethods + Debug + Send {
     /// Tests if the connection has been closed. Backends which do not
     /// support this check should return false.
     fn is_closed(&self) -> bool;
+    /// Check cheaply that the connection still works, such as for a
+    /// readiness probe, by making a round trip to the database where
+    /// the backend has one. The pools check connections with this
+    /// before reusing them.
+    async fn ping(&self) -> Result<()>;
+    /// Tests, without any I/O, whether the connection is known to be
+    /// unusable, such as because it has been closed or its adapter has
+    /// failed. A connection which is not broken may still fail to
+    /// [`ping`][Self::ping].
+    fn is_broken(&self) -> bool;
+    /// Set how many prepared statements this connection keeps for
+    /// reuse, discarding the least recently used beyond that. It is
+    /// [`STATEMENT_CACHE_CAPACITY`] to begin with, and zero disables
+    /// the cache.
+    fn set_statement_cache_capacity(&self, capacity: usize);
+    /// Set a function to be called with each statement this connection
+    /// and its transactions run, or remove it with `None`.
+    fn set_query_logger(&self, logger: Option<QueryLogger>);
+    /// Report the statements this connection and its transactions run
+    /// which are slow, or stop with `None`.
+    fn set_slow_query_log(&self, slow: Option<SlowQueryLog>);
+    /// Set the limits on the rows this connection and its transactions
+    /// read for queries.
+    fn set_query_limits(&self, limits: QueryLimits);
 }
 
 #[maybe_async_cfg::maybe(
@@ -117,8 +257,14 @@ pub trait BackendConnection: ConnectionMethods + Debug + Send {
 )]
 #[async_trait]
 impl BackendConnection for Box<dyn BackendConnection> {
-    async fn transaction(&mut self) -> Result<Transaction> {
-        self.deref_mut().transaction().await
+    async fn transaction_with(&mut self, options: TransactionOptions) -> Result<Transaction> {
+        self.deref_mut().transaction_with(options).await
+    }
+    async fn commit_prepared(&mut self, gid: &str) -> Result<()> {
+        self.deref_mut().commit_prepared(gid).await
+    }
+    async fn rollback_prepared(&mut self, gid: &str) -> Result<()> {
+        self.deref_mut().rollback_prepared(gid).await
     }
     fn backend(&self) -> Box<dyn Backend> {
         self.deref().backend()
@@ -129,6 +275,24 @@ fn backend_name(&self) -> &'static str {
     fn is_closed(&self) -> bool {
         self.deref().is_closed()
     }
+    async fn ping(&self) -> Result<()> {
+        self.deref().ping().await
+    }
+    fn is_broken(&self) -> bool {
+        self.deref().is_broken()
+    }
+    fn set_statement_cache_capacity(&self, capacity: usize) {
+        self.deref().set_statement_cache_capacity(capacity)
+    }
+    fn set_query_logger(&self, logger: Option<QueryLogger>) {
+        self.deref().set_query_logger(logger)
+    }
+    fn set_slow_query_log(&self, slow: Option<SlowQueryLog>) {
+        self.deref().set_slow_query_log(slow)
+    }
+    fn set_query_limits(&self, limits: QueryLimits) {
+        self.deref().set_query_limits(limits)
+    }
 }
 
 #[maybe_async_cfg::maybe(
@@ -158,6 +322,20 @@ async fn query<'c>(
             .query(table, columns, expr, limit, offset, sort)
             .await
     }
+    #[maybe_async_cfg::only_if(key = "async")]
+    async fn query_stream<'c>(
+        &'c self,
+        table: &str,
+        columns: &[Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+    ) -> Result<RowStream<'c>> {
+        self.deref()
+            .query_stream(table, columns, expr, limit, offset, sort)
+            .await
+    }
     async fn insert_returning_pk(
         &self,
         table: &str,
@@ -204,6 +382,12 @@ async fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
     async fn has_table(&self, table: &str) -> Result<bool> {
         self.deref().has_table(table).await
     }
+    async fn introspect(&self) -> Result<adb::ADB> {
+        self.deref().introspect().await
+    }
+    fn cancel_handle(&self) -> Option<CancelHandle> {
+        self.deref().cancel_handle()
+    }
 }
 
 /// Database connection. May be a connection to any type of database
@@ -216,25 +400,165 @@ async fn has_table(&self, table: &str) -> Result<bool> {
 #[derive(Debug)]
 pub struct Connection {
     conn: Box<dyn BackendConnection>,
+    db: trace::DbAttributes,
+    cache: Option<Arc<dyn Cache>>,
 }
 
 #[maybe_async_cfg::maybe(
-    idents(BackendConnection(sync = "BackendConnection")),
+    idents(
+        BackendConnection(sync = "BackendConnection"),
+        ConnectionMethods(sync = "ConnectionMethods")
+    ),
     sync(keep_self),
     async(feature = "async")
 )]
 impl Connection {
     pub fn new(conn: Box<dyn BackendConnection>) -> Self {
-        Self { conn }
+        Self {
+            conn,
+            db: trace::DbAttributes::default(),
+            cache: None,
+        }
+    }
+    /// Set the [`Cache`] which [`get`][crate::DataObjectOps::get] reads
+    /// objects through, and which saves and deletes through this
+    /// connection and its transactions invalidate. The same cache is
+    /// usually shared by all the connections to a database.
+    pub fn set_cache(&mut self, cache: Option<Arc<dyn Cache>>) {
+        self.cache = cache;
+    }
+    /// Describe the database connected to on the spans of operations.
+    // unused may occur if no backends are selected
+    #[allow(unused)]
+    pub(crate) fn with_db_attributes(mut self, db: trace::DbAttributes) -> Self {
+        self.db = db;
+        self
     }
     pub async fn execute(&self, sql: impl AsRef<str>) -> Result<()> {
-        self.conn.execute(sql.as_ref()).await
+        ConnectionMethods::execute(self, sql.as_ref()).await
     }
     // For use with connection_method_wrapper macro.
     #[allow(clippy::unnecessary_wraps)]
     fn wrapped_connection_methods(&self) -> Result<&dyn BackendConnection> {
         Ok(self.conn.as_ref())
     }
+    // For use with connection_method_wrapper macro.
+    #[allow(clippy::unnecessary_wraps)]
+    fn trace_parent(&self) -> Option<trace::Span> {
+        Some(trace::Span::current())
+    }
+    // For use with connection_method_wrapper macro.
+    fn db_attributes(&self) -> &trace::DbAttributes {
+        &self.db
+    }
+    // For use with connection_method_wrapper macro.
+    fn lookup_cache(&self) -> Option<&dyn Cache> {
+        self.cache.as_deref()
+    }
+    // For use with connection_method_wrapper macro.
+    fn invalidated_cache(&self) -> Option<&dyn Cache> {
+        self.cache.as_deref()
+    }
+
+    /// Run `f` in a transaction, which is committed if `f` returns `Ok`
+    /// and rolled back if it returns `Err`.
+    #[maybe_async_cfg::only_if(key = "sync")]
+    pub fn run_in_transaction<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnMut(&mut Transaction<'_>) -> Result<T>,
+    {
+        self.run_in_transaction_with(TransactionOptions::default(), RetryPolicy::none(), f)
+    }
+
+    /// Like [`run_in_transaction`][Self::run_in_transaction], but begins
+    /// the transaction with `options`, and runs `f` again in a new
+    /// transaction as allowed by `retry` if it fails with a
+    /// [retryable][crate::Error::is_retryable] error.
+    #[maybe_async_cfg::only_if(key = "sync")]
+    pub fn run_in_transaction_with<T, F>(
+        &mut self,
+        options: TransactionOptions,
+        retry: RetryPolicy,
+        mut f: F,
+    ) -> Result<T>
+    where
+        F: FnMut(&mut Transaction<'_>) -> Result<T>,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .transaction_with(options)
+                .and_then(|mut tr| match f(&mut tr) {
+                    Ok(value) => tr.commit().map(|()| value),
+                    Err(e) => {
+                        // The error from f is more useful than any from rolling back.
+                        let _ = tr.rollback();
+                        Err(e)
+                    }
+                });
+            match result {
+                Err(e) if e.is_retryable() && attempt < retry.max_retries => {
+                    std::thread::sleep(retry.backoff(attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Run `f` in a transaction, which is committed if `f` returns `Ok`
+    /// and rolled back if it returns `Err`. As `f` borrows the
+    /// transaction, it must box its future:
+    ///
+    /// ```ignore
+    /// conn.run_in_transaction(|tr| Box::pin(async move { post.save(tr).await }))
+    ///     .await?;
+    /// ```
+    #[maybe_async_cfg::only_if(key = "async")]
+    pub async fn run_in_transaction<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: for<'t> FnMut(&'t mut TransactionAsync<'_>) -> BoxFuture<'t, Result<T>>,
+    {
+        self.run_in_transaction_with(TransactionOptions::default(), RetryPolicy::none(), f)
+            .await
+    }
+
+    /// Like [`run_in_transaction`][Self::run_in_transaction], but begins
+    /// the transaction with `options`, and runs `f` again in a new
+    /// transaction as allowed by `retry` if it fails with a
+    /// [retryable][crate::Error::is_retryable] error.
+    #[maybe_async_cfg::only_if(key = "async")]
+    pub async fn run_in_transaction_with<T, F>(
+        &mut self,
+        options: TransactionOptions,
+        retry: RetryPolicy,
+        mut f: F,
+    ) -> Result<T>
+    where
+        F: for<'t> FnMut(&'t mut TransactionAsync<'_>) -> BoxFuture<'t, Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = match self.transaction_with(options).await {
+                Ok(mut tr) => match f(&mut tr).await {
+                    Ok(value) => tr.commit().await.map(|()| value),
+                    Err(e) => {
+                        // The error from f is more useful than any from rolling back.
+                        let _ = tr.rollback().await;
+                        Err(e)
+                    }
+                },
+                Err(e) => Err(e),
+            };
+            match result {
+                Err(e) if e.is_retryable() && attempt < retry.max_retries => {
+                    tokio::time::sleep(retry.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
 
     /// Consume this connection and convert it into an async one.
     /// Note that the under the hood this adds an adapter layer which runs
@@ -243,7 +567,10 @@ fn wrapped_connection_methods(&self) -> Result<&dyn BackendConnection> {
     #[maybe_async_cfg::only_if(key = "sync")]
     #[cfg(feature = "async-adapter")]
     pub fn into_async(self) -> Result<ConnectionAsync> {
-        Ok(adapter::AsyncAdapter::new(|| Ok(self))?.into_connection())
+        let db = self.db;
+        Ok(adapter::AsyncAdapter::new(|| Ok(self.conn))?
+            .into_connection()
+            .with_db_attributes(db))
     }
 
     /// Runs the provided function with a synchronous wrapper around this asynchronous connection.
@@ -260,13 +587,14 @@ pub async fn with_sync<F, T>(&mut self, f: F) -> Result<T>
     {
         let mut conn2 = Connection::new(Box::new(dummy::DummyConnection::new()));
         std::mem::swap(&mut conn2, self);
-        let ret: Result<(Result<T>, Connection)> = tokio::task::spawn_blocking(|| {
-            let mut sync_conn = SyncAdapter::new(conn2)?;
-            let f_ret = f(&mut sync_conn);
-            let async_conn = sync_conn.into_inner();
-            Ok((f_ret, async_conn))
-        })
-        .await?;
+        let ret: Result<(Result<T>, Connection)> =
+            tokio::task::spawn_blocking(trace::in_current_span(|| {
+                let mut sync_conn = SyncAdapter::new(conn2)?;
+                let f_ret = f(&mut sync_conn);
+                let async_conn = sync_conn.into_inner();
+                Ok((f_ret, async_conn))
+            }))
+            .await?;
         match ret {
             Ok((inner_ret, mut conn)) => {
                 std::mem::swap(&mut conn, self);
@@ -284,7 +612,9 @@ impl ConnectionAsync {
     /// Note that the under the hood this adds an adapter layer which drives
     /// the async connection  -- the async machinery is not eliminated.
     pub fn into_sync(self) -> Result<Connection> {
-        Ok(SyncAdapter::new(self)?.into_connection())
+        Ok(SyncAdapter::new(self.conn)?
+            .into_connection()
+            .with_db_attributes(self.db))
     }
 }
 
@@ -299,8 +629,18 @@ pub fn into_sync(self) -> Result<Connection> {
 )]
 #[async_trait]
 impl BackendConnection for Connection {
-    async fn transaction(&mut self) -> Result<Transaction> {
-        self.conn.transaction().await
+    async fn transaction_with(&mut self, options: TransactionOptions) -> Result<Transaction> {
+        let span = op_span!("butane.transaction");
+        let trans = self.conn.transaction_with(options).await?;
+        Ok(trans
+            .traced(span, self.db.clone())
+            .with_cache(self.cache.clone()))
+    }
+    async fn commit_prepared(&mut self, gid: &str) -> Result<()> {
+        self.conn.commit_prepared(gid).await
+    }
+    async fn rollback_prepared(&mut self, gid: &str) -> Result<()> {
+        self.conn.rollback_prepared(gid).await
     }
     fn backend(&self) -> Box<dyn Backend> {
         self.conn.backend()
@@ -311,11 +651,33 @@ fn backend_name(&self) -> &'static str {
     fn is_closed(&self) -> bool {
         self.conn.is_closed()
     }
+    async fn ping(&self) -> Result<()> {
+        self.conn.ping().await
+    }
+    fn is_broken(&self) -> bool {
+        self.conn.is_broken()
+    }
+    fn set_statement_cache_capacity(&self, capacity: usize) {
+        self.conn.set_statement_cache_capacity(capacity)
+    }
+    fn set_query_logger(&self, logger: Option<QueryLogger>) {
+        self.conn.set_query_logger(logger)
+    }
+    fn set_slow_query_log(&self, slow: Option<SlowQueryLog>) {
+        self.conn.set_slow_query_log(slow)
+    }
+    fn set_query_limits(&self, limits: QueryLimits) {
+        self.conn.set_query_limits(limits)
+    }
 }
 connection_method_wrapper!(Connection);
 
 #[maybe_async_cfg::maybe(
-    idents(ConnectionMethods(sync = "ConnectionMethods"), AsyncRequiresSend),
+    idents(
+        ConnectionMethods(sync = "ConnectionMethods"),
+        Transaction(sync = "Transaction"),
+        AsyncRequiresSend
+    ),
     sync(keep_self),
     async(feature = "async")
 )]
@@ -331,6 +693,11 @@ pub(super) trait BackendTransaction<'c>:
     async fn commit(&mut self) -> Result<()>;
     /// Roll back the transaction. Same comment about consuming self as above.
     async fn rollback(&mut self) -> Result<()>;
+    /// Begin a transaction nested in this one, using a savepoint.
+    async fn savepoint<'s>(&'s mut self) -> Result<Transaction<'s>>;
+    /// Prepare the transaction for two-phase commit. Same comment about
+    /// consuming self as for commit.
+    async fn prepare(&mut self, gid: &str) -> Result<()>;
 
     // Workaround for https://github.com/rust-lang/rfcs/issues/2765
     fn connection_methods(&self) -> &dyn ConnectionMethods;
@@ -348,6 +715,16 @@ pub(super) trait BackendTransaction<'c>:
 #[derive(Debug)]
 pub struct Transaction<'c> {
     pub(super) trans: Box<dyn BackendTransaction<'c> + 'c>,
+    /// The span of the transaction, the parent of the spans of its
+    /// statements. Transactions of a backend wrapped by another one
+    /// have none, so their statements are not traced twice.
+    span: Option<trace::Span>,
+    db: trace::DbAttributes,
+    /// The cache of the connection, which the transaction's writes
+    /// invalidate but its reads do not use, so that it never caches
+    /// rows which may be rolled back.
+    cache: Option<Arc<TransactionCache>>,
+    started: Instant,
 }
 
 #[maybe_async_cfg::maybe(
@@ -362,15 +739,72 @@ impl<'c> Transaction<'c> {
     // unused may occur if no backends are selected
     #[allow(unused)]
     pub(super) fn new(trans: Box<dyn BackendTransaction<'c> + 'c>) -> Self {
-        Transaction { trans }
+        Transaction {
+            trans,
+            span: None,
+            db: trace::DbAttributes::default(),
+            cache: None,
+            started: Instant::now(),
+        }
+    }
+    /// Set the span of this transaction, created as it began, and the
+    /// database it is in.
+    fn traced(mut self, span: trace::Span, db: trace::DbAttributes) -> Self {
+        self.span = Some(span);
+        self.db = db;
+        self
+    }
+    /// Set the cache invalidated by the transaction's writes.
+    fn with_cache(mut self, cache: Option<Arc<dyn Cache>>) -> Self {
+        self.cache = cache.map(|cache| Arc::new(TransactionCache::new(cache)));
+        self
+    }
+    /// Invalidate again the rows the transaction wrote, which another
+    /// connection may have cached as they were before, as it ends.
+    fn invalidate_written(&self) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate_written();
+        }
+    }
+    /// Record on the span how long the transaction ran, as it ends.
+    fn end_span(&self) {
+        if let Some(span) = &self.span {
+            trace::record_duration(span, self.started)
+        }
     }
     /// Commit the transaction.
     pub async fn commit(mut self) -> Result<()> {
-        self.trans.commit().await
+        self.end_span();
+        let result = self.trans.commit().await;
+        self.invalidate_written();
+        result
     }
     /// Roll back the transaction. Equivalent to dropping it.
     pub async fn rollback(mut self) -> Result<()> {
-        self.trans.deref_mut().rollback().await
+        self.end_span();
+        let result = self.trans.deref_mut().rollback().await;
+        self.invalidate_written();
+        result
+    }
+    /// Prepare the transaction for two-phase commit, identified by
+    /// `gid`. It is then no longer tied to this connection, and is
+    /// finished by calling
+    /// [`commit_prepared`][crate::db::BackendConnection::commit_prepared] or
+    /// [`rollback_prepared`][crate::db::BackendConnection::rollback_prepared]
+    /// with the same `gid`, from any connection to the database.
+    /// Only supported by PostgreSQL, and only if its
+    /// `max_prepared_transactions` setting is nonzero.
+    pub async fn prepare(mut self, gid: &str) -> Result<()> {
+        self.end_span();
+        self.trans.prepare(gid).await
+    }
+    /// Begin a transaction nested in this one. It is a savepoint:
+    /// committing it keeps its changes as part of this transaction,
+    /// and rolling it back or dropping it undoes only its own changes.
+    /// This lets code be transactional whether or not its caller
+    /// already began a transaction.
+    pub async fn transaction(&mut self) -> Result<Transaction<'_>> {
+        BackendTransaction::savepoint(self).await
     }
     // For use with connection_method_wrapper macro.
     #[allow(clippy::unnecessary_wraps)]
@@ -378,6 +812,22 @@ fn wrapped_connection_methods(&self) -> Result<&dyn ConnectionMethods> {
         let a: &dyn BackendTransaction<'c> = self.trans.as_ref();
         Ok(a.connection_methods())
     }
+    // For use with connection_method_wrapper macro.
+    fn trace_parent(&self) -> Option<trace::Span> {
+        self.span.clone()
+    }
+    // For use with connection_method_wrapper macro.
+    fn db_attributes(&self) -> &trace::DbAttributes {
+        &self.db
+    }
+    // For use with connection_method_wrapper macro.
+    fn lookup_cache(&self) -> Option<&dyn Cache> {
+        None
+    }
+    // For use with connection_method_wrapper macro.
+    fn invalidated_cache(&self) -> Option<&dyn Cache> {
+        self.cache.as_deref().map(|cache| cache as &dyn Cache)
+    }
 }
 
 connection_method_wrapper!(Transaction<'_>);
@@ -385,7 +835,8 @@ fn wrapped_connection_methods(&self) -> Result<&dyn ConnectionMethods> {
 #[maybe_async_cfg::maybe(
     idents(
         BackendTransaction(sync = "BackendTransaction"),
-        ConnectionMethods(sync = "ConnectionMethods")
+        ConnectionMethods(sync = "ConnectionMethods"),
+        Transaction(sync = "Transaction")
     ),
     sync(keep_self),
     async(feature = "async")
@@ -393,10 +844,31 @@ fn wrapped_connection_methods(&self) -> Result<&dyn ConnectionMethods> {
 #[async_trait]
 impl<'c> BackendTransaction<'c> for Transaction<'c> {
     async fn commit(&mut self) -> Result<()> {
-        self.trans.commit().await
+        self.end_span();
+        let result = self.trans.commit().await;
+        self.invalidate_written();
+        result
     }
     async fn rollback(&mut self) -> Result<()> {
-        self.trans.deref_mut().rollback().await
+        self.end_span();
+        let result = self.trans.deref_mut().rollback().await;
+        self.invalidate_written();
+        result
+    }
+    async fn savepoint<'s>(&'s mut self) -> Result<Transaction<'s>> {
+        let savepoint = self.trans.savepoint().await?;
+        Ok(match &self.span {
+            Some(parent) => savepoint.traced(
+                op_span!("butane.transaction", parent: parent),
+                self.db.clone(),
+            ),
+            None => savepoint,
+        }
+        .with_cache(self.cache.clone().map(|cache| cache as Arc<dyn Cache>)))
+    }
+    async fn prepare(&mut self, gid: &str) -> Result<()> {
+        self.end_span();
+        self.trans.prepare(gid).await
     }
     fn connection_methods(&self) -> &dyn ConnectionMethods {
         self
@@ -406,7 +878,8 @@ fn connection_methods(&self) -> &dyn ConnectionMethods {
 #[maybe_async_cfg::maybe(
     idents(
         BackendTransaction(sync = "BackendTransaction"),
-        ConnectionMethods(sync = "ConnectionMethods")
+        ConnectionMethods(sync = "ConnectionMethods"),
+        Transaction(sync = "Transaction")
     ),
     keep_self,
     sync(),
@@ -420,6 +893,12 @@ async fn commit(&mut self) -> Result<()> {
     async fn rollback(&mut self) -> Result<()> {
         self.deref_mut().rollback().await
     }
+    async fn savepoint<'s>(&'s mut self) -> Result<Transaction<'s>> {
+        self.deref_mut().savepoint().await
+    }
+    async fn prepare(&mut self, gid: &str) -> Result<()> {
+        self.deref_mut().prepare(gid).await
+    }
     fn connection_methods(&self) -> &dyn ConnectionMethods {
         self
     }
@@ -452,6 +931,20 @@ async fn query<'c>(
             .query(table, columns, expr, limit, offset, sort)
             .await
     }
+    #[maybe_async_cfg::only_if(key = "async")]
+    async fn query_stream<'c>(
+        &'c self,
+        table: &str,
+        columns: &[Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+    ) -> Result<RowStream<'c>> {
+        self.deref()
+            .query_stream(table, columns, expr, limit, offset, sort)
+            .await
+    }
     async fn insert_returning_pk(
         &self,
         table: &str,
@@ -498,6 +991,12 @@ async fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
     async fn has_table(&self, table: &str) -> Result<bool> {
         self.deref().has_table(table).await
     }
+    async fn introspect(&self) -> Result<adb::ADB> {
+        self.deref().introspect().await
+    }
+    fn cancel_handle(&self) -> Option<CancelHandle> {
+        self.deref().cancel_handle()
+    }
 }
 
 /// Database backend. A boxed implementation can be returned by name via [get_backend][crate::db::get_backend].
@@ -514,6 +1013,28 @@ pub trait Backend: Send + Sync + DynClone {
     /// It may be `None` if the backend does not support this.
     fn row_id_column(&self) -> Option<&'static str>;
     fn create_migration_sql(&self, current: &adb::ADB, ops: Vec<adb::Operation>) -> Result<String>;
+    /// Like [`create_migration_sql`][Backend::create_migration_sql], but
+    /// avoiding operations which block writes to a table for as long as
+    /// they take, such as building an index, where the backend can. The
+    /// resulting SQL may not be run in a transaction. By default the
+    /// same as `create_migration_sql`.
+    fn create_online_migration_sql(
+        &self,
+        current: &adb::ADB,
+        ops: Vec<adb::Operation>,
+    ) -> Result<String> {
+        self.create_migration_sql(current, ops)
+    }
+    /// The column as [introspecting][ConnectionMethods::introspect] a
+    /// database created with this backend reports it. Backends may
+    /// store several types the same way, or be unable to read back
+    /// some properties of the column.
+    fn introspected_column(&self, col: &adb::AColumn) -> adb::AColumn;
+    /// SQL which takes, and SQL which releases, a lock held by a
+    /// connection across transactions to prevent other connections
+    /// from applying migrations at the same time. `None` if the backend
+    /// has no such lock.
+    fn migration_lock_sql(&self) -> Option<(&'static str, &'static str)>;
     /// Establish a new sync connection.
     ///
     /// The format of the connection string is backend-dependent.
@@ -529,6 +1050,11 @@ pub trait Backend: Send + Sync + DynClone {
 /// Connection specification. Contains the name of a database backend
 /// and the backend-specific connection string. See [`connect`]
 /// to make a [`Connection`] from a `ConnectionSpec`.
+///
+/// So that credentials need not be saved with the spec, the connection
+/// string may refer to environment variables, which are read when
+/// connecting: `${VAR}` is replaced by the value of `VAR`, and a
+/// connection string `env:VAR` is the value of `VAR`.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ConnectionSpec {
     pub backend_name: String,
@@ -549,10 +1075,17 @@ pub fn save(&self, path: &Path) -> Result<()> {
         contents.push('\n');
         f.write_all(contents.as_bytes()).map_err(|e| e.into())
     }
-    /// Load a previously saved connection spec
+    /// Load a previously saved connection spec. If [profiles][ConnectionProfiles]
+    /// were saved instead, loads the default profile.
     pub fn load(path: impl AsRef<Path>) -> Result<Self> {
-        let path = conn_complete_if_dir(path.as_ref());
-        serde_json::from_reader(fs::File::open(path)?).map_err(|e| e.into())
+        Self::load_profile(path, None)
+    }
+    /// Load the [profile][ConnectionProfiles] named `profile`, or the
+    /// default profile, from a file saved by
+    /// [`ConnectionProfiles::save`]. A file holding a single connection
+    /// spec has only the default profile.
+    pub fn load_profile(path: impl AsRef<Path>, profile: Option<&str>) -> Result<Self> {
+        ConnectionProfiles::load(path)?.get(profile).cloned()
     }
     pub fn get_backend(&self) -> Result<Box<dyn Backend>> {
         match get_backend(&self.backend_name) {
@@ -560,6 +1093,92 @@ pub fn get_backend(&self) -> Result<Box<dyn Backend>> {
             None => Err(crate::Error::UnknownBackend(self.backend_name.clone())),
         }
     }
+    /// The connection string with the environment variables it refers
+    /// to replaced by their values.
+    pub fn expanded_conn_str(&self) -> Result<String> {
+        let var =
+            |name: &str| std::env::var(name).map_err(|_| Error::MissingEnvVar(name.to_string()));
+        if let Some(name) = self.conn_str.strip_prefix("env:") {
+            return var(name);
+        }
+        let mut expanded = String::new();
+        let mut rest = self.conn_str.as_str();
+        while let Some(start) = rest.find("${") {
+            let Some(len) = rest[start + 2..].find('}') else {
+                break;
+            };
+            expanded.push_str(&rest[..start]);
+            expanded.push_str(&var(&rest[start + 2..start + 2 + len])?);
+            rest = &rest[start + 3 + len..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+/// Connection specs saved under names, such as `dev`, `test` and
+/// `prod`, each with its own backend. They are saved in place of a
+/// single [`ConnectionSpec`], as
+/// `{"default": "dev", "profiles": {"dev": {...}, "prod": {...}}}`.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ConnectionProfiles {
+    /// Name of the profile used when none is named.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    pub profiles: BTreeMap<String, ConnectionSpec>,
+}
+
+/// The contents of a file of connection profiles, which may instead
+/// hold a single spec.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConnectionFile {
+    Profiles(ConnectionProfiles),
+    Spec(ConnectionSpec),
+}
+
+impl ConnectionProfiles {
+    /// The name given to the profile of a single saved [`ConnectionSpec`].
+    pub const DEFAULT_NAME: &'static str = "default";
+
+    /// Load previously saved profiles, or a single saved [`ConnectionSpec`]
+    /// as the default profile.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = conn_complete_if_dir(path.as_ref());
+        match serde_json::from_reader(fs::File::open(path)?)? {
+            ConnectionFile::Profiles(profiles) => Ok(profiles),
+            ConnectionFile::Spec(spec) => {
+                let mut profiles = ConnectionProfiles::default();
+                profiles.insert(Self::DEFAULT_NAME, spec);
+                Ok(profiles)
+            }
+        }
+    }
+    /// Save the profiles to the filesystem for later use.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let path = conn_complete_if_dir(path);
+        let mut f = fs::File::create(path)?;
+        let mut contents = serde_json::to_string_pretty(self)?;
+        contents.push('\n');
+        f.write_all(contents.as_bytes()).map_err(|e| e.into())
+    }
+    /// Get the profile named `name`, or the default profile.
+    pub fn get(&self, name: Option<&str>) -> Result<&ConnectionSpec> {
+        let name = name
+            .or(self.default.as_deref())
+            .ok_or_else(|| Error::UnknownProfile("default".to_string()))?;
+        self.profiles
+            .get(name)
+            .ok_or_else(|| Error::UnknownProfile(name.to_string()))
+    }
+    /// Add or replace the profile named `name`. The first profile added
+    /// becomes the default.
+    pub fn insert(&mut self, name: &str, spec: ConnectionSpec) {
+        if self.default.is_none() {
+            self.default = Some(name.to_string());
+        }
+        self.profiles.insert(name.to_string(), spec);
+    }
 }
 
 impl TryFrom<&str> for ConnectionSpec {
@@ -619,6 +1238,19 @@ fn row_id_column(&self) -> Option<&'static str> {
     fn create_migration_sql(&self, current: &adb::ADB, ops: Vec<adb::Operation>) -> Result<String> {
         self.deref().create_migration_sql(current, ops)
     }
+    fn create_online_migration_sql(
+        &self,
+        current: &adb::ADB,
+        ops: Vec<adb::Operation>,
+    ) -> Result<String> {
+        self.deref().create_online_migration_sql(current, ops)
+    }
+    fn introspected_column(&self, col: &adb::AColumn) -> adb::AColumn {
+        self.deref().introspected_column(col)
+    }
+    fn migration_lock_sql(&self) -> Option<(&'static str, &'static str)> {
+        self.deref().migration_lock_sql()
+    }
     fn connect(&self, conn_str: &str) -> Result<Connection> {
         self.deref().connect(conn_str)
     }
@@ -643,9 +1275,12 @@ pub fn get_backend(name: &str) -> Option<Box<dyn Backend>> {
 ///
 /// For non-boxed connections, see individual [`Backend`] implementations.
 pub fn connect(spec: &ConnectionSpec) -> Result<Connection> {
-    get_backend(&spec.backend_name)
-        .ok_or_else(|| Error::UnknownBackend(spec.backend_name.clone()))?
-        .connect(&spec.conn_str)
+    let span = op_span!("butane.connect", backend = spec.backend_name.as_str());
+    trace::in_span(span, || {
+        get_backend(&spec.backend_name)
+            .ok_or_else(|| Error::UnknownBackend(spec.backend_name.clone()))?
+            .connect(&spec.expanded_conn_str()?)
+    })
 }
 
 /// Connect to a database async.
@@ -653,8 +1288,12 @@ pub fn connect(spec: &ConnectionSpec) -> Result<Connection> {
 /// For non-boxed connections, see individual [`Backend`] implementations.
 #[cfg(feature = "async")]
 pub async fn connect_async(spec: &ConnectionSpec) -> Result<ConnectionAsync> {
-    get_backend(&spec.backend_name)
-        .ok_or_else(|| Error::UnknownBackend(spec.backend_name.clone()))?
-        .connect_async(&spec.conn_str)
-        .await
+    let span = op_span!("butane.connect", backend = spec.backend_name.as_str());
+    trace::in_span_async(span, || async {
+        get_backend(&spec.backend_name)
+            .ok_or_else(|| Error::UnknownBackend(spec.backend_name.clone()))?
+            .connect_async(&spec.expanded_conn_str()?)
+            .await
+    })
+    .await
 }
@@ -1,27 +1,37 @@
 //! Postgresql database backend
 use std::borrow::Cow;
+#[cfg(feature = "json")]
+use std::collections::HashMap;
 use std::fmt::{Debug, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
 use async_trait::async_trait;
 use bytes::BufMut;
 #[cfg(feature = "datetime")]
-use chrono::NaiveDateTime;
-use futures_util::stream::StreamExt;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use futures_util::stream::{StreamExt, TryStreamExt};
+use futures_util::FutureExt;
+use hashlink::LruCache;
 use tokio_postgres as postgres;
-use tokio_postgres::GenericClient;
+use tokio_postgres::{GenericClient, Statement};
 
 use super::connmethods::VecRows;
 use super::helper;
 use crate::custom::{SqlTypeCustom, SqlValRefCustom};
 use crate::db::{
     Backend, BackendConnectionAsync as BackendConnection, BackendRow,
-    BackendTransactionAsync as BackendTransaction, Column, Connection, ConnectionAsync,
-    ConnectionMethodsAsync as ConnectionMethods, RawQueryResult, SyncAdapter,
-    TransactionAsync as Transaction,
+    BackendTransactionAsync as BackendTransaction, CancelHandle, Column, Connection,
+    ConnectionAsync, ConnectionMethodsAsync as ConnectionMethods, IsolationLevel, QueryLimits,
+    QueryLog, QueryLogger, RawQueryResult, RowStream, SharedLimits, SlowQueryLog, SyncAdapter,
+    TransactionAsync as Transaction, TransactionOptions,
+};
+use crate::migrations::adb::{
+    AColumn, AConstraint, AConstraintKind, ACustomType, ACustomTypeKind, ARef, ARefLiteral, ATable,
+    DeferredSqlType, Operation, TypeIdentifier, ADB,
 };
-use crate::migrations::adb::{AColumn, ARef, ATable, Operation, TypeIdentifier, ADB};
 use crate::query::{BoolExpr, Expr};
-use crate::{debug, query, warn, Error, Result, SqlType, SqlVal, SqlValRef};
+use crate::{debug, query, trace, warn, Error, Result, SqlType, SqlVal, SqlValRef};
 
 /// The name of the postgres backend.
 pub const BACKEND_NAME: &str = "pg";
@@ -51,12 +61,59 @@ fn create_migration_sql(&self, current: &ADB, ops: Vec<Operation>) -> Result<Str
         let mut current: ADB = (*current).clone();
         let mut lines = ops
             .iter()
-            .map(|o| sql_for_op(&mut current, o))
+            .map(|o| sql_for_op(&mut current, o, false))
+            .collect::<Result<Vec<String>>>()?;
+        lines.retain(|s| !s.is_empty());
+        lines.splice(0..0, migration_prelude(&ops));
+        Ok(lines.join("\n"))
+    }
+
+    fn create_online_migration_sql(&self, current: &ADB, ops: Vec<Operation>) -> Result<String> {
+        let mut current: ADB = (*current).clone();
+        let mut lines = ops
+            .iter()
+            .map(|o| sql_for_op(&mut current, o, true))
             .collect::<Result<Vec<String>>>()?;
         lines.retain(|s| !s.is_empty());
+        lines.splice(0..0, migration_prelude(&ops));
         Ok(lines.join("\n"))
     }
 
+    fn introspected_column(&self, col: &AColumn) -> AColumn {
+        let sqltype = match col.typeid() {
+            Ok(TypeIdentifier::Ty(SqlType::Custom(SqlTypeCustom::Pg(ty)))) => {
+                DeferredSqlType::KnownId(TypeIdentifier::Name(ty.name().to_string()))
+            }
+            Ok(TypeIdentifier::Ty(SqlType::Custom(SqlTypeCustom::Named(name)))) => {
+                DeferredSqlType::KnownId(TypeIdentifier::Name(name.to_string()))
+            }
+            Ok(typeid) => DeferredSqlType::KnownId(typeid),
+            Err(_) => DeferredSqlType::KnownId(TypeIdentifier::Name(String::new())),
+        };
+        // Defaults are not introspected.
+        let mut introspected = AColumn::new(
+            col.name(),
+            sqltype,
+            col.nullable(),
+            col.is_pk(),
+            col.is_auto(),
+            col.unique(),
+            None,
+            col.reference().clone(),
+        );
+        introspected.set_deferrable(col.is_deferrable());
+        introspected
+    }
+
+    fn migration_lock_sql(&self) -> Option<(&'static str, &'static str)> {
+        // A session-level advisory lock, held until explicitly released.
+        // The key is "butane_m" read as a big-endian integer.
+        Some((
+            "SELECT pg_advisory_lock(7094704749804871533);",
+            "SELECT pg_advisory_unlock(7094704749804871533);",
+        ))
+    }
+
     fn connect(&self, path: &str) -> Result<Connection> {
         debug!("Postgres connecting via sync adapter");
         let conn = SyncAdapter::new(self.clone())?.connect(path)?;
@@ -64,9 +121,54 @@ fn connect(&self, path: &str) -> Result<Connection> {
     }
 
     async fn connect_async(&self, path: &str) -> Result<ConnectionAsync> {
-        Ok(ConnectionAsync {
-            conn: Box::new(PgConnection::open(path).await?),
-        })
+        let conn = ConnectionAsync::new(Box::new(PgConnection::open(path).await?));
+        Ok(conn.with_db_attributes(db_attributes(path)))
+    }
+}
+
+/// Statements prepared on a connection, keyed by their SQL and
+/// parameter types, and shared with its transactions.
+struct StatementCache {
+    statements: std::sync::Mutex<LruCache<(String, Vec<postgres::types::Type>), Statement>>,
+}
+impl StatementCache {
+    fn new() -> Self {
+        StatementCache {
+            statements: std::sync::Mutex::new(LruCache::new(super::STATEMENT_CACHE_CAPACITY)),
+        }
+    }
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, LruCache<(String, Vec<postgres::types::Type>), Statement>> {
+        // A panic while the lock is held leaves the cache consistent.
+        self.statements
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+    /// Get the statement prepared for `sql` on `client`, preparing it
+    /// if it is not cached.
+    async fn prepare<C>(
+        &self,
+        client: &C,
+        sql: &str,
+        types: &[postgres::types::Type],
+    ) -> Result<Statement>
+    where
+        C: GenericClient + Sync,
+    {
+        let key = (sql.to_string(), types.to_vec());
+        if let Some(stmt) = self.lock().get(&key) {
+            return Ok(stmt.clone());
+        }
+        let stmt = client.prepare_typed(sql, types).await?;
+        self.lock().insert(key, stmt.clone());
+        Ok(stmt)
+    }
+    fn set_capacity(&self, capacity: usize) {
+        self.lock().set_capacity(capacity)
+    }
+    fn clear(&self) {
+        self.lock().clear()
     }
 }
 
@@ -75,6 +177,9 @@ pub struct PgConnection {
     #[cfg(feature = "debug")]
     params: Box<str>,
     client: postgres::Client,
+    statements: StatementCache,
+    log: QueryLog,
+    limits: SharedLimits,
 }
 
 impl PgConnection {
@@ -84,18 +189,13 @@ async fn open(params: &str) -> Result<Self> {
             #[cfg(feature = "debug")]
             params: params.into(),
             client,
+            statements: StatementCache::new(),
+            log: QueryLog::default(),
+            limits: SharedLimits::default(),
         })
     }
     async fn connect(params: &str) -> Result<postgres::Client> {
-        cfg_if::cfg_if! {
-            if #[cfg(feature = "tls")] {
-                let connector = native_tls::TlsConnector::new()?;
-                let connector = postgres_native_tls::MakeTlsConnector::new(connector);
-            } else {
-                let connector = postgres::NoTls;
-            }
-        }
-        let (client, conn) = postgres::connect(params, connector).await?;
+        let (client, conn) = postgres::connect(params, tls_connector()?).await?;
         tokio::spawn(async move {
             #[allow(unused_variables)] // used only when logging is enabled
             if let Err(e) = conn.await {
@@ -105,20 +205,94 @@ async fn connect(params: &str) -> Result<postgres::Client> {
         Ok(client)
     }
 }
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "tls")] {
+        fn tls_connector() -> Result<postgres_native_tls::MakeTlsConnector> {
+            let connector = native_tls::TlsConnector::new()?;
+            Ok(postgres_native_tls::MakeTlsConnector::new(connector))
+        }
+    } else {
+        #[allow(clippy::unnecessary_wraps)]
+        fn tls_connector() -> Result<postgres::NoTls> {
+            Ok(postgres::NoTls)
+        }
+    }
+}
+
+/// Ask the server to cancel the statement of the connection `token`
+/// was taken from, connecting to it in the same way.
+pub(super) async fn cancel_query(token: &postgres::CancelToken) -> Result<()> {
+    Ok(token.cancel_query(tls_connector()?).await?)
+}
+
+/// Describe the database of a connection made with `params`. When no
+/// database is given, Postgres uses the one named after the user.
+fn db_attributes(params: &str) -> trace::DbAttributes {
+    let Ok(config) = params.parse::<postgres::Config>() else {
+        return trace::DbAttributes::new("postgresql", None, None);
+    };
+    let name = config.get_dbname().or(config.get_user());
+    let peer = config.get_hosts().iter().find_map(|host| match host {
+        postgres::config::Host::Tcp(host) => Some(host.as_str()),
+        #[allow(unreachable_patterns)] // Unix sockets exist only on Unix
+        _ => None,
+    });
+    trace::DbAttributes::new("postgresql", name, peer)
+}
+
 impl PgConnectionLike for PgConnection {
     type Client = postgres::Client;
+    const IN_TRANSACTION: bool = false;
     fn client(&self) -> Result<&Self::Client> {
         Ok(&self.client)
     }
+    fn statements(&self) -> &StatementCache {
+        &self.statements
+    }
+    fn schema_changed(&self) {}
+    fn query_log(&self) -> &QueryLog {
+        &self.log
+    }
+    fn limits(&self) -> QueryLimits {
+        self.limits.get()
+    }
+    fn cancel_token(&self) -> Result<postgres::CancelToken> {
+        Ok(self.client.cancel_token())
+    }
 }
 
 #[async_trait]
 impl BackendConnection for PgConnection {
-    async fn transaction(&mut self) -> Result<Transaction<'_>> {
-        let trans: postgres::Transaction<'_> = self.client.transaction().await?;
-        let trans = Box::new(PgTransaction::new(trans));
+    async fn transaction_with(&mut self, options: TransactionOptions) -> Result<Transaction<'_>> {
+        let mut builder = self.client.build_transaction().read_only(options.read_only);
+        if let Some(isolation) = options.isolation {
+            builder = builder.isolation_level(match isolation {
+                IsolationLevel::ReadUncommitted => postgres::IsolationLevel::ReadUncommitted,
+                IsolationLevel::ReadCommitted => postgres::IsolationLevel::ReadCommitted,
+                IsolationLevel::RepeatableRead => postgres::IsolationLevel::RepeatableRead,
+                IsolationLevel::Serializable => postgres::IsolationLevel::Serializable,
+            });
+        }
+        let trans: postgres::Transaction<'_> = builder.start().await?;
+        let trans = PgTransaction::new(trans, &self.statements, &self.log, &self.limits);
+        let trans = Box::new(trans);
         Ok(Transaction::new(trans))
     }
+    async fn commit_prepared(&mut self, gid: &str) -> Result<()> {
+        let gid = helper::sql_literal_value(&SqlVal::Text(gid.to_string()))?;
+        Ok(self
+            .client
+            .batch_execute(&format!("COMMIT PREPARED {gid}"))
+            .await?)
+    }
+    async fn rollback_prepared(&mut self, gid: &str) -> Result<()> {
+        let gid = helper::sql_literal_value(&SqlVal::Text(gid.to_string()))?;
+        Ok(self
+            .client
+            .batch_execute(&format!("ROLLBACK PREPARED {gid}"))
+            .await?)
+    }
     fn backend(&self) -> Box<dyn Backend> {
         Box::new(PgBackend {})
     }
@@ -128,6 +302,25 @@ fn backend_name(&self) -> &'static str {
     fn is_closed(&self) -> bool {
         self.client.is_closed()
     }
+    async fn ping(&self) -> Result<()> {
+        self.client.simple_query("SELECT 1").await?;
+        Ok(())
+    }
+    fn is_broken(&self) -> bool {
+        self.client.is_closed()
+    }
+    fn set_statement_cache_capacity(&self, capacity: usize) {
+        self.statements.set_capacity(capacity)
+    }
+    fn set_query_logger(&self, logger: Option<QueryLogger>) {
+        self.log.set(logger)
+    }
+    fn set_slow_query_log(&self, slow: Option<SlowQueryLog>) {
+        self.log.set_slow(slow)
+    }
+    fn set_query_limits(&self, limits: QueryLimits) {
+        self.limits.set(limits)
+    }
 }
 impl Debug for PgConnection {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -153,8 +346,80 @@ fn sqlvalref_for_pg_query<'a>(v: &'a SqlValRef<'a>) -> &'a dyn postgres::types::
 /// Shared functionality between connection and
 /// transaction. Implementation detail. Semver exempt.
 trait PgConnectionLike {
-    type Client: postgres::GenericClient + Send;
+    type Client: postgres::GenericClient + Send + Sync;
+    /// Whether statements run in a transaction.
+    const IN_TRANSACTION: bool;
     fn client(&self) -> Result<&Self::Client>;
+    fn statements(&self) -> &StatementCache;
+    fn query_log(&self) -> &QueryLog;
+    fn limits(&self) -> QueryLimits;
+    fn cancel_token(&self) -> Result<postgres::CancelToken>;
+    /// Note that SQL run by [`execute`][ConnectionMethods::execute] may
+    /// have changed the schema.
+    fn schema_changed(&self);
+}
+
+/// Run a statement through the statement cache, reporting it to the
+/// query log. Returns the number of rows affected.
+async fn execute_cached<T>(conn: &T, sql: &str, values: &[SqlValRef<'_>]) -> Result<u64>
+where
+    T: PgConnectionLike + Sync,
+{
+    let start = Instant::now();
+    let result: Result<u64> = async {
+        let params: Vec<&DynToSqlPg> = values.iter().map(|v| v as &DynToSqlPg).collect();
+        let future = conn.statements().prepare(conn.client()?, sql, &[]);
+        let stmt = future.await?;
+        let future = conn.client()?.execute(&stmt, params.as_slice());
+        Ok(future.await?)
+    }
+    .await;
+    let duration = start.elapsed();
+    let plan = match result {
+        Ok(_) if conn.query_log().wants_explain(duration) => {
+            explain(conn, sql, &[], values.iter().map(sqlvalref_for_pg_query)).await
+        }
+        _ => None,
+    };
+    conn.query_log().log_explained(
+        sql,
+        values.iter().cloned(),
+        duration,
+        result.as_ref().map(|n| Some(*n)),
+        plan.as_deref(),
+    );
+    result
+}
+
+/// Get the plan of a statement for the slow query log, or `None` if
+/// it cannot be explained.
+async fn explain<'a, T, I>(
+    conn: &T,
+    sql: &str,
+    types: &[postgres::types::Type],
+    params: I,
+) -> Option<String>
+where
+    T: PgConnectionLike + Sync,
+    I: IntoIterator<Item = &'a dyn postgres::types::ToSql> + Send + Sync,
+    I::IntoIter: ExactSizeIterator,
+{
+    let sql = format!("EXPLAIN {sql}");
+    let result: Result<Vec<postgres::Row>> = async {
+        let future = conn.client()?.prepare_typed(&sql, types);
+        let stmt = future.await?;
+        let future = conn.client()?.query_raw(&stmt, params);
+        let rows = future.await?;
+        Ok(rows.try_collect().await?)
+    }
+    .await;
+    let lines: Vec<String> = result
+        .ok()?
+        .iter()
+        .map(|row| row.try_get(0))
+        .collect::<std::result::Result<_, _>>()
+        .ok()?;
+    Some(lines.join("\n"))
 }
 
 #[async_trait]
@@ -166,9 +431,16 @@ async fn execute(&self, sql: &str) -> Result<()> {
         if cfg!(feature = "log") {
             debug!("execute sql {sql}");
         }
+        let start = Instant::now();
         // Note, let binding exists only so that the self.client() reference is not held across the await
         let future = self.client()?.batch_execute(sql.as_ref());
-        future.await?;
+        let result = future.await.map_err(Error::from);
+        self.query_log()
+            .log(sql, [], start, result.as_ref().map(|_| None));
+        result?;
+        // The SQL may have changed the schema cached statements were planned for.
+        self.statements().clear();
+        self.schema_changed();
         Ok(())
     }
 
@@ -181,50 +453,142 @@ async fn query<'c>(
         offset: Option<i32>,
         order: Option<&[query::Order]>,
     ) -> Result<RawQueryResult<'c>> {
-        let mut sqlquery = String::new();
-        helper::sql_select(columns, table, &mut sqlquery);
-        let mut values: Vec<SqlVal> = Vec::new();
-        if let Some(expr) = expr {
-            sqlquery.write_str(" WHERE ").unwrap();
-            sql_for_expr(
-                query::Expr::Condition(Box::new(expr)),
-                &mut values,
-                &mut PgPlaceholderSource::new(),
-                &mut sqlquery,
-            );
-        }
-
-        if let Some(order) = order {
-            helper::sql_order(order, &mut sqlquery)
-        }
-
-        if let Some(limit) = limit {
-            helper::sql_limit(limit, &mut sqlquery)
-        }
-
-        if let Some(offset) = offset {
-            helper::sql_offset(offset, &mut sqlquery)
-        }
-
+        let (sqlquery, values) = select_sql(table, columns, expr, limit, offset, order);
         if cfg!(feature = "log") {
             debug!("query sql {sqlquery}");
         }
 
+        let start = Instant::now();
         let types: Vec<postgres::types::Type> = values.iter().map(pgtype_for_val).collect();
-        let future = self.client()?.prepare_typed(&sqlquery, types.as_ref());
-        let stmt = future.await?;
-        let mut rowvec = Vec::<postgres::Row>::new();
-        let future = self
-            .client()?
-            .query_raw(&stmt, values.iter().map(sqlval_for_pg_query));
-        let rowstream = future.await.map_err(Error::Postgres)?;
-        let mut rowstream = Box::pin(rowstream);
-        while let Some(r) = rowstream.next().await {
-            let r = r?;
-            check_columns(&r, columns)?;
-            rowvec.push(r);
-        }
-        Ok(Box::new(VecRows::new(rowvec)))
+        let result: Result<Vec<postgres::Row>> = async {
+            let future = self
+                .statements()
+                .prepare(self.client()?, &sqlquery, types.as_ref());
+            let stmt = future.await?;
+            let limits = self.limits();
+            let mut rowvec = Vec::<postgres::Row>::new();
+            let future = self
+                .client()?
+                .query_raw(&stmt, values.iter().map(sqlval_for_pg_query));
+            let rowstream = future.await.map_err(Error::Postgres)?;
+            let mut rowstream = Box::pin(rowstream);
+            while let Some(r) = rowstream.next().await {
+                let r = r?;
+                check_columns(&r, columns)?;
+                rowvec.push(r);
+                limits.check(rowvec.len())?;
+            }
+            Ok(rowvec)
+        }
+        .await;
+        let duration = start.elapsed();
+        let plan = match result {
+            Ok(_) if self.query_log().wants_explain(duration) => {
+                let params = values.iter().map(sqlval_for_pg_query);
+                explain(self, &sqlquery, &types, params).await
+            }
+            _ => None,
+        };
+        self.query_log().log_explained(
+            &sqlquery,
+            values.iter().map(SqlValRef::from),
+            duration,
+            result.as_ref().map(|rows| Some(rows.len() as u64)),
+            plan.as_deref(),
+        );
+        Ok(Box::new(VecRows::new(result?)))
+    }
+    /// Reads the rows through a cursor, fetching
+    /// [`fetch_size`][QueryLimits::fetch_size] rows at a time. Outside a transaction the cursor is declared
+    /// `WITH HOLD`, for which Postgres computes all the rows when it is
+    /// declared, keeping them on the server. Dropping the stream before
+    /// its end closes the cursor.
+    async fn query_stream<'c>(
+        &'c self,
+        table: &str,
+        columns: &[Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[query::Order]>,
+    ) -> Result<RowStream<'c>> {
+        let (sqlquery, values) = select_sql(table, columns, expr, limit, offset, order);
+        let cursor = format!(
+            "butane_cursor_{}",
+            CURSOR_COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        );
+        let hold = if T::IN_TRANSACTION { "" } else { " WITH HOLD" };
+        let declare = format!("DECLARE {cursor} NO SCROLL CURSOR{hold} FOR {sqlquery}");
+        if cfg!(feature = "log") {
+            debug!("query sql {declare}");
+        }
+        let start = Instant::now();
+        let result: Result<u64> = async {
+            let types: Vec<postgres::types::Type> = values.iter().map(pgtype_for_val).collect();
+            let future = self.client()?.prepare_typed(&declare, types.as_ref());
+            let stmt = future.await?;
+            let future = self
+                .client()?
+                .execute_raw(&stmt, values.iter().map(sqlval_for_pg_query));
+            Ok(future.await?)
+        }
+        .await;
+        self.query_log().log(
+            &declare,
+            values.iter().map(SqlValRef::from),
+            start,
+            result.as_ref().map(|_| None),
+        );
+        result?;
+        // A fetch size of zero would fetch nothing.
+        let fetch_size = self.limits().fetch_size.max(1);
+        let state = CursorState {
+            client: self.client()?,
+            fetch: format!("FETCH {fetch_size} FROM {cursor};"),
+            close: format!("CLOSE {cursor};"),
+            columns: columns.to_vec(),
+            rows: std::collections::VecDeque::new(),
+            done: false,
+        };
+        Ok(Box::pin(futures_util::stream::try_unfold(
+            state,
+            move |mut state| async move {
+                if state.rows.is_empty() && !state.done {
+                    let start = Instant::now();
+                    let future = state.client.query(state.fetch.as_str(), &[]);
+                    let rows = future.await.map_err(Error::from);
+                    self.query_log().log(
+                        &state.fetch,
+                        [],
+                        start,
+                        rows.as_ref().map(|rows| Some(rows.len() as u64)),
+                    );
+                    let rows = rows?;
+                    state.done = rows.len() < fetch_size;
+                    if state.done {
+                        let start = Instant::now();
+                        let future = state.client.batch_execute(state.close.as_str());
+                        let result = future.await.map_err(Error::from);
+                        self.query_log().log(
+                            &state.close,
+                            [],
+                            start,
+                            result.as_ref().map(|_| None),
+                        );
+                        result?;
+                    }
+                    state.rows.extend(rows);
+                }
+                match state.rows.pop_front() {
+                    Some(row) => {
+                        check_columns(&row, &state.columns)?;
+                        let row: Box<dyn BackendRow + Send + 'c> = Box::new(row);
+                        Ok(Some((row, state)))
+                    }
+                    None => Ok(None),
+                }
+            },
+        )))
     }
     async fn insert_returning_pk(
         &self,
@@ -245,18 +609,37 @@ async fn insert_returning_pk(
             debug!("insert sql {sql}");
         }
 
-        // use query instead of execute so we can get our result back
-        let future = self
-            .client()?
-            .query_raw(sql.as_str(), values.iter().map(sqlvalref_for_pg_query));
-        let pk_stream = future
-            .await
-            .map_err(Error::Postgres)?
-            .map(|r| r.map(|x| sql_val_from_postgres(&x, 0, pkcol)));
-        Box::pin(pk_stream)
-            .next()
-            .await
-            .ok_or(Error::Internal(("could not get pk").to_string()))??
+        let start = Instant::now();
+        let result: Result<SqlVal> = async {
+            // use query instead of execute so we can get our result back
+            let future = self
+                .client()?
+                .query_raw(sql.as_str(), values.iter().map(sqlvalref_for_pg_query));
+            let pk_stream = future
+                .await
+                .map_err(Error::Postgres)?
+                .map(|r| r.map(|x| sql_val_from_postgres(&x, 0, pkcol)));
+            Box::pin(pk_stream)
+                .next()
+                .await
+                .ok_or(Error::Internal(("could not get pk").to_string()))??
+        }
+        .await;
+        let duration = start.elapsed();
+        let plan = match result {
+            Ok(_) if self.query_log().wants_explain(duration) => {
+                explain(self, &sql, &[], values.iter().map(sqlvalref_for_pg_query)).await
+            }
+            _ => None,
+        };
+        self.query_log().log_explained(
+            &sql,
+            values.iter().cloned(),
+            duration,
+            result.as_ref().map(|_| Some(1)),
+            plan.as_deref(),
+        );
+        result
     }
     async fn insert_only(
         &self,
@@ -271,9 +654,7 @@ async fn insert_only(
             &mut PgPlaceholderSource::new(),
             &mut sql,
         );
-        let params: Vec<&DynToSqlPg> = values.iter().map(|v| v as &DynToSqlPg).collect();
-        let future = self.client()?.execute(sql.as_str(), params.as_slice());
-        future.await?;
+        execute_cached(self, &sql, values).await?;
         Ok(())
     }
     async fn insert_or_replace(
@@ -285,9 +666,7 @@ async fn insert_or_replace(
     ) -> Result<()> {
         let mut sql = String::new();
         sql_insert_or_replace_with_placeholders(table, columns, pkcol, &mut sql);
-        let params: Vec<&DynToSqlPg> = values.iter().map(|v| v as &DynToSqlPg).collect();
-        let future = self.client()?.execute(sql.as_str(), params.as_slice());
-        future.await?;
+        execute_cached(self, &sql, values).await?;
         Ok(())
     }
     async fn update(
@@ -307,15 +686,10 @@ async fn update(
             &mut sql,
         );
         let placeholder_values = [values, &[pk]].concat();
-        let params: Vec<&DynToSqlPg> = placeholder_values
-            .iter()
-            .map(|v| v as &DynToSqlPg)
-            .collect();
         if cfg!(feature = "log") {
             debug!("update sql {sql}");
         }
-        let future = self.client()?.execute(sql.as_str(), params.as_slice());
-        future.await?;
+        execute_cached(self, &sql, &placeholder_values).await?;
         Ok(())
     }
     async fn delete(&self, table: &str, pkcol: &'static str, pk: SqlVal) -> Result<()> {
@@ -338,30 +712,137 @@ async fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
             &mut PgPlaceholderSource::new(),
             &mut sql,
         );
-        let params: Vec<&DynToSqlPg> = values.iter().map(|v| v as &DynToSqlPg).collect();
-        let future = self.client()?.execute(sql.as_str(), params.as_slice());
-        let cnt = future.await?;
+        let values: Vec<SqlValRef> = values.iter().map(SqlValRef::from).collect();
+        let cnt = execute_cached(self, &sql, &values).await?;
         Ok(cnt as usize)
     }
+    async fn introspect(&self) -> Result<ADB> {
+        // Only single column constraints are described by an ATable.
+        let future = self.client()?.query(
+            "SELECT tc.table_name::text, kcu.column_name::text, tc.constraint_type::text, \
+             ccu.table_name::text, ccu.column_name::text, tc.is_deferrable = 'YES' \
+             FROM information_schema.table_constraints tc \
+             JOIN information_schema.key_column_usage kcu \
+             ON kcu.constraint_schema = tc.constraint_schema AND kcu.constraint_name = tc.constraint_name \
+             LEFT JOIN information_schema.constraint_column_usage ccu \
+             ON tc.constraint_type = 'FOREIGN KEY' \
+             AND ccu.constraint_schema = tc.constraint_schema AND ccu.constraint_name = tc.constraint_name \
+             WHERE tc.table_schema = current_schema() \
+             AND tc.constraint_type IN ('PRIMARY KEY', 'UNIQUE', 'FOREIGN KEY') \
+             AND (SELECT count(*) FROM information_schema.key_column_usage other \
+             WHERE other.constraint_schema = tc.constraint_schema \
+             AND other.constraint_name = tc.constraint_name) = 1;",
+            &[],
+        );
+        let constraints = future.await?;
+
+        let future = self.client()?.query(
+            "SELECT c.table_name::text, c.column_name::text, \
+             (CASE WHEN c.data_type IN ('USER-DEFINED', 'ARRAY') THEN c.udt_name ELSE c.data_type END)::text, \
+             c.is_nullable = 'YES', coalesce(c.column_default LIKE 'nextval(%', false) \
+             FROM information_schema.columns c \
+             JOIN information_schema.tables t \
+             ON t.table_schema = c.table_schema AND t.table_name = c.table_name \
+             WHERE c.table_schema = current_schema() AND t.table_type = 'BASE TABLE' \
+             ORDER BY c.table_name, c.ordinal_position;",
+            &[],
+        );
+        let columns = future.await?;
+
+        let mut tables: std::collections::BTreeMap<String, ATable> =
+            std::collections::BTreeMap::new();
+        for row in columns {
+            let table_name: String = row.try_get(0)?;
+            let column_name: String = row.try_get(1)?;
+            let constraint = |kind: &str| {
+                constraints.iter().find(|c| {
+                    c.get::<_, &str>(0) == table_name
+                        && c.get::<_, &str>(1) == column_name
+                        && c.get::<_, &str>(2) == kind
+                })
+            };
+            let foreign_key = constraint("FOREIGN KEY");
+            let reference = foreign_key.map(|c| {
+                ARef::Literal(ARefLiteral::new(
+                    c.get::<_, String>(3),
+                    c.get::<_, String>(4),
+                ))
+            });
+            let mut column = AColumn::new(
+                column_name.clone(),
+                DeferredSqlType::KnownId(typeid_for_pg_type_name(row.try_get(2)?)),
+                row.try_get(3)?,
+                constraint("PRIMARY KEY").is_some(),
+                row.try_get(4)?,
+                constraint("UNIQUE").is_some(),
+                None,
+                reference,
+            );
+            column.set_deferrable(foreign_key.is_some_and(|c| c.get::<_, bool>(5)));
+            tables
+                .entry(table_name.clone())
+                .or_insert_with(|| ATable::new(table_name))
+                .add_column(column);
+        }
+        let mut db = ADB::new();
+        for table in tables.into_values() {
+            db.replace_table(table);
+        }
+        Ok(db)
+    }
     async fn has_table(&self, table: &str) -> Result<bool> {
         // future improvement, should be schema-aware
-        let future = self
-            .client()?
-            .prepare("SELECT table_name FROM information_schema.tables WHERE table_name=$1;");
-        let stmt = future.await?;
-        let tableref: &[&(dyn postgres::types::ToSql + Sync)] = &[&table];
-        let future = self.client()?.query(&stmt, tableref);
-        let rows = future.await?;
-        Ok(!rows.is_empty())
+        const SQL: &str = "SELECT table_name FROM information_schema.tables WHERE table_name=$1;";
+        let start = Instant::now();
+        let result: Result<Vec<postgres::Row>> = async {
+            let future = self.statements().prepare(self.client()?, SQL, &[]);
+            let stmt = future.await?;
+            let tableref: &[&(dyn postgres::types::ToSql + Sync)] = &[&table];
+            let future = self.client()?.query(&stmt, tableref);
+            Ok(future.await?)
+        }
+        .await;
+        self.query_log().log(
+            SQL,
+            [SqlValRef::Text(table)],
+            start,
+            result.as_ref().map(|rows| Some(rows.len() as u64)),
+        );
+        Ok(!result?.is_empty())
+    }
+    fn cancel_handle(&self) -> Option<CancelHandle> {
+        self.cancel_token().ok().map(CancelHandle::pg)
     }
 }
 
 struct PgTransaction<'c> {
     trans: Option<postgres::Transaction<'c>>,
+    statements: &'c StatementCache,
+    log: &'c QueryLog,
+    limits: &'c SharedLimits,
+    /// Whether SQL run in this transaction may have changed the schema,
+    /// in which case statements cached since were planned for a schema
+    /// a rollback undoes.
+    schema_changed: AtomicBool,
+    /// The `schema_changed` flag of the transaction this is a savepoint
+    /// within, if it is one.
+    parent_schema_changed: Option<&'c AtomicBool>,
 }
 impl<'c> PgTransaction<'c> {
-    fn new(trans: postgres::Transaction<'c>) -> Self {
-        PgTransaction { trans: Some(trans) }
+    fn new(
+        trans: postgres::Transaction<'c>,
+        statements: &'c StatementCache,
+        log: &'c QueryLog,
+        limits: &'c SharedLimits,
+    ) -> Self {
+        PgTransaction {
+            trans: Some(trans),
+            statements,
+            log,
+            limits,
+            schema_changed: AtomicBool::new(false),
+            parent_schema_changed: None,
+        }
     }
     fn get(&self) -> Result<&postgres::Transaction<'c>> {
         match &self.trans {
@@ -372,6 +853,21 @@ fn get(&self) -> Result<&postgres::Transaction<'c>> {
     fn already_consumed() -> Error {
         Error::Internal("transaction has already been consumed".to_string())
     }
+    /// Clear the statement cache if the schema may have changed, as the
+    /// transaction is rolled back.
+    fn rolled_back(&self) {
+        if self.schema_changed.load(Ordering::Relaxed) {
+            self.statements.clear();
+        }
+    }
+}
+impl Drop for PgTransaction<'_> {
+    fn drop(&mut self) {
+        // A transaction dropped while still open is rolled back.
+        if self.trans.is_some() {
+            self.rolled_back();
+        }
+    }
 }
 impl Debug for PgTransaction<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -384,9 +880,25 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 
 impl<'c> PgConnectionLike for PgTransaction<'c> {
     type Client = postgres::Transaction<'c>;
+    const IN_TRANSACTION: bool = true;
     fn client(&self) -> Result<&Self::Client> {
         self.get()
     }
+    fn statements(&self) -> &StatementCache {
+        self.statements
+    }
+    fn schema_changed(&self) {
+        self.schema_changed.store(true, Ordering::Relaxed);
+    }
+    fn query_log(&self) -> &QueryLog {
+        self.log
+    }
+    fn limits(&self) -> QueryLimits {
+        self.limits.get()
+    }
+    fn cancel_token(&self) -> Result<postgres::CancelToken> {
+        Ok(self.get()?.cancel_token())
+    }
 }
 
 #[async_trait]
@@ -394,14 +906,65 @@ impl<'c> BackendTransaction<'c> for PgTransaction<'c> {
     async fn commit(&mut self) -> Result<()> {
         match self.trans.take() {
             None => Err(Self::already_consumed()),
-            Some(trans) => Ok(trans.commit().await?),
+            Some(trans) => {
+                let result = trans.commit().await;
+                if result.is_err() {
+                    self.rolled_back();
+                } else if let Some(parent) = self.parent_schema_changed {
+                    // Rolling back the parent undoes the changes too.
+                    if self.schema_changed.load(Ordering::Relaxed) {
+                        parent.store(true, Ordering::Relaxed);
+                    }
+                }
+                Ok(result?)
+            }
         }
     }
 
     async fn rollback(&mut self) -> Result<()> {
         match self.trans.take() {
             None => Err(Self::already_consumed()),
-            Some(trans) => Ok(trans.rollback().await?),
+            Some(trans) => {
+                let result = trans.rollback().await;
+                self.rolled_back();
+                Ok(result?)
+            }
+        }
+    }
+    async fn savepoint<'s>(&'s mut self) -> Result<Transaction<'s>> {
+        let trans = match self.trans.as_mut() {
+            None => return Err(Self::already_consumed()),
+            Some(trans) => trans.transaction().await?,
+        };
+        Ok(Transaction::new(Box::new(PgTransaction {
+            trans: Some(trans),
+            statements: self.statements,
+            log: self.log,
+            limits: self.limits,
+            schema_changed: AtomicBool::new(false),
+            parent_schema_changed: Some(&self.schema_changed),
+        })))
+    }
+    async fn prepare(&mut self, gid: &str) -> Result<()> {
+        if self.parent_schema_changed.is_some() {
+            // PREPARE TRANSACTION would prepare the outer transaction.
+            return Err(Error::Unsupported(
+                "Two-phase commit of a nested transaction",
+                BACKEND_NAME,
+            ));
+        }
+        match self.trans.take() {
+            None => Err(Self::already_consumed()),
+            Some(trans) => {
+                let gid = helper::sql_literal_value(&SqlVal::Text(gid.to_string()))?;
+                trans
+                    .batch_execute(&format!("PREPARE TRANSACTION {gid}"))
+                    .await?;
+                // The transaction is no longer open on this connection, so
+                // it must not be rolled back when dropped.
+                std::mem::forget(trans);
+                Ok(())
+            }
         }
     }
     // Workaround for https://github.com/rust-lang/rfcs/issues/2765
@@ -437,17 +1000,39 @@ fn to_sql(
         Box<dyn std::error::Error + 'static + Sync + Send>,
     > {
         use SqlValRef::*;
+        // A domain is written as the type it constrains
+        if let postgres::types::Kind::Domain(base) = requested_ty.kind() {
+            return self.to_sql(base, out);
+        }
         match self {
             Bool(b) => b.to_sql_checked(requested_ty, out),
             Int(i) => i.to_sql_checked(requested_ty, out),
             BigInt(i) => i.to_sql_checked(requested_ty, out),
+            HugeInt(i) => PgHugeInt(*i).to_sql_checked(requested_ty, out),
             Real(r) => r.to_sql_checked(requested_ty, out),
             Text(t) => t.to_sql_checked(requested_ty, out),
             Blob(b) => b.to_sql_checked(requested_ty, out),
+            // A key-value map is written to an hstore, whose type is
+            // only known by the name the extension gives it.
+            #[cfg(feature = "json")]
+            Json(v) if requested_ty.name() == "hstore" => {
+                hstore_from_json(v)?.to_sql_checked(requested_ty, out)
+            }
             #[cfg(feature = "json")]
             Json(v) => v.to_sql_checked(requested_ty, out),
             #[cfg(feature = "datetime")]
             Timestamp(dt) => dt.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "datetime")]
+            TimestampTz(dt) => dt.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "datetime")]
+            Date(d) => d.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "datetime")]
+            Time(t) => t.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "decimal")]
+            Decimal(d) => d.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "uuid")]
+            Uuid(u) => u.to_sql_checked(requested_ty, out),
+            Array(_, items) => items.to_sql_checked(requested_ty, out),
             Null => Ok(postgres::types::IsNull::Yes),
             Custom(SqlValRefCustom::PgToSql { ty, tosql }) => {
                 check_type_match(ty, requested_ty)?;
@@ -469,11 +1054,133 @@ fn accepts(_ty: &postgres::types::Type) -> bool {
     postgres::types::to_sql_checked!();
 }
 
+/// The hstore holding the JSON object `v`, whose values must be strings
+/// or null.
+#[cfg(feature = "json")]
+fn hstore_from_json(
+    v: &serde_json::Value,
+) -> std::result::Result<
+    HashMap<String, Option<String>>,
+    Box<dyn std::error::Error + 'static + Sync + Send>,
+> {
+    use serde_json::Value;
+    let obj = v
+        .as_object()
+        .ok_or("only a JSON object can be written to an hstore")?;
+    obj.iter()
+        .map(|(key, val)| match val {
+            Value::String(s) => Ok((key.clone(), Some(s.clone()))),
+            Value::Null => Ok((key.clone(), None)),
+            _ => Err(format!("hstore value for key {key} is not a string").into()),
+        })
+        .collect()
+}
+
+/// The JSON object holding the entries of an hstore.
+#[cfg(feature = "json")]
+fn json_from_hstore(map: HashMap<String, Option<String>>) -> serde_json::Value {
+    use serde_json::Value;
+    map.into_iter()
+        .map(|(key, val)| (key, val.map_or(Value::Null, Value::String)))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// An integer in the binary format of a postgres `NUMERIC`, which
+/// holds a sign, a weight, and digits in base 10000 of which the first
+/// is multiplied by 10000 to the power of the weight.
+#[derive(Debug)]
+struct PgHugeInt(i128);
+
+const PG_NUMERIC_NEG: u16 = 0x4000;
+
+impl postgres::types::ToSql for PgHugeInt {
+    fn to_sql(
+        &self,
+        _ty: &postgres::types::Type,
+        out: &mut bytes::BytesMut,
+    ) -> std::result::Result<
+        postgres::types::IsNull,
+        Box<dyn std::error::Error + 'static + Sync + Send>,
+    > {
+        let mut n = self.0.unsigned_abs();
+        let mut digits = Vec::new();
+        while n > 0 {
+            digits.push((n % 10000) as i16);
+            n /= 10000;
+        }
+        let weight = digits.len().saturating_sub(1) as i16;
+        // Trailing zero digits are implied by the weight.
+        let zeros = digits.iter().take_while(|d| **d == 0).count();
+        out.put_i16((digits.len() - zeros) as i16);
+        out.put_i16(weight);
+        out.put_u16(if self.0 < 0 { PG_NUMERIC_NEG } else { 0 });
+        out.put_u16(0); // display scale
+        for d in digits[zeros..].iter().rev() {
+            out.put_i16(*d);
+        }
+        Ok(postgres::types::IsNull::No)
+    }
+    fn accepts(ty: &postgres::types::Type) -> bool {
+        *ty == postgres::types::Type::NUMERIC
+    }
+    postgres::types::to_sql_checked!();
+}
+
+impl<'a> postgres::types::FromSql<'a> for PgHugeInt {
+    fn from_sql(
+        _ty: &postgres::types::Type,
+        raw: &'a [u8],
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + 'static + Sync + Send>> {
+        let field = |i: usize| -> std::result::Result<u16, Error> {
+            raw.get(2 * i..2 * i + 2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .ok_or_else(|| Error::Internal("truncated numeric".to_string()))
+        };
+        let ndigits = field(0)? as usize;
+        let weight = field(1)? as i16 as i32;
+        let sign = field(2)?;
+        let mut n: u128 = 0;
+        // Each digit, followed by the zero digits implied by the weight.
+        for i in 0..=weight.max(ndigits as i32 - 1) {
+            let d = if (i as usize) < ndigits {
+                field(4 + i as usize)?
+            } else {
+                0
+            };
+            if i > weight {
+                if d != 0 {
+                    // A fractional part.
+                    return Err(Box::new(Error::OutOfRange));
+                }
+                continue;
+            }
+            n = n
+                .checked_mul(10000)
+                .and_then(|n| n.checked_add(d.into()))
+                .ok_or(Error::OutOfRange)?;
+        }
+        let i = match sign {
+            0 => i128::try_from(n).ok(),
+            PG_NUMERIC_NEG => 0i128.checked_sub_unsigned(n),
+            // NaN or infinity
+            _ => None,
+        };
+        Ok(PgHugeInt(i.ok_or(Error::OutOfRange)?))
+    }
+    fn accepts(ty: &postgres::types::Type) -> bool {
+        *ty == postgres::types::Type::NUMERIC
+    }
+}
+
 fn check_type_match(
     ty1: &postgres::types::Type,
     ty2: &postgres::types::Type,
 ) -> std::result::Result<(), Box<dyn std::error::Error + 'static + Sync + Send>> {
-    if ty1 == ty2 {
+    use postgres::types::Kind;
+    // A user-defined type has no fixed OID, so is matched by name.
+    let user_defined = matches!(ty2.kind(), Kind::Composite(_) | Kind::Enum(_));
+    if ty1 == ty2 || (user_defined && ty1.name() == ty2.name()) {
         Ok(())
     } else {
         Err(Box::new(crate::Error::Internal(format!(
@@ -505,10 +1212,35 @@ fn from_sql(
             )?)),
             #[cfg(feature = "datetime")]
             Type::TIMESTAMP => Ok(SqlValRef::Timestamp(NaiveDateTime::from_sql(ty, raw)?)),
-            _ => Ok(SqlValRef::Custom(SqlValRefCustom::PgBytes {
-                ty: ty.clone(),
-                data: raw,
-            })),
+            #[cfg(feature = "datetime")]
+            Type::TIMESTAMPTZ => Ok(SqlValRef::TimestampTz(DateTime::<FixedOffset>::from_sql(
+                ty, raw,
+            )?)),
+            #[cfg(feature = "datetime")]
+            Type::DATE => Ok(SqlValRef::Date(NaiveDate::from_sql(ty, raw)?)),
+            #[cfg(feature = "datetime")]
+            Type::TIME => Ok(SqlValRef::Time(NaiveTime::from_sql(ty, raw)?)),
+            #[cfg(feature = "decimal")]
+            Type::NUMERIC => Ok(SqlValRef::Decimal(rust_decimal::Decimal::from_sql(
+                ty, raw,
+            )?)),
+            #[cfg(feature = "uuid")]
+            Type::UUID => Ok(SqlValRef::Uuid(uuid::Uuid::from_sql(ty, raw)?)),
+            _ if ty.name() == "citext" => Ok(SqlValRef::Text(postgres::types::FromSql::from_sql(
+                ty, raw,
+            )?)),
+            #[cfg(feature = "json")]
+            _ if ty.name() == "hstore" => Ok(SqlValRef::Json(json_from_hstore(HashMap::from_sql(
+                ty, raw,
+            )?))),
+            _ => match ty.kind() {
+                // A domain is read as the type it constrains
+                postgres::types::Kind::Domain(base) => Self::from_sql(base, raw),
+                _ => Ok(SqlValRef::Custom(SqlValRefCustom::PgBytes {
+                    ty: ty.clone(),
+                    data: raw,
+                })),
+            },
         }
     }
 
@@ -528,6 +1260,72 @@ fn accepts(_ty: &postgres::types::Type) -> bool {
     }
 }
 
+/// The number of rows fetched at a time by
+/// [`query_stream`][ConnectionMethods::query_stream] unless the
+/// connection's [`QueryLimits`] say otherwise.
+pub const CURSOR_FETCH_SIZE: usize = super::DEFAULT_FETCH_SIZE;
+
+static CURSOR_COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// The state of the stream returned by
+/// [`query_stream`][ConnectionMethods::query_stream].
+struct CursorState<'c, C: GenericClient> {
+    client: &'c C,
+    fetch: String,
+    close: String,
+    columns: Vec<Column>,
+    rows: std::collections::VecDeque<postgres::Row>,
+    /// Whether the cursor has been read to its end and closed.
+    done: bool,
+}
+
+impl<C: GenericClient> Drop for CursorState<'_, C> {
+    fn drop(&mut self) {
+        if !self.done {
+            // The statement is sent when the future is first polled, the
+            // same way tokio_postgres rolls back a dropped transaction.
+            // Its result is not waited for.
+            let _ = self
+                .client
+                .batch_execute(self.close.as_str())
+                .now_or_never();
+        }
+    }
+}
+
+/// The SQL selecting `columns` of `table`, and the values of its placeholders.
+fn select_sql(
+    table: &str,
+    columns: &[Column],
+    expr: Option<BoolExpr>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    order: Option<&[query::Order]>,
+) -> (String, Vec<SqlVal>) {
+    let mut sqlquery = String::new();
+    helper::sql_select(columns, table, &mut sqlquery);
+    let mut values: Vec<SqlVal> = Vec::new();
+    if let Some(expr) = expr {
+        sqlquery.write_str(" WHERE ").unwrap();
+        sql_for_expr(
+            query::Expr::Condition(Box::new(expr)),
+            &mut values,
+            &mut PgPlaceholderSource::new(),
+            &mut sqlquery,
+        );
+    }
+    if let Some(order) = order {
+        helper::sql_order(order, &mut sqlquery)
+    }
+    if let Some(limit) = limit {
+        helper::sql_limit(limit, &mut sqlquery)
+    }
+    if let Some(offset) = offset {
+        helper::sql_offset(offset, &mut sqlquery)
+    }
+    (sqlquery, values)
+}
+
 fn check_columns(row: &postgres::Row, cols: &[Column]) -> Result<()> {
     if cols.len() != row.len() {
         Err(Error::Internal(format!(
@@ -541,8 +1339,8 @@ fn check_columns(row: &postgres::Row, cols: &[Column]) -> Result<()> {
 }
 
 impl BackendRow for postgres::Row {
-    fn get(&self, idx: usize, _ty: SqlType) -> Result<SqlValRef> {
-        Ok(self.try_get(idx)?)
+    fn get(&self, idx: usize, ty: SqlType) -> Result<SqlValRef> {
+        sql_valref_from_postgres(self, idx, &ty)
     }
     fn len(&self) -> usize {
         postgres::Row::len(self)
@@ -560,11 +1358,35 @@ fn sql_for_expr<W>(
     helper::sql_for_expr(expr, sql_for_expr, values, pls, w)
 }
 
+/// Read the value at `idx`, which is of type `ty`. A numeric is read
+/// as a huge int only where that is the expected type, and an array
+/// takes its element type from `ty`.
+fn sql_valref_from_postgres<'a, I>(
+    row: &'a postgres::Row,
+    idx: I,
+    ty: &SqlType,
+) -> Result<SqlValRef<'a>>
+where
+    I: postgres::row::RowIndex + std::fmt::Display,
+{
+    match ty {
+        SqlType::HugeInt => Ok(match row.try_get(idx)? {
+            Some(PgHugeInt(i)) => SqlValRef::HugeInt(i),
+            None => SqlValRef::Null,
+        }),
+        SqlType::Array(elem) => Ok(match row.try_get::<_, Option<Vec<SqlValRef>>>(idx)? {
+            Some(items) => SqlValRef::Array(elem, items.into_iter().map(SqlVal::from).collect()),
+            None => SqlValRef::Null,
+        }),
+        _ => Ok(row.try_get(idx)?),
+    }
+}
+
 fn sql_val_from_postgres<I>(row: &postgres::Row, idx: I, col: &Column) -> Result<SqlVal>
 where
     I: postgres::row::RowIndex + std::fmt::Display,
 {
-    let sqlref: SqlValRef = row.try_get(idx)?;
+    let sqlref = sql_valref_from_postgres(row, idx, col.ty())?;
     let sqlval: SqlVal = sqlref.into();
     if sqlval.is_compatible(col.ty(), true) {
         Ok(sqlval)
@@ -580,19 +1402,87 @@ fn sql_val_from_postgres<I>(row: &postgres::Row, idx: I, col: &Column) -> Result
     }
 }
 
-fn sql_for_op(current: &mut ADB, op: &Operation) -> Result<String> {
+/// Converts a `JSONB` object to an `HSTORE`, as a function because the
+/// conversion of a column's type cannot use a subquery.
+#[cfg(feature = "json")]
+const JSONB_TO_HSTORE_FN: &str =
+    "CREATE OR REPLACE FUNCTION pg_temp.butane_jsonb_to_hstore(j JSONB) \
+RETURNS HSTORE LANGUAGE SQL IMMUTABLE STRICT AS \
+$$ SELECT coalesce(hstore(array_agg(key), array_agg(value)), '') FROM jsonb_each_text(j) $$;";
+
+/// The extension providing `ty`, if it is not built in.
+fn type_extension(ty: &SqlType) -> Option<&'static str> {
+    match ty {
+        SqlType::CiText => Some("citext"),
+        #[cfg(feature = "json")]
+        SqlType::KeyValue => Some("hstore"),
+        _ => None,
+    }
+}
+
+/// Statements which must run before those of `ops`. The extensions
+/// providing the types of columns added are created, along with a
+/// function converting JSON to a key-value map if a JSON column
+/// becomes one.
+fn migration_prelude(ops: &[Operation]) -> Vec<String> {
+    let mut added: Vec<&AColumn> = Vec::new();
+    for op in ops {
+        match op {
+            Operation::AddTable(table) | Operation::AddTableIfNotExists(table) => {
+                added.extend(&table.columns)
+            }
+            Operation::AddColumn(_, col) => added.push(col),
+            Operation::ChangeColumn(_, old, new) if old.typeid().ok() != new.typeid().ok() => {
+                added.push(new)
+            }
+            _ => (),
+        }
+    }
+    let mut extensions: Vec<&str> = added
+        .iter()
+        .filter_map(|col| match col.typeid() {
+            Ok(TypeIdentifier::Ty(ty)) => type_extension(&ty),
+            _ => None,
+        })
+        .collect();
+    extensions.sort_unstable();
+    extensions.dedup();
+    #[allow(unused_mut)]
+    let mut stmts: Vec<String> = extensions
+        .iter()
+        .map(|extension| format!("CREATE EXTENSION IF NOT EXISTS {extension};"))
+        .collect();
+    #[cfg(feature = "json")]
+    {
+        let converts_json = ops.iter().any(|op| {
+            matches!(op, Operation::ChangeColumn(_, old, new)
+                if matches!(old.typeid(), Ok(TypeIdentifier::Ty(SqlType::Json)))
+                    && matches!(new.typeid(), Ok(TypeIdentifier::Ty(SqlType::KeyValue))))
+        });
+        if converts_json {
+            stmts.push(JSONB_TO_HSTORE_FN.to_string());
+        }
+    }
+    stmts
+}
+
+/// SQL for `op`. If `online` is true, operations which would otherwise
+/// block writes to a table for as long as they take are done in a way
+/// which does not.
+fn sql_for_op(current: &mut ADB, op: &Operation, online: bool) -> Result<String> {
     match op {
         Operation::AddTable(table) => Ok(create_table(table, false)?),
         Operation::AddTableConstraints(table) => Ok(create_table_fkey_constraints(table)),
         Operation::AddTableIfNotExists(table) => Ok(create_table(table, true)?),
         Operation::RemoveTable(name) => Ok(drop_table(name)),
+        Operation::RenameTable(from, to) => Ok(rename_table(from, to)),
         Operation::RemoveTableConstraints(table) => remove_table_fkey_constraints(table),
         Operation::AddColumn(tbl, col) => add_column(tbl, col),
         Operation::RemoveColumn(tbl, name) => Ok(remove_column(tbl, name)),
         Operation::ChangeColumn(tbl, old, new) => {
             let table = current.get_table(tbl);
             if let Some(table) = table {
-                change_column(table, old, new)
+                change_column(table, old, new, online)
             } else {
                 crate::warn!(
                     "Cannot alter column {} from table {} that does not exist",
@@ -602,6 +1492,18 @@ fn sql_for_op(current: &mut ADB, op: &Operation) -> Result<String> {
                 Ok(String::new())
             }
         }
+        Operation::AddIndex(tbl, index) => Ok(helper::create_index(tbl, index, true, online)),
+        Operation::RemoveIndex(_tbl, name) => Ok(helper::drop_index(name, online)),
+        Operation::AddConstraint(tbl, constraint) => Ok(add_constraint(tbl, constraint, online)),
+        Operation::ChangeSqliteOptions(..) => Ok(String::new()),
+        Operation::AddCustomType(ty) => Ok(create_custom_type(ty)),
+        Operation::RemoveCustomType(ty) => Ok(drop_custom_type(ty)),
+        Operation::ChangeCustomType(old, new) => Ok(change_custom_type(current, old, new)),
+        Operation::RemoveConstraint(tbl, name) => Ok(format!(
+            "ALTER TABLE {} DROP CONSTRAINT {};",
+            helper::quote_reserved_word(tbl),
+            helper::quote_reserved_word(name)
+        )),
     }
 }
 
@@ -632,13 +1534,55 @@ fn create_table_fkey_constraints(table: &ATable) -> String {
 }
 
 fn remove_table_fkey_constraints(table: &ATable) -> Result<String> {
-    Ok(table
+    let mut stmts = table
         .columns
         .iter()
         .filter(|column| column.reference().is_some())
         .map(|column| drop_fkey_constraints(table, column))
-        .collect::<Result<Vec<String>>>()?
-        .join("\n"))
+        .collect::<Result<Vec<String>>>()?;
+    stmts.extend(
+        table
+            .constraints
+            .iter()
+            .filter(|constraint| matches!(constraint.kind(), AConstraintKind::ForeignKey { .. }))
+            .map(|constraint| {
+                format!(
+                    "ALTER TABLE {} DROP CONSTRAINT {};",
+                    helper::quote_reserved_word(&table.name),
+                    helper::quote_reserved_word(constraint.name())
+                )
+            }),
+    );
+    Ok(stmts.join("\n"))
+}
+
+/// SQL to add `constraint` to the table `tbl_name`. If `online` is
+/// true, check and foreign key constraints are added without checking
+/// existing rows and then validated, which does not block writes, and
+/// a unique constraint uses an index built concurrently.
+fn add_constraint(tbl_name: &str, constraint: &AConstraint, online: bool) -> String {
+    let table = helper::quote_reserved_word(tbl_name);
+    let name = helper::quote_reserved_word(constraint.name());
+    match constraint.kind() {
+        _ if !online => format!(
+            "ALTER TABLE {table} ADD {};",
+            helper::define_constraint(constraint)
+        ),
+        AConstraintKind::Unique(columns) => format!(
+            "CREATE UNIQUE INDEX CONCURRENTLY {name} ON {table} ({});\n\
+             ALTER TABLE {table} ADD CONSTRAINT {name} UNIQUE USING INDEX {name};",
+            columns
+                .iter()
+                .map(|column| helper::quote_reserved_word(column))
+                .collect::<Vec<Cow<str>>>()
+                .join(", ")
+        ),
+        AConstraintKind::Check(_) | AConstraintKind::ForeignKey { .. } => format!(
+            "ALTER TABLE {table} ADD {} NOT VALID;\n\
+             ALTER TABLE {table} VALIDATE CONSTRAINT {name};",
+            helper::define_constraint(constraint)
+        ),
+    }
 }
 
 fn define_column(col: &AColumn) -> Result<String> {
@@ -675,22 +1619,85 @@ fn define_fkey_constraint(table_name: &str, column: &AColumn) -> String {
     match reference {
         ARef::Literal(literal) => {
             format!(
-                "ALTER TABLE {} ADD FOREIGN KEY ({}) REFERENCES {}({});",
+                "ALTER TABLE {} ADD FOREIGN KEY ({}) REFERENCES {}({}){};",
                 helper::quote_reserved_word(table_name),
                 helper::quote_reserved_word(column.name()),
                 helper::quote_reserved_word(literal.table_name()),
                 helper::quote_reserved_word(literal.column_name()),
+                deferrable_clause(column),
             )
         }
         _ => panic!(),
     }
 }
 
+/// The name PostgreSQL gives the constraint of `column` of `table` it
+/// names itself, such as its foreign key for the suffix `fkey`, quoted.
+/// The table and column names in it are folded to lower case, unless
+/// they are reserved words, which are quoted when the table is created.
+fn default_constraint_name(table: &str, column: &str, suffix: &str) -> String {
+    let folded = |name: &str| {
+        if helper::is_reserved_word(name) {
+            name.to_string()
+        } else {
+            name.to_lowercase()
+        }
+    };
+    format!("\"{}_{}_{suffix}\"", folded(table), folded(column))
+}
+
+fn deferrable_clause(column: &AColumn) -> &'static str {
+    if column.is_deferrable() {
+        " DEFERRABLE INITIALLY DEFERRED"
+    } else {
+        ""
+    }
+}
+
 fn drop_fkey_constraints(table: &ATable, column: &AColumn) -> Result<String> {
     let mut modified_column = column.clone();
     modified_column.remove_reference();
-    change_column(table, column, &modified_column)
+    change_column(table, column, &modified_column, false)
+}
+/// The type of a column with the given type as reported by `information_schema`.
+fn typeid_for_pg_type_name(name: &str) -> TypeIdentifier {
+    match name {
+        "boolean" => TypeIdentifier::Ty(SqlType::Bool),
+        "integer" => TypeIdentifier::Ty(SqlType::Int),
+        "bigint" => TypeIdentifier::Ty(SqlType::BigInt),
+        "double precision" => TypeIdentifier::Ty(SqlType::Real),
+        "text" => TypeIdentifier::Ty(SqlType::Text),
+        "citext" => TypeIdentifier::Ty(SqlType::CiText),
+        #[cfg(feature = "datetime")]
+        "timestamp without time zone" => TypeIdentifier::Ty(SqlType::Timestamp),
+        #[cfg(feature = "datetime")]
+        "timestamp with time zone" => TypeIdentifier::Ty(SqlType::TimestampTz),
+        #[cfg(feature = "datetime")]
+        "date" => TypeIdentifier::Ty(SqlType::Date),
+        #[cfg(feature = "datetime")]
+        "time without time zone" => TypeIdentifier::Ty(SqlType::Time),
+        #[cfg(feature = "decimal")]
+        "numeric" => TypeIdentifier::Ty(SqlType::Numeric(None)),
+        "bytea" => TypeIdentifier::Ty(SqlType::Blob),
+        #[cfg(feature = "json")]
+        "jsonb" => TypeIdentifier::Ty(SqlType::Json),
+        #[cfg(feature = "json")]
+        "hstore" => TypeIdentifier::Ty(SqlType::KeyValue),
+        #[cfg(feature = "uuid")]
+        "uuid" => TypeIdentifier::Ty(SqlType::Uuid),
+        // Arrays are named by the `udt_name` of their type.
+        "_bool" => TypeIdentifier::Ty(SqlType::Array(&SqlType::Bool)),
+        "_int4" => TypeIdentifier::Ty(SqlType::Array(&SqlType::Int)),
+        "_int8" => TypeIdentifier::Ty(SqlType::Array(&SqlType::BigInt)),
+        "_float8" => TypeIdentifier::Ty(SqlType::Array(&SqlType::Real)),
+        "_text" => TypeIdentifier::Ty(SqlType::Array(&SqlType::Text)),
+        "_bytea" => TypeIdentifier::Ty(SqlType::Array(&SqlType::Blob)),
+        #[cfg(feature = "uuid")]
+        "_uuid" => TypeIdentifier::Ty(SqlType::Array(&SqlType::Uuid)),
+        _ => TypeIdentifier::Name(name.to_string()),
+    }
 }
+
 fn col_sqltype(col: &AColumn) -> Result<Cow<str>> {
     match col.typeid()? {
         TypeIdentifier::Name(name) => Ok(Cow::Owned(name)),
@@ -702,38 +1709,169 @@ fn col_sqltype(col: &AColumn) -> Result<Cow<str>> {
                     _ => Err(Error::InvalidAuto(col.name().to_string())),
                 }
             } else {
-                Ok(match ty {
-                    SqlType::Bool => Cow::Borrowed("BOOLEAN"),
-                    SqlType::Int => Cow::Borrowed("INTEGER"),
-                    SqlType::BigInt => Cow::Borrowed("BIGINT"),
-                    SqlType::Real => Cow::Borrowed("DOUBLE PRECISION"),
-                    SqlType::Text => Cow::Borrowed("TEXT"),
+                match (ty, col.precision()) {
                     #[cfg(feature = "datetime")]
-                    SqlType::Timestamp => Cow::Borrowed("TIMESTAMP"),
-                    SqlType::Blob => Cow::Borrowed("BYTEA"),
-                    #[cfg(feature = "json")]
-                    SqlType::Json => Cow::Borrowed("JSONB"),
-                    SqlType::Custom(c) => match c {
-                        SqlTypeCustom::Pg(ref ty) => Cow::Owned(ty.name().to_string()),
-                    },
-                })
+                    (SqlType::Timestamp, Some(digits)) => {
+                        Ok(Cow::Owned(format!("TIMESTAMP({digits})")))
+                    }
+                    #[cfg(feature = "datetime")]
+                    (SqlType::TimestampTz, Some(digits)) => {
+                        Ok(Cow::Owned(format!("TIMESTAMPTZ({digits})")))
+                    }
+                    (ty, _) => Ok(pg_type_name(&ty)),
+                }
             }
         }
     }
 }
 
+/// The name of the postgres type of a column of type `ty`.
+fn pg_type_name(ty: &SqlType) -> Cow<'static, str> {
+    match ty {
+        SqlType::Bool => Cow::Borrowed("BOOLEAN"),
+        SqlType::Int => Cow::Borrowed("INTEGER"),
+        SqlType::BigInt => Cow::Borrowed("BIGINT"),
+        SqlType::HugeInt => Cow::Borrowed("NUMERIC(39, 0)"),
+        SqlType::Real => Cow::Borrowed("DOUBLE PRECISION"),
+        SqlType::Text => Cow::Borrowed("TEXT"),
+        SqlType::CiText => Cow::Borrowed("CITEXT"),
+        #[cfg(feature = "datetime")]
+        SqlType::Timestamp => Cow::Borrowed("TIMESTAMP"),
+        #[cfg(feature = "datetime")]
+        SqlType::TimestampTz => Cow::Borrowed("TIMESTAMPTZ"),
+        #[cfg(feature = "datetime")]
+        SqlType::Date => Cow::Borrowed("DATE"),
+        #[cfg(feature = "datetime")]
+        SqlType::Time => Cow::Borrowed("TIME"),
+        #[cfg(feature = "decimal")]
+        SqlType::Numeric(None) => Cow::Borrowed("NUMERIC"),
+        #[cfg(feature = "decimal")]
+        SqlType::Numeric(Some((precision, scale))) => {
+            Cow::Owned(format!("NUMERIC({precision}, {scale})"))
+        }
+        SqlType::Blob => Cow::Borrowed("BYTEA"),
+        #[cfg(feature = "json")]
+        SqlType::Json => Cow::Borrowed("JSONB"),
+        #[cfg(feature = "json")]
+        SqlType::KeyValue => Cow::Borrowed("HSTORE"),
+        #[cfg(feature = "uuid")]
+        SqlType::Uuid => Cow::Borrowed("UUID"),
+        SqlType::Array(elem) => Cow::Owned(format!("{}[]", pg_type_name(elem))),
+        SqlType::Custom(c) => match c {
+            SqlTypeCustom::Pg(ref ty) => Cow::Owned(ty.name().to_string()),
+            SqlTypeCustom::Named(name) => Cow::Owned(name.to_string()),
+        },
+    }
+}
+
+/// The literal of `val`, which unlike [`helper::sql_literal_value`]
+/// writes an array as a postgres array rather than as JSON, and a UUID
+/// as text rather than as a blob.
+fn sql_literal_value(val: &SqlVal) -> Result<String> {
+    match val {
+        #[cfg(feature = "uuid")]
+        SqlVal::Uuid(u) => Ok(format!("'{u}'")),
+        // Untyped, to take the type of the column.
+        SqlVal::Array(_, items) if items.is_empty() => Ok("'{}'".to_string()),
+        SqlVal::Array(_, items) => Ok(format!(
+            "ARRAY[{}]",
+            items
+                .iter()
+                .map(sql_literal_value)
+                .collect::<Result<Vec<String>>>()?
+                .join(", ")
+        )),
+        _ => helper::sql_literal_value(val),
+    }
+}
+
 fn drop_table(name: &str) -> String {
     format!("DROP TABLE {};", helper::quote_reserved_word(name))
 }
 
-fn add_column(tbl_name: &str, col: &AColumn) -> Result<String> {
-    let default: SqlVal = helper::column_default(col)?;
+fn create_custom_type(ty: &ACustomType) -> String {
+    match ty.kind() {
+        ACustomTypeKind::Domain => format!("CREATE DOMAIN {} AS {};", ty.name(), ty.definition()),
+        ACustomTypeKind::Composite => {
+            format!("CREATE TYPE {} AS ({});", ty.name(), ty.definition())
+        }
+    }
+}
+
+fn drop_custom_type(ty: &ACustomType) -> String {
+    format!("DROP {} {};", custom_type_keyword(ty), ty.name())
+}
+
+/// The keyword naming the kind of `ty` in `ALTER` and `DROP` statements.
+fn custom_type_keyword(ty: &ACustomType) -> &'static str {
+    match ty.kind() {
+        ACustomTypeKind::Domain => "DOMAIN",
+        ACustomTypeKind::Composite => "TYPE",
+    }
+}
+
+/// Redefine a type by creating the new definition alongside the old,
+/// converting the columns of the type through its text form and
+/// dropping the old definition. The type keeps its name, as a column
+/// type is written as the name of a user-defined type.
+fn change_custom_type(current: &ADB, old: &ACustomType, new: &ACustomType) -> String {
+    use helper::quote_reserved_word;
+    let replaced = format!("{}_butane_old", old.name());
     let mut stmts = vec![format!(
-        "ALTER TABLE {} ADD COLUMN {} DEFAULT {};",
-        helper::quote_reserved_word(tbl_name),
-        define_column(col)?,
-        helper::sql_literal_value(&default)?
+        "ALTER {} {} RENAME TO {replaced};",
+        custom_type_keyword(old),
+        old.name()
     )];
+    stmts.push(create_custom_type(new));
+    for table in current.tables() {
+        for col in &table.columns {
+            if matches!(col.typeid(), Ok(TypeIdentifier::Name(name)) if name == old.name()) {
+                stmts.push(format!(
+                    "ALTER TABLE {} ALTER COLUMN {} SET DATA TYPE {} USING {}::text::{};",
+                    quote_reserved_word(&table.name),
+                    quote_reserved_word(col.name()),
+                    new.name(),
+                    quote_reserved_word(col.name()),
+                    new.name()
+                ));
+            }
+        }
+    }
+    stmts.push(format!("DROP {} {replaced};", custom_type_keyword(old)));
+    stmts.join("\n")
+}
+
+fn rename_table(from: &str, to: &str) -> String {
+    // Foreign keys of other tables follow the renamed table
+    format!(
+        "ALTER TABLE {} RENAME TO {};",
+        helper::quote_reserved_word(from),
+        helper::quote_reserved_word(to)
+    )
+}
+
+fn add_column(tbl_name: &str, col: &AColumn) -> Result<String> {
+    let mut stmts = match col.backfill() {
+        Some(expr) => add_backfilled_column(tbl_name, col, expr)?,
+        None => {
+            let default = match helper::column_default(col)? {
+                // The literal of an empty hstore is not that of JSON.
+                #[cfg(feature = "json")]
+                SqlVal::Json(_)
+                    if matches!(col.typeid()?, TypeIdentifier::Ty(SqlType::KeyValue)) =>
+                {
+                    "''".to_string()
+                }
+                default => sql_literal_value(&default)?,
+            };
+            vec![format!(
+                "ALTER TABLE {} ADD COLUMN {} DEFAULT {};",
+                helper::quote_reserved_word(tbl_name),
+                define_column(col)?,
+                default
+            )]
+        }
+    };
     if col.reference().is_some() {
         stmts.push(define_fkey_constraint(tbl_name, col));
     }
@@ -741,6 +1879,30 @@ fn add_column(tbl_name: &str, col: &AColumn) -> Result<String> {
     Ok(result)
 }
 
+/// Add `col` as a nullable column, set its value in the existing rows
+/// to `expr` and only then make it non-null.
+fn add_backfilled_column(tbl_name: &str, col: &AColumn, expr: &str) -> Result<Vec<String>> {
+    let table = helper::quote_reserved_word(tbl_name);
+    let name = helper::quote_reserved_word(col.name());
+    let mut nullable = col.clone();
+    nullable.set_nullable(true);
+    let mut add = format!(
+        "ALTER TABLE {table} ADD COLUMN {}",
+        define_column(&nullable)?
+    );
+    if let Some(default) = col.default() {
+        add.push_str(&format!(" DEFAULT {}", sql_literal_value(default)?));
+    }
+    add.push(';');
+    let mut stmts = vec![add, format!("UPDATE {table} SET {name} = {expr};")];
+    if !col.nullable() {
+        stmts.push(format!(
+            "ALTER TABLE {table} ALTER COLUMN {name} SET NOT NULL;"
+        ));
+    }
+    Ok(stmts)
+}
+
 fn remove_column(tbl_name: &str, name: &str) -> String {
     format!(
         "ALTER TABLE {} DROP COLUMN {};",
@@ -749,7 +1911,49 @@ fn remove_column(tbl_name: &str, name: &str) -> String {
     )
 }
 
-fn change_column(table: &ATable, old: &AColumn, new: &AColumn) -> Result<String> {
+/// The expression converting the values of column `old` to the type
+/// of `new`, if postgres cannot convert them itself: the column's
+/// `convert_using`, or else one for a change between UUIDs and the
+/// blobs which held them before they had their own type.
+#[cfg_attr(not(any(feature = "uuid", feature = "json")), allow(unused_variables))]
+fn convert_using(old: &AColumn, new: &AColumn) -> Result<Option<String>> {
+    if let Some(expr) = new.convert_using() {
+        return Ok(Some(expr.to_string()));
+    }
+    #[cfg(feature = "uuid")]
+    {
+        // Any rename has been made by the time the type changes.
+        let name = helper::quote_reserved_word(new.name());
+        match (old.typeid()?, new.typeid()?) {
+            (TypeIdentifier::Ty(SqlType::Blob), TypeIdentifier::Ty(SqlType::Uuid)) => {
+                return Ok(Some(format!("encode({name}, 'hex')::uuid")));
+            }
+            (TypeIdentifier::Ty(SqlType::Uuid), TypeIdentifier::Ty(SqlType::Blob)) => {
+                return Ok(Some(format!(
+                    "decode(replace({name}::text, '-', ''), 'hex')"
+                )));
+            }
+            _ => (),
+        }
+    }
+    #[cfg(feature = "json")]
+    {
+        let name = helper::quote_reserved_word(new.name());
+        match (old.typeid()?, new.typeid()?) {
+            (TypeIdentifier::Ty(SqlType::Json), TypeIdentifier::Ty(SqlType::KeyValue)) => {
+                // Defined by the migration's prelude.
+                return Ok(Some(format!("pg_temp.butane_jsonb_to_hstore({name})")));
+            }
+            (TypeIdentifier::Ty(SqlType::KeyValue), TypeIdentifier::Ty(SqlType::Json)) => {
+                return Ok(Some(format!("hstore_to_jsonb({name})")));
+            }
+            _ => (),
+        }
+    }
+    Ok(None)
+}
+
+fn change_column(table: &ATable, old: &AColumn, new: &AColumn, online: bool) -> Result<String> {
     use helper::quote_reserved_word;
     let tbl_name = &table.name;
 
@@ -764,20 +1968,51 @@ fn change_column(table: &ATable, old: &AColumn, new: &AColumn) -> Result<String>
             quote_reserved_word(new.name())
         ));
     }
-    if old.typeid()? != new.typeid()? {
+    if old.typeid()? != new.typeid()? || old.precision() != new.precision() {
         // column type change
+        let using = match convert_using(old, new)? {
+            Some(expr) => format!(" USING {expr}"),
+            None => String::new(),
+        };
         stmts.push(format!(
-            "ALTER TABLE {} ALTER COLUMN {} SET DATA TYPE {};",
+            "ALTER TABLE {} ALTER COLUMN {} SET DATA TYPE {}{};",
             quote_reserved_word(tbl_name),
-            quote_reserved_word(old.name()),
+            quote_reserved_word(new.name()),
             col_sqltype(new)?,
+            using,
         ));
     }
-    if old.nullable() != new.nullable() {
+    if online && old.nullable() && !new.nullable() {
+        // SET NOT NULL scans the table while holding a lock which blocks
+        // writes, unless a validated constraint already proves that the
+        // column has no nulls. Validating a constraint does not block writes.
+        let constraint = format!("{}_{}_not_null", tbl_name, new.name());
+        stmts.push(format!(
+            "ALTER TABLE {} ADD CONSTRAINT {} CHECK ({} IS NOT NULL) NOT VALID;",
+            quote_reserved_word(tbl_name),
+            quote_reserved_word(&constraint),
+            quote_reserved_word(new.name())
+        ));
+        stmts.push(format!(
+            "ALTER TABLE {} VALIDATE CONSTRAINT {};",
+            quote_reserved_word(tbl_name),
+            quote_reserved_word(&constraint)
+        ));
+        stmts.push(format!(
+            "ALTER TABLE {} ALTER COLUMN {} SET NOT NULL;",
+            quote_reserved_word(tbl_name),
+            quote_reserved_word(new.name())
+        ));
+        stmts.push(format!(
+            "ALTER TABLE {} DROP CONSTRAINT {};",
+            quote_reserved_word(tbl_name),
+            quote_reserved_word(&constraint)
+        ));
+    } else if old.nullable() != new.nullable() {
         stmts.push(format!(
             "ALTER TABLE {} ALTER COLUMN {} {} NOT NULL;",
             quote_reserved_word(tbl_name),
-            quote_reserved_word(old.name()),
+            quote_reserved_word(new.name()),
             if new.nullable() { "DROP" } else { "SET" }
         ));
     }
@@ -816,10 +2051,9 @@ fn change_column(table: &ATable, old: &AColumn, new: &AColumn) -> Result<String>
         } else {
             // Standard constraint naming scheme
             stmts.push(format!(
-                "ALTER TABLE {} DROP CONSTRAINT {}_{}_key;",
+                "ALTER TABLE {} DROP CONSTRAINT {};",
                 quote_reserved_word(tbl_name),
-                tbl_name,
-                &old.name()
+                default_constraint_name(tbl_name, old.name(), "key")
             ));
         }
     }
@@ -829,13 +2063,13 @@ fn change_column(table: &ATable, old: &AColumn, new: &AColumn) -> Result<String>
             None => format!(
                 "ALTER TABLE {} ALTER COLUMN {} DROP DEFAULT;",
                 quote_reserved_word(tbl_name),
-                quote_reserved_word(old.name())
+                quote_reserved_word(new.name())
             ),
             Some(val) => format!(
                 "ALTER TABLE {} ALTER COLUMN {} SET DEFAULT {};",
                 quote_reserved_word(tbl_name),
-                quote_reserved_word(old.name()),
-                helper::sql_literal_value(val)?
+                quote_reserved_word(new.name()),
+                sql_literal_value(val)?
             ),
         });
     }
@@ -844,15 +2078,25 @@ fn change_column(table: &ATable, old: &AColumn, new: &AColumn) -> Result<String>
         if old.reference().is_some() {
             // Drop the old reference
             stmts.push(format!(
-                "ALTER TABLE {} DROP CONSTRAINT {}_{}_fkey;",
+                "ALTER TABLE {} DROP CONSTRAINT {};",
                 quote_reserved_word(tbl_name),
-                tbl_name,
-                old.name()
+                default_constraint_name(tbl_name, old.name(), "fkey")
             ));
         }
         if new.reference().is_some() {
             stmts.push(define_fkey_constraint(tbl_name, new));
         }
+    } else if old.is_deferrable() != new.is_deferrable() && new.reference().is_some() {
+        stmts.push(format!(
+            "ALTER TABLE {} ALTER CONSTRAINT {} {};",
+            quote_reserved_word(tbl_name),
+            default_constraint_name(tbl_name, old.name(), "fkey"),
+            if new.is_deferrable() {
+                "DEFERRABLE INITIALLY DEFERRED"
+            } else {
+                "NOT DEFERRABLE"
+            }
+        ));
     }
 
     let result = stmts.join("\n");
@@ -898,16 +2142,47 @@ fn pgtype_for_val(val: &SqlVal) -> postgres::types::Type {
         Some(SqlType::Bool) => postgres::types::Type::BOOL,
         Some(SqlType::Int) => postgres::types::Type::INT4,
         Some(SqlType::BigInt) => postgres::types::Type::INT8,
+        Some(SqlType::HugeInt) => postgres::types::Type::NUMERIC,
         Some(SqlType::Real) => postgres::types::Type::FLOAT8,
-        Some(SqlType::Text) => postgres::types::Type::TEXT,
+        // Text is left to be inferred, so that it compares as the type
+        // it is compared with, such as a case-insensitive CITEXT rather
+        // than that converted to text.
+        Some(SqlType::Text) => Type::UNKNOWN,
+        Some(SqlType::CiText) => Type::UNKNOWN,
         Some(SqlType::Blob) => postgres::types::Type::BYTEA,
         #[cfg(feature = "json")]
-        Some(SqlType::Json) => postgres::types::Type::JSON,
+        Some(SqlType::Json) => postgres::types::Type::JSONB,
+        // An hstore has no fixed type, so is left to be inferred.
+        #[cfg(feature = "json")]
+        Some(SqlType::KeyValue) => Type::UNKNOWN,
         #[cfg(feature = "datetime")]
         Some(SqlType::Timestamp) => postgres::types::Type::TIMESTAMP,
+        #[cfg(feature = "datetime")]
+        Some(SqlType::TimestampTz) => postgres::types::Type::TIMESTAMPTZ,
+        #[cfg(feature = "datetime")]
+        Some(SqlType::Date) => postgres::types::Type::DATE,
+        #[cfg(feature = "datetime")]
+        Some(SqlType::Time) => postgres::types::Type::TIME,
+        #[cfg(feature = "decimal")]
+        Some(SqlType::Numeric(_)) => postgres::types::Type::NUMERIC,
+        #[cfg(feature = "uuid")]
+        Some(SqlType::Uuid) => Type::UUID,
+        Some(SqlType::Array(elem)) => match elem {
+            SqlType::Bool => Type::BOOL_ARRAY,
+            SqlType::Int => Type::INT4_ARRAY,
+            SqlType::BigInt => Type::INT8_ARRAY,
+            SqlType::Real => Type::FLOAT8_ARRAY,
+            SqlType::Text => Type::TEXT_ARRAY,
+            SqlType::Blob => Type::BYTEA_ARRAY,
+            #[cfg(feature = "uuid")]
+            SqlType::Uuid => Type::UUID_ARRAY,
+            _ => Type::UNKNOWN,
+        },
         Some(SqlType::Custom(inner)) => match inner {
             #[cfg(feature = "pg")]
             SqlTypeCustom::Pg(ty, ..) => ty,
+            // A user-defined type has no fixed OID, so is left to be inferred.
+            SqlTypeCustom::Named(_) => Type::UNKNOWN,
         },
     }
 }
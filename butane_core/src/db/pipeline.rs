@@ -0,0 +1,70 @@
+//! Running independent operations concurrently.
+use std::future::Future;
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::{FuturesOrdered, TryStreamExt};
+
+use crate::Result;
+
+/// A queue of independent database operations run concurrently.
+///
+/// The operations are polled together rather than awaited one at a
+/// time. A PostgreSQL connection sends the statements of concurrent
+/// operations without waiting for the results of earlier ones, so a
+/// batch of saves costs about one network round trip rather than one
+/// per save. Other backends run the statements one after another.
+///
+/// ```ignore
+/// let mut pipeline = Pipeline::new();
+/// for post in &mut posts {
+///     pipeline.push(post.save(&conn));
+/// }
+/// pipeline.run().await?;
+/// ```
+///
+/// The operations must not depend on each other, as their statements
+/// may run in any order. Operations which run several statements, such
+/// as saving an object with many-to-many fields, interleave them with
+/// the statements of the other operations.
+pub struct Pipeline<'c, T = ()> {
+    ops: Vec<BoxFuture<'c, Result<T>>>,
+}
+
+impl<'c, T> Pipeline<'c, T> {
+    pub fn new() -> Self {
+        Pipeline { ops: Vec::new() }
+    }
+    /// Queue an operation, such as the future returned by `save`. It
+    /// does not begin until the pipeline is run.
+    pub fn push(&mut self, op: impl Future<Output = Result<T>> + Send + 'c) -> &mut Self {
+        self.ops.push(Box::pin(op));
+        self
+    }
+    /// The number of queued operations.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+    /// Run all the queued operations, returning their results in the
+    /// order they were queued.
+    ///
+    /// If any fails, this returns the error of the first one queued to
+    /// fail, without waiting for the rest, which are dropped. The
+    /// others may or may not have taken effect; run the pipeline in a
+    /// transaction to make it all or nothing.
+    pub async fn run(self) -> Result<Vec<T>> {
+        self.ops
+            .into_iter()
+            .collect::<FuturesOrdered<_>>()
+            .try_collect()
+            .await
+    }
+}
+
+impl<T> Default for Pipeline<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
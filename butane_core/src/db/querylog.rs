@@ -0,0 +1,212 @@
+//! Hooks for observing the statements butane runs.
+use std::fmt::{self, Debug, Write};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::{warn, Error, SqlValRef};
+
+/// A statement run by a connection, given to its [`QueryLogger`].
+#[derive(Debug)]
+pub struct QueryInfo<'a> {
+    /// The SQL text of the statement.
+    pub sql: &'a str,
+    /// A summary of each bound parameter. Null, boolean, numeric and
+    /// timestamp values are shown as they are, while text, binary, JSON
+    /// and custom values are redacted to their kind and length.
+    pub params: &'a [String],
+    /// How long the statement took to run.
+    pub duration: Duration,
+    /// The number of rows returned or affected, where known. Rows read
+    /// lazily from SQLite are not counted.
+    pub rows: Option<u64>,
+    /// The error, if the statement failed.
+    pub error: Option<&'a Error>,
+    /// The plan of the statement, given to the hook of a
+    /// [`SlowQueryLog`] which asks for it on PostgreSQL.
+    pub explain: Option<&'a str>,
+}
+
+/// A function called with each statement a connection runs. Set it
+/// with [`set_query_logger`][crate::db::BackendConnection::set_query_logger].
+#[derive(Clone)]
+pub struct QueryLogger(Arc<dyn Fn(&QueryInfo) + Send + Sync>);
+impl QueryLogger {
+    pub fn new(f: impl Fn(&QueryInfo) + Send + Sync + 'static) -> Self {
+        QueryLogger(Arc::new(f))
+    }
+}
+impl Debug for QueryLogger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("QueryLogger")
+    }
+}
+
+/// Reporting of the statements a connection runs which take at least
+/// a threshold. Set it with
+/// [`set_slow_query_log`][crate::db::BackendConnection::set_slow_query_log].
+///
+/// Slow statements are logged as warnings, if the `log` feature is
+/// enabled, unless a [hook][Self::hook] is given to report them
+/// instead.
+#[derive(Clone, Debug)]
+pub struct SlowQueryLog {
+    threshold: Duration,
+    hook: Option<QueryLogger>,
+    explain: bool,
+}
+impl SlowQueryLog {
+    /// Report statements which take at least `threshold`.
+    pub fn new(threshold: Duration) -> Self {
+        SlowQueryLog {
+            threshold,
+            hook: None,
+            explain: false,
+        }
+    }
+    /// Call `f` with each slow statement rather than logging it.
+    pub fn hook(mut self, f: impl Fn(&QueryInfo) + Send + Sync + 'static) -> Self {
+        self.hook = Some(QueryLogger::new(f));
+        self
+    }
+    /// Whether to run `EXPLAIN` for each slow query, save, update or
+    /// delete which succeeded, to report its plan. Only PostgreSQL
+    /// supports this; on other backends it is ignored. The statement is
+    /// planned again, not run, so the plan may differ from the one
+    /// which was slow, for instance if the table's statistics changed.
+    pub fn explain(mut self, explain: bool) -> Self {
+        self.explain = explain;
+        self
+    }
+}
+
+/// The query logger and slow query log of a connection, shared with
+/// its transactions.
+#[derive(Debug, Default)]
+pub(crate) struct QueryLog {
+    logger: RwLock<Option<QueryLogger>>,
+    slow: RwLock<Option<SlowQueryLog>>,
+}
+impl QueryLog {
+    pub(crate) fn set(&self, logger: Option<QueryLogger>) {
+        *self
+            .logger
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = logger;
+    }
+    pub(crate) fn set_slow(&self, slow: Option<SlowQueryLog>) {
+        *self
+            .slow
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = slow;
+    }
+    /// Report a statement begun at `start` to the logger, if there is
+    /// one. `outcome` is the number of rows, where known, or the error.
+    pub(crate) fn log<'v>(
+        &self,
+        sql: &str,
+        params: impl IntoIterator<Item = SqlValRef<'v>>,
+        start: Instant,
+        outcome: std::result::Result<Option<u64>, &Error>,
+    ) {
+        self.log_explained(sql, params, start.elapsed(), outcome, None)
+    }
+    /// Whether a statement which took `duration` should be explained
+    /// for the slow query log.
+    // unused may occur if the pg backend is not selected
+    #[allow(unused)]
+    pub(crate) fn wants_explain(&self, duration: Duration) -> bool {
+        self.slow
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_ref()
+            .is_some_and(|slow| slow.explain && duration >= slow.threshold)
+    }
+    /// Like [`log`][Self::log], for a statement which took `duration`,
+    /// with its plan if it was explained.
+    pub(crate) fn log_explained<'v>(
+        &self,
+        sql: &str,
+        params: impl IntoIterator<Item = SqlValRef<'v>>,
+        duration: Duration,
+        outcome: std::result::Result<Option<u64>, &Error>,
+        explain: Option<&str>,
+    ) {
+        crate::trace::record_statement(sql);
+        let logger = self
+            .logger
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let slow = self
+            .slow
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let slow = slow.as_ref().filter(|slow| duration >= slow.threshold);
+        if logger.is_none() && slow.is_none() {
+            return;
+        }
+        let params = summarize_params(params);
+        let (rows, error) = match outcome {
+            Ok(rows) => (rows, None),
+            Err(e) => (None, Some(e)),
+        };
+        let info = QueryInfo {
+            sql,
+            params: &params,
+            duration,
+            rows,
+            error,
+            explain,
+        };
+        if let Some(logger) = logger.as_ref() {
+            (logger.0)(&info);
+        }
+        match slow.map(|slow| &slow.hook) {
+            Some(Some(hook)) => (hook.0)(&info),
+            Some(None) => {
+                warn!(
+                    "slow statement took {duration:?}: {sql} {params:?}{}",
+                    explain.map(|plan| format!("\n{plan}")).unwrap_or_default()
+                );
+            }
+            None => {}
+        }
+    }
+}
+
+/// Describe each of `params` with [`summarize`].
+pub(crate) fn summarize_params<'v>(params: impl IntoIterator<Item = SqlValRef<'v>>) -> Vec<String> {
+    params.into_iter().map(|p| summarize(&p)).collect()
+}
+
+/// Describe a parameter without revealing values which may be
+/// sensitive.
+fn summarize(val: &SqlValRef) -> String {
+    let mut s = String::new();
+    match val {
+        SqlValRef::Null => s.push_str("NULL"),
+        SqlValRef::Bool(v) => write!(s, "{v}").unwrap(),
+        SqlValRef::Int(v) => write!(s, "{v}").unwrap(),
+        SqlValRef::BigInt(v) => write!(s, "{v}").unwrap(),
+        SqlValRef::HugeInt(v) => write!(s, "{v}").unwrap(),
+        SqlValRef::Real(v) => write!(s, "{v}").unwrap(),
+        SqlValRef::Text(v) => write!(s, "<text, {} chars>", v.chars().count()).unwrap(),
+        SqlValRef::Blob(v) => write!(s, "<blob, {} bytes>", v.len()).unwrap(),
+        #[cfg(feature = "uuid")]
+        SqlValRef::Uuid(_) => s.push_str("<uuid>"),
+        SqlValRef::Array(_, v) => write!(s, "<array, {} elements>", v.len()).unwrap(),
+        #[cfg(feature = "json")]
+        SqlValRef::Json(_) => s.push_str("<json>"),
+        #[cfg(feature = "datetime")]
+        SqlValRef::Timestamp(v) => write!(s, "{}", v.format("%+")).unwrap(),
+        #[cfg(feature = "datetime")]
+        SqlValRef::TimestampTz(v) => s.push_str(&v.to_rfc3339()),
+        #[cfg(feature = "datetime")]
+        SqlValRef::Date(v) => write!(s, "{v}").unwrap(),
+        #[cfg(feature = "datetime")]
+        SqlValRef::Time(v) => write!(s, "{v}").unwrap(),
+        #[cfg(feature = "decimal")]
+        SqlValRef::Decimal(v) => write!(s, "{v}").unwrap(),
+        SqlValRef::Custom(_) => s.push_str("<custom>"),
+    }
+    s
+}
@@ -0,0 +1,224 @@
+//! An identity map of the objects loaded through a connection.
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Mutex, PoisonError};
+
+use async_trait::async_trait;
+
+use super::connmethods::{VecRow, VecRows};
+#[cfg(feature = "async")]
+use super::ConnectionMethodsAsync;
+use super::{BackendRows, Cache, Column, ConnectionMethods, RawQueryResult};
+use crate::migrations::adb::ADB;
+use crate::query::{BoolExpr, Expr, Order};
+use crate::{Result, SqlVal, SqlValRef};
+
+/// A connection which remembers the objects loaded through it by
+/// primary key, so that looking the same one up again does not query
+/// the database.
+///
+/// A session is meant to live for a unit of work, such as handling
+/// one request, in which the same rows are often loaded more than
+/// once, for instance by dereferencing the [`ForeignKey`][crate::ForeignKey]
+/// of several posts to the same blog:
+///
+/// ```ignore
+/// let session = Session::new(&conn);
+/// for post in &posts {
+///     // The blog is queried only the first time.
+///     let blog = Blog::get(&session, post.blog.pk()).await?;
+/// }
+/// ```
+///
+/// Any lookup of a single row by equality with a value, as made by
+/// `get`, `try_get` and loading a foreign key, is cached. Other queries
+/// always run. Saving or deleting through the session forgets what it
+/// cached of that table, and running SQL with
+/// [`execute`][ConnectionMethods::execute] forgets everything, but
+/// changes made otherwise, such as by another connection or by a
+/// cascading delete, are not seen until [`clear`][Self::clear] is
+/// called.
+#[derive(Debug)]
+pub struct Session<C> {
+    conn: C,
+    cache: Mutex<HashMap<String, HashMap<Lookup, Vec<VecRow>>>>,
+}
+
+impl<C> Session<C> {
+    /// Wrap `conn`, which may be a reference to a connection or
+    /// transaction, or a connection checked out from a pool.
+    pub fn new(conn: C) -> Self {
+        Session {
+            conn,
+            cache: Mutex::default(),
+        }
+    }
+    /// Forget everything loaded so far.
+    pub fn clear(&self) {
+        self.cache().clear();
+    }
+    /// Unwrap the connection.
+    pub fn into_inner(self) -> C {
+        self.conn
+    }
+    fn cache(&self) -> std::sync::MutexGuard<'_, HashMap<String, HashMap<Lookup, Vec<VecRow>>>> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    fn cached(&self, table: &str, lookup: &Lookup) -> Option<Vec<VecRow>> {
+        self.cache().get(table)?.get(lookup).cloned()
+    }
+    fn remember(&self, table: &str, lookup: Lookup, rows: Vec<VecRow>) {
+        self.cache()
+            .entry(table.to_string())
+            .or_default()
+            .insert(lookup, rows);
+    }
+    fn forget(&self, table: &str) {
+        self.cache().remove(table);
+    }
+}
+
+/// The columns read by a query for the row whose `column` equals a
+/// value.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Lookup {
+    column: &'static str,
+    // SqlVal is not Hash, as it may hold a float, so the value is
+    // keyed by its debug representation, which also distinguishes
+    // its type. Integers are widened first, as the same key may be
+    // given as either.
+    value: String,
+    columns: Vec<&'static str>,
+}
+
+impl Lookup {
+    /// The lookup made by a query, if it is one which is cached.
+    fn of(
+        columns: &[Column],
+        expr: Option<&BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+    ) -> Option<Self> {
+        match (expr, limit, offset, sort) {
+            (Some(BoolExpr::Eq(column, Expr::Val(value))), Some(1), None, None) => Some(Lookup {
+                column,
+                value: match value {
+                    SqlVal::Int(v) => format!("{:?}", SqlVal::BigInt((*v).into())),
+                    value => format!("{value:?}"),
+                },
+                columns: columns.iter().map(Column::name).collect(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[maybe_async_cfg::maybe(
+    idents(ConnectionMethods(sync = "ConnectionMethods"), AsyncRequiresSync),
+    keep_self,
+    sync(),
+    async(feature = "async")
+)]
+#[async_trait]
+impl<C> ConnectionMethods for Session<C>
+where
+    C: Deref + super::internal::AsyncRequiresSync,
+    C::Target: ConnectionMethods,
+{
+    async fn execute(&self, sql: &str) -> Result<()> {
+        self.clear();
+        self.conn.execute(sql).await
+    }
+    async fn query<'c>(
+        &'c self,
+        table: &str,
+        columns: &[Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+    ) -> Result<RawQueryResult<'c>> {
+        let lookup = Lookup::of(columns, expr.as_ref(), limit, offset, sort);
+        if let Some(rows) = lookup.as_ref().and_then(|l| self.cached(table, l)) {
+            return Ok(Box::new(VecRows::new(rows)));
+        }
+        let mut found = self
+            .conn
+            .query(table, columns, expr, limit, offset, sort)
+            .await?;
+        let Some(lookup) = lookup else {
+            return Ok(found);
+        };
+        let mut rows = Vec::new();
+        while let Some(row) = found.next()? {
+            rows.push(VecRow::new(row, columns)?);
+        }
+        self.remember(table, lookup, rows.clone());
+        Ok(Box::new(VecRows::new(rows)))
+    }
+    async fn insert_returning_pk(
+        &self,
+        table: &str,
+        columns: &[Column],
+        pkcol: &Column,
+        values: &[SqlValRef<'_>],
+    ) -> Result<SqlVal> {
+        self.forget(table);
+        self.conn
+            .insert_returning_pk(table, columns, pkcol, values)
+            .await
+    }
+    async fn insert_only(
+        &self,
+        table: &str,
+        columns: &[Column],
+        values: &[SqlValRef<'_>],
+    ) -> Result<()> {
+        self.forget(table);
+        self.conn.insert_only(table, columns, values).await
+    }
+    async fn insert_or_replace(
+        &self,
+        table: &str,
+        columns: &[Column],
+        pkcol: &Column,
+        values: &[SqlValRef<'_>],
+    ) -> Result<()> {
+        self.forget(table);
+        self.conn
+            .insert_or_replace(table, columns, pkcol, values)
+            .await
+    }
+    async fn update(
+        &self,
+        table: &str,
+        pkcol: Column,
+        pk: SqlValRef<'_>,
+        columns: &[Column],
+        values: &[SqlValRef<'_>],
+    ) -> Result<()> {
+        self.forget(table);
+        self.conn.update(table, pkcol, pk, columns, values).await
+    }
+    async fn delete(&self, table: &str, pkcol: &'static str, pk: SqlVal) -> Result<()> {
+        self.forget(table);
+        self.conn.delete(table, pkcol, pk).await
+    }
+    async fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
+        self.forget(table);
+        self.conn.delete_where(table, expr).await
+    }
+    async fn has_table(&self, table: &str) -> Result<bool> {
+        self.conn.has_table(table).await
+    }
+    async fn introspect(&self) -> Result<ADB> {
+        self.conn.introspect().await
+    }
+    fn cache(&self) -> Option<&dyn Cache> {
+        self.conn.cache()
+    }
+    fn cancel_handle(&self) -> Option<super::CancelHandle> {
+        self.conn.cancel_handle()
+    }
+}
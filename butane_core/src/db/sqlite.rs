@@ -1,15 +1,18 @@
 //! SQLite database backend
 use std::borrow::Cow;
 use std::fmt::{Debug, Write};
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
 use std::path::Path;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 #[cfg(feature = "log")]
 use std::sync::Once;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
 
 use async_trait::async_trait;
 #[cfg(feature = "datetime")]
-use chrono::naive::NaiveDateTime;
+use chrono::{naive::NaiveDate, naive::NaiveDateTime, naive::NaiveTime, DateTime};
 use fallible_streaming_iterator::FallibleStreamingIterator;
 use pin_project::pin_project;
 
@@ -17,14 +20,30 @@
 use super::ConnectionAsync;
 use super::{helper, Backend, BackendRow, Column, RawQueryResult};
 use super::{BackendConnection, BackendTransaction, Connection, ConnectionMethods, Transaction};
-use crate::db::connmethods::BackendRows;
+use super::{CancelHandle, LimitedRows, TransactionOptions};
+use super::{IsolationLevel, QueryLimits, QueryLog, QueryLogger, SharedLimits, SlowQueryLog};
+use crate::db::connmethods::{vec_from_backend_rows, BackendRows};
 use crate::migrations::adb::ARef;
-use crate::migrations::adb::{AColumn, ATable, Operation, TypeIdentifier, ADB};
-use crate::query::{BoolExpr, Order};
-use crate::{debug, query, Error, Result, SqlType, SqlVal, SqlValRef};
+use crate::migrations::adb::{
+    AColumn, AConstraint, ARefLiteral, ATable, DeferredSqlType, Operation, TypeIdentifier, ADB,
+};
+use crate::migrations::{sqlval_from_json, sqlval_to_json};
+use crate::query::{BoolExpr, DatePart, Expr, Order};
+#[cfg(feature = "datetime")]
+use crate::sqlval::{truncate_fraction, MAX_TIMESTAMP_PRECISION};
+use crate::{debug, query, trace, Error, Result, SqlType, SqlVal, SqlValRef};
 
 #[cfg(feature = "datetime")]
 const SQLITE_DT_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+#[cfg(feature = "datetime")]
+/// Timezone-aware timestamps are stored in UTC as RFC 3339 text. The
+/// fraction of a second has a fixed width so that the text sorts in
+/// the order of the instants.
+const SQLITE_DT_TZ_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.9fZ";
+#[cfg(feature = "datetime")]
+const SQLITE_DATE_FORMAT: &str = "%Y-%m-%d";
+#[cfg(feature = "datetime")]
+const SQLITE_TIME_FORMAT: &str = "%H:%M:%S%.f";
 
 /// The minimum SQLite version required by this backend.
 pub const SQLITE_MIN_VERSION: i32 = 3035000;
@@ -92,10 +111,43 @@ fn create_migration_sql(&self, current: &ADB, ops: Vec<Operation>) -> Result<Str
         Ok(lines.join("\n"))
     }
 
+    fn introspected_column(&self, col: &AColumn) -> AColumn {
+        let typeid = match col.typeid() {
+            Ok(TypeIdentifier::Ty(ty)) => typeid_for_sqltype_name(sqltype(&ty)),
+            Ok(TypeIdentifier::Name(name)) => typeid_for_sqltype_name(&name),
+            Err(_) => TypeIdentifier::Name(String::new()),
+        };
+        // An integer primary key is an alias for the rowid whether or not
+        // it is auto, and defaults are not introspected.
+        let mut introspected = AColumn::new(
+            col.name(),
+            DeferredSqlType::KnownId(typeid),
+            col.nullable(),
+            col.is_pk(),
+            false,
+            col.unique(),
+            None,
+            col.reference().clone(),
+        );
+        introspected.set_deferrable(col.is_deferrable());
+        introspected
+    }
+
+    fn migration_lock_sql(&self) -> Option<(&'static str, &'static str)> {
+        // In exclusive locking mode the lock taken by an exclusive
+        // transaction is kept until the mode is reset and the
+        // database is next accessed. Another connection's migrations
+        // may take a while, so wait for the lock longer than the
+        // default busy timeout of 5 seconds, which is restored after.
+        Some((
+            "PRAGMA busy_timeout = 600000; PRAGMA locking_mode = EXCLUSIVE; BEGIN EXCLUSIVE; COMMIT;",
+            "PRAGMA locking_mode = NORMAL; BEGIN IMMEDIATE; COMMIT; PRAGMA busy_timeout = 5000;",
+        ))
+    }
+
     fn connect(&self, path: &str) -> Result<Connection> {
-        Ok(Connection {
-            conn: Box::new(self.connect(path)?),
-        })
+        let conn = Connection::new(Box::new(self.connect(path)?));
+        Ok(conn.with_db_attributes(trace::DbAttributes::new("sqlite", Some(path), None)))
     }
 
     #[cfg(feature = "async-adapter")]
@@ -113,6 +165,8 @@ async fn connect_async(&self, _path: &str) -> Result<ConnectionAsync> {
 #[derive(Debug)]
 pub struct SQLiteConnection {
     conn: rusqlite::Connection,
+    log: QueryLog,
+    limits: SharedLimits,
 }
 impl SQLiteConnection {
     fn open(path: impl AsRef<Path>) -> Result<Self> {
@@ -130,21 +184,29 @@ fn open(path: impl AsRef<Path>) -> Result<Self> {
             _ = unsafe { rusqlite::trace::config_log(Some(log_callback)) };
         });
 
-        rusqlite::Connection::open(path)
-            .map(|conn| SQLiteConnection { conn })
-            .map_err(|e| e.into())
+        let conn = rusqlite::Connection::open(path)?;
+        conn.set_prepared_statement_cache_capacity(super::STATEMENT_CACHE_CAPACITY);
+        Ok(SQLiteConnection {
+            conn,
+            log: QueryLog::default(),
+            limits: SharedLimits::default(),
+        })
     }
 
     // For use with connection_method_wrapper macro
     #[allow(clippy::unnecessary_wraps)]
-    fn wrapped_connection_methods(&self) -> Result<&rusqlite::Connection> {
-        Ok(&self.conn)
+    fn wrapped_connection_methods(&self) -> Result<SqliteConn<'_>> {
+        Ok(SqliteConn {
+            conn: &self.conn,
+            log: &self.log,
+            limits: &self.limits,
+        })
     }
 }
 
 impl ConnectionMethods for SQLiteConnection {
     fn execute(&self, sql: &str) -> Result<()> {
-        ConnectionMethods::execute(self.wrapped_connection_methods()?, sql)
+        self.wrapped_connection_methods()?.execute(sql)
     }
     fn query<'a, 'c>(
         &'c self,
@@ -202,12 +264,26 @@ fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
     fn has_table(&self, table: &str) -> Result<bool> {
         self.wrapped_connection_methods()?.has_table(table)
     }
+    fn introspect(&self) -> Result<ADB> {
+        self.wrapped_connection_methods()?.introspect()
+    }
+    fn cancel_handle(&self) -> Option<CancelHandle> {
+        Some(CancelHandle::sqlite(self.conn.get_interrupt_handle()))
+    }
 }
 
 impl BackendConnection for SQLiteConnection {
-    fn transaction(&mut self) -> Result<Transaction<'_>> {
-        let trans: rusqlite::Transaction<'_> = self.conn.transaction()?;
-        let trans = Box::new(SqliteTransaction::new(trans));
+    fn transaction_with(&mut self, options: TransactionOptions) -> Result<Transaction<'_>> {
+        let behavior = match options.isolation {
+            Some(IsolationLevel::Serializable) => rusqlite::TransactionBehavior::Immediate,
+            _ => rusqlite::TransactionBehavior::Deferred,
+        };
+        let trans: rusqlite::Transaction<'_> = self.conn.transaction_with_behavior(behavior)?;
+        if options.read_only {
+            trans.execute_batch("PRAGMA query_only = 1;")?;
+        }
+        let trans = SqliteTransaction::new(trans, &self.log, &self.limits, options.read_only);
+        let trans = Box::new(trans);
         Ok(Transaction::new(trans))
     }
     fn backend(&self) -> Box<dyn Backend> {
@@ -219,19 +295,49 @@ fn backend_name(&self) -> &'static str {
     fn is_closed(&self) -> bool {
         false
     }
+    fn ping(&self) -> Result<()> {
+        // The database is in-process, so there is nothing to lose
+        // contact with.
+        Ok(())
+    }
+    fn is_broken(&self) -> bool {
+        false
+    }
+    fn set_statement_cache_capacity(&self, capacity: usize) {
+        self.conn.set_prepared_statement_cache_capacity(capacity)
+    }
+    fn set_query_logger(&self, logger: Option<QueryLogger>) {
+        self.log.set(logger)
+    }
+    fn set_slow_query_log(&self, slow: Option<SlowQueryLog>) {
+        self.log.set_slow(slow)
+    }
+    fn set_query_limits(&self, limits: QueryLimits) {
+        self.limits.set(limits)
+    }
 }
 
-impl ConnectionMethods for rusqlite::Connection {
-    fn execute(&self, sql: &str) -> Result<()> {
+/// A SQLite connection or transaction, with the query log and limits
+/// of the connection it belongs to.
+#[derive(Clone, Copy)]
+struct SqliteConn<'c> {
+    conn: &'c rusqlite::Connection,
+    log: &'c QueryLog,
+    limits: &'c SharedLimits,
+}
+impl<'c> SqliteConn<'c> {
+    fn execute(self, sql: &str) -> Result<()> {
         if cfg!(feature = "log") {
             debug!("execute sql {sql}");
         }
-        self.execute_batch(sql.as_ref())?;
-        Ok(())
+        let start = Instant::now();
+        let result = self.conn.execute_batch(sql.as_ref()).map_err(Error::from);
+        self.log.log(sql, [], start, result.as_ref().map(|_| None));
+        result
     }
 
-    fn query<'c>(
-        &'c self,
+    fn query(
+        self,
         table: &str,
         columns: &[Column],
         expr: Option<BoolExpr>,
@@ -272,14 +378,30 @@ fn query<'c>(
 
         debug!("query sql {sqlquery}");
         #[cfg(feature = "debug")]
-        debug!("values {values:?}");
+        debug!(
+            "values {:?}",
+            super::querylog::summarize_params(values.iter().map(SqlValRef::from))
+        );
 
-        let stmt = self.prepare(&sqlquery)?;
-        let adapter = QueryAdapter::new(stmt, rusqlite::params_from_iter(values))?;
-        Ok(Box::new(adapter))
+        let start = Instant::now();
+        let result = self
+            .conn
+            .prepare_cached(&sqlquery)
+            .map_err(Error::from)
+            .and_then(|stmt| QueryAdapter::new(stmt, rusqlite::params_from_iter(values.iter())));
+        self.log.log(
+            &sqlquery,
+            values.iter().map(SqlValRef::from),
+            start,
+            result.as_ref().map(|_| None),
+        );
+        Ok(match self.limits.get().max_rows {
+            Some(max) => Box::new(LimitedRows::new(result?, max)),
+            None => Box::new(result?),
+        })
     }
     fn insert_returning_pk(
-        &self,
+        self,
         table: &str,
         columns: &[Column],
         pkcol: &Column,
@@ -295,21 +417,25 @@ fn insert_returning_pk(
         if cfg!(feature = "log") {
             debug!("insert sql {sql}");
             #[cfg(feature = "debug")]
-            debug!("values {values:?}");
-        }
-        self.execute(&sql, rusqlite::params_from_iter(values))?;
-        let pk: SqlVal = self.query_row_and_then(
-            &format!(
-                "SELECT {} FROM {} WHERE ROWID = last_insert_rowid()",
-                helper::quote_reserved_word(pkcol.name()),
-                helper::quote_reserved_word(table),
-            ),
-            [],
-            |row| sql_val_from_rusqlite(row.get_ref_unwrap(0), pkcol),
-        )?;
-        Ok(pk)
+            debug!(
+                "values {:?}",
+                super::querylog::summarize_params(values.iter().cloned())
+            );
+        }
+        self.execute_cached(&sql, values)?;
+        let sql = format!(
+            "SELECT {} FROM {} WHERE ROWID = last_insert_rowid()",
+            helper::quote_reserved_word(pkcol.name()),
+            helper::quote_reserved_word(table),
+        );
+        let start = Instant::now();
+        let pk: Result<SqlVal> = self.conn.query_row_and_then(&sql, [], |row| {
+            sql_val_from_rusqlite(row.get_ref_unwrap(0), pkcol)
+        });
+        self.log.log(&sql, [], start, pk.as_ref().map(|_| Some(1)));
+        pk
     }
-    fn insert_only(&self, table: &str, columns: &[Column], values: &[SqlValRef<'_>]) -> Result<()> {
+    fn insert_only(self, table: &str, columns: &[Column], values: &[SqlValRef<'_>]) -> Result<()> {
         let mut sql = String::new();
         helper::sql_insert_with_placeholders(
             table,
@@ -320,13 +446,16 @@ fn insert_only(&self, table: &str, columns: &[Column], values: &[SqlValRef<'_>])
         if cfg!(feature = "log") {
             debug!("insert sql {sql}");
             #[cfg(feature = "debug")]
-            debug!("values {values:?}");
+            debug!(
+                "values {:?}",
+                super::querylog::summarize_params(values.iter().cloned())
+            );
         }
-        self.execute(&sql, rusqlite::params_from_iter(values))?;
+        self.execute_cached(&sql, values)?;
         Ok(())
     }
     fn insert_or_replace(
-        &self,
+        self,
         table: &str,
         columns: &[Column],
         pkcol: &Column,
@@ -334,11 +463,11 @@ fn insert_or_replace(
     ) -> Result<()> {
         let mut sql = String::new();
         sql_insert_or_update(table, columns, pkcol, &mut sql);
-        self.execute(&sql, rusqlite::params_from_iter(values))?;
+        self.execute_cached(&sql, values)?;
         Ok(())
     }
     fn update(
-        &self,
+        self,
         table: &str,
         pkcol: Column,
         pk: SqlValRef,
@@ -357,12 +486,19 @@ fn update(
         if cfg!(feature = "log") {
             debug!("update sql {sql}");
             #[cfg(feature = "debug")]
-            debug!("placeholders {placeholder_values:?}");
+            debug!(
+                "placeholders {:?}",
+                super::querylog::summarize_params(placeholder_values.iter().cloned())
+            );
         }
-        self.execute(&sql, rusqlite::params_from_iter(placeholder_values))?;
+        self.execute_cached(&sql, &placeholder_values)?;
         Ok(())
     }
-    fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
+    fn delete(self, table: &str, pkcol: &'static str, pk: SqlVal) -> Result<()> {
+        self.delete_where(table, BoolExpr::Eq(pkcol, Expr::Val(pk)))?;
+        Ok(())
+    }
+    fn delete_where(self, table: &str, expr: BoolExpr) -> Result<usize> {
         let mut sql = String::new();
         let mut values: Vec<SqlVal> = Vec::new();
         write!(
@@ -380,43 +516,215 @@ fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
         if cfg!(feature = "log") {
             debug!("delete where sql {sql}");
             #[cfg(feature = "debug")]
-            debug!("placeholders {values:?}");
+            debug!(
+                "placeholders {:?}",
+                super::querylog::summarize_params(values.iter().map(SqlValRef::from))
+            );
         }
-        let cnt = self.execute(&sql, rusqlite::params_from_iter(values))?;
-        Ok(cnt)
+        let values: Vec<SqlValRef> = values.iter().map(SqlValRef::from).collect();
+        self.execute_cached(&sql, &values)
+    }
+    fn has_table(self, table: &str) -> Result<bool> {
+        const SQL: &str = "SELECT name FROM sqlite_master WHERE type='table' AND name=?;";
+        let start = Instant::now();
+        let result: Result<bool> = (|| {
+            let mut stmt = self.conn.prepare(SQL)?;
+            let mut rows = stmt.query([table])?;
+            Ok(rows.next()?.is_some())
+        })();
+        self.log.log(
+            SQL,
+            [SqlValRef::Text(table)],
+            start,
+            result.as_ref().map(|found| Some(u64::from(*found))),
+        );
+        result
     }
-    fn has_table(&self, table: &str) -> Result<bool> {
-        let mut stmt =
-            self.prepare("SELECT name FROM sqlite_master WHERE type='table' AND name=?;")?;
-        let mut rows = stmt.query([table])?;
-        Ok(rows.next()?.is_some())
+    fn introspect(self) -> Result<ADB> {
+        let mut db = ADB::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT name, sql FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name;",
+        )?;
+        let tables = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (table_name, table_sql) in tables {
+            db.replace_table(introspect_table(self.conn, &table_name, &table_sql)?);
+        }
+        Ok(db)
+    }
+    /// Run a statement through the statement cache, reporting it to
+    /// the query log. Returns the number of rows affected.
+    fn execute_cached(self, sql: &str, values: &[SqlValRef<'_>]) -> Result<usize> {
+        let start = Instant::now();
+        let result = self
+            .conn
+            .prepare_cached(sql)
+            .and_then(|mut stmt| stmt.execute(rusqlite::params_from_iter(values)))
+            .map_err(Error::from);
+        self.log.log(
+            sql,
+            values.iter().cloned(),
+            start,
+            result.as_ref().map(|n| Some(*n as u64)),
+        );
+        result
     }
 }
 
+fn introspect_table(conn: &rusqlite::Connection, name: &str, sql: &str) -> Result<ATable> {
+    // Only single column constraints are described by an ATable.
+    let mut unique: Vec<String> = Vec::new();
+    let mut stmt = conn.prepare(
+        "SELECT il.name FROM pragma_index_list(?1) il WHERE il.\"unique\" AND il.origin = 'u' AND (SELECT count(*) FROM pragma_index_info(il.name)) = 1;",
+    )?;
+    let indexes = stmt
+        .query_map([name], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for index in indexes {
+        let column: String =
+            conn.query_row("SELECT name FROM pragma_index_info(?1);", [index], |row| {
+                row.get(0)
+            })?;
+        unique.push(column);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT fk.\"from\", fk.\"table\", fk.\"to\" FROM pragma_foreign_key_list(?1) fk WHERE (SELECT count(*) FROM pragma_foreign_key_list(?1) other WHERE other.id = fk.id) = 1;",
+    )?;
+    let foreign_keys = stmt
+        .query_map([name], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut table = ATable::new(name.to_string());
+    let mut stmt = conn
+        .prepare("SELECT name, type, \"notnull\", pk FROM pragma_table_info(?1) ORDER BY cid;")?;
+    let columns = stmt
+        .query_map([name], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+                row.get::<_, i64>(3)? > 0,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (column_name, sqltype, notnull, pk) in columns {
+        let mut reference = None;
+        let mut deferrable = false;
+        if let Some((_, ref_table, ref_column)) = foreign_keys
+            .iter()
+            .find(|(from, _, _)| from == &column_name)
+        {
+            let ref_column = match ref_column {
+                Some(ref_column) => ref_column.clone(),
+                // The primary key of the referenced table is implied.
+                None => conn.query_row(
+                    "SELECT name FROM pragma_table_info(?1) WHERE pk = 1;",
+                    [ref_table],
+                    |row| row.get(0),
+                )?,
+            };
+            reference = Some(ARef::Literal(ARefLiteral::new(
+                ref_table.clone(),
+                ref_column,
+            )));
+            deferrable = is_deferrable_in_sql(sql, &column_name);
+        }
+        let mut column = AColumn::new(
+            column_name.clone(),
+            DeferredSqlType::KnownId(typeid_for_sqltype_name(&sqltype)),
+            // A primary key is never null, even if not declared NOT NULL.
+            !notnull && !pk,
+            pk,
+            false,
+            unique.contains(&column_name),
+            None,
+            reference,
+        );
+        column.set_deferrable(deferrable);
+        table.add_column(column);
+    }
+    Ok(table)
+}
+
+/// Whether the foreign key constraint on `column` in the `CREATE TABLE`
+/// statement `sql` is deferred, as SQLite does not report it otherwise.
+fn is_deferrable_in_sql(sql: &str, column: &str) -> bool {
+    let constraint = format!("FOREIGN KEY ({})", helper::quote_reserved_word(column));
+    sql.find(&constraint).is_some_and(|start| {
+        let rest = &sql[start + constraint.len()..];
+        let end = rest.find([',', '\n']).unwrap_or(rest.len());
+        rest[..end].contains("DEFERRABLE INITIALLY DEFERRED")
+    })
+}
+
+/// A transaction, or a savepoint within one.
+#[derive(Debug)]
+enum SqliteTrans<'c> {
+    Transaction(rusqlite::Transaction<'c>),
+    Savepoint(rusqlite::Savepoint<'c>),
+}
+
 #[derive(Debug)]
 struct SqliteTransaction<'c> {
-    trans: Option<rusqlite::Transaction<'c>>,
+    trans: Option<SqliteTrans<'c>>,
+    log: &'c QueryLog,
+    limits: &'c SharedLimits,
+    /// Whether `PRAGMA query_only` was set for this transaction and must
+    /// be cleared when it ends, as the pragma outlives the transaction.
+    read_only: bool,
 }
 impl<'c> SqliteTransaction<'c> {
-    fn new(trans: rusqlite::Transaction<'c>) -> Self {
-        SqliteTransaction { trans: Some(trans) }
+    fn new(
+        trans: rusqlite::Transaction<'c>,
+        log: &'c QueryLog,
+        limits: &'c SharedLimits,
+        read_only: bool,
+    ) -> Self {
+        SqliteTransaction {
+            trans: Some(SqliteTrans::Transaction(trans)),
+            log,
+            limits,
+            read_only,
+        }
+    }
+    fn end_read_only(&mut self) -> Result<()> {
+        if self.read_only {
+            self.conn()?.execute_batch("PRAGMA query_only = 0;")?;
+            self.read_only = false;
+        }
+        Ok(())
+    }
+    fn wrapped_connection_methods(&self) -> Result<SqliteConn<'_>> {
+        Ok(SqliteConn {
+            conn: self.conn()?,
+            log: self.log,
+            limits: self.limits,
+        })
     }
-    fn get(&self) -> Result<&rusqlite::Transaction<'c>> {
+    fn conn(&self) -> Result<&rusqlite::Connection> {
         match &self.trans {
             None => Err(Self::already_consumed()),
-            Some(trans) => Ok(trans),
+            Some(SqliteTrans::Transaction(trans)) => Ok(trans.deref()),
+            Some(SqliteTrans::Savepoint(savepoint)) => Ok(savepoint.deref()),
         }
     }
-    fn wrapped_connection_methods(&self) -> Result<&rusqlite::Connection> {
-        Ok(self.get()?.deref())
-    }
     fn already_consumed() -> Error {
         Error::Internal("transaction has already been consumed".to_string())
     }
 }
 impl ConnectionMethods for SqliteTransaction<'_> {
     fn execute(&self, sql: &str) -> Result<()> {
-        ConnectionMethods::execute(self.wrapped_connection_methods()?, sql)
+        self.wrapped_connection_methods()?.execute(sql)
     }
     fn query<'c>(
         &'c self,
@@ -474,26 +782,534 @@ fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
     fn has_table(&self, table: &str) -> Result<bool> {
         self.wrapped_connection_methods()?.has_table(table)
     }
+    fn introspect(&self) -> Result<ADB> {
+        self.wrapped_connection_methods()?.introspect()
+    }
+    fn cancel_handle(&self) -> Option<CancelHandle> {
+        Some(CancelHandle::sqlite(
+            self.conn().ok()?.get_interrupt_handle(),
+        ))
+    }
 }
 
 impl<'c> BackendTransaction<'c> for SqliteTransaction<'c> {
     fn commit(&mut self) -> Result<()> {
+        self.end_read_only()?;
         match self.trans.take() {
             None => Err(Self::already_consumed()),
-            Some(trans) => Ok(trans.commit()?),
+            Some(SqliteTrans::Transaction(trans)) => Ok(trans.commit()?),
+            Some(SqliteTrans::Savepoint(savepoint)) => Ok(savepoint.commit()?),
         }
     }
     fn rollback(&mut self) -> Result<()> {
+        self.end_read_only()?;
         match self.trans.take() {
             None => Err(Self::already_consumed()),
-            Some(trans) => Ok(trans.rollback()?),
+            Some(SqliteTrans::Transaction(trans)) => Ok(trans.rollback()?),
+            // Rolls back to the savepoint and releases it.
+            Some(SqliteTrans::Savepoint(savepoint)) => Ok(savepoint.finish()?),
         }
     }
+    fn savepoint<'s>(&'s mut self) -> Result<Transaction<'s>> {
+        let savepoint = match self.trans.as_mut() {
+            None => return Err(Self::already_consumed()),
+            Some(SqliteTrans::Transaction(trans)) => trans.savepoint()?,
+            Some(SqliteTrans::Savepoint(savepoint)) => savepoint.savepoint()?,
+        };
+        Ok(Transaction::new(Box::new(SqliteTransaction {
+            trans: Some(SqliteTrans::Savepoint(savepoint)),
+            log: self.log,
+            limits: self.limits,
+            read_only: false,
+        })))
+    }
+    fn prepare(&mut self, _gid: &str) -> Result<()> {
+        Err(Error::Unsupported("Two-phase commit", BACKEND_NAME))
+    }
     // Workaround for https://github.com/rust-lang/rfcs/issues/2765
     fn connection_methods(&self) -> &dyn ConnectionMethods {
         self
     }
 }
+impl Drop for SqliteTransaction<'_> {
+    fn drop(&mut self) {
+        // The transaction itself rolls back when dropped, but the
+        // pragma must be cleared while it is still open.
+        let _ = self.end_read_only();
+    }
+}
+
+/// Shared access to a SQLite database from many threads, without the
+/// `database is locked` errors of connections contending to write.
+///
+/// Every write, and every transaction which is not read-only, runs on
+/// a single writer connection, waiting its turn for it. Queries and
+/// read-only transactions run on a pool of read-only connections. The
+/// database is put in WAL mode, in which the readers and the writer
+/// do not block each other.
+///
+/// Each thread or task takes its own [`Connection`] from
+/// [`connection`][Self::connection], which is cheap; call
+/// [`Connection::into_async`] on it for an async one. The database
+/// must be a file, as each connection to `:memory:` would open a
+/// different database.
+#[derive(Clone, Debug)]
+pub struct SQLiteShared {
+    db: Arc<SharedDb>,
+}
+
+/// How many idle read-only connections a [`SQLiteShared`] keeps for
+/// reuse.
+const SHARED_IDLE_READERS: usize = 8;
+
+impl SQLiteShared {
+    /// Open the database at `path`, creating it if needed, and put it
+    /// in WAL mode.
+    pub fn open(path: &str) -> Result<Self> {
+        if path == ":memory:" {
+            return Err(Error::Unsupported(
+                "Sharing an in-memory database",
+                BACKEND_NAME,
+            ));
+        }
+        let writer = SQLiteConnection::open(path)?.conn;
+        writer.execute_batch("PRAGMA journal_mode = WAL; PRAGMA busy_timeout = 5000;")?;
+        // Open the WAL by reading now, in normal locking mode. Were it
+        // first opened in exclusive mode, as by the migration lock, it
+        // would stay exclusive to the writer.
+        writer.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))?;
+        Ok(SQLiteShared {
+            db: Arc::new(SharedDb {
+                path: path.to_string(),
+                writer: Mutex::new(writer),
+                readers: Mutex::new(Vec::new()),
+                exclusive: AtomicBool::new(false),
+                cache_capacity: Mutex::new(super::STATEMENT_CACHE_CAPACITY),
+            }),
+        })
+    }
+
+    /// A connection to the database, with its own query logger and
+    /// limits, which writes through the shared writer.
+    pub fn connection(&self) -> Connection {
+        let conn = Connection::new(Box::new(SharedConnection {
+            db: self.db.clone(),
+            log: QueryLog::default(),
+            limits: SharedLimits::default(),
+        }));
+        conn.with_db_attributes(trace::DbAttributes::new(
+            "sqlite",
+            Some(&self.db.path),
+            None,
+        ))
+    }
+}
+
+#[derive(Debug)]
+struct SharedDb {
+    path: String,
+    writer: Mutex<rusqlite::Connection>,
+    /// The idle read-only connections.
+    readers: Mutex<Vec<rusqlite::Connection>>,
+    /// Whether the writer is in exclusive locking mode, as while
+    /// migrating, which keeps the readers out of the database.
+    exclusive: AtomicBool,
+    cache_capacity: Mutex<usize>,
+}
+impl SharedDb {
+    fn writer(&self) -> MutexGuard<'_, rusqlite::Connection> {
+        self.writer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    /// Check out an idle read-only connection, or open another.
+    fn reader(&self) -> Result<Reader<'_>> {
+        let idle = self
+            .readers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => {
+                let conn = rusqlite::Connection::open_with_flags(
+                    &self.path,
+                    rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY
+                        | rusqlite::OpenFlags::SQLITE_OPEN_URI
+                        | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )?;
+                conn.execute_batch("PRAGMA busy_timeout = 5000;")?;
+                conn.set_prepared_statement_cache_capacity(self.cache_capacity());
+                conn
+            }
+        };
+        Ok(Reader {
+            db: self,
+            conn: Some(conn),
+        })
+    }
+    fn cache_capacity(&self) -> usize {
+        *self
+            .cache_capacity
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+    fn set_cache_capacity(&self, capacity: usize) {
+        *self
+            .cache_capacity
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = capacity;
+        self.writer()
+            .set_prepared_statement_cache_capacity(capacity);
+        for reader in self
+            .readers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            reader.set_prepared_statement_cache_capacity(capacity);
+        }
+    }
+}
+
+/// A read-only connection checked out of a [`SQLiteShared`], which is
+/// returned to it when dropped.
+#[derive(Debug)]
+struct Reader<'c> {
+    db: &'c SharedDb,
+    conn: Option<rusqlite::Connection>,
+}
+impl Deref for Reader<'_> {
+    type Target = rusqlite::Connection;
+    fn deref(&self) -> &rusqlite::Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+impl DerefMut for Reader<'_> {
+    fn deref_mut(&mut self) -> &mut rusqlite::Connection {
+        self.conn.as_mut().unwrap()
+    }
+}
+impl Drop for Reader<'_> {
+    fn drop(&mut self) {
+        let mut readers = self
+            .db
+            .readers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if readers.len() < SHARED_IDLE_READERS {
+            readers.extend(self.conn.take());
+        }
+    }
+}
+
+/// A connection of a [`SQLiteShared`].
+#[derive(Debug)]
+struct SharedConnection {
+    db: Arc<SharedDb>,
+    log: QueryLog,
+    limits: SharedLimits,
+}
+impl SharedConnection {
+    /// Run `f` on the writer, once no other connection is using it.
+    fn write<T>(&self, f: impl FnOnce(SqliteConn<'_>) -> Result<T>) -> Result<T> {
+        let conn = self.db.writer();
+        f(SqliteConn {
+            conn: &conn,
+            log: &self.log,
+            limits: &self.limits,
+        })
+    }
+    /// Run `f` on a read-only connection, or on the writer while it
+    /// keeps the readers out.
+    fn read<T>(&self, f: impl FnOnce(SqliteConn<'_>) -> Result<T>) -> Result<T> {
+        if self.db.exclusive.load(Ordering::Relaxed) {
+            return self.write(f);
+        }
+        let conn = self.db.reader()?;
+        f(SqliteConn {
+            conn: &conn,
+            log: &self.log,
+            limits: &self.limits,
+        })
+    }
+}
+
+impl ConnectionMethods for SharedConnection {
+    fn execute(&self, sql: &str) -> Result<()> {
+        self.write(|conn| {
+            let result = conn.execute(sql);
+            // The SQL may have changed the locking mode.
+            let mode: String = conn
+                .conn
+                .query_row("PRAGMA locking_mode", [], |row| row.get(0))?;
+            self.db
+                .exclusive
+                .store(mode.eq_ignore_ascii_case("exclusive"), Ordering::Relaxed);
+            result
+        })
+    }
+    fn query<'c>(
+        &'c self,
+        table: &str,
+        columns: &[Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[crate::query::Order]>,
+    ) -> Result<RawQueryResult<'c>> {
+        // The rows are read before the connection is returned.
+        self.read(|conn| {
+            let rows = conn.query(table, columns, expr, limit, offset, sort)?;
+            Ok(Box::new(vec_from_backend_rows(rows, columns)?) as RawQueryResult<'c>)
+        })
+    }
+    fn insert_returning_pk(
+        &self,
+        table: &str,
+        columns: &[Column],
+        pkcol: &Column,
+        values: &[SqlValRef<'_>],
+    ) -> Result<SqlVal> {
+        self.write(|conn| conn.insert_returning_pk(table, columns, pkcol, values))
+    }
+    fn insert_only(&self, table: &str, columns: &[Column], values: &[SqlValRef<'_>]) -> Result<()> {
+        self.write(|conn| conn.insert_only(table, columns, values))
+    }
+    fn insert_or_replace(
+        &self,
+        table: &str,
+        columns: &[Column],
+        pkcol: &Column,
+        values: &[SqlValRef<'_>],
+    ) -> Result<()> {
+        self.write(|conn| conn.insert_or_replace(table, columns, pkcol, values))
+    }
+    fn update(
+        &self,
+        table: &str,
+        pkcol: Column,
+        pk: SqlValRef<'_>,
+        columns: &[Column],
+        values: &[SqlValRef<'_>],
+    ) -> Result<()> {
+        self.write(|conn| conn.update(table, pkcol, pk, columns, values))
+    }
+    fn delete(&self, table: &str, pkcol: &'static str, pk: SqlVal) -> Result<()> {
+        self.write(|conn| conn.delete(table, pkcol, pk))
+    }
+    fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
+        self.write(|conn| conn.delete_where(table, expr))
+    }
+    fn has_table(&self, table: &str) -> Result<bool> {
+        self.read(|conn| conn.has_table(table))
+    }
+    fn introspect(&self) -> Result<ADB> {
+        self.read(|conn| conn.introspect())
+    }
+}
+
+impl BackendConnection for SharedConnection {
+    fn transaction_with(&mut self, options: TransactionOptions) -> Result<Transaction<'_>> {
+        // A read-only transaction reads a snapshot on a reader, while
+        // any other takes the write lock at once, as it holds the
+        // writer anyway.
+        let (conn, begin) = if options.read_only {
+            (Held::Reader(self.db.reader()?), "BEGIN DEFERRED")
+        } else {
+            (Held::Writer(self.db.writer()), "BEGIN IMMEDIATE")
+        };
+        conn.execute_batch(begin)?;
+        Ok(Transaction::new(Box::new(SharedTransaction {
+            conn: Some(conn),
+            log: &self.log,
+            limits: &self.limits,
+        })))
+    }
+    fn backend(&self) -> Box<dyn Backend> {
+        Box::new(SQLiteBackend {})
+    }
+    fn backend_name(&self) -> &'static str {
+        BACKEND_NAME
+    }
+    fn is_closed(&self) -> bool {
+        false
+    }
+    fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+    fn is_broken(&self) -> bool {
+        false
+    }
+    fn set_statement_cache_capacity(&self, capacity: usize) {
+        // The connections are shared, so this sets it for all of them.
+        self.db.set_cache_capacity(capacity)
+    }
+    fn set_query_logger(&self, logger: Option<QueryLogger>) {
+        self.log.set(logger)
+    }
+    fn set_slow_query_log(&self, slow: Option<SlowQueryLog>) {
+        self.log.set_slow(slow)
+    }
+    fn set_query_limits(&self, limits: QueryLimits) {
+        self.limits.set(limits)
+    }
+}
+
+/// The connection a transaction of a [`SQLiteShared`] holds until it
+/// ends.
+#[derive(Debug)]
+enum Held<'c> {
+    Writer(MutexGuard<'c, rusqlite::Connection>),
+    Reader(Reader<'c>),
+}
+impl Deref for Held<'_> {
+    type Target = rusqlite::Connection;
+    fn deref(&self) -> &rusqlite::Connection {
+        match self {
+            Held::Writer(conn) => conn,
+            Held::Reader(conn) => conn,
+        }
+    }
+}
+impl DerefMut for Held<'_> {
+    fn deref_mut(&mut self) -> &mut rusqlite::Connection {
+        match self {
+            Held::Writer(conn) => conn,
+            Held::Reader(conn) => conn,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SharedTransaction<'c> {
+    conn: Option<Held<'c>>,
+    log: &'c QueryLog,
+    limits: &'c SharedLimits,
+}
+impl SharedTransaction<'_> {
+    fn wrapped_connection_methods(&self) -> Result<SqliteConn<'_>> {
+        Ok(SqliteConn {
+            conn: self.conn.as_deref().ok_or_else(Self::already_consumed)?,
+            log: self.log,
+            limits: self.limits,
+        })
+    }
+    /// End the transaction with `sql`, rolling it back if that fails.
+    fn end(&mut self, sql: &str) -> Result<()> {
+        let conn = self.conn.take().ok_or_else(Self::already_consumed)?;
+        let result = conn.execute_batch(sql);
+        if result.is_err() && !conn.is_autocommit() {
+            let _ = conn.execute_batch("ROLLBACK");
+        }
+        Ok(result?)
+    }
+    fn already_consumed() -> Error {
+        Error::Internal("transaction has already been consumed".to_string())
+    }
+}
+impl ConnectionMethods for SharedTransaction<'_> {
+    fn execute(&self, sql: &str) -> Result<()> {
+        self.wrapped_connection_methods()?.execute(sql)
+    }
+    fn query<'c>(
+        &'c self,
+        table: &str,
+        columns: &[Column],
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[crate::query::Order]>,
+    ) -> Result<RawQueryResult<'c>> {
+        self.wrapped_connection_methods()?
+            .query(table, columns, expr, limit, offset, sort)
+    }
+    fn insert_returning_pk(
+        &self,
+        table: &str,
+        columns: &[Column],
+        pkcol: &Column,
+        values: &[SqlValRef<'_>],
+    ) -> Result<SqlVal> {
+        self.wrapped_connection_methods()?
+            .insert_returning_pk(table, columns, pkcol, values)
+    }
+    fn insert_only(&self, table: &str, columns: &[Column], values: &[SqlValRef<'_>]) -> Result<()> {
+        self.wrapped_connection_methods()?
+            .insert_only(table, columns, values)
+    }
+    fn insert_or_replace(
+        &self,
+        table: &str,
+        columns: &[Column],
+        pkcol: &Column,
+        values: &[SqlValRef<'_>],
+    ) -> Result<()> {
+        self.wrapped_connection_methods()?
+            .insert_or_replace(table, columns, pkcol, values)
+    }
+    fn update(
+        &self,
+        table: &str,
+        pkcol: Column,
+        pk: SqlValRef<'_>,
+        columns: &[Column],
+        values: &[SqlValRef<'_>],
+    ) -> Result<()> {
+        self.wrapped_connection_methods()?
+            .update(table, pkcol, pk, columns, values)
+    }
+    fn delete(&self, table: &str, pkcol: &'static str, pk: SqlVal) -> Result<()> {
+        self.wrapped_connection_methods()?.delete(table, pkcol, pk)
+    }
+    fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
+        self.wrapped_connection_methods()?.delete_where(table, expr)
+    }
+    fn has_table(&self, table: &str) -> Result<bool> {
+        self.wrapped_connection_methods()?.has_table(table)
+    }
+    fn introspect(&self) -> Result<ADB> {
+        self.wrapped_connection_methods()?.introspect()
+    }
+    fn cancel_handle(&self) -> Option<CancelHandle> {
+        Some(CancelHandle::sqlite(
+            self.conn.as_ref()?.get_interrupt_handle(),
+        ))
+    }
+}
+impl<'c> BackendTransaction<'c> for SharedTransaction<'c> {
+    fn commit(&mut self) -> Result<()> {
+        self.end("COMMIT")
+    }
+    fn rollback(&mut self) -> Result<()> {
+        self.end("ROLLBACK")
+    }
+    fn savepoint<'s>(&'s mut self) -> Result<Transaction<'s>> {
+        let conn = self
+            .conn
+            .as_deref_mut()
+            .ok_or_else(Self::already_consumed)?;
+        Ok(Transaction::new(Box::new(SqliteTransaction {
+            trans: Some(SqliteTrans::Savepoint(conn.savepoint()?)),
+            log: self.log,
+            limits: self.limits,
+            read_only: false,
+        })))
+    }
+    fn prepare(&mut self, _gid: &str) -> Result<()> {
+        Err(Error::Unsupported("Two-phase commit", BACKEND_NAME))
+    }
+    fn connection_methods(&self) -> &dyn ConnectionMethods {
+        self
+    }
+}
+impl Drop for SharedTransaction<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            if !conn.is_autocommit() {
+                let _ = conn.execute_batch("ROLLBACK");
+            }
+        }
+    }
+}
 
 impl rusqlite::ToSql for SqlVal {
     fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
@@ -514,6 +1330,8 @@ fn sqlvalref_to_sqlite<'a>(valref: &SqlValRef<'a>) -> rusqlite::types::ToSqlOutp
         Bool(b) => Owned(Value::Integer(*b as i64)),
         Int(i) => Owned(Value::Integer(*i as i64)),
         BigInt(i) => Owned(Value::Integer(*i)),
+        // SQLite integers have only 8 bytes.
+        HugeInt(i) => Owned(Value::Text(i.to_string())),
         Real(r) => Owned(Value::Real(*r)),
         Text(t) => Borrowed(ValueRef::Text(t.as_bytes())),
         Blob(b) => Borrowed(ValueRef::Blob(b)),
@@ -522,10 +1340,36 @@ fn sqlvalref_to_sqlite<'a>(valref: &SqlValRef<'a>) -> rusqlite::types::ToSqlOutp
             .map(rusqlite::types::ToSqlOutput::from)
             .unwrap(),
         #[cfg(feature = "datetime")]
+        // Truncated to the microseconds kept by PostgreSQL, so that
+        // both backends store the same value.
         Timestamp(dt) => {
-            let f = dt.format(SQLITE_DT_FORMAT);
+            let f = truncate_fraction(*dt, MAX_TIMESTAMP_PRECISION).format(SQLITE_DT_FORMAT);
+            Owned(Value::Text(f.to_string()))
+        }
+        #[cfg(feature = "datetime")]
+        TimestampTz(dt) => {
+            let f =
+                truncate_fraction(dt.to_utc(), MAX_TIMESTAMP_PRECISION).format(SQLITE_DT_TZ_FORMAT);
+            Owned(Value::Text(f.to_string()))
+        }
+        #[cfg(feature = "datetime")]
+        Date(d) => Owned(Value::Text(d.format(SQLITE_DATE_FORMAT).to_string())),
+        #[cfg(feature = "datetime")]
+        Time(t) => {
+            let f = truncate_fraction(*t, MAX_TIMESTAMP_PRECISION).format(SQLITE_TIME_FORMAT);
             Owned(Value::Text(f.to_string()))
         }
+        // As text, so that no digits are lost.
+        #[cfg(feature = "decimal")]
+        Decimal(d) => Owned(Value::Text(d.to_string())),
+        #[cfg(all(feature = "uuid", not(feature = "sqlite-uuid-text")))]
+        Uuid(u) => Owned(Value::Blob(u.as_bytes().to_vec())),
+        #[cfg(feature = "sqlite-uuid-text")]
+        Uuid(u) => Owned(Value::Text(u.to_string())),
+        // As a JSON array, which the json_each function can query.
+        Array(..) => Owned(Value::Text(
+            sqlval_to_json(valref).unwrap_or_default().to_string(),
+        )),
         Null => Owned(Value::Null),
         Custom(_) => panic!("Custom types not supported in sqlite"),
     }
@@ -534,22 +1378,26 @@ fn sqlvalref_to_sqlite<'a>(valref: &SqlValRef<'a>) -> rusqlite::types::ToSqlOutp
 #[pin_project]
 // Debug can not be derived because rusqlite::Rows doesn't implement it.
 struct QueryAdapterInner<'a> {
-    stmt: rusqlite::Statement<'a>,
     // will always be Some when the constructor has finished. We use an option only to get the
-    // stmt in place before we can reference it.
+    // stmt in place before we can reference it. Declared first so that it is dropped before
+    // the stmt it references, as it is when not all the rows were read.
     rows: Option<rusqlite::Rows<'a>>,
+    stmt: rusqlite::CachedStatement<'a>,
 }
 
 impl<'a> QueryAdapterInner<'a> {
-    fn new(stmt: rusqlite::Statement<'a>, params: impl rusqlite::Params) -> Result<Pin<Box<Self>>> {
-        let mut q = Box::pin(QueryAdapterInner { stmt, rows: None });
+    fn new(
+        stmt: rusqlite::CachedStatement<'a>,
+        params: impl rusqlite::Params,
+    ) -> Result<Pin<Box<Self>>> {
+        let mut q = Box::pin(QueryAdapterInner { rows: None, stmt });
         unsafe {
             //Soundness: we pin a QueryAdapterInner value containing
             //  both the stmt and the rows referencing the statement
             //  together. It is not possible to drop/move the stmt without
             //  bringing the referencing rows along with it.
             let q_ref = Pin::get_unchecked_mut(Pin::as_mut(&mut q));
-            let stmt_ref: *mut rusqlite::Statement<'a> = &mut q_ref.stmt;
+            let stmt_ref: *mut rusqlite::Statement<'a> = &mut *q_ref.stmt;
             q_ref.rows = Some((*stmt_ref).query(params)?)
         }
         Ok(q)
@@ -572,7 +1420,7 @@ struct QueryAdapter<'a> {
     inner: Pin<Box<QueryAdapterInner<'a>>>,
 }
 impl<'a> QueryAdapter<'a> {
-    fn new(stmt: rusqlite::Statement<'a>, params: impl rusqlite::Params) -> Result<Self> {
+    fn new(stmt: rusqlite::CachedStatement<'a>, params: impl rusqlite::Params) -> Result<Self> {
         Ok(QueryAdapter {
             inner: QueryAdapterInner::new(stmt, params)?,
         })
@@ -612,7 +1460,262 @@ fn sql_for_expr<W>(
 ) where
     W: Write,
 {
-    helper::sql_for_expr(expr, sql_for_expr, values, pls, w)
+    // Arrays are stored as JSON, whose elements are compared through
+    // json_each rather than by the array operators of the helper, and
+    // JSON and key-value maps are queried with the JSON functions
+    // rather than the JSONB and HSTORE operators.
+    match expr {
+        Expr::Condition(cond) => match *cond {
+            #[cfg(feature = "json")]
+            BoolExpr::Contains(col, Expr::Val(SqlVal::Json(v))) => {
+                let mut aliases = 0;
+                json_contains(
+                    &helper::quote_reserved_word(col),
+                    "$",
+                    &v,
+                    &mut aliases,
+                    values,
+                    w,
+                );
+            }
+            #[cfg(feature = "json")]
+            BoolExpr::PathEq(col, path, ex) => {
+                let col = helper::quote_reserved_word(col);
+                let path = json_path(&path);
+                match ex {
+                    Expr::Val(SqlVal::Json(v)) if !v.is_object() && !v.is_array() => {
+                        values.push(SqlVal::Text(path.clone()));
+                        values.push(SqlVal::Text(path));
+                        json_scalar_eq(
+                            &format!("json_extract({col}, ?)"),
+                            &format!("json_type({col}, ?)"),
+                            &v,
+                            values,
+                            w,
+                        );
+                    }
+                    ex => {
+                        values.push(SqlVal::Text(path));
+                        write!(w, "json_extract({col}, ?) = json(").unwrap();
+                        sql_for_expr(ex, values, pls, w);
+                        write!(w, ")").unwrap();
+                    }
+                }
+            }
+            #[cfg(feature = "json")]
+            BoolExpr::KeyEq(col, key, ex) => {
+                values.push(SqlVal::Text(format!("$.\"{key}\"")));
+                write!(
+                    w,
+                    "json_extract({}, ?) = ",
+                    helper::quote_reserved_word(col)
+                )
+                .unwrap();
+                sql_for_expr(ex, values, pls, w);
+            }
+            #[cfg(feature = "decimal")]
+            BoolExpr::Lt(col, ex @ Expr::Val(SqlVal::Decimal(_))) => {
+                decimal_cmp(col, "<", ex, values, pls, w)
+            }
+            #[cfg(feature = "decimal")]
+            BoolExpr::Gt(col, ex @ Expr::Val(SqlVal::Decimal(_))) => {
+                decimal_cmp(col, ">", ex, values, pls, w)
+            }
+            #[cfg(feature = "decimal")]
+            BoolExpr::Le(col, ex @ Expr::Val(SqlVal::Decimal(_))) => {
+                decimal_cmp(col, "<=", ex, values, pls, w)
+            }
+            #[cfg(feature = "decimal")]
+            BoolExpr::Ge(col, ex @ Expr::Val(SqlVal::Decimal(_))) => {
+                decimal_cmp(col, ">=", ex, values, pls, w)
+            }
+            // Dates and times are stored as text, whose parts are
+            // formatted by strftime.
+            BoolExpr::DatePart {
+                col,
+                part,
+                op,
+                expr,
+            } => {
+                let format = match part {
+                    DatePart::Year => "%Y",
+                    DatePart::Month => "%m",
+                    DatePart::Day => "%d",
+                    DatePart::Hour => "%H",
+                    DatePart::Minute => "%M",
+                    DatePart::Second => "%S",
+                };
+                write!(
+                    w,
+                    "CAST(strftime('{format}', {}) AS INTEGER) {} ",
+                    helper::quote_reserved_word(col),
+                    op.sql()
+                )
+                .unwrap();
+                sql_for_expr(expr, values, pls, w);
+            }
+            BoolExpr::Contains(col, ex) => {
+                write!(w, "NOT EXISTS (SELECT 1 FROM json_each(",).unwrap();
+                sql_for_expr(ex, values, pls, w);
+                write!(
+                    w,
+                    ") WHERE value NOT IN (SELECT value FROM json_each({})))",
+                    helper::quote_reserved_word(col)
+                )
+                .unwrap();
+            }
+            BoolExpr::Overlaps(col, ex) => {
+                write!(
+                    w,
+                    "EXISTS (SELECT 1 FROM json_each({}) WHERE value IN (SELECT value FROM json_each(",
+                    helper::quote_reserved_word(col)
+                )
+                .unwrap();
+                sql_for_expr(ex, values, pls, w);
+                write!(w, ")))").unwrap();
+            }
+            cond => helper::sql_for_expr(
+                Expr::Condition(Box::new(cond)),
+                sql_for_expr,
+                values,
+                pls,
+                w,
+            ),
+        },
+        expr => helper::sql_for_expr(expr, sql_for_expr, values, pls, w),
+    }
+}
+
+/// Write the comparison `col op ex` of a decimal column, which is
+/// stored as text and so must be compared as a number.
+#[cfg(feature = "decimal")]
+fn decimal_cmp(
+    col: &str,
+    op: &str,
+    ex: Expr,
+    values: &mut Vec<SqlVal>,
+    pls: &mut SQLitePlaceholderSource,
+    w: &mut impl Write,
+) {
+    write!(
+        w,
+        "CAST({} AS REAL) {op} CAST(",
+        helper::quote_reserved_word(col)
+    )
+    .unwrap();
+    sql_for_expr(ex, values, pls, w);
+    write!(w, " AS REAL)").unwrap();
+}
+
+/// The SQLite JSON path of `path`, whose segments of only digits are
+/// array indices and whose other segments are object keys.
+#[cfg(feature = "json")]
+fn json_path(path: &[String]) -> String {
+    let mut json_path = "$".to_string();
+    for segment in path {
+        if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+            write!(json_path, "[{segment}]").unwrap();
+        } else {
+            write!(json_path, ".\"{segment}\"").unwrap();
+        }
+    }
+    json_path
+}
+
+/// Write the condition that the JSON in `doc` contains `v` at `path`,
+/// as the `@>` operator of PostgreSQL's `JSONB` does. An object or
+/// array in an array must equal an element rather than be contained
+/// in one.
+#[cfg(feature = "json")]
+fn json_contains(
+    doc: &str,
+    path: &str,
+    v: &serde_json::Value,
+    aliases: &mut usize,
+    values: &mut Vec<SqlVal>,
+    w: &mut impl Write,
+) {
+    use serde_json::Value;
+    match v {
+        Value::Object(m) if !m.is_empty() => {
+            for (i, (key, item)) in m.iter().enumerate() {
+                if i > 0 {
+                    write!(w, " AND ").unwrap();
+                }
+                json_contains(doc, &format!("{path}.\"{key}\""), item, aliases, values, w);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(w, " AND ").unwrap();
+                }
+                *aliases += 1;
+                let alias = format!("butane_json_{aliases}");
+                values.push(SqlVal::Text(path.to_string()));
+                write!(
+                    w,
+                    "EXISTS (SELECT 1 FROM json_each({doc}, ?) {alias} WHERE "
+                )
+                .unwrap();
+                if item.is_object() || item.is_array() {
+                    values.push(SqlVal::Text(item.to_string()));
+                    write!(w, "{alias}.value = json(?)").unwrap();
+                } else {
+                    json_scalar_eq(
+                        &format!("{alias}.value"),
+                        &format!("{alias}.type"),
+                        item,
+                        values,
+                        w,
+                    );
+                }
+                write!(w, ")").unwrap();
+            }
+        }
+        Value::Object(_) | Value::Array(_) => {
+            values.push(SqlVal::Text(path.to_string()));
+            let ty = if v.is_object() { "object" } else { "array" };
+            write!(w, "json_type({doc}, ?) = '{ty}'").unwrap();
+        }
+        _ => {
+            values.push(SqlVal::Text(path.to_string()));
+            values.push(SqlVal::Text(path.to_string()));
+            json_scalar_eq(
+                &format!("json_extract({doc}, ?)"),
+                &format!("json_type({doc}, ?)"),
+                v,
+                values,
+                w,
+            );
+        }
+    }
+}
+
+/// Write the condition that the JSON value whose SQL value is `value`
+/// and whose JSON type is `ty` is the scalar `v`. The placeholders of
+/// `value` and `ty` must already be in `values`.
+#[cfg(feature = "json")]
+fn json_scalar_eq(
+    value: &str,
+    ty: &str,
+    v: &serde_json::Value,
+    values: &mut Vec<SqlVal>,
+    w: &mut impl Write,
+) {
+    use serde_json::Value;
+    match v {
+        Value::Null => write!(w, "{ty} = 'null'"),
+        Value::Bool(b) => write!(w, "{ty} = '{b}'"),
+        // Written out, as a number cannot inject SQL.
+        Value::Number(n) => write!(w, "{ty} IN ('integer', 'real') AND {value} = {n}"),
+        Value::String(s) => {
+            values.push(SqlVal::Text(s.clone()));
+            write!(w, "{ty} = 'text' AND {value} = ?")
+        }
+        Value::Object(_) | Value::Array(_) => unreachable!("not a scalar"),
+    }
+    .unwrap()
 }
 
 fn sql_val_from_rusqlite(val: rusqlite::types::ValueRef, col: &Column) -> Result<SqlVal> {
@@ -628,18 +1731,79 @@ fn sql_valref_from_rusqlite<'a>(
     }
     Ok(match ty {
         SqlType::Bool => SqlValRef::Bool(val.as_i64()? != 0),
-        SqlType::Int => SqlValRef::Int(val.as_i64()? as i32),
+        SqlType::Int => {
+            SqlValRef::Int(i32::try_from(val.as_i64()?).map_err(|_| Error::OutOfRange)?)
+        }
         SqlType::BigInt => SqlValRef::BigInt(val.as_i64()?),
+        SqlType::HugeInt => SqlValRef::HugeInt(match val {
+            // A value written other than by butane may be an integer.
+            rusqlite::types::ValueRef::Integer(i) => i.into(),
+            _ => val.as_str()?.parse().map_err(|_| Error::OutOfRange)?,
+        }),
         SqlType::Real => SqlValRef::Real(val.as_f64()?),
-        SqlType::Text => SqlValRef::Text(val.as_str()?),
+        SqlType::Text | SqlType::CiText => SqlValRef::Text(val.as_str()?),
         #[cfg(feature = "json")]
-        SqlType::Json => SqlValRef::Json(serde_json::from_str(val.as_str()?)?),
+        SqlType::Json | SqlType::KeyValue => SqlValRef::Json(serde_json::from_str(val.as_str()?)?),
+        #[cfg(feature = "datetime")]
+        SqlType::Timestamp => {
+            let text = val.as_str()?;
+            match NaiveDateTime::parse_from_str(text, SQLITE_DT_FORMAT) {
+                Ok(dt) => SqlValRef::Timestamp(dt),
+                // Written by a field with a time zone.
+                Err(_) if DateTime::parse_from_rfc3339(text).is_ok() => {
+                    return Err(Error::TimezoneMismatch("without", text.to_string()))
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
         #[cfg(feature = "datetime")]
-        SqlType::Timestamp => SqlValRef::Timestamp(NaiveDateTime::parse_from_str(
+        SqlType::TimestampTz => {
+            let text = val.as_str()?;
+            match DateTime::parse_from_rfc3339(text) {
+                Ok(dt) => SqlValRef::TimestampTz(dt),
+                // Written by a field without a time zone.
+                Err(_) if NaiveDateTime::parse_from_str(text, SQLITE_DT_FORMAT).is_ok() => {
+                    return Err(Error::TimezoneMismatch("with", text.to_string()))
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        #[cfg(feature = "datetime")]
+        SqlType::Date => SqlValRef::Date(NaiveDate::parse_from_str(
+            val.as_str()?,
+            SQLITE_DATE_FORMAT,
+        )?),
+        #[cfg(feature = "datetime")]
+        SqlType::Time => SqlValRef::Time(NaiveTime::parse_from_str(
             val.as_str()?,
-            SQLITE_DT_FORMAT,
+            SQLITE_TIME_FORMAT,
         )?),
+        #[cfg(feature = "decimal")]
+        SqlType::Numeric(_) => SqlValRef::Decimal(match val {
+            // A value written other than by butane may be a number.
+            rusqlite::types::ValueRef::Integer(i) => i.into(),
+            rusqlite::types::ValueRef::Real(r) => rust_decimal::Decimal::try_from(r)?,
+            _ => val.as_str()?.parse()?,
+        }),
         SqlType::Blob => SqlValRef::Blob(val.as_blob()?),
+        // Either form, whichever the `sqlite-uuid-text` feature writes.
+        #[cfg(feature = "uuid")]
+        SqlType::Uuid => SqlValRef::Uuid(match val {
+            rusqlite::types::ValueRef::Blob(b) => uuid::Uuid::from_slice(b)?,
+            _ => uuid::Uuid::parse_str(val.as_str()?)?,
+        }),
+        SqlType::Array(elem) => {
+            let text = val.as_str()?;
+            match sqlval_from_json(&serde_json::from_str(text)?, ty) {
+                Some(SqlVal::Array(_, items)) => SqlValRef::Array(elem, items),
+                _ => {
+                    return Err(Error::CannotConvertSqlVal(
+                        ty.clone(),
+                        SqlVal::Text(text.to_string()),
+                    ))
+                }
+            }
+        }
         SqlType::Custom(v) => return Err(Error::IncompatibleCustomT(v.clone(), BACKEND_NAME)),
     })
 }
@@ -650,10 +1814,51 @@ fn sql_for_op(current: &mut ADB, op: &Operation) -> Result<String> {
         Operation::AddTableConstraints(_table) => Ok("".to_owned()),
         Operation::AddTableIfNotExists(table) => Ok(create_table(table, true)),
         Operation::RemoveTable(name) => Ok(drop_table(name)),
+        Operation::RenameTable(from, to) => Ok(rename_table(from, to)),
         Operation::RemoveTableConstraints(_table) => Ok("".to_owned()),
-        Operation::AddColumn(tbl, col) => add_column(tbl, col),
+        Operation::AddColumn(tbl, col) => add_column(current, tbl, col),
         Operation::RemoveColumn(tbl, name) => remove_column(current, tbl, name),
-        Operation::ChangeColumn(tbl, old, new) => Ok(change_column(current, tbl, old, Some(new))),
+        Operation::ChangeColumn(tbl, old, new) => {
+            // Timestamps are text, whose declared precision needs no
+            // change to the table.
+            let mut same_precision = new.clone();
+            same_precision.set_precision(old.precision());
+            if &same_precision == old {
+                current.transform_with(op.clone());
+                return Ok(String::new());
+            }
+            if old.name() == new.name() {
+                return change_column(current, tbl, old, Some(new));
+            }
+            let mut renamed = old.clone();
+            renamed.set_name(new.name());
+            renamed.set_renamed_from(new.renamed_from().map(str::to_string));
+            let mut stmts = vec![rename_column(current, tbl, old, &renamed)];
+            if &renamed != new {
+                stmts.push(change_column(current, tbl, &renamed, Some(new))?);
+            }
+            Ok(stmts.join("\n"))
+        }
+        // SQLite has no index methods
+        Operation::AddIndex(tbl, index) => Ok(helper::create_index(tbl, index, false, false)),
+        Operation::RemoveIndex(_tbl, name) => Ok(helper::drop_index(name, false)),
+        Operation::AddConstraint(tbl, constraint) => {
+            change_constraint(current, tbl, constraint.name(), Some(constraint))
+        }
+        Operation::RemoveConstraint(tbl, name) => change_constraint(current, tbl, name, None),
+        Operation::ChangeSqliteOptions(tbl, options) => {
+            let mut table = current
+                .get_table(tbl)
+                .ok_or_else(|| Error::TableNotFound(tbl.clone()))?
+                .clone();
+            table.set_sqlite_options(*options);
+            rebuild_table(current, table)
+        }
+        Operation::AddCustomType(_)
+        | Operation::RemoveCustomType(_)
+        | Operation::ChangeCustomType(..) => {
+            Err(Error::Unsupported("User-defined types", BACKEND_NAME))
+        }
     }
 }
 
@@ -669,12 +1874,21 @@ fn create_table(table: &ATable, allow_exists: bool) -> String {
     if !constraints.is_empty() {
         constraints = ",\n".to_owned() + &constraints;
     }
+    let options = table.sqlite_options();
+    let mut table_options = Vec::new();
+    if options.strict {
+        table_options.push(" STRICT");
+    }
+    if options.without_rowid {
+        table_options.push(" WITHOUT ROWID");
+    }
     format!(
-        "CREATE TABLE {}{} (\n{}{}\n) STRICT;",
+        "CREATE TABLE {}{} (\n{}{}\n){};",
         modifier,
         helper::quote_reserved_word(&table.name),
         coldefs,
-        constraints
+        constraints,
+        table_options.join(",")
     )
 }
 
@@ -684,6 +1898,7 @@ fn create_table_constraints(table: &ATable) -> String {
         .iter()
         .filter(|column| column.reference().is_some())
         .map(define_constraint)
+        .chain(table.constraints.iter().map(helper::define_constraint))
         .collect::<Vec<String>>()
         .join("\n")
 }
@@ -728,10 +1943,15 @@ fn define_constraint(column: &AColumn) -> String {
     match reference {
         ARef::Literal(literal) => {
             format!(
-                "FOREIGN KEY ({}) REFERENCES {}({})",
+                "FOREIGN KEY ({}) REFERENCES {}({}){}",
                 helper::quote_reserved_word(column.name()),
                 helper::quote_reserved_word(literal.table_name()),
                 helper::quote_reserved_word(literal.column_name()),
+                if column.is_deferrable() {
+                    " DEFERRABLE INITIALLY DEFERRED"
+                } else {
+                    ""
+                }
             )
         }
         _ => panic!(),
@@ -740,6 +1960,8 @@ fn define_constraint(column: &AColumn) -> String {
 
 fn col_sqltype(col: &AColumn) -> Cow<str> {
     match col.typeid() {
+        // The collation is not part of the declared type, which is TEXT.
+        Ok(TypeIdentifier::Ty(SqlType::CiText)) => Cow::Borrowed("TEXT COLLATE NOCASE"),
         Ok(TypeIdentifier::Ty(ty)) => Cow::Borrowed(sqltype(&ty)),
         Ok(TypeIdentifier::Name(name)) => Cow::Owned(name),
         // sqlite doesn't actually require that the column type be
@@ -753,22 +1975,69 @@ fn sqltype(ty: &SqlType) -> &'static str {
         SqlType::Bool => "INTEGER",
         SqlType::Int => "INTEGER",
         SqlType::BigInt => "INTEGER",
+        SqlType::HugeInt => "TEXT",
         SqlType::Real => "REAL",
         SqlType::Text => "TEXT",
+        SqlType::CiText => "TEXT",
         SqlType::Blob => "BLOB",
         #[cfg(feature = "json")]
         SqlType::Json => "TEXT",
+        #[cfg(feature = "json")]
+        SqlType::KeyValue => "TEXT",
+        SqlType::Array(_) => "TEXT",
         #[cfg(feature = "datetime")]
         SqlType::Timestamp => "TEXT",
+        #[cfg(feature = "datetime")]
+        SqlType::TimestampTz => "TEXT",
+        #[cfg(feature = "datetime")]
+        SqlType::Date => "TEXT",
+        #[cfg(feature = "datetime")]
+        SqlType::Time => "TEXT",
+        #[cfg(feature = "decimal")]
+        SqlType::Numeric(_) => "TEXT",
+        #[cfg(all(feature = "uuid", not(feature = "sqlite-uuid-text")))]
+        SqlType::Uuid => "BLOB",
+        #[cfg(feature = "sqlite-uuid-text")]
+        SqlType::Uuid => "TEXT",
         SqlType::Custom(_) => panic!("Custom types not supported by sqlite backend"),
     }
 }
 
+/// The type of a column with the given declared SQL type.
+fn typeid_for_sqltype_name(name: &str) -> TypeIdentifier {
+    match name.to_uppercase().as_str() {
+        "INTEGER" => TypeIdentifier::Ty(SqlType::BigInt),
+        "REAL" => TypeIdentifier::Ty(SqlType::Real),
+        "TEXT" => TypeIdentifier::Ty(SqlType::Text),
+        "BLOB" => TypeIdentifier::Ty(SqlType::Blob),
+        _ => TypeIdentifier::Name(name.to_string()),
+    }
+}
+
 fn drop_table(name: &str) -> String {
     format!("DROP TABLE {};", helper::quote_reserved_word(name))
 }
 
-fn add_column(tbl_name: &str, col: &AColumn) -> Result<String> {
+fn rename_table(from: &str, to: &str) -> String {
+    // Foreign keys of other tables are updated to the new name
+    format!(
+        "ALTER TABLE {} RENAME TO {};",
+        helper::quote_reserved_word(from),
+        helper::quote_reserved_word(to)
+    )
+}
+
+fn add_column(current: &mut ADB, tbl_name: &str, col: &AColumn) -> Result<String> {
+    // "ALTER TABLE ADD COLUMN" cannot add a primary key or unique
+    // column, nor a foreign key constraint, and its default cannot be
+    // an expression of the other columns.
+    if col.is_pk() || col.unique() || col.reference().is_some() || col.backfill().is_some() {
+        if let Some(table) = current.get_table(tbl_name) {
+            let mut new_table = table.clone();
+            new_table.add_column(col.clone());
+            return rebuild_table(current, new_table);
+        }
+    }
     let default: SqlVal = helper::column_default(col)?;
     Ok(format!(
         "ALTER TABLE {} ADD COLUMN {} DEFAULT {};",
@@ -786,10 +2055,18 @@ fn remove_column(current: &mut ADB, tbl_name: &str, name: &str) -> Result<String
     let col = table
         .column(name)
         .ok_or_else(|| Error::ColumnNotFound(tbl_name.to_string(), name.to_string()))?;
-    // "ALTER TABLE b DROP COLUMN fkey;" fails due to sqlite not being
-    // able to remove the attached constraint.
-    if col.reference().is_some() {
-        Ok(change_column(current, tbl_name, col, None))
+    // "ALTER TABLE DROP COLUMN" fails for a column which is a primary
+    // key, unique, indexed or has a foreign key constraint.
+    let indexed = table
+        .indexes
+        .iter()
+        .any(|index| index.columns().iter().any(|c| c == name));
+    let constrained = table
+        .constraints
+        .iter()
+        .any(|constraint| constraint.columns().iter().any(|c| c == name));
+    if col.reference().is_some() || col.is_pk() || col.unique() || indexed || constrained {
+        change_column(current, tbl_name, col, None)
     } else {
         Ok(format!(
             "ALTER TABLE {} DROP COLUMN {};",
@@ -799,18 +2076,71 @@ fn remove_column(current: &mut ADB, tbl_name: &str, name: &str) -> Result<String
     }
 }
 
-fn copy_table(old: &ATable, new: &ATable) -> String {
+fn rename_column(current: &mut ADB, tbl_name: &str, old: &AColumn, new: &AColumn) -> String {
+    current.transform_with(Operation::ChangeColumn(
+        tbl_name.to_string(),
+        old.clone(),
+        new.clone(),
+    ));
+    format!(
+        "ALTER TABLE {} RENAME COLUMN {} TO {};",
+        helper::quote_reserved_word(tbl_name),
+        helper::quote_reserved_word(old.name()),
+        helper::quote_reserved_word(new.name()),
+    )
+}
+
+/// Copy the rows of table `from`, with the columns of `old`, into
+/// `new`, filling in columns which only `new` has with their backfill
+/// expression or their default.
+/// Columns whose type changed are converted with their conversion
+/// expression if they have one, and otherwise by SQLite, which fails
+/// rather than lose data.
+fn copy_table(from: &str, old: &ATable, new: &ATable) -> Result<String> {
     let column_names = new
         .columns
         .iter()
         .map(|col| helper::quote_reserved_word(col.name()))
         .collect::<Vec<Cow<str>>>()
         .join(", ");
-    format!(
-        "INSERT INTO {} SELECT {} FROM {};",
+    let values = new
+        .columns
+        .iter()
+        .map(|col| match (old.column(col.name()), col.convert_using()) {
+            (Some(old_col), Some(expr)) if old_col.typeid()? != col.typeid()? => {
+                Ok(expr.to_string())
+            }
+            #[cfg(feature = "sqlite-uuid-text")]
+            (Some(old_col), None)
+                if old_col.typeid()? == TypeIdentifier::Ty(SqlType::Blob)
+                    && col.typeid()? == TypeIdentifier::Ty(SqlType::Uuid) =>
+            {
+                Ok(uuid_text_from_blob(col.name()))
+            }
+            (Some(_), _) => Ok(helper::quote_reserved_word(col.name()).into_owned()),
+            (None, _) => match col.backfill() {
+                Some(expr) => Ok(expr.to_string()),
+                None => helper::sql_literal_value(&helper::column_default(col)?),
+            },
+        })
+        .collect::<Result<Vec<String>>>()?
+        .join(", ");
+    Ok(format!(
+        "INSERT INTO {} ({}) SELECT {} FROM {};",
         helper::quote_reserved_word(&new.name),
         column_names,
-        helper::quote_reserved_word(&old.name)
+        values,
+        helper::quote_reserved_word(from)
+    ))
+}
+
+/// The expression giving the canonical text of the UUID held as a
+/// blob in column `name`.
+#[cfg(feature = "sqlite-uuid-text")]
+fn uuid_text_from_blob(name: &str) -> String {
+    let hex = format!("hex({})", helper::quote_reserved_word(name));
+    format!(
+        "lower(substr({hex}, 1, 8) || '-' || substr({hex}, 9, 4) || '-' || substr({hex}, 13, 4) || '-' || substr({hex}, 17, 4) || '-' || substr({hex}, 21))"
     )
 }
 
@@ -823,7 +2153,7 @@ fn change_column(
     tbl_name: &str,
     old: &AColumn,
     new: Option<&AColumn>,
-) -> String {
+) -> Result<String> {
     let table = current.get_table(tbl_name);
     if table.is_none() {
         crate::warn!(
@@ -831,29 +2161,80 @@ fn change_column(
             &old.name(),
             tbl_name
         );
-        return "".to_string();
+        return Ok("".to_string());
     }
-    let old_table = table.unwrap();
-    let mut new_table = old_table.clone();
-    new_table.name = tmp_table_name(&new_table.name);
+    let mut new_table = table.unwrap().clone();
     match new {
         Some(col) => new_table.replace_column(col.clone()),
-        None => new_table.remove_column(old.name()),
-    }
-    let stmts: [&str; 4] = [
-        &create_table(&new_table, false),
-        &copy_table(old_table, &new_table),
-        &drop_table(&old_table.name),
-        &format!(
-            "ALTER TABLE {} RENAME TO {};",
-            helper::quote_reserved_word(&new_table.name),
-            helper::quote_reserved_word(tbl_name)
+        None => {
+            new_table.remove_column(old.name());
+            new_table
+                .indexes
+                .retain(|index| !index.columns().iter().any(|c| c == old.name()));
+            new_table
+                .constraints
+                .retain(|constraint| !constraint.columns().iter().any(|c| c == old.name()));
+        }
+    }
+    rebuild_table(current, new_table)
+}
+
+/// Add `constraint` named `name` to table `tbl_name`, or remove the
+/// constraint if `constraint` is `None`, by rebuilding the table, as
+/// "ALTER TABLE" cannot change constraints in SQLite.
+fn change_constraint(
+    current: &mut ADB,
+    tbl_name: &str,
+    name: &str,
+    constraint: Option<&AConstraint>,
+) -> Result<String> {
+    let table = current
+        .get_table(tbl_name)
+        .ok_or_else(|| Error::TableNotFound(tbl_name.to_string()))?;
+    // A table created in the same migration already has its constraints
+    if table.constraint(name) == constraint {
+        return Ok(String::new());
+    }
+    let mut new_table = table.clone();
+    match constraint {
+        Some(constraint) => new_table.add_constraint(constraint.clone()),
+        None => new_table.remove_constraint(name),
+    }
+    rebuild_table(current, new_table)
+}
+
+/// Change a table to `new_table` by copying its rows aside, dropping
+/// and recreating it, and copying the rows back, for changes which
+/// "ALTER TABLE" cannot make in SQLite. The rows are copied back rather
+/// than renaming a new table into place so that rows referencing the
+/// table are checked against them when the migration commits.
+fn rebuild_table(current: &mut ADB, new_table: ATable) -> Result<String> {
+    let tbl_name = new_table.name.clone();
+    let old_table = current
+        .get_table(&tbl_name)
+        .ok_or_else(|| Error::TableNotFound(tbl_name.clone()))?;
+    let tmp_name = tmp_table_name(&tbl_name);
+    let mut stmts: Vec<String> = vec![
+        "PRAGMA defer_foreign_keys = ON;".to_string(),
+        format!(
+            "CREATE TABLE {} AS SELECT * FROM {};",
+            helper::quote_reserved_word(&tmp_name),
+            helper::quote_reserved_word(&tbl_name)
         ),
+        drop_table(&tbl_name),
+        create_table(&new_table, false),
+        copy_table(&tmp_name, old_table, &new_table)?,
+        drop_table(&tmp_name),
     ];
-    let result = stmts.join("\n");
-    new_table.name.clone_from(&old_table.name);
+    // Dropping the old table dropped its indexes
+    stmts.extend(
+        new_table
+            .indexes
+            .iter()
+            .map(|index| helper::create_index(&tbl_name, index, false, false)),
+    );
     current.replace_table(new_table);
-    result
+    Ok(stmts.join("\n"))
 }
 
 pub fn sql_insert_or_update(table: &str, columns: &[Column], pkcol: &Column, w: &mut impl Write) {
@@ -6,7 +6,7 @@
 use crate::db::{
     Backend, BackendConnection, BackendConnectionAsync, BackendTransaction,
     BackendTransactionAsync, Connection, ConnectionAsync, ConnectionMethods, RawQueryResult,
-    Transaction, TransactionAsync,
+    Transaction, TransactionAsync, TransactionOptions,
 };
 use crate::migrations::adb;
 use crate::query::{BoolExpr, Order};
@@ -138,19 +138,26 @@ fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
     fn has_table(&self, table: &str) -> Result<bool> {
         self.block_on(self.inner.has_table(table))
     }
+    fn introspect(&self) -> Result<crate::migrations::adb::ADB> {
+        self.block_on(self.inner.introspect())
+    }
+    fn cancel_handle(&self) -> Option<crate::db::CancelHandle> {
+        self.inner.cancel_handle()
+    }
 }
 
 impl<T> BackendConnection for SyncAdapter<T>
 where
     T: BackendConnectionAsync,
 {
-    fn transaction(&mut self) -> Result<Transaction<'_>> {
+    fn transaction_with(&mut self, options: TransactionOptions) -> Result<Transaction<'_>> {
         // We can't use chain because of the lifetimes and mutable borrows below,
         // so set up these runtime clones now.
         let runtime_handle = self.runtime_handle.clone();
         let runtime = self._runtime.as_ref().cloned();
-        let transaction: TransactionAsync =
-            self.runtime_handle.block_on(self.inner.transaction())?;
+        let transaction: TransactionAsync = self
+            .runtime_handle
+            .block_on(self.inner.transaction_with(options))?;
         let transaction_adapter = SyncAdapter {
             runtime_handle,
             _runtime: runtime,
@@ -158,6 +165,14 @@ fn transaction(&mut self) -> Result<Transaction<'_>> {
         };
         Ok(Transaction::new(Box::new(transaction_adapter)))
     }
+    fn commit_prepared(&mut self, gid: &str) -> Result<()> {
+        self.runtime_handle
+            .block_on(self.inner.commit_prepared(gid))
+    }
+    fn rollback_prepared(&mut self, gid: &str) -> Result<()> {
+        self.runtime_handle
+            .block_on(self.inner.rollback_prepared(gid))
+    }
     fn backend(&self) -> Box<dyn crate::db::Backend> {
         self.inner.backend()
     }
@@ -167,6 +182,24 @@ fn backend_name(&self) -> &'static str {
     fn is_closed(&self) -> bool {
         self.inner.is_closed()
     }
+    fn ping(&self) -> Result<()> {
+        self.runtime_handle.block_on(self.inner.ping())
+    }
+    fn is_broken(&self) -> bool {
+        self.inner.is_broken()
+    }
+    fn set_statement_cache_capacity(&self, capacity: usize) {
+        self.inner.set_statement_cache_capacity(capacity)
+    }
+    fn set_query_logger(&self, logger: Option<crate::db::QueryLogger>) {
+        self.inner.set_query_logger(logger)
+    }
+    fn set_slow_query_log(&self, slow: Option<crate::db::SlowQueryLog>) {
+        self.inner.set_slow_query_log(slow)
+    }
+    fn set_query_limits(&self, limits: crate::db::QueryLimits) {
+        self.inner.set_query_limits(limits)
+    }
 }
 
 impl<T> SyncAdapter<T>
@@ -188,6 +221,20 @@ fn commit(&mut self) -> Result<()> {
     fn rollback(&mut self) -> Result<()> {
         self.runtime_handle.block_on(self.inner.rollback())
     }
+    fn savepoint<'s>(&'s mut self) -> Result<Transaction<'s>> {
+        let runtime_handle = self.runtime_handle.clone();
+        let runtime = self._runtime.as_ref().cloned();
+        let savepoint: TransactionAsync = self.runtime_handle.block_on(self.inner.savepoint())?;
+        let savepoint_adapter = SyncAdapter {
+            runtime_handle,
+            _runtime: runtime,
+            inner: savepoint.trans,
+        };
+        Ok(Transaction::new(Box::new(savepoint_adapter)))
+    }
+    fn prepare(&mut self, gid: &str) -> Result<()> {
+        self.runtime_handle.block_on(self.inner.prepare(gid))
+    }
     fn connection_methods(&self) -> &dyn ConnectionMethods {
         self
     }
@@ -207,12 +254,23 @@ fn row_id_column(&self) -> Option<&'static str> {
     fn create_migration_sql(&self, current: &adb::ADB, ops: Vec<adb::Operation>) -> Result<String> {
         self.inner.create_migration_sql(current, ops)
     }
+    fn create_online_migration_sql(
+        &self,
+        current: &adb::ADB,
+        ops: Vec<adb::Operation>,
+    ) -> Result<String> {
+        self.inner.create_online_migration_sql(current, ops)
+    }
+    fn introspected_column(&self, col: &adb::AColumn) -> adb::AColumn {
+        self.inner.introspected_column(col)
+    }
+    fn migration_lock_sql(&self) -> Option<(&'static str, &'static str)> {
+        self.inner.migration_lock_sql()
+    }
     fn connect(&self, conn_str: &str) -> Result<Connection> {
         let conn_async = self.block_on(self.inner.connect_async(conn_str))?;
-        let conn = Connection {
-            conn: Box::new(self.chain(conn_async.conn)),
-        };
-        Ok(conn)
+        let conn = Connection::new(Box::new(self.chain(conn_async.conn)));
+        Ok(conn.with_db_attributes(conn_async.db))
     }
     async fn connect_async(&self, conn_str: &str) -> Result<ConnectionAsync> {
         self.inner.connect_async(conn_str).await
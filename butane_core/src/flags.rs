@@ -0,0 +1,112 @@
+//! Contains the [FlagSet] type for fields stored as bit flags.
+
+use std::ops::{Deref, DerefMut};
+
+use bitflags::Flags;
+
+use super::{Error, FieldType, FromSql, Result, SqlType, SqlVal, SqlValRef, ToSql};
+
+/// Wrapper around a set of flags generated by the `bitflags!` macro
+/// to store it as a big integer holding their bits. Unknown bits are
+/// kept when loading. Dereferences to the wrapped flags.
+///
+/// Fields of this type may be filtered with `has_flag`, true if every
+/// given flag is set, and `has_any`, true if any is.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct FlagSet<T>(pub T);
+
+impl<T> FlagSet<T> {
+    /// Unwrap the flags.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for FlagSet<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for FlagSet<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for FlagSet<T> {
+    fn from(flags: T) -> Self {
+        FlagSet(flags)
+    }
+}
+
+/// The integer types which may hold the bits of a [FlagSet], which
+/// are stored as the `i64` with the same bit pattern.
+pub trait FlagBits: Copy {
+    /// The `i64` holding the bits.
+    fn to_i64(self) -> i64;
+    /// The bits held in `val`, ignoring any which do not fit.
+    fn from_i64(val: i64) -> Self;
+}
+
+macro_rules! impl_flag_bits {
+    ($($ty:ty),*) => {
+        $(impl FlagBits for $ty {
+            fn to_i64(self) -> i64 {
+                self as i64
+            }
+            fn from_i64(val: i64) -> Self {
+                val as $ty
+            }
+        })*
+    };
+}
+impl_flag_bits!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// The bits of `flags` as they are stored.
+pub(crate) fn flag_bits<T>(flags: &T) -> i64
+where
+    T: Flags,
+    T::Bits: FlagBits,
+{
+    flags.bits().to_i64()
+}
+
+impl<T> FromSql for FlagSet<T>
+where
+    T: Flags,
+    T::Bits: FlagBits,
+{
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::BigInt(bits) => Ok(FlagSet(T::from_bits_retain(FlagBits::from_i64(bits)))),
+            SqlValRef::Int(bits) => Ok(FlagSet(T::from_bits_retain(FlagBits::from_i64(
+                bits.into(),
+            )))),
+            _ => Err(Error::CannotConvertSqlVal(SqlType::BigInt, valref.into())),
+        }
+    }
+}
+
+impl<T> ToSql for FlagSet<T>
+where
+    T: Flags,
+    T::Bits: FlagBits,
+{
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::BigInt(flag_bits(&self.0))
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::BigInt(flag_bits(&self.0))
+    }
+}
+
+impl<T> FieldType for FlagSet<T>
+where
+    T: Flags,
+    T::Bits: FlagBits,
+{
+    const SQLTYPE: SqlType = SqlType::BigInt;
+    type RefType = Self;
+}
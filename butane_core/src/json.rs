@@ -0,0 +1,68 @@
+//! Contains the [Json] type for fields stored as JSON.
+
+use std::ops::{Deref, DerefMut};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::{Error, FieldType, FromSql, Result, SqlType, SqlVal, SqlValRef, ToSql};
+
+/// Wrapper around any serializable type to store it as JSON, in a
+/// `JSONB` column on PostgreSQL and as text on SQLite.
+/// Dereferences to the wrapped value.
+///
+/// Fields of this type may be filtered on their contents with
+/// `contains` and `path_eq`, which use the `JSONB` operators on
+/// PostgreSQL.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Json<T>(pub T);
+
+impl<T> Json<T> {
+    /// Unwrap the value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for Json<T> {
+    fn from(val: T) -> Self {
+        Json(val)
+    }
+}
+
+impl<T: DeserializeOwned> FromSql for Json<T> {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::Json(val) => Ok(Json(serde_json::from_value(val)?)),
+            _ => Err(Error::CannotConvertSqlVal(SqlType::Json, valref.into())),
+        }
+    }
+}
+
+impl<T: Serialize> ToSql for Json<T> {
+    fn to_sql(&self) -> SqlVal {
+        self.to_sql_ref().into()
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Json(serde_json::to_value(&self.0).unwrap())
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> FieldType for Json<T> {
+    const SQLTYPE: SqlType = SqlType::Json;
+    type RefType = Self;
+}
@@ -0,0 +1,365 @@
+//! Implementation of large blobs which are not loaded with their objects.
+#![deny(missing_docs)]
+
+#[cfg(feature = "fake")]
+use fake::{Dummy, Faker};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "async")]
+use crate::db::ConnectionMethodsAsync;
+use crate::db::{Column, ConnectionMethods};
+use crate::query::{BoolExpr, Expr};
+use crate::{Error, Result, SqlType, SqlVal, SqlValRef};
+
+fn default_name() -> &'static str {
+    "not_initialized"
+}
+
+/// A blob which is not loaded with the object holding it, so that
+/// objects may be listed without reading data which may run to many
+/// megabytes. It is stored in a nullable blob column of the object's
+/// table, and a null blob is empty.
+///
+/// The blob is read when asked for, either whole with `load` or in
+/// ranges with `read_range`, `chunks` or `stream`, which read only the
+/// bytes asked for. A blob given with `set` is written when the object
+/// holding it is saved.
+///
+/// See [`LargeBlobOpsSync`] and [`LargeBlobOpsAsync`] for operations requiring a live database connection.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LargeBlob {
+    #[serde(skip, default = "default_name")]
+    table: &'static str,
+    #[serde(skip, default = "default_name")]
+    pkcol: &'static str,
+    #[serde(skip, default = "default_name")]
+    column: &'static str,
+    #[serde(skip)]
+    pk: Option<SqlVal>,
+    #[serde(skip, default = "default_pk_type")]
+    pk_type: SqlType,
+    data: Option<Vec<u8>>,
+    #[serde(skip)]
+    modified: bool,
+}
+
+fn default_pk_type() -> SqlType {
+    SqlType::Int
+}
+
+impl LargeBlob {
+    /// Constructs a new, empty LargeBlob. `ensure_init` must be called
+    /// before it can be read from the database or saved, and will
+    /// automatically be called when a [`DataObject`] with a
+    /// `LargeBlob` field is loaded or saved.
+    ///
+    /// [`DataObject`]: super::DataObject
+    pub fn new() -> Self {
+        LargeBlob {
+            table: default_name(),
+            pkcol: default_name(),
+            column: default_name(),
+            pk: None,
+            pk_type: default_pk_type(),
+            data: None,
+            modified: false,
+        }
+    }
+
+    /// Used by macro-generated code. You do not need to call this directly.
+    pub fn ensure_init(
+        &mut self,
+        table: &'static str,
+        pkcol: &'static str,
+        column: &'static str,
+        pk: SqlVal,
+        pk_type: SqlType,
+    ) {
+        // The names are not serialized, so always restore them.
+        self.table = table;
+        self.pkcol = pkcol;
+        self.column = column;
+        if self.pk.is_none() {
+            self.pk = Some(pk);
+            self.pk_type = pk_type;
+        }
+    }
+
+    /// Replaces the blob, which is written when the object holding it
+    /// is saved.
+    pub fn set(&mut self, data: impl Into<Vec<u8>>) {
+        self.data = Some(data.into());
+        self.modified = true;
+    }
+
+    /// Returns a reference to the blob. It must have already been
+    /// loaded or set. If not, returns Error::ValueNotLoaded
+    pub fn get(&self) -> Result<&[u8]> {
+        self.data.as_deref().ok_or(Error::ValueNotLoaded)
+    }
+
+    /// Iterates over the blob in chunks of at most `chunk_size` bytes,
+    /// using [`LargeBlobOpsSync::read_range`]. Chunks are never cached,
+    /// so large blobs can be processed without loading them into
+    /// memory at once.
+    pub fn chunks<'a, C: ConnectionMethods>(
+        &'a self,
+        conn: &'a C,
+        chunk_size: u32,
+    ) -> LargeBlobChunks<'a, C> {
+        LargeBlobChunks {
+            blob: self,
+            conn,
+            chunk_size,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// Streams the blob in chunks of at most `chunk_size` bytes, using
+    /// [`LargeBlobOpsAsync::read_range`]. This is the async equivalent
+    /// of [`LargeBlob::chunks`].
+    #[cfg(feature = "async")]
+    pub fn stream<'a>(
+        &'a self,
+        conn: &'a impl ConnectionMethodsAsync,
+        chunk_size: u32,
+    ) -> impl futures_util::Stream<Item = Result<Vec<u8>>> + 'a {
+        futures_util::stream::unfold(Some(0u64), move |offset: Option<u64>| async move {
+            let offset = offset?;
+            match LargeBlobOpsAsync::read_range(self, conn, offset, chunk_size).await {
+                Ok(chunk) if chunk.is_empty() => None,
+                Ok(chunk) => {
+                    let next = if chunk.len() < chunk_size as usize {
+                        None
+                    } else {
+                        Some(offset + chunk.len() as u64)
+                    };
+                    Some((Ok(chunk), next))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+
+    fn blob_column(&self) -> Column {
+        Column::new(self.column, SqlType::Blob)
+    }
+}
+
+/// The bytes of a blob read from the database.
+fn blob_bytes(val: SqlVal) -> Result<Vec<u8>> {
+    match val {
+        SqlVal::Blob(data) => Ok(data),
+        SqlVal::Null => Ok(Vec::new()),
+        val => Err(Error::CannotConvertSqlVal(SqlType::Blob, val)),
+    }
+}
+
+/// Selects `column`, which reads some part of the blob, from the row
+/// holding `blob`.
+#[maybe_async_cfg::maybe(
+    idents(ConnectionMethods(sync, async = "ConnectionMethodsAsync")),
+    sync(),
+    async(feature = "async")
+)]
+async fn select_part(
+    blob: &LargeBlob,
+    conn: &impl ConnectionMethods,
+    column: Column,
+) -> Result<SqlVal> {
+    let pk = blob.pk.clone().ok_or(Error::NotInitialized)?;
+    let ty = column.ty().clone();
+    let mut rows = conn
+        .query(
+            blob.table,
+            &[column],
+            Some(BoolExpr::Eq(blob.pkcol, Expr::Val(pk))),
+            Some(1),
+            None,
+            None,
+        )
+        .await?;
+    match rows.next()? {
+        Some(row) => Ok(row.get(0, ty)?.into()),
+        None => Err(Error::NoSuchObject),
+    }
+}
+
+/// [`LargeBlob`] operations which require a `Connection`.
+#[allow(async_fn_in_trait)] // Not intended to be implemented outside Butane
+#[maybe_async_cfg::maybe(
+    idents(ConnectionMethods(sync = "ConnectionMethods"),),
+    sync(),
+    async(feature = "async")
+)]
+pub trait LargeBlobOps {
+    /// Used by macro-generated code. You do not need to call this directly.
+    async fn save(&mut self, conn: &impl ConnectionMethods) -> Result<()>;
+
+    /// Loads the whole blob from the database if necessary and returns
+    /// a reference to it.
+    async fn load(&mut self, conn: &impl ConnectionMethods) -> Result<&[u8]>;
+
+    /// The length of the blob in bytes, read from the database unless
+    /// the blob has been loaded or set.
+    async fn size(&self, conn: &impl ConnectionMethods) -> Result<u64>;
+
+    /// Reads at most `len` bytes of the blob from `offset`, fewer only
+    /// at its end. Only those bytes are read from the database, unless
+    /// the blob has been loaded or set.
+    async fn read_range(
+        &self,
+        conn: &impl ConnectionMethods,
+        offset: u64,
+        len: u32,
+    ) -> Result<Vec<u8>>;
+}
+
+#[maybe_async_cfg::maybe(
+    idents(
+        ConnectionMethods(sync = "ConnectionMethods"),
+        LargeBlobOps,
+        select_part(sync = "select_part_sync", async = "select_part_async"),
+    ),
+    keep_self,
+    sync(),
+    async(feature = "async")
+)]
+impl LargeBlobOps for LargeBlob {
+    async fn save(&mut self, conn: &impl ConnectionMethods) -> Result<()> {
+        if !self.modified {
+            return Ok(());
+        }
+        let pk = self.pk.as_ref().ok_or(Error::NotInitialized)?;
+        let data = self.data.as_deref().unwrap_or_default();
+        conn.update(
+            self.table,
+            Column::new(self.pkcol, self.pk_type.clone()),
+            pk.as_ref(),
+            &[self.blob_column()],
+            &[SqlValRef::Blob(data)],
+        )
+        .await?;
+        self.modified = false;
+        Ok(())
+    }
+
+    async fn load(&mut self, conn: &impl ConnectionMethods) -> Result<&[u8]> {
+        if self.data.is_none() {
+            // If not initialised then the blob has never been saved
+            let data = if self.pk.is_none() {
+                Vec::new()
+            } else {
+                blob_bytes(select_part(self, conn, self.blob_column()).await?)?
+            };
+            self.data = Some(data);
+        }
+        self.get()
+    }
+
+    async fn size(&self, conn: &impl ConnectionMethods) -> Result<u64> {
+        if let Some(data) = &self.data {
+            return Ok(data.len() as u64);
+        }
+        if self.pk.is_none() {
+            return Ok(0);
+        }
+        match select_part(self, conn, Column::length(self.column)).await? {
+            SqlVal::Null => Ok(0),
+            SqlVal::Int(len) => u64::try_from(len).map_err(|_| Error::OutOfRange),
+            SqlVal::BigInt(len) => u64::try_from(len).map_err(|_| Error::OutOfRange),
+            val => Err(Error::CannotConvertSqlVal(SqlType::BigInt, val)),
+        }
+    }
+
+    async fn read_range(
+        &self,
+        conn: &impl ConnectionMethods,
+        offset: u64,
+        len: u32,
+    ) -> Result<Vec<u8>> {
+        if let Some(data) = &self.data {
+            let start = usize::try_from(offset)
+                .unwrap_or(usize::MAX)
+                .min(data.len());
+            let end = start.saturating_add(len as usize).min(data.len());
+            return Ok(data[start..end].to_vec());
+        }
+        if self.pk.is_none() {
+            return Ok(Vec::new());
+        }
+        let offset = u32::try_from(offset).map_err(|_| Error::OutOfRange)?;
+        let column = Column::range(self.column, offset, Some(len));
+        blob_bytes(select_part(self, conn, column).await?)
+    }
+}
+
+/// Iterator over chunks of a [`LargeBlob`]. Created by
+/// [`LargeBlob::chunks`].
+#[derive(Debug)]
+pub struct LargeBlobChunks<'a, C> {
+    blob: &'a LargeBlob,
+    conn: &'a C,
+    chunk_size: u32,
+    offset: u64,
+    done: bool,
+}
+
+impl<C> Iterator for LargeBlobChunks<'_, C>
+where
+    C: ConnectionMethods,
+{
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match LargeBlobOpsSync::read_range(self.blob, self.conn, self.offset, self.chunk_size) {
+            Ok(chunk) if chunk.is_empty() => {
+                self.done = true;
+                None
+            }
+            Ok(chunk) => {
+                if chunk.len() < self.chunk_size as usize {
+                    self.done = true;
+                }
+                self.offset += chunk.len() as u64;
+                Some(Ok(chunk))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl From<Vec<u8>> for LargeBlob {
+    fn from(data: Vec<u8>) -> Self {
+        let mut blob = Self::new();
+        blob.set(data);
+        blob
+    }
+}
+
+impl PartialEq<LargeBlob> for LargeBlob {
+    fn eq(&self, other: &LargeBlob) -> bool {
+        (self.pk == other.pk) && (self.table == other.table) && (self.column == other.column)
+    }
+}
+impl Eq for LargeBlob {}
+impl Default for LargeBlob {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "fake")]
+/// Fake data support is currently limited to empty blobs.
+impl Dummy<Faker> for LargeBlob {
+    fn dummy_with_rng<R: rand::Rng + ?Sized>(_: &Faker, _rng: &mut R) -> Self {
+        Self::new()
+    }
+}
@@ -5,6 +5,8 @@
 
 use std::borrow::Borrow;
 use std::cmp::{Eq, PartialEq};
+use std::collections::HashMap;
+use std::hash::Hash;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
@@ -13,22 +15,44 @@
 pub mod custom;
 pub mod db;
 pub mod fkey;
+pub mod largeblob;
 pub mod many;
 pub mod migrations;
+#[cfg(feature = "decimal")]
+pub mod money;
 pub mod query;
 pub mod sqlval;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 
+#[cfg(feature = "time")]
+pub mod time;
 #[cfg(feature = "uuid")]
 pub mod uuid;
 
 mod autopk;
+mod citext;
+#[cfg(feature = "bitflags")]
+mod flags;
+#[cfg(feature = "json")]
+mod json;
+mod redacted;
+mod trace;
 mod util;
 
 pub use autopk::AutoPk;
+pub use citext::CiString;
 use custom::SqlTypeCustom;
 use db::{BackendRow, Column, ConnectionMethods};
+#[cfg(feature = "bitflags")]
+pub use flags::{FlagBits, FlagSet};
+#[cfg(feature = "json")]
+pub use json::Json;
 pub use query::Query;
-pub use sqlval::{AsPrimaryKey, FieldType, FromSql, PrimaryKeyType, SqlVal, SqlValRef, ToSql};
+pub use redacted::Redacted;
+pub use sqlval::{
+    ArrayElement, AsPrimaryKey, FieldType, FromSql, PrimaryKeyType, SqlVal, SqlValRef, ToSql,
+};
 
 #[cfg(feature = "async")]
 use db::ConnectionMethodsAsync;
@@ -51,6 +75,10 @@ pub trait DataResult: Sized {
     const COLUMNS: &'static [Column];
 
     /// Load an object from a database backend row.
+    ///
+    /// The row's columns are those of [`COLUMNS`][Self::COLUMNS], in
+    /// the same order, so each field is read by its position without
+    /// looking up columns by name.
     fn from_row<'a>(row: &(dyn BackendRow + 'a)) -> Result<Self>
     where
         Self: Sized;
@@ -75,7 +103,8 @@ pub trait DataObjectInternal: DataResult<DBO = Self> {
         /// Get the primary key as mutable. Used internally in the case of [AutoPk].
         fn pk_mut(&mut self) -> &mut impl PrimaryKeyType;
 
-        /// Saves many-to-many relationships pointed to by fields on this model.
+        /// Saves many-to-many relationships pointed to by fields on this model,
+        /// and any [LargeBlob][largeblob::LargeBlob] fields which have been set.
         /// Performed automatically by `save`. You do not need to call this directly.
         #[cfg(feature = "async")]
         async fn save_many_to_many_async(
@@ -83,7 +112,8 @@ async fn save_many_to_many_async(
             conn: &impl ConnectionMethodsAsync,
         ) -> Result<()>;
 
-        /// Saves many-to-many relationships pointed to by fields on this model.
+        /// Saves many-to-many relationships pointed to by fields on this model,
+        /// and any [LargeBlob][largeblob::LargeBlob] fields which have been set.
         /// Performed automatically by `save`. You do not need to call this directly.
         fn save_many_to_many_sync(&mut self, conn: &impl ConnectionMethods) -> Result<()>;
 
@@ -115,6 +145,11 @@ pub trait DataObject: DataResult<DBO = Self> + internal::DataObjectInternal + Sy
     fn pk(&self) -> &Self::PKType;
 }
 
+/// How many primary keys [`get_many`][DataObjectOps::get_many] looks
+/// up with each query, keeping well within the limits backends place
+/// on the number of parameters of a statement.
+pub const GET_MANY_CHUNK_SIZE: usize = 1000;
+
 /// [`DataObject`] operations that require a live database connection.
 #[allow(async_fn_in_trait)] // Implementation is intended to be through procmacro
 #[maybe_async_cfg::maybe(
@@ -144,6 +179,32 @@ async fn try_get(conn: &impl ConnectionMethods, id: impl ToSql) -> Result<Option
         Self: DataObject + Sized,
     {
         use crate::query::QueryOps;
+        if let Some(cache) = conn.cache() {
+            let pk = id.borrow().to_sql();
+            let key = db::cache_key(pk.clone());
+            if let Some(values) = cache.get(Self::TABLE, &key) {
+                return Self::from_row(&db::VecRow::from_values(values)).map(Some);
+            }
+            let mut rows = conn
+                .query(
+                    Self::TABLE,
+                    Self::COLUMNS,
+                    Some(query::BoolExpr::Eq(T::PKCOL, query::Expr::Val(pk))),
+                    Some(1),
+                    None,
+                    None,
+                )
+                .await?;
+            return match rows.next()? {
+                Some(row) => {
+                    let row = db::VecRow::new(row, Self::COLUMNS)?;
+                    let obj = Self::from_row(&row)?;
+                    cache.put(Self::TABLE, key, row.into_values());
+                    Ok(Some(obj))
+                }
+                None => Ok(None),
+            };
+        }
         Ok(<Self as DataResult>::query()
             .filter(query::BoolExpr::Eq(
                 T::PKCOL,
@@ -156,6 +217,45 @@ async fn try_get(conn: &impl ConnectionMethods, id: impl ToSql) -> Result<Option
             .nth(0))
     }
 
+    /// Find the objects with the given primary keys, with one query per
+    /// [`GET_MANY_CHUNK_SIZE`] keys rather than one per key. Keys which
+    /// do not exist are skipped, and the objects are returned in no
+    /// particular order.
+    async fn get_many(conn: &impl ConnectionMethods, ids: &[impl ToSql]) -> Result<Vec<Self>>
+    where
+        Self: DataObject + Sized,
+    {
+        use crate::query::QueryOps;
+        let mut objs = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(GET_MANY_CHUNK_SIZE) {
+            let ids = chunk.iter().map(ToSql::to_sql).collect();
+            objs.extend(
+                <Self as DataResult>::query()
+                    .filter(query::BoolExpr::In(T::PKCOL, ids))
+                    .load(conn)
+                    .await?,
+            );
+        }
+        Ok(objs)
+    }
+
+    /// Like [`get_many`][Self::get_many], but returns the objects found
+    /// by their primary keys.
+    async fn get_many_map(
+        conn: &impl ConnectionMethods,
+        ids: &[impl ToSql],
+    ) -> Result<HashMap<Self::PKType, Self>>
+    where
+        Self: DataObject + Sized,
+        Self::PKType: Eq + Hash,
+    {
+        Ok(Self::get_many(conn, ids)
+            .await?
+            .into_iter()
+            .map(|obj| (obj.pk().clone(), obj))
+            .collect())
+    }
+
     /// Save the object to the database.
     async fn save(&mut self, conn: &impl ConnectionMethods) -> Result<()>
     where
@@ -260,8 +360,20 @@ pub enum Error {
     UriParse(#[from] url::ParseError),
     #[error("Unknown backend {0}")]
     UnknownBackend(String),
+    #[error("Unknown connection profile {0}")]
+    UnknownProfile(String),
+    #[error("Environment variable {0} is not set")]
+    MissingEnvVar(String),
     #[error("Range error")]
     OutOfRange,
+    #[error("Zero loaded for a non-zero type")]
+    UnexpectedZero,
+    #[cfg(feature = "decimal")]
+    #[error("Expected currency {0}, found {1}")]
+    CurrencyMismatch(&'static str, String),
+    #[cfg(feature = "datetime")]
+    #[error("Expected a timestamp {0} a time zone, found {1}")]
+    TimezoneMismatch(&'static str, String),
     #[error("Internal logic error {0}")]
     Internal(String),
     #[error("Cannot resolve type {0}. Are you missing a #[butane_type] attribute?")]
@@ -280,6 +392,8 @@ pub enum Error {
     LiteralForCustomUnsupported(custom::SqlValCustom),
     #[error("This DataObject doesn't support determining whether it has been saved.")]
     SaveDeterminationNotSupported,
+    #[error("{0} is not supported by backend {1}")]
+    Unsupported(&'static str, &'static str),
     #[error("This is a dummy poisoned connection.")]
     PoisonedConnection,
     #[error("Connect connect_async for synchronous backend {0}. To support this, enable the async-adapter feature.")]
@@ -300,6 +414,12 @@ pub enum Error {
     #[cfg(feature = "datetime")]
     #[error("Chrono error {0}")]
     Chrono(#[from] chrono::ParseError),
+    #[cfg(feature = "decimal")]
+    #[error("Decimal error {0}")]
+    Decimal(#[from] rust_decimal::Error),
+    #[cfg(feature = "uuid")]
+    #[error("UUID error {0}")]
+    Uuid(#[from] ::uuid::Error),
     #[error("RefCell error {0}")]
     CellBorrow(#[from] std::cell::BorrowMutError),
     #[cfg(feature = "tls")]
@@ -322,6 +442,36 @@ pub enum Error {
     TableNotFound(String),
     #[error("Column \"{0}\".\"{1}\" not found in schema definitions")]
     ColumnNotFound(String, String),
+    #[error("Query returned more than the limit of {0} rows")]
+    TooManyRows(usize),
+    #[error("Query did not finish within {0:?}")]
+    Timeout(std::time::Duration),
+}
+
+impl Error {
+    /// Whether this error means the transaction it occurred in failed
+    /// only because of other transactions running concurrently, so that
+    /// it may succeed if tried again: a serialization failure or deadlock
+    /// on PostgreSQL, or a busy or locked database on SQLite.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            #[cfg(feature = "pg")]
+            Error::Postgres(e) => {
+                use tokio_postgres::error::SqlState;
+                matches!(
+                    e.code(),
+                    Some(&SqlState::T_R_SERIALIZATION_FAILURE)
+                        | Some(&SqlState::T_R_DEADLOCK_DETECTED)
+                )
+            }
+            #[cfg(feature = "sqlite")]
+            Error::SQLite(rusqlite::Error::SqliteFailure(e, _)) => matches!(
+                e.code,
+                rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+            ),
+            _ => false,
+        }
+    }
 }
 
 #[cfg(feature = "sqlite")]
@@ -365,21 +515,94 @@ pub enum SqlType {
     Int,
     /// 8 bytes
     BigInt,
+    /// 16 bytes, stored as a `NUMERIC` on PostgreSQL and as text on SQLite
+    HugeInt,
     /// 8 byte float
     Real,
     /// String
     Text,
+    /// String compared without regard to case, stored as a `CITEXT` on
+    /// PostgreSQL and as text with the `NOCASE` collation on SQLite.
+    /// Its values are [`SqlVal::Text`].
+    CiText,
     #[cfg(feature = "datetime")]
     /// Timestamp
     Timestamp,
+    #[cfg(feature = "datetime")]
+    /// Timestamp with a timezone, stored as an instant in time
+    TimestampTz,
+    #[cfg(feature = "datetime")]
+    /// Calendar date, without a time of day
+    Date,
+    #[cfg(feature = "datetime")]
+    /// Time of day, without a date
+    Time,
+    #[cfg(feature = "decimal")]
+    /// Exact decimal number, with an optional precision and scale
+    Numeric(Option<(u32, u32)>),
     /// Blob
     Blob,
     #[cfg(feature = "json")]
     /// JSON
     Json,
+    #[cfg(feature = "json")]
+    /// Map of string keys to string values, stored as an `HSTORE` on
+    /// PostgreSQL and as a JSON object on SQLite. Its values are
+    /// [`SqlVal::Json`] objects.
+    KeyValue,
+    #[cfg(feature = "uuid")]
+    /// UUID, stored as a native `UUID` on PostgreSQL and as a 16 byte
+    /// blob on SQLite, or as canonical text with the `sqlite-uuid-text`
+    /// feature
+    Uuid,
+    /// Array of elements of the given type, which is one of those for
+    /// which [`array_element`][SqlType::array_element] is some. Stored
+    /// as a native array on PostgreSQL and as a JSON array on SQLite.
+    Array(#[serde(with = "array_element")] &'static SqlType),
     /// Custom SQL type
     Custom(SqlTypeCustom),
 }
+impl SqlType {
+    /// The `'static` equivalent of this type, if it may be the element
+    /// type of an [`SqlType::Array`].
+    pub fn array_element(&self) -> Option<&'static SqlType> {
+        Some(match self {
+            SqlType::Bool => &SqlType::Bool,
+            SqlType::Int => &SqlType::Int,
+            SqlType::BigInt => &SqlType::BigInt,
+            SqlType::Real => &SqlType::Real,
+            SqlType::Text => &SqlType::Text,
+            SqlType::Blob => &SqlType::Blob,
+            #[cfg(feature = "uuid")]
+            SqlType::Uuid => &SqlType::Uuid,
+            _ => return None,
+        })
+    }
+}
+
+/// Serialization of the element type of an array, which is
+/// deserialized to the `'static` type it must be.
+pub(crate) mod array_element {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::SqlType;
+
+    pub fn serialize<S: Serializer>(
+        ty: &&'static SqlType,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        ty.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<&'static SqlType, D::Error> {
+        let ty = SqlType::deserialize(deserializer)?;
+        ty.array_element()
+            .ok_or_else(|| D::Error::custom(format!("{ty} cannot be an array element")))
+    }
+}
 impl std::fmt::Display for SqlType {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         use SqlType::*;
@@ -387,13 +610,28 @@ fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
             Bool => "bool",
             Int => "int",
             BigInt => "big int",
+            HugeInt => "huge int",
             Real => "float",
             Text => "string",
+            CiText => "case-insensitive string",
             #[cfg(feature = "datetime")]
             Timestamp => "timestamp",
+            #[cfg(feature = "datetime")]
+            TimestampTz => "timestamp with time zone",
+            #[cfg(feature = "datetime")]
+            Date => "date",
+            #[cfg(feature = "datetime")]
+            Time => "time",
+            #[cfg(feature = "decimal")]
+            Numeric(_) => "numeric",
             Blob => "blob",
             #[cfg(feature = "json")]
             Json => "json",
+            #[cfg(feature = "json")]
+            KeyValue => "key value",
+            #[cfg(feature = "uuid")]
+            Uuid => "uuid",
+            Array(elem) => return write!(f, "{elem} array"),
             Custom(_) => "custom",
         }
         .fmt(f)
@@ -21,12 +21,21 @@ fn default_oc<T>() -> OnceLock<Vec<T>> {
     OnceLock::new()
 }
 
+fn default_owner_column() -> &'static str {
+    "owner"
+}
+
+fn default_has_column() -> &'static str {
+    "has"
+}
+
 /// Used to implement a many-to-many relationship between models.
 ///
 /// Creates a new table with columns "owner" and "has" If type T has a
 /// many-to-many relationship with U, owner type is T::PKType, has is
 /// U::PKType. Table name is T_foo_Many where foo is the name of
-/// the Many field
+/// the Many field. The table and column names may be overridden with
+/// the `#[many_table]` field attribute.
 ///
 /// See [`ManyOpsSync`] and [`ManyOpsAsync`] for operations requiring a live database connection.
 //
@@ -36,6 +45,10 @@ pub struct Many<T>
     T: DataObject,
 {
     item_table: Cow<'static, str>,
+    #[serde(skip, default = "default_owner_column")]
+    owner_column: &'static str,
+    #[serde(skip, default = "default_has_column")]
+    has_column: &'static str,
     owner: Option<SqlVal>,
     owner_type: SqlType,
     #[serde(skip)]
@@ -59,6 +72,8 @@ impl<T> Many<T>
     pub fn new() -> Self {
         Many {
             item_table: Cow::Borrowed("not_initialized"),
+            owner_column: default_owner_column(),
+            has_column: default_has_column(),
             owner: None,
             owner_type: SqlType::Int,
             new_values: Vec::new(),
@@ -68,7 +83,17 @@ pub fn new() -> Self {
     }
 
     /// Used by macro-generated code. You do not need to call this directly.
-    pub fn ensure_init(&mut self, item_table: &'static str, owner: SqlVal, owner_type: SqlType) {
+    pub fn ensure_init(
+        &mut self,
+        item_table: &'static str,
+        owner_column: &'static str,
+        has_column: &'static str,
+        owner: SqlVal,
+        owner_type: SqlType,
+    ) {
+        // The column names are not serialized, so always restore them.
+        self.owner_column = owner_column;
+        self.has_column = has_column;
         if self.owner.is_some() {
             return;
         }
@@ -111,23 +136,88 @@ pub fn get(&self) -> Result<impl Iterator<Item = &T>> {
     /// Query the values referred to by this many relationship from the
     /// database if necessary and returns a reference to them.
     fn query(&self) -> Result<Query<T>> {
+        Ok(T::query().filter(self.owner_filter()?))
+    }
+
+    /// Filter matching the values referred to by this many relationship.
+    fn owner_filter(&self) -> Result<BoolExpr> {
         let owner: &SqlVal = match &self.owner {
             Some(o) => o,
             None => return Err(Error::NotInitialized),
         };
-        Ok(T::query().filter(BoolExpr::Subquery {
+        Ok(BoolExpr::Subquery {
             col: T::PKCOL,
             tbl2: self.item_table.clone(),
-            tbl2_col: "has",
-            expr: Box::new(BoolExpr::Eq("owner", Expr::Val(owner.clone()))),
-        }))
+            tbl2_col: self.has_column,
+            expr: Box::new(BoolExpr::Eq(self.owner_column, Expr::Val(owner.clone()))),
+        })
+    }
+
+    /// Query for one page of the values referred to by this many
+    /// relationship, ordered by primary key and starting after `after`.
+    fn page_query(&self, after: Option<SqlVal>, limit: i32) -> Result<Query<T>> {
+        let filter = match after {
+            Some(after) => BoolExpr::And(
+                Box::new(self.owner_filter()?),
+                Box::new(BoolExpr::Gt(T::PKCOL, Expr::Val(after))),
+            ),
+            None => self.owner_filter()?,
+        };
+        Ok(T::query().filter(filter).order_asc(T::PKCOL).limit(limit))
+    }
+
+    /// Iterates over the values referred to by this many relationship in
+    /// pages of at most `page_size` values, using [`ManyOpsSync::load_page`].
+    /// Values are never cached, so large relationships can be processed
+    /// without loading them all into memory at once.
+    pub fn pages<'a, C: ConnectionMethods>(
+        &'a self,
+        conn: &'a C,
+        page_size: i32,
+    ) -> ManyPages<'a, T, C> {
+        ManyPages {
+            many: self,
+            conn,
+            page_size,
+            after: None,
+            done: false,
+        }
+    }
+
+    /// Streams the values referred to by this many relationship in pages
+    /// of at most `page_size` values, using [`ManyOpsAsync::load_page`].
+    /// This is the async equivalent of [`Many::pages`].
+    #[cfg(feature = "async")]
+    pub fn stream<'a>(
+        &'a self,
+        conn: &'a impl ConnectionMethodsAsync,
+        page_size: i32,
+    ) -> impl futures_util::Stream<Item = Result<Vec<T>>> + 'a {
+        futures_util::stream::unfold(
+            Some(None),
+            move |after: Option<Option<SqlVal>>| async move {
+                let after = after?;
+                match load_page_uncached_async(self, conn, after, page_size).await {
+                    Ok(page) if page.is_empty() => None,
+                    Ok(page) => {
+                        let next = if page.len() < page_size as usize {
+                            None
+                        } else {
+                            page.last().map(|last| Some(last.pk().to_sql()))
+                        };
+                        Some((Ok(page), next))
+                    }
+                    Err(e) => Some((Err(e), None)),
+                }
+            },
+        )
     }
 
     /// Describes the columns of the Many table.
     pub fn columns(&self) -> [Column; 2] {
         [
-            Column::new("owner", self.owner_type.clone()),
-            Column::new("has", <T::PKType as FieldType>::SQLTYPE),
+            Column::new(self.owner_column, self.owner_type.clone()),
+            Column::new(self.has_column, <T::PKType as FieldType>::SQLTYPE),
         ]
     }
 }
@@ -161,6 +251,28 @@ async fn load_query_uncached<'a, T>(
     Ok(vals)
 }
 
+/// Loads one page of the values referred to by this many relationship,
+/// bypassing the cache.
+#[maybe_async_cfg::maybe(
+    idents(ConnectionMethods(sync, async = "ConnectionMethodsAsync"), QueryOps),
+    sync(),
+    async(feature = "async")
+)]
+async fn load_page_uncached<T: DataObject>(
+    many: &Many<T>,
+    conn: &impl ConnectionMethods,
+    after: Option<SqlVal>,
+    limit: i32,
+) -> Result<Vec<T>> {
+    use crate::query::QueryOps;
+    match many.page_query(after, limit) {
+        Ok(query) => query.load(conn).await,
+        // If not initialised then there are no values
+        Err(Error::NotInitialized) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
 /// Loads the values referred to by this many relationship from a
 /// database query if necessary and returns a reference to them.
 #[maybe_async_cfg::maybe(
@@ -216,6 +328,20 @@ async fn load_ordered<'a>(
     ) -> Result<impl Iterator<Item = &'a T>>
     where
         T: 'a;
+
+    /// Loads at most `limit` of the values referred to by this many
+    /// relationship, ordered by primary key and starting after the
+    /// primary key `after`. Pass the primary key of the last value of a
+    /// page as `after` to load the next page.
+    ///
+    /// Unlike `load`, the result is not cached and values added but not
+    /// yet saved are not included.
+    async fn load_page(
+        &self,
+        conn: &impl ConnectionMethods,
+        after: Option<&T::PKType>,
+        limit: i32,
+    ) -> Result<Vec<T>>;
 }
 
 #[maybe_async_cfg::maybe(
@@ -224,6 +350,7 @@ async fn load_ordered<'a>(
         ManyOpsInternal,
         ManyOps,
         load_query(sync = "load_query_sync", async = "load_query_async"),
+        load_page_uncached(sync = "load_page_uncached_sync", async = "load_page_uncached_async"),
     ),
     keep_self,
     sync(),
@@ -246,7 +373,7 @@ async fn save(&mut self, conn: &impl ConnectionMethods) -> Result<()> {
         if !self.removed_values.is_empty() {
             conn.delete_where(
                 &self.item_table,
-                BoolExpr::In("has", std::mem::take(&mut self.removed_values)),
+                BoolExpr::In(self.has_column, std::mem::take(&mut self.removed_values)),
             )
             .await?;
         }
@@ -258,7 +385,7 @@ async fn delete(&mut self, conn: &impl ConnectionMethods) -> Result<()> {
         let owner = self.owner.as_ref().ok_or(Error::NotInitialized)?;
         conn.delete_where(
             &self.item_table,
-            BoolExpr::Eq("owner", Expr::Val(owner.clone())),
+            BoolExpr::Eq(self.owner_column, Expr::Val(owner.clone())),
         )
         .await?;
         self.new_values.clear();
@@ -306,6 +433,60 @@ async fn load_ordered<'a>(
         };
         vals.map(|v| v.into_iter())
     }
+
+    async fn load_page(
+        &self,
+        conn: &impl ConnectionMethods,
+        after: Option<&T::PKType>,
+        limit: i32,
+    ) -> Result<Vec<T>> {
+        load_page_uncached(self, conn, after.map(|pk| pk.to_sql()), limit).await
+    }
+}
+
+/// Iterator over pages of the values referred to by a [`Many`].
+/// Created by [`Many::pages`].
+#[derive(Debug)]
+pub struct ManyPages<'a, T, C>
+where
+    T: DataObject,
+{
+    many: &'a Many<T>,
+    conn: &'a C,
+    page_size: i32,
+    after: Option<SqlVal>,
+    done: bool,
+}
+
+impl<T, C> Iterator for ManyPages<'_, T, C>
+where
+    T: DataObject,
+    C: ConnectionMethods,
+{
+    type Item = Result<Vec<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match load_page_uncached_sync(self.many, self.conn, self.after.take(), self.page_size) {
+            Ok(page) if page.is_empty() => {
+                self.done = true;
+                None
+            }
+            Ok(page) => {
+                if page.len() < self.page_size as usize {
+                    self.done = true;
+                }
+                self.after = page.last().map(|last| last.pk().to_sql());
+                Some(Ok(page))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
 }
 
 impl<T: DataObject> PartialEq<Many<T>> for Many<T> {
@@ -1,6 +1,12 @@
 //! Abstract representation of a database schema. If using the butane
 //! CLI tool, there is no need to use this module. Even if applying
 //! migrations without this tool, you are unlikely to need this module.
+//!
+//! Tools comparing two schemas can use it directly: an [`ADB`] can be
+//! saved and loaded with serde, [`diff`] gives the [`Operation`]s
+//! between two of them, and [`diff_sql`][super::diff_sql] or
+//! [`Backend::create_migration_sql`][crate::db::Backend::create_migration_sql]
+//! renders them for a backend.
 
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
@@ -35,6 +41,30 @@
     prefixes
 });
 
+/// Names of `HashMap<String, String>`, which is stored as a key-value
+/// map rather than as JSON.
+#[cfg(feature = "json")]
+static KEY_VALUE_MAP_NAMES: Lazy<Vec<String>> = Lazy::new(|| {
+    let map_type_names: [&str; 3] = [
+        "HashMap",
+        "collections::HashMap",
+        "std::collections::HashMap",
+    ];
+    let string_tynames: [&str; 3] = ["String", "string::String", "std::string::String"];
+
+    let mut names = Vec::new();
+    for map_type_name in map_type_names {
+        for key_type_name in string_tynames {
+            for value_type_name in string_tynames {
+                names.push(format!(
+                    "{map_type_name}<{key_type_name},{value_type_name}>"
+                ));
+            }
+        }
+    }
+    names
+});
+
 /// Identifier for a type as used in a database column. Supports both
 /// [`SqlType`] and identifiers known only by name.
 /// The latter is used for custom types. `SqlType::Custom` cannot easily be used
@@ -145,6 +175,9 @@ fn new() -> Self {
     fn find_type(&self, key: &TypeKey) -> Option<TypeIdentifier> {
         #[cfg(feature = "json")]
         if let TypeKey::CustomType(ct) = key {
+            if KEY_VALUE_MAP_NAMES.contains(ct) {
+                return Some(TypeIdentifier::from(SqlType::KeyValue));
+            }
             for prefix in JSON_MAP_PREFIXES.iter() {
                 if ct.starts_with(prefix) {
                     return Some(TypeIdentifier::from(SqlType::Json));
@@ -175,12 +208,15 @@ fn insert_pk(&mut self, key: &str, ty: TypeIdentifier) -> bool {
 pub struct ADB {
     tables: BTreeMap<String, ATable>,
     extra_types: BTreeMap<TypeKey, DeferredSqlType>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    custom_types: BTreeMap<String, ACustomType>,
 }
 impl ADB {
     pub fn new() -> Self {
         ADB {
             tables: BTreeMap::new(),
             extra_types: BTreeMap::new(),
+            custom_types: BTreeMap::new(),
         }
     }
     pub fn tables(&self) -> impl Iterator<Item = &ATable> {
@@ -201,6 +237,19 @@ pub fn remove_table(&mut self, name: &str) {
     pub fn add_type(&mut self, key: TypeKey, sqltype: DeferredSqlType) {
         self.extra_types.insert(key, sqltype);
     }
+    /// The user-defined SQL types created by the migrations.
+    pub fn custom_types(&self) -> impl Iterator<Item = &ACustomType> {
+        self.custom_types.values()
+    }
+    pub fn get_custom_type<'a>(&'a self, name: &str) -> Option<&'a ACustomType> {
+        self.custom_types.get(name)
+    }
+    pub fn replace_custom_type(&mut self, ty: ACustomType) {
+        self.custom_types.insert(ty.name.clone(), ty);
+    }
+    pub fn remove_custom_type(&mut self, name: &str) {
+        self.custom_types.remove(name);
+    }
 
     /// Fixup as many DeferredSqlType::Deferred instances as possible
     /// into DeferredSqlType::Known
@@ -219,13 +268,13 @@ pub fn resolve_types(&mut self) -> Result<()> {
             changed = false;
 
             for table in &mut self.tables.values_mut() {
+                // Tables without a primary key, such as the join tables
+                // backing `Many` fields, have no pk type to contribute.
                 if let Some(pk) = table.pk() {
                     let pktype = pk.typeid();
                     if let Ok(pktype) = pktype {
                         changed |= resolver.insert_pk(&table.name, pktype.clone());
                     }
-                } else if !table.name.ends_with(MANY_SUFFIX) {
-                    unreachable!();
                 }
 
                 for col in &mut table.columns {
@@ -265,6 +314,21 @@ pub fn resolve_types(&mut self) -> Result<()> {
     pub fn transform_with(&mut self, op: Operation) {
         use Operation::*;
         match op {
+            RenameTable(from, to) => {
+                if let Some(mut table) = self.tables.remove(&from) {
+                    table.name.clone_from(&to);
+                    self.tables.insert(to.clone(), table);
+                }
+                // Databases point the foreign keys of other tables at the renamed table
+                for table in self.tables.values_mut() {
+                    for col in &mut table.columns {
+                        col.rename_referenced_table(&from, &to);
+                    }
+                    for constraint in &mut table.constraints {
+                        constraint.rename_referenced_table(&from, &to);
+                    }
+                }
+            }
             AddTable(table) => {
                 self.tables.insert(table.name.clone(), table);
             }
@@ -282,15 +346,115 @@ pub fn transform_with(&mut self, op: Operation) {
             RemoveColumn(table, name) => {
                 if let Some(t) = self.tables.get_mut(&table) {
                     t.remove_column(&name);
+                    // Databases drop the indexes and constraints of a dropped column
+                    t.indexes.retain(|index| !index.columns.contains(&name));
+                    t.constraints
+                        .retain(|constraint| !constraint.columns().contains(&name));
+                }
+            }
+            ChangeColumn(table, old, new) => {
+                if let Some(t) = self.tables.get_mut(&table) {
+                    if old.name != new.name {
+                        for index in &mut t.indexes {
+                            for column in &mut index.columns {
+                                if *column == old.name {
+                                    column.clone_from(&new.name);
+                                }
+                            }
+                        }
+                        for constraint in &mut t.constraints {
+                            constraint.rename_column(&old.name, &new.name);
+                        }
+                    }
+                    // Keep the position of a renamed column
+                    match t.columns.iter_mut().find(|c| c.name == old.name) {
+                        Some(existing) => *existing = new,
+                        None => t.replace_column(new),
+                    }
+                }
+            }
+            AddIndex(table, index) => {
+                if let Some(t) = self.tables.get_mut(&table) {
+                    t.add_index(index);
+                }
+            }
+            RemoveIndex(table, name) => {
+                if let Some(t) = self.tables.get_mut(&table) {
+                    t.remove_index(&name);
+                }
+            }
+            AddConstraint(table, constraint) => {
+                if let Some(t) = self.tables.get_mut(&table) {
+                    t.add_constraint(constraint);
                 }
             }
-            ChangeColumn(table, _, new) => {
+            RemoveConstraint(table, name) => {
                 if let Some(t) = self.tables.get_mut(&table) {
-                    t.replace_column(new);
+                    t.remove_constraint(&name);
                 }
             }
+            ChangeSqliteOptions(table, options) => {
+                if let Some(t) = self.tables.get_mut(&table) {
+                    t.set_sqlite_options(options);
+                }
+            }
+            AddCustomType(ty) | ChangeCustomType(_, ty) => self.replace_custom_type(ty),
+            RemoveCustomType(ty) => self.remove_custom_type(&ty.name),
+        }
+    }
+}
+
+/// Abstract representation of a user-defined SQL type, created by the
+/// migrations from its definition. A column has the type if its type
+/// is [`TypeIdentifier::Name`] with the name of the type.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ACustomType {
+    name: String,
+    kind: ACustomTypeKind,
+    definition: String,
+}
+impl ACustomType {
+    /// Create a type named `name` from its definition, whose form
+    /// depends on the `kind` of type.
+    pub fn new(
+        name: impl Into<String>,
+        kind: ACustomTypeKind,
+        definition: impl Into<String>,
+    ) -> Self {
+        ACustomType {
+            name: name.into(),
+            kind,
+            definition: definition.into(),
         }
     }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn kind(&self) -> ACustomTypeKind {
+        self.kind
+    }
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+}
+
+/// The kind of an [`ACustomType`].
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum ACustomTypeKind {
+    /// A constrained form of another type, defined by that type and
+    /// any constraints, such as `TEXT CHECK (VALUE LIKE '%@%')`.
+    Domain,
+    /// A row of named fields, defined by the list of fields, such as
+    /// `x DOUBLE PRECISION, y DOUBLE PRECISION`.
+    Composite,
+}
+impl std::fmt::Display for ACustomTypeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            ACustomTypeKind::Domain => "domain",
+            ACustomTypeKind::Composite => "composite type",
+        })
+    }
 }
 
 /// Abstract representation of a database table schema.
@@ -298,14 +462,42 @@ pub fn transform_with(&mut self, op: Operation) {
 pub struct ATable {
     pub name: String,
     pub columns: Vec<AColumn>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub indexes: Vec<AIndex>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<AConstraint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    renamed_from: Option<String>,
+    #[serde(default, skip_serializing_if = "SqliteTableOptions::is_default")]
+    sqlite_options: SqliteTableOptions,
 }
 impl ATable {
     pub fn new(name: String) -> ATable {
         ATable {
             name,
             columns: Vec::new(),
+            indexes: Vec::new(),
+            constraints: Vec::new(),
+            renamed_from: None,
+            sqlite_options: SqliteTableOptions::default(),
         }
     }
+    /// The options of the table on SQLite.
+    pub fn sqlite_options(&self) -> SqliteTableOptions {
+        self.sqlite_options
+    }
+    pub fn set_sqlite_options(&mut self, options: SqliteTableOptions) {
+        self.sqlite_options = options;
+    }
+    /// The previous name of the table, if it has been renamed.
+    pub fn renamed_from(&self) -> Option<&str> {
+        self.renamed_from.as_deref()
+    }
+    /// Set the previous name of the table, so that a migration renames
+    /// it rather than dropping it and creating a new table.
+    pub fn set_renamed_from(&mut self, name: Option<String>) {
+        self.renamed_from = name;
+    }
     pub fn add_column(&mut self, col: AColumn) {
         self.replace_column(col);
     }
@@ -325,6 +517,205 @@ pub fn remove_column(&mut self, name: &str) {
     pub fn pk(&self) -> Option<&AColumn> {
         self.columns.iter().find(|c| c.is_pk())
     }
+    /// Add an index, replacing any existing index of the same name.
+    pub fn add_index(&mut self, index: AIndex) {
+        if let Some(existing) = self.indexes.iter_mut().find(|i| i.name == index.name) {
+            *existing = index;
+        } else {
+            self.indexes.push(index);
+        }
+    }
+    pub fn index<'a>(&'a self, name: &str) -> Option<&'a AIndex> {
+        self.indexes.iter().find(|i| i.name == name)
+    }
+    pub fn remove_index(&mut self, name: &str) {
+        self.indexes.retain(|i| i.name != name);
+    }
+    /// Add a constraint, replacing any existing constraint of the same name.
+    pub fn add_constraint(&mut self, constraint: AConstraint) {
+        if let Some(existing) = self
+            .constraints
+            .iter_mut()
+            .find(|c| c.name == constraint.name)
+        {
+            *existing = constraint;
+        } else {
+            self.constraints.push(constraint);
+        }
+    }
+    pub fn constraint<'a>(&'a self, name: &str) -> Option<&'a AConstraint> {
+        self.constraints.iter().find(|c| c.name == name)
+    }
+    pub fn remove_constraint(&mut self, name: &str) {
+        self.constraints.retain(|c| c.name != name);
+    }
+}
+
+/// Options of a table only used by the SQLite backend.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default)]
+pub struct SqliteTableOptions {
+    /// Whether the table is `STRICT`, so that SQLite enforces the types
+    /// of its columns. Strict tables need SQLite 3.37.0 or later.
+    pub strict: bool,
+    /// Whether the table is `WITHOUT ROWID`, storing its rows by primary
+    /// key. The table must have a primary key which is not auto.
+    pub without_rowid: bool,
+}
+impl SqliteTableOptions {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+impl Default for SqliteTableOptions {
+    fn default() -> Self {
+        SqliteTableOptions {
+            strict: true,
+            without_rowid: false,
+        }
+    }
+}
+
+/// Abstract representation of a database index.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct AIndex {
+    name: String,
+    columns: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    unique: bool,
+    /// Index method, such as `btree` or `gin`. Only used by backends supporting it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    method: Option<String>,
+}
+impl AIndex {
+    /// Create a new non-unique index on the given columns, using the default method.
+    pub fn new(name: impl Into<String>, columns: Vec<String>) -> Self {
+        AIndex {
+            name: name.into(),
+            columns,
+            unique: false,
+            method: None,
+        }
+    }
+    /// The default name of an index on a single column.
+    pub fn default_name(table_name: &str, column_name: &str) -> String {
+        format!("{table_name}_{column_name}_idx")
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+    pub fn unique(&self) -> bool {
+        self.unique
+    }
+    pub fn set_unique(&mut self, unique: bool) {
+        self.unique = unique;
+    }
+    pub fn method(&self) -> Option<&str> {
+        self.method.as_deref()
+    }
+    pub fn set_method(&mut self, method: Option<String>) {
+        self.method = method;
+    }
+}
+
+/// Abstract representation of a named table constraint, other than the
+/// primary key, `NOT NULL` and the constraints of single columns.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct AConstraint {
+    name: String,
+    kind: AConstraintKind,
+}
+impl AConstraint {
+    /// Create a constraint that the values of `columns` are unique together.
+    pub fn unique(name: impl Into<String>, columns: Vec<String>) -> Self {
+        AConstraint {
+            name: name.into(),
+            kind: AConstraintKind::Unique(columns),
+        }
+    }
+    /// Create a constraint that the SQL expression `expr` is not false for any row.
+    pub fn check(name: impl Into<String>, expr: impl Into<String>) -> Self {
+        AConstraint {
+            name: name.into(),
+            kind: AConstraintKind::Check(expr.into()),
+        }
+    }
+    /// Create a constraint that the values of `columns` are values of
+    /// `ref_columns` in table `ref_table`.
+    pub fn foreign_key(
+        name: impl Into<String>,
+        columns: Vec<String>,
+        ref_table: impl Into<String>,
+        ref_columns: Vec<String>,
+    ) -> Self {
+        AConstraint {
+            name: name.into(),
+            kind: AConstraintKind::ForeignKey {
+                columns,
+                ref_table: ref_table.into(),
+                ref_columns,
+            },
+        }
+    }
+    /// The default name of a check constraint on a single column.
+    pub fn default_check_name(table_name: &str, column_name: &str) -> String {
+        format!("{table_name}_{column_name}_check")
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn kind(&self) -> &AConstraintKind {
+        &self.kind
+    }
+    /// The columns of the table the constraint is on, which are not
+    /// known for a check constraint.
+    pub fn columns(&self) -> &[String] {
+        match &self.kind {
+            AConstraintKind::Unique(columns) => columns,
+            AConstraintKind::ForeignKey { columns, .. } => columns,
+            AConstraintKind::Check(_) => &[],
+        }
+    }
+    fn rename_column(&mut self, old: &str, new: &str) {
+        let columns = match &mut self.kind {
+            AConstraintKind::Unique(columns) => columns,
+            AConstraintKind::ForeignKey { columns, .. } => columns,
+            AConstraintKind::Check(_) => return,
+        };
+        for column in columns {
+            if column == old {
+                *column = new.to_string();
+            }
+        }
+    }
+    fn rename_referenced_table(&mut self, from: &str, to: &str) {
+        if let AConstraintKind::ForeignKey { ref_table, .. } = &mut self.kind {
+            if ref_table == from {
+                *ref_table = to.to_string();
+            }
+        }
+    }
+}
+
+/// The kind of an [`AConstraint`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum AConstraintKind {
+    /// The values of the columns are unique together.
+    Unique(Vec<String>),
+    /// The SQL expression is not false for any row.
+    Check(String),
+    /// The values of the columns are values of columns of another table.
+    ForeignKey {
+        columns: Vec<String>,
+        ref_table: String,
+        ref_columns: Vec<String>,
+    },
 }
 
 /// SqlType which may not yet be known.
@@ -432,6 +823,25 @@ pub struct AColumn {
     /// Whether this column refers to another column.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     reference: Option<ARef>,
+    /// Whether the foreign key constraint on this column is checked
+    /// only at the end of the transaction.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    deferrable: bool,
+    /// Previous name of the column, used to detect renames.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    renamed_from: Option<String>,
+    /// SQL expression converting the previous value of the column when
+    /// its type changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    convert_using: Option<String>,
+    /// SQL expression giving the value of an added column in the
+    /// existing rows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backfill: Option<String>,
+    /// Number of digits of the fraction of a second kept by a
+    /// timestamp column, if declared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    precision: Option<u32>,
 }
 impl AColumn {
     /// Create new column.
@@ -455,6 +865,11 @@ pub fn new(
             unique,
             default,
             reference,
+            deferrable: false,
+            renamed_from: None,
+            convert_using: None,
+            backfill: None,
+            precision: None,
         }
     }
     /// Simple column that is non-null, non-auto, non-pk, non-unique with no default
@@ -464,9 +879,73 @@ pub fn new_simple(name: impl Into<String>, sqltype: DeferredSqlType) -> Self {
     pub fn name(&self) -> &str {
         &self.name
     }
+    /// Set the column name.
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+    /// The name this column previously had, if it has been renamed.
+    pub fn renamed_from(&self) -> Option<&str> {
+        self.renamed_from.as_deref()
+    }
+    /// Record the name this column previously had, so that a diff
+    /// produces a rename rather than removing and adding a column.
+    pub fn set_renamed_from(&mut self, name: Option<String>) {
+        self.renamed_from = name;
+    }
+    /// The SQL expression converting the previous value of this column
+    /// when its type changes, if one was given.
+    pub fn convert_using(&self) -> Option<&str> {
+        self.convert_using.as_deref()
+    }
+    /// Set the SQL expression converting the previous value of this
+    /// column when its type changes, such as `CAST(price * 100 AS
+    /// INTEGER)`. The expression may refer to the column by name.
+    pub fn set_convert_using(&mut self, expr: Option<String>) {
+        self.convert_using = expr;
+    }
+    /// The SQL expression giving the value of this column in existing
+    /// rows when it is added, if one was given.
+    pub fn backfill(&self) -> Option<&str> {
+        self.backfill.as_deref()
+    }
+    /// Set the SQL expression giving the value of this column in the
+    /// existing rows of its table when it is added, instead of its
+    /// default. The expression may refer to the other columns by name.
+    pub fn set_backfill(&mut self, expr: Option<String>) {
+        self.backfill = expr;
+    }
+    /// The number of digits of the fraction of a second kept by this
+    /// timestamp column, if declared. Otherwise as many as the backend
+    /// keeps.
+    pub fn precision(&self) -> Option<u32> {
+        self.precision
+    }
+    /// Set the number of digits of the fraction of a second kept by
+    /// this timestamp column, as declared by `#[timestamp(precision = 3)]`.
+    pub fn set_precision(&mut self, precision: Option<u32>) {
+        self.precision = precision;
+    }
+    /// Whether two columns have the same schema, ignoring rename and
+    /// conversion hints.
+    fn same_schema(&self, other: &AColumn) -> bool {
+        self.name == other.name
+            && self.sqltype == other.sqltype
+            && self.nullable == other.nullable
+            && self.pk == other.pk
+            && self.auto == other.auto
+            && self.unique == other.unique
+            && self.default == other.default
+            && self.reference == other.reference
+            && self.deferrable == other.deferrable
+            && self.precision == other.precision
+    }
     pub fn nullable(&self) -> bool {
         self.nullable
     }
+    /// Set whether the column may be null.
+    pub fn set_nullable(&mut self, nullable: bool) {
+        self.nullable = nullable;
+    }
     pub fn unique(&self) -> bool {
         self.unique
     }
@@ -484,10 +963,25 @@ pub fn reference(&self) -> &Option<ARef> {
     pub fn add_reference(&mut self, reference: &ARef) {
         self.reference = Some(reference.clone())
     }
+    fn rename_referenced_table(&mut self, from: &str, to: &str) {
+        if let Some(ARef::Literal(reference)) = &mut self.reference {
+            if reference.table_name == from {
+                reference.table_name = to.to_string();
+            }
+        }
+    }
     /// Remove the column that this column refers to.
     pub fn remove_reference(&mut self) {
         self.reference = None;
     }
+    /// Whether the foreign key constraint is deferred until the end of the transaction.
+    pub fn is_deferrable(&self) -> bool {
+        self.deferrable
+    }
+    /// Set whether the foreign key constraint is deferred until the end of the transaction.
+    pub fn set_deferrable(&mut self, deferrable: bool) {
+        self.deferrable = deferrable;
+    }
     /// Get the type identifier.
     pub fn typeid(&self) -> Result<TypeIdentifier> {
         match &self.sqltype {
@@ -554,9 +1048,32 @@ pub fn create_many_table(
     main_table_pk_field_name: &str,
     main_table_pk_field_type: DeferredSqlType,
 ) -> ATable {
-    let mut table = ATable::new(format!("{main_table_name}_{many_field_name}{MANY_SUFFIX}"));
-    let col = AColumn::new(
+    create_named_many_table(
+        &format!("{main_table_name}_{many_field_name}{MANY_SUFFIX}"),
         "owner",
+        "has",
+        main_table_name,
+        many_field_type,
+        main_table_pk_field_name,
+        main_table_pk_field_type,
+    )
+}
+
+/// Create a table for the [crate::many::Many] relationship with
+/// explicitly chosen table and column names.
+/// Should not be used directly, except in tests.
+pub fn create_named_many_table(
+    many_table_name: &str,
+    owner_column_name: &str,
+    has_column_name: &str,
+    main_table_name: &str,
+    many_field_type: DeferredSqlType,
+    main_table_pk_field_name: &str,
+    main_table_pk_field_type: DeferredSqlType,
+) -> ATable {
+    let mut table = ATable::new(many_table_name.to_owned());
+    let col = AColumn::new(
+        owner_column_name,
         main_table_pk_field_type,
         false, // nullable
         false, // pk
@@ -569,7 +1086,7 @@ pub fn create_many_table(
         ))),
     );
     table.add_column(col);
-    let mut col = AColumn::new_simple("has", many_field_type.clone());
+    let mut col = AColumn::new_simple(has_column_name, many_field_type.clone());
     if matches!(many_field_type, DeferredSqlType::Deferred(TypeKey::PK(_))) {
         col.add_reference(&ARef::Deferred(many_field_type));
     }
@@ -579,9 +1096,13 @@ pub fn create_many_table(
 
 /// Individual operation use to apply a migration.
 /// The order of operations in a diff roughly follows this enum order.
+/// Operations are saved with migrations, so their serialized form only
+/// changes in ways which keep older migrations readable.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[allow(clippy::large_enum_variant)] // boxing the columns would break the API
 pub enum Operation {
-    //future improvement: support column renames
+    /// Rename a table (old name, new name).
+    RenameTable(String, String),
     /// Add a table.
     AddTable(ATable),
     /// Add a table, if it doesnt already exist.
@@ -598,14 +1119,185 @@ pub enum Operation {
     ChangeColumn(String, AColumn, AColumn),
     /// Add table constraints referring to other tables, if the backend supports it.
     AddTableConstraints(ATable),
+    /// Add an index to the named table.
+    AddIndex(String, AIndex),
+    /// Remove the named index (index name) from the named table (table name).
+    RemoveIndex(String, String),
+    /// Add a constraint to the named table.
+    AddConstraint(String, AConstraint),
+    /// Remove the named constraint (constraint name) from the named table (table name).
+    RemoveConstraint(String, String),
+    /// Change the SQLite options of the named table.
+    ChangeSqliteOptions(String, SqliteTableOptions),
+    /// Create a user-defined type.
+    AddCustomType(ACustomType),
+    /// Drop a user-defined type.
+    RemoveCustomType(ACustomType),
+    /// Change the definition of a user-defined type (old, new),
+    /// converting the columns of the type.
+    ChangeCustomType(ACustomType, ACustomType),
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use Operation::*;
+        match self {
+            RenameTable(from, to) => write!(f, "Rename table {from} to {to}"),
+            AddTable(table) | AddTableIfNotExists(table) => {
+                let columns: Vec<String> = table.columns.iter().map(describe_column).collect();
+                write!(
+                    f,
+                    "Add table {} with columns {}",
+                    table.name,
+                    columns.join(", ")
+                )
+            }
+            AddTableConstraints(table) => write!(f, "Add foreign keys of table {}", table.name),
+            RemoveTableConstraints(table) => {
+                write!(f, "Remove foreign keys of table {}", table.name)
+            }
+            RemoveTable(name) => write!(f, "Remove table {name}"),
+            AddColumn(table, column) => {
+                write!(f, "Add column {table}.{}", describe_column(column))
+            }
+            RemoveColumn(table, name) => write!(f, "Remove column {table}.{name}"),
+            ChangeColumn(table, old, new) => {
+                let mut renamed = old.clone();
+                renamed.set_name(new.name());
+                if old.name() != new.name() {
+                    write!(f, "Rename column {table}.{} to {}", old.name(), new.name())?;
+                    if renamed.same_schema(new) {
+                        return Ok(());
+                    }
+                    write!(f, " and change")?;
+                } else {
+                    write!(f, "Change column {table}.{}", new.name())?;
+                }
+                write!(
+                    f,
+                    " from {} to {}",
+                    describe_column(&renamed),
+                    describe_column(new)
+                )
+            }
+            AddIndex(table, index) => write!(
+                f,
+                "Add {}index {} on {table} ({})",
+                if index.unique() { "unique " } else { "" },
+                index.name(),
+                index.columns().join(", ")
+            ),
+            RemoveIndex(table, name) => write!(f, "Remove index {name} from {table}"),
+            AddConstraint(table, constraint) => {
+                write!(f, "Add constraint {} on {table}: ", constraint.name())?;
+                match constraint.kind() {
+                    AConstraintKind::Unique(columns) => {
+                        write!(f, "unique ({})", columns.join(", "))
+                    }
+                    AConstraintKind::Check(expr) => write!(f, "check ({expr})"),
+                    AConstraintKind::ForeignKey {
+                        columns,
+                        ref_table,
+                        ref_columns,
+                    } => write!(
+                        f,
+                        "foreign key ({}) references {ref_table} ({})",
+                        columns.join(", "),
+                        ref_columns.join(", ")
+                    ),
+                }
+            }
+            RemoveConstraint(table, name) => write!(f, "Remove constraint {name} from {table}"),
+            ChangeSqliteOptions(table, options) => write!(
+                f,
+                "Make table {table} {}strict and {} rowid on SQLite",
+                if options.strict { "" } else { "not " },
+                if options.without_rowid {
+                    "without"
+                } else {
+                    "with"
+                }
+            ),
+            AddCustomType(ty) => write!(f, "Add {} {} as {}", ty.kind, ty.name, ty.definition),
+            RemoveCustomType(ty) => write!(f, "Remove {} {}", ty.kind, ty.name),
+            ChangeCustomType(old, new) => write!(
+                f,
+                "Change {} {} from {} to {}",
+                new.kind, new.name, old.definition, new.definition
+            ),
+        }
+    }
+}
+
+/// Describe a column as its name followed by its type and constraints,
+/// such as `tags (json, not null)`.
+fn describe_column(column: &AColumn) -> String {
+    let mut attributes = vec![match column.typeid() {
+        Ok(TypeIdentifier::Ty(ty)) => ty.to_string(),
+        Ok(TypeIdentifier::Name(name)) => name,
+        Err(_) => "unresolved type".to_string(),
+    }];
+    if column.is_pk() {
+        attributes.push("primary key".to_string());
+    } else if !column.nullable() {
+        attributes.push("not null".to_string());
+    }
+    if column.is_auto() {
+        attributes.push("auto".to_string());
+    }
+    if column.unique() {
+        attributes.push("unique".to_string());
+    }
+    if let Some(default) = column.default() {
+        attributes.push(format!("default {default}"));
+    }
+    if let Some(ARef::Literal(reference)) = column.reference() {
+        attributes.push(format!(
+            "references {}.{}",
+            reference.table_name(),
+            reference.column_name()
+        ));
+    }
+    format!("{} ({})", column.name(), attributes.join(", "))
 }
 
-/// Determine the operations necessary to move the database schema from `old` to `new`.
+/// Determine the operations necessary to move the database schema from
+/// `old` to `new`. The schemas need not be those of consecutive
+/// migrations, but their types should be resolved with
+/// [`ADB::resolve_types`].
 pub fn diff(old: &ADB, new: &ADB) -> Vec<Operation> {
     let mut ops: Vec<Operation> = Vec::new();
+
+    // Rename tables first, then diff them like any other existing table
+    let renamed_old;
+    let renames = renamed_tables(old, new);
+    let old = if renames.is_empty() {
+        old
+    } else {
+        let mut renamed = old.clone();
+        for (from, to) in renames {
+            let op = Operation::RenameTable(from, to);
+            renamed.transform_with(op.clone());
+            ops.push(op);
+        }
+        renamed_old = renamed;
+        &renamed_old
+    };
+
     let new_names: BTreeSet<&String> = new.tables.keys().collect();
     let old_names: BTreeSet<&String> = old.tables.keys().collect();
 
+    // Create and change types before the columns which use them
+    for ty in new.custom_types() {
+        match old.get_custom_type(&ty.name) {
+            None => ops.push(Operation::AddCustomType(ty.clone())),
+            Some(old_ty) if old_ty != ty => {
+                ops.push(Operation::ChangeCustomType(old_ty.clone(), ty.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+
     // Add new tables
     let new_tables = new_names.difference(&old_names);
     for added in new_tables.clone() {
@@ -615,6 +1307,19 @@ pub fn diff(old: &ADB, new: &ADB) -> Vec<Operation> {
         ));
     }
 
+    // Remove indexes and constraints before the columns they cover change
+    for table in new_names.intersection(&old_names) {
+        let table: &str = table.as_ref();
+        ops.append(&mut removed_indexes(
+            old.tables.get(table).expect("no table"),
+            new.tables.get(table).expect("no table"),
+        ));
+        ops.append(&mut removed_constraints(
+            old.tables.get(table).expect("no table"),
+            new.tables.get(table).expect("no table"),
+        ));
+    }
+
     // Remove tables
     let removed_tables = old_names.difference(&new_names);
     for removed in removed_tables.clone() {
@@ -629,10 +1334,22 @@ pub fn diff(old: &ADB, new: &ADB) -> Vec<Operation> {
     // Change existing tables
     for table in new_names.intersection(&old_names) {
         let table: &str = table.as_ref();
-        ops.append(&mut diff_table(
-            old.tables.get(table).expect("no table"),
-            new.tables.get(table).expect("no table"),
-        ));
+        let old_table = old.tables.get(table).expect("no table");
+        let new_table = new.tables.get(table).expect("no table");
+        ops.append(&mut diff_table(old_table, new_table));
+        if old_table.sqlite_options != new_table.sqlite_options {
+            ops.push(Operation::ChangeSqliteOptions(
+                table.to_string(),
+                new_table.sqlite_options,
+            ));
+        }
+    }
+
+    // Drop types once no column uses them
+    for ty in old.custom_types() {
+        if new.get_custom_type(&ty.name).is_none() {
+            ops.push(Operation::RemoveCustomType(ty.clone()));
+        }
     }
     for added in new_tables {
         let added: &str = added.as_ref();
@@ -640,10 +1357,147 @@ pub fn diff(old: &ADB, new: &ADB) -> Vec<Operation> {
         if table.columns.iter().any(|x| x.reference.is_some()) {
             ops.push(Operation::AddTableConstraints(table.clone()));
         }
+        for index in &table.indexes {
+            ops.push(Operation::AddIndex(added.to_string(), index.clone()));
+        }
+        for constraint in &table.constraints {
+            ops.push(Operation::AddConstraint(
+                added.to_string(),
+                constraint.clone(),
+            ));
+        }
+    }
+    for table in new_names.intersection(&old_names) {
+        let table: &str = table.as_ref();
+        ops.append(&mut added_indexes(
+            old.tables.get(table).expect("no table"),
+            new.tables.get(table).expect("no table"),
+        ));
+        ops.append(&mut added_constraints(
+            old.tables.get(table).expect("no table"),
+            new.tables.get(table).expect("no table"),
+        ));
     }
     ops
 }
 
+/// Tables of `old` renamed in `new`, as pairs of old and new names.
+///
+/// An added table is renamed from a removed table if it names it as its
+/// previous name, if they are the only added and removed tables with the
+/// same columns, or if it is the [`crate::many::Many`] table of the same
+/// field of a renamed table. When diffing in reverse (e.g. for a down
+/// migration) it is the removed table which carries the previous name.
+fn renamed_tables(old: &ADB, new: &ADB) -> Vec<(String, String)> {
+    let added: Vec<&ATable> = new
+        .tables()
+        .filter(|t| old.get_table(&t.name).is_none())
+        .collect();
+    let removed: Vec<&ATable> = old
+        .tables()
+        .filter(|t| new.get_table(&t.name).is_none())
+        .collect();
+    let mut renames: Vec<(String, String)> = Vec::new();
+    let unmatched = |renames: &[(String, String)], from: &str, to: &str| {
+        !renames.iter().any(|(f, t)| f == from || t == to)
+    };
+
+    for table in &added {
+        if let Some(from) = table.renamed_from() {
+            if removed.iter().any(|t| t.name == from) && unmatched(&renames, from, &table.name) {
+                renames.push((from.to_string(), table.name.clone()));
+            }
+        }
+    }
+    for table in &removed {
+        if let Some(to) = table.renamed_from() {
+            if added.iter().any(|t| t.name == to) && unmatched(&renames, &table.name, to) {
+                renames.push((table.name.clone(), to.to_string()));
+            }
+        }
+    }
+
+    for table in &added {
+        let candidates: Vec<&&ATable> = removed
+            .iter()
+            .filter(|r| unmatched(&renames, &r.name, &table.name) && same_columns(r, table))
+            .collect();
+        if let [from] = candidates[..] {
+            let matches = added
+                .iter()
+                .filter(|a| unmatched(&renames, &from.name, &a.name) && same_columns(from, a))
+                .count();
+            if matches == 1 {
+                renames.push((from.name.clone(), table.name.clone()));
+            }
+        }
+    }
+
+    for (from, to) in renames.clone() {
+        let prefix = format!("{from}_");
+        for table in &removed {
+            let Some(field) = table
+                .name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(MANY_SUFFIX))
+            else {
+                continue;
+            };
+            let many = format!("{to}_{field}{MANY_SUFFIX}");
+            if added.iter().any(|t| t.name == many) && unmatched(&renames, &table.name, &many) {
+                renames.push((table.name.clone(), many));
+            }
+        }
+    }
+    renames
+}
+
+/// Whether two tables have the same, non-empty, set of columns.
+fn same_columns(a: &ATable, b: &ATable) -> bool {
+    !a.columns.is_empty()
+        && a.columns.len() == b.columns.len()
+        && a.columns.iter().all(|col| {
+            b.column(&col.name)
+                .is_some_and(|other| other.same_schema(col))
+        })
+}
+
+/// Indexes of `old` which are missing or different in `new`.
+fn removed_indexes(old: &ATable, new: &ATable) -> Vec<Operation> {
+    old.indexes
+        .iter()
+        .filter(|index| new.index(&index.name) != Some(index))
+        .map(|index| Operation::RemoveIndex(old.name.clone(), index.name.clone()))
+        .collect()
+}
+
+/// Indexes of `new` which are missing or different in `old`.
+fn added_indexes(old: &ATable, new: &ATable) -> Vec<Operation> {
+    new.indexes
+        .iter()
+        .filter(|index| old.index(&index.name) != Some(index))
+        .map(|index| Operation::AddIndex(new.name.clone(), index.clone()))
+        .collect()
+}
+
+/// Constraints of `old` which are missing or different in `new`.
+fn removed_constraints(old: &ATable, new: &ATable) -> Vec<Operation> {
+    old.constraints
+        .iter()
+        .filter(|constraint| new.constraint(&constraint.name) != Some(constraint))
+        .map(|constraint| Operation::RemoveConstraint(old.name.clone(), constraint.name.clone()))
+        .collect()
+}
+
+/// Constraints of `new` which are missing or different in `old`.
+fn added_constraints(old: &ATable, new: &ATable) -> Vec<Operation> {
+    new.constraints
+        .iter()
+        .filter(|constraint| old.constraint(&constraint.name) != Some(constraint))
+        .map(|constraint| Operation::AddConstraint(new.name.clone(), constraint.clone()))
+        .collect()
+}
+
 fn col_by_name<'a>(columns: &'a [AColumn], name: &str) -> Option<&'a AColumn> {
     columns.iter().find(|c| c.name == name)
 }
@@ -653,10 +1507,34 @@ fn diff_table(old: &ATable, new: &ATable) -> Vec<Operation> {
     let new_names: BTreeSet<&String> = new.columns.iter().map(|c| &c.name).collect();
     let old_names: BTreeSet<&String> = old.columns.iter().map(|c| &c.name).collect();
 
+    // Renamed columns are added columns naming a removed column as their
+    // previous name. When diffing in reverse (e.g. for a down migration) it is
+    // the removed column which carries the previous name.
+    let mut renames: BTreeMap<&str, &str> = new_names
+        .difference(&old_names)
+        .filter_map(|added| {
+            let col = col_by_name(&new.columns, added).unwrap();
+            col.renamed_from()
+                .filter(|from| old.column(from).is_some() && new.column(from).is_none())
+                .map(|from| (added.as_str(), from))
+        })
+        .collect();
+    for removed in old_names.difference(&new_names) {
+        let col = col_by_name(&old.columns, removed).unwrap();
+        if let Some(to) = col.renamed_from() {
+            if new.column(to).is_some() && old.column(to).is_none() {
+                renames.entry(to).or_insert(removed.as_str());
+            }
+        }
+    }
+
     // Add columns
     let added_names = new_names.difference(&old_names);
     for added in added_names {
         let added: &str = added.as_ref();
+        if renames.contains_key(added) {
+            continue;
+        }
         ops.push(Operation::AddColumn(
             new.name.clone(),
             col_by_name(&new.columns, added).unwrap().clone(),
@@ -665,18 +1543,30 @@ fn diff_table(old: &ATable, new: &ATable) -> Vec<Operation> {
 
     // Remove columns
     for removed in old_names.difference(&new_names) {
+        if renames.values().any(|from| from == removed) {
+            continue;
+        }
         ops.push(Operation::RemoveColumn(
             old.name.clone(),
             (*removed).to_string(),
         ));
     }
 
+    // Rename columns
+    for (to, from) in &renames {
+        ops.push(Operation::ChangeColumn(
+            new.name.clone(),
+            col_by_name(&old.columns, from).unwrap().clone(),
+            col_by_name(&new.columns, to).unwrap().clone(),
+        ));
+    }
+
     // Change columns
     for colname in new_names.intersection(&old_names) {
         let colname: &str = colname.as_ref();
         let col = col_by_name(&new.columns, colname).unwrap();
         let old_col = col_by_name(&old.columns, colname).unwrap();
-        if col == old_col {
+        if col.same_schema(old_col) {
             continue;
         }
         ops.push(Operation::ChangeColumn(
@@ -0,0 +1,75 @@
+//! Backups of the data which undoing a migration drops.
+
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::adb::{diff, Operation, ADB};
+use crate::db::helper::quote_reserved_word;
+use crate::db::ConnectionMethods;
+use crate::{Error, Result};
+
+/// Copy the data which changing the schema of the database from `db`
+/// back to `prev_db` drops into backup tables named
+/// `<table>__backup_<timestamp>`: all of a dropped table, and the
+/// dropped columns of a table along with its primary key. The names of
+/// the backup tables are added to `backups`, whose names are not reused.
+pub(super) fn backup_dropped_data(
+    conn: &impl ConnectionMethods,
+    db: &ADB,
+    prev_db: &ADB,
+    backups: &mut Vec<String>,
+) -> Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| Error::MigrationError(e.to_string()))?
+        .as_secs();
+    let mut dropped_columns: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for op in diff(db, prev_db) {
+        match op {
+            Operation::RemoveTable(table) => {
+                let name = backup_table(conn, &table, "*", timestamp, backups)?;
+                backups.push(name);
+            }
+            Operation::RemoveColumn(table, column) => {
+                dropped_columns.entry(table).or_default().push(column);
+            }
+            _ => {}
+        }
+    }
+    for (table, mut columns) in dropped_columns {
+        if let Some(pk) = db.get_table(&table).and_then(|t| t.pk()) {
+            columns.insert(0, pk.name().to_string());
+        }
+        let columns = columns
+            .iter()
+            .map(|column| quote_reserved_word(column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let name = backup_table(conn, &table, &columns, timestamp, backups)?;
+        backups.push(name);
+    }
+    Ok(())
+}
+
+/// Copy `columns` of `table` into a new backup table, returning its name.
+fn backup_table(
+    conn: &impl ConnectionMethods,
+    table: &str,
+    columns: &str,
+    timestamp: u64,
+    used: &[String],
+) -> Result<String> {
+    let mut name = format!("{table}__backup_{timestamp}");
+    let mut n = 1;
+    while used.contains(&name) {
+        n += 1;
+        name = format!("{table}__backup_{timestamp}_{n}");
+    }
+    conn.execute(&format!(
+        "CREATE TABLE {} AS SELECT {columns} FROM {};",
+        quote_reserved_word(&name),
+        quote_reserved_word(table)
+    ))?;
+    crate::info!("Backed up data of table {table} to {name}");
+    Ok(name)
+}
@@ -0,0 +1,74 @@
+//! Callbacks run while migrating a database.
+
+use std::fmt;
+
+use crate::db::ConnectionMethods;
+use crate::Result;
+
+/// A callback given the name of a migration and the connection it is
+/// applied to.
+pub type MigrationCallback = Box<dyn Fn(&str, &dyn ConnectionMethods) -> Result<()> + Send + Sync>;
+
+/// A callback given the names of the migrations applied, in order, and
+/// the connection they were applied to.
+pub type MigrateCallback =
+    Box<dyn Fn(&[String], &dyn ConnectionMethods) -> Result<()> + Send + Sync>;
+
+/// Callbacks run by
+/// [`migrate_with_callbacks`][crate::migrations::Migrations::migrate_with_callbacks],
+/// for example to refresh materialized views or notify other services
+/// of a schema change. Unlike [hooks][crate::migrations::MigrationHooks],
+/// which belong to a migration, these are given by the application
+/// migrating the database and run for every migration. If a callback
+/// fails, migrating stops with its error.
+#[derive(Default)]
+pub struct MigrateCallbacks {
+    pub(super) before_each: Vec<MigrationCallback>,
+    pub(super) after_each: Vec<MigrationCallback>,
+    pub(super) after_all: Vec<MigrateCallback>,
+}
+
+impl MigrateCallbacks {
+    /// Create an empty set of callbacks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a callback run before each migration is applied.
+    pub fn before_each(
+        mut self,
+        f: impl Fn(&str, &dyn ConnectionMethods) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.before_each.push(Box::new(f));
+        self
+    }
+
+    /// Add a callback run after each migration has been applied.
+    pub fn after_each(
+        mut self,
+        f: impl Fn(&str, &dyn ConnectionMethods) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.after_each.push(Box::new(f));
+        self
+    }
+
+    /// Add a callback run once all migrations have been applied, even
+    /// if there were none to apply.
+    pub fn after_all(
+        mut self,
+        f: impl Fn(&[String], &dyn ConnectionMethods) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.after_all.push(Box::new(f));
+        self
+    }
+}
+
+impl fmt::Debug for MigrateCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MigrateCallbacks")
+            .field("before_each", &self.before_each.len())
+            .field("after_each", &self.after_each.len())
+            .field("after_all", &self.after_all.len())
+            .finish()
+    }
+}
@@ -0,0 +1,188 @@
+//! Detection of differences between the schema a database is expected
+//! to have and the schema it actually has.
+
+use std::fmt;
+
+use super::adb::{AColumn, ARef, ATable, Operation, ADB};
+use crate::db::Backend;
+use crate::Result;
+
+/// A difference between the expected schema of a database and its
+/// actual schema, as found by [`check_drift`][super::Migrations::check_drift].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaDrift {
+    /// An expected table is missing from the database.
+    MissingTable(String),
+    /// The database has a table which is not expected.
+    ExtraTable(String),
+    /// An expected column (table name, column name) is missing from the database.
+    MissingColumn(String, String),
+    /// The database has a column (table name, column name) which is not expected.
+    ExtraColumn(String, String),
+    /// A column in the database does not match its expected definition.
+    MismatchedColumn {
+        /// Table name.
+        table: String,
+        /// The column as expected.
+        expected: Box<AColumn>,
+        /// The column as found in the database.
+        actual: Box<AColumn>,
+    },
+}
+
+impl fmt::Display for SchemaDrift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaDrift::MissingTable(table) => write!(f, "Missing table {table}"),
+            SchemaDrift::ExtraTable(table) => write!(f, "Unexpected table {table}"),
+            SchemaDrift::MissingColumn(table, column) => {
+                write!(f, "Missing column {table}.{column}")
+            }
+            SchemaDrift::ExtraColumn(table, column) => {
+                write!(f, "Unexpected column {table}.{column}")
+            }
+            SchemaDrift::MismatchedColumn {
+                table,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Mismatched column {table}.{}: expected {expected:?}, found {actual:?}",
+                expected.name()
+            ),
+        }
+    }
+}
+
+/// Find the differences between the `expected` schema and the `actual`
+/// schema introspected from a database using `backend`.
+///
+/// Names are compared case-insensitively, as Postgres folds unquoted
+/// identifiers to lower case. Column defaults are not compared.
+pub fn schema_drift(expected: &ADB, actual: &ADB, backend: &dyn Backend) -> Vec<SchemaDrift> {
+    let mut drift = Vec::new();
+    for table in expected.tables() {
+        match find_table(actual, &table.name) {
+            None => drift.push(SchemaDrift::MissingTable(table.name.clone())),
+            Some(actual_table) => table_drift(table, actual_table, backend, &mut drift),
+        }
+    }
+    for table in actual.tables() {
+        if find_table(expected, &table.name).is_none() {
+            drift.push(SchemaDrift::ExtraTable(table.name.clone()));
+        }
+    }
+    drift
+}
+
+/// SQL changing a database using `backend` from the `actual` schema
+/// introspected from it to the `expected` schema, undoing the drift
+/// found by [`schema_drift`]: missing tables and columns are created,
+/// unexpected ones are dropped along with their data, and mismatched
+/// columns are changed. Review it before running it.
+pub fn drift_fix_sql(expected: &ADB, actual: &ADB, backend: &dyn Backend) -> Result<String> {
+    // Use the expected names for tables whose names differ only in case,
+    // so the operations find them.
+    let mut current = ADB::new();
+    for table in actual.tables() {
+        let mut table = table.clone();
+        if let Some(expected_table) = find_table(expected, &table.name) {
+            table.name = expected_table.name.clone();
+        }
+        current.replace_table(table);
+    }
+    let expected_column = |table: &str, column: &str| {
+        find_table(expected, table)
+            .and_then(|t| find_column(t, column))
+            .cloned()
+    };
+    let mut ops = Vec::new();
+    for drift in schema_drift(expected, &current, backend) {
+        ops.push(match drift {
+            SchemaDrift::MissingTable(table) => match find_table(expected, &table) {
+                Some(table) => Operation::AddTable(table.clone()),
+                None => continue,
+            },
+            SchemaDrift::ExtraTable(table) => Operation::RemoveTable(table),
+            SchemaDrift::MissingColumn(table, column) => match expected_column(&table, &column) {
+                Some(column) => Operation::AddColumn(table, column),
+                None => continue,
+            },
+            SchemaDrift::ExtraColumn(table, column) => Operation::RemoveColumn(table, column),
+            SchemaDrift::MismatchedColumn { table, actual, .. } => {
+                match expected_column(&table, actual.name()) {
+                    Some(column) => Operation::ChangeColumn(table, *actual, column),
+                    None => continue,
+                }
+            }
+        });
+    }
+    backend.create_migration_sql(&current, ops)
+}
+
+fn table_drift(
+    expected: &ATable,
+    actual: &ATable,
+    backend: &dyn Backend,
+    drift: &mut Vec<SchemaDrift>,
+) {
+    for column in &expected.columns {
+        match find_column(actual, column.name()) {
+            None => drift.push(SchemaDrift::MissingColumn(
+                expected.name.clone(),
+                column.name().to_string(),
+            )),
+            Some(actual_column) => {
+                let column = backend.introspected_column(column);
+                if !same_column(&column, actual_column) {
+                    drift.push(SchemaDrift::MismatchedColumn {
+                        table: expected.name.clone(),
+                        expected: Box::new(column),
+                        actual: Box::new(actual_column.clone()),
+                    });
+                }
+            }
+        }
+    }
+    for column in &actual.columns {
+        if find_column(expected, column.name()).is_none() {
+            drift.push(SchemaDrift::ExtraColumn(
+                expected.name.clone(),
+                column.name().to_string(),
+            ));
+        }
+    }
+}
+
+fn find_table<'a>(db: &'a ADB, name: &str) -> Option<&'a ATable> {
+    db.tables().find(|t| t.name.eq_ignore_ascii_case(name))
+}
+
+fn find_column<'a>(table: &'a ATable, name: &str) -> Option<&'a AColumn> {
+    table
+        .columns
+        .iter()
+        .find(|c| c.name().eq_ignore_ascii_case(name))
+}
+
+fn same_column(expected: &AColumn, actual: &AColumn) -> bool {
+    let same_reference = match (expected.reference(), actual.reference()) {
+        (None, None) => true,
+        (Some(ARef::Literal(expected)), Some(ARef::Literal(actual))) => {
+            expected
+                .table_name()
+                .eq_ignore_ascii_case(actual.table_name())
+                && expected
+                    .column_name()
+                    .eq_ignore_ascii_case(actual.column_name())
+        }
+        _ => false,
+    };
+    same_reference
+        && expected.typeid().ok() == actual.typeid().ok()
+        && expected.nullable() == actual.nullable()
+        && expected.is_pk() == actual.is_pk()
+        && expected.is_auto() == actual.is_auto()
+        && expected.unique() == actual.unique()
+        && expected.is_deferrable() == actual.is_deferrable()
+}
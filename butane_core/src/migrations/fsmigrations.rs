@@ -1,5 +1,5 @@
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
@@ -7,9 +7,12 @@
 use fs2::FileExt;
 use serde::{Deserialize, Serialize};
 
-use super::adb::{ATable, DeferredSqlType, TypeKey, ADB};
+use super::adb::{ACustomType, ATable, DeferredSqlType, SqliteTableOptions, TypeKey, ADB};
 use super::fs::{Filesystem, OsFilesystem};
-use super::{Migration, MigrationMut, Migrations, MigrationsMut};
+use super::{
+    Migration, MigrationHooks, MigrationMut, MigrationStep, Migrations, MigrationsMut, Seed,
+    SeedTable,
+};
 use crate::{Error, Result};
 
 type SqlTypeMap = BTreeMap<TypeKey, DeferredSqlType>;
@@ -29,6 +32,18 @@ struct MigrationInfo {
     table_bases: BTreeMap<String, String>,
     /// List of backends supported by this migration.
     backends: Vec<String>,
+    /// Migrations which this migration was squashed from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    replaces: Vec<String>,
+    /// Migrations besides `from_name` which this migration merges.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    merges: Vec<String>,
+    /// Steps this migration was created from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    steps: Vec<MigrationStep>,
+    /// Whether this migration must not be applied in a transaction.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    non_atomic: bool,
 }
 impl MigrationInfo {
     fn new() -> Self {
@@ -36,6 +51,10 @@ fn new() -> Self {
             from_name: None,
             table_bases: BTreeMap::new(),
             backends: Vec::new(),
+            replaces: Vec::new(),
+            merges: Vec::new(),
+            steps: Vec::new(),
+            non_atomic: false,
         }
     }
 }
@@ -56,6 +75,7 @@ fn new() -> Self {
 pub struct FsMigration {
     fs: std::sync::Arc<dyn Filesystem + Send + Sync>,
     root: PathBuf,
+    hooks: MigrationHooks,
 }
 
 impl FsMigration {
@@ -115,9 +135,17 @@ fn write_contents(&self, fname: &str, contents: &[u8]) -> Result<()> {
         self.ensure_dir()?;
         let path = self.root.join(fname);
         let mut contents: Vec<u8> = contents.into();
-        if contents[contents.len() - 1] != b'\n' {
+        if contents.last().is_some_and(|c| *c != b'\n') {
             contents.push(b'\n');
         }
+        // `#[model]` writes the tables of the current migration on
+        // every build, so leave a file which already has these contents
+        // alone. Its modification time then changes only when a model
+        // does, and builds which track the migrations, such as those
+        // embedding them, are not needlessly rerun.
+        if self.has_contents(&path, &contents) {
+            return Ok(());
+        }
         self.fs
             .write(&path)?
             .write_all(&contents)
@@ -126,6 +154,15 @@ fn write_contents(&self, fname: &str, contents: &[u8]) -> Result<()> {
         Ok(())
     }
 
+    fn has_contents(&self, path: &Path, contents: &[u8]) -> bool {
+        let mut existing = Vec::with_capacity(contents.len());
+        self.fs
+            .read(path)
+            .and_then(|mut f| f.read_to_end(&mut existing))
+            .is_ok()
+            && existing == contents
+    }
+
     fn ensure_dir(&self) -> Result<()> {
         Ok(self.fs.ensure_dir(&self.root)?)
     }
@@ -162,6 +199,7 @@ pub fn delete_db(&self) -> Result<()> {
                         let info = self.info()?;
                         let info = MigrationInfo {
                             from_name: info.from_name,
+                            replaces: info.replaces,
                             ..Default::default()
                         };
                         self.write_info(&info)?;
@@ -261,12 +299,47 @@ fn add_type(&mut self, key: TypeKey, sqltype: DeferredSqlType) -> Result<()> {
         Ok(())
     }
 
+    fn add_type_definition(&mut self, ty: &ACustomType) -> Result<()> {
+        self.write_contents(
+            &format!("{}.type", ty.name()),
+            serde_json::to_string_pretty(ty)?.as_bytes(),
+        )
+    }
+
+    fn delete_type_definition(&mut self, name: &str) -> Result<()> {
+        self.delete_file(&format!("{name}.type"))
+    }
+
     /// Set the migration before this one.
     fn set_migration_from(&mut self, prev: Option<String>) -> Result<()> {
         let mut info = self.info()?;
         info.from_name = prev;
         self.write_info(&info)
     }
+
+    fn set_replaces(&mut self, names: Vec<String>) -> Result<()> {
+        let mut info = self.info()?;
+        info.replaces = names;
+        self.write_info(&info)
+    }
+
+    fn set_merges(&mut self, names: Vec<String>) -> Result<()> {
+        let mut info = self.info()?;
+        info.merges = names;
+        self.write_info(&info)
+    }
+
+    fn set_steps(&mut self, steps: Vec<MigrationStep>) -> Result<()> {
+        let mut info = self.info()?;
+        info.steps = steps;
+        self.write_info(&info)
+    }
+
+    fn set_atomic(&mut self, atomic: bool) -> Result<()> {
+        let mut info = self.info()?;
+        info.non_atomic = !atomic;
+        self.write_info(&info)
+    }
 }
 
 impl Migration for FsMigration {
@@ -295,6 +368,9 @@ fn db(&self) -> Result<ADB> {
                     if name.ends_with(".table") {
                         let table: ATable = serde_json::from_reader(self.fs.read(&entry)?)?;
                         db.replace_table(table)
+                    } else if name.ends_with(".type") {
+                        let ty: ACustomType = serde_json::from_reader(self.fs.read(&entry)?)?;
+                        db.replace_custom_type(ty)
                     } else if name == TYPES_FILENAME {
                         let types: SqlTypeMap = serde_json::from_reader(
                             self.fs.read(&self.root.join(TYPES_FILENAME))?,
@@ -331,6 +407,26 @@ fn down_sql(&self, backend_name: &str) -> Result<Option<String>> {
     fn sql_backends(&self) -> Result<Vec<String>> {
         Ok(self.info()?.backends)
     }
+
+    fn replaces(&self) -> Result<Vec<String>> {
+        Ok(self.info()?.replaces)
+    }
+
+    fn merges(&self) -> Result<Vec<String>> {
+        Ok(self.info()?.merges)
+    }
+
+    fn steps(&self) -> Result<Vec<MigrationStep>> {
+        Ok(self.info()?.steps)
+    }
+
+    fn atomic(&self) -> Result<bool> {
+        Ok(!self.info()?.non_atomic)
+    }
+
+    fn hooks(&self) -> MigrationHooks {
+        self.hooks
+    }
 }
 
 impl PartialEq for FsMigration {
@@ -346,6 +442,7 @@ pub struct FsMigrations {
     fs: std::sync::Arc<dyn Filesystem + Send + Sync>,
     root: PathBuf,
     current: FsMigration,
+    hooks: BTreeMap<String, MigrationHooks>,
 }
 impl FsMigrations {
     /// Create a new instance.
@@ -354,8 +451,25 @@ pub fn new(root: PathBuf) -> Self {
         let current = FsMigration {
             fs: fs.clone(),
             root: root.join("current"),
+            hooks: MigrationHooks::default(),
         };
-        FsMigrations { fs, root, current }
+        FsMigrations {
+            fs,
+            root,
+            current,
+            hooks: BTreeMap::new(),
+        }
+    }
+    /// Register Rust functions to run when the named migration is
+    /// applied or undone. Replaces any hooks previously registered for it.
+    pub fn add_hooks(&mut self, migration_name: &str, hooks: MigrationHooks) -> Result<()> {
+        if self.get_migration(migration_name).is_none() {
+            return Err(Error::MigrationError(format!(
+                "Unknown migration {migration_name}"
+            )));
+        }
+        self.hooks.insert(migration_name.to_string(), hooks);
+        Ok(())
     }
     fn get_state(&self) -> Result<MigrationsState> {
         let path = self.root.join("state.json");
@@ -383,6 +497,11 @@ fn save_state(&mut self, state: &MigrationsState) -> Result<()> {
         contents.push('\n');
         f.write_all(contents.as_bytes()).map_err(|e| e.into())
     }
+    /// The directory containing a directory of seeds for each
+    /// environment, next to the migrations directory.
+    fn seeds_dir(&self) -> PathBuf {
+        self.root.with_file_name("seeds")
+    }
     /// Detach the latest migration from the list of migrations,
     /// leaving the migration on the filesystem.
     pub fn detach_latest_migration(&mut self) -> Result<()> {
@@ -431,6 +550,7 @@ fn get_migration(&self, name: &str) -> Option<Self::M> {
             Some(FsMigration {
                 fs: self.fs.clone(),
                 root: dir,
+                hooks: self.hooks.get(name).copied().unwrap_or_default(),
             })
         } else {
             None
@@ -443,6 +563,75 @@ fn latest(&self) -> Option<Self::M> {
             Some(name) => self.get_migration(&name),
         })
     }
+
+    fn heads(&self) -> Result<Vec<Self::M>> {
+        let entries = match self.fs.list_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut migrations = Vec::new();
+        for entry in entries {
+            let Some(name) = entry.file_name() else {
+                continue;
+            };
+            let name = name.to_string_lossy();
+            if name == "current" {
+                continue;
+            }
+            if let Some(m) = self.get_migration(&name) {
+                migrations.push(m);
+            }
+        }
+        let mut based_on: BTreeSet<String> = BTreeSet::new();
+        for m in &migrations {
+            based_on.extend(m.migration_from()?.map(|s| s.to_string()));
+            based_on.extend(m.merges()?);
+        }
+        migrations.retain(|m| !based_on.contains(m.name().as_ref()));
+        migrations.sort_by(|a, b| a.name().cmp(&b.name()));
+        Ok(migrations)
+    }
+
+    fn seeds(&self) -> Result<Vec<Seed>> {
+        let env_dirs = match self.fs.list_dir(&self.seeds_dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut seeds = Vec::new();
+        for env_dir in env_dirs.into_iter().filter(|path| path.is_dir()) {
+            let Some(env) = env_dir.file_name().map(|name| name.to_string_lossy()) else {
+                continue;
+            };
+            for path in self.fs.list_dir(&env_dir)? {
+                let Some(name) = path.file_stem().map(|name| name.to_string_lossy()) else {
+                    continue;
+                };
+                let seed = match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("json") => match serde_json::from_reader(self.fs.read(&path)?)? {
+                        SeedFile::Tables(tables) => Seed::new(&env, &name, tables),
+                        SeedFile::WithDependencies { depends_on, tables } => {
+                            let mut seed = Seed::new(&env, &name, tables);
+                            seed.set_depends_on(depends_on);
+                            seed
+                        }
+                    },
+                    Some("sql") => {
+                        let mut sql = String::new();
+                        self.fs.read(&path)?.read_to_string(&mut sql)?;
+                        let mut seed = Seed::from_sql(&env, &name, &sql);
+                        seed.set_depends_on(sql_seed_dependencies(&sql));
+                        seed
+                    }
+                    _ => continue,
+                };
+                seeds.push(seed);
+            }
+        }
+        seeds.sort_by(|a, b| (a.env(), a.name()).cmp(&(b.env(), b.name())));
+        Ok(seeds)
+    }
 }
 
 impl MigrationsMut for FsMigrations {
@@ -460,6 +649,7 @@ fn new_migration(&self, name: &str) -> Self::M {
         FsMigration {
             fs: self.fs.clone(),
             root: dir,
+            hooks: MigrationHooks::default(),
         }
     }
     fn add_migration(&mut self, m: Self::M) -> Result<()> {
@@ -473,6 +663,53 @@ fn add_migration(&mut self, m: Self::M) -> Result<()> {
         Ok(())
     }
 
+    fn delete_migration(&mut self, name: &str) -> Result<()> {
+        let m = self
+            .get_migration(name)
+            .ok_or_else(|| Error::MigrationError(format!("Unknown migration {name}")))?;
+        let mut state = self.get_state()?;
+        if state.latest.as_deref() == Some(name) {
+            state.latest = m.migration_from()?.map(|s| s.to_string());
+            self.save_state(&state)?;
+        }
+        crate::info!("Deleting {}", m.root.display());
+        std::fs::remove_dir_all(&m.root)?;
+        Ok(())
+    }
+
+    /// Read from `sqlite.json` next to the migrations directory, such as
+    /// `{"strict": false}`, if it exists.
+    fn default_sqlite_options(&self) -> Result<SqliteTableOptions> {
+        match self.fs.read(&self.root.with_file_name("sqlite.json")) {
+            Ok(f) => serde_json::from_reader(f).map_err(|e| e.into()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SqliteTableOptions::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn add_seed(&mut self, seed: Seed) -> Result<()> {
+        let dir = self.seeds_dir().join(seed.env());
+        self.fs.ensure_dir(&dir)?;
+        if let Some(sql) = seed.sql() {
+            let mut f = self.fs.write(&dir.join(format!("{}.sql", seed.name())))?;
+            if sql_seed_dependencies(sql) != seed.depends_on() {
+                writeln!(f, "{SEED_DEPENDS_ON}{}", seed.depends_on().join(", "))?;
+            }
+            return f.write_all(sql.as_bytes()).map_err(|e| e.into());
+        }
+        let mut f = self.fs.write(&dir.join(format!("{}.json", seed.name())))?;
+        let mut contents = if seed.depends_on().is_empty() {
+            serde_json::to_string_pretty(seed.tables())?
+        } else {
+            serde_json::to_string_pretty(&SeedFile::WithDependencies {
+                depends_on: seed.depends_on().to_vec(),
+                tables: seed.tables().to_vec(),
+            })?
+        };
+        contents.push('\n');
+        f.write_all(contents.as_bytes()).map_err(|e| e.into())
+    }
+
     fn delete_migrations(&mut self) -> Result<()> {
         crate::info!(
             "Deleting everything in {} except 'current'",
@@ -494,6 +731,38 @@ fn delete_migrations(&mut self) -> Result<()> {
 }
 
 #[derive(Debug)]
+/// The contents of a JSON seed file: the tables of the seed, or an
+/// object also naming the seeds it depends on.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum SeedFile {
+    Tables(Vec<SeedTable>),
+    WithDependencies {
+        depends_on: Vec<String>,
+        tables: Vec<SeedTable>,
+    },
+}
+
+/// Start of the comment of an SQL seed file naming the seeds it depends on.
+const SEED_DEPENDS_ON: &str = "-- depends_on: ";
+
+/// The seeds named by a `-- depends_on: a, b` comment among the
+/// comments starting an SQL seed.
+fn sql_seed_dependencies(sql: &str) -> Vec<String> {
+    sql.lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("--"))
+        .find_map(|line| line.strip_prefix(SEED_DEPENDS_ON.trim_end()))
+        .map(|names| {
+            names
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 struct MigrationLock {
     file: File,
 }
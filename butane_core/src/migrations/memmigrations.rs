@@ -1,12 +1,19 @@
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use serde::{Deserialize, Serialize};
 
-use super::adb::{ATable, DeferredSqlType, TypeKey, ADB};
-use super::{Migration, MigrationMut, Migrations, MigrationsMut};
+use super::adb::{AConstraintKind, ACustomType, ARef, ATable, DeferredSqlType, TypeKey, ADB};
+use super::{
+    copy_migration, migrations_table, Migration, MigrationHooks, MigrationMut, MigrationStep,
+    Migrations, MigrationsMut, Seed,
+};
 
-use crate::Result;
+use crate::{Error, Result};
+
+/// Identifies the format written by [`MemMigrations::to_bytes`]: compact
+/// JSON compressed with DEFLATE.
+const BYTES_HEADER: &[u8] = b"butane-migrations-v1\0";
 
 /// A migration stored in memory.
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -16,16 +23,31 @@ pub struct MemMigration {
     from: Option<String>,
     up: BTreeMap<String, String>,
     down: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    replaces: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    merges: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    steps: Vec<MigrationStep>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    non_atomic: bool,
+    #[serde(skip)]
+    hooks: MigrationHooks,
 }
 
 impl MemMigration {
-    fn new(name: String) -> Self {
+    pub(super) fn new(name: String) -> Self {
         MemMigration {
             name,
             db: ADB::new(),
             from: None,
             up: BTreeMap::new(),
             down: BTreeMap::new(),
+            replaces: Vec::new(),
+            merges: Vec::new(),
+            steps: Vec::new(),
+            non_atomic: false,
+            hooks: MigrationHooks::default(),
         }
     }
 }
@@ -57,6 +79,21 @@ fn down_sql(&self, backend_name: &str) -> Result<Option<String>> {
     fn sql_backends(&self) -> Result<Vec<String>> {
         Ok(self.up.keys().map(|k| k.to_string()).collect())
     }
+    fn replaces(&self) -> Result<Vec<String>> {
+        Ok(self.replaces.clone())
+    }
+    fn merges(&self) -> Result<Vec<String>> {
+        Ok(self.merges.clone())
+    }
+    fn steps(&self) -> Result<Vec<MigrationStep>> {
+        Ok(self.steps.clone())
+    }
+    fn atomic(&self) -> Result<bool> {
+        Ok(!self.non_atomic)
+    }
+    fn hooks(&self) -> MigrationHooks {
+        self.hooks
+    }
 }
 impl PartialEq for MemMigration {
     fn eq(&self, other: &Self) -> bool {
@@ -95,10 +132,34 @@ fn add_type(&mut self, key: TypeKey, sqltype: DeferredSqlType) -> Result<()> {
         self.db.resolve_types()?;
         Ok(())
     }
+    fn add_type_definition(&mut self, ty: &ACustomType) -> Result<()> {
+        self.db.replace_custom_type(ty.clone());
+        Ok(())
+    }
+    fn delete_type_definition(&mut self, name: &str) -> Result<()> {
+        self.db.remove_custom_type(name);
+        Ok(())
+    }
     fn set_migration_from(&mut self, prev: Option<String>) -> Result<()> {
         self.from = prev;
         Ok(())
     }
+    fn set_replaces(&mut self, names: Vec<String>) -> Result<()> {
+        self.replaces = names;
+        Ok(())
+    }
+    fn set_merges(&mut self, names: Vec<String>) -> Result<()> {
+        self.merges = names;
+        Ok(())
+    }
+    fn set_steps(&mut self, steps: Vec<MigrationStep>) -> Result<()> {
+        self.steps = steps;
+        Ok(())
+    }
+    fn set_atomic(&mut self, atomic: bool) -> Result<()> {
+        self.non_atomic = !atomic;
+        Ok(())
+    }
 }
 
 /// A collection of migrations stored in memory.
@@ -107,6 +168,8 @@ pub struct MemMigrations {
     migrations: BTreeMap<String, MemMigration>,
     current: MemMigration,
     latest: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    seeds: Vec<Seed>,
 }
 
 impl MemMigrations {
@@ -115,12 +178,157 @@ pub fn new() -> Self {
             migrations: BTreeMap::new(),
             current: MemMigration::new("current".to_string()),
             latest: None,
+            seeds: Vec::new(),
         }
     }
     pub fn from_json(json: &str) -> Result<Self> {
         serde_json::from_str(json).map_err(|e| e.into())
     }
+    /// Serialize the migrations to a compact binary representation,
+    /// which can be read back with [`from_bytes`][MemMigrations::from_bytes].
+    /// It is much smaller than JSON, for embedding in a binary.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec(self)?;
+        let mut bytes = BYTES_HEADER.to_vec();
+        bytes.extend(miniz_oxide::deflate::compress_to_vec(&json, 9));
+        Ok(bytes)
+    }
+    /// Deserialize migrations serialized by [`to_bytes`][MemMigrations::to_bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let invalid = |reason: &str| {
+            Error::MigrationError(format!("Invalid serialized migrations: {reason}"))
+        };
+        let compressed = bytes
+            .strip_prefix(BYTES_HEADER)
+            .ok_or_else(|| invalid("unknown format"))?;
+        let json = miniz_oxide::inflate::decompress_to_vec(compressed)
+            .map_err(|e| invalid(&e.to_string()))?;
+        serde_json::from_slice(&json).map_err(|e| e.into())
+    }
+    /// Copy the migrations of `ms`, from the first to its latest one,
+    /// and its seeds, for example to embed
+    /// [filesystem migrations][super::FsMigrations] in a binary.
+    pub fn from_migrations(ms: &impl Migrations) -> Result<Self> {
+        let mut mem_ms = MemMigrations::new();
+        for m in ms.all_migrations()? {
+            let mut new_m = mem_ms.new_migration(&m.name());
+            copy_migration(&m, &mut new_m)?;
+            mem_ms.add_migration(new_m)?;
+        }
+        for seed in ms.seeds()? {
+            mem_ms.add_seed(seed)?;
+        }
+        Ok(mem_ms)
+    }
+    /// Combine `parts`, the migrations of crates which each define their
+    /// own models, into a single chain. See [`Migrations::compose`].
+    pub(super) fn compose(parts: &[impl Migrations]) -> Result<Self> {
+        let mut dbs = Vec::new();
+        for part in parts {
+            let mut db = match part.latest() {
+                Some(m) => m.db()?,
+                None => ADB::new(),
+            };
+            db.remove_table(&migrations_table().name);
+            dbs.push(db);
+        }
+        for (i, db) in dbs.iter().enumerate() {
+            for other in &dbs[i + 1..] {
+                if let Some(table) = db.tables().find(|t| other.get_table(&t.name).is_some()) {
+                    return Err(Error::MigrationError(format!(
+                        "Table {} is defined by more than one of the composed migrations",
+                        table.name
+                    )));
+                }
+            }
+        }
+
+        // Migrations creating tables come before those referring to them
+        let mut order: Vec<usize> = Vec::new();
+        while order.len() < parts.len() {
+            let next = (0..parts.len()).find(|i| {
+                !order.contains(i)
+                    && referenced_tables(&dbs[*i]).iter().all(|table| {
+                        dbs[*i].get_table(table).is_some()
+                            || order.iter().any(|j| dbs[*j].get_table(table).is_some())
+                            || !dbs.iter().any(|db| db.get_table(table).is_some())
+                    })
+            });
+            match next {
+                Some(i) => order.push(i),
+                None => {
+                    return Err(Error::MigrationError(
+                        "The composed migrations refer to each other's tables in a cycle"
+                            .to_string(),
+                    ))
+                }
+            }
+        }
+
+        let mut composed = MemMigrations::new();
+        let mut earlier_db = ADB::new();
+        let mut previous: Option<String> = None;
+        for i in order {
+            for m in parts[i].all_migrations()? {
+                if composed.migrations.contains_key(m.name().as_ref()) {
+                    return Err(Error::MigrationError(format!(
+                        "Migration {} is in more than one of the composed migrations",
+                        m.name()
+                    )));
+                }
+                let mut new_m = composed.new_migration(&m.name());
+                copy_migration(&m, &mut new_m)?;
+                if new_m.from.is_none() {
+                    new_m.from.clone_from(&previous);
+                }
+                // The tables of the migrations before this one still exist
+                for table in earlier_db.tables() {
+                    new_m.add_modified_table(table)?;
+                }
+                new_m.hooks = m.hooks();
+                composed.add_migration(new_m)?;
+            }
+            for table in dbs[i].tables() {
+                earlier_db.replace_table(table.clone());
+            }
+            if let Some(latest) = parts[i].latest() {
+                previous = Some(latest.name().to_string());
+            }
+            for seed in parts[i].seeds()? {
+                composed.add_seed(seed)?;
+            }
+        }
+        Ok(composed)
+    }
+    /// Register Rust functions to run when the named migration is
+    /// applied or undone. Replaces any hooks previously registered for it.
+    pub fn add_hooks(&mut self, migration_name: &str, hooks: MigrationHooks) -> Result<()> {
+        let m = self
+            .migrations
+            .get_mut(migration_name)
+            .ok_or_else(|| Error::MigrationError(format!("Unknown migration {migration_name}")))?;
+        m.hooks = hooks;
+        Ok(())
+    }
+}
+/// The tables which the tables of `db` refer to with foreign keys.
+fn referenced_tables(db: &ADB) -> BTreeSet<String> {
+    let mut tables = BTreeSet::new();
+    for table in db.tables() {
+        for column in &table.columns {
+            if let Some(ARef::Literal(reference)) = column.reference() {
+                tables.insert(reference.table_name().to_string());
+            }
+        }
+        for constraint in &table.constraints {
+            if let AConstraintKind::ForeignKey { ref_table, .. } = constraint.kind() {
+                tables.insert(ref_table.clone());
+            }
+        }
+    }
+    tables
 }
+
 impl Default for MemMigrations {
     fn default() -> Self {
         Self::new()
@@ -137,6 +345,22 @@ fn latest(&self) -> Option<Self::M> {
             Some(name) => self.get_migration(name),
         }
     }
+    fn heads(&self) -> Result<Vec<Self::M>> {
+        let mut based_on: BTreeSet<String> = BTreeSet::new();
+        for m in self.migrations.values() {
+            based_on.extend(m.from.clone());
+            based_on.extend(m.merges.iter().cloned());
+        }
+        Ok(self
+            .migrations
+            .values()
+            .filter(|m| !based_on.contains(&m.name))
+            .cloned()
+            .collect())
+    }
+    fn seeds(&self) -> Result<Vec<Seed>> {
+        Ok(self.seeds.clone())
+    }
 }
 
 impl MigrationsMut for MemMigrations {
@@ -168,9 +392,29 @@ fn add_migration(&mut self, m: Self::M) -> Result<()> {
         Ok(())
     }
 
+    fn delete_migration(&mut self, name: &str) -> Result<()> {
+        let m = self
+            .migrations
+            .remove(name)
+            .ok_or_else(|| Error::MigrationError(format!("Unknown migration {name}")))?;
+        if self.latest.as_deref() == Some(name) {
+            self.latest = m.from;
+        }
+        Ok(())
+    }
+
     fn delete_migrations(&mut self) -> Result<()> {
         self.migrations.clear();
         self.latest = None;
         Ok(())
     }
+
+    fn add_seed(&mut self, seed: Seed) -> Result<()> {
+        self.seeds
+            .retain(|s| s.env() != seed.env() || s.name() != seed.name());
+        self.seeds.push(seed);
+        self.seeds
+            .sort_by(|a, b| (a.env(), a.name()).cmp(&(b.env(), b.name())));
+        Ok(())
+    }
 }
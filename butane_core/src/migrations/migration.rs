@@ -1,11 +1,74 @@
 use std::borrow::Cow;
 use std::fmt::Debug;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::adb::{ATable, DeferredSqlType, TypeKey, ADB};
-use super::ButaneMigration;
-use crate::db::{BackendConnection, ConnectionMethods};
-use crate::query::{BoolExpr, Expr};
-use crate::{sqlval::ToSql, DataObject, DataResult, Error, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::adb::{ACustomType, ATable, DeferredSqlType, Operation, TypeKey, ADB};
+use super::{ensure_migrations_table, ButaneMigration};
+use crate::db::{BackendConnection, ConnectionMethods, Transaction};
+use crate::query::BoolExpr;
+use crate::trace::{self, op_span};
+use crate::{sqlval::ToSql, DataObject, Error, Result};
+
+/// A Rust function run as part of a migration. It is given the
+/// transaction the migration is being applied in, so any changes it
+/// makes are committed or rolled back together with the schema change.
+pub type MigrationFn = fn(&Transaction) -> Result<()>;
+
+/// A step of a migration created from a list of steps with
+/// [`create_migration_from_steps`][crate::migrations::MigrationsMut::create_migration_from_steps].
+/// Steps are stored with the migration, so SQL can be rendered from
+/// them for backends added later.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum MigrationStep {
+    /// A schema change, which each backend renders into its own SQL.
+    Operation(Box<Operation>),
+    /// SQL to run on the named backends only, or on every backend if
+    /// `backends` is empty.
+    Sql {
+        backends: Vec<String>,
+        up: String,
+        down: String,
+    },
+}
+
+impl MigrationStep {
+    /// Whether this step applies to the named backend.
+    pub fn applies_to(&self, backend_name: &str) -> bool {
+        match self {
+            MigrationStep::Operation(_) => true,
+            MigrationStep::Sql { backends, .. } => {
+                backends.is_empty() || backends.iter().any(|b| b == backend_name)
+            }
+        }
+    }
+}
+
+/// Rust functions to run alongside the SQL of a migration, e.g. to
+/// backfill or transform data. The `up` function runs after the
+/// migration's up SQL and the `down` function runs before its down
+/// SQL, so both see the schema of this migration.
+///
+/// Hooks are not stored with the migration. Register them with the
+/// migrations collection, e.g. [`MemMigrations::add_hooks`][crate::migrations::MemMigrations::add_hooks]
+/// or [`FsMigrations::add_hooks`][crate::migrations::FsMigrations::add_hooks],
+/// before migrating.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MigrationHooks {
+    /// Run when the migration is applied.
+    pub up: Option<MigrationFn>,
+    /// Run when the migration is undone.
+    pub down: Option<MigrationFn>,
+}
+
+impl MigrationHooks {
+    /// Create hooks with the given `up` and optional `down` functions.
+    pub fn new(up: MigrationFn, down: Option<MigrationFn>) -> Self {
+        MigrationHooks { up: Some(up), down }
+    }
+}
 
 /// Type representing a database migration. A migration describes how
 /// to bring the database from state A to state B. In general, the
@@ -35,29 +98,144 @@ fn migration_from(&self) -> Result<Option<Cow<str>>>
     /// The names of the backends this migration has sql for.
     fn sql_backends(&self) -> Result<Vec<String>>;
 
+    /// The names of the migrations this one replaces, if it was created
+    /// by [squashing][crate::migrations::MigrationsMut::squash] them.
+    fn replaces(&self) -> Result<Vec<String>>;
+
+    /// The steps this migration was created from, if it was created
+    /// with [`create_migration_from_steps`][crate::migrations::MigrationsMut::create_migration_from_steps].
+    fn steps(&self) -> Result<Vec<MigrationStep>>;
+
+    /// The names of the migrations, besides the one it is
+    /// [from][Migration::migration_from], which this migration
+    /// [merges][crate::migrations::MigrationsMut::merge_migrations].
+    fn merges(&self) -> Result<Vec<String>>;
+
+    /// Whether this migration is applied and undone within a single
+    /// transaction, which is the default. Migrations containing
+    /// statements which cannot run inside a transaction, such as
+    /// `CREATE INDEX CONCURRENTLY` on Postgres, must be non-atomic.
+    fn atomic(&self) -> Result<bool>;
+
+    /// The Rust functions registered to run alongside this migration's SQL.
+    fn hooks(&self) -> MigrationHooks {
+        MigrationHooks::default()
+    }
+
+    /// Whether the migration can be undone on the backend. It cannot
+    /// if it has no down SQL for the backend, or if its down SQL is
+    /// empty while its up SQL is not, which is how an irreversible
+    /// migration is written.
+    fn has_down(&self, backend_name: &str) -> Result<bool> {
+        Ok(match self.down_sql(backend_name)? {
+            Some(down) => {
+                !down.trim().is_empty()
+                    || self
+                        .up_sql(backend_name)?
+                        .is_none_or(|up| up.trim().is_empty())
+            }
+            None => false,
+        })
+    }
+
+    /// A SHA-256 checksum of the SQL of the migration for all its
+    /// backends, as a hex string. It is recorded when the migration is
+    /// applied, to detect migrations edited afterwards.
+    fn checksum(&self) -> Result<String> {
+        let mut backends = self.sql_backends()?;
+        backends.sort();
+        let mut hasher = Sha256::new();
+        for backend in backends {
+            for part in [
+                Some(backend.clone()),
+                self.up_sql(&backend)?,
+                self.down_sql(&backend)?,
+            ] {
+                hasher.update(part.unwrap_or_default());
+                hasher.update([0]);
+            }
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+
     /// Apply the migration to a database connection. The connection
     /// must be for the same type of database as this and the database
     /// must be in the state of the migration prior to this one
+    ///
+    /// An [atomic][Migration::atomic] migration is applied in a
+    /// transaction, so it is either applied completely or not at all.
+    /// The statements of a non-atomic migration are executed one at a
+    /// time, and a failure may leave the migration partially applied.
     fn apply(&self, conn: &mut impl BackendConnection) -> Result<()> {
-        let backend_name = conn.backend_name();
-        let tx = conn.transaction()?;
+        let name = self.name();
+        let span = op_span!(
+            "butane.migration",
+            migration = name.as_ref(),
+            direction = "up"
+        );
+        trace::in_span(span, || {
+            let backend_name = conn.backend_name();
+            let sql = self
+                .up_sql(backend_name)?
+                .ok_or_else(|| Error::UnknownBackend(backend_name.to_string()))?;
+            ensure_migrations_table(conn)?;
+            let atomic = self.atomic()?;
+            if !atomic {
+                for statement in split_sql_statements(&sql) {
+                    conn.execute(statement)?;
+                }
+            }
+            let tx = conn.transaction()?;
+            if atomic {
+                tx.execute(&sql)?;
+            }
+            if let Some(up) = self.hooks().up {
+                up(&tx)?;
+            }
+            self.mark_applied(&tx)?;
+            tx.commit()
+        })
+    }
+
+    /// The SQL which [`apply`][Migration::apply] executes for the
+    /// backend, preceded by a comment naming this migration. Useful to
+    /// review a migration before applying it.
+    fn apply_sql(&self, backend_name: &str) -> Result<String> {
         let sql = self
             .up_sql(backend_name)?
             .ok_or_else(|| Error::UnknownBackend(backend_name.to_string()))?;
-        tx.execute(&sql)?;
-        self.mark_applied(&tx)?;
-        tx.commit()
+        let mut preview = format!("-- Migration {}\n", self.name());
+        if !self.atomic()? {
+            preview.push_str("-- Not run in a transaction\n");
+        }
+        preview.push_str(&sql);
+        if !preview.ends_with('\n') {
+            preview.push('\n');
+        }
+        if self.hooks().up.is_some() {
+            preview.push_str("-- Followed by the Rust up hook of this migration\n");
+        }
+        Ok(preview)
     }
 
     /// Mark the migration as being applied without doing any
     /// work. Use carefully -- the caller must ensure that the
     /// database schema already matches that expected by this
-    /// migration.
+    /// migration. The migrations table must be up to date, see
+    /// [`ensure_migrations_table`][crate::migrations::ensure_migrations_table].
     fn mark_applied(&self, conn: &impl ConnectionMethods) -> Result<()> {
+        let applied_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| Error::MigrationError(e.to_string()))?
+            .as_secs() as i64;
         conn.insert_only(
             ButaneMigration::TABLE,
-            ButaneMigration::COLUMNS,
-            &[self.name().as_ref().to_sql_ref()],
+            ButaneMigration::STATUS_COLUMNS,
+            &[
+                self.name().as_ref().to_sql_ref(),
+                applied_at.to_sql_ref(),
+                self.checksum()?.to_sql_ref(),
+            ],
         )
     }
 
@@ -65,19 +243,47 @@ fn mark_applied(&self, conn: &impl ConnectionMethods) -> Result<()> {
     /// connection. The connection must be for the same type of
     /// database as this and this must be the latest migration applied
     /// to the database.
+    ///
+    /// As with [`apply`][Migration::apply], a non-atomic migration is
+    /// not undone within a transaction.
     fn downgrade(&self, conn: &mut impl BackendConnection) -> Result<()> {
-        let backend_name = conn.backend_name();
-        let tx = conn.transaction()?;
-        let sql = self
-            .down_sql(backend_name)?
-            .ok_or_else(|| Error::UnknownBackend(backend_name.to_string()))?;
-        tx.execute(&sql)?;
-        let nameval = self.name().as_ref().to_sql();
-        tx.delete_where(
-            ButaneMigration::TABLE,
-            BoolExpr::Eq(ButaneMigration::PKCOL, Expr::Val(nameval)),
-        )?;
-        tx.commit()
+        let name = self.name();
+        let span = op_span!(
+            "butane.migration",
+            migration = name.as_ref(),
+            direction = "down"
+        );
+        trace::in_span(span, || {
+            let backend_name = conn.backend_name();
+            let sql = self
+                .down_sql(backend_name)?
+                .ok_or_else(|| Error::UnknownBackend(backend_name.to_string()))?;
+            let atomic = self.atomic()?;
+            if !atomic {
+                if let Some(down) = self.hooks().down {
+                    let tx = conn.transaction()?;
+                    down(&tx)?;
+                    tx.commit()?;
+                }
+                for statement in split_sql_statements(&sql) {
+                    conn.execute(statement)?;
+                }
+            }
+            let tx = conn.transaction()?;
+            if atomic {
+                if let Some(down) = self.hooks().down {
+                    down(&tx)?;
+                }
+                tx.execute(&sql)?;
+            }
+            let mut names = vec![self.name().as_ref().to_sql()];
+            names.extend(self.replaces()?.iter().map(|name| name.to_sql()));
+            tx.delete_where(
+                ButaneMigration::TABLE,
+                BoolExpr::In(ButaneMigration::PKCOL, names),
+            )?;
+            tx.commit()
+        })
     }
 }
 
@@ -109,6 +315,82 @@ pub trait MigrationMut: Migration {
     /// Adds a TypeKey -> SqlType mapping. Only meaningful on the special current migration.
     fn add_type(&mut self, key: TypeKey, sqltype: DeferredSqlType) -> Result<()>;
 
+    /// Adds a user-defined SQL type, which the migrations will create,
+    /// or replaces the definition of the type with the same name.
+    fn add_type_definition(&mut self, ty: &ACustomType) -> Result<()>;
+
+    /// Delete the user-defined SQL type with the given name. Like a
+    /// table, a type removed from the code remains until explicitly
+    /// deleted.
+    fn delete_type_definition(&mut self, name: &str) -> Result<()>;
+
     /// Set the name of the migration before this one.
     fn set_migration_from(&mut self, prev: Option<String>) -> Result<()>;
+
+    /// Set the names of the migrations this one replaces.
+    fn set_replaces(&mut self, names: Vec<String>) -> Result<()>;
+
+    /// Set the steps this migration was created from.
+    fn set_steps(&mut self, steps: Vec<MigrationStep>) -> Result<()>;
+
+    /// Set the names of the migrations this one merges.
+    fn set_merges(&mut self, names: Vec<String>) -> Result<()>;
+
+    /// Set whether the migration is applied within a transaction.
+    /// See [`Migration::atomic`].
+    fn set_atomic(&mut self, atomic: bool) -> Result<()>;
+}
+
+/// Split SQL into its statements, so they can be executed one at a
+/// time. Semicolons within quotes, comments and Postgres dollar-quoted
+/// strings do not end a statement.
+fn split_sql_statements(sql: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let bytes = sql.as_bytes();
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += 1;
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'$' => {
+                let tag_len = bytes[i + 1..]
+                    .iter()
+                    .position(|b| !(b.is_ascii_alphanumeric() || *b == b'_'));
+                if let Some(tag_len) = tag_len.filter(|len| bytes[i + 1 + len] == b'$') {
+                    let tag = &sql[i..i + tag_len + 2];
+                    i += tag.len();
+                    i = match sql[i..].find(tag) {
+                        Some(end) => i + end + tag.len() - 1,
+                        None => bytes.len(),
+                    };
+                }
+            }
+            b';' => {
+                statements.push(&sql[start..=i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    statements.push(&sql[start.min(sql.len())..]);
+    statements.retain(|s| !s.trim().is_empty());
+    statements
 }
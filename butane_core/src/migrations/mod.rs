@@ -3,7 +3,10 @@
 
 #![allow(missing_docs)]
 
+use std::collections::BTreeSet;
+use std::ops::RangeInclusive;
 use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
 use fallible_iterator::FallibleIterator;
@@ -16,13 +19,33 @@
 use crate::{db, query, DataObject, DataResult, Error, PrimaryKeyType, Result, SqlType};
 
 pub mod adb;
-use adb::{AColumn, ATable, DeferredSqlType, Operation, TypeIdentifier, ADB};
+use adb::{AColumn, ATable, DeferredSqlType, Operation, SqliteTableOptions, TypeIdentifier, ADB};
 
 mod migration;
-pub use migration::{Migration, MigrationMut};
+pub use migration::{Migration, MigrationFn, MigrationHooks, MigrationMut, MigrationStep};
 
 mod fs;
 
+mod drift;
+pub use drift::{drift_fix_sql, schema_drift, SchemaDrift};
+
+mod callbacks;
+pub use callbacks::{MigrateCallback, MigrateCallbacks, MigrationCallback};
+
+mod online;
+pub use online::{backfill_in_batches, locking_operations};
+
+mod seeds;
+pub use seeds::{sqlval_from_json, sqlval_to_json, Seed, SeedTable};
+
+mod backup;
+
+mod snapshot;
+pub use snapshot::schema_snapshot;
+
+mod testdb;
+pub use testdb::TestDatabase;
+
 mod fsmigrations;
 pub use fsmigrations::{FsMigration, FsMigrations};
 mod memmigrations;
@@ -40,87 +63,424 @@ pub trait Migrations: Clone {
     /// no migrations have been created.
     fn latest(&self) -> Option<Self::M>;
 
+    /// Get the migrations which no other migration is based on. Usually
+    /// this is just the [latest][Migrations::latest] migration, but
+    /// migrations created in parallel from the same parent (for
+    /// example on separate version control branches), or detached
+    /// from the chain, are heads too. Parallel migrations can be
+    /// combined with [`merge_migrations`][MigrationsMut::merge_migrations].
+    fn heads(&self) -> Result<Vec<Self::M>>;
+
+    /// Get the [seeds][Seed] of every environment, ordered by
+    /// environment and then by name.
+    fn seeds(&self) -> Result<Vec<Seed>>;
+
+    /// Combine the migrations of several crates, each defining its own
+    /// models, into a single chain of migrations applied by an
+    /// application using them all. The migrations of a crate come after
+    /// those of the crates whose tables its tables refer to, and
+    /// otherwise in the order of `parts`. Fails if more than one crate
+    /// defines the same table or a migration of the same name, or if
+    /// the crates refer to each other's tables in a cycle.
+    fn compose(parts: &[Self]) -> Result<MemMigrations> {
+        MemMigrations::compose(parts)
+    }
+
     /// Returns migrations since the given migration.
     fn migrations_since(&self, since: &Self::M) -> Result<Vec<Self::M>> {
-        let mut last = self.latest();
-        let mut accum: Vec<Self::M> = Vec::new();
-        while let Some(m) = last {
-            if m != *since {
-                last = match m.migration_from()? {
-                    None => None,
-                    Some(name) => self.get_migration(&name),
-                };
-                accum.push(m);
-                continue;
-            }
-
-            return Ok(accum.into_iter().rev().collect());
+        let mut all = self.all_migrations()?;
+        match all.iter().position(|m| m == since) {
+            Some(index) => Ok(all.split_off(index + 1)),
+            None => Err(Error::MigrationError("Migration not in chain".to_string())),
         }
-        Err(Error::MigrationError("Migration not in chain".to_string()))
     }
 
-    /// Returns all migrations
+    /// Returns all migrations. The migrations on the branches joined
+    /// by a [merge][MigrationsMut::merge_migrations] come before it,
+    /// the branch it is from first.
     fn all_migrations(&self) -> Result<Vec<Self::M>> {
-        let mut last = self.latest();
-        let mut accum: Vec<Self::M> = Vec::new();
-        while let Some(m) = last {
-            last = match m.migration_from()? {
-                None => None,
-                Some(name) => self.get_migration(&name),
-            };
-            accum.push(m);
+        migration_chain(self, self.latest())
+    }
+
+    /// Describe what is inconsistent in the chain of migrations: a
+    /// migration based on one which does not exist, several
+    /// [heads][Migrations::heads], or a latest migration which others
+    /// are based on. Returns no descriptions if the chain is sound.
+    fn chain_problems(&self) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+        let heads = self.heads()?;
+        if heads.len() > 1 {
+            let names: Vec<String> = heads.iter().map(|m| m.name().to_string()).collect();
+            problems.push(format!(
+                "Migrations {} are all heads. Merge those created in parallel, and delete or re-attach detached ones",
+                names.join(", ")
+            ));
+        }
+        match self.latest() {
+            Some(latest) if !heads.contains(&latest) => problems.push(format!(
+                "The latest migration {} has migrations based on it",
+                latest.name()
+            )),
+            None if !heads.is_empty() => {
+                problems.push("No migration is recorded as the latest".to_string())
+            }
+            _ => {}
+        }
+        for head in heads {
+            for m in migration_chain(self, Some(head))? {
+                let mut parents: Vec<String> = m
+                    .migration_from()?
+                    .map(|s| s.to_string())
+                    .into_iter()
+                    .collect();
+                parents.extend(m.merges()?);
+                for parent in parents {
+                    let problem = format!(
+                        "Migration {} is based on migration {parent}, which does not exist",
+                        m.name()
+                    );
+                    if self.get_migration(&parent).is_none() && !problems.contains(&problem) {
+                        problems.push(problem);
+                    }
+                }
+            }
         }
-        Ok(accum.into_iter().rev().collect())
+        Ok(problems)
     }
 
     /// Get migrations which have not yet been applied to the database
     fn unapplied_migrations(&self, conn: &impl ConnectionMethods) -> Result<Vec<Self::M>> {
-        match self.last_applied_migration(conn)? {
-            None => self.all_migrations(),
-            Some(m) => self.migrations_since(&m),
+        let applied = applied_migration_names(conn)?;
+        let mut unapplied = Vec::new();
+        for m in self.all_migrations()? {
+            if !is_applied(&m, &applied)? {
+                unapplied.push(m);
+            }
+        }
+        Ok(unapplied)
+    }
+
+    /// Get the migrations which have been applied to the database, in
+    /// the order of [`all_migrations`][Migrations::all_migrations].
+    fn applied_migrations(&self, conn: &impl ConnectionMethods) -> Result<Vec<Self::M>> {
+        let applied = applied_migration_names(conn)?;
+        let mut migrations = Vec::new();
+        for m in self.all_migrations()? {
+            if is_applied(&m, &applied)? {
+                migrations.push(m);
+            }
         }
+        Ok(migrations)
     }
 
     /// Get the last migration that has been applied to the database or None
     /// if no migrations have been applied
     fn last_applied_migration(&self, conn: &impl ConnectionMethods) -> Result<Option<Self::M>> {
-        if !conn.has_table(ButaneMigration::TABLE)? {
-            return Ok(None);
-        }
-        let migrations: Vec<ButaneMigration> = conn
-            .query(
-                ButaneMigration::TABLE,
-                ButaneMigration::COLUMNS,
-                None,
-                None,
-                None,
-                None,
-            )?
-            .mapped(ButaneMigration::from_row)
-            .collect()?;
-
-        let mut m_opt = self.latest();
-        while let Some(m) = m_opt {
-            if migrations.contains(&ButaneMigration {
+        Ok(self.applied_migrations(conn)?.pop())
+    }
+
+    /// The status in the database of each migration, in the order of
+    /// [`all_migrations`][Migrations::all_migrations].
+    fn status(&self, conn: &impl BackendConnection) -> Result<Vec<MigrationStatus>> {
+        let records = applied_migration_records(conn)?;
+        let applied: Vec<String> = records.iter().map(|r| r.name.clone()).collect();
+        let mut statuses = Vec::new();
+        for m in self.all_migrations()? {
+            let record = records.iter().find(|r| r.name == m.name());
+            let checksum_matches = match record.and_then(|r| r.checksum.as_ref()) {
+                Some(checksum) => Some(*checksum == m.checksum()?),
+                None => None,
+            };
+            statuses.push(MigrationStatus {
                 name: m.name().to_string(),
-            }) {
-                return Ok(Some(m));
-            }
-            m_opt = m
-                .migration_from()?
-                .and_then(|name| self.get_migration(&name))
+                applied: is_applied(&m, &applied)?,
+                applied_at: record
+                    .and_then(|r| r.applied_at)
+                    .map(|secs| UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)),
+                checksum_matches,
+                has_down: m.has_down(conn.backend_name())?,
+            });
+        }
+        Ok(statuses)
+    }
+
+    #[cfg(feature = "async")]
+    /// The status in the database of each migration. See [`status`][Migrations::status].
+    async fn status_async(&self, conn: &mut ConnectionAsync) -> Result<Vec<MigrationStatus>>
+    where
+        Self: Send + 'static,
+    {
+        let m2 = self.clone();
+        conn.with_sync(move |conn| m2.status(conn)).await
+    }
+
+    /// Insert or update the rows of each [seed][Seed] for environment
+    /// `env`, in order of name except that a seed comes after those it
+    /// [depends on][Seed::depends_on]. Seeding is idempotent, so it can
+    /// be done every time the database is migrated. All migrations must
+    /// be applied first. Returns the number of rows seeded.
+    fn seed(&self, conn: &impl ConnectionMethods, env: &str) -> Result<usize> {
+        seed_migrations(self, conn, env, None)
+    }
+
+    /// Apply the seed named `name` for environment `env`, after the
+    /// seeds it depends on. See [`seed`][Migrations::seed].
+    fn seed_named(&self, conn: &impl ConnectionMethods, env: &str, name: &str) -> Result<usize> {
+        seed_migrations(self, conn, env, Some(name))
+    }
+
+    #[cfg(feature = "async")]
+    /// Seed the database for environment `env`. See [`seed`][Migrations::seed].
+    async fn seed_async(&self, conn: &mut ConnectionAsync, env: &str) -> Result<usize>
+    where
+        Self: Send + 'static,
+    {
+        let m2 = self.clone();
+        let env = env.to_string();
+        conn.with_sync(move |conn| m2.seed(conn, &env)).await
+    }
+
+    #[cfg(feature = "async")]
+    /// Apply the named seed for environment `env`. See [`seed_named`][Migrations::seed_named].
+    async fn seed_named_async(
+        &self,
+        conn: &mut ConnectionAsync,
+        env: &str,
+        name: &str,
+    ) -> Result<usize>
+    where
+        Self: Send + 'static,
+    {
+        let m2 = self.clone();
+        let env = env.to_string();
+        let name = name.to_string();
+        conn.with_sync(move |conn| m2.seed_named(conn, &env, &name))
+            .await
+    }
+
+    /// Get the SQL which [`migrate`][Migrations::migrate] would execute
+    /// on the connection, without applying anything. The SQL of each
+    /// unapplied migration is preceded by a comment naming it.
+    fn pending_sql(&self, conn: &impl BackendConnection) -> Result<String> {
+        let backend_name = conn.backend_name();
+        let mut sql = String::new();
+        for migration in self.unapplied_migrations(conn)? {
+            sql.push_str(&migration.apply_sql(backend_name)?);
         }
-        Ok(None)
+        Ok(sql)
+    }
+
+    /// SQL creating the schema of the latest migration from scratch with
+    /// `backend`: every table, with its constraints and indexes. Useful
+    /// as documentation, or for provisioning tools which take a single
+    /// SQL file rather than a chain of migrations.
+    fn schema_sql(&self, backend: &dyn Backend) -> Result<String> {
+        let db = match self.latest() {
+            Some(m) => m.db()?,
+            None => ADB::new(),
+        };
+        backend.create_migration_sql(&ADB::new(), adb::diff(&ADB::new(), &db))
+    }
+
+    /// Compare the schema of the database with the schema expected after
+    /// the last migration applied to it, as read by
+    /// [`introspect`][ConnectionMethods::introspect]. Returns the
+    /// differences found, which is empty if the database matches.
+    fn check_drift(&self, conn: &impl BackendConnection) -> Result<Vec<SchemaDrift>> {
+        let expected = match self.last_applied_migration(conn)? {
+            Some(m) => m.db()?,
+            None => ADB::new(),
+        };
+        let mut actual = conn.introspect()?;
+        actual.remove_table(ButaneMigration::TABLE);
+        Ok(schema_drift(&expected, &actual, conn.backend().as_ref()))
+    }
+
+    #[cfg(feature = "async")]
+    /// Compare the schema of the database with the schema expected after
+    /// the last migration applied to it. See [`check_drift`][Migrations::check_drift].
+    async fn check_drift_async(&self, conn: &mut ConnectionAsync) -> Result<Vec<SchemaDrift>>
+    where
+        Self: Send + 'static,
+    {
+        let m2 = self.clone();
+        conn.with_sync(move |conn| m2.check_drift(conn)).await
     }
 
     /// Migrate connection forward.
+    ///
+    /// The backend's migration lock is held while migrating, so several
+    /// connections migrating the same database at once apply each
+    /// migration only once.
     fn migrate(&self, connection: &mut impl BackendConnection) -> Result<()> {
-        let to_apply = self.unapplied_migrations(connection)?;
-        for migration in &to_apply {
-            crate::info!("Applying migration {}", migration.name());
-            migration.apply(connection)?;
+        self.migrate_with_callbacks(connection, &MigrateCallbacks::default())
+    }
+
+    /// Migrate connection forward, like [`migrate`][Migrations::migrate],
+    /// running `callbacks` before and after each migration is applied
+    /// and once all are. The callbacks run while the migration lock is
+    /// held, outside the transaction of any migration.
+    fn migrate_with_callbacks(
+        &self,
+        connection: &mut impl BackendConnection,
+        callbacks: &MigrateCallbacks,
+    ) -> Result<()> {
+        with_migration_lock(connection, |connection| {
+            check_not_forked(self, connection)?;
+            let to_apply = self.unapplied_migrations(connection)?;
+            let mut applied = Vec::with_capacity(to_apply.len());
+            for migration in &to_apply {
+                let name = migration.name();
+                for callback in &callbacks.before_each {
+                    callback(&name, &*connection)?;
+                }
+                crate::info!("Applying migration {}", name);
+                migration.apply(connection)?;
+                for callback in &callbacks.after_each {
+                    callback(&name, &*connection)?;
+                }
+                applied.push(name.to_string());
+            }
+            for callback in &callbacks.after_all {
+                callback(&applied, &*connection)?;
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "async")]
+    /// Migrate connection forward, running `callbacks`. See
+    /// [`migrate_with_callbacks`][Migrations::migrate_with_callbacks].
+    async fn migrate_with_callbacks_async(
+        &self,
+        conn: &mut ConnectionAsync,
+        callbacks: MigrateCallbacks,
+    ) -> Result<()>
+    where
+        Self: Send + 'static,
+    {
+        let m2 = self.clone();
+        conn.with_sync(move |conn| m2.migrate_with_callbacks(conn, &callbacks))
+            .await
+    }
+
+    /// The migrations to apply or undo so that the migration `name` is
+    /// the last one applied to the database.
+    fn migrations_to(
+        &self,
+        conn: &impl ConnectionMethods,
+        name: &str,
+    ) -> Result<MigrationSteps<Self::M>> {
+        let all = self.all_migrations()?;
+        let target = all
+            .iter()
+            .position(|m| m.name() == name)
+            .ok_or_else(|| Error::MigrationError(format!("Unknown migration {name}")))?;
+        let next = match self.last_applied_migration(conn)? {
+            None => 0,
+            Some(applied) => {
+                all.iter()
+                    .position(|m| *m == applied)
+                    .ok_or_else(|| Error::MigrationError("Migration not in chain".to_string()))?
+                    + 1
+            }
+        };
+        let mut all = all;
+        let mut steps = MigrationSteps::default();
+        if target >= next {
+            all.truncate(target + 1);
+            steps.to_apply = all.split_off(next);
+        } else {
+            all.truncate(next);
+            steps.to_undo = all.split_off(target + 1);
+            steps.to_undo.reverse();
         }
-        Ok(())
+        Ok(steps)
+    }
+
+    /// Apply or undo migrations so that the migration `name` is the
+    /// last one applied to the database. As with
+    /// [`migrate`][Migrations::migrate], the backend's migration lock
+    /// is held meanwhile.
+    fn migrate_to(&self, connection: &mut impl BackendConnection, name: &str) -> Result<()> {
+        with_migration_lock(connection, |connection| {
+            check_not_forked(self, connection)?;
+            let steps = self.migrations_to(connection, name)?;
+            for migration in &steps.to_apply {
+                crate::info!("Applying migration {}", migration.name());
+                migration.apply(connection)?;
+            }
+            for migration in &steps.to_undo {
+                crate::info!("Rolling back migration {}", migration.name());
+                migration.downgrade(connection)?;
+            }
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "async")]
+    /// Apply or undo migrations so that the migration `name` is the
+    /// last one applied. See [`migrate_to`][Migrations::migrate_to].
+    async fn migrate_to_async(&self, conn: &mut ConnectionAsync, name: &str) -> Result<()>
+    where
+        Self: Send + 'static,
+    {
+        let m2 = self.clone();
+        let name = name.to_string();
+        conn.with_sync(move |conn| m2.migrate_to(conn, &name)).await
+    }
+
+    /// Record the migrations up to and including `name` as applied to
+    /// the database without running their SQL. This is for adopting
+    /// butane onto a database whose schema already matches them. The
+    /// migrations table is created if it does not exist yet. Returns
+    /// the migrations newly recorded as applied.
+    fn fake_migrate_to(
+        &self,
+        connection: &mut impl BackendConnection,
+        name: &str,
+    ) -> Result<Vec<Self::M>> {
+        with_migration_lock(connection, |connection| {
+            let mut chain = self.all_migrations()?;
+            let target = chain
+                .iter()
+                .position(|m| m.name() == name)
+                .ok_or_else(|| Error::MigrationError(format!("Unknown migration {name}")))?;
+            chain.truncate(target + 1);
+            ensure_migrations_table(connection)?;
+            let applied = applied_migration_names(connection)?;
+            let mut faked = Vec::new();
+            for m in chain {
+                if !is_applied(&m, &applied)? {
+                    faked.push(m);
+                }
+            }
+            let tx = connection.transaction()?;
+            for m in &faked {
+                crate::info!("Recording migration {} as applied", m.name());
+                m.mark_applied(&tx)?;
+            }
+            tx.commit()?;
+            Ok(faked)
+        })
+    }
+
+    #[cfg(feature = "async")]
+    /// Record the migrations up to and including `name` as applied
+    /// without running their SQL. See [`fake_migrate_to`][Migrations::fake_migrate_to].
+    async fn fake_migrate_to_async(
+        &self,
+        conn: &mut ConnectionAsync,
+        name: &str,
+    ) -> Result<Vec<Self::M>>
+    where
+        Self: Send + 'static,
+        Self::M: Send,
+    {
+        let m2 = self.clone();
+        let name = name.to_string();
+        conn.with_sync(move |conn| m2.fake_migrate_to(conn, &name))
+            .await
     }
 
     #[cfg(feature = "async")]
@@ -137,22 +497,71 @@ async fn migrate_async(&self, conn: &mut ConnectionAsync) -> Result<()>
         .await
     }
 
+    /// Undo the last `n` migrations applied to the database, using
+    /// their down SQL. Fails without undoing anything if fewer than `n`
+    /// migrations are applied, or if any of them has no down SQL for
+    /// the backend. As with [`migrate`][Migrations::migrate], the
+    /// backend's migration lock is held meanwhile.
+    fn rollback(&self, connection: &mut impl BackendConnection, n: usize) -> Result<()> {
+        rollback_migrations(self, connection, n, false)?;
+        Ok(())
+    }
+
+    /// Undo the last `n` migrations applied to the database like
+    /// [`rollback`][Migrations::rollback], but first copy the data
+    /// which undoing each migration drops into backup tables: a dropped
+    /// table into `<table>__backup_<timestamp>`, and the dropped columns
+    /// of a table, along with its primary key, likewise. Useful to try
+    /// out rolling back during development without losing data.
+    /// Returns the names of the backup tables.
+    fn rollback_with_backup(
+        &self,
+        connection: &mut impl BackendConnection,
+        n: usize,
+    ) -> Result<Vec<String>> {
+        rollback_migrations(self, connection, n, true)
+    }
+
+    /// Undo the last `n` migrations applied to the database.
+    /// See [`rollback`][Migrations::rollback].
+    #[cfg(feature = "async")]
+    async fn rollback_async(&self, conn: &mut ConnectionAsync, n: usize) -> Result<()>
+    where
+        Self: Send + 'static,
+    {
+        let m2 = self.clone();
+        conn.with_sync(move |conn| m2.rollback(conn, n)).await
+    }
+
+    /// Undo the last `n` migrations applied to the database, backing up
+    /// the data they drop.
+    /// See [`rollback_with_backup`][Migrations::rollback_with_backup].
+    #[cfg(feature = "async")]
+    async fn rollback_with_backup_async(
+        &self,
+        conn: &mut ConnectionAsync,
+        n: usize,
+    ) -> Result<Vec<String>>
+    where
+        Self: Send + 'static,
+    {
+        let m2 = self.clone();
+        conn.with_sync(move |conn| m2.rollback_with_backup(conn, n))
+            .await
+    }
+
     /// Remove all applied migrations.
+    ///
+    /// As with [`migrate`][Migrations::migrate], the backend's migration
+    /// lock is held meanwhile.
     fn unmigrate(&self, connection: &mut impl BackendConnection) -> Result<()> {
-        let mut migration = match self.last_applied_migration(connection)? {
-            Some(migration) => migration,
-            None => return Ok(()),
-        };
-        migration.downgrade(connection)?;
-
-        while let Ok(Some(migration_name)) = migration.migration_from() {
-            migration = self
-                .get_migration(&migration_name)
-                .ok_or(Error::MigrationError("Migration not in chain".to_string()))?;
-            crate::info!("Rolling back migration {}", migration.name());
-            migration.downgrade(connection)?;
-        }
-        Ok(())
+        with_migration_lock(connection, |connection| {
+            for migration in self.applied_migrations(connection)?.iter().rev() {
+                crate::info!("Rolling back migration {}", migration.name());
+                migration.downgrade(connection)?;
+            }
+            Ok(())
+        })
     }
 
     /// Remove all applied migrations.
@@ -170,6 +579,47 @@ async fn unmigrate_async(&self, conn: &mut ConnectionAsync) -> Result<()>
     }
 }
 
+/// The state of a migration in a database, as found by
+/// [`Migrations::status`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MigrationStatus {
+    /// The name of the migration.
+    pub name: String,
+    /// Whether the migration is applied to the database.
+    pub applied: bool,
+    /// When the migration was applied. `None` if it is not applied, or
+    /// was applied by a version of butane which did not record this.
+    pub applied_at: Option<SystemTime>,
+    /// Whether the [checksum][Migration::checksum] of the migration is
+    /// the one recorded when it was applied, which it is not if the
+    /// migration was edited afterwards. `None` if it is not applied, or
+    /// no checksum was recorded.
+    pub checksum_matches: Option<bool>,
+    /// Whether the migration can be rolled back on the connection's
+    /// backend, see [`Migration::has_down`].
+    pub has_down: bool,
+}
+
+/// Migrations to apply or undo to bring a database to a given
+/// migration, as found by [`Migrations::migrations_to`]. At most one
+/// of the lists is non-empty.
+#[derive(Clone, Debug)]
+pub struct MigrationSteps<M> {
+    /// Migrations to apply, in order.
+    pub to_apply: Vec<M>,
+    /// Migrations to undo, latest first.
+    pub to_undo: Vec<M>,
+}
+
+impl<M> Default for MigrationSteps<M> {
+    fn default() -> Self {
+        MigrationSteps {
+            to_apply: Vec::new(),
+            to_undo: Vec::new(),
+        }
+    }
+}
+
 /// Extension of [`Migrations`] to modify the series of migrations.
 pub trait MigrationsMut: Migrations
 where
@@ -185,11 +635,38 @@ pub trait MigrationsMut: Migrations
     /// details. Unnecessary when using `create_migration`.
     fn add_migration(&mut self, m: Self::M) -> Result<()>;
 
+    /// Deletes the migration with the given name from this object (and
+    /// any storage backing it). If it was the latest migration, the
+    /// migration before it becomes the latest. Migrations based on the
+    /// deleted migration are not modified. The database is not modified.
+    fn delete_migration(&mut self, name: &str) -> Result<()>;
+
     /// Deletes all migrations -- deleting them from this object (and
     /// any storage backing it). The database schema is not modified,
     /// nor is any other data removed. Use carefully.
     fn delete_migrations(&mut self) -> Result<()>;
 
+    /// Add a [seed][Seed], replacing any seed of the same name for the
+    /// same environment.
+    fn add_seed(&mut self, seed: Seed) -> Result<()>;
+
+    /// The SQLite options of tables whose model does not give its own
+    /// with a `#[sqlite(...)]` attribute.
+    fn default_sqlite_options(&self) -> Result<SqliteTableOptions> {
+        Ok(SqliteTableOptions::default())
+    }
+
+    /// Describe in plain language the changes to the models since the
+    /// latest migration, which the next migration created would make.
+    /// Each change is one line, such as `Add column Post.tags (json, not
+    /// null)`. Empty if the models have not changed.
+    fn describe_changes(&self) -> Result<Vec<String>> {
+        Ok(pending_operations(self)?
+            .iter()
+            .map(ToString::to_string)
+            .collect())
+    }
+
     /// Clears all migrations -- deleting them from this object (and
     /// any storage backing it) and deleting the record of their
     /// existence/application from the database. The database schema
@@ -236,58 +713,810 @@ fn create_migration_to(
         from: Option<&Self::M>,
         to_db: ADB,
     ) -> Result<bool> {
-        let empty_db = Ok(ADB::new());
-        let from_none = from.is_none();
-        let from_db = from.map_or(empty_db, |m| m.db())?;
-        let mut ops = adb::diff(&from_db, &to_db);
-        if ops.is_empty() {
-            return Ok(false);
-        }
+        create_migration_to(self, backends, name, from, to_db, false)
+    }
 
+    /// Like [`create_migration`][MigrationsMut::create_migration], but
+    /// the migration is "online": operations which would block writes
+    /// to a table for as long as they take are done in a way which does
+    /// not, where the backend can. On PostgreSQL indexes are created and
+    /// dropped concurrently, and a `NOT NULL` constraint is checked by a
+    /// validated `CHECK` constraint first. As these cannot run in a
+    /// transaction, the migration is not [atomic][Migration::atomic].
+    fn create_online_migration(
+        &mut self,
+        backends: &NonEmpty<Box<dyn Backend>>,
+        name: &str,
+        from: Option<&Self::M>,
+    ) -> Result<bool> {
+        let to_db = self.current().db()?;
+        self.create_online_migration_to(backends, name, from, to_db)
+    }
+
+    /// Like [`create_online_migration`][MigrationsMut::create_online_migration],
+    /// but the migration is to `to_db`.
+    fn create_online_migration_to(
+        &mut self,
+        backends: &NonEmpty<Box<dyn Backend>>,
+        name: &str,
+        from: Option<&Self::M>,
+        to_db: ADB,
+    ) -> Result<bool> {
+        create_migration_to(self, backends, name, from, to_db, true)
+    }
+
+    /// Create a migration `from` -> `from` named `name`, which makes no
+    /// schema changes and has empty SQL for each of `backends`. Its SQL
+    /// is to be written by hand, for changes which cannot be expressed
+    /// in models, such as triggers, extensions or data fixes. From may
+    /// be None, in which case the migration is the first one.
+    fn create_empty_migration(
+        &mut self,
+        backends: &NonEmpty<Box<dyn Backend>>,
+        name: &str,
+        from: Option<&Self::M>,
+    ) -> Result<Self::M> {
+        self.create_migration_from_steps(backends, name, from, Vec::new())
+    }
+
+    /// Create a migration from `from` named `name` which consists of
+    /// `steps`. Schema changes are given as [operations][Operation],
+    /// which each backend renders into its own SQL, and may be mixed
+    /// with SQL for particular backends only. The steps are stored with
+    /// the migration, so that SQL can be rendered from them for
+    /// backends added later; see [`render_steps`]. From may be None, in
+    /// which case the migration is the first one.
+    fn create_migration_from_steps(
+        &mut self,
+        backends: &NonEmpty<Box<dyn Backend>>,
+        name: &str,
+        from: Option<&Self::M>,
+        steps: Vec<MigrationStep>,
+    ) -> Result<Self::M> {
+        if self.get_migration(name).is_some() {
+            return Err(Error::MigrationError(format!(
+                "Migration {name} already exists"
+            )));
+        }
+        let from_db = from.map_or(Ok(ADB::new()), |m| m.db())?;
+        let mut to_db = from_db.clone();
         let mut modified_tables: Vec<String> = Vec::new();
+        for step in &steps {
+            if let MigrationStep::Operation(op) = step {
+                modified_tables.extend(modified_table(op).map(str::to_string));
+                to_db.transform_with((**op).clone());
+            }
+        }
 
-        for op in &ops {
-            match op {
-                Operation::AddTable(table)
-                | Operation::AddTableConstraints(table)
-                | Operation::AddTableIfNotExists(table) => modified_tables.push(table.name.clone()),
-                Operation::AddColumn(table_name, _) => modified_tables.push(table_name.clone()),
-                Operation::RemoveColumn(table_name, _) => modified_tables.push(table_name.clone()),
-                Operation::ChangeColumn(table_name, _, _) => {
-                    modified_tables.push(table_name.clone())
+        let mut m = self.new_migration(name);
+        for table in to_db.tables() {
+            // Renaming a table also changes the foreign keys referring to it
+            let unmodified = !modified_tables.contains(&table.name)
+                && from_db.get_table(&table.name) == Some(table);
+            match from {
+                Some(from) if unmodified => {
+                    m.add_unmodified_table(table, &from.name())?;
+                }
+                _ => m.add_modified_table(table)?,
+            }
+        }
+        for ty in to_db.custom_types() {
+            m.add_type_definition(ty)?;
+        }
+        for backend in backends {
+            let (mut up_sql, down_sql) = render_steps(backend.as_ref(), &from_db, &steps)?;
+            if from.is_none() {
+                // The first migration creates the butane_migration table.
+                let sql = backend.create_migration_sql(
+                    &to_db,
+                    vec![Operation::AddTableIfNotExists(migrations_table())],
+                )?;
+                if !up_sql.is_empty() {
+                    up_sql.push('\n');
                 }
-                Operation::RemoveTable(_) | Operation::RemoveTableConstraints(_) => {}
+                up_sql.push_str(&sql);
             }
+            m.add_sql(backend.name(), &up_sql, &down_sql)?;
         }
+        if !steps.is_empty() {
+            m.set_steps(steps)?;
+        }
+        m.set_migration_from(from.map(|m| m.name().to_string()))?;
+        self.add_migration(m)?;
+        self.get_migration(name)
+            .ok_or_else(|| Error::MigrationError(format!("Unknown migration {name}")))
+    }
 
-        if from_none {
-            // This may be the first migration. Create the butane_migration table
-            ops.push(Operation::AddTableIfNotExists(migrations_table()));
+    /// Replace the linear chain of migrations from the start to the end
+    /// of `range` (inclusive) with a single migration named `name`, whose
+    /// SQL for `backends` is regenerated to go directly from the state
+    /// before the first migration to the state after the last. Migrations
+    /// after the range are kept as they are, but now follow the new
+    /// migration.
+    ///
+    /// The new migration records the names of those it replaces, so a
+    /// database which has applied the last of them considers it
+    /// applied. Hand-edited SQL and [hooks][MigrationHooks] of the
+    /// replaced migrations are not carried over. If squashing fails,
+    /// the migrations are left as they were.
+    fn squash(
+        &mut self,
+        backends: &NonEmpty<Box<dyn Backend>>,
+        name: &str,
+        range: RangeInclusive<&str>,
+    ) -> Result<Self::M> {
+        let (first, last) = range.into_inner();
+        let all = self.all_migrations()?;
+        let position = |target: &str| {
+            all.iter()
+                .position(|m| m.name() == target)
+                .ok_or_else(|| Error::MigrationError(format!("Unknown migration {target}")))
+        };
+        let (first_idx, last_idx) = (position(first)?, position(last)?);
+        if first_idx >= last_idx {
+            return Err(Error::MigrationError(format!(
+                "Migration {first} must come before {last} to squash them"
+            )));
+        }
+        let replaced = &all[first_idx..=last_idx];
+        if self.get_migration(name).is_some() && !replaced.iter().any(|m| m.name() == name) {
+            return Err(Error::MigrationError(format!(
+                "Migration {name} already exists"
+            )));
+        }
+        let from_name = replaced[0].migration_from()?.map(|s| s.to_string());
+        let to_db = replaced[replaced.len() - 1].db()?;
+        let replaces: Vec<String> = replaced.iter().map(|m| m.name().to_string()).collect();
+
+        let from = from_name.as_deref().and_then(|n| self.get_migration(n));
+        let from_db = from.map_or(Ok(ADB::new()), |m| m.db())?;
+        if adb::diff(&from_db, &to_db).is_empty() {
+            return Err(Error::MigrationError(format!(
+                "Migrations {first} to {last} make no changes"
+            )));
+        }
+
+        // Keep a copy of the replaced and later migrations. The later ones
+        // are recreated on top of the new one, and all of them are
+        // restored if squashing fails part way.
+        let mut originals = Vec::new();
+        for m in &all[first_idx..] {
+            let mut copy = MemMigration::new(m.name().to_string());
+            copy_migration(m, &mut copy)?;
+            originals.push(copy);
+        }
+        let later = &originals[replaced.len()..];
+        if let Err(e) = replace_migrations(
+            self,
+            backends,
+            name,
+            from_name.as_deref(),
+            to_db,
+            replaces,
+            &originals,
+            later,
+        ) {
+            #[allow(unused_variables)] // used only when logging is enabled
+            if let Err(restore_err) = restore_migrations(self, name, &originals) {
+                crate::warn!("Cannot restore migrations after failed squash: {restore_err}");
+            }
+            return Err(e);
+        }
+        self.get_migration(name)
+            .ok_or_else(|| Error::MigrationError(format!("Unknown migration {name}")))
+    }
+
+    /// Create a migration named `name` which merges `heads`, migrations
+    /// created in parallel with the [latest][Migrations::latest]
+    /// migration from a common ancestor, into the chain. The new
+    /// migration follows the latest one and its schema combines the
+    /// changes of both; it has no SQL of its own, as applying it means
+    /// applying the migrations on the merged branches.
+    ///
+    /// Fails if a branch changes a table which is also changed since
+    /// the common ancestor in the chain, as such changes must be
+    /// reconciled by hand.
+    fn merge_migrations(
+        &mut self,
+        backends: &NonEmpty<Box<dyn Backend>>,
+        name: &str,
+        heads: &[Self::M],
+    ) -> Result<Self::M> {
+        let latest = self
+            .latest()
+            .ok_or_else(|| Error::MigrationError("There are no migrations".to_string()))?;
+        if heads.is_empty() {
+            return Err(Error::MigrationError(
+                "There are no migrations to merge".to_string(),
+            ));
+        }
+        if self.get_migration(name).is_some() {
+            return Err(Error::MigrationError(format!(
+                "Migration {name} already exists"
+            )));
+        }
+        let mut chain_names: BTreeSet<String> = self
+            .all_migrations()?
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect();
+        let latest_db = latest.db()?;
+        let mut merged_db = latest_db.clone();
+        for head in heads {
+            if chain_names.contains(head.name().as_ref()) {
+                return Err(Error::MigrationError(format!(
+                    "Migration {} is already in the migration chain",
+                    head.name()
+                )));
+            }
+            // The branch consists of the head and its ancestors which are not in the chain.
+            let branch = migration_chain(self, self.get_migration(&head.name()))?;
+            let base_db = match branch
+                .iter()
+                .rev()
+                .find(|m| chain_names.contains(m.name().as_ref()))
+            {
+                Some(base) => base.db()?,
+                None => ADB::new(),
+            };
+            let chain_ops = adb::diff(&base_db, &merged_db);
+            let chain_tables: BTreeSet<&str> =
+                chain_ops.iter().filter_map(modified_table).collect();
+            let branch_ops = adb::diff(&base_db, &head.db()?);
+            for table in branch_ops.iter().filter_map(modified_table) {
+                if chain_tables.contains(table) {
+                    return Err(Error::MigrationError(format!(
+                        "Migration {} and the migration chain both change table {table}; merge them by hand",
+                        head.name()
+                    )));
+                }
+            }
+            for op in branch_ops {
+                merged_db.transform_with(op);
+            }
+            chain_names.extend(branch.iter().map(|m| m.name().to_string()));
         }
 
         let mut m = self.new_migration(name);
-        // Save the DB for use by other migrations from this one
-        for table in to_db.tables() {
-            if modified_tables.contains(&table.name) {
-                m.add_modified_table(table)?;
+        for table in merged_db.tables() {
+            if latest_db.get_table(&table.name) == Some(table) {
+                m.add_unmodified_table(table, &latest.name())?;
             } else {
-                let from =
-                    from.ok_or(Error::MigrationError("unmodified requires a from".into()))?;
-                m.add_unmodified_table(table, &from.name())?;
+                m.add_modified_table(table)?;
             }
         }
-
+        for ty in merged_db.custom_types() {
+            m.add_type_definition(ty)?;
+        }
         for backend in backends {
-            let up_sql = backend.create_migration_sql(&from_db, ops.clone())?;
-            let down_sql = backend.create_migration_sql(&to_db, adb::diff(&to_db, &from_db))?;
-            m.add_sql(backend.name(), &up_sql, &down_sql)?;
+            m.add_sql(backend.name(), "", "")?;
         }
+        m.set_migration_from(Some(latest.name().to_string()))?;
+        m.set_merges(heads.iter().map(|h| h.name().to_string()).collect())?;
+        self.add_migration(m)?;
+        self.get_migration(name)
+            .ok_or_else(|| Error::MigrationError(format!("Unknown migration {name}")))
+    }
+}
 
-        m.set_migration_from(from.map(|m| m.name().to_string()))?;
+/// The migrations leading up to and including `head`, each after
+/// those it is based on.
+fn migration_chain<Ms: Migrations>(ms: &Ms, head: Option<Ms::M>) -> Result<Vec<Ms::M>> {
+    let mut chain: Vec<Ms::M> = Vec::new();
+    let mut visited: BTreeSet<String> = BTreeSet::new();
+    // Depth-first, adding each migration once all those it is based on are added.
+    let mut stack: Vec<(Ms::M, bool)> = head.map(|m| (m, false)).into_iter().collect();
+    while let Some((m, parents_added)) = stack.pop() {
+        if parents_added {
+            chain.push(m);
+            continue;
+        }
+        if !visited.insert(m.name().to_string()) {
+            continue;
+        }
+        let mut parents: Vec<String> = m
+            .migration_from()?
+            .map(|s| s.to_string())
+            .into_iter()
+            .collect();
+        parents.extend(m.merges()?);
+        stack.push((m, true));
+        for name in parents.iter().rev() {
+            if let Some(parent) = ms.get_migration(name) {
+                stack.push((parent, false));
+            }
+        }
+    }
+    Ok(chain)
+}
 
-        self.add_migration(m)?;
-        Ok(true)
+/// Render the up and down SQL of a migration consisting of `steps` for
+/// `backend`, starting from the schema `from_db`. SQL steps for other
+/// backends are left out.
+pub fn render_steps(
+    backend: &dyn Backend,
+    from_db: &ADB,
+    steps: &[MigrationStep],
+) -> Result<(String, String)> {
+    let mut db = from_db.clone();
+    let mut up: Vec<String> = Vec::new();
+    let mut down: Vec<String> = Vec::new();
+    let mut ops: Vec<Operation> = Vec::new();
+    for step in steps {
+        match step {
+            MigrationStep::Operation(op) => ops.push((**op).clone()),
+            MigrationStep::Sql {
+                up: up_sql,
+                down: down_sql,
+                ..
+            } => {
+                render_operations(backend, &mut db, &mut ops, &mut up, &mut down)?;
+                if step.applies_to(backend.name()) {
+                    up.push(up_sql.clone());
+                    down.push(down_sql.clone());
+                }
+            }
+        }
+    }
+    render_operations(backend, &mut db, &mut ops, &mut up, &mut down)?;
+    // Steps are undone in reverse order.
+    down.reverse();
+    up.retain(|s| !s.is_empty());
+    down.retain(|s| !s.is_empty());
+    Ok((up.join("\n"), down.join("\n")))
+}
+
+/// Render the up and down SQL for `backend` of a migration between two
+/// arbitrary schemas, such as snapshots saved by other tools or the
+/// schemas of two migrations which do not follow each other. The up
+/// SQL changes a database with the schema `old` to `new`, and the down
+/// SQL changes it back. The [operations][Operation] themselves are given
+/// by [`adb::diff`].
+pub fn diff_sql(backend: &dyn Backend, old: &ADB, new: &ADB) -> Result<(String, String)> {
+    let up = backend.create_migration_sql(old, adb::diff(old, new))?;
+    let down = backend.create_migration_sql(new, adb::diff(new, old))?;
+    Ok((up, down))
+}
+
+/// Render the up and down SQL of `ops`, applying them to `db` and
+/// leaving `ops` empty.
+fn render_operations(
+    backend: &dyn Backend,
+    db: &mut ADB,
+    ops: &mut Vec<Operation>,
+    up: &mut Vec<String>,
+    down: &mut Vec<String>,
+) -> Result<()> {
+    if ops.is_empty() {
+        return Ok(());
+    }
+    let before = db.clone();
+    up.push(backend.create_migration_sql(&before, ops.clone())?);
+    for op in ops.drain(..) {
+        db.transform_with(op);
+    }
+    down.push(backend.create_migration_sql(db, adb::diff(db, &before))?);
+    Ok(())
+}
+
+/// Create a migration `from` -> `to_db` named `name`, see
+/// [`MigrationsMut::create_migration_to`] and
+/// [`MigrationsMut::create_online_migration`].
+fn create_migration_to<Ms: MigrationsMut>(
+    ms: &mut Ms,
+    backends: &NonEmpty<Box<dyn Backend>>,
+    name: &str,
+    from: Option<&Ms::M>,
+    to_db: ADB,
+    online: bool,
+) -> Result<bool>
+where
+    Ms::M: MigrationMut,
+{
+    let empty_db = Ok(ADB::new());
+    let from_none = from.is_none();
+    let from_db = from.map_or(empty_db, |m| m.db())?;
+    let mut ops = adb::diff(&from_db, &to_db);
+    if ops.is_empty() {
+        return Ok(false);
+    }
+
+    let modified_tables: Vec<String> = ops
+        .iter()
+        .filter_map(modified_table)
+        .map(str::to_string)
+        .collect();
+
+    if from_none {
+        // This may be the first migration. Create the butane_migration table
+        ops.push(Operation::AddTableIfNotExists(migrations_table()));
+    }
+
+    let mut m = ms.new_migration(name);
+    // Save the DB for use by other migrations from this one
+    for table in to_db.tables() {
+        if modified_tables.contains(&table.name) {
+            // Conversions and backfills only apply to this migration's changes
+            let mut table = table.clone();
+            for column in &mut table.columns {
+                column.set_convert_using(None);
+                column.set_backfill(None);
+            }
+            m.add_modified_table(&table)?;
+        } else {
+            let from = from.ok_or(Error::MigrationError("unmodified requires a from".into()))?;
+            m.add_unmodified_table(table, &from.name())?;
+        }
+    }
+    for ty in to_db.custom_types() {
+        m.add_type_definition(ty)?;
+    }
+
+    for backend in backends {
+        let up_sql = if online {
+            backend.create_online_migration_sql(&from_db, ops.clone())?
+        } else {
+            backend.create_migration_sql(&from_db, ops.clone())?
+        };
+        let down_sql = backend.create_migration_sql(&to_db, adb::diff(&to_db, &from_db))?;
+        m.add_sql(backend.name(), &up_sql, &down_sql)?;
+    }
+
+    m.set_migration_from(from.map(|m| m.name().to_string()))?;
+    if online {
+        m.set_atomic(false)?;
+    }
+
+    ms.add_migration(m)?;
+    Ok(true)
+}
+
+/// Replace `originals` by the migration `name` from `from` to `to_db`,
+/// replacing `replaces`, followed by `later` recreated on top of it.
+#[allow(clippy::too_many_arguments)]
+fn replace_migrations<Ms: MigrationsMut>(
+    ms: &mut Ms,
+    backends: &NonEmpty<Box<dyn Backend>>,
+    name: &str,
+    from: Option<&str>,
+    to_db: ADB,
+    replaces: Vec<String>,
+    originals: &[MemMigration],
+    later: &[MemMigration],
+) -> Result<()>
+where
+    Ms::M: MigrationMut,
+{
+    for m in originals.iter().rev() {
+        ms.delete_migration(&m.name())?;
+    }
+    let from = from.and_then(|n| ms.get_migration(n));
+    if !ms.create_migration_to(backends, name, from.as_ref(), to_db)? {
+        return Err(Error::MigrationError(format!(
+            "Migration {name} makes no changes"
+        )));
+    }
+    let mut squashed = ms
+        .get_migration(name)
+        .ok_or_else(|| Error::MigrationError(format!("Unknown migration {name}")))?;
+    squashed.set_replaces(replaces)?;
+    // Store the updated migration, in case it is not backed by storage.
+    ms.add_migration(squashed)?;
+
+    let mut prev = name.to_string();
+    for copy in later {
+        let mut m = ms.new_migration(&copy.name());
+        copy_migration(copy, &mut m)?;
+        m.set_migration_from(Some(prev))?;
+        prev = m.name().to_string();
+        ms.add_migration(m)?;
+    }
+    Ok(())
+}
+
+/// Put back `originals` in place of whatever a failed squash into the
+/// migration `name` left of them.
+fn restore_migrations<Ms: MigrationsMut>(
+    ms: &mut Ms,
+    name: &str,
+    originals: &[MemMigration],
+) -> Result<()>
+where
+    Ms::M: MigrationMut,
+{
+    let mut names: Vec<String> = originals.iter().map(|m| m.name().to_string()).collect();
+    names.push(name.to_string());
+    // Latest first, so that the latest migration is that before them.
+    for m in ms.all_migrations()?.iter().rev() {
+        if names.iter().any(|n| *n == m.name()) {
+            ms.delete_migration(&m.name())?;
+        }
+    }
+    for n in &names {
+        if ms.get_migration(n).is_some() {
+            ms.delete_migration(n)?;
+        }
     }
+    for copy in originals {
+        let mut m = ms.new_migration(&copy.name());
+        copy_migration(copy, &mut m)?;
+        ms.add_migration(m)?;
+    }
+    Ok(())
+}
+
+/// Panic if the models do not match the latest migration of
+/// `migrations`, listing the differences. The models are those recorded
+/// in the current migration by the `#[model]` attribute as the crate
+/// is compiled, so this catches a model changed without running
+/// `butane makemigration`. Intended to be called from a test:
+///
+/// ```ignore
+/// #[test]
+/// fn migrations_are_current() {
+///     let ms = butane::migrations::from_root(concat!(
+///         env!("CARGO_MANIFEST_DIR"),
+///         "/.butane/migrations"
+///     ));
+///     butane::migrations::assert_current(&ms);
+/// }
+/// ```
+pub fn assert_current<Ms: MigrationsMut>(migrations: &Ms)
+where
+    Ms::M: MigrationMut,
+{
+    let changes = match migrations.describe_changes() {
+        Ok(changes) => changes,
+        Err(e) => panic!("Cannot compare the models with the migrations: {e}"),
+    };
+    if !changes.is_empty() {
+        let latest = migrations
+            .latest()
+            .map_or("none".to_string(), |m| m.name().to_string());
+        panic!(
+            "The models have changed since the latest migration ({latest}). Run `butane makemigration` to create a migration with these changes:\n  {}",
+            changes.join("\n  ")
+        );
+    }
+}
+
+/// The operations needed to bring the schema of the latest migration
+/// of `ms` to that of its current migration.
+fn pending_operations<Ms: MigrationsMut>(ms: &Ms) -> Result<Vec<Operation>>
+where
+    Ms::M: MigrationMut,
+{
+    let mut ms = ms.clone();
+    let from_db = match ms.latest() {
+        Some(m) => m.db()?,
+        None => ADB::new(),
+    };
+    let to_db = ms.current().db()?;
+    Ok(adb::diff(&from_db, &to_db))
+}
+
+/// Undo the last `n` migrations of `ms` applied to the database, first
+/// backing up the data they drop if `backup` is true. Returns the names
+/// of the backup tables.
+fn seed_migrations<Ms: Migrations>(
+    ms: &Ms,
+    conn: &impl ConnectionMethods,
+    env: &str,
+    name: Option<&str>,
+) -> Result<usize> {
+    if !ms.unapplied_migrations(conn)?.is_empty() {
+        return Err(Error::MigrationError(
+            "Migrations must be applied before seeding".to_string(),
+        ));
+    }
+    let db = match ms.latest() {
+        Some(m) => m.db()?,
+        None => ADB::new(),
+    };
+    let seeds = ms
+        .seeds()?
+        .into_iter()
+        .filter(|seed| seed.env() == env)
+        .collect();
+    let mut count = 0;
+    for seed in seeds::dependency_order(seeds, name)? {
+        crate::info!("Seeding {} for {env}", seed.name());
+        count += seed.apply(&db, conn)?;
+    }
+    Ok(count)
+}
+
+fn rollback_migrations<Ms: Migrations>(
+    ms: &Ms,
+    connection: &mut impl BackendConnection,
+    n: usize,
+    backup: bool,
+) -> Result<Vec<String>> {
+    with_migration_lock(connection, |connection| {
+        let backend_name = connection.backend_name();
+        let mut applied = ms.applied_migrations(connection)?;
+        if applied.len() < n {
+            return Err(Error::MigrationError(format!(
+                "Cannot roll back {n} migrations, only {} are applied",
+                applied.len()
+            )));
+        }
+        let mut to_undo = applied.split_off(applied.len() - n);
+        to_undo.reverse();
+        for migration in &to_undo {
+            if !migration.has_down(backend_name)? {
+                return Err(Error::MigrationError(format!(
+                    "Migration {} has no down SQL for backend {backend_name} and cannot be rolled back",
+                    migration.name()
+                )));
+            }
+        }
+        let mut backups = Vec::new();
+        for migration in &to_undo {
+            if backup {
+                let prev_db = match migration.migration_from()? {
+                    Some(from) => ms
+                        .get_migration(&from)
+                        .ok_or_else(|| Error::MigrationError(format!("No migration {from}")))?
+                        .db()?,
+                    None => ADB::default(),
+                };
+                backup::backup_dropped_data(connection, &migration.db()?, &prev_db, &mut backups)?;
+            }
+            crate::info!("Rolling back migration {}", migration.name());
+            migration.downgrade(connection)?;
+        }
+        Ok(backups)
+    })
+}
+
+/// The table modified by `op`, if any.
+fn modified_table(op: &Operation) -> Option<&str> {
+    match op {
+        Operation::AddTable(table)
+        | Operation::AddTableConstraints(table)
+        | Operation::AddTableIfNotExists(table) => Some(&table.name),
+        Operation::AddColumn(table_name, _)
+        | Operation::RemoveColumn(table_name, _)
+        | Operation::ChangeColumn(table_name, _, _)
+        | Operation::AddIndex(table_name, _)
+        | Operation::RemoveIndex(table_name, _)
+        | Operation::AddConstraint(table_name, _)
+        | Operation::RemoveConstraint(table_name, _)
+        | Operation::RenameTable(_, table_name)
+        | Operation::ChangeSqliteOptions(table_name, _) => Some(table_name),
+        Operation::RemoveTable(_)
+        | Operation::RemoveTableConstraints(_)
+        | Operation::AddCustomType(_)
+        | Operation::RemoveCustomType(_)
+        | Operation::ChangeCustomType(..) => None,
+    }
+}
+
+/// The names of the migrations recorded as applied to the database.
+fn applied_migration_names(conn: &impl ConnectionMethods) -> Result<Vec<String>> {
+    if !conn.has_table(ButaneMigration::TABLE)? {
+        return Ok(Vec::new());
+    }
+    let migrations: Vec<ButaneMigration> = conn
+        .query(
+            ButaneMigration::TABLE,
+            ButaneMigration::COLUMNS,
+            None,
+            None,
+            None,
+            None,
+        )?
+        .mapped(ButaneMigration::from_row)
+        .collect()?;
+    Ok(migrations.into_iter().map(|m| m.name).collect())
+}
+
+/// A row of the migrations table.
+struct AppliedRecord {
+    name: String,
+    applied_at: Option<i64>,
+    checksum: Option<String>,
+}
+
+/// The rows of the migrations table, reading only the columns which a
+/// table created by an earlier version of butane has.
+fn applied_migration_records(conn: &impl ConnectionMethods) -> Result<Vec<AppliedRecord>> {
+    let db = conn.introspect()?;
+    let Some(table) = db.get_table(ButaneMigration::TABLE) else {
+        return Ok(Vec::new());
+    };
+    let columns: Vec<Column> = ButaneMigration::STATUS_COLUMNS
+        .iter()
+        .filter(|column| table.column(column.name()).is_some())
+        .cloned()
+        .collect();
+    let mut rows = conn.query(ButaneMigration::TABLE, &columns, None, None, None, None)?;
+    let mut records = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut record = AppliedRecord {
+            name: FromSql::from_sql_ref(row.get(0, SqlType::Text)?)?,
+            applied_at: None,
+            checksum: None,
+        };
+        for (i, column) in columns.iter().enumerate().skip(1) {
+            let value = row.get(i, column.ty().clone())?;
+            match column.name() {
+                "applied_at" => record.applied_at = FromSql::from_sql_ref(value)?,
+                "checksum" => record.checksum = FromSql::from_sql_ref(value)?,
+                _ => {}
+            }
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Whether `m` is applied, given the names of the applied migrations.
+fn is_applied(m: &impl Migration, applied: &[String]) -> Result<bool> {
+    let is_applied = |name: &str| applied.iter().any(|a| a == name);
+    if is_applied(&m.name()) {
+        return Ok(true);
+    }
+    // A squashed migration is applied if the migrations it replaces were.
+    let replaces = m.replaces()?;
+    if let Some(last_replaced) = replaces.last() {
+        if is_applied(last_replaced) {
+            return Ok(true);
+        }
+        if replaces.iter().any(|name| is_applied(name)) {
+            return Err(Error::MigrationError(format!(
+                "Migration {} replaces migrations which are only partially applied",
+                m.name()
+            )));
+        }
+    }
+    Ok(false)
+}
+
+/// Fail if the database has applied a migration which is not in the
+/// chain of `ms`, as happens when migrations were created in parallel
+/// and have not been merged.
+fn check_not_forked(ms: &impl Migrations, conn: &impl ConnectionMethods) -> Result<()> {
+    let applied = applied_migration_names(conn)?;
+    if applied.is_empty() {
+        return Ok(());
+    }
+    let mut known: BTreeSet<String> = BTreeSet::new();
+    for m in ms.all_migrations()? {
+        known.extend(m.replaces()?);
+        known.insert(m.name().to_string());
+    }
+    match applied.iter().find(|name| !known.contains(*name)) {
+        Some(name) => Err(Error::MigrationError(format!(
+            "Migration {name} is applied to the database but is not in the migration chain. \
+             If migrations were created in parallel, merge them with `butane merge-migrations`"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Run `f` while holding the [migration lock][Backend::migration_lock_sql]
+/// of the connection's backend, if it has one. The lock is released
+/// whether or not `f` succeeds.
+fn with_migration_lock<C: BackendConnection, T>(
+    conn: &mut C,
+    f: impl FnOnce(&mut C) -> Result<T>,
+) -> Result<T> {
+    let lock = conn.backend().migration_lock_sql();
+    let Some((lock_sql, unlock_sql)) = lock else {
+        return f(conn);
+    };
+    if let Err(err) = conn.execute(lock_sql) {
+        // Undo any part of taking the lock which succeeded
+        let _ = conn.execute(unlock_sql);
+        return Err(err);
+    }
+    let result = f(conn);
+    let unlocked = conn.execute(unlock_sql);
+    let value = result?;
+    unlocked?;
+    Ok(value)
 }
 
 /// Returns [`ATable`] describing the migration metadata.
@@ -304,9 +1533,53 @@ pub fn migrations_table() -> ATable {
         None,  // references
     );
     table.add_column(col);
+    // Not recorded by earlier versions of butane.
+    for (name, ty) in [
+        // Seconds since the Unix epoch.
+        ("applied_at", SqlType::BigInt),
+        ("checksum", SqlType::Text),
+    ] {
+        table.add_column(AColumn::new(
+            name,
+            DeferredSqlType::KnownId(TypeIdentifier::Ty(ty)),
+            true,  // nullable
+            false, // pk
+            false, // auto
+            false, // unique
+            None,  // default
+            None,  // references
+        ));
+    }
+    // Created before any migration runs, so not STRICT, which older SQLite lacks
+    table.set_sqlite_options(SqliteTableOptions {
+        strict: false,
+        without_rowid: false,
+    });
     table
 }
 
+/// Create the table recording applied migrations if it does not
+/// exist, and add any columns missing from a table created by an
+/// earlier version of butane.
+pub fn ensure_migrations_table(conn: &impl BackendConnection) -> Result<()> {
+    let backend = conn.backend();
+    let expected = migrations_table();
+    let db = conn.introspect()?;
+    let ops = match db.get_table(&expected.name) {
+        None => vec![Operation::AddTableIfNotExists(expected)],
+        Some(table) => expected
+            .columns
+            .iter()
+            .filter(|column| table.column(column.name()).is_none())
+            .map(|column| Operation::AddColumn(expected.name.clone(), column.clone()))
+            .collect(),
+    };
+    if !ops.is_empty() {
+        conn.execute(&backend.create_migration_sql(&db, ops)?)?;
+    }
+    Ok(())
+}
+
 /// Create a `Migrations` from a filesystem location. The `#[model]`
 /// attribute will write migration information to a
 /// `butane/migrations` directory under the project directory.
@@ -317,6 +1590,10 @@ pub fn from_root<P: AsRef<Path>>(path: P) -> FsMigrations {
 /// Copies the data in `from` to `to`.
 pub fn copy_migration(from: &impl Migration, to: &mut impl MigrationMut) -> Result<()> {
     to.set_migration_from(from.migration_from()?.map(|s| s.to_string()))?;
+    to.set_replaces(from.replaces()?)?;
+    to.set_merges(from.merges()?)?;
+    to.set_steps(from.steps()?)?;
+    to.set_atomic(from.atomic()?)?;
     let db = from.db()?;
     for table in db.tables() {
         to.add_modified_table(table)?;
@@ -324,6 +1601,9 @@ pub fn copy_migration(from: &impl Migration, to: &mut impl MigrationMut) -> Resu
     for (k, v) in db.types() {
         to.add_type(k.clone(), v.clone())?;
     }
+    for ty in db.custom_types() {
+        to.add_type_definition(ty)?;
+    }
     for backend_name in from.sql_backends()? {
         let up_sql = from.up_sql(&backend_name)?;
         let down_sql = from.down_sql(&backend_name)?;
@@ -339,6 +1619,16 @@ struct ButaneMigration {
     name: String,
 }
 
+impl ButaneMigration {
+    /// All the columns of the migrations table, which a table created
+    /// by an earlier version of butane may not have.
+    const STATUS_COLUMNS: &'static [Column] = &[
+        Column::new("name", SqlType::Text),
+        Column::new("applied_at", SqlType::BigInt),
+        Column::new("checksum", SqlType::Text),
+    ];
+}
+
 #[async_trait]
 impl DataResult for ButaneMigration {
     type DBO = Self;
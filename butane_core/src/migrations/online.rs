@@ -0,0 +1,130 @@
+//! Helpers for changing the schema of a database which is in use.
+
+use super::adb::{Operation, ADB};
+use crate::db::helper::{quote_reserved_word, sql_literal_value};
+use crate::db::{BackendRows, Column, ConnectionMethods};
+use crate::query::{BoolExpr, Expr, Order, OrderDirection};
+use crate::{Result, SqlVal};
+
+/// Describe the operations in `ops`, applied to `db`, which lock a
+/// table for as long as they take, and so may block an application
+/// using a large table. Operations which an online migration (see
+/// [`create_online_migration`][super::MigrationsMut::create_online_migration])
+/// does without blocking writes on PostgreSQL are only included if
+/// `online` is false.
+pub fn locking_operations(db: &ADB, ops: &[Operation], online: bool) -> Vec<String> {
+    let mut descriptions = Vec::new();
+    for op in ops {
+        match op {
+            Operation::ChangeColumn(table, old, new) => {
+                if old.typeid().ok() != new.typeid().ok() {
+                    descriptions.push(format!(
+                        "Changing the type of column {table}.{} rewrites table {table}, blocking reads and writes",
+                        old.name()
+                    ));
+                }
+                if !online && old.nullable() && !new.nullable() {
+                    descriptions.push(format!(
+                        "Making column {table}.{} NOT NULL scans table {table}, blocking writes",
+                        new.name()
+                    ));
+                }
+                if !old.unique() && new.unique() {
+                    descriptions.push(format!(
+                        "Making column {table}.{} unique builds an index on table {table}, blocking writes",
+                        new.name()
+                    ));
+                }
+            }
+            Operation::AddColumn(table, column) if db.get_table(table).is_some() => {
+                if column.unique() {
+                    descriptions.push(format!(
+                        "Adding unique column {table}.{} builds an index on table {table}, blocking writes",
+                        column.name()
+                    ));
+                }
+                if column.backfill().is_some() {
+                    descriptions.push(format!(
+                        "Backfilling column {table}.{} updates every row of table {table}, blocking writes",
+                        column.name()
+                    ));
+                }
+            }
+            Operation::AddIndex(table, index) if !online && db.get_table(table).is_some() => {
+                descriptions.push(format!(
+                    "Creating index {} builds it on table {table}, blocking writes",
+                    index.name()
+                ));
+            }
+            Operation::AddConstraint(table, constraint)
+                if !online && db.get_table(table).is_some() =>
+            {
+                descriptions.push(format!(
+                    "Adding constraint {} checks every row of table {table}, blocking writes",
+                    constraint.name()
+                ));
+            }
+            _ => {}
+        }
+    }
+    descriptions
+}
+
+/// Update the rows of `table` matching `filter` with the SQL `set`
+/// clause (such as `"total = price * quantity"`), `batch_size` rows at
+/// a time in order of the primary key column `pk`. Outside a
+/// transaction each batch is committed on its own, so that rows are
+/// only locked briefly. Returns the number of rows updated.
+pub fn backfill_in_batches(
+    conn: &impl ConnectionMethods,
+    table: &str,
+    pk: &Column,
+    set: &str,
+    filter: BoolExpr,
+    batch_size: i32,
+) -> Result<usize> {
+    let order = [Order {
+        direction: OrderDirection::Ascending,
+        column: pk.name(),
+    }];
+    let mut updated = 0;
+    let mut last: Option<SqlVal> = None;
+    loop {
+        let expr = match &last {
+            Some(last) => BoolExpr::And(
+                Box::new(filter.clone()),
+                Box::new(BoolExpr::Gt(pk.name(), Expr::Val(last.clone()))),
+            ),
+            None => filter.clone(),
+        };
+        let mut pks: Vec<SqlVal> = Vec::new();
+        let mut rows = conn.query(
+            table,
+            std::slice::from_ref(pk),
+            Some(expr),
+            Some(batch_size),
+            None,
+            Some(&order),
+        )?;
+        while let Some(row) = rows.next()? {
+            pks.push(row.get(0, pk.ty().clone())?.into());
+        }
+        drop(rows);
+        let Some(max) = pks.last() else {
+            break;
+        };
+        let values = pks
+            .iter()
+            .map(sql_literal_value)
+            .collect::<Result<Vec<String>>>()?
+            .join(", ");
+        conn.execute(&format!(
+            "UPDATE {} SET {set} WHERE {} IN ({values});",
+            quote_reserved_word(table),
+            quote_reserved_word(pk.name())
+        ))?;
+        updated += pks.len();
+        last = Some(max.clone());
+    }
+    Ok(updated)
+}
@@ -0,0 +1,295 @@
+//! Data inserted into a database after migrating it.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::adb::{ATable, TypeIdentifier, ADB};
+use crate::db::helper::{quote_reserved_word, sql_literal_value};
+use crate::db::ConnectionMethods;
+use crate::{Error, Result, SqlType, SqlVal, SqlValRef};
+
+/// The rows of one table in a [`Seed`].
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SeedTable {
+    /// Name of the table.
+    pub table: String,
+    /// The rows, each mapping column names to values. Each row must
+    /// give the primary key.
+    pub rows: Vec<Map<String, Value>>,
+}
+
+/// Rows inserted into a database for one environment, such as
+/// reference data for every environment or demo data for development,
+/// by [`seed`][super::Migrations::seed]. A row whose primary key
+/// already exists is updated instead, so seeding more than once has
+/// the same result as seeding once.
+///
+/// A seed may instead be SQL, which should likewise be written so that
+/// running it again has no further effect, for example with
+/// `INSERT ... ON CONFLICT DO NOTHING`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Seed {
+    name: String,
+    env: String,
+    tables: Vec<SeedTable>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sql: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    depends_on: Vec<String>,
+}
+
+impl Seed {
+    /// Create a seed named `name` for environment `env`. Its tables are
+    /// seeded in order.
+    pub fn new(env: &str, name: &str, tables: Vec<SeedTable>) -> Self {
+        Seed {
+            name: name.to_string(),
+            env: env.to_string(),
+            tables,
+            sql: None,
+            depends_on: Vec::new(),
+        }
+    }
+    /// Create a seed named `name` for environment `env` which executes
+    /// `sql`.
+    pub fn from_sql(env: &str, name: &str, sql: &str) -> Self {
+        Seed {
+            sql: Some(sql.to_string()),
+            ..Seed::new(env, name, Vec::new())
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// The environment the seed is for.
+    pub fn env(&self) -> &str {
+        &self.env
+    }
+    pub fn tables(&self) -> &[SeedTable] {
+        &self.tables
+    }
+    /// The SQL of a seed created with [`from_sql`][Seed::from_sql].
+    pub fn sql(&self) -> Option<&str> {
+        self.sql.as_deref()
+    }
+    /// Names of the seeds of the same environment which are applied
+    /// before this one, such as those seeding the tables its rows refer to.
+    pub fn depends_on(&self) -> &[String] {
+        &self.depends_on
+    }
+    pub fn set_depends_on(&mut self, depends_on: Vec<String>) {
+        self.depends_on = depends_on;
+    }
+
+    /// Insert or update the rows of the seed, whose tables are described
+    /// by `db`, or execute its SQL. Returns the number of rows, which
+    /// is not known for SQL.
+    pub(super) fn apply(&self, db: &ADB, conn: &impl ConnectionMethods) -> Result<usize> {
+        if let Some(sql) = &self.sql {
+            conn.execute(sql)?;
+            return Ok(0);
+        }
+        let mut count = 0;
+        for seed_table in &self.tables {
+            let table = db
+                .get_table(&seed_table.table)
+                .ok_or_else(|| Error::TableNotFound(seed_table.table.clone()))?;
+            for row in &seed_table.rows {
+                conn.execute(&self.upsert_sql(table, row)?)?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    fn upsert_sql(&self, table: &ATable, row: &Map<String, Value>) -> Result<String> {
+        let pk = table
+            .pk()
+            .ok_or_else(|| self.error(format!("table {} has no primary key", table.name)))?;
+        if !row.contains_key(pk.name()) {
+            return Err(self.error(format!(
+                "a row of table {} has no value for its primary key {}",
+                table.name,
+                pk.name()
+            )));
+        }
+        let mut columns = Vec::new();
+        let mut values = Vec::new();
+        let mut updates = Vec::new();
+        for (name, value) in row {
+            let column = table
+                .column(name)
+                .ok_or_else(|| Error::ColumnNotFound(table.name.clone(), name.clone()))?;
+            let val = match column.typeid()? {
+                TypeIdentifier::Ty(ty) => sqlval_from_json(value, &ty),
+                TypeIdentifier::Name(_) => None,
+            }
+            .ok_or_else(|| {
+                self.error(format!(
+                    "{value} is not a valid value for column {}.{name}",
+                    table.name
+                ))
+            })?;
+            let name = quote_reserved_word(name);
+            if column.name() != pk.name() {
+                updates.push(format!("{name} = excluded.{name}"));
+            }
+            columns.push(name);
+            values.push(sql_literal_value(&val)?);
+        }
+        let on_conflict = if updates.is_empty() {
+            "NOTHING".to_string()
+        } else {
+            format!("UPDATE SET {}", updates.join(", "))
+        };
+        Ok(format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) DO {on_conflict};",
+            quote_reserved_word(&table.name),
+            columns.join(", "),
+            values.join(", "),
+            quote_reserved_word(pk.name()),
+        ))
+    }
+
+    fn error(&self, reason: String) -> Error {
+        Error::MigrationError(format!("Seed {} for {}: {reason}", self.name, self.env))
+    }
+}
+
+/// Order `seeds`, which are of one environment and sorted by name, so
+/// that each comes after the seeds it depends on, and otherwise in order
+/// of name. If `name` is given, only that seed and those it depends on
+/// are kept.
+pub(super) fn dependency_order(seeds: Vec<Seed>, name: Option<&str>) -> Result<Vec<Seed>> {
+    fn visit<'a>(
+        seed: &'a Seed,
+        seeds: &'a [Seed],
+        visiting: &mut Vec<&'a str>,
+        ordered: &mut Vec<&'a Seed>,
+    ) -> Result<()> {
+        if ordered.iter().any(|s| s.name == seed.name) {
+            return Ok(());
+        }
+        if visiting.contains(&seed.name.as_str()) {
+            return Err(seed.error(format!(
+                "its dependencies form a cycle through {}",
+                visiting.join(", ")
+            )));
+        }
+        visiting.push(&seed.name);
+        for dependency in &seed.depends_on {
+            let dependency = seeds
+                .iter()
+                .find(|s| s.name == *dependency)
+                .ok_or_else(|| seed.error(format!("it depends on unknown seed {dependency}")))?;
+            visit(dependency, seeds, visiting, ordered)?;
+        }
+        visiting.pop();
+        ordered.push(seed);
+        Ok(())
+    }
+
+    let mut ordered = Vec::new();
+    let roots: Vec<&Seed> = match name {
+        Some(name) => vec![seeds
+            .iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| Error::MigrationError(format!("No seed named {name}")))?],
+        None => seeds.iter().collect(),
+    };
+    for seed in roots {
+        visit(seed, &seeds, &mut Vec::new(), &mut ordered)?;
+    }
+    Ok(ordered.into_iter().cloned().collect())
+}
+
+/// Convert a JSON value, such as one from a seed, to a value of type
+/// `ty`. Blobs are given as hex strings, timestamps as ISO 8601
+/// strings, UUIDs as hyphenated strings, and arrays as JSON arrays.
+/// Returns `None` if the value does not fit the type, or for a custom
+/// type.
+pub fn sqlval_from_json(value: &Value, ty: &SqlType) -> Option<SqlVal> {
+    if value.is_null() {
+        return Some(SqlVal::Null);
+    }
+    Some(match ty {
+        SqlType::Bool => SqlVal::Bool(value.as_bool()?),
+        SqlType::Int => SqlVal::Int(value.as_i64()?.try_into().ok()?),
+        SqlType::BigInt => SqlVal::BigInt(value.as_i64()?),
+        SqlType::HugeInt => SqlVal::HugeInt(match value {
+            Value::Number(n) => n.to_string().parse().ok()?,
+            _ => value.as_str()?.parse().ok()?,
+        }),
+        SqlType::Real => SqlVal::Real(value.as_f64()?),
+        SqlType::Text | SqlType::CiText => SqlVal::Text(value.as_str()?.to_string()),
+        SqlType::Blob => SqlVal::Blob(hex::decode(value.as_str()?).ok()?),
+        #[cfg(feature = "json")]
+        SqlType::Json => SqlVal::Json(value.clone()),
+        #[cfg(feature = "json")]
+        SqlType::KeyValue => SqlVal::Json(value.clone()),
+        #[cfg(feature = "datetime")]
+        SqlType::Timestamp => SqlVal::Timestamp(value.as_str()?.parse().ok()?),
+        #[cfg(feature = "datetime")]
+        SqlType::TimestampTz => {
+            SqlVal::TimestampTz(chrono::DateTime::parse_from_rfc3339(value.as_str()?).ok()?)
+        }
+        #[cfg(feature = "datetime")]
+        SqlType::Date => SqlVal::Date(value.as_str()?.parse().ok()?),
+        #[cfg(feature = "datetime")]
+        SqlType::Time => SqlVal::Time(value.as_str()?.parse().ok()?),
+        #[cfg(feature = "decimal")]
+        SqlType::Numeric(_) => SqlVal::Decimal(match value {
+            Value::Number(n) => n.to_string().parse().ok()?,
+            _ => value.as_str()?.parse().ok()?,
+        }),
+        #[cfg(feature = "uuid")]
+        SqlType::Uuid => SqlVal::Uuid(value.as_str()?.parse().ok()?),
+        SqlType::Array(elem) => SqlVal::Array(
+            elem,
+            value
+                .as_array()?
+                .iter()
+                .map(|v| sqlval_from_json(v, elem))
+                .collect::<Option<_>>()?,
+        ),
+        SqlType::Custom(_) => return None,
+    })
+}
+
+/// Convert a value to JSON, the inverse of [`sqlval_from_json`].
+/// Returns `None` for a value of a custom type.
+pub fn sqlval_to_json(val: &SqlValRef) -> Option<Value> {
+    Some(match val {
+        SqlValRef::Null => Value::Null,
+        SqlValRef::Bool(b) => Value::Bool(*b),
+        SqlValRef::Int(i) => Value::from(*i),
+        SqlValRef::BigInt(i) => Value::from(*i),
+        // A string, as a JSON number may not keep every digit.
+        SqlValRef::HugeInt(i) => Value::from(i.to_string()),
+        SqlValRef::Real(f) => Value::from(*f),
+        SqlValRef::Text(s) => Value::from(*s),
+        SqlValRef::Blob(b) => Value::from(hex::encode(b)),
+        #[cfg(feature = "json")]
+        SqlValRef::Json(v) => v.clone(),
+        #[cfg(feature = "datetime")]
+        SqlValRef::Timestamp(t) => Value::from(t.format("%Y-%m-%dT%H:%M:%S%.f").to_string()),
+        #[cfg(feature = "datetime")]
+        SqlValRef::TimestampTz(t) => Value::from(t.to_rfc3339()),
+        #[cfg(feature = "datetime")]
+        SqlValRef::Date(d) => Value::from(d.to_string()),
+        #[cfg(feature = "datetime")]
+        SqlValRef::Time(t) => Value::from(t.to_string()),
+        // A string, as a JSON number may not keep every digit.
+        #[cfg(feature = "decimal")]
+        SqlValRef::Decimal(d) => Value::from(d.to_string()),
+        #[cfg(feature = "uuid")]
+        SqlValRef::Uuid(u) => Value::from(u.to_string()),
+        SqlValRef::Array(_, items) => Value::Array(
+            items
+                .iter()
+                .map(|v| sqlval_to_json(&v.as_ref()))
+                .collect::<Option<_>>()?,
+        ),
+        SqlValRef::Custom(_) => return None,
+    })
+}
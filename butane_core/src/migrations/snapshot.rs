@@ -0,0 +1,144 @@
+//! Rendering of a schema as text for snapshot tests.
+
+use std::fmt::Write;
+
+use super::adb::{AColumn, AConstraintKind, ARef, ATable, TypeIdentifier, ADB};
+use crate::{Result, SqlType, SqlVal};
+
+/// Render the schema `db` as text which is stable and diffs well, for
+/// comparing with a snapshot kept under version control, so that a
+/// change to the schema shows up in review as a change to the snapshot.
+///
+/// Each table, column, index, constraint and user-defined type is on a
+/// line of its own. Tables, indexes, constraints and types are sorted
+/// by name, and columns are in the order they are declared in. Hints
+/// used only to create migrations, such as renames, are left out.
+pub fn schema_snapshot(db: &ADB) -> Result<String> {
+    let mut db = db.clone();
+    db.resolve_types()?;
+    let mut out = String::new();
+    for table in db.tables() {
+        write_table(&mut out, table);
+    }
+    for ty in db.custom_types() {
+        writeln!(out, "type {}: {} {}", ty.name(), ty.kind(), ty.definition()).unwrap();
+    }
+    Ok(out)
+}
+
+fn write_table(out: &mut String, table: &ATable) {
+    writeln!(out, "table {}", table.name).unwrap();
+    let options = table.sqlite_options();
+    if !options.strict {
+        out.push_str("  sqlite not strict\n");
+    }
+    if options.without_rowid {
+        out.push_str("  sqlite without rowid\n");
+    }
+    for column in &table.columns {
+        writeln!(out, "  column {}", column_line(column)).unwrap();
+    }
+    let mut indexes: Vec<_> = table.indexes.iter().collect();
+    indexes.sort_by_key(|index| index.name());
+    for index in indexes {
+        write!(
+            out,
+            "  index {} ({})",
+            index.name(),
+            index.columns().join(", ")
+        )
+        .unwrap();
+        if index.unique() {
+            out.push_str(" unique");
+        }
+        if let Some(method) = index.method() {
+            write!(out, " using {method}").unwrap();
+        }
+        out.push('\n');
+    }
+    let mut constraints: Vec<_> = table.constraints.iter().collect();
+    constraints.sort_by_key(|constraint| constraint.name());
+    for constraint in constraints {
+        write!(out, "  constraint {} ", constraint.name()).unwrap();
+        match constraint.kind() {
+            AConstraintKind::Unique(columns) => {
+                writeln!(out, "unique ({})", columns.join(", ")).unwrap()
+            }
+            AConstraintKind::Check(expr) => writeln!(out, "check ({expr})").unwrap(),
+            AConstraintKind::ForeignKey {
+                columns,
+                ref_table,
+                ref_columns,
+            } => writeln!(
+                out,
+                "foreign key ({}) references {ref_table} ({})",
+                columns.join(", "),
+                ref_columns.join(", ")
+            )
+            .unwrap(),
+        }
+    }
+}
+
+/// The name and type of `column`, followed by its other properties.
+fn column_line(column: &AColumn) -> String {
+    let mut line = format!("{}: ", column.name());
+    match column.typeid() {
+        Ok(ty) => line.push_str(&type_name(&ty)),
+        Err(_) => line.push_str("unresolved"),
+    }
+    if column.nullable() {
+        line.push_str(", nullable");
+    }
+    if column.is_pk() {
+        line.push_str(", primary key");
+    }
+    if column.is_auto() {
+        line.push_str(", auto");
+    }
+    if column.unique() {
+        line.push_str(", unique");
+    }
+    if let Some(default) = column.default() {
+        write!(line, ", default {}", value(default)).unwrap();
+    }
+    match column.reference() {
+        Some(ARef::Literal(reference)) => write!(
+            line,
+            ", references {}.{}",
+            reference.table_name(),
+            reference.column_name()
+        )
+        .unwrap(),
+        Some(ARef::Deferred(_)) => line.push_str(", references unresolved"),
+        None => (),
+    }
+    if column.is_deferrable() {
+        line.push_str(", deferrable");
+    }
+    if let Some(precision) = column.precision() {
+        write!(line, ", precision {precision}").unwrap();
+    }
+    line
+}
+
+/// The name of a type, including the details left out by its `Display`.
+fn type_name(ty: &TypeIdentifier) -> String {
+    match ty {
+        #[cfg(feature = "decimal")]
+        TypeIdentifier::Ty(SqlType::Numeric(Some((precision, scale)))) => {
+            format!("numeric({precision}, {scale})")
+        }
+        TypeIdentifier::Ty(ty @ SqlType::Custom(_)) => format!("{ty:?}"),
+        TypeIdentifier::Ty(ty) => ty.to_string(),
+        TypeIdentifier::Name(name) => name.clone(),
+    }
+}
+
+/// A value, with text quoted so that it is distinct from other values.
+fn value(val: &SqlVal) -> String {
+    match val {
+        SqlVal::Text(text) => format!("{text:?}"),
+        val => val.to_string(),
+    }
+}
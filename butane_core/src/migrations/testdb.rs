@@ -0,0 +1,129 @@
+//! Databases created for tests.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::Migrations;
+use crate::db::{self, ConnectionSpec};
+use crate::{Error, Result};
+
+/// A database created for a test, with all migrations applied, so that
+/// tests running in parallel each have a database of their own. It is
+/// dropped, or for SQLite its file deleted, when the `TestDatabase` is
+/// dropped, unless it is [kept][TestDatabase::keep].
+#[derive(Debug)]
+pub struct TestDatabase {
+    base: ConnectionSpec,
+    name: String,
+    spec: ConnectionSpec,
+    keep: bool,
+}
+
+impl TestDatabase {
+    /// Create a database with a unique name and apply `migrations` to
+    /// it. For Postgres, the database is created on the server `base`
+    /// connects to. For SQLite, it is a file in the temporary directory.
+    pub fn create(base: &ConnectionSpec, migrations: &impl Migrations) -> Result<Self> {
+        let name = unique_name();
+        let (name, spec) = match base.backend_name.as_str() {
+            "pg" => {
+                db::connect(base)?.execute(format!("CREATE DATABASE {name};"))?;
+                let spec = ConnectionSpec::new("pg", pg_conn_str(base, &name)?);
+                (name, spec)
+            }
+            "sqlite" => {
+                let path = std::env::temp_dir().join(format!("{name}.db"));
+                let path = path.to_string_lossy().to_string();
+                (path.clone(), ConnectionSpec::new("sqlite", path))
+            }
+            other => return Err(Error::UnknownBackend(other.to_string())),
+        };
+        let db = TestDatabase {
+            base: base.clone(),
+            name,
+            spec,
+            keep: false,
+        };
+        migrations.migrate(&mut db::connect(&db.spec)?)?;
+        Ok(db)
+    }
+
+    /// The name of the database, or for SQLite the path of its file.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The spec to connect to the database with.
+    pub fn spec(&self) -> &ConnectionSpec {
+        &self.spec
+    }
+
+    /// Keep the database when this is dropped, returning the spec to
+    /// connect to it with. It can be dropped later with
+    /// [`drop_database`][TestDatabase::drop_database].
+    pub fn keep(mut self) -> ConnectionSpec {
+        self.keep = true;
+        self.spec.clone()
+    }
+
+    /// Drop the test database `name` created with `base`, or for SQLite
+    /// delete the file at path `name`.
+    pub fn drop_database(base: &ConnectionSpec, name: &str) -> Result<()> {
+        match base.backend_name.as_str() {
+            "pg" => {
+                if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(Error::Internal(format!(
+                        "{name} is not the name of a test database"
+                    )));
+                }
+                db::connect(base)?.execute(format!("DROP DATABASE IF EXISTS {name};"))
+            }
+            "sqlite" => match std::fs::remove_file(name) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            other => Err(Error::UnknownBackend(other.to_string())),
+        }
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        if let Err(_e) = Self::drop_database(&self.base, &self.name) {
+            crate::warn!("Cannot drop test database {}: {}", self.name, _e);
+        }
+    }
+}
+
+/// A database name which no other test database has.
+fn unique_name() -> String {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "butane_test_{}_{nanos}_{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// The connection string of `base` with its database replaced by `name`.
+fn pg_conn_str(base: &ConnectionSpec, name: &str) -> Result<String> {
+    let conn_str = if base.conn_str.starts_with("env:") {
+        base.expanded_conn_str()?
+    } else {
+        base.conn_str.clone()
+    };
+    if conn_str.starts_with("postgres://") || conn_str.starts_with("postgresql://") {
+        let mut url = url::Url::parse(&conn_str)?;
+        url.set_path(&format!("/{name}"));
+        Ok(url.to_string())
+    } else {
+        Ok(format!("{conn_str} dbname={name}"))
+    }
+}
@@ -0,0 +1,216 @@
+//! Contains the [Money] type for amounts of a currency.
+#![deny(missing_docs)]
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Add, Neg, Sub};
+
+use rust_decimal::Decimal;
+
+use crate::{Error, FieldType, FromSql, Result, SqlType, SqlVal, SqlValRef, ToSql};
+
+/// A currency, identified by its ISO 4217 code. Implemented by the
+/// types given as the currency of [Money], such as [Usd].
+pub trait Currency {
+    /// The three-letter code of the currency, such as `USD`.
+    const CODE: &'static str;
+}
+
+macro_rules! currencies {
+    ($($ty:ident => $code:literal, $name:literal;)*) => {
+        $(
+            #[doc = concat!("The ", $name, ", `", $code, "`.")]
+            #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+            pub struct $ty;
+            impl Currency for $ty {
+                const CODE: &'static str = $code;
+            }
+        )*
+    };
+}
+
+currencies! {
+    Aud => "AUD", "Australian dollar";
+    Cad => "CAD", "Canadian dollar";
+    Chf => "CHF", "Swiss franc";
+    Cny => "CNY", "Chinese yuan";
+    Eur => "EUR", "euro";
+    Gbp => "GBP", "pound sterling";
+    Inr => "INR", "Indian rupee";
+    Jpy => "JPY", "Japanese yen";
+    Usd => "USD", "United States dollar";
+}
+
+/// An amount of the currency `C`. The currency is part of the type,
+/// so amounts of different currencies cannot be compared, added or
+/// filtered against each other.
+///
+/// A `Money` field `price` is stored in two columns: the amount in
+/// the `NUMERIC` column `price` and the currency code in the text
+/// column `price_currency`. Loading a row whose currency is not `C`
+/// fails with [`Error::CurrencyMismatch`]. Filters such as
+/// `query!(Product, price < { Money::<Usd>::new(dec!(10)) })` compare
+/// the amount.
+pub struct Money<C> {
+    amount: Decimal,
+    currency: PhantomData<fn() -> C>,
+}
+
+impl<C: Currency> Money<C> {
+    /// Constructs an amount of the currency.
+    pub fn new(amount: Decimal) -> Self {
+        Money {
+            amount,
+            currency: PhantomData,
+        }
+    }
+
+    /// The amount.
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    /// The code of the currency, such as `USD`.
+    pub fn currency(&self) -> &'static str {
+        C::CODE
+    }
+}
+
+impl<C> Clone for Money<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<C> Copy for Money<C> {}
+
+impl<C: Currency> Default for Money<C> {
+    fn default() -> Self {
+        Money::new(Decimal::ZERO)
+    }
+}
+
+impl<C: Currency> fmt::Debug for Money<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Money({} {})", self.amount, C::CODE)
+    }
+}
+
+impl<C: Currency> fmt::Display for Money<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount, C::CODE)
+    }
+}
+
+impl<C> PartialEq for Money<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.amount == other.amount
+    }
+}
+impl<C> Eq for Money<C> {}
+
+impl<C> PartialOrd for Money<C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<C> Ord for Money<C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.amount.cmp(&other.amount)
+    }
+}
+
+impl<C> Hash for Money<C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.amount.hash(state)
+    }
+}
+
+impl<C: Currency> Add for Money<C> {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Money::new(self.amount + other.amount)
+    }
+}
+
+impl<C: Currency> Sub for Money<C> {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Money::new(self.amount - other.amount)
+    }
+}
+
+impl<C: Currency> Neg for Money<C> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Money::new(-self.amount)
+    }
+}
+
+impl<C: Currency> FromSql for Money<C> {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        Decimal::from_sql_ref(valref).map(Money::new)
+    }
+}
+
+impl<C: Currency> ToSql for Money<C> {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Decimal(self.amount)
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Decimal(self.amount)
+    }
+}
+
+impl<C: Currency> FieldType for Money<C> {
+    const SQLTYPE: SqlType = SqlType::Numeric(None);
+    type RefType = Self;
+}
+
+/// A field holding [Money], which is stored with the code of its
+/// currency in a second column. Used by macro-generated code; you do
+/// not need to call it directly.
+pub trait MoneyField: FieldType + Sized {
+    /// The value of the currency column.
+    fn currency_sql(&self) -> SqlValRef<'static>;
+
+    /// Load the field from the values of its amount and currency
+    /// columns, checking the currency.
+    fn from_sql_with_currency(amount: SqlValRef, currency: SqlValRef) -> Result<Self>;
+}
+
+/// Check that `currency` is the code of `C`.
+fn check_currency<C: Currency>(currency: SqlValRef) -> Result<()> {
+    match currency {
+        SqlValRef::Text(code) if code == C::CODE => Ok(()),
+        SqlValRef::Text(code) => Err(Error::CurrencyMismatch(C::CODE, code.to_string())),
+        _ => Err(Error::CannotConvertSqlVal(SqlType::Text, currency.into())),
+    }
+}
+
+impl<C: Currency> MoneyField for Money<C> {
+    fn currency_sql(&self) -> SqlValRef<'static> {
+        SqlValRef::Text(C::CODE)
+    }
+    fn from_sql_with_currency(amount: SqlValRef, currency: SqlValRef) -> Result<Self> {
+        check_currency::<C>(currency)?;
+        Self::from_sql_ref(amount)
+    }
+}
+
+impl<C: Currency> MoneyField for Option<Money<C>> {
+    fn currency_sql(&self) -> SqlValRef<'static> {
+        match self {
+            Some(money) => money.currency_sql(),
+            None => SqlValRef::Null,
+        }
+    }
+    fn from_sql_with_currency(amount: SqlValRef, currency: SqlValRef) -> Result<Self> {
+        if let SqlValRef::Null = amount {
+            return Ok(None);
+        }
+        Money::from_sql_with_currency(amount, currency).map(Some)
+    }
+}
@@ -1,12 +1,20 @@
 //! Not expected to be used directly.
 
 use std::borrow::{Borrow, Cow};
+#[cfg(feature = "json")]
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
 use crate::fkey::ForeignKey;
-use crate::query::{BoolExpr, Column, Expr, Join};
-use crate::sqlval::{FieldType, SqlVal, ToSql};
+#[cfg(feature = "bitflags")]
+use crate::flags::{flag_bits, FlagBits};
+use crate::query::{BoolExpr, CmpOp, Column, DatePart, Expr, Join};
+use crate::sqlval::{ArrayElement, FieldType, SqlVal, ToSql};
 use crate::DataObject;
+#[cfg(feature = "bitflags")]
+use crate::FlagSet;
+#[cfg(feature = "json")]
+use crate::Json;
 
 macro_rules! binary_op {
     ($func_name:ident, $bound:path, $cond:ident) => {
@@ -26,6 +34,58 @@ pub trait DataOrd<Rhs> {}
 impl<T> DataOrd<T> for Option<T> where T: PartialOrd<T> + FieldType {}
 impl<T> DataOrd<T> for T where T: PartialOrd<T> + FieldType {}
 
+/// Marker trait for the types of fields holding a date, which may be
+/// nullable, whose year, month and day can be compared.
+pub trait HasDate {}
+impl<T: HasDate> HasDate for Option<T> {}
+
+/// Marker trait for the types of fields holding a time of day, which
+/// may be nullable, whose hour, minute and second can be compared.
+pub trait HasTime {}
+impl<T: HasTime> HasTime for Option<T> {}
+
+#[cfg(feature = "datetime")]
+mod chrono_parts {
+    use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+    use super::{HasDate, HasTime};
+
+    impl HasDate for NaiveDate {}
+    impl HasDate for NaiveDateTime {}
+    impl HasDate for DateTime<Utc> {}
+    impl HasDate for DateTime<FixedOffset> {}
+    impl HasTime for NaiveTime {}
+    impl HasTime for NaiveDateTime {}
+    impl HasTime for DateTime<Utc> {}
+    impl HasTime for DateTime<FixedOffset> {}
+}
+
+#[cfg(feature = "time")]
+mod time_parts {
+    use time::{Date, OffsetDateTime, PrimitiveDateTime, Time};
+
+    use super::{HasDate, HasTime};
+
+    impl HasDate for Date {}
+    impl HasDate for PrimitiveDateTime {}
+    impl HasDate for OffsetDateTime {}
+    impl HasTime for Time {}
+    impl HasTime for PrimitiveDateTime {}
+    impl HasTime for OffsetDateTime {}
+}
+
+/// Marker trait for the types of array fields, which may be
+/// nullable, giving the type of their elements.
+pub trait DataArray {
+    type Element: ArrayElement;
+}
+impl<T: ArrayElement> DataArray for Vec<T> {
+    type Element = T;
+}
+impl<T: ArrayElement> DataArray for Option<Vec<T>> {
+    type Element = T;
+}
+
 /// Used to implement the `query!` and `filter!` macros.
 #[derive(Clone, Debug)]
 pub struct FieldExpr<T>
@@ -65,6 +125,179 @@ pub fn like<U>(&self, val: U) -> BoolExpr
         BoolExpr::Like(self.name, Expr::Val(val.to_sql()))
     }
 }
+impl<T> FieldExpr<T>
+where
+    T: Into<SqlVal> + HasDate,
+{
+    /// The year of the date, for use as `date.year() == 2024`.
+    pub fn year(&self) -> DatePartExpr {
+        DatePartExpr::new(self.name, DatePart::Year)
+    }
+    /// The month of the date, from 1.
+    pub fn month(&self) -> DatePartExpr {
+        DatePartExpr::new(self.name, DatePart::Month)
+    }
+    /// The day of the month of the date, from 1.
+    pub fn day(&self) -> DatePartExpr {
+        DatePartExpr::new(self.name, DatePart::Day)
+    }
+}
+impl<T> FieldExpr<T>
+where
+    T: Into<SqlVal> + HasTime,
+{
+    /// The hour of the time.
+    pub fn hour(&self) -> DatePartExpr {
+        DatePartExpr::new(self.name, DatePart::Hour)
+    }
+    /// The minute of the time.
+    pub fn minute(&self) -> DatePartExpr {
+        DatePartExpr::new(self.name, DatePart::Minute)
+    }
+    /// The whole seconds of the time.
+    pub fn second(&self) -> DatePartExpr {
+        DatePartExpr::new(self.name, DatePart::Second)
+    }
+}
+
+macro_rules! date_part_op {
+    ($func_name:ident, $op:ident) => {
+        pub fn $func_name<U>(&self, val: &U) -> BoolExpr
+        where
+            U: Copy + Into<i64>,
+        {
+            BoolExpr::DatePart {
+                col: self.name,
+                part: self.part,
+                op: CmpOp::$op,
+                expr: Expr::Val(SqlVal::BigInt((*val).into())),
+            }
+        }
+    };
+}
+
+/// A part of the date or time of a field, such as its year. Used to
+/// implement the `query!` and `filter!` macros.
+#[derive(Clone, Debug)]
+pub struct DatePartExpr {
+    name: &'static str,
+    part: DatePart,
+}
+impl DatePartExpr {
+    fn new(name: &'static str, part: DatePart) -> Self {
+        DatePartExpr { name, part }
+    }
+
+    date_part_op!(eq, Eq);
+    date_part_op!(ne, Ne);
+    date_part_op!(lt, Lt);
+    date_part_op!(gt, Gt);
+    date_part_op!(le, Le);
+    date_part_op!(ge, Ge);
+}
+
+impl<T> FieldExpr<T>
+where
+    T: Into<SqlVal> + DataArray,
+{
+    /// True if the array contains `val`.
+    pub fn contains(&self, val: impl Into<T::Element>) -> BoolExpr {
+        let vals: Vec<T::Element> = vec![val.into()];
+        BoolExpr::Contains(self.name, Expr::Val(vals.into_sql()))
+    }
+    /// The same as [`contains`][Self::contains], which the `filter!`
+    /// macro calls with a literal.
+    pub fn containspk(&self, val: impl Into<T::Element>) -> BoolExpr {
+        self.contains(val)
+    }
+    /// True if the array has an element in common with `vals`.
+    pub fn overlaps<U>(&self, vals: &[U]) -> BoolExpr
+    where
+        U: Clone + Into<T::Element>,
+    {
+        let vals: Vec<T::Element> = vals.iter().cloned().map(Into::into).collect();
+        BoolExpr::Overlaps(self.name, Expr::Val(vals.into_sql()))
+    }
+}
+
+#[cfg(feature = "json")]
+macro_rules! json_ops {
+    ($ty:ty) => {
+        impl<T> FieldExpr<$ty>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned,
+        {
+            /// True if the JSON contains `val`: an object containing
+            /// its members, an array containing its elements, or a
+            /// scalar equal to it.
+            pub fn contains(&self, val: impl serde::Serialize) -> BoolExpr {
+                let val = serde_json::to_value(val).unwrap();
+                BoolExpr::Contains(self.name, Expr::Val(SqlVal::Json(val)))
+            }
+            /// The same as [`contains`][Self::contains], which the
+            /// `filter!` macro calls with a literal.
+            pub fn containspk(&self, val: impl serde::Serialize) -> BoolExpr {
+                self.contains(val)
+            }
+            /// True if the JSON has a value equal to `val` at `path`,
+            /// whose segments are object keys or array indices.
+            pub fn path_eq(&self, path: &[&str], val: impl serde::Serialize) -> BoolExpr {
+                let path = path.iter().map(|s| s.to_string()).collect();
+                let val = serde_json::to_value(val).unwrap();
+                BoolExpr::PathEq(self.name, path, Expr::Val(SqlVal::Json(val)))
+            }
+        }
+    };
+}
+#[cfg(feature = "json")]
+json_ops!(Json<T>);
+#[cfg(feature = "json")]
+json_ops!(Option<Json<T>>);
+
+#[cfg(feature = "json")]
+macro_rules! key_value_ops {
+    ($ty:ty) => {
+        impl FieldExpr<$ty> {
+            /// True if the map has `key`, with the value `val`.
+            pub fn key_equals(&self, key: &str, val: &str) -> BoolExpr {
+                BoolExpr::KeyEq(
+                    self.name,
+                    key.to_string(),
+                    Expr::Val(SqlVal::Text(val.to_string())),
+                )
+            }
+        }
+    };
+}
+#[cfg(feature = "json")]
+key_value_ops!(HashMap<String, String>);
+#[cfg(feature = "json")]
+key_value_ops!(Option<HashMap<String, String>>);
+
+#[cfg(feature = "bitflags")]
+macro_rules! flag_ops {
+    ($ty:ty) => {
+        impl<T> FieldExpr<$ty>
+        where
+            T: bitflags::Flags,
+            T::Bits: FlagBits,
+        {
+            /// True if every flag in `flags` is set.
+            pub fn has_flag(&self, flags: T) -> BoolExpr {
+                BoolExpr::HasFlags(self.name, Expr::Val(SqlVal::BigInt(flag_bits(&flags))))
+            }
+            /// True if any flag in `flags` is set.
+            pub fn has_any(&self, flags: T) -> BoolExpr {
+                BoolExpr::HasAnyFlag(self.name, Expr::Val(SqlVal::BigInt(flag_bits(&flags))))
+            }
+        }
+    };
+}
+#[cfg(feature = "bitflags")]
+flag_ops!(FlagSet<T>);
+#[cfg(feature = "bitflags")]
+flag_ops!(Option<FlagSet<T>>);
+
 impl<F: DataObject> FieldExpr<ForeignKey<F>> {
     pub fn subfilter(&self, q: BoolExpr) -> BoolExpr {
         BoolExpr::Subquery {
@@ -92,6 +325,8 @@ pub struct ManyFieldExpr<O, T>
     T: DataObject, // owned
 {
     many_table: &'static str,
+    owner_column: &'static str,
+    has_column: &'static str,
     phantomo: PhantomData<O>,
     phantomt: PhantomData<T>,
 }
@@ -100,9 +335,15 @@ impl<O, T> ManyFieldExpr<O, T>
     O: DataObject,
     T: DataObject,
 {
-    pub fn new(many_table: &'static str) -> Self {
+    pub fn new(
+        many_table: &'static str,
+        owner_column: &'static str,
+        has_column: &'static str,
+    ) -> Self {
         ManyFieldExpr {
             many_table,
+            owner_column,
+            has_column,
             phantomo: PhantomData,
             phantomt: PhantomData,
         }
@@ -111,10 +352,10 @@ pub fn contains(&self, q: BoolExpr) -> BoolExpr {
         BoolExpr::SubqueryJoin {
             col: O::PKCOL,
             tbl2: Cow::Borrowed(T::TABLE),
-            col2: Column::new(self.many_table, "owner"),
+            col2: Column::new(self.many_table, self.owner_column),
             joins: vec![Join::Inner {
                 join_table: self.many_table,
-                col1: Column::new(self.many_table, "has"),
+                col1: Column::new(self.many_table, self.has_column),
                 col2: Column::new(T::TABLE, T::PKCOL),
             }],
             expr: Box::new(q),
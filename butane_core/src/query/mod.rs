@@ -16,7 +16,7 @@
 
 mod fieldexpr;
 
-pub use fieldexpr::{DataOrd, FieldExpr, ManyFieldExpr};
+pub use fieldexpr::{DataArray, DataOrd, DatePartExpr, FieldExpr, HasDate, HasTime, ManyFieldExpr};
 
 type TblName = Cow<'static, str>;
 
@@ -44,6 +44,26 @@ pub enum BoolExpr {
     Le(&'static str, Expr),
     Ge(&'static str, Expr),
     Like(&'static str, Expr),
+    /// Expression which is true if the array in `col` contains every
+    /// element of the array in the expression, or if the JSON in `col`
+    /// contains the JSON in the expression.
+    Contains(&'static str, Expr),
+    /// Expression which is true if the array in `col` has an element in
+    /// common with the array in the expression.
+    Overlaps(&'static str, Expr),
+    /// Expression which is true if the JSON in `col` has, at the path
+    /// of object keys or array indices, a value equal to the JSON in
+    /// the expression.
+    PathEq(&'static str, Vec<String>, Expr),
+    /// Expression which is true if the key-value map in `col` has the
+    /// key, with a value equal to the text expression.
+    KeyEq(&'static str, String, Expr),
+    /// Expression which is true if every bit set in the integer
+    /// expression is also set in `col`.
+    HasFlags(&'static str, Expr),
+    /// Expression which is true if any bit set in the integer
+    /// expression is also set in `col`.
+    HasAnyFlag(&'static str, Expr),
     AllOf(Vec<BoolExpr>),
     And(Box<BoolExpr>, Box<BoolExpr>),
     Or(Box<BoolExpr>, Box<BoolExpr>),
@@ -68,6 +88,52 @@ pub enum BoolExpr {
         joins: Vec<Join>,
         expr: Box<BoolExpr>,
     },
+    /// Expression which is true if the `part` of the date, time or
+    /// timestamp in `col` compares with the integer expression as `op`.
+    DatePart {
+        col: &'static str,
+        part: DatePart,
+        op: CmpOp,
+        expr: Expr,
+    },
+}
+
+/// A part of a date or time, as compared by [`BoolExpr::DatePart`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatePart {
+    Year,
+    /// Month of the year, from 1.
+    Month,
+    /// Day of the month, from 1.
+    Day,
+    Hour,
+    Minute,
+    /// Whole seconds of the minute.
+    Second,
+}
+
+/// A comparison operator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+impl CmpOp {
+    /// The SQL operator.
+    pub fn sql(&self) -> &'static str {
+        match self {
+            CmpOp::Eq => "=",
+            CmpOp::Ne => "<>",
+            CmpOp::Lt => "<",
+            CmpOp::Gt => ">",
+            CmpOp::Le => "<=",
+            CmpOp::Ge => ">=",
+        }
+    }
 }
 
 /// Represents the direction of a sort.
@@ -259,6 +325,31 @@ pub trait QueryOps<T> {
 
     /// Executes the query against `conn` and deletes all matching objects.
     async fn delete(self, conn: &impl ConnectionMethods) -> Result<usize>;
+
+    /// Executes the query against `conn` like [`load`][Self::load],
+    /// but fails with [`Error::Timeout`][crate::Error::Timeout] if it
+    /// does not finish within `timeout`. The statement is cancelled on
+    /// the database when the timeout elapses or the future is dropped,
+    /// where the backend supports it, rather than being left to run.
+    #[maybe_async_cfg::only_if(key = "async")]
+    async fn load_with_timeout(
+        self,
+        conn: &impl ConnectionMethods,
+        timeout: std::time::Duration,
+    ) -> Result<QueryResult<T>>;
+
+    /// Executes the query against `conn` when the stream is first
+    /// polled, yielding the results as they are read. On Postgres the
+    /// rows are fetched from a server-side cursor in batches, so
+    /// processing any number of rows takes bounded memory; other
+    /// backends read all the rows first.
+    #[maybe_async_cfg::only_if(key = "async")]
+    fn stream<'c>(
+        self,
+        conn: &'c impl ConnectionMethods,
+    ) -> impl futures_util::Stream<Item = Result<T>> + Send + 'c
+    where
+        T: Send + 'c;
 }
 
 #[maybe_async_cfg::maybe(
@@ -289,4 +380,57 @@ async fn delete(self, conn: &impl ConnectionMethods) -> Result<usize> {
         conn.delete_where(&self.table, self.filter.unwrap_or(BoolExpr::True))
             .await
     }
+    #[maybe_async_cfg::only_if(key = "async")]
+    async fn load_with_timeout(
+        self,
+        conn: &impl ConnectionMethods,
+        timeout: std::time::Duration,
+    ) -> Result<QueryResult<T>> {
+        let load = QueryOps::load(self, conn);
+        // Declared after `load` so that, if this future is dropped, the
+        // statement is cancelled before `load` waits for it to end.
+        let cancel = crate::db::CancelOnDrop::new(conn.cancel_handle());
+        futures_util::pin_mut!(load);
+        match futures_util::future::select(load, Box::pin(tokio::time::sleep(timeout))).await {
+            futures_util::future::Either::Left((result, _)) => {
+                cancel.disarm();
+                result
+            }
+            futures_util::future::Either::Right((_, load)) => {
+                drop(cancel);
+                // Wait for the cancelled statement to end, so that the
+                // connection is free to use afterwards.
+                _ = load.await;
+                Err(crate::Error::Timeout(timeout))
+            }
+        }
+    }
+    #[maybe_async_cfg::only_if(key = "async")]
+    fn stream<'c>(
+        self,
+        conn: &'c impl ConnectionMethods,
+    ) -> impl futures_util::Stream<Item = Result<T>> + Send + 'c
+    where
+        T: Send + 'c,
+    {
+        use futures_util::{StreamExt, TryStreamExt};
+        futures_util::stream::once(async move {
+            let sort = if self.sort.is_empty() {
+                None
+            } else {
+                Some(self.sort.as_slice())
+            };
+            conn.query_stream(
+                &self.table,
+                T::COLUMNS,
+                self.filter,
+                self.limit,
+                self.offset,
+                sort,
+            )
+            .await
+        })
+        .try_flatten()
+        .map(|row| row.and_then(|row| T::from_row(row.as_ref())))
+    }
 }
@@ -0,0 +1,76 @@
+//! Contains the [Redacted] type for secret values.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{FieldType, FromSql, Result, SqlType, SqlVal, SqlValRef, ToSql};
+
+/// Wrapper around a secret, such as an API token, which is stored as
+/// the type it wraps but never shown in `Debug` output, so that a
+/// model holding one can be debug-printed or logged safely. The value
+/// is read with [`expose`][Redacted::expose].
+///
+/// Butane never logs the values of query parameters, which the query
+/// log and the `debug` feature's logs describe by kind and length,
+/// and tracing spans record only the SQL of a statement. Serializing
+/// a `Redacted` does give its value.
+#[derive(Clone, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Redacted<T>(pub T);
+
+impl<T> Redacted<T> {
+    /// The secret value.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Unwrap the secret value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+impl<T> From<T> for Redacted<T> {
+    fn from(val: T) -> Self {
+        Redacted(val)
+    }
+}
+
+impl From<&str> for Redacted<String> {
+    fn from(val: &str) -> Self {
+        Redacted(val.to_string())
+    }
+}
+
+impl<T: FromSql> FromSql for Redacted<T> {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        T::from_sql_ref(valref).map(Redacted)
+    }
+    fn from_sql(val: SqlVal) -> Result<Self> {
+        T::from_sql(val).map(Redacted)
+    }
+}
+
+impl<T: ToSql> ToSql for Redacted<T> {
+    fn to_sql(&self) -> SqlVal {
+        self.0.to_sql()
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        self.0.to_sql_ref()
+    }
+    fn into_sql(self) -> SqlVal {
+        self.0.into_sql()
+    }
+}
+
+impl<T: FieldType> FieldType for Redacted<T> {
+    const SQLTYPE: SqlType = T::SQLTYPE;
+    type RefType = Self;
+}
@@ -6,14 +6,20 @@
 #[cfg(feature = "json")]
 use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroU16, NonZeroU32, NonZeroU64,
+    NonZeroU8,
+};
 
 #[cfg(feature = "datetime")]
-use chrono::{naive::NaiveDateTime, DateTime};
+use chrono::{
+    naive::NaiveDate, naive::NaiveDateTime, naive::NaiveTime, DateTime, FixedOffset, Timelike, Utc,
+};
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-#[cfg(feature = "pg")]
-use crate::custom::SqlTypeCustom;
-use crate::custom::{SqlValCustom, SqlValRefCustom};
+use crate::custom::{SqlTypeCustom, SqlValCustom, SqlValRefCustom};
 use crate::{DataObject, Error::CannotConvertSqlVal, Result, SqlType};
 
 #[derive(Clone, Debug)]
@@ -22,6 +28,7 @@ pub enum SqlValRef<'a> {
     Bool(bool),
     Int(i32),
     BigInt(i64),
+    HugeInt(i128),
     Real(f64),
     Text(&'a str),
     Blob(&'a [u8]),
@@ -29,6 +36,19 @@ pub enum SqlValRef<'a> {
     Json(serde_json::Value),
     #[cfg(feature = "datetime")]
     Timestamp(NaiveDateTime), // NaiveDateTime is Copy
+    #[cfg(feature = "datetime")]
+    TimestampTz(DateTime<FixedOffset>), // DateTime is Copy
+    #[cfg(feature = "datetime")]
+    Date(NaiveDate),
+    #[cfg(feature = "datetime")]
+    Time(NaiveTime),
+    #[cfg(feature = "decimal")]
+    Decimal(Decimal), // Decimal is Copy
+    #[cfg(feature = "uuid")]
+    Uuid(uuid::Uuid), // Uuid is Copy
+    /// The element type and the elements, which are owned as they are
+    /// computed rather than borrowed from either a row or a field.
+    Array(&'static SqlType, Vec<SqlVal>),
     Custom(SqlValRefCustom<'a>),
 }
 impl SqlValRef<'_> {
@@ -39,13 +59,25 @@ pub fn sqltype(&self) -> Option<SqlType> {
             SqlValRef::Bool(_) => Some(SqlType::Bool),
             SqlValRef::Int(_) => Some(SqlType::Int),
             SqlValRef::BigInt(_) => Some(SqlType::BigInt),
+            SqlValRef::HugeInt(_) => Some(SqlType::HugeInt),
             SqlValRef::Real(_) => Some(SqlType::Real),
             SqlValRef::Text(_) => Some(SqlType::Text),
             #[cfg(feature = "datetime")]
             SqlValRef::Timestamp(_) => Some(SqlType::Timestamp),
+            #[cfg(feature = "datetime")]
+            SqlValRef::TimestampTz(_) => Some(SqlType::TimestampTz),
+            #[cfg(feature = "datetime")]
+            SqlValRef::Date(_) => Some(SqlType::Date),
+            #[cfg(feature = "datetime")]
+            SqlValRef::Time(_) => Some(SqlType::Time),
+            #[cfg(feature = "decimal")]
+            SqlValRef::Decimal(_) => Some(SqlType::Numeric(None)),
             SqlValRef::Blob(_) => Some(SqlType::Blob),
             #[cfg(feature = "json")]
             SqlValRef::Json(_) => Some(SqlType::Json),
+            #[cfg(feature = "uuid")]
+            SqlValRef::Uuid(_) => Some(SqlType::Uuid),
+            SqlValRef::Array(elem, _) => Some(SqlType::Array(elem)),
             #[cfg(feature = "pg")]
             SqlValRef::Custom(c) => match c {
                 SqlValRefCustom::PgToSql { ty, .. } => {
@@ -61,6 +93,33 @@ pub fn sqltype(&self) -> Option<SqlType> {
     }
 }
 
+/// The most digits of the fraction of a second kept by a timestamp or
+/// time. PostgreSQL keeps microseconds, so the same is stored on SQLite.
+#[cfg(feature = "datetime")]
+pub const MAX_TIMESTAMP_PRECISION: u32 = 6;
+
+/// `t` with the fraction of its second truncated to `digits` digits.
+#[cfg(feature = "datetime")]
+pub fn truncate_fraction<T: Timelike>(t: T, digits: u32) -> T {
+    let unit = 10u32.pow(9 - digits.min(9));
+    t.with_nanosecond(t.nanosecond() / unit * unit).unwrap_or(t)
+}
+
+#[cfg(feature = "datetime")]
+impl SqlValRef<'_> {
+    /// This value with the fraction of the second of a timestamp
+    /// truncated to `digits` digits, as stored in a column declared
+    /// with `#[timestamp(precision = digits)]`. Other values are
+    /// unchanged.
+    pub fn with_precision(self, digits: u32) -> Self {
+        match self {
+            SqlValRef::Timestamp(dt) => SqlValRef::Timestamp(truncate_fraction(dt, digits)),
+            SqlValRef::TimestampTz(dt) => SqlValRef::TimestampTz(truncate_fraction(dt, digits)),
+            other => other,
+        }
+    }
+}
+
 /// A database value.
 ///
 /// For conversion between `SqlVal` and other types, see [`FromSql`] and [`ToSql`].
@@ -73,6 +132,7 @@ pub enum SqlVal {
     Bool(bool),
     Int(i32),
     BigInt(i64),
+    HugeInt(i128),
     Real(f64),
     Text(String),
     Blob(Vec<u8>),
@@ -80,6 +140,21 @@ pub enum SqlVal {
     Json(serde_json::Value),
     #[cfg(feature = "datetime")]
     Timestamp(NaiveDateTime),
+    #[cfg(feature = "datetime")]
+    TimestampTz(DateTime<FixedOffset>),
+    #[cfg(feature = "datetime")]
+    Date(NaiveDate),
+    #[cfg(feature = "datetime")]
+    Time(NaiveTime),
+    #[cfg(feature = "decimal")]
+    Decimal(Decimal),
+    #[cfg(feature = "uuid")]
+    Uuid(uuid::Uuid),
+    /// The element type and the elements.
+    Array(
+        #[serde(with = "crate::array_element")] &'static SqlType,
+        Vec<SqlVal>,
+    ),
     Custom(Box<SqlValCustom>),
 }
 impl SqlVal {
@@ -141,11 +216,20 @@ pub fn owned_blob(self) -> Result<Vec<u8>> {
     /// `SqlType`. There are no implicit type conversions (i.e. if
     /// this is a `SqlVal::Bool`, it is only compatible with
     /// `SqlType::Bool`, not with `SqlType::Int`, even though an int
-    /// contains enough information to encode a bool.
+    /// contains enough information to encode a bool. A decimal is
+    /// compatible with a numeric type of any precision, text with
+    /// case-insensitive text, and JSON with a key-value map. Any value
+    /// is compatible with a user-defined type.
     #[allow(unreachable_patterns)]
     pub fn is_compatible(&self, t: &SqlType, null_allowed: bool) -> bool {
         match self.sqltype() {
             None => null_allowed,
+            Some(_) if matches!(t, SqlType::Custom(SqlTypeCustom::Named(_))) => true,
+            #[cfg(feature = "decimal")]
+            Some(SqlType::Numeric(_)) => matches!(t, SqlType::Numeric(_)),
+            Some(SqlType::Text) => matches!(t, SqlType::Text | SqlType::CiText),
+            #[cfg(feature = "json")]
+            Some(SqlType::Json) => matches!(t, SqlType::Json | SqlType::KeyValue),
             Some(self_ty) => *t == self_ty,
         }
     }
@@ -158,13 +242,25 @@ pub fn sqltype(&self) -> Option<SqlType> {
             SqlVal::Bool(_) => Some(SqlType::Bool),
             SqlVal::Int(_) => Some(SqlType::Int),
             SqlVal::BigInt(_) => Some(SqlType::BigInt),
+            SqlVal::HugeInt(_) => Some(SqlType::HugeInt),
             SqlVal::Real(_) => Some(SqlType::Real),
             SqlVal::Text(_) => Some(SqlType::Text),
             #[cfg(feature = "datetime")]
             SqlVal::Timestamp(_) => Some(SqlType::Timestamp),
+            #[cfg(feature = "datetime")]
+            SqlVal::TimestampTz(_) => Some(SqlType::TimestampTz),
+            #[cfg(feature = "datetime")]
+            SqlVal::Date(_) => Some(SqlType::Date),
+            #[cfg(feature = "datetime")]
+            SqlVal::Time(_) => Some(SqlType::Time),
+            #[cfg(feature = "decimal")]
+            SqlVal::Decimal(_) => Some(SqlType::Numeric(None)),
             SqlVal::Blob(_) => Some(SqlType::Blob),
             #[cfg(feature = "json")]
             SqlVal::Json(_) => Some(SqlType::Json),
+            #[cfg(feature = "uuid")]
+            SqlVal::Uuid(_) => Some(SqlType::Uuid),
+            SqlVal::Array(elem, _) => Some(SqlType::Array(elem)),
             #[cfg(feature = "pg")]
             SqlVal::Custom(c) => match c.as_ref() {
                 SqlValCustom::Pg { ty, .. } => Some(SqlType::Custom(SqlTypeCustom::Pg(ty.clone()))),
@@ -182,6 +278,7 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             SqlVal::Bool(val) => val.fmt(f),
             Int(val) => val.fmt(f),
             BigInt(val) => val.fmt(f),
+            HugeInt(val) => val.fmt(f),
             Real(val) => val.fmt(f),
             Text(val) => val.fmt(f),
             Blob(val) => f.write_str(&hex::encode(val)),
@@ -189,6 +286,26 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             Json(val) => f.write_str(val.as_str().unwrap()),
             #[cfg(feature = "datetime")]
             Timestamp(val) => val.format("%+").fmt(f),
+            #[cfg(feature = "datetime")]
+            TimestampTz(val) => val.to_rfc3339().fmt(f),
+            #[cfg(feature = "datetime")]
+            Date(val) => val.fmt(f),
+            #[cfg(feature = "datetime")]
+            Time(val) => val.fmt(f),
+            #[cfg(feature = "decimal")]
+            Decimal(val) => val.fmt(f),
+            #[cfg(feature = "uuid")]
+            Uuid(val) => val.fmt(f),
+            Array(_, items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    item.fmt(f)?;
+                }
+                f.write_str("]")
+            }
             Custom(val) => val.fmt(f),
         }
     }
@@ -248,6 +365,7 @@ fn from(valref: SqlValRef) -> SqlVal {
             Bool(v) => SqlVal::Bool(v),
             Int(v) => SqlVal::Int(v),
             BigInt(v) => SqlVal::BigInt(v),
+            HugeInt(v) => SqlVal::HugeInt(v),
             Real(v) => SqlVal::Real(v),
             Text(v) => SqlVal::Text(v.to_string()),
             Blob(v) => SqlVal::Blob(v.into()),
@@ -255,6 +373,17 @@ fn from(valref: SqlValRef) -> SqlVal {
             Json(v) => SqlVal::Json(v),
             #[cfg(feature = "datetime")]
             Timestamp(v) => SqlVal::Timestamp(v),
+            #[cfg(feature = "datetime")]
+            TimestampTz(v) => SqlVal::TimestampTz(v),
+            #[cfg(feature = "datetime")]
+            Date(v) => SqlVal::Date(v),
+            #[cfg(feature = "datetime")]
+            Time(v) => SqlVal::Time(v),
+            #[cfg(feature = "decimal")]
+            Decimal(v) => SqlVal::Decimal(v),
+            #[cfg(feature = "uuid")]
+            Uuid(v) => SqlVal::Uuid(v),
+            Array(elem, v) => SqlVal::Array(elem, v),
             Custom(v) => SqlVal::Custom(Box::new(v.into())),
         }
     }
@@ -268,6 +397,7 @@ fn from(val: &'a SqlVal) -> SqlValRef<'a> {
             Bool(v) => SqlValRef::Bool(*v),
             Int(v) => SqlValRef::Int(*v),
             BigInt(v) => SqlValRef::BigInt(*v),
+            HugeInt(v) => SqlValRef::HugeInt(*v),
             Real(v) => SqlValRef::Real(*v),
             Text(v) => SqlValRef::Text(v.as_ref()),
             Blob(v) => SqlValRef::Blob(v.as_ref()),
@@ -275,6 +405,17 @@ fn from(val: &'a SqlVal) -> SqlValRef<'a> {
             Json(v) => SqlValRef::Json(v.to_owned()),
             #[cfg(feature = "datetime")]
             Timestamp(v) => SqlValRef::Timestamp(*v),
+            #[cfg(feature = "datetime")]
+            TimestampTz(v) => SqlValRef::TimestampTz(*v),
+            #[cfg(feature = "datetime")]
+            Date(v) => SqlValRef::Date(*v),
+            #[cfg(feature = "datetime")]
+            Time(v) => SqlValRef::Time(*v),
+            #[cfg(feature = "decimal")]
+            Decimal(v) => SqlValRef::Decimal(*v),
+            #[cfg(feature = "uuid")]
+            Uuid(v) => SqlValRef::Uuid(*v),
+            Array(elem, v) => SqlValRef::Array(elem, v.clone()),
             Custom(v) => SqlValRef::Custom(v.as_valref()),
         }
     }
@@ -356,12 +497,39 @@ fn from_sql(val: SqlVal) -> Result<Self> {
     };
 }
 
-macro_rules! impl_prim_sql {
+/// Like `impl_basic_from_sql`, but for an integer type, failing with
+/// [`Error::OutOfRange`][crate::Error::OutOfRange] rather than
+/// truncating a value which does not fit.
+macro_rules! impl_int_from_sql {
     ($prim:ty, $variant:ident, $sqltype:ident) => {
-        impl_prim_sql! {$prim, $variant, $sqltype, $prim}
+        impl FromSql for $prim {
+            fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+                if let SqlValRef::$variant(val) = valref {
+                    <$prim>::try_from(val).map_err(|_| crate::Error::OutOfRange)
+                } else {
+                    sql_conv_err!(valref, $sqltype)
+                }
+            }
+        }
     };
-    ($prim:ty, $variant:ident, $sqltype:ident, $reftype: ty) => {
+}
+
+macro_rules! impl_prim_sql {
+    ($prim:ty, $variant:ident, $sqltype:ident) => {
         impl_basic_from_sql!($prim, $variant, $sqltype);
+        impl_prim_to_sql!($prim, $variant, $sqltype);
+    };
+}
+
+macro_rules! impl_int_sql {
+    ($prim:ty, $variant:ident, $sqltype:ident) => {
+        impl_int_from_sql!($prim, $variant, $sqltype);
+        impl_prim_to_sql!($prim, $variant, $sqltype);
+    };
+}
+
+macro_rules! impl_prim_to_sql {
+    ($prim:ty, $variant:ident, $sqltype:ident) => {
         impl ToSql for $prim {
             fn to_sql(&self) -> SqlVal {
                 self.clone().into_sql()
@@ -375,7 +543,7 @@ fn into_sql(self) -> SqlVal {
         }
         impl FieldType for $prim {
             const SQLTYPE: SqlType = SqlType::$sqltype;
-            type RefType = $reftype;
+            type RefType = $prim;
         }
 
         impl PrimaryKeyType for $prim {}
@@ -383,16 +551,76 @@ impl PrimaryKeyType for $prim {}
 }
 
 impl_prim_sql!(bool, Bool, Bool);
-impl_prim_sql!(i64, BigInt, BigInt);
-impl_prim_sql!(i32, Int, Int);
-impl_prim_sql!(u32, BigInt, BigInt);
+impl_int_sql!(i64, BigInt, BigInt);
+impl_int_sql!(i32, Int, Int);
+impl_int_sql!(u32, BigInt, BigInt);
 // TODO need a small int type
-impl_prim_sql!(u16, Int, Int);
-impl_prim_sql!(i16, Int, Int);
-impl_prim_sql!(u8, Int, Int);
-impl_prim_sql!(i8, Int, Int);
+impl_int_sql!(u16, Int, Int);
+impl_int_sql!(i16, Int, Int);
+impl_int_sql!(u8, Int, Int);
+impl_int_sql!(i8, Int, Int);
 impl_prim_sql!(f64, Real, Real);
 impl_prim_sql!(f32, Real, Real);
+impl_int_sql!(i128, HugeInt, HugeInt);
+
+impl FromSql for u64 {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        if let SqlValRef::HugeInt(val) = valref {
+            u64::try_from(val).map_err(|_| crate::Error::OutOfRange)
+        } else {
+            sql_conv_err!(valref, HugeInt)
+        }
+    }
+}
+impl ToSql for u64 {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::HugeInt((*self).into())
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::HugeInt((*self).into())
+    }
+}
+impl FieldType for u64 {
+    const SQLTYPE: SqlType = SqlType::HugeInt;
+    type RefType = Self;
+}
+impl PrimaryKeyType for u64 {}
+
+/// Implements the SQL traits for a [`std::num`] non-zero integer type,
+/// stored as the integer type `$int`. Loading a zero fails with
+/// [`Error::UnexpectedZero`][crate::Error::UnexpectedZero].
+macro_rules! impl_nonzero_sql {
+    ($nonzero:ty, $int:ty, $variant:ident, $sqltype:ident) => {
+        impl FromSql for $nonzero {
+            fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+                <$nonzero>::new(<$int>::from_sql_ref(valref)?).ok_or(crate::Error::UnexpectedZero)
+            }
+        }
+        impl ToSql for $nonzero {
+            fn to_sql(&self) -> SqlVal {
+                SqlVal::$variant(self.get().into())
+            }
+            fn to_sql_ref(&self) -> SqlValRef<'_> {
+                SqlValRef::$variant(self.get().into())
+            }
+        }
+        impl FieldType for $nonzero {
+            const SQLTYPE: SqlType = SqlType::$sqltype;
+            type RefType = Self;
+        }
+        impl PrimaryKeyType for $nonzero {}
+    };
+}
+
+impl_nonzero_sql!(NonZeroI64, i64, BigInt, BigInt);
+impl_nonzero_sql!(NonZeroI32, i32, Int, Int);
+impl_nonzero_sql!(NonZeroU32, u32, BigInt, BigInt);
+impl_nonzero_sql!(NonZeroU16, u16, Int, Int);
+impl_nonzero_sql!(NonZeroI16, i16, Int, Int);
+impl_nonzero_sql!(NonZeroU8, u8, Int, Int);
+impl_nonzero_sql!(NonZeroI8, i8, Int, Int);
+impl_nonzero_sql!(NonZeroI128, i128, HugeInt, HugeInt);
+impl_nonzero_sql!(NonZeroU64, u64, HugeInt, HugeInt);
 
 impl FromSql for String {
     fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
@@ -460,6 +688,137 @@ impl FieldType for Vec<u8> {
 }
 impl PrimaryKeyType for Vec<u8> {}
 
+/// A type which may be an element of a `Vec` stored as an
+/// [`SqlType::Array`]. Its [`SQLTYPE`][FieldType::SQLTYPE] must be one
+/// for which [`SqlType::array_element`] is some.
+pub trait ArrayElement: FieldType {
+    /// `Self::SQLTYPE`, borrowed for the `'static` lifetime which
+    /// [`SqlType::Array`] requires.
+    const ELEMENT: &'static SqlType = &Self::SQLTYPE;
+}
+impl ArrayElement for bool {}
+impl ArrayElement for i32 {}
+impl ArrayElement for i64 {}
+impl ArrayElement for f64 {}
+impl ArrayElement for String {}
+
+impl<T: ArrayElement> FromSql for Vec<T> {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::Array(_, items) => items.into_iter().map(T::from_sql).collect(),
+            _ => Err(CannotConvertSqlVal(Self::SQLTYPE, valref.into())),
+        }
+    }
+    fn from_sql(val: SqlVal) -> Result<Self> {
+        match val {
+            SqlVal::Array(_, items) => items.into_iter().map(T::from_sql).collect(),
+            _ => Err(CannotConvertSqlVal(Self::SQLTYPE, val)),
+        }
+    }
+}
+impl<T: ArrayElement> ToSql for Vec<T> {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Array(T::ELEMENT, self.iter().map(T::to_sql).collect())
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Array(T::ELEMENT, self.iter().map(T::to_sql).collect())
+    }
+    fn into_sql(self) -> SqlVal {
+        SqlVal::Array(T::ELEMENT, self.into_iter().map(T::into_sql).collect())
+    }
+}
+impl<T: ArrayElement> FieldType for Vec<T> {
+    const SQLTYPE: SqlType = SqlType::Array(T::ELEMENT);
+    type RefType = Self;
+}
+
+/// Loading reads the text from the row as it is borrowed, copying it
+/// only once, into the `Owned` value.
+impl FromSql for Cow<'static, str> {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        String::from_sql_ref(valref).map(Cow::Owned)
+    }
+    fn from_sql(val: SqlVal) -> Result<Self> {
+        String::from_sql(val).map(Cow::Owned)
+    }
+}
+impl ToSql for Cow<'static, str> {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Text(self.to_string())
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Text(self)
+    }
+    fn into_sql(self) -> SqlVal {
+        SqlVal::Text(self.into_owned())
+    }
+}
+impl FieldType for Cow<'static, str> {
+    const SQLTYPE: SqlType = SqlType::Text;
+    type RefType = str;
+}
+impl PrimaryKeyType for Cow<'static, str> {}
+
+/// Loading reads the bytes from the row as they are borrowed, copying
+/// them only once, into the `Owned` value.
+impl FromSql for Cow<'static, [u8]> {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        Vec::<u8>::from_sql_ref(valref).map(Cow::Owned)
+    }
+    fn from_sql(val: SqlVal) -> Result<Self> {
+        Vec::<u8>::from_sql(val).map(Cow::Owned)
+    }
+}
+impl ToSql for Cow<'static, [u8]> {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Blob(self.to_vec())
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Blob(self)
+    }
+    fn into_sql(self) -> SqlVal {
+        SqlVal::Blob(self.into_owned())
+    }
+}
+impl FieldType for Cow<'static, [u8]> {
+    const SQLTYPE: SqlType = SqlType::Blob;
+    type RefType = Self;
+}
+
+/// Loading copies the bytes once from the row. Saving borrows them,
+/// and cloning a model with a `Bytes` field shares them rather than
+/// copying.
+#[cfg(feature = "bytes")]
+impl FromSql for bytes::Bytes {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        if let SqlValRef::Blob(val) = valref {
+            Ok(bytes::Bytes::copy_from_slice(val))
+        } else {
+            sql_conv_err!(valref, Blob)
+        }
+    }
+    fn from_sql(val: SqlVal) -> Result<Self> {
+        Vec::<u8>::from_sql(val).map(bytes::Bytes::from)
+    }
+}
+#[cfg(feature = "bytes")]
+impl ToSql for bytes::Bytes {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Blob(self.to_vec())
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Blob(self)
+    }
+    fn into_sql(self) -> SqlVal {
+        SqlVal::Blob(self.into())
+    }
+}
+#[cfg(feature = "bytes")]
+impl FieldType for bytes::Bytes {
+    const SQLTYPE: SqlType = SqlType::Blob;
+    type RefType = Self;
+}
+
 #[cfg(feature = "json")]
 impl FromSql for serde_json::Value {
     fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
@@ -597,33 +956,120 @@ impl FieldType for NaiveDateTime {
 impl PrimaryKeyType for NaiveDateTime {}
 
 #[cfg(feature = "datetime")]
-impl FromSql for DateTime<chrono::offset::Utc> {
-    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
-        Ok(NaiveDateTime::from_sql_ref(valref)?.and_utc())
+impl_basic_from_sql!(NaiveDate, Date, Date);
+#[cfg(feature = "datetime")]
+impl ToSql for NaiveDate {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Date(*self)
     }
-    fn from_sql(val: SqlVal) -> Result<Self> {
-        Ok(NaiveDateTime::from_sql(val)?.and_utc())
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Date(*self)
     }
 }
 #[cfg(feature = "datetime")]
-impl ToSql for DateTime<chrono::offset::Utc> {
+impl FieldType for NaiveDate {
+    const SQLTYPE: SqlType = SqlType::Date;
+    type RefType = Self;
+}
+#[cfg(feature = "datetime")]
+impl PrimaryKeyType for NaiveDate {}
+
+#[cfg(feature = "datetime")]
+impl_basic_from_sql!(NaiveTime, Time, Time);
+#[cfg(feature = "datetime")]
+impl ToSql for NaiveTime {
     fn to_sql(&self) -> SqlVal {
-        SqlVal::Timestamp(self.naive_utc())
+        SqlVal::Time(*self)
     }
     fn to_sql_ref(&self) -> SqlValRef<'_> {
-        SqlValRef::Timestamp(self.naive_utc())
+        SqlValRef::Time(*self)
     }
-    fn into_sql(self) -> SqlVal {
-        SqlVal::Timestamp(self.naive_utc())
+}
+#[cfg(feature = "datetime")]
+impl FieldType for NaiveTime {
+    const SQLTYPE: SqlType = SqlType::Time;
+    type RefType = Self;
+}
+#[cfg(feature = "datetime")]
+impl PrimaryKeyType for NaiveTime {}
+
+#[cfg(feature = "datetime")]
+impl FromSql for DateTime<FixedOffset> {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::TimestampTz(dt) => Ok(dt),
+            // Columns created before timezone-aware timestamps were
+            // supported hold naive timestamps in UTC.
+            SqlValRef::Timestamp(dt) => Ok(dt.and_utc().fixed_offset()),
+            _ => sql_conv_err!(valref, TimestampTz),
+        }
     }
 }
 #[cfg(feature = "datetime")]
-impl FieldType for DateTime<chrono::offset::Utc> {
-    const SQLTYPE: SqlType = SqlType::Timestamp;
-    type RefType = str;
+impl ToSql for DateTime<FixedOffset> {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::TimestampTz(*self)
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::TimestampTz(*self)
+    }
 }
 #[cfg(feature = "datetime")]
-impl PrimaryKeyType for DateTime<chrono::offset::Utc> {}
+impl FieldType for DateTime<FixedOffset> {
+    const SQLTYPE: SqlType = SqlType::TimestampTz;
+    type RefType = Self;
+}
+#[cfg(feature = "datetime")]
+impl PrimaryKeyType for DateTime<FixedOffset> {}
+
+#[cfg(feature = "datetime")]
+impl FromSql for DateTime<Utc> {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        Ok(DateTime::<FixedOffset>::from_sql_ref(valref)?.to_utc())
+    }
+}
+#[cfg(feature = "datetime")]
+impl ToSql for DateTime<Utc> {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::TimestampTz(self.fixed_offset())
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::TimestampTz(self.fixed_offset())
+    }
+}
+#[cfg(feature = "datetime")]
+impl FieldType for DateTime<Utc> {
+    const SQLTYPE: SqlType = SqlType::TimestampTz;
+    type RefType = Self;
+}
+#[cfg(feature = "datetime")]
+impl PrimaryKeyType for DateTime<Utc> {}
+
+#[cfg(feature = "decimal")]
+impl FromSql for Decimal {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::Decimal(d) => Ok(d),
+            _ => Err(CannotConvertSqlVal(SqlType::Numeric(None), valref.into())),
+        }
+    }
+}
+#[cfg(feature = "decimal")]
+impl ToSql for Decimal {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Decimal(*self)
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Decimal(*self)
+    }
+}
+#[cfg(feature = "decimal")]
+impl FieldType for Decimal {
+    const SQLTYPE: SqlType = SqlType::Numeric(None);
+    type RefType = Self;
+}
+#[cfg(feature = "decimal")]
+impl PrimaryKeyType for Decimal {}
 
 impl ToSql for &str {
     fn to_sql(&self) -> SqlVal {
@@ -0,0 +1,213 @@
+//! Factories building and saving instances of models for tests.
+//!
+//! A factory is generated for a model by `#[derive(Factory)]`, placed
+//! after `#[model]`. For a model `Post`, `PostFactory` has a method
+//! setting each field, and gives the fields not set their defaults:
+//! ```ignore
+//! let post = PostFactory::new().published(true).create(&conn)?;
+//! ```
+//! A required [`ForeignKey`][crate::fkey::ForeignKey] which is not set
+//! refers to a parent created by the parent model's own factory.
+//!
+//! [`TestDb`] sets up the database of a test generated by
+//! `#[butane::test]`, and [`current_schema_snapshot`] renders the
+//! schema of the models for snapshot tests.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::db::{self, Connection, ConnectionMethods, ConnectionSpec};
+#[cfg(feature = "async")]
+use crate::db::{ConnectionAsync, ConnectionMethodsAsync};
+use crate::migrations::{self, MemMigrations, Migration, Migrations, MigrationsMut, TestDatabase};
+use crate::{DataObject, Error, Result};
+
+/// A builder of instances of a model, giving the fields which are not
+/// set their defaults.
+///
+/// Rather than implementing this trait manually, use `#[derive(Factory)]`.
+#[allow(async_fn_in_trait)] // Implementation is intended to be through procmacro
+pub trait Factory: Default {
+    /// The model built.
+    type Model: DataObject;
+
+    /// Build the model without saving it.
+    ///
+    /// Panics if a required foreign key has not been set, which
+    /// `create` would have created.
+    fn build(self) -> Self::Model;
+
+    /// Creates the parents of the required foreign keys which have not
+    /// been set, using their own factories. Performed automatically by
+    /// `create`. You do not need to call this directly.
+    #[cfg(feature = "async")]
+    async fn create_parents_async(&mut self, conn: &impl ConnectionMethodsAsync) -> Result<()>;
+
+    /// Creates the parents of the required foreign keys which have not
+    /// been set, using their own factories. Performed automatically by
+    /// `create`. You do not need to call this directly.
+    fn create_parents_sync(&mut self, conn: &impl ConnectionMethods) -> Result<()>;
+}
+
+/// A model with a [`Factory`], generated by `#[derive(Factory)]`.
+pub trait HasFactory: DataObject {
+    /// The factory building this model.
+    type Factory: Factory<Model = Self>;
+
+    /// A factory of this model with none of its fields set.
+    fn factory() -> Self::Factory {
+        Self::Factory::default()
+    }
+}
+
+/// [`Factory`] operations which require a live database connection.
+#[allow(async_fn_in_trait)] // Not intended to be implemented outside Butane
+#[maybe_async_cfg::maybe(
+    idents(
+        ConnectionMethods(sync = "ConnectionMethods"),
+        DataObjectOps,
+        create_parents(snake),
+    ),
+    sync(),
+    async(feature = "async")
+)]
+pub trait FactoryOps: Factory {
+    /// Build the model and save it, first creating the parents of its
+    /// required foreign keys which have not been set.
+    async fn create(mut self, conn: &impl ConnectionMethods) -> Result<Self::Model> {
+        use crate::DataObjectOps;
+        Self::create_parents(&mut self, conn).await?;
+        let mut obj = self.build();
+        obj.save(conn).await?;
+        Ok(obj)
+    }
+}
+
+impl<T> FactoryOpsSync for T where T: Factory {}
+#[cfg(feature = "async")]
+impl<T> FactoryOpsAsync for T where T: Factory {}
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+/// The next number of a sequence shared by all factories, which gives
+/// each value of a field declared `#[factory(sequence)]` a number not
+/// given to any other in the process.
+pub fn next_sequence() -> u64 {
+    SEQUENCE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A type whose values may be numbered by [`next_sequence`], as the
+/// defaults of fields declared `#[factory(sequence)]`.
+pub trait Sequence {
+    /// The value numbered `n`.
+    fn from_sequence(n: u64) -> Self;
+}
+
+macro_rules! impl_sequence_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Sequence for $ty {
+                fn from_sequence(n: u64) -> Self {
+                    n as $ty
+                }
+            }
+        )*
+    };
+}
+impl_sequence_for_int!(i16, i32, i64, u16, u32, u64);
+
+impl Sequence for String {
+    fn from_sequence(n: u64) -> Self {
+        n.to_string()
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl Sequence for uuid::Uuid {
+    fn from_sequence(n: u64) -> Self {
+        uuid::Uuid::from_u64_pair(0, n)
+    }
+}
+
+/// The environment variable with the connection string of the Postgres
+/// server on which `#[butane::test(pg)]` creates its databases.
+pub const PG_CONNSTR_VAR: &str = "BUTANE_PG_CONNSTR";
+
+/// The database of a test generated by `#[butane::test]`, with the
+/// crate's migrations applied.
+///
+/// For SQLite, each connection is to a new in-memory database, which
+/// the migrations are applied to when connecting. For Postgres, a
+/// [`TestDatabase`] is created on the server [`PG_CONNSTR_VAR`]
+/// connects to, and dropped when this is.
+#[derive(Debug)]
+pub struct TestDb {
+    migrations: MemMigrations,
+    database: Option<TestDatabase>,
+}
+
+impl TestDb {
+    /// Set up a database for the backend `backend_name` with `migrations`.
+    pub fn new(backend_name: &str, migrations: MemMigrations) -> Result<Self> {
+        let database = match backend_name {
+            "sqlite" => None,
+            "pg" => {
+                let conn_str = std::env::var(PG_CONNSTR_VAR)
+                    .map_err(|_| Error::MissingEnvVar(PG_CONNSTR_VAR.to_string()))?;
+                let base = ConnectionSpec::new("pg", conn_str);
+                Some(TestDatabase::create(&base, &migrations)?)
+            }
+            other => return Err(Error::UnknownBackend(other.to_string())),
+        };
+        Ok(TestDb {
+            migrations,
+            database,
+        })
+    }
+
+    /// Connect to the database.
+    pub fn connect(&self) -> Result<Connection> {
+        match &self.database {
+            Some(database) => db::connect(database.spec()),
+            None => {
+                let mut conn = db::connect(&Self::sqlite_spec())?;
+                self.migrations.migrate(&mut conn)?;
+                Ok(conn)
+            }
+        }
+    }
+
+    /// Connect to the database asynchronously.
+    #[cfg(feature = "async")]
+    pub async fn connect_async(&self) -> Result<ConnectionAsync> {
+        match &self.database {
+            Some(database) => db::connect_async(database.spec()).await,
+            None => {
+                let mut conn = db::connect_async(&Self::sqlite_spec()).await?;
+                self.migrations.migrate_async(&mut conn).await?;
+                Ok(conn)
+            }
+        }
+    }
+
+    fn sqlite_spec() -> ConnectionSpec {
+        ConnectionSpec::new("sqlite", ":memory:")
+    }
+}
+
+/// The schema of the models, as `#[model]` last wrote it to the current
+/// migration in `.butane/migrations`, rendered by
+/// [`schema_snapshot`][crate::migrations::schema_snapshot].
+///
+/// Comparing it with a snapshot makes a test fail when the schema
+/// changes, e.g. with the `insta` crate:
+/// ```ignore
+/// #[test]
+/// fn schema() {
+///     insta::assert_snapshot!(butane::test_utils::current_schema_snapshot().unwrap());
+/// }
+/// ```
+pub fn current_schema_snapshot() -> Result<String> {
+    let root = std::env::current_dir()?.join(".butane/migrations");
+    let mut ms = migrations::from_root(root);
+    migrations::schema_snapshot(&ms.current().db()?)
+}
@@ -0,0 +1,164 @@
+//! Support for the date and time types of the `time` crate.
+//!
+//! Values are stored exactly as the equivalent `chrono` types are:
+//! `OffsetDateTime` as a [`SqlType::TimestampTz`],
+//! `PrimitiveDateTime` as a [`SqlType::Timestamp`], and `Date` and
+//! `Time` as a [`SqlType::Date`] and a [`SqlType::Time`].
+
+#![deny(missing_docs)]
+use ::time::{Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+use crate::{Error, FieldType, FromSql, PrimaryKeyType, Result, SqlType, SqlVal, SqlValRef, ToSql};
+
+fn date_to_chrono(date: Date) -> NaiveDate {
+    // Every date representable by `time` is representable by chrono.
+    NaiveDate::from_yo_opt(date.year(), date.ordinal().into()).unwrap()
+}
+
+fn date_from_chrono(date: NaiveDate) -> Result<Date> {
+    // The ordinal is at most 366.
+    Date::from_ordinal_date(date.year(), date.ordinal() as u16).map_err(|_| Error::OutOfRange)
+}
+
+fn time_to_chrono(time: Time) -> NaiveTime {
+    let (h, m, s, ns) = time.as_hms_nano();
+    NaiveTime::from_hms_nano_opt(h.into(), m.into(), s.into(), ns).unwrap()
+}
+
+fn time_from_chrono(time: NaiveTime) -> Result<Time> {
+    // The components of a chrono time all fit in a u8, except for the
+    // nanoseconds, which are above a second only for a leap second
+    // and are then rejected by `time`.
+    Time::from_hms_nano(
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+        time.nanosecond(),
+    )
+    .map_err(|_| Error::OutOfRange)
+}
+
+fn primitive_to_chrono(dt: PrimitiveDateTime) -> NaiveDateTime {
+    NaiveDateTime::new(date_to_chrono(dt.date()), time_to_chrono(dt.time()))
+}
+
+fn primitive_from_chrono(dt: NaiveDateTime) -> Result<PrimitiveDateTime> {
+    Ok(PrimitiveDateTime::new(
+        date_from_chrono(dt.date())?,
+        time_from_chrono(dt.time())?,
+    ))
+}
+
+fn utc_to_chrono(dt: OffsetDateTime) -> NaiveDateTime {
+    let utc = dt.to_offset(UtcOffset::UTC);
+    primitive_to_chrono(PrimitiveDateTime::new(utc.date(), utc.time()))
+}
+
+/// The equivalent chrono date and time of `dt`, or
+/// [`Error::OutOfRange`] if its offset is a day or more, which
+/// chrono's [`FixedOffset`] cannot represent but a [`UtcOffset`] can.
+pub fn offset_datetime_to_chrono(dt: OffsetDateTime) -> Result<DateTime<FixedOffset>> {
+    let offset = FixedOffset::east_opt(dt.offset().whole_seconds()).ok_or(Error::OutOfRange)?;
+    Ok(utc_to_chrono(dt).and_utc().with_timezone(&offset))
+}
+
+impl ToSql for Date {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Date(date_to_chrono(*self))
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Date(date_to_chrono(*self))
+    }
+}
+impl FromSql for Date {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::Date(date) => date_from_chrono(date),
+            _ => Err(Error::CannotConvertSqlVal(SqlType::Date, valref.into())),
+        }
+    }
+}
+impl FieldType for Date {
+    const SQLTYPE: SqlType = SqlType::Date;
+    type RefType = Self;
+}
+impl PrimaryKeyType for Date {}
+
+impl ToSql for Time {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Time(time_to_chrono(*self))
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Time(time_to_chrono(*self))
+    }
+}
+impl FromSql for Time {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::Time(time) => time_from_chrono(time),
+            _ => Err(Error::CannotConvertSqlVal(SqlType::Time, valref.into())),
+        }
+    }
+}
+impl FieldType for Time {
+    const SQLTYPE: SqlType = SqlType::Time;
+    type RefType = Self;
+}
+impl PrimaryKeyType for Time {}
+
+impl ToSql for PrimitiveDateTime {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Timestamp(primitive_to_chrono(*self))
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Timestamp(primitive_to_chrono(*self))
+    }
+}
+impl FromSql for PrimitiveDateTime {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::Timestamp(dt) => primitive_from_chrono(dt),
+            _ => Err(Error::CannotConvertSqlVal(
+                SqlType::Timestamp,
+                valref.into(),
+            )),
+        }
+    }
+}
+impl FieldType for PrimitiveDateTime {
+    const SQLTYPE: SqlType = SqlType::Timestamp;
+    type RefType = Self;
+}
+impl PrimaryKeyType for PrimitiveDateTime {}
+
+impl ToSql for OffsetDateTime {
+    fn to_sql(&self) -> SqlVal {
+        self.to_sql_ref().into()
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        let dt = match offset_datetime_to_chrono(*self) {
+            Ok(dt) => dt,
+            // The databases keep only the instant, so an offset chrono
+            // cannot represent is replaced by UTC, as conversion to SQL
+            // cannot fail.
+            Err(_) => utc_to_chrono(*self).and_utc().fixed_offset(),
+        };
+        SqlValRef::TimestampTz(dt)
+    }
+}
+impl FromSql for OffsetDateTime {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        let dt = chrono::DateTime::<FixedOffset>::from_sql_ref(valref)?;
+        let offset = UtcOffset::from_whole_seconds(dt.offset().local_minus_utc())
+            .map_err(|_| Error::OutOfRange)?;
+        Ok(primitive_from_chrono(dt.naive_utc())?
+            .assume_utc()
+            .to_offset(offset))
+    }
+}
+impl FieldType for OffsetDateTime {
+    const SQLTYPE: SqlType = SqlType::TimestampTz;
+    type RefType = Self;
+}
+impl PrimaryKeyType for OffsetDateTime {}
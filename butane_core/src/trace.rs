@@ -0,0 +1,198 @@
+//! Spans for database operations, emitted with the `tracing`
+//! feature. Without it these compile to nothing. The `otel` feature
+//! adds the OpenTelemetry database attributes to them.
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "otel")]
+use std::sync::Arc;
+use std::time::Instant;
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+/// Stands in for [`tracing::Span`] when the `tracing` feature is disabled.
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Span;
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn none() -> Self {
+        Span
+    }
+    pub(crate) fn current() -> Self {
+        Span
+    }
+}
+
+/// Create an `INFO` span for a database operation, with the given
+/// fields and empty `db.statement` and `duration_ms` fields, which are
+/// recorded as the operation runs. The parent is the current span
+/// unless one is given, either as `parent` or as an `Option` `within`,
+/// in which case there is no span if it is `None`.
+///
+/// With the `otel` feature the span also has empty fields for the
+/// attributes set by [`record_db`].
+macro_rules! op_span {
+    ($name:literal, parent: $parent:expr $(, $field:ident = $value:expr)* $(,)?) => {{
+        #[cfg(all(feature = "tracing", not(feature = "otel")))]
+        let span = ::tracing::info_span!(
+            parent: $parent,
+            $name,
+            $($field = $value,)*
+            db.statement = ::tracing::field::Empty,
+            duration_ms = ::tracing::field::Empty,
+        );
+        #[cfg(feature = "otel")]
+        let span = ::tracing::info_span!(
+            parent: $parent,
+            $name,
+            $($field = $value,)*
+            db.statement = ::tracing::field::Empty,
+            duration_ms = ::tracing::field::Empty,
+            db.system = ::tracing::field::Empty,
+            db.name = ::tracing::field::Empty,
+            db.operation = ::tracing::field::Empty,
+            net.peer.name = ::tracing::field::Empty,
+        );
+        #[cfg(not(feature = "tracing"))]
+        let span = {
+            let _ = &$parent;
+            $(let _ = &$value;)*
+            $crate::trace::Span
+        };
+        span
+    }};
+    // A span which is disabled if there is no parent.
+    ($name:literal, within: $within:expr $(, $field:ident = $value:expr)* $(,)?) => {
+        match $within {
+            Some(parent) => $crate::trace::op_span!($name, parent: &parent $(, $field = $value)*),
+            None => $crate::trace::Span::none(),
+        }
+    };
+    ($name:literal $(, $field:ident = $value:expr)* $(,)?) => {
+        $crate::trace::op_span!(
+            $name,
+            parent: &$crate::trace::Span::current()
+            $(, $field = $value)*
+        )
+    };
+}
+pub(crate) use op_span;
+
+/// Run `f` in `span`, recording how long it took.
+pub(crate) fn in_span<T>(span: Span, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    #[cfg(feature = "tracing")]
+    let result = span.in_scope(f);
+    #[cfg(not(feature = "tracing"))]
+    let result = f();
+    record_duration(&span, start);
+    result
+}
+
+/// Run the future returned by `f` in `span`, recording how long it took.
+#[cfg(feature = "async")]
+pub(crate) async fn in_span_async<T, Fut>(span: Span, f: impl FnOnce() -> Fut) -> T
+where
+    Fut: Future<Output = T>,
+{
+    let start = Instant::now();
+    #[cfg(feature = "tracing")]
+    let result = tracing::Instrument::instrument(f(), span.clone()).await;
+    #[cfg(not(feature = "tracing"))]
+    let result = f().await;
+    record_duration(&span, start);
+    result
+}
+
+/// Wrap `f`, which is to run on another thread, to run in the
+/// current span.
+#[cfg(feature = "async")]
+pub(crate) fn in_current_span<T>(f: impl FnOnce() -> T) -> impl FnOnce() -> T {
+    #[cfg(feature = "tracing")]
+    {
+        // The subscriber may be the default only on this thread.
+        let dispatch = tracing::dispatcher::get_default(Clone::clone);
+        let span = Span::current();
+        move || tracing::dispatcher::with_default(&dispatch, || span.in_scope(f))
+    }
+    #[cfg(not(feature = "tracing"))]
+    f
+}
+
+/// Record the SQL of a statement on the current span.
+pub(crate) fn record_statement(sql: &str) {
+    #[cfg(feature = "tracing")]
+    Span::current().record("db.statement", sql);
+    #[cfg(not(feature = "tracing"))]
+    let _ = sql;
+}
+
+/// Record the time since `start` on `span`.
+pub(crate) fn record_duration(span: &Span, start: Instant) {
+    #[cfg(feature = "tracing")]
+    span.record("duration_ms", start.elapsed().as_secs_f64() * 1000.0);
+    #[cfg(not(feature = "tracing"))]
+    let _ = (span, start);
+}
+
+/// What a connection is connected to, described by the attributes of
+/// the OpenTelemetry semantic conventions for database clients.
+/// Without the `otel` feature it is empty.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DbAttributes {
+    #[cfg(feature = "otel")]
+    inner: Option<Arc<DbAttributesInner>>,
+}
+
+#[cfg(feature = "otel")]
+#[derive(Debug)]
+struct DbAttributesInner {
+    system: &'static str,
+    name: Option<String>,
+    peer: Option<String>,
+}
+
+impl DbAttributes {
+    /// Describe a connection to the database `name` of the `system`,
+    /// such as `postgresql`, on the host `peer`.
+    pub(crate) fn new(system: &'static str, name: Option<&str>, peer: Option<&str>) -> Self {
+        #[cfg(feature = "otel")]
+        return DbAttributes {
+            inner: Some(Arc::new(DbAttributesInner {
+                system,
+                name: name.map(str::to_string),
+                peer: peer.map(str::to_string),
+            })),
+        };
+        #[cfg(not(feature = "otel"))]
+        {
+            let _ = (system, name, peer);
+            DbAttributes {}
+        }
+    }
+}
+
+/// Record on `span` the attributes of the database it operates on,
+/// and its `operation`, the SQL keyword such as `SELECT` it begins
+/// with.
+pub(crate) fn record_db(span: &Span, db: &DbAttributes, operation: &str) {
+    #[cfg(feature = "otel")]
+    if let Some(db) = &db.inner {
+        span.record("db.system", db.system);
+        if let Some(name) = &db.name {
+            span.record("db.name", name.as_str());
+        }
+        if let Some(peer) = &db.peer {
+            span.record("net.peer.name", peer.as_str());
+        }
+        span.record("db.operation", operation.to_ascii_uppercase().as_str());
+    }
+    #[cfg(not(feature = "otel"))]
+    let _ = (span, db, operation);
+}
+
+/// The first keyword of `sql`, which is its operation.
+pub(crate) fn operation(sql: &str) -> &str {
+    sql.split_whitespace().next().unwrap_or_default()
+}
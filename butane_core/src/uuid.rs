@@ -4,28 +4,29 @@
 use uuid::Uuid;
 
 use crate::{
-    Error::CannotConvertSqlVal, FieldType, FromSql, PrimaryKeyType, Result, SqlType, SqlVal,
-    SqlValRef, ToSql,
+    ArrayElement, Error::CannotConvertSqlVal, FieldType, FromSql, PrimaryKeyType, Result, SqlType,
+    SqlVal, SqlValRef, ToSql,
 };
 
 impl ToSql for Uuid {
     fn to_sql(&self) -> SqlVal {
-        SqlVal::Blob(self.as_bytes().to_vec())
+        SqlVal::Uuid(*self)
     }
     fn to_sql_ref(&self) -> SqlValRef<'_> {
-        SqlValRef::Blob(self.as_bytes())
+        SqlValRef::Uuid(*self)
     }
 }
 impl FromSql for Uuid {
     fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
         match valref {
+            SqlValRef::Uuid(uuid) => return Ok(uuid),
+            // Columns created before UUIDs had their own type hold
+            // them as blobs.
             SqlValRef::Blob(bytes) => {
                 if let Ok(uuid) = Uuid::from_slice(bytes) {
                     return Ok(uuid);
                 }
             }
-            // Generally we expect uuid to be a blob, but if we get a
-            // string we can try to work with it.
             SqlValRef::Text(text) => {
                 if let Ok(uuid) = Uuid::parse_str(text) {
                     return Ok(uuid);
@@ -33,16 +34,17 @@ fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
             }
             _ => (),
         }
-        Err(CannotConvertSqlVal(SqlType::Blob, valref.into()))
+        Err(CannotConvertSqlVal(SqlType::Uuid, valref.into()))
     }
     // No point in implementing a `from_sql` method for greater
-    // efficiency since to construct the UUID we always end up copying
-    // the bytes.
+    // efficiency since a UUID is Copy.
 }
 
 impl FieldType for Uuid {
-    const SQLTYPE: SqlType = SqlType::Blob;
+    const SQLTYPE: SqlType = SqlType::Uuid;
     type RefType = Self;
 }
 
 impl PrimaryKeyType for Uuid {}
+
+impl ArrayElement for Uuid {}
@@ -1,6 +1,7 @@
 use butane_core::db::ConnectionAsync;
 use butane_core::migrations::adb::*;
-use butane_core::SqlType;
+use butane_core::migrations::{schema_drift, schema_snapshot, SchemaDrift};
+use butane_core::{SqlType, SqlVal};
 use butane_test_helper::*;
 use butane_test_macros::butane_test;
 
@@ -759,3 +760,716 @@ fn add_table_many_ddl_pg() {
         ]
     );
 }
+
+/// Toggling `deferrable` on a foreign key column changes the existing constraint.
+#[butane_test(nomigrate)]
+async fn change_fkey_deferrable(conn: ConnectionAsync) {
+    let known_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int));
+
+    let old = ADB::default();
+    let mut new = ADB::default();
+    let mut table_a = ATable::new("a".to_owned());
+    let id_column = AColumn::new(
+        "id".to_owned(),
+        known_int_type.clone(),
+        false, // nullable
+        true,  // pk
+        false, // auto
+        false, // unique
+        None,  // default
+        None,  // reference
+    );
+    table_a.add_column(id_column.clone());
+    new.replace_table(table_a);
+
+    let mut table_b = ATable::new("b".to_owned());
+    table_b.add_column(id_column);
+    let column = AColumn::new(
+        "fkey".to_owned(),
+        known_int_type,
+        false, // nullable
+        false, // pk
+        false, // auto
+        false, // unique
+        None,  // default
+        Some(ARef::Literal(ARefLiteral::new(
+            "a".to_owned(),
+            "id".to_owned(),
+        ))),
+    );
+    table_b.add_column(column.clone());
+    new.replace_table(table_b.clone());
+
+    let backend = conn.backend();
+    let sql = backend
+        .create_migration_sql(&new, diff(&old, &new))
+        .unwrap();
+    conn.execute(&sql).await.unwrap();
+
+    let mut deferrable_column = column.clone();
+    deferrable_column.set_deferrable(true);
+    let mut deferred = new.clone();
+    table_b.replace_column(deferrable_column.clone());
+    deferred.replace_table(table_b);
+
+    let ops = diff(&new, &deferred);
+    assert_eq!(
+        ops,
+        vec![Operation::ChangeColumn(
+            "b".to_owned(),
+            column,
+            deferrable_column
+        )]
+    );
+
+    let sql = backend.create_migration_sql(&new, ops).unwrap();
+    if backend.name() == "pg" {
+        assert!(sql.contains("DEFERRABLE INITIALLY DEFERRED"));
+    }
+    conn.execute(&sql).await.unwrap();
+    conn.execute("SELECT * from b").await.unwrap();
+}
+
+/// The constraints PostgreSQL names itself are named after the table
+/// and column as it stores them, folded to lower case unless quoted.
+#[test]
+fn change_fkey_mixed_case_ddl_pg() {
+    let known_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int));
+    let reference = |table: &str| {
+        Some(ARef::Literal(ARefLiteral::new(
+            table.to_owned(),
+            "id".to_owned(),
+        )))
+    };
+    let id_column = AColumn::new(
+        "id".to_owned(),
+        known_int_type.clone(),
+        false, // nullable
+        true,  // pk
+        false, // auto
+        false, // unique
+        None,  // default
+        None,  // reference
+    );
+    let mut db = ADB::default();
+    let mut account = ATable::new("Account".to_owned());
+    account.add_column(id_column.clone());
+    db.replace_table(account);
+
+    // A reserved word, which is quoted and so keeps its case.
+    let mut order = ATable::new("Order".to_owned());
+    order.add_column(id_column.clone());
+    let parent = AColumn::new(
+        "ParentId".to_owned(),
+        known_int_type.clone(),
+        false, // nullable
+        false, // pk
+        false, // auto
+        false, // unique
+        None,  // default
+        reference("Account"),
+    );
+    order.add_column(parent.clone());
+    db.replace_table(order);
+
+    let mut item = ATable::new("Item".to_owned());
+    item.add_column(id_column);
+    let order_id = AColumn::new(
+        "OrderId".to_owned(),
+        known_int_type.clone(),
+        false, // nullable
+        false, // pk
+        false, // auto
+        true,  // unique
+        None,  // default
+        reference("Order"),
+    );
+    item.add_column(order_id.clone());
+    db.replace_table(item);
+
+    let mut deferrable_parent = parent.clone();
+    deferrable_parent.set_deferrable(true);
+    let plain_order_id = AColumn::new(
+        "OrderId".to_owned(),
+        known_int_type,
+        false, // nullable
+        false, // pk
+        false, // auto
+        false, // unique
+        None,  // default
+        None,  // reference
+    );
+    let ops = vec![
+        Operation::ChangeColumn("Order".to_owned(), parent, deferrable_parent),
+        Operation::ChangeColumn("Item".to_owned(), order_id, plain_order_id),
+    ];
+
+    let backend = butane_core::db::get_backend("pg").unwrap();
+    let sql = backend.create_migration_sql(&db, ops).unwrap();
+    let sql_lines: Vec<&str> = sql.lines().collect();
+    assert!(sql_lines.contains(
+        &"ALTER TABLE \"Order\" ALTER CONSTRAINT \"Order_parentid_fkey\" DEFERRABLE INITIALLY DEFERRED;"
+    ));
+    assert!(sql_lines.contains(&"ALTER TABLE Item DROP CONSTRAINT \"item_orderid_fkey\";"));
+    assert!(sql_lines.contains(&"ALTER TABLE Item DROP CONSTRAINT \"item_orderid_key\";"));
+}
+
+#[butane_test(nomigrate)]
+async fn rename_column(conn: ConnectionAsync) {
+    let known_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int));
+    let known_text_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Text));
+
+    let old = ADB::default();
+    let mut new = ADB::default();
+    let mut table = ATable::new("a".to_owned());
+    table.add_column(AColumn::new(
+        "id".to_owned(),
+        known_int_type,
+        false, // nullable
+        true,  // pk
+        false, // auto
+        false, // unique
+        None,  // default
+        None,  // reference
+    ));
+    let column = AColumn::new(
+        "label".to_owned(),
+        known_text_type,
+        false, // nullable
+        false, // pk
+        false, // auto
+        false, // unique
+        None,  // default
+        None,  // reference
+    );
+    table.add_column(column.clone());
+    new.replace_table(table.clone());
+
+    let backend = conn.backend();
+    let sql = backend
+        .create_migration_sql(&new, diff(&old, &new))
+        .unwrap();
+    conn.execute(&sql).await.unwrap();
+    conn.execute("INSERT INTO a (id, label) VALUES (1, 'kept');")
+        .await
+        .unwrap();
+
+    let mut renamed_column = column.clone();
+    renamed_column.set_name("title".to_owned());
+    renamed_column.set_renamed_from(Some("label".to_owned()));
+    let mut renamed = new.clone();
+    table.remove_column("label");
+    table.add_column(renamed_column.clone());
+    renamed.replace_table(table);
+
+    let ops = diff(&new, &renamed);
+    assert_eq!(
+        ops,
+        vec![Operation::ChangeColumn(
+            "a".to_owned(),
+            column,
+            renamed_column
+        )]
+    );
+
+    let sql = backend.create_migration_sql(&new, ops).unwrap();
+    assert!(sql.contains("RENAME COLUMN"));
+    assert!(!sql.contains("DROP COLUMN"));
+    conn.execute(&sql).await.unwrap();
+    conn.execute("SELECT title FROM a WHERE title = 'kept';")
+        .await
+        .unwrap();
+}
+
+#[test]
+fn rename_column_without_renamed_from_is_drop_and_add() {
+    let known_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int));
+    let mut old = ADB::default();
+    let mut table = ATable::new("a".to_owned());
+    let column = AColumn::new_simple("label".to_owned(), known_int_type);
+    table.add_column(column.clone());
+    old.replace_table(table.clone());
+
+    let mut renamed_column = column;
+    renamed_column.set_name("title".to_owned());
+    let mut new = old.clone();
+    table.remove_column("label");
+    table.add_column(renamed_column.clone());
+    new.replace_table(table);
+
+    let ops = diff(&old, &new);
+    assert_eq!(
+        ops,
+        vec![
+            Operation::AddColumn("a".to_owned(), renamed_column),
+            Operation::RemoveColumn("a".to_owned(), "label".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn add_change_and_remove_index() {
+    let known_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int));
+    let mut table = ATable::new("a".to_owned());
+    table.add_column(AColumn::new_simple("b".to_owned(), known_int_type.clone()));
+    table.add_column(AColumn::new_simple("c".to_owned(), known_int_type));
+    let mut old = ADB::default();
+    old.replace_table(table.clone());
+
+    let index_b = AIndex::new("a_b_idx", vec!["b".to_owned()]);
+    table.add_index(index_b.clone());
+    let mut new = ADB::default();
+    new.replace_table(table.clone());
+    let ops = diff(&old, &new);
+    assert_eq!(
+        ops,
+        vec![Operation::AddIndex("a".to_owned(), index_b.clone())]
+    );
+    assert_eq!(
+        diff(&new, &old),
+        vec![Operation::RemoveIndex("a".to_owned(), "a_b_idx".to_owned())]
+    );
+
+    // A changed index is dropped before columns change, and recreated after
+    let mut old = new.clone();
+    let mut unique_b = index_b.clone();
+    unique_b.set_unique(true);
+    let mut changed = table.clone();
+    changed.add_index(unique_b.clone());
+    changed.remove_column("c");
+    new.replace_table(changed);
+    let ops = diff(&old, &new);
+    assert_eq!(
+        ops,
+        vec![
+            Operation::RemoveIndex("a".to_owned(), "a_b_idx".to_owned()),
+            Operation::RemoveColumn("a".to_owned(), "c".to_owned()),
+            Operation::AddIndex("a".to_owned(), unique_b),
+        ]
+    );
+    for op in ops {
+        old.transform_with(op);
+    }
+    assert!(diff(&old, &new).is_empty());
+}
+
+#[test]
+fn add_and_remove_constraints() {
+    let known_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int));
+    let mut table = ATable::new("a".to_owned());
+    table.add_column(AColumn::new_simple("b".to_owned(), known_int_type.clone()));
+    table.add_column(AColumn::new_simple("c".to_owned(), known_int_type));
+    let mut old = ADB::default();
+    old.replace_table(table.clone());
+
+    let unique = AConstraint::unique("a_b_c_key", vec!["b".to_owned(), "c".to_owned()]);
+    let fkey =
+        AConstraint::foreign_key("a_c_fkey", vec!["c".to_owned()], "d", vec!["id".to_owned()]);
+    table.add_constraint(unique.clone());
+    table.add_constraint(fkey.clone());
+    let mut new = ADB::default();
+    new.replace_table(table.clone());
+    let ops = diff(&old, &new);
+    assert_eq!(
+        ops,
+        vec![
+            Operation::AddConstraint("a".to_owned(), unique.clone()),
+            Operation::AddConstraint("a".to_owned(), fkey),
+        ]
+    );
+    assert_eq!(
+        diff(&new, &old),
+        vec![
+            Operation::RemoveConstraint("a".to_owned(), "a_b_c_key".to_owned()),
+            Operation::RemoveConstraint("a".to_owned(), "a_c_fkey".to_owned()),
+        ]
+    );
+
+    let pg = butane_core::db::get_backend("pg").unwrap();
+    let sql = pg.create_migration_sql(&old, ops.clone()).unwrap();
+    assert_eq!(
+        sql,
+        "ALTER TABLE a ADD CONSTRAINT a_b_c_key UNIQUE (b, c);\n\
+         ALTER TABLE a ADD CONSTRAINT a_c_fkey FOREIGN KEY (c) REFERENCES d(\"id\");"
+    );
+    // SQLite cannot add constraints to a table, so rebuilds it.
+    let sqlite = butane_core::db::get_backend("sqlite").unwrap();
+    let sql = sqlite
+        .create_migration_sql(&old, vec![ops[0].clone()])
+        .unwrap();
+    assert!(sql.contains("CONSTRAINT a_b_c_key UNIQUE (b, c)"), "{sql}");
+    assert!(sql.contains("a__butane_tmp"), "{sql}");
+
+    // Dropping a column drops the constraints covering it
+    let mut changed = new.clone();
+    changed.transform_with(Operation::RemoveColumn("a".to_owned(), "b".to_owned()));
+    let constraints = &changed.get_table("a").unwrap().constraints;
+    assert_eq!(constraints.len(), 1);
+    assert_eq!(constraints[0].name(), "a_c_fkey");
+}
+
+#[test]
+fn add_table_with_index() {
+    let known_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int));
+    let mut table = ATable::new("a".to_owned());
+    table.add_column(AColumn::new_simple("b".to_owned(), known_int_type));
+    let mut index = AIndex::new("a_b_idx", vec!["b".to_owned()]);
+    index.set_method(Some("hash".to_owned()));
+    table.add_index(index.clone());
+    let mut new = ADB::default();
+    new.replace_table(table.clone());
+
+    let ops = diff(&ADB::default(), &new);
+    assert_eq!(
+        ops,
+        vec![
+            Operation::AddTable(table),
+            Operation::AddIndex("a".to_owned(), index),
+        ]
+    );
+
+    let sqlite = butane_core::db::get_backend("sqlite").unwrap();
+    let sql = sqlite
+        .create_migration_sql(&ADB::default(), ops.clone())
+        .unwrap();
+    assert!(sql.ends_with("\nCREATE INDEX a_b_idx ON a (b);"), "{sql}");
+    let pg = butane_core::db::get_backend("pg").unwrap();
+    let sql = pg.create_migration_sql(&ADB::default(), ops).unwrap();
+    assert!(
+        sql.ends_with("\nCREATE INDEX a_b_idx ON a USING hash (b);"),
+        "{sql}"
+    );
+}
+
+#[test]
+fn sqlite_table_rebuild_recreates_indexes() {
+    let known_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int));
+    let mut table = ATable::new("a".to_owned());
+    table.add_column(AColumn::new_simple("b".to_owned(), known_int_type.clone()));
+    table.add_index(AIndex::new("a_b_idx", vec!["b".to_owned()]));
+    let mut current = ADB::default();
+    current.replace_table(table);
+
+    let old = AColumn::new_simple("b".to_owned(), known_int_type.clone());
+    let new = AColumn::new("b", known_int_type, true, false, false, false, None, None);
+    let sqlite = butane_core::db::get_backend("sqlite").unwrap();
+    let sql = sqlite
+        .create_migration_sql(
+            &current,
+            vec![Operation::ChangeColumn("a".to_owned(), old, new)],
+        )
+        .unwrap();
+    assert!(sql.contains("DROP TABLE a;"), "{sql}");
+    assert!(sql.ends_with("\nCREATE INDEX a_b_idx ON a (b);"), "{sql}");
+}
+
+#[test]
+fn schema_drift_missing_and_mismatched() {
+    let backend = butane_core::db::get_backend("sqlite").unwrap();
+    let known_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int));
+    let known_text_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Text));
+
+    let mut expected = ADB::default();
+    let mut table = ATable::new("a".to_owned());
+    table.add_column(AColumn::new_simple("id", known_int_type.clone()));
+    table.add_column(AColumn::new_simple("label", known_text_type.clone()));
+    table.add_column(AColumn::new_simple("gone", known_int_type.clone()));
+    expected.replace_table(table);
+    expected.replace_table(ATable::new("b".to_owned()));
+
+    // Int and BigInt are the same type to SQLite.
+    let mut actual = ADB::default();
+    let mut table = ATable::new("A".to_owned());
+    let big_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::BigInt));
+    table.add_column(AColumn::new_simple("id", big_int_type));
+    let mismatched = AColumn::new(
+        "label",
+        known_text_type,
+        true,  // nullable
+        false, // pk
+        false, // auto
+        false, // unique
+        None,  // default
+        None,  // reference
+    );
+    table.add_column(mismatched.clone());
+    table.add_column(AColumn::new_simple("extra", known_int_type));
+    actual.replace_table(table);
+    actual.replace_table(ATable::new("c".to_owned()));
+
+    let drift = schema_drift(&expected, &actual, backend.as_ref());
+    assert_eq!(
+        drift,
+        vec![
+            SchemaDrift::MismatchedColumn {
+                table: "a".to_owned(),
+                expected: Box::new(AColumn::new_simple(
+                    "label",
+                    DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Text))
+                )),
+                actual: Box::new(mismatched),
+            },
+            SchemaDrift::MissingColumn("a".to_owned(), "gone".to_owned()),
+            SchemaDrift::ExtraColumn("a".to_owned(), "extra".to_owned()),
+            SchemaDrift::MissingTable("b".to_owned()),
+            SchemaDrift::ExtraTable("c".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn detect_renamed_table() {
+    let known_int_type = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int));
+    let mut a = ATable::new("a".to_owned());
+    a.add_column(AColumn::new_simple("b".to_owned(), known_int_type.clone()));
+    let mut old = ADB::default();
+    old.replace_table(a.clone());
+
+    // The same columns under a new name
+    let mut renamed = a.clone();
+    renamed.name = "c".to_owned();
+    let mut new = ADB::default();
+    new.replace_table(renamed.clone());
+    assert_eq!(
+        diff(&old, &new),
+        vec![Operation::RenameTable("a".to_owned(), "c".to_owned())]
+    );
+    assert_eq!(
+        diff(&new, &old),
+        vec![Operation::RenameTable("c".to_owned(), "a".to_owned())]
+    );
+
+    // Ambiguous without a hint
+    let mut other = a.clone();
+    other.name = "d".to_owned();
+    new.replace_table(other);
+    assert!(!diff(&old, &new)
+        .iter()
+        .any(|op| matches!(op, Operation::RenameTable(..))));
+
+    // A hint renames the table even if its columns change
+    renamed.set_renamed_from(Some("a".to_owned()));
+    renamed.add_column(AColumn::new_simple("e".to_owned(), known_int_type));
+    new.replace_table(renamed.clone());
+    let ops = diff(&old, &new);
+    assert_eq!(
+        ops[0],
+        Operation::RenameTable("a".to_owned(), "c".to_owned())
+    );
+    assert!(ops.contains(&Operation::AddColumn(
+        "c".to_owned(),
+        renamed.column("e").unwrap().clone()
+    )));
+    assert!(!ops.contains(&Operation::RemoveTable("a".to_owned())));
+}
+
+#[test]
+fn diff_sql_between_snapshots() {
+    let mut table = ATable::new("a".to_owned());
+    table.add_column(AColumn::new_simple(
+        "b".to_owned(),
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int)),
+    ));
+    let mut old = ADB::default();
+    old.replace_table(table.clone());
+    table.add_column(AColumn::new(
+        "c",
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Text)),
+        true,
+        false,
+        false,
+        false,
+        None,
+        None,
+    ));
+    let mut new = ADB::default();
+    new.replace_table(table);
+
+    // Snapshots can be saved and loaded
+    let old: ADB = serde_json::from_str(&serde_json::to_string(&old).unwrap()).unwrap();
+    let sqlite = butane_core::db::get_backend("sqlite").unwrap();
+    let (up, down) = butane_core::migrations::diff_sql(sqlite.as_ref(), &old, &new).unwrap();
+    assert_eq!(up, "ALTER TABLE a ADD COLUMN c TEXT DEFAULT NULL;");
+    assert_eq!(down, "ALTER TABLE a DROP COLUMN c;");
+}
+
+#[test]
+fn schema_snapshot_text() {
+    let mut blog = ATable::new("blog".to_owned());
+    blog.add_column(AColumn::new(
+        "id",
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::BigInt)),
+        false, // nullable
+        true,  // pk
+        true,  // auto
+        false, // unique
+        None,
+        None,
+    ));
+    let mut name = AColumn::new_simple(
+        "name",
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Text)),
+    );
+    name.set_renamed_from(Some("title".to_owned()));
+    blog.add_column(name);
+    let mut post = ATable::new("post".to_owned());
+    post.add_column(AColumn::new(
+        "title",
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Text)),
+        false, // nullable
+        true,  // pk
+        false, // auto
+        false, // unique
+        None,
+        None,
+    ));
+    post.add_column(AColumn::new(
+        "blog",
+        DeferredSqlType::Deferred(TypeKey::PK("blog".to_owned())),
+        true,  // nullable
+        false, // pk
+        false, // auto
+        false, // unique
+        Some(SqlVal::Text("none".to_owned())),
+        Some(ARef::Literal(ARefLiteral::new("blog", "id"))),
+    ));
+    post.add_index(AIndex::new("post_blog_idx", vec!["blog".to_owned()]));
+    post.add_constraint(AConstraint::check("post_title_check", "title <> ''"));
+    let mut db = email_domain_db("TEXT CHECK (VALUE LIKE '%@%')");
+    db.replace_table(post);
+    db.replace_table(blog);
+
+    let snapshot = schema_snapshot(&db).unwrap();
+    assert_eq!(
+        snapshot,
+        "table blog
+  column id: big int, primary key, auto
+  column name: string
+table person
+  column email: email_address
+table post
+  column title: string, primary key
+  column blog: big int, nullable, default \"none\", references blog.id
+  index post_blog_idx (blog)
+  constraint post_title_check check (title <> '')
+type email_address: domain TEXT CHECK (VALUE LIKE '%@%')
+"
+    );
+}
+
+/// A database with a table whose column has the user-defined type
+/// `email_address`, defined as `definition`.
+fn email_domain_db(definition: &str) -> ADB {
+    let mut db = ADB::default();
+    db.replace_custom_type(ACustomType::new(
+        "email_address",
+        ACustomTypeKind::Domain,
+        definition,
+    ));
+    let mut table = ATable::new("person".to_owned());
+    table.add_column(AColumn::new_simple(
+        "email".to_owned(),
+        DeferredSqlType::KnownId(TypeIdentifier::Name("email_address".to_owned())),
+    ));
+    db.replace_table(table);
+    db
+}
+
+#[test]
+fn custom_type_diff() {
+    let new = email_domain_db("TEXT");
+    let ty = new.get_custom_type("email_address").unwrap().clone();
+    let table = new.get_table("person").unwrap().clone();
+
+    // The type is created before the table using it and dropped after
+    assert_eq!(
+        diff(&ADB::default(), &new),
+        vec![
+            Operation::AddCustomType(ty.clone()),
+            Operation::AddTable(table.clone())
+        ]
+    );
+    assert_eq!(
+        diff(&new, &ADB::default()),
+        vec![
+            Operation::RemoveTableConstraints(table),
+            Operation::RemoveTable("person".to_owned()),
+            Operation::RemoveCustomType(ty.clone()),
+        ]
+    );
+
+    let changed = email_domain_db("TEXT CHECK (VALUE LIKE '%@%')");
+    assert_eq!(
+        diff(&new, &changed),
+        vec![Operation::ChangeCustomType(
+            ty,
+            changed.get_custom_type("email_address").unwrap().clone()
+        )]
+    );
+    assert_eq!(diff(&changed, &changed), vec![]);
+}
+
+#[test]
+fn custom_type_ddl_pg() {
+    let old = email_domain_db("TEXT");
+    let backend = butane_core::db::get_backend("pg").unwrap();
+    let sql = backend
+        .create_migration_sql(&ADB::default(), diff(&ADB::default(), &old))
+        .unwrap();
+    let sql_lines: Vec<&str> = sql.lines().collect();
+    assert_eq!(
+        sql_lines,
+        vec![
+            "CREATE DOMAIN email_address AS TEXT;",
+            "CREATE TABLE person (",
+            "email email_address NOT NULL",
+            ");",
+        ]
+    );
+
+    let new = email_domain_db("TEXT CHECK (VALUE LIKE '%@%')");
+    let sql = backend
+        .create_migration_sql(&old, diff(&old, &new))
+        .unwrap();
+    let sql_lines: Vec<&str> = sql.lines().collect();
+    assert_eq!(
+        sql_lines,
+        vec![
+            "ALTER DOMAIN email_address RENAME TO email_address_butane_old;",
+            "CREATE DOMAIN email_address AS TEXT CHECK (VALUE LIKE '%@%');",
+            "ALTER TABLE person ALTER COLUMN email SET DATA TYPE email_address USING email::text::email_address;",
+            "DROP DOMAIN email_address_butane_old;",
+        ]
+    );
+
+    let mut point = ADB::default();
+    point.replace_custom_type(ACustomType::new(
+        "point2d",
+        ACustomTypeKind::Composite,
+        "x DOUBLE PRECISION, y DOUBLE PRECISION",
+    ));
+    let sql = backend
+        .create_migration_sql(&ADB::default(), diff(&ADB::default(), &point))
+        .unwrap();
+    assert_eq!(
+        sql,
+        "CREATE TYPE point2d AS (x DOUBLE PRECISION, y DOUBLE PRECISION);"
+    );
+    let sql = backend
+        .create_migration_sql(&point, diff(&point, &ADB::default()))
+        .unwrap();
+    assert_eq!(sql, "DROP TYPE point2d;");
+}
+
+#[test]
+fn custom_type_unsupported_sqlite() {
+    let new = email_domain_db("TEXT");
+    let backend = butane_core::db::get_backend("sqlite").unwrap();
+    let err = backend
+        .create_migration_sql(&ADB::default(), diff(&ADB::default(), &new))
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        butane_core::Error::Unsupported("User-defined types", "sqlite")
+    ));
+}
@@ -1,5 +1,11 @@
-use butane_core::codegen::{get_deferred_sql_type, make_ident_literal_str, make_lit};
+#[cfg(feature = "sqlite")]
+use butane_core::codegen::{butane_type_with_migrations, model_with_migrations};
+use butane_core::codegen::{
+    embed_migrations, get_deferred_sql_type, make_ident_literal_str, make_lit,
+};
 use butane_core::migrations::adb::{DeferredSqlType, TypeIdentifier, TypeKey};
+#[cfg(feature = "sqlite")]
+use butane_core::migrations::{FsMigrations, Migration, Migrations, MigrationsMut};
 use butane_core::SqlType;
 use proc_macro2::Span;
 use syn::{Ident, LitStr};
@@ -39,6 +45,25 @@ fn test_get_deferred_sql_type() {
         panic!()
     }
 
+    // arrays, but a Vec<u8> is a blob
+    let type_path: syn::TypePath = syn::parse_quote!(Vec<i64>);
+    let typ = syn::Type::Path(type_path);
+    let rv = get_deferred_sql_type(&typ);
+    if let DeferredSqlType::KnownId(TypeIdentifier::Ty(sql_type)) = rv {
+        assert_eq!(sql_type, SqlType::Array(&SqlType::BigInt));
+    } else {
+        panic!()
+    }
+
+    let type_path: syn::TypePath = syn::parse_quote!(Vec<u8>);
+    let typ = syn::Type::Path(type_path);
+    let rv = get_deferred_sql_type(&typ);
+    if let DeferredSqlType::KnownId(TypeIdentifier::Ty(sql_type)) = rv {
+        assert_eq!(sql_type, SqlType::Blob);
+    } else {
+        panic!()
+    }
+
     // custom types
     let type_path: syn::TypePath = syn::parse_quote!(Foo);
     let typ = syn::Type::Path(type_path);
@@ -86,3 +111,131 @@ fn test_get_deferred_sql_type() {
         panic!()
     }
 }
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn embed_fs_migrations() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut ms = FsMigrations::new(dir.path().into());
+    let backends = nonempty::NonEmpty::new(butane_core::db::get_backend("sqlite").unwrap());
+    ms.create_empty_migration(&backends, "init", None).unwrap();
+
+    let tokens = embed_migrations(dir.path()).to_string();
+    assert!(tokens.contains("butane :: migrations :: MemMigrations :: from_bytes (b\""));
+    let info = dir.path().join("init").join("info.json");
+    assert!(tokens.contains(&format!("include_bytes ! ({:?})", info.to_string_lossy())));
+    let current = dir.path().join("current");
+    assert!(!tokens.contains(current.to_string_lossy().as_ref()));
+
+    let missing = embed_migrations(&dir.path().join("missing")).to_string();
+    assert!(missing.starts_with("compile_error !"), "{missing}");
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn unchanged_tables_not_rewritten() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut ms = FsMigrations::new(dir.path().into());
+    let model = quote::quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    model_with_migrations(model.clone(), &mut ms);
+    let table = dir.path().join("current").join("Foo.table");
+    let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(86400);
+    std::fs::File::options()
+        .write(true)
+        .open(&table)
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+
+    // Expanding the same model again leaves its table alone.
+    model_with_migrations(model, &mut ms);
+    let modified = || std::fs::metadata(&table).unwrap().modified().unwrap();
+    assert_eq!(modified(), old);
+
+    // A changed model is written.
+    let changed = quote::quote! {
+        struct Foo {
+            id: i64,
+            baz: String,
+        }
+    };
+    model_with_migrations(changed, &mut ms);
+    assert_ne!(modified(), old);
+    assert!(std::fs::read_to_string(&table).unwrap().contains("baz"));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn custom_type_definition() {
+    use butane_core::migrations::adb::{ACustomType, ACustomTypeKind};
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut ms = FsMigrations::new(dir.path().into());
+    let tokens = butane_type_with_migrations(
+        quote::quote!(
+            Custom(email_address),
+            domain = "TEXT CHECK (VALUE LIKE '%@%')"
+        ),
+        quote::quote!(
+            struct Email(String);
+        ),
+        &mut ms,
+    );
+    assert!(!tokens.to_string().contains("compile_error"), "{tokens}");
+    model_with_migrations(
+        quote::quote! {
+            struct Person {
+                id: i64,
+                email: Email,
+            }
+        },
+        &mut ms,
+    );
+
+    let db = ms.current().db().unwrap();
+    assert_eq!(
+        db.get_custom_type("email_address"),
+        Some(&ACustomType::new(
+            "email_address",
+            ACustomTypeKind::Domain,
+            "TEXT CHECK (VALUE LIKE '%@%')"
+        ))
+    );
+    let column = db.get_table("Person").unwrap().column("email").unwrap();
+    assert_eq!(
+        column.typeid().unwrap(),
+        TypeIdentifier::Name("email_address".to_string())
+    );
+
+    // Migrations carry the type forward
+    let backends = nonempty::NonEmpty::new(butane_core::db::get_backend("pg").unwrap());
+    ms.create_migration(&backends, "init", None).unwrap();
+    let init = ms.latest().unwrap();
+    assert!(init
+        .db()
+        .unwrap()
+        .get_custom_type("email_address")
+        .is_some());
+    assert!(init
+        .up_sql("pg")
+        .unwrap()
+        .unwrap()
+        .starts_with("CREATE DOMAIN email_address AS TEXT CHECK (VALUE LIKE '%@%');"));
+
+    let tokens = butane_type_with_migrations(
+        quote::quote!(Custom(email_address), domian = "TEXT"),
+        quote::quote!(
+            struct Email(String);
+        ),
+        &mut ms,
+    );
+    assert!(
+        tokens.to_string().starts_with("compile_error !"),
+        "{tokens}"
+    );
+}
@@ -1,4 +1,8 @@
-use butane_core::db::{connect_async, ConnectionAsync, ConnectionSpec};
+use butane_core::db::{
+    connect_async, BackendRows, Column, ConnectionAsync, ConnectionMethodsAsync,
+    ConnectionProfiles, ConnectionSpec,
+};
+use butane_core::{SqlType, SqlVal};
 use butane_test_helper::*;
 use butane_test_macros::butane_test;
 
@@ -118,6 +122,75 @@ async fn unreachable_pg_connection() {
     }
 }
 
+/// The number of statements prepared in this Postgres session.
+async fn prepared_statement_count(conn: &ConnectionAsync) -> usize {
+    let columns = [Column::new("name", SqlType::Text)];
+    let mut rows = conn
+        .query("pg_prepared_statements", &columns, None, None, None, None)
+        .await
+        .unwrap();
+    let mut count = 0;
+    while rows.next().unwrap().is_some() {
+        count += 1;
+    }
+    count
+}
+
+#[butane_test(async, pg, nomigrate)]
+async fn statement_cache(conn: ConnectionAsync) {
+    for _ in 0..3 {
+        assert!(!conn.has_table("foo").await.unwrap());
+    }
+    // The has_table statement, and the one counting statements.
+    assert_eq!(prepared_statement_count(&conn).await, 2);
+    assert_eq!(prepared_statement_count(&conn).await, 2);
+
+    conn.set_statement_cache_capacity(1);
+    assert!(!conn.has_table("foo").await.unwrap());
+    assert_eq!(prepared_statement_count(&conn).await, 1);
+}
+
+/// Read the integer column `a` of the only row of table `t`.
+async fn read_int(conn: &impl ConnectionMethodsAsync) -> butane_core::Result<SqlVal> {
+    let columns = [Column::new("a", SqlType::Int)];
+    let mut rows = conn.query("t", &columns, None, None, None, None).await?;
+    let row = rows.next()?.unwrap();
+    Ok(row.get(0, SqlType::Int)?.into())
+}
+
+#[butane_test(async, pg, nomigrate)]
+async fn statement_cache_rollback(mut conn: ConnectionAsync) {
+    conn.execute("CREATE TABLE t (a INTEGER); INSERT INTO t VALUES (1);")
+        .await
+        .unwrap();
+    assert_eq!(read_int(&conn).await.unwrap(), SqlVal::Int(1));
+
+    // Statements cached after the table changes within a transaction
+    // must not outlive its rollback.
+    let change = "DROP TABLE t; CREATE TABLE t (a TEXT); INSERT INTO t VALUES ('x');";
+    let tr = conn.transaction().await.unwrap();
+    tr.execute(change).await.unwrap();
+    assert!(read_int(&tr).await.is_err());
+    tr.rollback().await.unwrap();
+    assert_eq!(read_int(&conn).await.unwrap(), SqlVal::Int(1));
+
+    // Nor the implicit rollback of a dropped transaction.
+    let tr = conn.transaction().await.unwrap();
+    tr.execute(change).await.unwrap();
+    assert!(read_int(&tr).await.is_err());
+    drop(tr);
+    assert_eq!(read_int(&conn).await.unwrap(), SqlVal::Int(1));
+
+    // Nor the rollback of a transaction around a nested one that changed it.
+    let mut tr = conn.transaction().await.unwrap();
+    let nested = tr.transaction().await.unwrap();
+    nested.execute(change).await.unwrap();
+    assert!(read_int(&nested).await.is_err());
+    nested.commit().await.unwrap();
+    tr.rollback().await.unwrap();
+    assert_eq!(read_int(&conn).await.unwrap(), SqlVal::Int(1));
+}
+
 #[butane_test(nomigrate)]
 async fn debug_connection(conn: ConnectionAsync) {
     let backend_name = conn.backend_name();
@@ -164,3 +237,56 @@ fn saves_invalid_connection_spec_to_missing_path() {
     let loaded_spec = ConnectionSpec::load(path).unwrap();
     assert_eq!(spec, loaded_spec);
 }
+
+#[test]
+fn connection_profiles() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let single = ConnectionSpec::new("sqlite", "single.db");
+    single.save(dir.path()).unwrap();
+    let profiles = ConnectionProfiles::load(dir.path()).unwrap();
+    assert_eq!(profiles.get(None).unwrap(), &single);
+    assert_eq!(
+        profiles
+            .get(Some(ConnectionProfiles::DEFAULT_NAME))
+            .unwrap(),
+        &single
+    );
+
+    let dev = ConnectionSpec::new("sqlite", "dev.db");
+    let prod = ConnectionSpec::new("pg", "host=prod");
+    let mut profiles = ConnectionProfiles::default();
+    profiles.insert("dev", dev.clone());
+    profiles.insert("prod", prod.clone());
+    profiles.save(dir.path()).unwrap();
+
+    assert_eq!(ConnectionSpec::load(dir.path()).unwrap(), dev);
+    assert_eq!(
+        ConnectionSpec::load_profile(dir.path(), Some("prod")).unwrap(),
+        prod
+    );
+    assert!(matches!(
+        ConnectionSpec::load_profile(dir.path(), Some("test")),
+        Err(butane_core::Error::UnknownProfile(_))
+    ));
+}
+
+#[test]
+fn connection_env_vars() {
+    std::env::set_var("BUTANE_TEST_DB_USER", "alice");
+    std::env::set_var("BUTANE_TEST_DB_URL", "postgres://bob@localhost/db");
+    let spec = ConnectionSpec::new("pg", "host=localhost user=${BUTANE_TEST_DB_USER} dbname=db");
+    assert_eq!(
+        spec.expanded_conn_str().unwrap(),
+        "host=localhost user=alice dbname=db"
+    );
+    let spec = ConnectionSpec::new("pg", "env:BUTANE_TEST_DB_URL");
+    assert_eq!(
+        spec.expanded_conn_str().unwrap(),
+        "postgres://bob@localhost/db"
+    );
+    let spec = ConnectionSpec::new("sqlite", "${BUTANE_TEST_DB_UNSET}.db");
+    assert!(matches!(
+        spec.expanded_conn_str(),
+        Err(butane_core::Error::MissingEnvVar(name)) if name == "BUTANE_TEST_DB_UNSET"
+    ));
+}
@@ -1,17 +1,32 @@
 extern crate alloc;
 
+use std::sync::{Arc, Mutex};
+
 use butane_core::codegen::{butane_type_with_migrations, model_with_migrations};
-use butane_core::db::{BackendConnection, Connection};
-use butane_core::migrations::adb::{DeferredSqlType, TypeIdentifier, TypeKey};
-use butane_core::migrations::{MemMigrations, Migration, MigrationMut, Migrations, MigrationsMut};
-use butane_core::{SqlType, SqlVal};
-#[cfg(feature = "pg")]
-use butane_test_helper::pg_connection;
+#[cfg(feature = "async")]
+use butane_core::db::ConnectionAsync;
+use butane_core::db::{
+    Backend, BackendConnection, BackendRows, Column, Connection, ConnectionMethods, ConnectionSpec,
+};
+use butane_core::migrations::adb::{
+    self, AColumn, ATable, DeferredSqlType, Operation, TypeIdentifier, TypeKey, ADB,
+};
+use butane_core::migrations::{
+    assert_current, backfill_in_batches, drift_fix_sql, locking_operations, migrations_table,
+    render_steps, schema_drift, sqlval_from_json, sqlval_to_json, FsMigrations, MemMigrations,
+    MigrateCallbacks, Migration, MigrationHooks, MigrationMut, MigrationStep, Migrations,
+    MigrationsMut, Seed, SeedTable, TestDatabase,
+};
+use butane_core::query::{BoolExpr, Expr, Order, OrderDirection};
+use butane_core::{Error, Result, SqlType, SqlVal};
 #[cfg(feature = "sqlite")]
 use butane_test_helper::sqlite_connection;
+#[cfg(feature = "pg")]
+use butane_test_helper::{pg_connection, pg_connstr};
 use pretty_assertions::assert_eq;
 use proc_macro2::TokenStream;
 use quote::quote;
+use serde_json::json;
 use sqlparser::dialect::GenericDialect;
 use sqlparser::parser::Parser as SqlParser;
 
@@ -58,6 +73,81 @@ struct Foo {
     assert_eq!(table.pk(), Some(idcol))
 }
 
+#[cfg(feature = "sqlite")]
+#[test]
+fn assert_current_migration() {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+            baz: Option<i64>,
+        }
+    };
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![butane_core::db::get_backend("sqlite").unwrap()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    assert_current(&ms);
+
+    // The model changes, but no migration is made.
+    model_with_migrations(v2, &mut ms);
+    let ms2 = ms.clone();
+    let err = std::panic::catch_unwind(move || assert_current(&ms2)).unwrap_err();
+    let msg = err.downcast_ref::<String>().unwrap();
+    assert!(msg.contains("latest migration (init)"), "{msg}");
+    assert!(msg.contains("Add column Foo.baz (big int)"), "{msg}");
+
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+    assert_current(&ms);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn describe_changes() {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[renamed_from = "bar"]
+            title: Option<String>,
+            #[index]
+            likes: i32,
+        }
+    };
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![butane_core::db::get_backend("sqlite").unwrap()];
+    model_with_migrations(init, &mut ms);
+    assert_eq!(
+        ms.describe_changes().unwrap(),
+        ["Add table Foo with columns id (big int, primary key), bar (string, not null)"]
+    );
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    assert!(ms.describe_changes().unwrap().is_empty());
+
+    model_with_migrations(v2, &mut ms);
+    assert_eq!(
+        ms.describe_changes().unwrap(),
+        [
+            "Add column Foo.likes (int, not null)",
+            "Rename column Foo.bar to title and change from title (string, not null) to title (string)",
+            "Add index Foo_likes_idx on Foo (likes)",
+        ]
+    );
+}
+
 #[test]
 fn current_migration_pk_attribute() {
     let tokens = quote! {
@@ -285,277 +375,2995 @@ fn migration_add_and_remove_field_pg() {
     );
 }
 
+#[cfg(all(feature = "sqlite", feature = "datetime"))]
+#[test]
+fn migration_timestamp_precision_sqlite() {
+    // Timestamps are text, so the table is unchanged.
+    migration_timestamp_precision(&mut sqlite_connection(), "", "");
+}
+
+#[cfg(all(feature = "pg", feature = "datetime"))]
+#[test]
+fn migration_timestamp_precision_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_timestamp_precision(
+        &mut conn,
+        "ALTER TABLE Foo ALTER COLUMN bar SET DATA TYPE TIMESTAMP(3);",
+        "ALTER TABLE Foo ALTER COLUMN bar SET DATA TYPE TIMESTAMP;",
+    );
+}
+
 #[cfg(feature = "sqlite")]
 #[test]
-fn migration_delete_table_sqlite() {
-    migration_delete_table(
+fn migration_rename_field_sqlite() {
+    migration_rename_field(
         &mut sqlite_connection(),
-        "DROP TABLE Foo;",
-        "CREATE TABLE Foo (\"id\" INTEGER NOT NULL PRIMARY KEY,bar TEXT NOT NULL) STRICT;",
+        "ALTER TABLE Foo RENAME COLUMN bar TO baz;",
+        "ALTER TABLE Foo RENAME COLUMN baz TO bar;",
     );
 }
 
 #[cfg(feature = "pg")]
 #[test]
-fn migration_delete_table_pg() {
+fn migration_rename_field_pg() {
     let (mut conn, _data) = pg_connection();
-    migration_delete_table(
+    migration_rename_field(
         &mut conn,
-        "DROP TABLE Foo;",
-        "CREATE TABLE Foo (\"id\" BIGINT NOT NULL PRIMARY KEY,bar TEXT NOT NULL);",
+        "ALTER TABLE Foo RENAME COLUMN bar TO baz;",
+        "ALTER TABLE Foo RENAME COLUMN baz TO bar;",
     );
 }
 
-fn test_migrate(
-    conn: &mut Connection,
-    init_tokens: TokenStream,
-    v2_tokens: TokenStream,
-    expected_up_sql: &str,
-    expected_down_sql: &str,
-) {
-    let mut ms = MemMigrations::new();
-    let backend = conn.backend();
-    let backends = nonempty::nonempty![backend];
-    model_with_migrations(init_tokens, &mut ms);
-    assert!(ms.create_migration(&backends, "init", None).unwrap());
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_rename_table_sqlite() {
+    migration_rename_table(
+        &mut sqlite_connection(),
+        "ALTER TABLE Foo RENAME TO Article;\nALTER TABLE Foo_tags_Many RENAME TO Article_tags_Many;",
+        "ALTER TABLE Article RENAME TO Foo;\nALTER TABLE Article_tags_Many RENAME TO Foo_tags_Many;",
+    );
+}
 
-    model_with_migrations(v2_tokens, &mut ms);
-    assert!(ms
-        .create_migration(&backends, "v2", ms.latest().as_ref())
-        .unwrap());
+#[cfg(feature = "pg")]
+#[test]
+fn migration_rename_table_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_rename_table(
+        &mut conn,
+        "ALTER TABLE Foo RENAME TO Article;\nALTER TABLE Foo_tags_Many RENAME TO Article_tags_Many;",
+        "ALTER TABLE Article RENAME TO Foo;\nALTER TABLE Article_tags_Many RENAME TO Foo_tags_Many;",
+    );
+}
 
-    let to_apply = ms.unapplied_migrations(conn).unwrap();
-    assert_eq!(to_apply.len(), 2);
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_add_index_sqlite() {
+    migration_add_index(
+        &mut sqlite_connection(),
+        "CREATE INDEX Foo_bar_idx ON Foo (bar);",
+        "DROP INDEX Foo_bar_idx;",
+    );
+}
 
-    ms.migrate(conn).unwrap();
+#[cfg(feature = "pg")]
+#[test]
+fn migration_add_index_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_add_index(
+        &mut conn,
+        "CREATE INDEX Foo_bar_idx ON Foo (bar);",
+        "DROP INDEX Foo_bar_idx;",
+    );
+}
 
-    let to_apply = ms.unapplied_migrations(conn).unwrap();
-    assert_eq!(to_apply.len(), 0);
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_add_index_options_sqlite() {
+    migration_add_index_options(
+        &mut sqlite_connection(),
+        "CREATE UNIQUE INDEX foo_bar ON Foo (bar);",
+        "DROP INDEX foo_bar;",
+    );
+}
 
-    verify_sql(conn, &ms, expected_up_sql, expected_down_sql);
+#[cfg(feature = "pg")]
+#[test]
+fn migration_add_index_options_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_add_index_options(
+        &mut conn,
+        "CREATE UNIQUE INDEX foo_bar ON Foo USING btree (bar);",
+        "DROP INDEX foo_bar;",
+    );
+}
 
-    // Now downgrade, just to make sure we can
-    ms.unmigrate(conn).unwrap();
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_hooks_sqlite() {
+    migration_hooks(&mut sqlite_connection());
+}
 
-    let to_apply = ms.unapplied_migrations(conn).unwrap();
-    assert_eq!(to_apply.len(), 2);
+#[cfg(feature = "pg")]
+#[test]
+fn migration_hooks_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_hooks(&mut conn);
 }
 
-fn verify_sql(
-    conn: &Connection,
-    ms: &impl Migrations,
-    expected_up_sql: &str,
-    expected_down_sql: &str,
-) {
-    let dialect = GenericDialect {};
-    let expected_up_ast = SqlParser::parse_sql(&dialect, expected_up_sql).unwrap();
-    let expected_down_ast = SqlParser::parse_sql(&dialect, expected_down_sql).unwrap();
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_non_atomic_sqlite() {
+    migration_non_atomic(
+        &mut sqlite_connection(),
+        "CREATE INDEX Foo_bar_idx ON Foo (bar);",
+        "DROP INDEX Foo_bar_idx;",
+    );
+}
 
-    let backend = conn.backend();
-    let v2_migration = ms.latest().unwrap();
+#[cfg(feature = "pg")]
+#[test]
+fn migration_non_atomic_pg() {
+    let (mut conn, _data) = pg_connection();
+    // Neither statement may run inside a transaction
+    migration_non_atomic(
+        &mut conn,
+        "CREATE INDEX CONCURRENTLY Foo_bar_idx ON Foo (bar);",
+        "DROP INDEX CONCURRENTLY Foo_bar_idx;",
+    );
+}
 
-    let actual_up_sql = v2_migration.up_sql(backend.name()).unwrap().unwrap();
-    let actual_up_ast = sqlparser::parser::Parser::parse_sql(&dialect, &actual_up_sql).unwrap();
-    assert_eq!(actual_up_ast, expected_up_ast);
-    let actual_down_sql = v2_migration.down_sql(backend.name()).unwrap().unwrap();
-    let actual_down_ast = sqlparser::parser::Parser::parse_sql(&dialect, &actual_down_sql).unwrap();
-    assert_eq!(actual_down_ast, expected_down_ast);
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_concurrent_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("db.sqlite");
+    migration_concurrent(&ConnectionSpec::new("sqlite", path.to_str().unwrap()));
 }
 
-fn migration_add_field(conn: &mut Connection, up_sql: &str, down_sql: &str) {
-    let init = quote! {
-        struct Foo {
-            id: i64,
-            bar: String,
-        }
-    };
+#[cfg(feature = "pg")]
+#[test]
+fn migration_concurrent_pg() {
+    let (_conn, data) = pg_connection();
+    migration_concurrent(&ConnectionSpec::new("pg", pg_connstr(&data)));
+}
 
-    let v2 = quote! {
-        struct Foo {
-            id: i64,
-            bar: String,
-            baz: u32,
-        }
-    };
-    test_migrate(conn, init, v2, up_sql, down_sql);
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_migrate_to_sqlite() {
+    migration_migrate_to(&mut sqlite_connection());
 }
 
-fn migration_add_field_with_default(conn: &mut Connection, up_sql: &str, down_sql: &str) {
-    let init = quote! {
-        struct Foo {
-            id: i64,
-            bar: String,
-        }
-    };
+#[cfg(feature = "pg")]
+#[test]
+fn migration_migrate_to_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_migrate_to(&mut conn);
+}
 
-    let v2 = quote! {
-        struct Foo {
-            id: i64,
-            bar: String,
-            #[default=42]
-            baz: u32,
-        }
-    };
-    test_migrate(conn, init, v2, up_sql, down_sql);
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_rollback_sqlite() {
+    migration_rollback(&mut sqlite_connection());
 }
 
-fn migration_modify_field_type_change(conn: &mut Connection, up_sql: &str, down_sql: &str) {
-    let init = quote! {
-        struct Foo {
-            id: i64,
-            bar: i32,
-        }
-    };
+#[cfg(feature = "pg")]
+#[test]
+fn migration_rollback_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_rollback(&mut conn);
+}
 
-    let v2 = quote! {
-        struct Foo {
-            id: i64,
-            bar: i64,
-        }
-    };
-    test_migrate(conn, init, v2, up_sql, down_sql);
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_rollback_with_backup_sqlite() {
+    migration_rollback_with_backup(&mut sqlite_connection());
 }
 
-fn migration_modify_field_nullability_change(conn: &mut Connection, up_sql: &str, down_sql: &str) {
-    let init = quote! {
-        struct Foo {
-            id: i64,
-            bar: i32,
-        }
-    };
+#[cfg(feature = "pg")]
+#[test]
+fn migration_rollback_with_backup_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_rollback_with_backup(&mut conn);
+}
 
-    let v2 = quote! {
-        struct Foo {
-            id: i64,
-            bar: Option<i32>,
-        }
-    };
-    test_migrate(conn, init, v2, up_sql, down_sql);
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_merge_mem_sqlite() {
+    migration_merge(&mut sqlite_connection(), MemMigrations::new());
 }
 
-fn migration_modify_field_uniqueness_change(conn: &mut Connection, up_sql: &str, down_sql: &str) {
-    let init = quote! {
-        struct Foo {
-            id: i64,
-            bar: i32,
-        }
-    };
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_merge_fs_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    migration_merge(
+        &mut sqlite_connection(),
+        FsMigrations::new(dir.path().into()),
+    );
+}
 
-    let v2 = quote! {
-        struct Foo {
+#[cfg(feature = "pg")]
+#[test]
+fn migration_merge_fs_pg() {
+    let (mut conn, _data) = pg_connection();
+    let dir = tempfile::tempdir().unwrap();
+    migration_merge(&mut conn, FsMigrations::new(dir.path().into()));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_seed_mem_sqlite() {
+    migration_seed(&mut sqlite_connection(), MemMigrations::new());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_seed_fs_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    migration_seed(
+        &mut sqlite_connection(),
+        FsMigrations::new(dir.path().join("migrations")),
+    );
+    assert!(dir.path().join("seeds/test/01_foo.json").is_file());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_seed_mem_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_seed(&mut conn, MemMigrations::new());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_seed_dependencies_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    migration_seed_dependencies(
+        &mut sqlite_connection(),
+        FsMigrations::new(dir.path().join("migrations")),
+    );
+    let sql = std::fs::read_to_string(dir.path().join("seeds/test/01_sql.sql")).unwrap();
+    assert!(sql.starts_with("-- depends_on: 02_first\nUPDATE"), "{sql}");
+    let json = std::fs::read_to_string(dir.path().join("seeds/test/02_first.json")).unwrap();
+    assert!(json.contains("\"depends_on\""), "{json}");
+}
+
+#[test]
+fn sqlval_json_round_trip() {
+    let vals = [
+        (SqlVal::Null, SqlType::Text, json!(null)),
+        (SqlVal::Bool(true), SqlType::Bool, json!(true)),
+        (SqlVal::Int(-3), SqlType::Int, json!(-3)),
+        (SqlVal::BigInt(1 << 40), SqlType::BigInt, json!(1_i64 << 40)),
+        (SqlVal::Real(1.5), SqlType::Real, json!(1.5)),
+        (SqlVal::Text("a\nb".into()), SqlType::Text, json!("a\nb")),
+        (SqlVal::Blob(vec![0, 255]), SqlType::Blob, json!("00ff")),
+        (
+            SqlVal::Array(&SqlType::Text, vec![SqlVal::Text("a".into()), SqlVal::Null]),
+            SqlType::Array(&SqlType::Text),
+            json!(["a", null]),
+        ),
+        #[cfg(feature = "datetime")]
+        (
+            SqlVal::Timestamp("2024-02-03T04:05:06.5".parse().unwrap()),
+            SqlType::Timestamp,
+            json!("2024-02-03T04:05:06.500"),
+        ),
+        #[cfg(feature = "decimal")]
+        (
+            SqlVal::Decimal("12345678901234567890.10".parse().unwrap()),
+            SqlType::Numeric(None),
+            json!("12345678901234567890.10"),
+        ),
+    ];
+    for (val, ty, expected) in vals {
+        let value = sqlval_to_json(&val.as_ref()).unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(sqlval_from_json(&value, &ty), Some(val));
+    }
+    assert_eq!(sqlval_from_json(&json!("x"), &SqlType::Int), None);
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_seed_dependencies_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_seed_dependencies(&mut conn, MemMigrations::new());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_empty_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    migration_empty(
+        &mut sqlite_connection(),
+        FsMigrations::new(dir.path().into()),
+    );
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_empty_pg() {
+    let (mut conn, _data) = pg_connection();
+    let dir = tempfile::tempdir().unwrap();
+    migration_empty(&mut conn, FsMigrations::new(dir.path().into()));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_steps_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    migration_steps(
+        &mut sqlite_connection(),
+        FsMigrations::new(dir.path().into()),
+    );
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_steps_pg() {
+    let (mut conn, _data) = pg_connection();
+    let dir = tempfile::tempdir().unwrap();
+    migration_steps(&mut conn, FsMigrations::new(dir.path().into()));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_fake_sqlite() {
+    migration_fake(&mut sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_fake_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_fake(&mut conn);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_status_sqlite() {
+    migration_status(&mut sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_status_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_status(&mut conn);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_bytes_sqlite() {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+            baz: i64,
+        }
+    };
+    let mut conn = sqlite_connection();
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+
+    let bytes = ms.to_bytes().unwrap();
+    assert!(bytes.len() < serde_json::to_string_pretty(&ms).unwrap().len() / 2);
+    let loaded = MemMigrations::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        loaded.all_migrations().unwrap(),
+        ms.all_migrations().unwrap()
+    );
+    loaded.migrate(&mut conn).unwrap();
+    conn.execute("SELECT baz FROM Foo;").unwrap();
+
+    assert!(MemMigrations::from_bytes(b"{}").is_err());
+    assert!(MemMigrations::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_rebuild_table_sqlite() {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            #[unique]
+            code: String,
+            #[index]
+            tag: String,
+            name: String,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            name: String,
+            #[unique]
+            slug: String,
+        }
+    };
+    let mut conn = sqlite_connection();
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(&mut conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, code, tag, name) VALUES (1, 'c', 't', 'kept');")
+        .unwrap();
+    // A row referencing Foo must survive Foo being rebuilt.
+    conn.execute("CREATE TABLE Child (id INTEGER PRIMARY KEY, foo INTEGER REFERENCES Foo(id));")
+        .unwrap();
+    conn.execute("INSERT INTO Child (id, foo) VALUES (1, 1);")
+        .unwrap();
+
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+    let v2_migration = ms.latest().unwrap();
+    let up_sql = v2_migration.up_sql("sqlite").unwrap().unwrap();
+    assert!(!up_sql.contains("DROP COLUMN code"));
+    assert!(!up_sql.contains("ADD COLUMN"));
+    assert!(up_sql.contains("Foo__butane_tmp"));
+
+    ms.migrate(&mut conn).unwrap();
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[Column::new("name", SqlType::Text)],
+            Some(BoolExpr::Eq("slug", Expr::Val("".into()))),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let row = rows.next().unwrap().unwrap();
+    let name: SqlVal = row.get(0, SqlType::Text).unwrap().into();
+    assert_eq!(name, SqlVal::Text("kept".to_string()));
+    drop(rows);
+    assert!(conn.execute("SELECT code FROM Foo;").is_err());
+    conn.execute("INSERT INTO Foo (id, name, slug) VALUES (2, 'other', 'b');")
+        .unwrap();
+    assert!(conn
+        .execute("INSERT INTO Foo (id, name, slug) VALUES (3, 'dup', 'b');")
+        .is_err());
+
+    conn.execute("DELETE FROM Foo WHERE id = 2;").unwrap();
+    v2_migration.downgrade(&mut conn).unwrap();
+    conn.execute("SELECT code, tag FROM Foo WHERE name = 'kept';")
+        .unwrap();
+    assert!(conn.execute("SELECT slug FROM Foo;").is_err());
+    assert!(conn
+        .execute("INSERT INTO Child (id, foo) VALUES (2, 42);")
+        .is_err());
+}
+
+#[cfg(all(feature = "sqlite", feature = "uuid"))]
+#[test]
+fn migration_blob_to_uuid_sqlite() {
+    migration_blob_to_uuid(&mut sqlite_connection());
+}
+
+#[cfg(all(feature = "pg", feature = "uuid"))]
+#[test]
+fn migration_blob_to_uuid_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_blob_to_uuid(&mut conn);
+}
+
+/// A UUID stored as a blob, as before UUIDs had their own type, is
+/// converted when its field's column changes to the UUID type.
+#[cfg(feature = "uuid")]
+fn migration_blob_to_uuid(conn: &mut Connection) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            key: Vec<u8>,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            key: uuid::Uuid,
+        }
+    };
+    let key = uuid::uuid!("97f40d6c-e39b-47e5-b145-1edbd599861f");
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(conn).unwrap();
+    conn.insert_only(
+        "Foo",
+        &[
+            Column::new("id", SqlType::BigInt),
+            Column::new("key", SqlType::Blob),
+        ],
+        &[
+            SqlVal::BigInt(1).as_ref(),
+            SqlVal::Blob(key.as_bytes().to_vec()).as_ref(),
+        ],
+    )
+    .unwrap();
+
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+    ms.migrate(conn).unwrap();
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[Column::new("key", SqlType::Uuid)],
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let row = rows.next().unwrap().unwrap();
+    let val: SqlVal = row.get(0, SqlType::Uuid).unwrap().into();
+    assert_eq!(val, SqlVal::Uuid(key));
+}
+
+#[cfg(all(feature = "sqlite", feature = "json"))]
+#[test]
+fn migration_json_to_key_value_sqlite() {
+    migration_json_to_key_value(&mut sqlite_connection());
+}
+
+#[cfg(all(feature = "pg", feature = "json"))]
+#[test]
+fn migration_json_to_key_value_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_json_to_key_value(&mut conn);
+}
+
+/// A map of strings stored as JSON, as before they were stored as
+/// key-value maps, is converted when its field's column changes type.
+#[cfg(feature = "json")]
+fn migration_json_to_key_value(conn: &mut Connection) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            tags: serde_json::Value,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            tags: HashMap<String, String>,
+        }
+    };
+    let tags = json!({ "color": "red", "size": "large" });
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(conn).unwrap();
+    conn.insert_only(
+        "Foo",
+        &[
+            Column::new("id", SqlType::BigInt),
+            Column::new("tags", SqlType::Json),
+        ],
+        &[
+            SqlVal::BigInt(1).as_ref(),
+            SqlVal::Json(tags.clone()).as_ref(),
+        ],
+    )
+    .unwrap();
+
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+    let db = ms.latest().unwrap().db().unwrap();
+    let column = db.get_table("Foo").unwrap().column("tags").unwrap();
+    assert_eq!(
+        column.typeid().unwrap(),
+        TypeIdentifier::Ty(SqlType::KeyValue)
+    );
+    ms.migrate(conn).unwrap();
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[Column::new("tags", SqlType::Json)],
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let row = rows.next().unwrap().unwrap();
+    let val: SqlVal = row.get(0, SqlType::Json).unwrap().into();
+    assert_eq!(val, SqlVal::Json(tags));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_convert_using_sqlite() {
+    migration_convert_using(&mut sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_convert_using_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_convert_using(&mut conn);
+}
+
+fn migration_convert_using(conn: &mut Connection) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[convert_using = "CAST(bar AS BIGINT) * 2"]
+            bar: i64,
+        }
+    };
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, bar) VALUES (1, '21');")
+        .unwrap();
+
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+    let v2_migration = ms.latest().unwrap();
+    let backend_name = conn.backend_name();
+    let up_sql = v2_migration.up_sql(backend_name).unwrap().unwrap();
+    assert!(up_sql.contains("CAST(bar AS BIGINT) * 2"));
+    let down_sql = v2_migration.down_sql(backend_name).unwrap().unwrap();
+    assert!(!down_sql.contains("CAST(bar AS BIGINT) * 2"));
+    // The conversion is not kept in the migration's schema, so that it
+    // does not apply to later changes.
+    let db = v2_migration.db().unwrap();
+    let bar = db.get_table("Foo").unwrap().column("bar").unwrap();
+    assert_eq!(bar.convert_using(), None);
+
+    ms.migrate(conn).unwrap();
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[Column::new("bar", SqlType::BigInt)],
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let row = rows.next().unwrap().unwrap();
+    let bar: SqlVal = row.get(0, SqlType::BigInt).unwrap().into();
+    assert_eq!(bar, SqlVal::BigInt(42));
+    drop(rows);
+
+    v2_migration.downgrade(conn).unwrap();
+    conn.execute("SELECT id FROM Foo WHERE bar = '42';")
+        .unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_backfill_sqlite() {
+    migration_backfill(&mut sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_backfill_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_backfill(&mut conn);
+}
+
+fn migration_backfill(conn: &mut Connection) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+            baz: i64,
+        }
+    };
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, bar) VALUES (1, 'abc');")
+        .unwrap();
+
+    model_with_migrations(v2, &mut ms);
+    let mut to_db = ms.current().db().unwrap();
+    let mut table = to_db.get_table("Foo").unwrap().clone();
+    for column in &mut table.columns {
+        if column.name() == "baz" {
+            column.set_backfill(Some("length(bar) * 2".to_string()));
+        }
+    }
+    to_db.replace_table(table);
+    assert!(ms
+        .create_migration_to(&backends, "v2", ms.latest().as_ref(), to_db)
+        .unwrap());
+    let v2_migration = ms.latest().unwrap();
+    let up_sql = v2_migration.up_sql(conn.backend_name()).unwrap().unwrap();
+    assert!(up_sql.contains("length(bar) * 2"));
+    // The backfill is not kept in the migration's schema
+    let db = v2_migration.db().unwrap();
+    let column = db.get_table("Foo").unwrap().column("baz").unwrap();
+    assert_eq!(column.backfill(), None);
+
+    ms.migrate(conn).unwrap();
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[Column::new("baz", SqlType::BigInt)],
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let row = rows.next().unwrap().unwrap();
+    let value: SqlVal = row.get(0, SqlType::BigInt).unwrap().into();
+    assert_eq!(value, SqlVal::BigInt(6));
+    drop(rows);
+    // The column is still NOT NULL
+    assert!(conn
+        .execute("INSERT INTO Foo (id, bar) VALUES (2, 'def');")
+        .is_err());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_sqlite_options_sqlite() {
+    let (init_sql, v2_sql) = migration_sqlite_options(&mut sqlite_connection());
+    assert!(init_sql.contains("bar TEXT NOT NULL\n);"), "{init_sql}");
+    assert!(
+        v2_sql.contains("bar TEXT NOT NULL\n) STRICT, WITHOUT ROWID;"),
+        "{v2_sql}"
+    );
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_sqlite_options_pg() {
+    let (mut conn, _data) = pg_connection();
+    let (init_sql, v2_sql) = migration_sqlite_options(&mut conn);
+    assert!(!init_sql.contains("STRICT"), "{init_sql}");
+    assert_eq!(v2_sql, "");
+}
+
+/// Migrate a model which is not strict, then make it strict and without
+/// rowid, returning the up SQL of both migrations.
+fn migration_sqlite_options(conn: &mut Connection) -> (String, String) {
+    let init = quote! {
+        #[sqlite(strict = false)]
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
+        #[sqlite(without_rowid)]
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, bar) VALUES (1, 'kept');")
+        .unwrap();
+    let init_sql = ms
+        .latest()
+        .unwrap()
+        .up_sql(conn.backend_name())
+        .unwrap()
+        .unwrap();
+
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+    ms.migrate(conn).unwrap();
+    conn.execute("SELECT id FROM Foo WHERE bar = 'kept';")
+        .unwrap();
+    let v2_sql = ms
+        .latest()
+        .unwrap()
+        .up_sql(conn.backend_name())
+        .unwrap()
+        .unwrap();
+    ms.unmigrate(conn).unwrap();
+    (init_sql, v2_sql)
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_add_check_sqlite() {
+    migration_add_check(
+        &mut sqlite_connection(),
+        false,
+        &["CONSTRAINT Foo_price_check CHECK (price >= 0)"],
+    );
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_add_check_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_add_check(
+        &mut conn,
+        false,
+        &["ALTER TABLE Foo ADD CONSTRAINT Foo_price_check CHECK (price >= 0);"],
+    );
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_add_check_online_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_add_check(
+        &mut conn,
+        true,
+        &[
+            "ALTER TABLE Foo ADD CONSTRAINT Foo_price_check CHECK (price >= 0) NOT VALID;",
+            "ALTER TABLE Foo VALIDATE CONSTRAINT Foo_price_check;",
+        ],
+    );
+}
+
+fn migration_add_check(conn: &mut Connection, online: bool, expected_up_sql: &[&str]) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            price: i64,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[check = "price >= 0"]
+            price: i64,
+        }
+    };
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, price) VALUES (1, 5);")
+        .unwrap();
+
+    model_with_migrations(v2, &mut ms);
+    let from_db = ms.latest().unwrap().db().unwrap();
+    let ops = adb::diff(&from_db, &ms.current().db().unwrap());
+    assert_eq!(locking_operations(&from_db, &ops, false).len(), 1);
+    assert!(locking_operations(&from_db, &ops, true).is_empty());
+    if online {
+        assert!(ms
+            .create_online_migration(&backends, "v2", ms.latest().as_ref())
+            .unwrap());
+    } else {
+        assert!(ms
+            .create_migration(&backends, "v2", ms.latest().as_ref())
+            .unwrap());
+    }
+    let v2_migration = ms.latest().unwrap();
+    let up_sql = v2_migration.up_sql(conn.backend_name()).unwrap().unwrap();
+    for sql in expected_up_sql {
+        assert!(up_sql.contains(sql), "{sql} not in {up_sql}");
+    }
+
+    ms.migrate(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, price) VALUES (2, 0);")
+        .unwrap();
+    assert!(conn
+        .execute("INSERT INTO Foo (id, price) VALUES (3, -1);")
+        .is_err());
+
+    v2_migration.downgrade(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, price) VALUES (3, -1);")
+        .unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_schema_sql_sqlite() {
+    migration_schema_sql(&mut sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_schema_sql_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_schema_sql(&mut conn);
+}
+
+fn migration_schema_sql(conn: &mut Connection) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[index]
+            bar: String,
+            #[check = "baz > 0"]
+            baz: i64,
+        }
+    };
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+
+    // The schema is created in one go rather than by altering tables.
+    let sql = ms.schema_sql(conn.backend().as_ref()).unwrap();
+    assert!(!sql.contains("ALTER TABLE Foo ADD COLUMN"), "{sql}");
+    assert!(sql.contains("CREATE INDEX Foo_bar_idx"), "{sql}");
+    conn.execute(&sql).unwrap();
+    conn.execute("INSERT INTO Foo (id, bar, baz) VALUES (1, 'a', 1);")
+        .unwrap();
+    assert!(conn
+        .execute("INSERT INTO Foo (id, bar, baz) VALUES (2, 'b', 0);")
+        .is_err());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_compose_sqlite() {
+    migration_compose(&mut sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_compose_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_compose(&mut conn);
+}
+
+fn migration_compose(conn: &mut Connection) {
+    let backends = nonempty::nonempty![conn.backend()];
+    let mut blogs = MemMigrations::new();
+    model_with_migrations(
+        quote! {
+            struct Blog {
+                id: i64,
+                name: String,
+            }
+        },
+        &mut blogs,
+    );
+    assert!(blogs
+        .create_migration(&backends, "blog_init", None)
+        .unwrap());
+
+    // Another crate's model refers to Blog.
+    let bigint = DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::BigInt));
+    let mut post = ATable::new("Post".to_string());
+    post.add_column(AColumn::new(
+        "id",
+        bigint.clone(),
+        false,
+        true,
+        false,
+        false,
+        None,
+        None,
+    ));
+    post.add_column(AColumn::new(
+        "blog",
+        bigint,
+        false,
+        false,
+        false,
+        false,
+        None,
+        Some(adb::ARef::Literal(adb::ARefLiteral::new("Blog", "id"))),
+    ));
+    let mut posts_db = ADB::new();
+    posts_db.replace_table(post);
+    let mut posts = MemMigrations::new();
+    assert!(posts
+        .create_migration_to(&backends, "post_init", None, posts_db)
+        .unwrap());
+
+    let composed = Migrations::compose(&[posts.clone(), blogs.clone()]).unwrap();
+    let names: Vec<String> = composed
+        .all_migrations()
+        .unwrap()
+        .iter()
+        .map(|m| m.name().to_string())
+        .collect();
+    assert_eq!(names, ["blog_init", "post_init"]);
+    let db = composed.latest().unwrap().db().unwrap();
+    assert!(db.get_table("Blog").is_some());
+    assert!(db.get_table("Post").is_some());
+
+    composed.migrate(conn).unwrap();
+    conn.execute("INSERT INTO Blog (id, name) VALUES (1, 'blog');")
+        .unwrap();
+    conn.execute("INSERT INTO Post (id, blog) VALUES (1, 1);")
+        .unwrap();
+    assert!(composed.unapplied_migrations(conn).unwrap().is_empty());
+
+    let err = Migrations::compose(&[blogs.clone(), blogs]).unwrap_err();
+    match err {
+        Error::MigrationError(msg) => assert!(msg.contains("Table Blog"), "{msg}"),
+        _ => panic!("unexpected error {err:?}"),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_callbacks_sqlite() {
+    migration_callbacks(&mut sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_callbacks_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_callbacks(&mut conn);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_online_sqlite() {
+    // SQLite has no online equivalents, so the SQL is unchanged.
+    migration_online(
+        &mut sqlite_connection(),
+        &["CREATE INDEX Foo_bar_idx ON Foo (bar);"],
+    );
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_online_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_online(
+        &mut conn,
+        &[
+            "ALTER TABLE Foo ADD CONSTRAINT Foo_bar_not_null CHECK (bar IS NOT NULL) NOT VALID;",
+            "ALTER TABLE Foo VALIDATE CONSTRAINT Foo_bar_not_null;",
+            "ALTER TABLE Foo ALTER COLUMN bar SET NOT NULL;",
+            "ALTER TABLE Foo DROP CONSTRAINT Foo_bar_not_null;",
+            "CREATE INDEX CONCURRENTLY Foo_bar_idx ON Foo (bar);",
+        ],
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_squash_mem_sqlite() {
+    migration_squash(&mut sqlite_connection(), MemMigrations::new());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_squash_fs_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    migration_squash(
+        &mut sqlite_connection(),
+        FsMigrations::new(dir.path().into()),
+    );
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_squash_fs_pg() {
+    let (mut conn, _data) = pg_connection();
+    let dir = tempfile::tempdir().unwrap();
+    migration_squash(&mut conn, FsMigrations::new(dir.path().into()));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_squash_failure_mem_sqlite() {
+    migration_squash_failure(&mut sqlite_connection(), MemMigrations::new());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_squash_failure_fs_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    migration_squash_failure(
+        &mut sqlite_connection(),
+        FsMigrations::new(dir.path().into()),
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_pending_sql_sqlite() {
+    migration_pending_sql(&mut sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_pending_sql_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_pending_sql(&mut conn);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_delete_table_sqlite() {
+    migration_delete_table(
+        &mut sqlite_connection(),
+        "DROP TABLE Foo;",
+        "CREATE TABLE Foo (\"id\" INTEGER NOT NULL PRIMARY KEY,bar TEXT NOT NULL) STRICT;",
+    );
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_delete_table_pg() {
+    let (mut conn, _data) = pg_connection();
+    migration_delete_table(
+        &mut conn,
+        "DROP TABLE Foo;",
+        "CREATE TABLE Foo (\"id\" BIGINT NOT NULL PRIMARY KEY,bar TEXT NOT NULL);",
+    );
+}
+
+fn test_migrate(
+    conn: &mut Connection,
+    init_tokens: TokenStream,
+    v2_tokens: TokenStream,
+    expected_up_sql: &str,
+    expected_down_sql: &str,
+) {
+    let mut ms = MemMigrations::new();
+    let backend = conn.backend();
+    let backends = nonempty::nonempty![backend];
+    model_with_migrations(init_tokens, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+
+    model_with_migrations(v2_tokens, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+
+    let to_apply = ms.unapplied_migrations(conn).unwrap();
+    assert_eq!(to_apply.len(), 2);
+
+    ms.migrate(conn).unwrap();
+
+    let to_apply = ms.unapplied_migrations(conn).unwrap();
+    assert_eq!(to_apply.len(), 0);
+
+    verify_sql(conn, &ms, expected_up_sql, expected_down_sql);
+
+    // Now downgrade, just to make sure we can
+    ms.unmigrate(conn).unwrap();
+
+    let to_apply = ms.unapplied_migrations(conn).unwrap();
+    assert_eq!(to_apply.len(), 2);
+}
+
+fn verify_sql(
+    conn: &Connection,
+    ms: &impl Migrations,
+    expected_up_sql: &str,
+    expected_down_sql: &str,
+) {
+    let dialect = GenericDialect {};
+    let expected_up_ast = SqlParser::parse_sql(&dialect, expected_up_sql).unwrap();
+    let expected_down_ast = SqlParser::parse_sql(&dialect, expected_down_sql).unwrap();
+
+    let backend = conn.backend();
+    let v2_migration = ms.latest().unwrap();
+
+    let actual_up_sql = v2_migration.up_sql(backend.name()).unwrap().unwrap();
+    let actual_up_ast = sqlparser::parser::Parser::parse_sql(&dialect, &actual_up_sql).unwrap();
+    assert_eq!(actual_up_ast, expected_up_ast);
+    let actual_down_sql = v2_migration.down_sql(backend.name()).unwrap().unwrap();
+    let actual_down_ast = sqlparser::parser::Parser::parse_sql(&dialect, &actual_down_sql).unwrap();
+    assert_eq!(actual_down_ast, expected_down_ast);
+}
+
+fn migration_add_field(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+            baz: u32,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_add_field_with_default(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+            #[default=42]
+            baz: u32,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_modify_field_type_change(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: i32,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            bar: i64,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_modify_field_nullability_change(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: i32,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            bar: Option<i32>,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_modify_field_uniqueness_change(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: i32,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[unique]
+            bar: i32,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_modify_field_pkey_change(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            #[pk]
+            bar: i64,
+            baz: i32,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            bar: i64,
+            #[pk]
+            baz: i32
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_modify_field_default_added(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[default=42]
+            bar: String,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_modify_field_different_default(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            #[default=41]
+            bar: String,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[default=42]
+            bar: String,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+#[cfg(feature = "datetime")]
+fn migration_timestamp_precision(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: chrono::NaiveDateTime,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[timestamp(precision = 3)]
+            bar: chrono::NaiveDateTime,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_add_and_remove_field(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            baz: u32,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_rename_field(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[renamed_from = "bar"]
+            baz: String,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_rename_table(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let mut ms = MemMigrations::new();
+    let backend = conn.backend();
+    let backends = nonempty::nonempty![backend];
+    model_with_migrations(
+        quote! {
+            struct Tag {
+                id: i64,
+            }
+        },
+        &mut ms,
+    );
+    model_with_migrations(
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+                tags: Many<Tag>,
+            }
+        },
+        &mut ms,
+    );
+    model_with_migrations(
+        quote! {
+            struct Baz {
+                id: i64,
+                foo: ForeignKey<Foo>,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, bar) VALUES (1, 'kept');")
+        .unwrap();
+
+    ms.current().delete_table("Foo").unwrap();
+    ms.current().delete_table("Foo_tags_Many").unwrap();
+    model_with_migrations(
+        quote! {
+            #[renamed_from = "Foo"]
+            struct Article {
+                id: i64,
+                bar: String,
+                tags: Many<Tag>,
+            }
+        },
+        &mut ms,
+    );
+    model_with_migrations(
+        quote! {
+            struct Baz {
+                id: i64,
+                foo: ForeignKey<Article>,
+            }
+        },
+        &mut ms,
+    );
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+    ms.migrate(conn).unwrap();
+    verify_sql(conn, &ms, up_sql, down_sql);
+
+    // The row was kept and the foreign key follows the renamed table
+    conn.execute("INSERT INTO Baz (id, foo) VALUES (1, 1);")
+        .unwrap();
+    assert!(conn
+        .execute("INSERT INTO Baz (id, foo) VALUES (2, 42);")
+        .is_err());
+
+    ms.unmigrate(conn).unwrap();
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 2);
+}
+
+fn migration_add_index(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[index]
+            bar: String,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_add_index_options(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[index(name = "foo_bar", unique, method = "btree")]
+            bar: String,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
+fn migration_delete_table(conn: &mut Connection, expected_up_sql: &str, expected_down_sql: &str) {
+    let init_tokens = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+
+    let mut ms = MemMigrations::new();
+    let backend = conn.backend();
+    let backends = nonempty::nonempty![backend];
+    model_with_migrations(init_tokens, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+
+    ms.current().delete_table("Foo").unwrap();
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+
+    let to_apply = ms.unapplied_migrations(conn).unwrap();
+    assert_eq!(to_apply.len(), 2);
+
+    ms.migrate(conn).unwrap();
+
+    let to_apply = ms.unapplied_migrations(conn).unwrap();
+    assert_eq!(to_apply.len(), 0);
+
+    verify_sql(conn, &ms, expected_up_sql, expected_down_sql);
+
+    // Now downgrade, just to make sure we can
+    ms.unmigrate(conn).unwrap();
+
+    let to_apply = ms.unapplied_migrations(conn).unwrap();
+    assert_eq!(to_apply.len(), 2);
+}
+
+fn has_foo_with_baz(conn: &Connection, value: &str) -> bool {
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[Column::new("id", SqlType::BigInt)],
+            Some(BoolExpr::Eq("baz", Expr::Val(value.into()))),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    rows.next().unwrap().is_some()
+}
+
+fn migration_hooks(conn: &mut Connection) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+            baz: String,
+        }
+    };
+
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, bar) VALUES (1, 'copied');")
+        .unwrap();
+
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+
+    // A failing hook rolls back the whole migration.
+    ms.add_hooks(
+        "v2",
+        MigrationHooks::new(
+            |_| Err(Error::MigrationError("backfill failed".to_string())),
+            None,
+        ),
+    )
+    .unwrap();
+    assert!(ms.migrate(conn).is_err());
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 1);
+    assert!(conn.execute("SELECT baz FROM Foo;").is_err());
+
+    ms.add_hooks(
+        "v2",
+        MigrationHooks::new(
+            |tx| tx.execute("UPDATE Foo SET baz = bar;"),
+            Some(|tx| tx.execute("UPDATE Foo SET bar = baz || '-restored';")),
+        ),
+    )
+    .unwrap();
+    ms.migrate(conn).unwrap();
+    assert!(ms.unapplied_migrations(conn).unwrap().is_empty());
+    assert!(has_foo_with_baz(conn, "copied"));
+
+    let v2_migration = ms.latest().unwrap();
+    v2_migration.downgrade(conn).unwrap();
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 1);
+    conn.execute("SELECT id FROM Foo WHERE bar = 'copied-restored';")
+        .unwrap();
+}
+
+fn migration_squash<Ms>(conn: &mut Connection, mut ms: Ms)
+where
+    Ms: MigrationsMut,
+    Ms::M: MigrationMut,
+{
+    let versions = [
+        quote! {
+            struct Foo {
+                id: i64,
+            }
+        },
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+            }
+        },
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+                baz: i32,
+            }
+        },
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+                baz: i32,
+                qux: f64,
+            }
+        },
+    ];
+    let backends = nonempty::nonempty![conn.backend()];
+    for (i, tokens) in versions.into_iter().enumerate() {
+        model_with_migrations(tokens, &mut ms);
+        assert!(ms
+            .create_migration(&backends, &format!("v{i}"), ms.latest().as_ref())
+            .unwrap());
+        if i == 2 {
+            ms.migrate(conn).unwrap();
+        }
+    }
+
+    let squashed = ms.squash(&backends, "squashed", "v1"..="v2").unwrap();
+    assert_eq!(squashed.replaces().unwrap(), vec!["v1", "v2"]);
+    let names: Vec<String> = ms
+        .all_migrations()
+        .unwrap()
+        .iter()
+        .map(|m| m.name().to_string())
+        .collect();
+    assert_eq!(names, vec!["v0", "squashed", "v3"]);
+    let latest_db = ms.latest().unwrap().db().unwrap();
+    assert!(butane_core::migrations::adb::diff(&latest_db, &ms.current().db().unwrap()).is_empty());
+
+    // The database applied the squashed migrations, so only v3 remains.
+    assert_eq!(ms.last_applied_migration(conn).unwrap(), Some(squashed));
+    let to_apply = ms.unapplied_migrations(conn).unwrap();
+    assert_eq!(to_apply.len(), 1);
+    assert_eq!(to_apply[0].name(), "v3");
+    ms.migrate(conn).unwrap();
+    conn.execute("SELECT qux FROM Foo;").unwrap();
+
+    // Downgrading removes the record of the replaced migrations as well.
+    ms.unmigrate(conn).unwrap();
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 3);
+    ms.migrate(conn).unwrap();
+    conn.execute("SELECT qux FROM Foo;").unwrap();
+
+    assert!(ms.squash(&backends, "bad", "v3"..="v0").is_err());
+}
+
+/// Backend which cannot create migrations, to make a squash fail after
+/// the squashed migrations are checked.
+#[derive(Clone)]
+struct FailingBackend(Box<dyn Backend>);
+
+#[async_trait::async_trait]
+impl Backend for FailingBackend {
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+    fn row_id_column(&self) -> Option<&'static str> {
+        self.0.row_id_column()
+    }
+    fn create_migration_sql(&self, _current: &ADB, _ops: Vec<Operation>) -> Result<String> {
+        Err(Error::MigrationError(
+            "cannot create migrations".to_string(),
+        ))
+    }
+    fn introspected_column(&self, col: &AColumn) -> AColumn {
+        self.0.introspected_column(col)
+    }
+    fn migration_lock_sql(&self) -> Option<(&'static str, &'static str)> {
+        self.0.migration_lock_sql()
+    }
+    fn connect(&self, conn_str: &str) -> Result<Connection> {
+        self.0.connect(conn_str)
+    }
+    #[cfg(feature = "async")]
+    async fn connect_async(&self, conn_str: &str) -> Result<ConnectionAsync> {
+        self.0.connect_async(conn_str).await
+    }
+}
+
+fn migration_squash_failure<Ms>(conn: &mut Connection, mut ms: Ms)
+where
+    Ms: MigrationsMut,
+    Ms::M: MigrationMut,
+{
+    let versions = [
+        quote! {
+            struct Foo {
+                id: i64,
+            }
+        },
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+            }
+        },
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+                baz: i32,
+            }
+        },
+    ];
+    let backends = nonempty::nonempty![conn.backend()];
+    for (i, tokens) in versions.into_iter().enumerate() {
+        model_with_migrations(tokens, &mut ms);
+        assert!(ms
+            .create_migration(&backends, &format!("v{i}"), ms.latest().as_ref())
+            .unwrap());
+    }
+    let before: Vec<(String, Option<String>)> = ms
+        .all_migrations()
+        .unwrap()
+        .iter()
+        .map(|m| (m.name().to_string(), m.up_sql(conn.backend_name()).unwrap()))
+        .collect();
+
+    let failing: Box<dyn Backend> = Box::new(FailingBackend(conn.backend()));
+    let err = ms
+        .squash(&nonempty::nonempty![failing], "squashed", "v0"..="v1")
+        .unwrap_err();
+    assert!(matches!(err, Error::MigrationError(_)), "{err}");
+
+    // The migrations are as they were before the squash.
+    let after: Vec<(String, Option<String>)> = ms
+        .all_migrations()
+        .unwrap()
+        .iter()
+        .map(|m| (m.name().to_string(), m.up_sql(conn.backend_name()).unwrap()))
+        .collect();
+    assert_eq!(after, before);
+    assert!(ms.get_migration("squashed").is_none());
+    assert_eq!(ms.latest().unwrap().name(), "v2");
+    ms.migrate(conn).unwrap();
+    conn.execute("SELECT baz FROM Foo;").unwrap();
+}
+
+fn migration_non_atomic(conn: &mut Connection, create_index_sql: &str, drop_index_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[index]
+            bar: String,
+        }
+    };
+
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+
+    // Hand-edit the migration, with a semicolon which does not end a statement.
+    let mut v2 = ms.latest().unwrap();
+    assert!(v2.atomic().unwrap());
+    v2.set_atomic(false).unwrap();
+    v2.add_sql(
+        conn.backend_name(),
+        &format!("{create_index_sql}\nINSERT INTO Foo (id, bar) VALUES (1, 'a;b');"),
+        &format!("DELETE FROM Foo WHERE bar = 'a;b';\n{drop_index_sql}"),
+    )
+    .unwrap();
+    ms.add_migration(v2).unwrap();
+
+    let v2 = ms.latest().unwrap();
+    assert!(!v2.atomic().unwrap());
+    assert!(v2
+        .apply_sql(conn.backend_name())
+        .unwrap()
+        .starts_with("-- Migration v2\n-- Not run in a transaction\n"));
+
+    ms.migrate(conn).unwrap();
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 0);
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[Column::new("id", SqlType::BigInt)],
+            Some(BoolExpr::Eq("bar", Expr::Val("a;b".into()))),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    assert!(rows.next().unwrap().is_some());
+    drop(rows);
+
+    ms.unmigrate(conn).unwrap();
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 2);
+}
+
+fn migration_concurrent(spec: &ConnectionSpec) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+            baz: String,
+        }
+    };
+
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![butane_core::db::get_backend(&spec.backend_name).unwrap()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+
+    // Each migration is applied by exactly one of the connections.
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let ms = ms.clone();
+            let spec = spec.clone();
+            std::thread::spawn(move || {
+                let mut conn = butane_core::db::connect(&spec).unwrap();
+                ms.migrate(&mut conn)
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap().unwrap();
+    }
+
+    let conn = butane_core::db::connect(spec).unwrap();
+    assert_eq!(ms.unapplied_migrations(&conn).unwrap().len(), 0);
+}
+
+fn migration_migrate_to(conn: &mut Connection) {
+    let versions = [
+        quote! {
+            struct Foo {
+                id: i64,
+            }
+        },
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+            }
+        },
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+                baz: String,
+            }
+        },
+    ];
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    for (i, tokens) in versions.into_iter().enumerate() {
+        model_with_migrations(tokens, &mut ms);
+        assert!(ms
+            .create_migration(&backends, &format!("v{i}"), ms.latest().as_ref())
+            .unwrap());
+    }
+    let last_applied = |conn: &Connection| {
+        ms.last_applied_migration(conn)
+            .unwrap()
+            .map(|m| m.name().to_string())
+    };
+
+    ms.migrate_to(conn, "v1").unwrap();
+    assert_eq!(last_applied(conn).as_deref(), Some("v1"));
+    let steps = ms.migrations_to(conn, "v0").unwrap();
+    assert!(steps.to_apply.is_empty());
+    assert_eq!(steps.to_undo, vec![ms.get_migration("v1").unwrap()]);
+
+    ms.migrate_to(conn, "v2").unwrap();
+    assert_eq!(last_applied(conn).as_deref(), Some("v2"));
+    ms.migrate_to(conn, "v2").unwrap();
+    assert_eq!(last_applied(conn).as_deref(), Some("v2"));
+    ms.migrate_to(conn, "v0").unwrap();
+    assert_eq!(last_applied(conn).as_deref(), Some("v0"));
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 2);
+
+    let err = ms.migrate_to(conn, "v3").unwrap_err();
+    assert!(matches!(err, Error::MigrationError(_)), "{err:?}");
+    assert_eq!(last_applied(conn).as_deref(), Some("v0"));
+}
+
+fn migration_rollback(conn: &mut Connection) {
+    let versions = [
+        quote! {
+            struct Foo {
+                id: i64,
+            }
+        },
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+            }
+        },
+        quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+                baz: String,
+            }
+        },
+    ];
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    for (i, tokens) in versions.into_iter().enumerate() {
+        model_with_migrations(tokens, &mut ms);
+        assert!(ms
+            .create_migration(&backends, &format!("v{i}"), ms.latest().as_ref())
+            .unwrap());
+    }
+    let last_applied = |ms: &MemMigrations, conn: &Connection| {
+        ms.last_applied_migration(conn)
+            .unwrap()
+            .map(|m| m.name().to_string())
+    };
+
+    ms.migrate(conn).unwrap();
+    ms.rollback(conn, 2).unwrap();
+    assert_eq!(last_applied(&ms, conn).as_deref(), Some("v0"));
+    conn.execute("SELECT id FROM Foo").unwrap();
+
+    // Nothing is rolled back if fewer migrations are applied than requested.
+    let err = ms.rollback(conn, 2).unwrap_err();
+    assert!(matches!(err, Error::MigrationError(_)), "{err:?}");
+    assert_eq!(last_applied(&ms, conn).as_deref(), Some("v0"));
+
+    ms.migrate(conn).unwrap();
+    let backend_name = conn.backend_name();
+    let mut v2 = ms.get_migration("v2").unwrap();
+    let up_sql = v2.up_sql(backend_name).unwrap().unwrap();
+    v2.add_sql(backend_name, &up_sql, "").unwrap();
+    ms.add_migration(v2).unwrap();
+    let err = ms.rollback(conn, 2).unwrap_err();
+    match err {
+        Error::MigrationError(msg) => assert!(msg.contains("v2 has no down SQL"), "{msg}"),
+        _ => panic!("unexpected error {err:?}"),
+    }
+    assert_eq!(last_applied(&ms, conn).as_deref(), Some("v2"));
+}
+
+fn migration_rollback_with_backup(conn: &mut Connection) {
+    let versions = [
+        vec![quote! {
+            struct Foo {
+                id: i64,
+            }
+        }],
+        vec![
+            quote! {
+                struct Foo {
+                    id: i64,
+                    bar: String,
+                }
+            },
+            quote! {
+                struct Bar {
+                    id: i64,
+                    name: String,
+                }
+            },
+        ],
+        vec![quote! {
+            struct Foo {
+                id: i64,
+                bar: String,
+                baz: i64,
+            }
+        }],
+    ];
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    for (i, models) in versions.into_iter().enumerate() {
+        for tokens in models {
+            model_with_migrations(tokens, &mut ms);
+        }
+        assert!(ms
+            .create_migration(&backends, &format!("v{i}"), ms.latest().as_ref())
+            .unwrap());
+    }
+    ms.migrate(conn).unwrap();
+    conn.execute("INSERT INTO Foo (id, bar, baz) VALUES (1, 'kept', 7);")
+        .unwrap();
+    conn.execute("INSERT INTO Bar (id, name) VALUES (1, 'also kept');")
+        .unwrap();
+
+    let backups = ms.rollback_with_backup(conn, 2).unwrap();
+    assert_eq!(backups.len(), 3, "{backups:?}");
+    conn.execute("SELECT id FROM Foo").unwrap();
+    assert!(conn.execute("SELECT bar FROM Foo").is_err());
+
+    // Undoing v2 backs up baz, then undoing v1 backs up Bar and bar.
+    let backed_up = |table: &str, column: Column| -> SqlVal {
+        let mut rows = conn
+            .query(table, std::slice::from_ref(&column), None, None, None, None)
+            .unwrap();
+        let row = rows.next().unwrap().unwrap();
+        row.get(0, column.ty().clone()).unwrap().into()
+    };
+    assert!(backups[0].starts_with("Foo__backup_"));
+    assert_eq!(
+        backed_up(&backups[0], Column::new("baz", SqlType::BigInt)),
+        SqlVal::BigInt(7)
+    );
+    assert!(backups[1].starts_with("Bar__backup_"));
+    assert_eq!(
+        backed_up(&backups[1], Column::new("name", SqlType::Text)),
+        SqlVal::Text("also kept".to_string())
+    );
+    assert!(backups[2].starts_with("Foo__backup_"));
+    assert_ne!(backups[2], backups[0]);
+    assert_eq!(
+        backed_up(&backups[2], Column::new("bar", SqlType::Text)),
+        SqlVal::Text("kept".to_string())
+    );
+}
+
+fn seed_table(table: &str, rows: serde_json::Value) -> Vec<SeedTable> {
+    let rows = rows
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row.as_object().unwrap().clone())
+        .collect();
+    vec![SeedTable {
+        table: table.to_string(),
+        rows,
+    }]
+}
+
+fn foo_bars(conn: &Connection) -> Vec<(SqlVal, SqlVal)> {
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[
+                Column::new("id", SqlType::BigInt),
+                Column::new("bar", SqlType::Text),
+            ],
+            None,
+            None,
+            None,
+            Some(&[Order {
+                direction: OrderDirection::Ascending,
+                column: "id",
+            }]),
+        )
+        .unwrap();
+    let mut bars = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        bars.push((
+            row.get(0, SqlType::BigInt).unwrap().into(),
+            row.get(1, SqlType::Text).unwrap().into(),
+        ));
+    }
+    bars
+}
+
+fn migration_seed_dependencies<Ms>(conn: &mut Connection, mut ms: Ms)
+where
+    Ms: MigrationsMut,
+    Ms::M: MigrationMut,
+{
+    let init = quote! {
+        struct Foo {
             id: i64,
-            #[unique]
-            bar: i32,
+            bar: String,
         }
     };
-    test_migrate(conn, init, v2, up_sql, down_sql);
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(conn).unwrap();
+
+    // Only has an effect if applied after 02_first, which it depends on.
+    let mut sql = Seed::from_sql(
+        "test",
+        "01_sql",
+        "UPDATE Foo SET bar = 'updated' WHERE id = 1;\n",
+    );
+    sql.set_depends_on(vec!["02_first".to_string()]);
+    ms.add_seed(sql).unwrap();
+    let first = json!([{"id": 1, "bar": "one"}]);
+    ms.add_seed(Seed::new("test", "02_first", seed_table("Foo", first)))
+        .unwrap();
+    let other = json!([{"id": 2, "bar": "two"}]);
+    ms.add_seed(Seed::new("test", "03_other", seed_table("Foo", other)))
+        .unwrap();
+    let seeds = ms.seeds().unwrap();
+    assert!(seeds[0]
+        .sql()
+        .unwrap()
+        .ends_with("UPDATE Foo SET bar = 'updated' WHERE id = 1;\n"));
+    assert_eq!(seeds[0].depends_on(), ["02_first".to_string()]);
+
+    assert_eq!(ms.seed_named(conn, "test", "01_sql").unwrap(), 1);
+    assert_eq!(
+        foo_bars(conn),
+        [(SqlVal::BigInt(1), SqlVal::Text("updated".to_string()))]
+    );
+    assert_eq!(ms.seed(conn, "test").unwrap(), 2);
+    assert_eq!(
+        foo_bars(conn),
+        [
+            (SqlVal::BigInt(1), SqlVal::Text("updated".to_string())),
+            (SqlVal::BigInt(2), SqlVal::Text("two".to_string())),
+        ]
+    );
+    assert!(ms.seed_named(conn, "test", "04_missing").is_err());
+
+    let first = json!([{"id": 1, "bar": "one"}]);
+    let mut first = Seed::new("test", "02_first", seed_table("Foo", first));
+    first.set_depends_on(vec!["01_sql".to_string()]);
+    ms.add_seed(first).unwrap();
+    let err = ms.seed(conn, "test").unwrap_err().to_string();
+    assert!(err.contains("cycle"), "{err}");
 }
 
-fn migration_modify_field_pkey_change(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+fn migration_seed<Ms>(conn: &mut Connection, mut ms: Ms)
+where
+    Ms: MigrationsMut,
+    Ms::M: MigrationMut,
+{
     let init = quote! {
         struct Foo {
-            #[pk]
-            bar: i64,
-            baz: i32,
+            id: i64,
+            bar: String,
+            baz: Option<i64>,
         }
     };
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
 
-    let v2 = quote! {
+    let more = json!([{"id": 2, "bar": "two"}]);
+    ms.add_seed(Seed::new("test", "02_more", seed_table("Foo", more)))
+        .unwrap();
+    let first = json!([{"id": 1, "bar": "it's", "baz": 3}]);
+    ms.add_seed(Seed::new("test", "01_foo", seed_table("Foo", first)))
+        .unwrap();
+    let demo = json!([{"id": 3, "bar": "demo"}]);
+    ms.add_seed(Seed::new("demo", "01_demo", seed_table("Foo", demo)))
+        .unwrap();
+    let seeds: Vec<(String, String)> = ms
+        .seeds()
+        .unwrap()
+        .iter()
+        .map(|seed| (seed.env().to_string(), seed.name().to_string()))
+        .collect();
+    assert_eq!(
+        seeds,
+        [
+            ("demo".to_string(), "01_demo".to_string()),
+            ("test".to_string(), "01_foo".to_string()),
+            ("test".to_string(), "02_more".to_string()),
+        ]
+    );
+
+    // Seeding needs the tables created by the migrations.
+    assert!(ms.seed(conn, "test").is_err());
+    ms.migrate(conn).unwrap();
+    assert_eq!(ms.seed(conn, "test").unwrap(), 2);
+    assert_eq!(ms.seed(conn, "test").unwrap(), 2);
+    assert_eq!(
+        foo_bars(conn),
+        [
+            (SqlVal::BigInt(1), SqlVal::Text("it's".to_string())),
+            (SqlVal::BigInt(2), SqlVal::Text("two".to_string())),
+        ]
+    );
+
+    // Changed rows are updated.
+    let first = json!([{"id": 1, "bar": "one", "baz": null}]);
+    ms.add_seed(Seed::new("test", "01_foo", seed_table("Foo", first)))
+        .unwrap();
+    ms.seed(conn, "test").unwrap();
+    assert_eq!(foo_bars(conn)[0].1, SqlVal::Text("one".to_string()));
+
+    let bad = json!([{"id": 4, "bar": 5}]);
+    ms.add_seed(Seed::new("test", "03_bad", seed_table("Foo", bad)))
+        .unwrap();
+    assert!(ms.seed(conn, "test").is_err());
+    let bad = json!([{"bar": "no id"}]);
+    ms.add_seed(Seed::new("test", "03_bad", seed_table("Foo", bad)))
+        .unwrap();
+    assert!(ms.seed(conn, "test").is_err());
+}
+
+fn migration_merge<Ms>(conn: &mut Connection, mut ms: Ms)
+where
+    Ms: MigrationsMut,
+    Ms::M: MigrationMut,
+{
+    let backends = nonempty::nonempty![conn.backend()];
+    let foo_v0 = quote! {
         struct Foo {
-            bar: i64,
-            #[pk]
-            baz: i32
+            id: i64,
         }
     };
-    test_migrate(conn, init, v2, up_sql, down_sql);
+    let bar_v0 = quote! {
+        struct Bar {
+            id: i64,
+        }
+    };
+    model_with_migrations(foo_v0.clone(), &mut ms);
+    model_with_migrations(bar_v0.clone(), &mut ms);
+    assert!(ms.create_migration(&backends, "v0", None).unwrap());
+    let v0 = ms.get_migration("v0").unwrap();
+
+    // Two branches from v0, changing different tables.
+    let foo_a = quote! {
+        struct Foo {
+            id: i64,
+            a: String,
+        }
+    };
+    model_with_migrations(foo_a, &mut ms);
+    assert!(ms.create_migration(&backends, "a", Some(&v0)).unwrap());
+    model_with_migrations(foo_v0.clone(), &mut ms);
+    let bar_b = quote! {
+        struct Bar {
+            id: i64,
+            b: String,
+        }
+    };
+    model_with_migrations(bar_b, &mut ms);
+    assert!(ms.create_migration(&backends, "b", Some(&v0)).unwrap());
+    assert_eq!(ms.latest().unwrap().name(), "a");
+    let mut heads: Vec<String> = ms
+        .heads()
+        .unwrap()
+        .iter()
+        .map(|m| m.name().to_string())
+        .collect();
+    heads.sort();
+    assert_eq!(heads, vec!["a", "b"]);
+    let problems = ms.chain_problems().unwrap();
+    assert_eq!(problems.len(), 1, "{problems:?}");
+    assert!(problems[0].contains("a, b are all heads"), "{problems:?}");
+
+    // A database which applied the other branch is detected.
+    ms.migrate_to(conn, "v0").unwrap();
+    let b = ms.get_migration("b").unwrap();
+    b.apply(conn).unwrap();
+    let err = ms.migrate(conn).unwrap_err();
+    match err {
+        Error::MigrationError(msg) => assert!(msg.contains("merge-migrations"), "{msg}"),
+        _ => panic!("unexpected error {err:?}"),
+    }
+
+    let merge = ms.merge_migrations(&backends, "merge", &[b]).unwrap();
+    assert_eq!(ms.latest().unwrap(), merge);
+    let heads = ms.heads().unwrap();
+    assert_eq!(heads.len(), 1);
+    assert_eq!(heads[0].name(), "merge");
+    assert_eq!(ms.chain_problems().unwrap(), Vec::<String>::new());
+    let names: Vec<String> = ms
+        .all_migrations()
+        .unwrap()
+        .iter()
+        .map(|m| m.name().to_string())
+        .collect();
+    assert_eq!(names, vec!["v0", "a", "b", "merge"]);
+    let db = merge.db().unwrap();
+    assert!(db.get_table("Foo").unwrap().column("a").is_some());
+    assert!(db.get_table("Bar").unwrap().column("b").is_some());
+
+    ms.migrate(conn).unwrap();
+    assert_eq!(ms.last_applied_migration(conn).unwrap(), Some(merge));
+    conn.execute("SELECT a FROM Foo").unwrap();
+    conn.execute("SELECT b FROM Bar").unwrap();
+
+    // The merged chain can be undone and applied from scratch.
+    ms.unmigrate(conn).unwrap();
+    assert_eq!(ms.last_applied_migration(conn).unwrap(), None);
+    ms.migrate(conn).unwrap();
+    conn.execute("SELECT a, id FROM Foo").unwrap();
+    conn.execute("SELECT b, id FROM Bar").unwrap();
+
+    // A branch changing a table also changed in the chain cannot be merged.
+    model_with_migrations(foo_v0, &mut ms);
+    model_with_migrations(bar_v0, &mut ms);
+    let foo_c = quote! {
+        struct Foo {
+            id: i64,
+            c: String,
+        }
+    };
+    model_with_migrations(foo_c, &mut ms);
+    assert!(ms.create_migration(&backends, "c", Some(&v0)).unwrap());
+    let c = ms.get_migration("c").unwrap();
+    let err = ms.merge_migrations(&backends, "merge2", &[c]).unwrap_err();
+    match err {
+        Error::MigrationError(msg) => assert!(msg.contains("table Foo"), "{msg}"),
+        _ => panic!("unexpected error {err:?}"),
+    }
 }
 
-fn migration_modify_field_default_added(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+#[test]
+fn migration_chain_problems() {
+    let mut ms = MemMigrations::new();
+    let mut m = ms.new_migration("orphan");
+    m.set_migration_from(Some("gone".to_string())).unwrap();
+    ms.add_migration(m).unwrap();
+    assert_eq!(
+        ms.chain_problems().unwrap(),
+        vec!["Migration orphan is based on migration gone, which does not exist"]
+    );
+}
+
+fn migration_empty<Ms>(conn: &mut Connection, mut ms: Ms)
+where
+    Ms: MigrationsMut,
+    Ms::M: MigrationMut,
+{
+    let backends = nonempty::nonempty![conn.backend()];
+    let backend_name = conn.backend_name();
+
+    // An empty first migration still creates the butane_migrations table.
+    let mut first_ms = MemMigrations::new();
+    first_ms
+        .create_empty_migration(&backends, "first", None)
+        .unwrap();
+    first_ms.migrate(conn).unwrap();
+    assert_eq!(
+        first_ms
+            .last_applied_migration(conn)
+            .unwrap()
+            .unwrap()
+            .name(),
+        "first"
+    );
+    first_ms.unmigrate(conn).unwrap();
+
     let init = quote! {
+        struct Foo {
+            id: i64,
+        }
+    };
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    let init = ms.latest().unwrap();
+
+    let mut hand_written = ms
+        .create_empty_migration(&backends, "hand_written", Some(&init))
+        .unwrap();
+    assert!(
+        butane_core::migrations::adb::diff(&init.db().unwrap(), &hand_written.db().unwrap())
+            .is_empty()
+    );
+    assert_eq!(hand_written.up_sql(backend_name).unwrap().unwrap(), "");
+    assert_eq!(hand_written.down_sql(backend_name).unwrap().unwrap(), "");
+    hand_written
+        .add_sql(
+            backend_name,
+            "CREATE TABLE extra (x INTEGER);",
+            "DROP TABLE extra;",
+        )
+        .unwrap();
+    // Store the updated migration, in case it is not backed by storage.
+    ms.add_migration(hand_written).unwrap();
+    let err = ms
+        .create_empty_migration(&backends, "hand_written", Some(&init))
+        .unwrap_err();
+    assert!(matches!(err, Error::MigrationError(_)), "{err:?}");
+
+    // Later migrations are generated as usual.
+    let v2 = quote! {
         struct Foo {
             id: i64,
             bar: String,
         }
     };
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+    let names: Vec<String> = ms
+        .all_migrations()
+        .unwrap()
+        .iter()
+        .map(|m| m.name().to_string())
+        .collect();
+    assert_eq!(names, vec!["init", "hand_written", "v2"]);
+    assert_eq!(
+        ms.get_migration("v2")
+            .unwrap()
+            .up_sql(backend_name)
+            .unwrap()
+            .unwrap()
+            .matches("CREATE TABLE")
+            .count(),
+        0
+    );
+
+    ms.migrate(conn).unwrap();
+    conn.execute("SELECT x FROM extra").unwrap();
+    conn.execute("SELECT bar FROM Foo").unwrap();
+    ms.rollback(conn, 2).unwrap();
+    assert!(conn.execute("SELECT x FROM extra").is_err());
+}
+
+fn migration_steps<Ms>(conn: &mut Connection, mut ms: Ms)
+where
+    Ms: MigrationsMut,
+    Ms::M: MigrationMut,
+{
+    let backends = nonempty::nonempty![conn.backend()];
+    let backend_name = conn.backend_name();
+    let text = || DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Text));
+    let mut table = ATable::new("Foo".to_string());
+    table.add_column(AColumn::new(
+        "id",
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::BigInt)),
+        false,
+        true,
+        false,
+        false,
+        None,
+        None,
+    ));
+    let sql_step = |backend: &str| MigrationStep::Sql {
+        backends: vec![backend.to_string()],
+        up: format!("CREATE TABLE only_{backend} (x INTEGER);"),
+        down: format!("DROP TABLE only_{backend};"),
+    };
+    let steps = vec![
+        MigrationStep::Operation(Box::new(Operation::AddTable(table))),
+        sql_step("sqlite"),
+        sql_step("pg"),
+        MigrationStep::Sql {
+            backends: Vec::new(),
+            up: "INSERT INTO Foo (id) VALUES (1);".to_string(),
+            down: "DELETE FROM Foo;".to_string(),
+        },
+        MigrationStep::Operation(Box::new(Operation::AddColumn(
+            "Foo".to_string(),
+            AColumn::new("bar", text(), true, false, false, false, None, None),
+        ))),
+    ];
+    let m = ms
+        .create_migration_from_steps(&backends, "steps", None, steps.clone())
+        .unwrap();
+    assert_eq!(m.steps().unwrap(), steps);
+    let db = m.db().unwrap();
+    assert!(db.get_table("Foo").unwrap().column("bar").is_some());
+    let up_sql = m.up_sql(backend_name).unwrap().unwrap();
+    assert!(up_sql.contains(&format!("only_{backend_name}")), "{up_sql}");
+    let other = if backend_name == "pg" { "sqlite" } else { "pg" };
+    assert!(!up_sql.contains(&format!("only_{other}")), "{up_sql}");
+
+    // SQL can be rendered again from the stored steps, as for a backend added later.
+    let (up_sql, down_sql) =
+        render_steps(conn.backend().as_ref(), &ADB::new(), &m.steps().unwrap()).unwrap();
+    // The first migration also creates the butane_migrations table.
+    let stored_up_sql = m.up_sql(backend_name).unwrap().unwrap();
+    assert!(stored_up_sql.starts_with(&up_sql), "{stored_up_sql}");
+    assert_eq!(
+        down_sql,
+        m.down_sql(backend_name).unwrap().unwrap().trim_end()
+    );
+
+    ms.migrate(conn).unwrap();
+    conn.execute(format!("SELECT x FROM only_{backend_name}"))
+        .unwrap();
+    assert!(conn.execute(format!("SELECT x FROM only_{other}")).is_err());
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[Column::new("bar", SqlType::Text)],
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    assert!(rows.next().unwrap().is_some());
+    assert!(rows.next().unwrap().is_none());
+    drop(rows);
+
+    ms.rollback(conn, 1).unwrap();
+    assert!(conn.execute("SELECT id FROM Foo").is_err());
+    assert!(conn
+        .execute(format!("SELECT x FROM only_{backend_name}"))
+        .is_err());
+}
 
+fn migration_pending_sql(conn: &mut Connection) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+        }
+    };
     let v2 = quote! {
         struct Foo {
             id: i64,
-            #[default=42]
             bar: String,
         }
     };
-    test_migrate(conn, init, v2, up_sql, down_sql);
+
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+
+    let backend_name = conn.backend_name();
+    let init_sql = ms
+        .get_migration("init")
+        .unwrap()
+        .up_sql(backend_name)
+        .unwrap()
+        .unwrap();
+    let v2_sql = ms.latest().unwrap().up_sql(backend_name).unwrap().unwrap();
+    let sql = ms.pending_sql(conn).unwrap();
+    assert_eq!(
+        sql,
+        format!("-- Migration init\n{init_sql}\n-- Migration v2\n{v2_sql}\n")
+    );
+    // Nothing has been applied, but the previewed SQL can be.
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 2);
+    conn.execute(&sql).unwrap();
+
+    ms.get_migration("init")
+        .unwrap()
+        .mark_applied(conn)
+        .unwrap();
+    let sql = ms.pending_sql(conn).unwrap();
+    assert!(sql.starts_with("-- Migration v2\n"));
+    assert!(!sql.contains("init"));
+
+    ms.latest().unwrap().mark_applied(conn).unwrap();
+    assert_eq!(ms.pending_sql(conn).unwrap(), "");
 }
 
-fn migration_modify_field_different_default(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+fn migration_online(conn: &mut Connection, expected_up_sql: &[&str]) {
     let init = quote! {
         struct Foo {
             id: i64,
-            #[default=41]
+            bar: Option<String>,
+        }
+    };
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            #[index]
             bar: String,
         }
     };
 
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    ms.migrate(conn).unwrap();
+    for id in 1..=5 {
+        conn.execute(format!("INSERT INTO Foo (id) VALUES ({id});"))
+            .unwrap();
+    }
+
+    // Fill in the column before making it NOT NULL.
+    let id = Column::new("id", SqlType::BigInt);
+    let updated =
+        backfill_in_batches(conn, "Foo", &id, "bar = 'filled'", BoolExpr::True, 2).unwrap();
+    assert_eq!(updated, 5);
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[id],
+            Some(BoolExpr::Eq("bar", Expr::Val("filled".into()))),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    let mut count = 0;
+    while rows.next().unwrap().is_some() {
+        count += 1;
+    }
+    drop(rows);
+    assert_eq!(count, 5);
+
+    model_with_migrations(v2, &mut ms);
+    let from_db = ms.latest().unwrap().db().unwrap();
+    let ops = adb::diff(&from_db, &ms.current().db().unwrap());
+    assert_eq!(locking_operations(&from_db, &ops, false).len(), 2);
+    assert!(locking_operations(&from_db, &ops, true).is_empty());
+
+    assert!(ms
+        .create_online_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+    let v2 = ms.latest().unwrap();
+    assert!(!v2.atomic().unwrap());
+    let up_sql = v2.up_sql(conn.backend_name()).unwrap().unwrap();
+    for sql in expected_up_sql {
+        assert!(up_sql.contains(sql), "{sql} not in {up_sql}");
+    }
+
+    ms.migrate(conn).unwrap();
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 0);
+    assert!(conn.execute("INSERT INTO Foo (id) VALUES (6);").is_err());
+    ms.unmigrate(conn).unwrap();
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 2);
+}
+
+fn migration_fake(conn: &mut Connection) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
     let v2 = quote! {
         struct Foo {
             id: i64,
-            #[default=42]
             bar: String,
+            baz: i64,
         }
     };
-    test_migrate(conn, init, v2, up_sql, down_sql);
+
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+
+    // A database which already has the schema of the first migration,
+    // but not butane's record of it.
+    let init_sql = ms
+        .get_migration("init")
+        .unwrap()
+        .up_sql(conn.backend_name());
+    conn.execute(init_sql.unwrap().unwrap()).unwrap();
+    conn.execute("DROP TABLE butane_migrations;").unwrap();
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 2);
+
+    assert!(ms.fake_migrate_to(conn, "unknown").is_err());
+    let faked = ms.fake_migrate_to(conn, "init").unwrap();
+    assert_eq!(faked.len(), 1);
+    assert_eq!(faked[0].name(), "init");
+    assert!(ms.fake_migrate_to(conn, "init").unwrap().is_empty());
+    assert_eq!(
+        ms.last_applied_migration(conn).unwrap().unwrap().name(),
+        "init"
+    );
+
+    // The remaining migration is applied for real.
+    ms.migrate(conn).unwrap();
+    conn.execute("SELECT baz FROM Foo;").unwrap();
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 0);
 }
 
-fn migration_add_and_remove_field(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+fn migration_status(conn: &mut Connection) {
     let init = quote! {
         struct Foo {
             id: i64,
             bar: String,
         }
     };
-
     let v2 = quote! {
         struct Foo {
             id: i64,
-            baz: u32,
+            bar: String,
+            baz: i64,
         }
     };
-    test_migrate(conn, init, v2, up_sql, down_sql);
+
+    let mut ms = MemMigrations::new();
+    let backend_name = conn.backend_name();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+    model_with_migrations(v2, &mut ms);
+    assert!(ms
+        .create_migration(&backends, "v2", ms.latest().as_ref())
+        .unwrap());
+    // An irreversible migration.
+    let mut v2 = ms.latest().unwrap();
+    let v2_up_sql = v2.up_sql(backend_name).unwrap().unwrap();
+    v2.add_sql(backend_name, &v2_up_sql, "").unwrap();
+    ms.add_migration(v2).unwrap();
+
+    // The first migration was applied by a version of butane which
+    // recorded only the names of applied migrations.
+    conn.execute("CREATE TABLE butane_migrations (name TEXT NOT NULL PRIMARY KEY);")
+        .unwrap();
+    let init_sql = ms.get_migration("init").unwrap().up_sql(backend_name);
+    conn.execute(init_sql.unwrap().unwrap()).unwrap();
+    conn.execute("INSERT INTO butane_migrations (name) VALUES ('init');")
+        .unwrap();
+
+    let status = ms.status(conn).unwrap();
+    assert_eq!(status.len(), 2);
+    assert_eq!(status[0].name, "init");
+    assert!(status[0].applied);
+    assert_eq!(status[0].applied_at, None);
+    assert_eq!(status[0].checksum_matches, None);
+    assert!(status[0].has_down);
+    assert_eq!(status[1].name, "v2");
+    assert!(!status[1].applied);
+    assert_eq!(status[1].checksum_matches, None);
+    assert!(!status[1].has_down);
+
+    let before = std::time::SystemTime::now() - std::time::Duration::from_secs(1);
+    ms.migrate(conn).unwrap();
+    let status = ms.status(conn).unwrap();
+    assert!(status[1].applied);
+    assert!(status[1].applied_at.unwrap() >= before);
+    assert_eq!(status[1].checksum_matches, Some(true));
+
+    // Editing an applied migration is detected.
+    let mut v2 = ms.latest().unwrap();
+    v2.add_sql(backend_name, &format!("{v2_up_sql}\n-- edited"), "")
+        .unwrap();
+    ms.add_migration(v2).unwrap();
+    let status = ms.status(conn).unwrap();
+    assert_eq!(status[1].checksum_matches, Some(false));
+    assert_eq!(status[0].checksum_matches, None);
 }
 
-fn migration_delete_table(conn: &mut Connection, expected_up_sql: &str, expected_down_sql: &str) {
-    let init_tokens = quote! {
+fn migration_callbacks(conn: &mut Connection) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let v2 = quote! {
         struct Foo {
             id: i64,
             bar: String,
+            baz: i64,
         }
     };
 
     let mut ms = MemMigrations::new();
-    let backend = conn.backend();
-    let backends = nonempty::nonempty![backend];
-    model_with_migrations(init_tokens, &mut ms);
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
     assert!(ms.create_migration(&backends, "init", None).unwrap());
-
-    ms.current().delete_table("Foo").unwrap();
+    model_with_migrations(v2, &mut ms);
     assert!(ms
         .create_migration(&backends, "v2", ms.latest().as_ref())
         .unwrap());
 
-    let to_apply = ms.unapplied_migrations(conn).unwrap();
-    assert_eq!(to_apply.len(), 2);
+    // A failing callback stops migrating.
+    let failing = MigrateCallbacks::new().after_each(|name, _conn| match name {
+        "init" => Ok(()),
+        _ => Err(Error::MigrationError(format!("{name} failed"))),
+    });
+    assert!(ms.migrate_with_callbacks(conn, &failing).is_err());
+    assert_eq!(ms.unapplied_migrations(conn).unwrap().len(), 0);
+    ms.unmigrate(conn).unwrap();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let (before, after, all) = (events.clone(), events.clone(), events.clone());
+    let callbacks = MigrateCallbacks::new()
+        .before_each(move |name, conn| {
+            let has_foo = conn.execute("SELECT id FROM Foo;").is_ok();
+            before
+                .lock()
+                .unwrap()
+                .push(format!("before {name} {has_foo}"));
+            Ok(())
+        })
+        .after_each(move |name, conn| {
+            let has_foo = conn.execute("SELECT id FROM Foo;").is_ok();
+            after
+                .lock()
+                .unwrap()
+                .push(format!("after {name} {has_foo}"));
+            Ok(())
+        })
+        .after_all(move |names, _conn| {
+            all.lock().unwrap().push(format!("all {}", names.join(",")));
+            Ok(())
+        });
+    ms.migrate_with_callbacks(conn, &callbacks).unwrap();
+    ms.migrate_with_callbacks(conn, &callbacks).unwrap();
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            "before init false",
+            "after init true",
+            "before v2 true",
+            "after v2 true",
+            "all init,v2",
+            "all ",
+        ]
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn fix_drift_sqlite() {
+    fix_drift(&mut sqlite_connection());
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn fix_drift_pg() {
+    let (mut conn, _data) = pg_connection();
+    fix_drift(&mut conn);
+}
 
+fn fix_drift(conn: &mut Connection) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+            baz: Option<i64>,
+        }
+    };
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![conn.backend()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
     ms.migrate(conn).unwrap();
+    conn.execute("ALTER TABLE Foo DROP COLUMN baz;").unwrap();
+    conn.execute("CREATE TABLE extra (id INTEGER PRIMARY KEY);")
+        .unwrap();
+
+    let expected = ms.latest().unwrap().db().unwrap();
+    let actual = |conn: &Connection| {
+        let mut db = conn.introspect().unwrap();
+        db.remove_table(&migrations_table().name);
+        db
+    };
+    let backend = conn.backend();
+    let drift = schema_drift(&expected, &actual(conn), backend.as_ref());
+    assert_eq!(drift.len(), 2, "{drift:?}");
 
-    let to_apply = ms.unapplied_migrations(conn).unwrap();
-    assert_eq!(to_apply.len(), 0);
+    let sql = drift_fix_sql(&expected, &actual(conn), backend.as_ref()).unwrap();
+    conn.execute(&sql).unwrap();
+    assert_eq!(schema_drift(&expected, &actual(conn), backend.as_ref()), []);
+}
 
-    verify_sql(conn, &ms, expected_up_sql, expected_down_sql);
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_database_sqlite() {
+    test_database(&ConnectionSpec::new("sqlite", ":memory:"));
+}
 
-    // Now downgrade, just to make sure we can
-    ms.unmigrate(conn).unwrap();
+#[cfg(feature = "pg")]
+#[test]
+fn test_database_pg() {
+    let (_conn, data) = pg_connection();
+    test_database(&ConnectionSpec::new("pg", pg_connstr(&data)));
+}
 
-    let to_apply = ms.unapplied_migrations(conn).unwrap();
-    assert_eq!(to_apply.len(), 2);
+fn test_database(base: &ConnectionSpec) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: String,
+        }
+    };
+    let mut ms = MemMigrations::new();
+    let backends = nonempty::nonempty![butane_core::db::get_backend(&base.backend_name).unwrap()];
+    model_with_migrations(init, &mut ms);
+    assert!(ms.create_migration(&backends, "init", None).unwrap());
+
+    let first = TestDatabase::create(base, &ms).unwrap();
+    let second = TestDatabase::create(base, &ms).unwrap();
+    assert_ne!(first.name(), second.name());
+    let conn = butane_core::db::connect(first.spec()).unwrap();
+    assert_eq!(ms.unapplied_migrations(&conn).unwrap(), []);
+    conn.execute("INSERT INTO Foo (id, bar) VALUES (1, 'one');")
+        .unwrap();
+    drop(conn);
+    let conn = butane_core::db::connect(second.spec()).unwrap();
+    let mut rows = conn
+        .query(
+            "Foo",
+            &[Column::new("id", SqlType::BigInt)],
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    assert!(rows.next().unwrap().is_none());
+    drop(rows);
+    drop(conn);
+
+    // Dropping removes the database unless it is kept. Connecting would
+    // create a missing SQLite database, so its file is looked for instead.
+    let exists = |spec: &ConnectionSpec| match spec.backend_name.as_str() {
+        "sqlite" => std::path::Path::new(&spec.conn_str).exists(),
+        _ => butane_core::db::connect(spec).is_ok(),
+    };
+    let first_spec = first.spec().clone();
+    drop(first);
+    assert!(!exists(&first_spec));
+    let second_name = second.name().to_string();
+    let second_spec = second.keep();
+    assert!(exists(&second_spec));
+    TestDatabase::drop_database(base, &second_name).unwrap();
+    assert!(!exists(&second_spec));
 }
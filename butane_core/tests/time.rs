@@ -0,0 +1,34 @@
+use butane_core::time::offset_datetime_to_chrono;
+use butane_core::{Error, FromSql, ToSql};
+use time::{OffsetDateTime, UtcOffset};
+
+#[test]
+fn offset_of_a_day_or_more() {
+    let dt = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    for hours in [25, -25] {
+        let offset = UtcOffset::from_hms(hours, 0, 0).unwrap();
+        let dt = dt.to_offset(offset);
+        assert!(matches!(
+            offset_datetime_to_chrono(dt),
+            Err(Error::OutOfRange)
+        ));
+
+        // Converting to SQL keeps the instant, in UTC.
+        let sql_val = dt.to_sql();
+        let back = OffsetDateTime::from_sql_ref(sql_val.as_ref()).unwrap();
+        assert_eq!(back, dt);
+        assert_eq!(back.offset(), UtcOffset::UTC);
+    }
+}
+
+#[test]
+fn offset_of_less_than_a_day() {
+    let dt = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let offset = UtcOffset::from_hms(-3, -30, 0).unwrap();
+    let dt = dt.to_offset(offset);
+    let chrono_dt = offset_datetime_to_chrono(dt).unwrap();
+    assert_eq!(chrono_dt.offset().local_minus_utc(), -(3 * 3600 + 30 * 60));
+    assert_eq!(chrono_dt.timestamp(), dt.unix_timestamp());
+    let back = OffsetDateTime::from_sql_ref(dt.to_sql().as_ref()).unwrap();
+    assert_eq!(back.offset(), offset);
+}
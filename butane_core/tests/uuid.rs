@@ -1,14 +1,22 @@
 use butane_core::{Error::CannotConvertSqlVal, FromSql, SqlType, ToSql};
 
 #[test]
-fn sqlval_blob_serde() {
+fn sqlval_uuid_serde() {
     let input = uuid::uuid!("97f40d6c-e39b-47e5-b145-1edbd599861f");
     let sql_val = input.to_sql();
     let s = serde_json::to_string(&sql_val).unwrap();
-    assert_eq!(
-        s,
-        "{\"Blob\":[151,244,13,108,227,155,71,229,177,69,30,219,213,153,134,31]}"
-    );
+    assert_eq!(s, "{\"Uuid\":\"97f40d6c-e39b-47e5-b145-1edbd599861f\"}");
+    let back: butane_core::SqlVal = serde_json::from_str(&s).unwrap();
+    assert_eq!(back, sql_val);
+}
+
+#[test]
+fn sqlval_blob_from_sql() {
+    // As UUIDs were stored before they had their own type.
+    let input = uuid::uuid!("97f40d6c-e39b-47e5-b145-1edbd599861f");
+    let sql_val = butane_core::SqlVal::Blob(input.as_bytes().to_vec());
+    let rv = uuid::Uuid::from_sql_ref(sql_val.as_ref()).unwrap();
+    assert_eq!(rv, input);
 }
 
 #[test]
@@ -23,7 +31,7 @@ fn sqlval_other_causes_error() {
     let sql_val = butane_core::SqlVal::Null;
     let sql_val_ref = sql_val.as_ref();
     let rv = uuid::Uuid::from_sql_ref(sql_val_ref).unwrap_err();
-    assert_matches::assert_matches!(rv, CannotConvertSqlVal(SqlType::Blob, _));
+    assert_matches::assert_matches!(rv, CannotConvertSqlVal(SqlType::Uuid, _));
 }
 
 #[test]
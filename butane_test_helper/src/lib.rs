@@ -243,6 +243,8 @@ pub fn create_tmp_server() -> PgServerState {
     let mut proc = Command::new("postgres")
         .arg("-c")
         .arg("logging_collector=false")
+        .arg("-c")
+        .arg("max_prepared_transactions=10")
         .arg("-D")
         .arg(&dir)
         .arg("-k")
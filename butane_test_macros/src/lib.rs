@@ -31,6 +31,7 @@ pub fn butane_test(args: TokenStream, input: TokenStream) -> TokenStream {
         use butane_core::DataResult;
         use butane_core::db::BackendConnection;
         use butane_core::fkey::ForeignKeyOpsSync;
+        use butane_core::largeblob::LargeBlobOpsSync;
         use butane_core::many::ManyOpsSync;
         use butane_core::query::QueryOpsSync;
         use butane_core::DataObjectOpsSync;
@@ -47,6 +48,7 @@ pub fn butane_test(args: TokenStream, input: TokenStream) -> TokenStream {
         use butane_core::DataResult;
         use butane_core::db::BackendConnectionAsync;
         use butane_core::fkey::ForeignKeyOpsAsync;
+        use butane_core::largeblob::LargeBlobOpsAsync;
         use butane_core::many::ManyOpsAsync;
         use butane_core::query::QueryOpsAsync;
         use butane_core::DataObjectOpsAsync;
@@ -69,6 +71,7 @@ pub fn butane_test(args: TokenStream, input: TokenStream) -> TokenStream {
                     find_async(sync="find"),
                     setup_blog(sync="setup_blog_sync"),
                     create_tag(sync="create_tag_sync"),
+                    FactoryOpsAsync(sync="FactoryOpsSync"),
                 )
             )]
             #[cfg(test)]
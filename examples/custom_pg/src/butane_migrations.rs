@@ -4,19 +4,5 @@
 
 /// Load the butane migrations embedded in Rust.
 pub fn get_migrations() -> Result<MemMigrations, butane::Error> {
-    let json = r#"{
-  "migrations": {},
-  "current": {
-    "name": "current",
-    "db": {
-      "tables": {},
-      "extra_types": {}
-    },
-    "from": null,
-    "up": {},
-    "down": {}
-  },
-  "latest": null
-}"#;
-    MemMigrations::from_json(json)
+    butane::embed_migrations!()
 }
@@ -0,0 +1,37 @@
+use butane::db::Connection;
+use butane::prelude::*;
+
+use getting_started::models::{Blog, Post};
+use getting_started::{create_blog, create_post, existing_blog};
+
+#[butane::test]
+fn migrated(conn: Connection) {
+    assert!(existing_blog(&conn).is_none());
+    let blog = create_blog(&conn, "Cats");
+    let post = create_post(&conn, &blog, "The Tiger".into(), "Stripes".into());
+
+    let post = Post::get(&conn, post.id).unwrap();
+    assert_eq!(post.blog.load(&conn).unwrap().name, "Cats");
+    assert_eq!(Blog::query().load(&conn).unwrap().len(), 1);
+}
+
+#[butane::test]
+fn isolated(conn: Connection) {
+    // Each test has a database of its own.
+    assert!(existing_blog(&conn).is_none());
+    create_blog(&conn, "Dogs");
+}
+
+#[butane::test(migrations = ".butane/migrations")]
+fn returns_result(conn: Connection) -> Result<(), butane::Error> {
+    let mut blog = Blog::new("Birds");
+    blog.save(&conn)?;
+    Ok(())
+}
+
+#[test]
+fn schema_snapshot() {
+    // A change to the models fails this test until the snapshot is updated.
+    let snapshot = butane::test_utils::current_schema_snapshot().unwrap();
+    assert_eq!(snapshot, include_str!("schema.txt"));
+}
@@ -0,0 +1,15 @@
+use butane::db::ConnectionAsync;
+use butane::prelude_async::*;
+
+use getting_started_async::models::Post;
+use getting_started_async::{create_blog, create_post, existing_blog};
+
+#[butane::test]
+async fn migrated(conn: ConnectionAsync) {
+    assert!(existing_blog(&conn).await.is_none());
+    let blog = create_blog(&conn, "Cats").await;
+    let post = create_post(&conn, &blog, "The Tiger".into(), "Stripes".into()).await;
+
+    let post = Post::get(&conn, post.id).await.unwrap();
+    assert_eq!(post.blog.load(&conn).await.unwrap().name, "Cats");
+}
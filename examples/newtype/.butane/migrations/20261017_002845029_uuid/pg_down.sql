@@ -0,0 +1,5 @@
+ALTER TABLE Post DROP CONSTRAINT Post_blog_fkey;
+ALTER TABLE Blog ALTER COLUMN "id" SET DATA TYPE BYTEA USING decode(replace("id"::text, '-', ''), 'hex');
+ALTER TABLE Post ALTER COLUMN blog SET DATA TYPE BYTEA USING decode(replace(blog::text, '-', ''), 'hex');
+ALTER TABLE Post ALTER COLUMN "id" SET DATA TYPE BYTEA USING decode(replace("id"::text, '-', ''), 'hex');
+ALTER TABLE Post ADD FOREIGN KEY (blog) REFERENCES Blog("id");
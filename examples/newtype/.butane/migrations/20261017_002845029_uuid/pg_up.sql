@@ -0,0 +1,5 @@
+ALTER TABLE Post DROP CONSTRAINT Post_blog_fkey;
+ALTER TABLE Blog ALTER COLUMN "id" SET DATA TYPE UUID USING encode("id", 'hex')::uuid;
+ALTER TABLE Post ALTER COLUMN blog SET DATA TYPE UUID USING encode(blog, 'hex')::uuid;
+ALTER TABLE Post ALTER COLUMN "id" SET DATA TYPE UUID USING encode("id", 'hex')::uuid;
+ALTER TABLE Post ADD FOREIGN KEY (blog) REFERENCES Blog("id");
@@ -0,0 +1,41 @@
+PRAGMA defer_foreign_keys = ON;
+CREATE TABLE Blog__butane_tmp AS SELECT * FROM Blog;
+DROP TABLE Blog;
+CREATE TABLE Blog (
+"id" BLOB NOT NULL PRIMARY KEY,
+"name" TEXT NOT NULL
+) STRICT;
+INSERT INTO Blog ("id", "name") SELECT "id", "name" FROM Blog__butane_tmp;
+DROP TABLE Blog__butane_tmp;
+PRAGMA defer_foreign_keys = ON;
+CREATE TABLE Post__butane_tmp AS SELECT * FROM Post;
+DROP TABLE Post;
+CREATE TABLE Post (
+"id" BLOB NOT NULL PRIMARY KEY,
+title TEXT NOT NULL,
+body TEXT NOT NULL,
+published INTEGER NOT NULL,
+tags TEXT NOT NULL,
+blog BLOB NOT NULL,
+byline TEXT,
+likes INTEGER NOT NULL,
+FOREIGN KEY (blog) REFERENCES Blog("id")
+) STRICT;
+INSERT INTO Post ("id", title, body, published, tags, blog, byline, likes) SELECT "id", title, body, published, tags, blog, byline, likes FROM Post__butane_tmp;
+DROP TABLE Post__butane_tmp;
+PRAGMA defer_foreign_keys = ON;
+CREATE TABLE Post__butane_tmp AS SELECT * FROM Post;
+DROP TABLE Post;
+CREATE TABLE Post (
+"id" BLOB NOT NULL PRIMARY KEY,
+title TEXT NOT NULL,
+body TEXT NOT NULL,
+published INTEGER NOT NULL,
+tags TEXT NOT NULL,
+blog BLOB NOT NULL,
+byline TEXT,
+likes INTEGER NOT NULL,
+FOREIGN KEY (blog) REFERENCES Blog("id")
+) STRICT;
+INSERT INTO Post ("id", title, body, published, tags, blog, byline, likes) SELECT "id", title, body, published, tags, blog, byline, likes FROM Post__butane_tmp;
+DROP TABLE Post__butane_tmp;